@@ -14,27 +14,46 @@ limitations under the License.
 //! Add a package to the dependencies for your project.
 
 use crate::{
-    core::model::lock_file::{DependencyID, DependencyLock, LockFile},
+    core::model::lock_file::{DependencyMeta, LockFile},
+    core::utils::dedup::DedupReport,
+    core::utils::extensions::{apply_extensions, record_extensions},
+    core::utils::fallbacks::apply_native_fallbacks,
+    core::utils::git::{fetch_git_package, install_git_package},
+    core::utils::insight::{confirm_install_scripts, new_install_scripts},
+    core::utils::managed::{shim_targets, ManagedPaths},
+    core::utils::npm::{is_dist_tag, parse_versions, resolve_peer_dependencies},
+    core::utils::output,
+    core::utils::overrides::{apply_overrides, load_overrides, record_overrides},
+    core::utils::platform,
+    core::utils::signing::{check_lockfile, sign_install},
+    core::utils::verify::file_hashes,
     core::utils::voltapi::VoltPackage,
-    core::utils::{constants::PROGRESS_CHARS, install_extract_package, print_elapsed},
-    core::utils::{fetch_dep_tree, package::PackageJson},
+    core::utils::workspace::{
+        link_workspace_package, workspace_packages, workspace_root, WorkspacePackage,
+    },
+    core::utils::zero_install,
+    core::utils::{create_global_shims, optional_package_names, satisfies},
+    core::utils::{fetch_dep_tree, lock_entry, lock_id, locked_version, package::PackageJson},
+    core::utils::{install_packages, print_elapsed, progress::InstallProgress},
     core::{command::Command, VERSION},
     App,
 };
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::Utc;
 use colored::Colorize;
-use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
-use indicatif::{ProgressBar, ProgressStyle};
 use miette::Result;
+use tracing::{info, warn};
 
 #[derive(Clone, Debug)]
 pub struct Package {
     pub name: String,
     pub version: Option<String>,
+    /// Name the package is installed under, from `alias@npm:name@range`.
+    pub alias: Option<String>,
 }
 
 /// Struct implementation for the `Add` command.
@@ -55,7 +74,25 @@ impl Command for Add {
             {} {} Output the version number.
             {} {} Output verbose messages on internal operations.
             {} {} Adds package as a dev dependency
-            {} {} Disable progress bar."#,
+            {} {} Install globally and link its executables.
+            {} {} Install missing peer dependencies.
+            {} Only install versions published before the given date.
+            {} Record a hash of every installed file in the lockfile.
+            {} Reuse locked versions that satisfy the requested range.
+            {} Save workspace packages as `workspace:` ranges (true|false).
+            {} {} Add to the workspace packages matching the filter.
+            {} {} Install packages that introduce install scripts without asking.
+            {} Don't run the install scripts of dependencies or of the project.
+            {} Fail when node doesn't satisfy the `engines` of a package.
+            {} Replace files in node_modules that volt didn't create.
+            {} Use less memory at the cost of speed, for small containers.
+  {} Downloads and extractions run at once, a multiple of the CPU count by default.
+            {} Resolve from the metadata cache and install from the store only.
+            {} Only ask the registry for packages missing from the cache.
+            {} Record why the packages were added, e.g. a ticket url.
+            {} Registry to install from instead of the configured one.
+            {} Log every package on a line instead of drawing progress bars.
+            {} Print the resolved packages as a JSON document instead of text."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
             "add".bright_purple(),
@@ -67,8 +104,29 @@ impl Command for Add {
             "(-v)".yellow(),
             "--dev".blue(),
             "(-D)".yellow(),
+            "--global".blue(),
+            "(-g)".yellow(),
+            "--auto-install-peers".blue(),
+            "(-P)".yellow(),
+            "--before".blue(),
+            "--file-integrity".blue(),
+            "--prefer-dedupe".blue(),
+            "--save-workspace-protocol".blue(),
+            "--filter".blue(),
+            "(-F)".yellow(),
+            "--yes".blue(),
+            "(-y)".yellow(),
+            "--ignore-scripts".blue(),
+            "--engine-strict".blue(),
+            "--force-overwrite".blue(),
+            "--low-memory".blue(),
+            "--concurrency <n>".blue(),
+            "--offline".blue(),
+            "--prefer-offline".blue(),
+            "--track".blue(),
+            "--registry <url>".blue(),
             "--no-progress".blue(),
-            "(-np)".yellow()
+            "--json".blue()
         )
     }
 
@@ -86,134 +144,460 @@ impl Command for Add {
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        // Get input packages
-        let packages = app.get_packages()?;
+        app.within_deadline(add(app.clone())).await
+    }
+}
+
+/// Add the packages passed in, to every workspace package selected with `--filter` if any.
+async fn add(app: Arc<App>) -> Result<()> {
+    // with `--filter` the packages are added to every selected workspace package
+    match app.filtered_packages()? {
+        Some(members) => {
+            for member in members.iter() {
+                info!("adding to {}", member.name.bright_cyan());
+
+                add_packages(Arc::new(app.in_directory(&member.path)?)).await?;
+            }
+
+            Ok(())
+        }
+        None => add_packages(app).await,
+    }
+}
+
+/// Add the packages passed in to the project in `app.current_dir`.
+async fn add_packages(app: Arc<App>) -> Result<()> {
+    // Get input packages
+    let mut packages = app.get_packages()?;
 
-        // Load the existing package.json file
-        let (mut package_file, package_file_path) = PackageJson::open("package.json")?;
+    let global = app.has_flag("global");
 
-        // Construct a path to the local and global lockfile.
-        let lockfile_path = &app.lock_file_path;
+    let before = app.before()?;
 
-        let global_lockfile = &app.home_dir.join(".global.lock");
+    if let Some(before) = &before {
+        info!(
+            "resolving versions published before {}",
+            before.to_rfc3339().bright_yellow()
+        );
+    }
 
-        // Load local and global lockfiles.
-        let mut lock_file =
-            LockFile::load(lockfile_path).unwrap_or_else(|_| LockFile::new(lockfile_path));
+    // Global installs go to the global directory and never touch the project.
+    let app = if global { Arc::new(app.global()) } else { app };
 
-        let mut global_lock_file =
-            LockFile::load(global_lockfile).unwrap_or_else(|_| LockFile::new(global_lockfile));
+    // Construct a path to the local and global lockfile.
+    let lockfile_path = &app.lock_file_path;
 
-        // Create progress bar for resolving dependencies.
+    let global_lockfile = &app.home_dir.join(".global.lock");
 
-        let progress_bar = ProgressBar::new(packages.len() as u64);
+    // a tampered lockfile must not decide what gets downloaded
+    if !global {
+        check_lockfile(&app)?;
+    }
 
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .progress_chars(PROGRESS_CHARS)
-                .template(&format!(
-                    "{} [{{bar:40.magenta/blue}}] {{msg:.blue}}",
-                    "Resolving Dependencies".bright_blue()
-                )),
+    // Load local and global lockfiles.
+    let mut lock_file = LockFile::load_or_new(lockfile_path)?;
+
+    let mut global_lock_file = LockFile::load_or_new(global_lockfile)?;
+
+    // Packages that live in the workspace are linked instead of fetched.
+    let workspace = match workspace_root(&app.current_dir) {
+        Some(root) if !global => workspace_packages(&root)?,
+        _ => vec![],
+    };
+
+    let mut linked: Vec<(Package, WorkspacePackage)> = vec![];
+
+    packages.retain(|package| {
+        let member = workspace.iter().find(|member| {
+            package.alias.is_none()
+                && member.name == package.name
+                && package
+                    .version
+                    .as_deref()
+                    .is_none_or(|range| satisfies(&member.version, range))
+        });
+
+        match member {
+            Some(member) => {
+                linked.push((package.clone(), member.clone()));
+                false
+            }
+            None => true,
+        }
+    });
+
+    // What the user asked for, saved to package.json as-is.
+    let requested = packages.clone();
+
+    // Git dependencies are checked out up front, their own dependencies come from the registry.
+    let mut git_packages = vec![];
+
+    for spec in app.get_git_packages() {
+        let package = fetch_git_package(&app, &spec)?;
+
+        println!(
+            "{}: fetched {}@{} from {}#{}",
+            "success".bright_green(),
+            package.name.bright_cyan(),
+            package.version,
+            spec.url,
+            &package.commit[..package.commit.len().min(10)]
         );
 
-        // Fetch pre-flattened dependency trees from the registry
-        let (responses, elapsed) = fetch_dep_tree(&packages, &progress_bar).await?;
+        let mut ranges = package.dependencies.iter().collect::<Vec<_>>();
+        ranges.sort();
+
+        for (name, range) in ranges {
+            if packages.iter().any(|p| &p.name == name) {
+                continue;
+            }
+
+            match parse_versions(&vec![format!("{}@{}", name, range)]) {
+                Ok(parsed) => packages.extend(parsed),
+                Err(_) => warn!(
+                    "skipping {}@{}, only registry dependencies of git packages are installed",
+                    name.bright_cyan(),
+                    range
+                ),
+            }
+        }
 
-        let mut dependencies: HashMap<String, VoltPackage> = HashMap::new();
+        git_packages.push(package);
+    }
+
+    // Keep versions that are already locked when they satisfy the requested range.
+    if app.config.prefer_dedupe || app.has_flag("prefer-dedupe") {
+        for package in packages.iter_mut() {
+            if let Some(version) = locked_version(&lock_file, package) {
+                info!(
+                    "reusing locked {}@{}",
+                    package.name.bright_cyan(),
+                    version.bright_yellow()
+                );
+
+                package.version = Some(version);
+            }
+        }
+    }
 
-        for res in responses.iter() {
-            let current_version = res.versions.get(&res.version).unwrap();
-            dependencies.extend(current_version.to_owned());
+    // Overrides belong to the project, global installs don't have one.
+    let overrides = if global {
+        vec![]
+    } else {
+        load_overrides(&app.current_dir)?
+    };
+
+    // Show the progress of resolving, every package on a line of its own.
+    let progress = InstallProgress::new(&app, "Resolving Dependencies", packages.len());
+
+    // Fetch pre-flattened dependency trees from the registry
+    let (mut dependencies, elapsed) = if packages.is_empty() {
+        (HashMap::new(), 0.0)
+    } else {
+        fetch_dep_tree(
+            &packages,
+            &app.registries,
+            before.as_ref(),
+            app.concurrency(),
+            &progress,
+        )
+        .await?
+    };
+
+    // trees from the CDN carry the metadata as published, extensions fix it up
+    apply_extensions(&app, &mut dependencies, before.as_ref()).await?;
+
+    apply_overrides(
+        &mut dependencies,
+        &overrides,
+        &app.registries,
+        before.as_ref(),
+    )
+    .await?;
+
+    // Warn about (or install) peer dependencies the tree doesn't satisfy.
+    let auto_install_peers = app.config.auto_install_peers || app.has_flag("auto-install-peers");
+
+    resolve_peer_dependencies(&app, &mut dependencies, auto_install_peers, before.as_ref()).await?;
+
+    // The resolved versions of the packages that were asked for.
+    let requested_packages = packages
+        .iter()
+        .filter_map(|p| {
+            dependencies
+                .values()
+                .find(|d| d.name == p.name && d.alias.is_none())
+                .map(|d| VoltPackage {
+                    alias: p.alias.clone(),
+                    ..d.clone()
+                })
+        })
+        .collect::<Vec<VoltPackage>>();
+
+    // Aliased packages go into node_modules under their alias, and under their real
+    // name only when something else needs them there too.
+    for package in requested_packages.iter() {
+        let alias = match &package.alias {
+            Some(alias) => alias,
+            None => continue,
+        };
+
+        let needed = packages
+            .iter()
+            .any(|p| p.name == package.name && p.alias.is_none())
+            || dependencies.values().any(|d| {
+                d.dependencies
+                    .as_ref()
+                    .is_some_and(|dependencies| dependencies.contains(&package.name))
+            });
+
+        if !needed {
+            dependencies.retain(|_, d| d.name != package.name || d.alias.is_some());
         }
 
-        progress_bar.finish_with_message("[OK]".bright_green().to_string());
+        dependencies.insert(
+            format!("{}@npm:{}@{}", alias, package.name, package.version),
+            package.clone(),
+        );
+    }
+
+    // Native packages without a build for this platform make way for their pure JS build.
+    let fallbacks = apply_native_fallbacks(&app, &mut dependencies, before.as_ref()).await?;
 
+    progress.finish(&"[OK]".bright_green().to_string());
+
+    if !packages.is_empty() {
         print_elapsed(dependencies.len(), elapsed);
+    }
+
+    // Packages only pulled in through `optionalDependencies` are allowed to fail.
+    let optional_packages = optional_package_names(&dependencies, &packages);
+
+    // Builds for other platforms are recorded in the lockfile, but not installed.
+    platform::resolve_platforms(&app, &mut dependencies, &optional_packages).await;
+
+    // Scripts showing up in the tree are a common way in for malicious code.
+    let introduced = new_install_scripts(&lock_file, &dependencies.values().collect::<Vec<_>>());
+
+    confirm_install_scripts(&app, &introduced)?;
 
-        let mut dependencies: Vec<_> = dependencies
+    let mut dependencies: Vec<_> = dependencies
+        .values()
+        .inspect(|object| {
+            let optional = optional_packages.contains(&object.name);
+
+            lock_file
+                .dependencies
+                .insert(lock_id(object), lock_entry(object, optional));
+
+            global_lock_file
+                .dependencies
+                .insert(lock_id(object), lock_entry(object, optional));
+        })
+        .collect();
+
+    dependencies.dedup();
+
+    output::resolved(dependencies.iter().copied());
+
+    // shims are checked up front too, nothing should be installed when they'd clobber
+    if global {
+        let shims = requested_packages
             .iter()
-            .map(|(_name, object)| {
-                let mut lock_dependencies: Vec<String> = vec![];
-
-                if let Some(peer_deps) = &object.peer_dependencies {
-                    for dep in peer_deps {
-                        if !crate::core::utils::check_peer_dependency(&dep) {
-                            progress_bar.println(format!(
-                                "{}{} {} has unmet peer dependency {}",
-                                " warn ".black().bright_yellow(),
-                                ":",
-                                object.name.bright_cyan(),
-                                &dep.bright_yellow()
-                            ));
-                        }
-                    }
-                }
+            .map(|package| fallbacks.get(&package.name).unwrap_or(package))
+            .flat_map(|package| {
+                shim_targets(&app, package)
+                    .into_iter()
+                    .map(move |path| (path, package))
+            })
+            .collect::<Vec<_>>();
 
-                if let Some(dependencies) = &object.dependencies {
-                    for dep in dependencies {
-                        lock_dependencies.push(dep.to_string());
-                    }
-                }
+        ManagedPaths::load(&app).check(&app, &shims)?;
+    }
 
-                let object_instance = object.clone();
+    let direct = packages
+        .iter()
+        .map(|package| {
+            (
+                package.alias.as_ref().unwrap_or(&package.name).clone(),
+                package.version.clone().unwrap_or_default(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
 
-                lock_file.dependencies.insert(
-                    DependencyID(object_instance.name, object_instance.version),
-                    DependencyLock {
-                        name: object.name.clone(),
-                        version: object.version.clone(),
-                        tarball: object.tarball.clone(),
-                        integrity: object.integrity.clone(),
-                        dependencies: lock_dependencies.clone(),
-                    },
-                );
+    install_packages(&app, &dependencies, &optional_packages, &direct, &[]).await?;
 
-                let second_instance = object.clone();
+    for package in git_packages.iter() {
+        install_git_package(&app, package)?;
 
-                global_lock_file.dependencies.insert(
-                    DependencyID(second_instance.name, second_instance.version.to_owned()),
-                    DependencyLock {
-                        name: object.name.clone(),
-                        version: object.version.clone(),
-                        tarball: object.tarball.clone(),
-                        integrity: object.integrity.clone(),
-                        dependencies: lock_dependencies,
-                    },
+        let lock = package.lock();
+        let id = package.lock_id();
+
+        lock_file.dependencies.insert(id.clone(), lock.clone());
+        global_lock_file.dependencies.insert(id, lock);
+    }
+
+    // Record a hash of every installed file so `volt check --deep` can spot tampering.
+    if app.config.file_integrity || app.has_flag("file-integrity") {
+        let installed = dependencies
+            .iter()
+            .map(|package| (package.install_name().to_string(), lock_id(package)))
+            .chain(
+                git_packages
+                    .iter()
+                    .map(|package| (package.name.clone(), package.lock_id())),
+            );
+
+        for (name, id) in installed {
+            let directory = app.node_modules_dir.join(&name);
+
+            if !directory.exists() {
+                continue;
+            }
+
+            let files = file_hashes(&directory)?;
+
+            if let Some(lock) = lock_file.dependencies.get_mut(&id) {
+                lock.files = files.clone();
+            }
+
+            if let Some(lock) = global_lock_file.dependencies.get_mut(&id) {
+                lock.files = files;
+            }
+        }
+    }
+
+    // Show what the store saved compared to copying every file.
+    DedupReport::collect(&app.node_modules_dir, &dependencies).print();
+
+    if global {
+        let mut managed = ManagedPaths::load(&app);
+
+        for package in requested_packages.iter() {
+            let package = fallbacks.get(&package.name).unwrap_or(package);
+
+            let linked = create_global_shims(&app, package)?;
+
+            for shim in shim_targets(&app, package) {
+                managed.insert(&shim);
+            }
+
+            for bin in linked {
+                println!(
+                    "{}: linked {} from {}@{}",
+                    "success".bright_green(),
+                    bin.bright_cyan(),
+                    package.name,
+                    package.version
                 );
+            }
+        }
 
-                object
-            })
-            .collect();
+        managed.save().await;
 
-        let progress_bar = ProgressBar::new(dependencies.len() as u64);
+        global_lock_file.save_merged().await?;
 
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .progress_chars(PROGRESS_CHARS)
-                .template(&format!(
-                    "{} [{{bar:40.magenta/blue}}] {{msg:.blue}}",
-                    "Installing Packages".bright_blue()
-                )),
-        );
+        let bin_dir = app.global_bin_dir();
 
-        dependencies.dedup();
+        let on_path = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).any(|p| p == bin_dir))
+            .unwrap_or(false);
 
-        dependencies
-            .into_iter()
-            .map(|v| install_extract_package(&app, &v))
-            .collect::<FuturesUnordered<_>>()
-            .inspect(|_| progress_bar.inc(1))
-            .try_collect::<()>()
-            .await
-            .unwrap();
+        if !on_path {
+            warn!(
+                "add {} to your PATH to use globally installed executables",
+                bin_dir.display().to_string().bright_cyan()
+            );
+        }
+    }
+
+    if !global {
+        for (_, member) in linked.iter() {
+            link_workspace_package(&app.node_modules_dir, member)?;
+
+            println!(
+                "{}: linked {}@{} from the workspace",
+                "success".bright_green(),
+                member.name.bright_cyan(),
+                member.version
+            );
+        }
+
+        let save_workspace_protocol = match app.args.value_of("save-workspace-protocol") {
+            Some(value) => value == "true",
+            None => app.config.save_workspace_protocol.unwrap_or(true),
+        };
+
+        let mut saved = vec![];
+
+        for package in requested.iter() {
+            let resolved = requested_packages
+                .iter()
+                .find(|p| p.name == package.name && p.alias == package.alias);
+
+            if let Some(resolved) = resolved {
+                // dist-tags move, so a tag is saved as the version it pointed to
+                let spec = match package.version.as_deref() {
+                    Some(tag) if is_dist_tag(tag) => resolved.version.clone(),
+                    Some(range) => range.to_string(),
+                    None if app.config.save_exact.unwrap_or(false) => resolved.version.clone(),
+                    None => format!("^{}", resolved.version),
+                };
+
+                match &package.alias {
+                    Some(alias) => {
+                        saved.push((alias.clone(), format!("npm:{}@{}", package.name, spec)))
+                    }
+                    None => saved.push((package.name.clone(), spec)),
+                }
+            }
+        }
+
+        for package in git_packages.iter() {
+            saved.push((package.name.clone(), package.spec.spec.clone()));
+        }
+
+        for (package, member) in linked.iter() {
+            let range = package.version.clone().unwrap_or_else(|| String::from("^"));
 
-        progress_bar.finish();
+            let spec = if save_workspace_protocol {
+                format!("workspace:{}", range)
+            } else if range == "^" {
+                format!("^{}", member.version)
+            } else {
+                range
+            };
 
-        for package in packages {
-            package_file.add_dependency(package);
+            saved.push((package.name.clone(), spec));
         }
 
-        Ok(())
+        PackageJson::save_dependencies(&app.current_dir.join("package.json"), &saved)?;
+
+        output::set("added", saved.iter().cloned().collect::<BTreeMap<_, _>>());
+
+        record_overrides(&mut lock_file, &overrides);
+        record_extensions(&mut lock_file, &app.registries.extensions);
+
+        if let Some(note) = app.args.value_of("track") {
+            let added = Utc::now().format("%Y-%m-%d").to_string();
+
+            for (name, _) in saved.iter() {
+                lock_file.meta.insert(
+                    name.clone(),
+                    DependencyMeta {
+                        note: note.to_string(),
+                        added: added.clone(),
+                    },
+                );
+            }
+        }
+
+        lock_file.save()?;
+
+        sign_install(&app, &lock_file, true)?;
+
+        zero_install::check_size(&app);
     }
+
+    Ok(())
 }
@@ -11,15 +11,41 @@
     limitations under the License.
 */
 
-//! Handle an unknown command (can be listed in scripts).
+//! Audit the packages in the lockfile.
+//!
+//! `volt audit` reports the registry advisories affecting the locked packages. It fails on
+//! the ones of at least `level` severity under `[audit]` in volt.toml, except advisories
+//! listed under `[[audit.ignore]]`, so CI only fails on what the team hasn't triaged yet.
+//! An ignore can expire, after which it's reported and the advisory fails the audit again.
 
+use crate::core::model::config::{AdvisorySeverity, IgnoredAdvisory};
+use crate::core::model::http_manager::{
+    get_advisories, get_full_packument, get_packument, get_registry_keys, Advisory, Registries,
+};
+use crate::core::model::lock_file::{DependencyLock, LockFile};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::output;
+use crate::core::utils::package::Packument;
+use crate::core::utils::signatures::{verify_signatures, SignatureStatus};
+use crate::core::utils::{integrity_to_hex, satisfies};
+use crate::core::VERSION;
 use crate::App;
 use crate::Command;
 use async_trait::async_trait;
 
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use futures::{stream, StreamExt};
 use miette::Result;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+/// Packuments fetched at the same time while auditing.
+const CONCURRENT_REQUESTS: usize = 16;
 
 pub struct Audit {}
 
@@ -109,74 +135,487 @@ pub struct AuditMetadata {
 //     packages
 // }
 
+/// Whether the lockfile entry was installed from the registry, rather than git or a workspace.
+fn from_registry(lock: &DependencyLock) -> bool {
+    lock.tarball.starts_with("http://") || lock.tarball.starts_with("https://")
+}
+
+/// Whether the integrity recorded in the lockfile is the one the registry serves.
+///
+/// Packages resolved through the Volt CDN lock a hex digest instead of an SRI string.
+fn same_integrity(locked: &str, integrity: &str, shasum: &str) -> bool {
+    locked == integrity
+        || locked == shasum
+        || integrity_to_hex(integrity).is_ok_and(|hex| hex == locked)
+}
+
+/// Fetch the documents of `names`, full ones when `full` is set (they carry publish times).
+async fn fetch_packuments(
+    registries: &Registries,
+    names: Vec<String>,
+    full: bool,
+) -> Result<HashMap<String, Packument>> {
+    let results = stream::iter(names.into_iter().map(|name| async move {
+        let packument = if full {
+            get_full_packument(registries, &name).await
+        } else {
+            get_packument(registries, &name).await
+        };
+
+        packument.map(|packument| (name, packument))
+    }))
+    .buffer_unordered(CONCURRENT_REQUESTS)
+    .collect::<Vec<_>>()
+    .await;
+
+    let mut packuments = HashMap::new();
+
+    for result in results {
+        let (name, packument) = result?;
+        packuments.insert(name, packument);
+    }
+
+    Ok(packuments)
+}
+
+/// Verify the registry signature of every package in the lockfile.
+async fn audit_signatures(app: &App) -> Result<()> {
+    let lock_file = LockFile::load(&app.lock_file_path)?;
+
+    let mut locks = lock_file
+        .dependencies
+        .values()
+        .filter(|lock| from_registry(lock))
+        .collect::<Vec<_>>();
+
+    locks.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    let skipped = lock_file.dependencies.len() - locks.len();
+
+    let keys = get_registry_keys(&app.registry()).await?;
+
+    let mut names = locks
+        .iter()
+        .map(|lock| lock.name.clone())
+        .collect::<Vec<_>>();
+    names.dedup();
+
+    let packuments = fetch_packuments(&app.registries, names, false).await?;
+
+    // keys with an expiry need the publish time, which only the full document has
+    let expiring = keys
+        .iter()
+        .filter(|key| key.expires.is_some())
+        .map(|key| key.keyid.as_str())
+        .collect::<Vec<_>>();
+
+    let mut timed = locks
+        .iter()
+        .filter(|lock| {
+            packuments
+                .get(&lock.name)
+                .and_then(|packument| packument.versions.get(&lock.version))
+                .is_some_and(|version| {
+                    version
+                        .dist
+                        .signatures
+                        .iter()
+                        .any(|signature| expiring.contains(&signature.keyid.as_str()))
+                })
+        })
+        .map(|lock| lock.name.clone())
+        .collect::<Vec<_>>();
+
+    timed.dedup();
+
+    let full_packuments = fetch_packuments(&app.registries, timed, true).await?;
+
+    let mut verified = 0;
+    let mut missing = vec![];
+    let mut invalid = vec![];
+
+    for lock in locks.iter() {
+        let id = format!("{}@{}", lock.name, lock.version);
+
+        let version = match packuments
+            .get(&lock.name)
+            .and_then(|packument| packument.versions.get(&lock.version))
+        {
+            Some(version) => version,
+            None => {
+                invalid.push((id, String::from("the registry doesn't serve this version")));
+                continue;
+            }
+        };
+
+        if !same_integrity(
+            &lock.integrity,
+            &version.dist.integrity,
+            &version.dist.shasum,
+        ) {
+            invalid.push((
+                id,
+                String::from("the lockfile integrity differs from the registry's"),
+            ));
+            continue;
+        }
+
+        let published = full_packuments
+            .get(&lock.name)
+            .and_then(|packument| packument.time.get(&lock.version))
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc));
+
+        match verify_signatures(
+            &keys,
+            &lock.name,
+            &lock.version,
+            &version.dist.integrity,
+            &version.dist.signatures,
+            published.as_ref(),
+        ) {
+            SignatureStatus::Verified => verified += 1,
+            SignatureStatus::Missing => missing.push(id),
+            SignatureStatus::Invalid(reason) => invalid.push((id, reason)),
+        }
+    }
+
+    output::set(
+        "signatures",
+        serde_json::json!({
+            "audited": locks.len(),
+            "skipped": skipped,
+            "verified": verified,
+            "missing": missing,
+            "invalid": invalid.iter().cloned().collect::<BTreeMap<_, _>>(),
+        }),
+    );
+
+    println!(
+        "audited {} packages in {}",
+        locks.len(),
+        app.lock_file_path.display()
+    );
+
+    if skipped > 0 {
+        info!(
+            "skipped {} packages that weren't installed from the registry",
+            skipped
+        );
+    }
+
+    println!(
+        "{} packages have {} registry signatures",
+        verified.to_string().bright_green(),
+        "verified".bright_green()
+    );
+
+    if !missing.is_empty() {
+        println!(
+            "{} packages have {} registry signatures:",
+            missing.len().to_string().bright_yellow(),
+            "missing".bright_yellow()
+        );
+
+        for id in missing.iter() {
+            println!("  {}", id.bright_cyan());
+        }
+    }
+
+    if !invalid.is_empty() {
+        // sorted by package so the report diffs cleanly between CI runs
+        let invalid = invalid.into_iter().collect::<BTreeMap<_, _>>();
+
+        println!(
+            "{} packages have {} registry signatures:",
+            invalid.len().to_string().bright_red(),
+            "invalid".bright_red()
+        );
+
+        for (id, reason) in invalid.iter() {
+            println!("  {} {}", id.bright_cyan(), reason.bright_black());
+        }
+
+        return Err(VoltError::InvalidSignatures {
+            count: invalid.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The GitHub id of `advisory`, which its url ends with.
+fn github_id(advisory: &Advisory) -> Option<&str> {
+    advisory.url.strip_prefix("https://github.com/advisories/")
+}
+
+/// The day `ignored` expired on, `None` while it still applies.
+fn expired(ignored: &IgnoredAdvisory, today: NaiveDate) -> Result<Option<NaiveDate>> {
+    let expires = match &ignored.expires {
+        Some(expires) => NaiveDate::parse_from_str(expires, "%Y-%m-%d").map_err(|_| {
+            VoltError::InvalidIgnoreDate {
+                id: ignored.id.clone(),
+                date: expires.clone(),
+            }
+        })?,
+        None => return Ok(None),
+    };
+
+    Ok(Some(expires).filter(|expires| *expires < today))
+}
+
+/// Report the advisories affecting the packages in the lockfile, failing on those of at
+/// least the configured level that aren't ignored.
+async fn audit_advisories(app: &App) -> Result<()> {
+    let config = &app.config.audit;
+
+    let level = match app.args.value_of("level") {
+        Some(level) => AdvisorySeverity::parse(level),
+        None => config.level,
+    }
+    .unwrap_or(AdvisorySeverity::Info);
+
+    let today = Utc::now().date_naive();
+
+    let mut ignores = vec![];
+
+    for ignored in config.ignore.iter() {
+        match expired(ignored, today)? {
+            Some(expired) => warn!(
+                "the ignore of advisory {} expired on {}",
+                ignored.id.bright_cyan(),
+                expired
+            ),
+            None => ignores.push(ignored),
+        }
+    }
+
+    let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for lock in lock_file
+        .dependencies
+        .values()
+        .filter(|lock| from_registry(lock))
+    {
+        versions
+            .entry(lock.name.clone())
+            .or_default()
+            .push(lock.version.clone());
+    }
+
+    if versions.is_empty() {
+        info!("no packages from the registry in the lockfile, nothing to audit");
+        return Ok(());
+    }
+
+    let found = get_advisories(&app.registry(), &versions).await?;
+
+    // sorted so the report diffs cleanly between CI runs
+    let mut found = found.into_iter().collect::<Vec<_>>();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut counts: BTreeMap<AdvisorySeverity, usize> = BTreeMap::new();
+    let mut report = vec![];
+    let mut used = vec![];
+    let mut failures = 0;
+
+    for (name, advisories) in found.iter() {
+        for advisory in advisories.iter() {
+            let mut affected = versions[name]
+                .iter()
+                .filter(|version| satisfies(version, &advisory.vulnerable_versions))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if affected.is_empty() {
+                continue;
+            }
+
+            affected.sort();
+            affected.dedup();
+
+            let severity = AdvisorySeverity::parse(&advisory.severity);
+
+            if let Some(severity) = severity {
+                *counts.entry(severity).or_default() += 1;
+            }
+
+            let ignored = ignores.iter().find(|ignored| {
+                ignored.id == advisory.id.to_string()
+                    || Some(ignored.id.as_str()) == github_id(advisory)
+            });
+
+            // a severity volt doesn't know can't be ranked, so it always counts
+            let fails = ignored.is_none() && severity.is_none_or(|severity| severity >= level);
+
+            if let Some(ignored) = ignored {
+                used.push(ignored.id.as_str());
+            }
+
+            if fails {
+                failures += 1;
+            }
+
+            report.push(serde_json::json!({
+                "id": advisory.id,
+                "package": name,
+                "versions": affected,
+                "severity": advisory.severity,
+                "title": advisory.title,
+                "url": advisory.url,
+                "ignored": ignored.is_some(),
+                "fails": fails,
+            }));
+
+            if app.json() {
+                continue;
+            }
+
+            let severity = match severity {
+                Some(AdvisorySeverity::Critical) | Some(AdvisorySeverity::High) => {
+                    advisory.severity.bright_red()
+                }
+                Some(AdvisorySeverity::Moderate) => advisory.severity.bright_yellow(),
+                _ => advisory.severity.bright_black(),
+            };
+
+            let affected = affected
+                .iter()
+                .map(|version| format!("{}@{}", name, version))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            match ignored {
+                Some(ignored) => println!(
+                    "{} {} {} {}  {}",
+                    "ignored".bright_black(),
+                    severity,
+                    affected.bright_cyan(),
+                    advisory.title,
+                    format!(
+                        "# {}{}",
+                        ignored.reason.as_deref().unwrap_or("no reason given"),
+                        ignored
+                            .expires
+                            .as_ref()
+                            .map(|expires| format!(", until {}", expires))
+                            .unwrap_or_default()
+                    )
+                    .bright_black()
+                ),
+                None => println!(
+                    "{} {} {} {}",
+                    severity,
+                    affected.bright_cyan(),
+                    advisory.title,
+                    advisory.url.bright_blue().underline()
+                ),
+            }
+        }
+    }
+
+    for ignored in ignores
+        .iter()
+        .filter(|ignored| !used.contains(&ignored.id.as_str()))
+    {
+        info!(
+            "advisory {} is ignored but no longer affects any package, it can be removed",
+            ignored.id.bright_cyan()
+        );
+    }
+
+    output::set("advisories", &report);
+
+    if !app.json() {
+        let summary = counts
+            .iter()
+            .rev()
+            .map(|(severity, count)| format!("{} {:?}", count, severity).to_lowercase())
+            .collect::<Vec<_>>();
+
+        match report.is_empty() {
+            true => println!(
+                "audited {} packages, no advisories affect them",
+                versions.len()
+            ),
+            false => println!(
+                "audited {} packages, found {} advisories ({}), {} ignored",
+                versions.len(),
+                report.len(),
+                summary.join(", "),
+                used.len()
+            ),
+        }
+    }
+
+    if failures > 0 {
+        return Err(VoltError::AdvisoriesFound {
+            count: failures,
+            level: format!("{:?}", level).to_lowercase(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
 #[async_trait]
 impl Command for Audit {
     fn help() -> String {
-        todo!()
+        format!(
+            r#"volt {}
+
+Audit the packages in the lockfile.
+
+Usage: {} {} {} {}
+
+Without a command, reports the advisories affecting the locked packages and exits non-zero
+on those of at least `level` under `[audit]` in volt.toml, except `[[audit.ignore]]` ones.
+
+Commands:
+
+  {} Verify the registry signature of every package, exits non-zero on invalid ones.
+
+Options:
+
+  {} Least severe advisories that fail, `info`, `low`, `moderate`, `high` or `critical`.
+  {} Print the results as a JSON document instead of text.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "audit".bright_purple(),
+            "[command]".white(),
+            "[flags]".white(),
+            "signatures".blue(),
+            "--level <severity>".blue(),
+            "--json".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
     }
 
     /// Execute the `volt audit` command
     ///
-    /// Execute a audit command
+    /// Audit the packages in the lockfile for advisories, `volt audit signatures` verifies
+    /// their registry signatures instead.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
-    /// //
+    /// // Verify the registry signatures of the locked packages
     /// // .exec() is an async call so you need to await it
     /// Audit.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
-        // let package_json = PackageJson::from("package.json");
-
-        // let mut requires = package_json.dependencies;
-        // requires.extend(package_json.dev_dependencies);
-
-        // let responses =
-        //     utils::get_volt_response_multi(requires.keys().cloned().collect::<Vec<String>>()).await;
-
-        // let mut dependencies: HashMap<String, AuditDependency> = HashMap::new();
-
-        // let start = Instant::now();
-
-        // for res in responses {
-        //     let version = res.version;
-
-        //     let packages = &res.versions.get(&version).unwrap().packages;
-
-        //     for package in packages {
-        //         let flattened_dependency_tree: Vec<AuditDependency> =
-        //             flatten_dependency_tree(package.1, packages);
-
-        //         for dp in flattened_dependency_tree {
-        //             dependencies.insert(package.0.to_owned(), dp);
-        //         }
-        //     }
-        // }
-
-        // let audit = AuditObject {
-        //     name: package_json.name,
-        //     version: package_json.version,
-        //     install: vec![],
-        //     remove: vec![],
-        //     metadata: HashMap::new(),
-        //     requires: requires,
-        //     dependencies: dependencies,
-        // };
-
-        // let mut response = chttp::post_async(
-        //     "http://registry.npmjs.org/-/npm/v1/security/audits",
-        //     format!("{:?}", audit),
-        // )
-        // .await
-        // .unwrap();
-
-        // let text = response.text_async().await.unwrap();
-
-        // let response: AuditResponse = serde_json::from_str(text.as_str()).unwrap();
+    async fn exec(app: Arc<App>) -> Result<()> {
+        if app.args.value_of("command") == Some("signatures") {
+            return audit_signatures(&app).await;
+        }
 
-        Ok(())
+        audit_advisories(&app).await
     }
 }
@@ -0,0 +1,331 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Bundle what a maintainer needs to look into a problem into one archive for an issue.
+//!
+//! The archive holds the version of volt and what it runs on, volt.toml and the .npmrc files
+//! with their secrets redacted, the end of `<volt dir>/volt.log`, volt.lock, package.json, the
+//! timings of recent runs and the versions of node and other tools on PATH. Settings and
+//! variables whose name marks a secret are replaced by a hash, credentials in URLs are dropped.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::core::utils::compat::{node_cpu, node_os, node_version};
+use crate::core::utils::env_snapshot::{is_secret, redact, tool_versions};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::{experiments, logging};
+use crate::{core::VERSION, App, Command};
+
+use async_trait::async_trait;
+use chrono::Local;
+use colored::Colorize;
+use flate2::{write::GzEncoder, Compression};
+use miette::Result;
+use serde_json::json;
+use tar::{Builder, Header};
+use tracing::info;
+
+/// Lines of the log kept, the latest runs are the ones that matter.
+const LOG_TAIL: usize = 2000;
+
+/// Where issues are opened.
+const ISSUES_URL: &str = "https://github.com/voltpkg/volt/issues/new";
+
+/// Variables that change how volt, npm or node behave, beyond those starting with `PREFIXES`.
+const VARIABLES: &[&str] = &[
+    "CI",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "PATH",
+    "SHELL",
+    "TERM",
+    "LANG",
+];
+
+const PREFIXES: &[&str] = &["VOLT_", "NPM_CONFIG_", "NODE_"];
+
+/// Struct implementation for the `BugReport` command.
+pub struct BugReport;
+
+#[async_trait]
+impl Command for BugReport {
+    /// Display a help menu for the `volt bug-report` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Bundles the version of volt, its configuration with secrets redacted, recent logs, the
+lockfile, timings and details of the environment into an archive to attach to an issue.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Write the archive to this path.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "bug-report".bright_purple(),
+            "[flags]".white(),
+            "--output".blue(),
+            "(-o)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt bug-report` command
+    ///
+    /// Writes `volt-bug-report-<time>.tar.gz` to the current directory, or to `--output`.
+    /// Files that don't exist are left out of the archive.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// BugReport.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let output = match app.args.value_of("output") {
+            Some(path) => app.current_dir.join(path),
+            None => app.current_dir.join(format!(
+                "volt-bug-report-{}.tar.gz",
+                Local::now().format("%Y%m%d-%H%M%S")
+            )),
+        };
+
+        let mut files: Vec<(&str, String)> = vec![
+            ("version.txt", version(&app)),
+            ("environment.json", environment(&app)),
+        ];
+
+        let user_npmrc = std::env::var_os("NPM_CONFIG_USERCONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| app.home_dir.join(".npmrc"));
+
+        let redacted = [
+            ("volt.toml", app.current_dir.join("volt.toml")),
+            ("project.npmrc", app.current_dir.join(".npmrc")),
+            ("user.npmrc", user_npmrc),
+        ];
+
+        for (name, path) in redacted.iter() {
+            if let Ok(data) = std::fs::read_to_string(path) {
+                files.push((*name, redact_config(&data)));
+            }
+        }
+
+        let copied = [
+            ("volt.lock", app.lock_file_path.clone()),
+            ("package.json", app.current_dir.join("package.json")),
+            ("timings.json", app.volt_dir.join("timings.json")),
+        ];
+
+        for (name, path) in copied.iter() {
+            if let Ok(data) = std::fs::read_to_string(path) {
+                files.push((*name, data));
+            }
+        }
+
+        if let Some(data) = logging::log_path().and_then(|path| std::fs::read_to_string(path).ok())
+        {
+            files.push(("volt.log", tail(&data, LOG_TAIL)));
+        }
+
+        let archive = archive(&files)?;
+
+        std::fs::write(&output, archive).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: output.display().to_string(),
+        })?;
+
+        for (name, _) in files.iter() {
+            println!("  {}", name.bright_cyan());
+        }
+
+        println!(
+            "{}: wrote {}",
+            "success".bright_green(),
+            output.display().to_string().bright_cyan()
+        );
+
+        info!(
+            "look it over, then attach it to an issue at {}",
+            ISSUES_URL.underline()
+        );
+
+        Ok(())
+    }
+}
+
+/// The version of volt, the platform and node it runs on and the experiments that are on.
+fn version(app: &App) -> String {
+    let mut lines = vec![
+        format!("volt {}", VERSION),
+        format!("platform: {} {}", node_os(), node_cpu()),
+        format!(
+            "node: {}",
+            node_version().unwrap_or_else(|| String::from("not found"))
+        ),
+    ];
+
+    let active = experiments::active(&app.config);
+
+    if active.is_empty() {
+        lines.push(String::from("experiments: none"));
+    }
+
+    for (experiment, origin) in active {
+        lines.push(format!(
+            "experiment: {} (from {})",
+            experiment.name(),
+            origin
+        ));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Where volt runs, the tools on PATH and the variables that affect it, secrets redacted.
+fn environment(app: &App) -> String {
+    let env = std::env::vars()
+        .filter(|(name, _)| {
+            let upper = name.to_uppercase();
+
+            VARIABLES.contains(&upper.as_str())
+                || PREFIXES.iter().any(|prefix| upper.starts_with(prefix))
+        })
+        .map(|(name, value)| {
+            let value = redact(&name, &strip_credentials(&value));
+
+            (name, value)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let tools = std::env::var_os("PATH")
+        .map(|path| tool_versions(&path))
+        .unwrap_or_default();
+
+    let environment = json!({
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "directory": app.current_dir,
+        "volt-dir": app.volt_dir,
+        "tools": tools,
+        "env": env,
+    });
+
+    serde_json::to_string_pretty(&environment).unwrap_or_default()
+}
+
+/// volt.toml or .npmrc with the value of every secret setting hashed and credentials dropped
+/// from URLs, e.g. `//registry.npmjs.org/:_authToken=...`.
+fn redact_config(data: &str) -> String {
+    data.lines()
+        .map(|line| {
+            let line = strip_credentials(line);
+
+            match line.split_once('=') {
+                Some((key, value)) if is_secret(key) => {
+                    let spacing = &value[..value.len() - value.trim_start().len()];
+                    let quoted = value.trim().starts_with('"');
+                    let secret = redact(key.trim(), value.trim().trim_matches('"'));
+
+                    match quoted {
+                        true => format!("{}={}\"{}\"", key, spacing, secret),
+                        false => format!("{}={}{}", key, spacing, secret),
+                    }
+                }
+                _ => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// `text` without the `user:password@` of the URLs in it.
+fn strip_credentials(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+
+    while let Some(index) = rest.find("://") {
+        let (head, tail) = rest.split_at(index + 3);
+        stripped.push_str(head);
+
+        let authority = tail
+            .find(|c: char| c == '/' || c.is_whitespace() || c == '"')
+            .unwrap_or(tail.len());
+
+        rest = match tail[..authority].rfind('@') {
+            Some(at) => {
+                stripped.push_str("<redacted>@");
+                &tail[at + 1..]
+            }
+            None => tail,
+        };
+    }
+
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The last `lines` lines of `data`.
+fn tail(data: &str, lines: usize) -> String {
+    let all = data.lines().collect::<Vec<_>>();
+    let start = all.len().saturating_sub(lines);
+
+    all[start..].join("\n") + "\n"
+}
+
+/// A gzipped tarball of `files`, nested under `volt-bug-report/`.
+fn archive(files: &[(&str, String)]) -> Result<Vec<u8>> {
+    let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+
+    let directory = Path::new("volt-bug-report");
+
+    for (name, contents) in files {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Local::now().timestamp() as u64);
+        header.set_cksum();
+
+        builder
+            .append_data(&mut header, directory.join(name), contents.as_bytes())
+            .map_err(|e| VoltError::WriteFileError {
+                name: String::from("bug report"),
+                source: e,
+            })?;
+    }
+
+    let encoder = builder
+        .into_inner()
+        .map_err(|e| VoltError::WriteFileError {
+            name: String::from("bug report"),
+            source: e,
+        })?;
+
+    Ok(encoder.finish().map_err(|e| VoltError::WriteFileError {
+        name: String::from("bug report"),
+        source: e,
+    })?)
+}
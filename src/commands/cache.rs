@@ -1,109 +1,200 @@
 /*
-Copyright 2021 Volt Contributors
-Licensed under the Apache License, Version 2.0 (the "License");
-you may not use this file except in compliance with the License.
-You may obtain a copy of the License at
-    http://www.apache.org/licenses/LICENSE-2.0
-Unless required by applicable law or agreed to in writing, software
-distributed under the License is distributed on an "AS IS" BASIS,
-WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-See the License for the specific language governing permissions and
-limitations under the License.
-*/
+    Copyright 2021 Volt Contributors
 
-//! Clean cached download files.
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
 
-use std::env::temp_dir;
-use std::fs;
-use std::fs::remove_file;
-use std::process::exit;
-use std::sync::Arc;
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Manage the zero-install cache of package archives committed with the project.
 
-use crate::coreutils::constants::PROGRESS_CHARS;
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::constants::PROGRESS_CHARS;
+use crate::core::utils::dedup::format_bytes;
+use crate::core::utils::download_tarball;
+use crate::core::utils::logging;
+use crate::core::utils::progress::PackageProgress;
+use crate::core::utils::zero_install::{self, archives, cache_dir, locked_package};
+use crate::core::{command::Command, VERSION};
 use crate::App;
+
 use async_trait::async_trait;
 use colored::Colorize;
+use futures_util::{stream, StreamExt, TryStreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
 use miette::Result;
-use crate::Command;
-use volt_core::VERSION;
+use std::sync::Arc;
+use tracing::{info, warn};
 
-/// Struct implementation for the `Add` command.
-#[derive(Clone)]
-pub struct Cache {}
+/// Struct implementation for the `Cache` command.
+pub struct Cache;
 
 #[async_trait]
 impl Command for Cache {
-    /// Display a help menu for the `volt cahe` command.
+    /// Display a help menu for the `volt cache` command.
     fn help() -> String {
         format!(
             r#"volt {}
-    
-Handle the volt cache files.
+
+Manages the archives in .volt/cache, committed so a fresh clone installs without network.
+
 Usage: {} {} {}
 
 Commands:
-  clean - Clean downloaded cache files and metadata. 
 
-Options: 
-    
-  {} {} Output verbose messages on internal operations.
-  {} {} Disable progress bar."#,
+  {} Put the archive of every package in volt.lock into .volt/cache.
+  {} Remove .volt/cache, installs download from the registry again.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
             "cache".bright_purple(),
-            "[command]".bright_purple(),
+            "<command>".white(),
+            "vendor".bright_blue(),
+            "unvendor".bright_blue(),
             "--verbose".blue(),
-            "(-v)".yellow(),
-            "--no-progress".blue(),
-            "(-np)".yellow()
+            "(-v)".yellow()
         )
     }
 
     /// Execute the `volt cache` command
     ///
-    /// Clean your download cache.
-    /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
+    /// `vendor` creates the cache, fills it with the archives of the packages in volt.lock
+    /// and drops the ones volt.lock doesn't have anymore. From then on every install keeps it
+    /// in sync. `unvendor` removes it.
     /// ## Examples
     /// ```
-    /// // Clean your download cache (does not break symlinks)
     /// // .exec() is an async call so you need to await it
-    /// Add.exec(app).await;
+    /// Cache.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        // if app.args.len() == 1 {
-        //     println!("{}", Self::help());
-        //     exit(1);
-        // }
-        // if app.args[1].as_str() == "clean" {
-        //     let files: Vec<_> = fs::read_dir(temp_dir().join("volt")).unwrap().collect();
-
-        //     let count = files.len();
-
-        //     let progress_bar = ProgressBar::new(count.to_owned() as u64);
-
-        //     progress_bar.set_style(
-        //         ProgressStyle::default_bar()
-        //             .progress_chars(PROGRESS_CHARS)
-        //             .template(&format!(
-        //                 "{} [{{bar:40.magenta/blue}}] {{msg:.blue}} {{len}} / {{pos}}",
-        //                 "Deleting Cache".bright_blue()
-        //             )),
-        //     );
-
-        //     for file in files {
-        //         let os_str = file.unwrap().file_name();
-        //         let f_name = format!(r"{}volt\{}", temp_dir().display(), os_str.to_str().unwrap());
-
-        //         remove_file(f_name).unwrap();
-        //         progress_bar.inc(1);
-        //     }
-
-        //     progress_bar.finish();
-        // }
-        Ok(())
+        if let Some(mirror) = &app.config.offline_mirror {
+            miette::bail!(
+                "`offline-mirror` is set to {}, installs use it instead of {}",
+                mirror.display(),
+                cache_dir(&app.current_dir).display()
+            );
+        }
+
+        match app.args.value_of("command") {
+            Some("unvendor") => unvendor(&app),
+            _ => vendor(&app).await,
+        }
+    }
+}
+
+async fn vendor(app: &Arc<App>) -> Result<()> {
+    let lock_file = LockFile::load(&app.lock_file_path)?;
+
+    let directory = zero_install::create(app)?;
+
+    // git and local packages don't come from a registry
+    let packages = lock_file
+        .dependencies
+        .values()
+        .filter(|lock| lock.tarball.starts_with("http"))
+        .map(|lock| (locked_package(lock), lock.optional))
+        .collect::<Vec<_>>();
+
+    let progress_bar = ProgressBar::new(packages.len() as u64);
+
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .progress_chars(PROGRESS_CHARS)
+            .template(&format!(
+                "{} [{{bar:40.magenta/blue}}] {{msg:.blue}}",
+                "Vendoring Packages".bright_blue()
+            )),
+    );
+
+    logging::attach(&progress_bar);
+
+    let downloads = packages
+        .iter()
+        .map(|(package, optional)| async move {
+            match download_tarball(app, package, &PackageProgress::hidden()).await {
+                Err(error) if *optional => {
+                    warn!(
+                        "skipping optional dependency {}: {}",
+                        package.name.bright_cyan(),
+                        error
+                    );
+
+                    Ok(())
+                }
+                result => result,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let downloaded = stream::iter(downloads)
+        .buffer_unordered(app.concurrency())
+        .inspect(|_| progress_bar.inc(1))
+        .try_collect::<()>()
+        .await;
+
+    progress_bar.finish();
+
+    logging::detach();
+
+    downloaded?;
+
+    for name in zero_install::prune(app, &lock_file)? {
+        println!("  {} {}", "-".bright_red().bold(), name.bright_black());
     }
+
+    let archives = archives(&directory);
+
+    println!(
+        "{}: {} archives in {} ({})",
+        "success".bright_green(),
+        archives.len(),
+        directory.display(),
+        format_bytes(archives.iter().map(|(_, size)| size).sum())
+    );
+
+    info!("commit .volt/.gitignore and .volt/cache, a fresh clone then installs without network");
+
+    zero_install::check_size(app);
+
+    Ok(())
+}
+
+fn unvendor(app: &App) -> Result<()> {
+    let directory = cache_dir(&app.current_dir);
+
+    if !directory.exists() {
+        info!("there's no {} to remove", directory.display());
+
+        return Ok(());
+    }
+
+    let size = zero_install::remove(app)?;
+
+    println!(
+        "{}: removed {} ({})",
+        "success".bright_green(),
+        directory.display(),
+        format_bytes(size)
+    );
+
+    if app.config.zero_install.enabled {
+        warn!(
+            "`enabled` is still set under `[zero-install]` in volt.toml, the next install creates it again"
+        );
+    }
+
+    Ok(())
 }
@@ -16,13 +16,20 @@
 
 //! Check for errors
 
+use crate::core::model::lock_file::{DependencyLock, LockFile};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::installed_version;
+use crate::core::utils::linker::ProjectState;
+use crate::core::utils::verify::verify_files;
 use crate::core::{command::Command, VERSION};
 use crate::App;
 
 use async_trait::async_trait;
 use colored::Colorize;
 use miette::Result;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use tracing::{info, warn};
 
 /// Struct implementation for the `Check` command.
 pub struct Check;
@@ -34,32 +41,135 @@ impl Command for Check {
         format!(
             r#"volt {}
     
-Checks for errors.
+Checks that node_modules matches the lockfile.
 
-Usage: {} {}
+Usage: {} {} {}
 
 Options: 
 
+  {} {} Also verify every file against the hashes in the lockfile.
   {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
             "check".bright_purple(),
+            "[flags]".white(),
+            "--deep".blue(),
+            "(-d)".yellow(),
             "--verbose".blue(),
             "(-v)".yellow()
         )
     }
 
-    /// Execute the `volt Check` command
+    /// Execute the `volt check` command
     ///
-    /// Removes a package from your direct dependencies.
+    /// Checks that every locked package is installed at its locked version, and with
+    /// `--deep` that none of its files were modified, added or removed.
     /// ## Examples
     /// ```
     /// // .exec() is an async call so you need to await it
-    /// Create.exec(app, vec![], vec!["--verbose"]).await;
+    /// Check.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let lock_file = LockFile::load(&app.lock_file_path)?;
+
+        let deep = app.has_flag("deep");
+
+        // a lockfile can list several versions of a package, only one of them is installed
+        let mut locked: BTreeMap<&str, Vec<&DependencyLock>> = BTreeMap::new();
+
+        for lock in lock_file.dependencies.values() {
+            locked.entry(lock.install_name()).or_default().push(lock);
+        }
+
+        let mut problems = 0;
+        let mut unpinned = 0;
+
+        for (name, locks) in locked.iter() {
+            let installed = match installed_version(&app, name) {
+                Some(installed) => installed,
+                None => {
+                    if locks.iter().all(|lock| lock.optional) {
+                        continue;
+                    }
+
+                    problems += 1;
+                    println!("{} {} is not installed", "missing".bright_red(), name);
+                    continue;
+                }
+            };
+
+            let lock = match locks.iter().find(|lock| lock.version == installed) {
+                Some(lock) => lock,
+                None => {
+                    problems += 1;
+                    println!(
+                        "{} {}@{} is installed, the lockfile has {}",
+                        "version".bright_red(),
+                        name,
+                        installed,
+                        locks
+                            .iter()
+                            .map(|lock| lock.version.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    continue;
+                }
+            };
+
+            if !deep {
+                continue;
+            }
+
+            if lock.files.is_empty() {
+                unpinned += 1;
+                continue;
+            }
+
+            let report = verify_files(&app.node_modules_dir.join(name), &lock.files)?;
+
+            if report.is_clean() {
+                continue;
+            }
+
+            problems += 1;
+
+            for (label, paths) in [
+                ("modified", &report.modified),
+                ("missing", &report.missing),
+                ("added", &report.added),
+            ] {
+                for path in paths.iter() {
+                    println!("{} {}/{}", label.bright_red(), name, path);
+                }
+            }
+        }
+
+        // installs fall back to copies when the store can't be hard linked from here
+        if let Some(hint) = ProjectState::load(&app).and_then(|state| state.hint()) {
+            info!("{}", hint);
+        }
+
+        if unpinned > 0 {
+            warn!(
+                "{} packages have no file hashes, install them with `--file-integrity` to check their files",
+                unpinned
+            );
+        }
+
+        if problems > 0 {
+            return Err(VoltError::CheckFailed { problems }.into());
+        }
+
+        println!(
+            "{}: node_modules matches the lockfile ({} packages{})",
+            "success".bright_green(),
+            locked.len(),
+            if deep { ", every file verified" } else { "" }
+        );
+
         Ok(())
     }
 }
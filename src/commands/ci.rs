@@ -0,0 +1,104 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Clean install for CI, from the lockfile alone.
+
+use crate::commands::install::install;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::workspace::workspace_root;
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::sync::Arc;
+use tracing::info;
+
+pub struct Ci {}
+
+#[async_trait]
+impl Command for Ci {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Remove node_modules and install exactly what volt.lock describes.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Accept all prompts while installing dependencies.
+  {} Don't run the install scripts of dependencies or of the project.
+  {} Fail when node doesn't satisfy the `engines` of a package.
+  {} Replace files in node_modules that volt didn't create.
+  {} Use less memory at the cost of speed, for small containers.
+  {} Downloads and extractions run at once, a multiple of the CPU count by default.
+  {} Log every package on a line instead of drawing progress bars.
+  {} Install from the store only, failing for packages that aren't in it.
+  {} Registry to download from instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "ci".bright_purple(),
+            "[flags]".white(),
+            "--yes".blue(),
+            "(-y)".yellow(),
+            "--ignore-scripts".blue(),
+            "--engine-strict".blue(),
+            "--force-overwrite".blue(),
+            "--low-memory".blue(),
+            "--concurrency <n>".blue(),
+            "--no-progress".blue(),
+            "--offline".blue(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt ci` command
+    ///
+    /// Deletes node_modules and runs a frozen install, which fails with a diff when
+    /// package.json and volt.lock disagree instead of updating the lockfile.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Clean install from volt.lock
+    /// // .exec() is an async call so you need to await it
+    /// Ci.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let root = workspace_root(&app.current_dir).unwrap_or_else(|| app.current_dir.clone());
+        let node_modules = root.join("node_modules");
+
+        if std::fs::symlink_metadata(&node_modules).is_ok() {
+            std::fs::remove_dir_all(&node_modules).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: node_modules.display().to_string(),
+            })?;
+
+            info!("removed {}", node_modules.display());
+        }
+
+        app.within_deadline(install(app.clone(), true)).await
+    }
+}
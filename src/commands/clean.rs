@@ -0,0 +1,177 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Remove what installs and builds leave in a project.
+
+use crate::core::utils::dedup::format_bytes;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::fs_utils::tree_size;
+use crate::core::utils::linker::remove_path;
+use crate::core::utils::pnp;
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+/// What `.volt` keeps, the zero-install cache is committed with the project.
+const KEPT: &[&str] = &["cache", ".gitignore"];
+
+/// Struct implementation for the `Clean` command.
+pub struct Clean;
+
+#[async_trait]
+impl Command for Clean {
+    /// Display a help menu for the `volt clean` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Removes node_modules and the install state in .volt, the next install starts from scratch.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Also remove the build output directories listed under `clean` in volt.toml.
+  {} {} List what would be removed without removing anything.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "clean".bright_purple(),
+            "[flags]".white(),
+            "--build".blue(),
+            "(-b)".yellow(),
+            "--dry-run".blue(),
+            "(-n)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt clean` command
+    ///
+    /// Removes node_modules, `.pnp.cjs` and everything in `.volt` but the zero-install cache,
+    /// and with `--build` the directories listed under `clean` in volt.toml. Only runs in the
+    /// directory holding package.json, and never removes anything outside of it.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Clean.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        if !app.current_dir.join("package.json").is_file() {
+            return Err(VoltError::NotProjectRoot {
+                path: app.current_dir.display().to_string(),
+            }
+            .into());
+        }
+
+        let mut paths = vec![
+            app.node_modules_dir.clone(),
+            app.current_dir.join(pnp::RUNTIME_FILE),
+        ];
+
+        let state = app.current_dir.join(".volt");
+
+        for entry in std::fs::read_dir(&state).into_iter().flatten().flatten() {
+            if !KEPT.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                paths.push(entry.path());
+            }
+        }
+
+        if app.has_flag("build") {
+            for directory in app.config.clean.iter() {
+                paths.push(build_dir(&app.current_dir, directory)?);
+            }
+        }
+
+        let paths = paths
+            .into_iter()
+            .filter(|path| std::fs::symlink_metadata(path).is_ok())
+            .collect::<Vec<_>>();
+
+        if paths.is_empty() {
+            info!("nothing to clean");
+
+            return Ok(());
+        }
+
+        let dry_run = app.has_flag("dry-run");
+
+        let mut total = 0;
+
+        for path in paths.iter() {
+            let size = tree_size(path).bytes;
+
+            total += size;
+
+            println!(
+                "  {} {} {}",
+                "-".bright_red().bold(),
+                path.strip_prefix(&app.current_dir)
+                    .unwrap_or(path)
+                    .display(),
+                format_bytes(size).bright_black()
+            );
+
+            if !dry_run {
+                remove_path(path).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                })?;
+            }
+        }
+
+        match dry_run {
+            true => info!(
+                "would remove {} paths ({}), run without `--dry-run` to remove them",
+                paths.len(),
+                format_bytes(total)
+            ),
+            false => println!(
+                "{}: removed {} paths ({})",
+                "success".bright_green(),
+                paths.len(),
+                format_bytes(total)
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+/// `directory` from volt.toml inside `project`, absolute paths and `..` could point anywhere.
+fn build_dir(project: &Path, directory: &Path) -> Result<PathBuf> {
+    let inside = directory
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !inside || directory.components().all(|c| c == Component::CurDir) {
+        miette::bail!(
+            "`{}` under `clean` in volt.toml isn't a directory inside the project",
+            directory.display()
+        );
+    }
+
+    Ok(project.join(directory))
+}
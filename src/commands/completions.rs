@@ -0,0 +1,59 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Print a completion script for a shell.
+//!
+//! The scripts are generated from the clap arguments `main` parses, so every subcommand and
+//! flag volt accepts completes without a list to keep in sync. Unlike the other commands
+//! this one needs those arguments rather than an `App`, `main` runs it before initializing one.
+//!
+//! Where each shell picks the script up from:
+//!
+//! ```text
+//! volt completions bash > ~/.local/share/bash-completion/completions/volt
+//! volt completions zsh > "${fpath[1]}/_volt"
+//! volt completions fish > ~/.config/fish/completions/volt.fish
+//! volt completions powershell >> $PROFILE
+//! volt completions elvish >> ~/.elvish/rc.elv
+//! ```
+
+use clap_generate::generate;
+use clap_generate::generators::{Bash, Elvish, Fish, PowerShell, Zsh};
+use miette::Result;
+
+/// Shells a script can be generated for.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell", "elvish"];
+
+/// Struct implementation for the `Completions` command.
+pub struct Completions;
+
+impl Completions {
+    /// Print the completion script of `shell` for `cli`, the arguments volt parses.
+    pub fn generate(cli: &mut clap::App, shell: &str) -> Result<()> {
+        let mut stdout = std::io::stdout();
+
+        match shell {
+            "bash" => generate::<Bash, _>(cli, "volt", &mut stdout),
+            "zsh" => generate::<Zsh, _>(cli, "volt", &mut stdout),
+            "fish" => generate::<Fish, _>(cli, "volt", &mut stdout),
+            "powershell" => generate::<PowerShell, _>(cli, "volt", &mut stdout),
+            "elvish" => generate::<Elvish, _>(cli, "volt", &mut stdout),
+            _ => miette::bail!("volt can't complete {}, only {}", shell, SHELLS.join(", ")),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Read and change settings in the volt.toml of the project.
+//!
+//! Keys are dotted paths into volt.toml, like `timeouts.connect` or
+//! `experiments.isolated-linker`. Values are written as booleans or numbers when they parse
+//! as one and as strings otherwise, and the changed file has to load before it's written.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::core::model::config::{ConfigError, VoltConfig};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::experiments::Experiment;
+use crate::{core::VERSION, App, Command};
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use toml::Value;
+use tracing::{info, warn};
+
+/// Struct implementation for the `Config` command.
+pub struct Config;
+
+#[async_trait]
+impl Command for Config {
+    /// Display a help menu for the `volt config` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Reads and changes settings in the volt.toml of the project.
+
+Usage: {} {} {} {} {}
+
+Commands:
+
+  {} Print the value of a setting.
+  {} Change a setting, e.g. `volt config set experiments.isolated-linker true`.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "config".bright_purple(),
+            "<command>".white(),
+            "<key>".white(),
+            "[value]".white(),
+            "get".bright_blue(),
+            "set".bright_blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt config` command
+    ///
+    /// `get` prints the value of a dotted key of volt.toml, `set` changes it and writes the
+    /// file back, comments are not kept.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Config.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let path = app.current_dir.join("volt.toml");
+        let key = app.args.value_of("key").unwrap_or_default();
+
+        let mut document = read_document(&path)?;
+
+        match app.args.value_of("command") {
+            Some("set") => {
+                let value = match app.args.value_of("value") {
+                    Some(value) => value,
+                    None => miette::bail!("`volt config set` needs a value for {}", key),
+                };
+
+                set(&mut document, key, parse_value(value))?;
+
+                let data = toml::to_string(&document)
+                    .map_err(|e| miette::miette!("unable to write {}: {}", key, e))?;
+
+                // a value of the wrong type would only fail the next command
+                toml::from_str::<VoltConfig>(&data)
+                    .map_err(|e| ConfigError::Decode(path.display().to_string(), e))?;
+
+                std::fs::write(&path, data).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                })?;
+
+                if let Some(name) = key.strip_prefix("experiments.") {
+                    if Experiment::from_name(name).is_none() {
+                        warn!("volt has no experiment called {}", name.bright_cyan());
+                    }
+                }
+
+                println!(
+                    "{}: set {} to {} in {}",
+                    "success".bright_green(),
+                    key.bright_cyan(),
+                    value,
+                    path.display()
+                );
+            }
+            _ => match get(&document, key) {
+                Some(Value::String(value)) => println!("{}", value),
+                Some(value) => println!("{}", value),
+                None => info!("{} isn't set in {}", key.bright_cyan(), path.display()),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// The volt.toml at `path` as a table, empty when there is none yet.
+fn read_document(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(Value::Table(Default::default()));
+    }
+
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::IO(path.display().to_string(), e))?;
+
+    let document =
+        toml::from_str(&data).map_err(|e| ConfigError::Decode(path.display().to_string(), e))?;
+
+    Ok(document)
+}
+
+/// `true`, `false` and numbers as themselves, anything else as a string.
+fn parse_value(value: &str) -> Value {
+    if let Ok(boolean) = value.parse::<bool>() {
+        return Value::Boolean(boolean);
+    }
+
+    if let Ok(integer) = value.parse::<i64>() {
+        return Value::Integer(integer);
+    }
+
+    Value::String(value.to_string())
+}
+
+fn get<'a>(document: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(document, |value, segment| value.get(segment))
+}
+
+/// Set the dotted `key` of `document`, creating the tables on the way.
+fn set(document: &mut Value, key: &str, value: Value) -> Result<()> {
+    let mut segments = key.split('.').collect::<Vec<_>>();
+    let last = segments.pop().unwrap_or_default();
+
+    if last.is_empty() || segments.iter().any(|segment| segment.is_empty()) {
+        miette::bail!("{} isn't a valid key, like `timeouts.connect`", key);
+    }
+
+    let mut table = document;
+
+    for segment in segments {
+        let next = match table {
+            Value::Table(entries) => entries
+                .entry(segment.to_string())
+                .or_insert_with(|| Value::Table(Default::default())),
+            _ => miette::bail!("{} isn't a table in volt.toml", segment),
+        };
+
+        table = next;
+    }
+
+    match table {
+        Value::Table(entries) => {
+            entries.insert(last.to_string(), value);
+            Ok(())
+        }
+        _ => miette::bail!("{} can't be set, its parent isn't a table", key),
+    }
+}
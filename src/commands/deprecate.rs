@@ -0,0 +1,115 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Deprecate versions of a package on the registry.
+//!
+//! npm warns with the message when a deprecated version is installed, and `volt query` hands it
+//! to editors. The message is set on every version the range matches, an empty one takes it
+//! back.
+
+use std::sync::Arc;
+
+use crate::core::model::http_manager::deprecate_versions;
+use crate::core::utils::auth::Credentials;
+use crate::{core::VERSION, App, Command};
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+
+/// Struct implementation for the `Deprecate` command.
+pub struct Deprecate;
+
+#[async_trait]
+impl Command for Deprecate {
+    /// Display a help menu for the `volt deprecate` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Deprecates the versions of a package matching a range, or every version without one. An
+empty message takes the deprecation back.
+
+Usage: {} {} {} {}
+
+Examples:
+
+  volt deprecate my-package@"<2" "upgrade to 2.x, 1.x is no longer maintained"
+  volt deprecate my-package@1.2.3 ""
+
+Options:
+
+  {} Registry of the package instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "deprecate".bright_purple(),
+            "<package>[@range]".white(),
+            "<message>".white(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt deprecate` command
+    ///
+    /// Sets the `deprecated` field of the matching versions in the registry document of the
+    /// package, with the token of its registry.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Deprecate.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let spec = app.args.value_of("package").unwrap_or_default();
+        let message = app.args.value_of("message").unwrap_or_default();
+
+        // the `@` of a scope is never a version separator, and `latest` is a tag like any other
+        let (name, range) = match spec.rfind('@') {
+            Some(index) if index > 0 => (&spec[..index], &spec[index + 1..]),
+            _ => (spec, "*"),
+        };
+
+        let registry = app.registries.for_package(name).to_string();
+        let credentials = Credentials::new(&app, &registry);
+
+        let changed = deprecate_versions(&registry, &credentials, name, range, message).await?;
+
+        for version in changed.iter() {
+            println!("  {}@{}", name, version.bright_cyan());
+        }
+
+        let action = match message.is_empty() {
+            true => "undeprecated",
+            false => "deprecated",
+        };
+
+        println!(
+            "{}: {} {} versions of {}",
+            "success".bright_green(),
+            action,
+            changed.len(),
+            name.bright_cyan()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Compare two environment snapshots written by `volt run --env-snapshot`.
+
+use crate::core::utils::env_snapshot::EnvSnapshot;
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::sync::Arc;
+
+/// Struct implementation for the `EnvDiff` command.
+pub struct EnvDiff;
+
+#[async_trait]
+impl Command for EnvDiff {
+    /// Display a help menu for the `volt env-diff` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Shows how two environment snapshots of `volt run --env-snapshot` differ.
+
+Usage: {} {} {} {}
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "env-diff".bright_purple(),
+            "<before>".white(),
+            "<after>".white(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt env-diff` command
+    ///
+    /// Prints what differs between the snapshots: platform and shell, tool versions, where
+    /// the script's commands resolve to, PATH entries and their order, and every other
+    /// variable. Secrets are compared by their hash.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// EnvDiff.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let before = app.current_dir.join(app.args.value_of("before").unwrap());
+        let after = app.current_dir.join(app.args.value_of("after").unwrap());
+
+        let sections = EnvSnapshot::load(&before)?.diff(&EnvSnapshot::load(&after)?);
+
+        if sections.is_empty() {
+            println!(
+                "{}: the environments are the same",
+                "success".bright_green()
+            );
+
+            return Ok(());
+        }
+
+        println!(
+            "{} {} {}",
+            before.display().to_string().bright_red(),
+            "->".bright_black(),
+            after.display().to_string().bright_green()
+        );
+
+        for (section, changes) in sections.iter() {
+            println!("\n{}", section.bright_cyan().bold());
+
+            for change in changes.iter() {
+                change.print();
+            }
+        }
+
+        println!();
+
+        Ok(())
+    }
+}
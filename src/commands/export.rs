@@ -0,0 +1,109 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Export the lockfile in the format of other package managers.
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package_lock::export_package_lock;
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::sync::Arc;
+use tracing::warn;
+
+pub struct ExportLockfile {}
+
+#[async_trait]
+impl Command for ExportLockfile {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Export the lockfile in the format of another package manager.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Format to export, only `npm` (package-lock.json) for now.
+  {} {} Path to write, defaults to package-lock.json.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "export-lockfile".bright_purple(),
+            "[flags]".white(),
+            "--format".blue(),
+            "(-f)".yellow(),
+            "--output".blue(),
+            "(-o)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt export-lockfile` command
+    ///
+    /// Converts the lockfile into a version 3 package-lock.json, so tools that only
+    /// understand npm's format keep working for projects installed with volt.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Write package-lock.json next to volt.lock
+    /// // .exec() is an async call so you need to await it
+    /// ExportLockfile.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let lock_file = LockFile::load(&app.lock_file_path)?;
+
+        let path = app
+            .current_dir
+            .join(app.args.value_of("output").unwrap_or("package-lock.json"));
+
+        let (document, unplaced) = export_package_lock(&app, &lock_file);
+
+        if unplaced > 0 {
+            warn!(
+                "left out {} versions no package in node_modules depends on, install before exporting to include them",
+                unplaced
+            );
+        }
+
+        let mut data = serde_json::to_string_pretty(&document).unwrap();
+        data.push('\n');
+
+        std::fs::write(&path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        println!(
+            "{}: exported {} packages to {}",
+            "success".bright_green(),
+            lock_file.dependencies.len() - unplaced,
+            path.display().to_string().bright_cyan()
+        );
+
+        Ok(())
+    }
+}
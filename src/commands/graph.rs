@@ -0,0 +1,618 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Export the dependency graph of the lockfile for visualization and analysis with other tools.
+
+use crate::core::model::http_manager::{get_advisories, Advisory};
+use crate::core::model::lock_file::{DependencyLock, LockFile};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::installed_version;
+use crate::core::utils::package_lock::reachable;
+use crate::core::utils::satisfies;
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use rusqlite::{params, Connection};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+pub struct Graph {}
+
+/// Tables `volt graph export --sqlite` writes, dropped and recreated on every export so
+/// other tables in the same database are left alone.
+const SCHEMA: &str = r#"
+DROP TABLE IF EXISTS affected_versions;
+DROP TABLE IF EXISTS advisories;
+DROP TABLE IF EXISTS edges;
+DROP TABLE IF EXISTS versions;
+DROP TABLE IF EXISTS packages;
+
+CREATE TABLE packages (
+    name TEXT PRIMARY KEY,
+    direct INTEGER NOT NULL
+);
+
+CREATE TABLE versions (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL REFERENCES packages(name),
+    version TEXT NOT NULL,
+    alias TEXT,
+    tarball TEXT NOT NULL,
+    integrity TEXT NOT NULL,
+    optional INTEGER NOT NULL,
+    has_install_script INTEGER NOT NULL,
+    overridden TEXT,
+    license TEXT,
+    size INTEGER,
+    files INTEGER
+);
+
+CREATE TABLE edges (
+    from_id INTEGER NOT NULL REFERENCES versions(id),
+    to_id INTEGER NOT NULL REFERENCES versions(id),
+    name TEXT NOT NULL
+);
+
+CREATE TABLE advisories (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    url TEXT NOT NULL,
+    vulnerable_versions TEXT NOT NULL
+);
+
+CREATE TABLE affected_versions (
+    version_id INTEGER NOT NULL REFERENCES versions(id),
+    advisory_id INTEGER NOT NULL REFERENCES advisories(id)
+);
+
+CREATE INDEX edges_from ON edges(from_id);
+CREATE INDEX edges_to ON edges(to_id);
+CREATE INDEX versions_name ON versions(name);
+"#;
+
+/// What node_modules says about an installed package.
+#[derive(Default)]
+struct Installed {
+    license: Option<String>,
+    size: Option<u64>,
+    files: Option<u64>,
+}
+
+/// License of a package manifest, old manifests list several under `licenses`.
+fn manifest_license(manifest: &Value) -> Option<String> {
+    match &manifest["license"] {
+        Value::String(license) => return Some(license.clone()),
+        Value::Object(license) => return license.get("type")?.as_str().map(String::from),
+        _ => {}
+    }
+
+    let licenses = manifest["licenses"]
+        .as_array()?
+        .iter()
+        .filter_map(|license| license["type"].as_str())
+        .collect::<Vec<_>>();
+
+    (!licenses.is_empty()).then(|| licenses.join(" OR "))
+}
+
+/// Size and license of `lock` when node_modules holds that exact version.
+fn installed(app: &App, lock: &DependencyLock) -> Installed {
+    let directory = app.node_modules_dir.join(lock.install_name());
+
+    if installed_version(app, lock.install_name()).as_deref() != Some(&lock.version) {
+        return Installed::default();
+    }
+
+    let license = read_to_string(directory.join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+        .and_then(|manifest| manifest_license(&manifest));
+
+    let (mut size, mut files) = (0, 0);
+
+    for entry in WalkDir::new(&directory)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "node_modules")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+    {
+        size += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        files += 1;
+    }
+
+    Installed {
+        license,
+        size: Some(size),
+        files: Some(files),
+    }
+}
+
+/// The package.json in `directory`, `Value::Null` when it can't be read.
+fn read_manifest(directory: &Path) -> Value {
+    read_to_string(directory.join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+        .unwrap_or(Value::Null)
+}
+
+/// Names the root package.json depends on directly, dev dependencies only with `dev`.
+fn direct_dependencies(manifest: &Value, dev: bool) -> BTreeSet<String> {
+    [
+        "dependencies",
+        "devDependencies",
+        "optionalDependencies",
+        "peerDependencies",
+    ]
+    .iter()
+    .filter(|field| dev || **field != "devDependencies")
+    .filter_map(|field| manifest[field].as_object())
+    .flat_map(|section| section.keys().cloned())
+    .collect()
+}
+
+/// Write the locked `locks` and the advisories `found` for them into the database at `path`,
+/// in one transaction. Returns the number of edges and advisories written.
+fn write_sqlite(
+    app: &App,
+    path: &str,
+    locks: &[&DependencyLock],
+    packages: &BTreeMap<&str, bool>,
+    found: &[(String, Vec<Advisory>)],
+) -> rusqlite::Result<(usize, usize)> {
+    let mut connection = Connection::open(path)?;
+    let transaction = connection.transaction()?;
+
+    transaction.execute_batch(SCHEMA)?;
+
+    // statements are scoped, they borrow the transaction until it commits
+    {
+        let mut insert = transaction.prepare("INSERT INTO packages VALUES (?1, ?2)")?;
+
+        for (name, direct) in packages.iter() {
+            insert.execute(params![name, direct])?;
+        }
+    }
+
+    // ids of the versions every install name resolves to, the installed one first
+    let mut ids: HashMap<&str, Vec<i64>> = HashMap::new();
+
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO versions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        )?;
+
+        for (index, lock) in locks.iter().enumerate() {
+            let id = index as i64 + 1;
+            let installed = installed(app, lock);
+
+            let entry = ids.entry(lock.install_name()).or_default();
+
+            if installed.size.is_some() {
+                entry.insert(0, id);
+            } else {
+                entry.push(id);
+            }
+
+            insert.execute(params![
+                id,
+                lock.name,
+                lock.version,
+                lock.alias,
+                lock.tarball,
+                lock.integrity,
+                lock.optional,
+                lock.has_install_script,
+                lock.overridden,
+                installed.license,
+                installed.size.map(|size| size as i64),
+                installed.files.map(|files| files as i64),
+            ])?;
+        }
+    }
+
+    let mut edges = 0;
+
+    {
+        let mut insert = transaction.prepare("INSERT INTO edges VALUES (?1, ?2, ?3)")?;
+
+        for (index, lock) in locks.iter().enumerate() {
+            for name in lock.dependencies.iter() {
+                if let Some(to) = ids.get(name.as_str()).and_then(|ids| ids.first()) {
+                    insert.execute(params![index as i64 + 1, to, name])?;
+
+                    edges += 1;
+                }
+            }
+        }
+    }
+
+    let mut advisories = 0;
+
+    {
+        let mut insert_advisory = transaction
+            .prepare("INSERT OR IGNORE INTO advisories VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        let mut insert_affected =
+            transaction.prepare("INSERT INTO affected_versions VALUES (?1, ?2)")?;
+
+        for (name, list) in found.iter() {
+            for advisory in list.iter() {
+                insert_advisory.execute(params![
+                    advisory.id as i64,
+                    name,
+                    advisory.title,
+                    advisory.severity,
+                    advisory.url,
+                    advisory.vulnerable_versions,
+                ])?;
+
+                advisories += 1;
+
+                for (index, lock) in locks.iter().enumerate() {
+                    if &lock.name == name && satisfies(&lock.version, &advisory.vulnerable_versions)
+                    {
+                        insert_affected.execute(params![index as i64 + 1, advisory.id as i64])?;
+                    }
+                }
+            }
+        }
+    }
+
+    transaction.commit()?;
+
+    Ok((edges, advisories))
+}
+
+async fn export_sqlite(app: &App, path: &str) -> Result<()> {
+    let lock_file = LockFile::load(&app.lock_file_path)?;
+
+    let mut locks = lock_file.dependencies.values().collect::<Vec<_>>();
+
+    locks.sort_by(|a, b| {
+        (a.install_name(), &a.name, &a.version).cmp(&(b.install_name(), &b.name, &b.version))
+    });
+
+    let direct = direct_dependencies(&read_manifest(&app.current_dir), true);
+
+    let mut packages: BTreeMap<&str, bool> = BTreeMap::new();
+
+    for lock in locks.iter() {
+        *packages.entry(&lock.name).or_default() |= direct.contains(lock.install_name());
+    }
+
+    let mut found = vec![];
+
+    if !app.has_flag("no-advisories") {
+        let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for lock in locks.iter().filter(|lock| lock.tarball.starts_with("http")) {
+            versions
+                .entry(lock.name.clone())
+                .or_default()
+                .push(lock.version.clone());
+        }
+
+        found = get_advisories(&app.registry(), &versions)
+            .await?
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    let (edges, advisories) = write_sqlite(app, path, &locks, &packages, &found).map_err(|e| {
+        VoltError::SqliteExportFailed {
+            path: path.to_string(),
+            message: e.to_string(),
+        }
+    })?;
+
+    println!(
+        "{}: exported {} versions of {} packages, {} edges and {} advisories to {}",
+        "success".bright_green(),
+        locks.len(),
+        packages.len(),
+        edges,
+        advisories,
+        path.bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// A version in the graph `export_graph` writes.
+struct Node {
+    /// Steps from the project, its direct dependencies are 1.
+    depth: usize,
+    /// Only the dev dependencies of the project need it.
+    dev: bool,
+    /// Its dependencies are left out, `--collapse-dev` stops at dev dependencies.
+    collapsed: bool,
+    dependencies: BTreeSet<usize>,
+}
+
+/// A DOT identifier, quoted.
+fn quoted(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The graph from the project down as Graphviz DOT or a JSON adjacency list, each name
+/// resolving to the version node_modules holds like in the SQLite export. Versions nothing
+/// in the project depends on anymore are left out.
+fn export_graph(app: &App) -> Result<()> {
+    let lock_file = LockFile::load(&app.lock_file_path)?;
+    let manifest = read_manifest(&app.current_dir);
+
+    let max_depth = app
+        .args
+        .value_of("depth")
+        .and_then(|depth| depth.parse::<usize>().ok())
+        .unwrap_or(usize::MAX);
+
+    let collapse_dev = app.has_flag("collapse-dev");
+
+    let mut locks = lock_file.dependencies.values().collect::<Vec<_>>();
+
+    locks.sort_by(|a, b| {
+        (a.install_name(), &a.name, &a.version).cmp(&(b.install_name(), &b.name, &b.version))
+    });
+
+    let mut ids: HashMap<&str, Vec<usize>> = HashMap::new();
+
+    for (index, lock) in locks.iter().enumerate() {
+        let entry = ids.entry(lock.install_name()).or_default();
+
+        if installed_version(app, lock.install_name()).as_deref() == Some(&lock.version) {
+            entry.insert(0, index);
+        } else {
+            entry.push(index);
+        }
+    }
+
+    let resolve = |name: &str| ids.get(name).and_then(|ids| ids.first()).copied();
+
+    let production_roots = direct_dependencies(&manifest, false);
+
+    let production = reachable(
+        &lock_file,
+        production_roots.iter().map(String::as_str).collect(),
+    );
+
+    let roots = direct_dependencies(&manifest, true)
+        .iter()
+        .filter_map(|name| resolve(name.as_str()))
+        .collect::<BTreeSet<_>>();
+
+    let mut nodes: BTreeMap<usize, Node> = BTreeMap::new();
+    let mut queue = VecDeque::new();
+
+    if max_depth > 0 {
+        queue.extend(roots.iter().map(|index| (*index, 1)));
+    }
+
+    // breadth first, every version is placed at the depth it's first reached at
+    while let Some((index, depth)) = queue.pop_front() {
+        if nodes.contains_key(&index) {
+            continue;
+        }
+
+        let lock = locks[index];
+        let dev = !production.contains(lock.install_name());
+        let collapsed = collapse_dev && dev && !lock.dependencies.is_empty();
+
+        let dependencies = match collapsed || depth >= max_depth {
+            true => BTreeSet::new(),
+            false => lock
+                .dependencies
+                .iter()
+                .filter_map(|name| resolve(name.as_str()))
+                .collect(),
+        };
+
+        queue.extend(
+            dependencies
+                .iter()
+                .map(|dependency| (*dependency, depth + 1)),
+        );
+
+        nodes.insert(
+            index,
+            Node {
+                depth,
+                dev,
+                collapsed,
+                dependencies,
+            },
+        );
+    }
+
+    let name = manifest["name"].as_str().unwrap_or("project");
+    let version = manifest["version"].as_str().unwrap_or("0.0.0");
+
+    let root = format!("{}@{}", name, version);
+    let id = |index: &usize| format!("{}@{}", locks[*index].name, locks[*index].version);
+
+    let mut data = String::new();
+
+    match app.args.value_of("format") {
+        Some("json") => {
+            let mut packages = Map::new();
+
+            let roots = match max_depth {
+                0 => vec![],
+                _ => roots.iter().map(id).collect(),
+            };
+
+            packages.insert(
+                root.clone(),
+                json!({ "name": name, "version": version, "depth": 0, "dependencies": roots }),
+            );
+
+            for (index, node) in nodes.iter() {
+                let mut entry = json!({
+                    "name": locks[*index].name,
+                    "version": locks[*index].version,
+                    "depth": node.depth,
+                    "dev": node.dev,
+                    "dependencies": node.dependencies.iter().map(id).collect::<Vec<_>>(),
+                });
+
+                if node.collapsed {
+                    entry["collapsed"] = json!(true);
+                }
+
+                packages.insert(id(index), entry);
+            }
+
+            data = serde_json::to_string_pretty(&json!({ "root": root, "packages": packages }))
+                .unwrap();
+            data.push('\n');
+        }
+        _ => {
+            writeln!(data, "digraph dependencies {{").unwrap();
+            writeln!(data, "  rankdir=LR;").unwrap();
+            writeln!(data, "  node [shape=box];").unwrap();
+            writeln!(data, "  {} [style=bold];", quoted(&root)).unwrap();
+
+            for (index, node) in nodes.iter() {
+                let style = match (node.dev, node.collapsed) {
+                    (true, true) => " [style=dashed, shape=box3d]",
+                    (true, false) => " [style=dashed]",
+                    (false, _) => "",
+                };
+
+                writeln!(data, "  {}{};", quoted(&id(index)), style).unwrap();
+            }
+
+            for index in roots.iter().filter(|index| nodes.contains_key(*index)) {
+                writeln!(data, "  {} -> {};", quoted(&root), quoted(&id(index))).unwrap();
+            }
+
+            for (index, node) in nodes.iter() {
+                for dependency in node.dependencies.iter() {
+                    writeln!(
+                        data,
+                        "  {} -> {};",
+                        quoted(&id(index)),
+                        quoted(&id(dependency))
+                    )
+                    .unwrap();
+                }
+            }
+
+            writeln!(data, "}}").unwrap();
+        }
+    }
+
+    let path = match app.args.value_of("output") {
+        Some(output) => app.current_dir.join(output),
+        None => {
+            print!("{}", data);
+            return Ok(());
+        }
+    };
+
+    std::fs::write(&path, data).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    println!(
+        "{}: exported {} versions to {}",
+        "success".bright_green(),
+        nodes.len(),
+        path.display().to_string().bright_cyan()
+    );
+
+    Ok(())
+}
+
+#[async_trait]
+impl Command for Graph {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Export the dependency graph of the lockfile.
+
+Usage: {} {} {} {}
+
+Commands:
+
+  {}      Print the graph as Graphviz DOT or JSON, or write it to a database.
+
+Options:
+
+  {} {}     `dot` (the default) or `json`, an adjacency list keyed by `name@version`.
+  {} {}     Path to write the graph to, it's printed without one.
+  {}           Leave out versions more than this many dependencies deep.
+  {}      Show dev dependencies without their own dependencies.
+  {}        Path of the SQLite database to write instead.
+  {} Don't ask the registry for security advisories.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "graph".bright_purple(),
+            "[export]".white(),
+            "[flags]".white(),
+            "export".bright_blue(),
+            "--format".blue(),
+            "(-f)".yellow(),
+            "--output".blue(),
+            "(-o)".yellow(),
+            "--depth <n>".blue(),
+            "--collapse-dev".blue(),
+            "--sqlite".blue(),
+            "--no-advisories".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt graph` command
+    ///
+    /// `volt graph` prints the graph from the project down as Graphviz DOT, `--format json`
+    /// as an adjacency list for other tools. `volt graph export --sqlite deps.db` writes
+    /// the locked packages, the edges between them, their installed size and license and
+    /// the advisories that apply to them into a SQLite database, for ad-hoc SQL over large
+    /// dependency graphs.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Export the dependency graph to deps.db
+    /// // .exec() is an async call so you need to await it
+    /// Graph.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        match app.args.value_of("sqlite") {
+            Some(path) => export_sqlite(&app, path).await,
+            None => export_graph(&app),
+        }
+    }
+}
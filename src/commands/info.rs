@@ -13,14 +13,27 @@
 
 //! Display info about a package.
 
+use crate::core::model::http_manager::{
+    get_dependents_count, get_full_document, get_full_packument, get_weekly_downloads, NetworkMode,
+};
+use crate::core::utils::compat::{
+    check_engines, check_peer_dependencies, Compatibility, Requirement,
+};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::npm::{parse_versions, resolve_version};
+use crate::core::utils::output;
+use crate::core::utils::package::{Packument, PackumentVersion};
 use crate::{core::VERSION, Command};
 
 use std::sync::Arc;
 
 use crate::App;
 use async_trait::async_trait;
+use chrono::DateTime;
 use colored::Colorize;
 use miette::Result;
+use node_semver::Version;
+use serde_json::Value;
 
 pub struct Info {}
 
@@ -30,17 +43,31 @@ impl Command for Info {
         format!(
             r#"volt {}
     
-Shows the information of a package 
+Shows the information of a package, how widely it's used and when its dist-tags were last
+published, and whether it fits the current project
 
-Usage: {} {} {}
+Usage: {} {} {} {}
+
+Fields:
+
+  {}     Check the `engines` of the package against the installed node.
+  {}        Check the peer dependencies of the package against node_modules.
+  {}      Print a field of the version, or of the package, e.g. `versions`,
+              `dist.tarball`, `dependencies.loose-envify` or `maintainers.name`.
 
 Options: 
 
+  {} Registry to fetch the package from instead of the configured one.
   {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
-            "deploy".bright_purple(),
-            "[commit]".white(),
+            "info".bright_purple(),
+            "<package>[@range]".white(),
+            "[field]".white(),
+            "engines".bright_blue(),
+            "peer".bright_blue(),
+            "<path>".bright_blue(),
+            "--registry <url>".bright_blue(),
             "--verbose".bright_blue(),
             "(-v)".yellow()
         )
@@ -48,9 +75,13 @@ Options:
 
     /// Execute the `volt info` command
     ///
-    /// Display info about a package
+    /// Display info about a package, with its weekly downloads and dependents when it's on the
+    /// public registry. With the `engines` or `peer` field only that part is checked against
+    /// the current project, and conflicts make the command fail, so it can be run before
+    /// `volt add` without modifying anything. Any other field is a dot path into the registry
+    /// document, printed plainly for scalars and as JSON otherwise.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
     /// // Display info about a package
@@ -60,239 +91,374 @@ Options:
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        // #[allow(unused_assignments)]
-        // let mut name = String::new();
-
-        // if !std::env::current_dir()
-        //     .unwrap()
-        //     .join("package.json")
-        //     .exists()
-        //     && app.args.len() == 1
-        // {
-        //     println!(
-        //         "{}: {}\n",
-        //         "warning".yellow().bold(),
-        //         "Could not find a package.json file in the current directory"
-        //     );
-        //     name = utils::get_basename(app.current_dir.to_str().unwrap()).to_string();
-        // }
-
-        // let mut field: String = String::new();
-
-        // if app.args.len() > 2 {
-        //     field = String::from(&app.args[2]);
-        //     name = String::from(&app.args[1]);
-        // } else if app.args.len() == 2 {
-        //     name = String::from(&app.args[1]);
-        // }
-
-        // let package: Package = get_package(&name).await.unwrap().unwrap();
-
-        // if field == String::new() {
-        //     let latest_version = package.dist_tags.get("latest").unwrap();
-        //     println!("{}\n", format!("v{}", latest_version).bright_blue());
-
-        //     if package.description != None {
-        //         println!("{}\n", package.description.unwrap());
-        //     }
-        //     if package.keywords != None {
-        //         print!("{}: ", "keywords".bright_blue().bold());
-        //         for keyword in package.keywords.unwrap().iter() {
-        //             print!("{} ", keyword.green())
-        //         }
-        //         print!("\n\n")
-        //     }
-
-        //     let latestpackage: &Version = &package.versions[latest_version];
-        //     println!("distribution:");
-        //     println!(
-        //         "  tarball: {}",
-        //         latestpackage.dist.tarball.bright_blue().underline()
-        //     );
-        //     println!("  shasum: {}", latestpackage.dist.shasum.bright_green());
-        //     if latestpackage.dist.integrity != "" {
-        //         println!(
-        //             "  integrity: {}",
-        //             latestpackage.dist.integrity.bright_blue()
-        //         );
-        //     }
-        //     if latestpackage.dist.unpacked_size != 0 {
-        //         println!(
-        //             "  unpackedSize: {}{}",
-        //             (latestpackage.dist.unpacked_size / 1024)
-        //                 .to_string()
-        //                 .bright_blue()
-        //                 .bold(),
-        //             "kb".bright_blue().bold()
-        //         );
-        //     }
-
-        //     let dependencies = latestpackage
-        //         .dependencies
-        //         .keys()
-        //         .cloned()
-        //         .collect::<Vec<String>>();
-
-        //     if dependencies.len() != 0 {
-        //         println!("\ndependencies:");
-        //         for dep in dependencies.iter() {
-        //             println!("{}{}", "  - ".bright_magenta(), dep);
-        //         }
-        //     }
-
-        //     println!("{}", "\nmaintainers:");
-        //     for maintainer in latestpackage.maintainers.iter() {
-        //         println!(
-        //             "  {} {}<{}>",
-        //             "-".bright_magenta(),
-        //             maintainer.email,
-        //             maintainer.name.yellow().bold()
-        //         )
-        //     }
-        //     print!("\n");
-        // } else {
-        //     match field.as_str() {
-        //         "readme" => {
-        //             let text: String;
-
-        //             if package.readme.is_some() && package.readme.as_ref().unwrap().trim() != "" {
-        //                 text = package.readme.unwrap();
-        //             } else {
-        //                 let latest_version = package.dist_tags.get("latest").unwrap();
-        //                 let current_version = package.versions.get(latest_version).unwrap();
-
-        //                 if current_version.readme.is_some() {
-        //                     text = current_version.readme.as_ref().unwrap().to_string();
-        //                 } else {
-        //                     text = String::new();
-        //                 }
-        //             }
-        //             if text != String::new() {
-        //                 PrettyPrinter::new()
-        //                     .input_from_bytes(text.as_bytes())
-        //                     .theme("Dracula")
-        //                     .language("markdown")
-        //                     .print()
-        //                     .unwrap();
-
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a readme for {}", name);
-        //             }
-        //         }
-        //         "version" => {
-        //             let mut table = Table::new();
-
-        //             let versions: HashMap<String, String> = package.dist_tags;
-
-        //             let mut labels: Vec<Cell> = vec![Cell::new("")];
-
-        //             let mut values: Vec<Cell> =
-        //                 vec![Cell::new(&name.bright_blue().to_string().as_str())];
-
-        //             for (k, v) in versions.iter() {
-        //                 labels.push(Cell::new(k.as_str()));
-        //                 values.push(Cell::new(v.bright_magenta().to_string().as_str()));
-        //             }
-
-        //             table.add_row(Row::new(labels));
-
-        //             table.add_row(Row::new(values));
-
-        //             table.printstd();
-        //             print!("\n");
-        //         }
-        //         "versions" => {
-        //             let versions = package.versions.keys().cloned().collect::<Vec<String>>();
-
-        //             println!("{}{}", "versions".bright_cyan(), ":".bright_magenta());
-        //             for v in versions {
-        //                 println!("  {} {}", "-".bright_magenta(), v.bright_green());
-        //             }
-        //             print!("\n");
-        //         }
-        //         "description" => {
-        //             let description = package.description;
-
-        //             if description.is_some() {
-        //                 println!("{}", description.unwrap());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a description for {}", name);
-        //             }
-        //         }
-        //         "name" => {
-        //             println!("{}", name);
-        //             print!("\n");
-        //         }
-        //         "maintainers" => {
-        //             for maintainer in package.maintainers.iter() {
-        //                 println!(
-        //                     "{} <{}>",
-        //                     maintainer.name.bright_green().bold(),
-        //                     maintainer.email.bright_magenta(),
-        //                 )
-        //             }
-        //             print!("\n");
-        //         }
-        //         "time" => {
-        //             let times = package.time;
-
-        //             for (version, time) in times {
-        //                 println!(
-        //                     "{} {} {}",
-        //                     version.bright_cyan(),
-        //                     ":".bright_magenta(),
-        //                     time.bright_black()
-        //                 );
-        //             }
-
-        //             print!("\n");
-        //         }
-        //         "repository" => {
-        //             let repo = package.repository;
-
-        //             if repo.is_some() {
-        //                 let data = repo.unwrap();
-
-        //                 println!("{}: {}", "provider".bright_yellow(), data.type_field);
-        //                 println!("{}: {}", "url".bright_purple(), data.url.underline());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a repository for {}", name);
-        //             }
-        //         }
-        //         "homepage" => {
-        //             let page = package.homepage;
-
-        //             if page.is_some() {
-        //                 let data = page.unwrap();
-
-        //                 println!("{}", data);
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a homepage for {}", name);
-        //             }
-        //         }
-        //         "keywords" => {
-        //             let keywords = package.keywords;
-        //             println!("{}{}", "keywords".bright_cyan(), ":".bright_cyan());
-        //             if keywords.is_some() {
-        //                 let keywords = keywords.unwrap();
-
-        //                 for kw in keywords {
-        //                     println!("{} {}", "-".bright_magenta(), kw);
-        //                 }
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find keywords for {}", name);
-        //             }
-        //         }
-        //         "users" => {}
-        //         &_ => {}
-        //     }
-        // }
+        let spec = app.args.value_of("package").unwrap();
+
+        let package = parse_versions(&vec![spec.to_string()])?.remove(0);
+
+        let packument = get_full_packument(&app.registries, &package.name).await?;
+
+        let version = resolve_version(&packument, package.version.as_deref().unwrap_or(""))?;
+
+        let field = app.args.value_of("field");
+
+        let requirements = match field {
+            Some("engines") => check_engines(version),
+            Some("peer") => check_peer_dependencies(&app, version),
+            Some(path) => {
+                let document = get_full_document(&app.registries, &package.name).await?;
+
+                let value = lookup(&view(&document, &version.version), path).ok_or_else(|| {
+                    VoltError::MissingInfoField {
+                        package: format!("{}@{}", version.name, version.version),
+                        field: path.to_string(),
+                    }
+                })?;
+
+                if app.json() {
+                    output::set("value", &value);
+                    return Ok(());
+                }
+
+                match &value {
+                    Value::String(text) => println!("{}", text),
+                    Value::Object(_) | Value::Array(_) => println!("{}", highlight(&value, 0)),
+                    scalar => println!("{}", scalar),
+                }
+
+                return Ok(());
+            }
+            None => {
+                // downloads and dependents are only counted for the public registry
+                let (downloads, dependents) = if app.registries.network != NetworkMode::Offline
+                    && app.registries.is_public(&package.name)
+                {
+                    futures::join!(
+                        get_weekly_downloads(&package.name),
+                        get_dependents_count(&package.name)
+                    )
+                } else {
+                    (None, None)
+                };
+
+                print_summary(&packument, version);
+                print_usage(downloads, dependents);
+                print_dist_tags(&packument);
+                print_maintainers(&packument);
+
+                print_requirements("engines", &check_engines(version));
+                print_requirements("peer dependencies", &check_peer_dependencies(&app, version));
+
+                return Ok(());
+            }
+        };
+
+        print_requirements(field.unwrap(), &requirements);
+
+        let conflicts = requirements
+            .iter()
+            .filter(|requirement| matches!(requirement.status, Compatibility::Conflict(_)))
+            .count();
+
+        if conflicts > 0 {
+            return Err(VoltError::IncompatiblePackage {
+                name: format!("{}@{}", version.name, version.version),
+                count: conflicts,
+            }
+            .into());
+        }
 
         Ok(())
     }
 }
+
+fn print_summary(packument: &Packument, version: &PackumentVersion) {
+    println!(
+        "{}@{}",
+        version.name.bright_cyan().bold(),
+        version.version.bright_yellow()
+    );
+
+    if let Some(description) = version
+        .description
+        .as_ref()
+        .or(packument.description.as_ref())
+    {
+        println!("  {}", description);
+    }
+
+    println!();
+
+    if let Some(license) = packument
+        .license
+        .as_ref()
+        .and_then(|license| license.expression())
+    {
+        println!("  license: {}", license.bright_green());
+    }
+
+    if let Some(author) = &packument.author {
+        println!("  author: {}", author);
+    }
+
+    if let Some(repository) = packument
+        .repository
+        .as_ref()
+        .and_then(|repository| repository.url())
+    {
+        println!("  repository: {}", repository.bright_blue().underline());
+    }
+
+    if let Some(homepage) = version.homepage.as_ref().or(packument.homepage.as_ref()) {
+        println!("  homepage: {}", homepage.bright_blue().underline());
+    }
+
+    if let Some(published) = packument.time.get(&version.version) {
+        println!("  published: {}", date(published));
+    }
+
+    if let (Some(created), Some(modified)) = (&packument.time.created, &packument.time.modified) {
+        println!("  created: {}, modified: {}", date(created), date(modified));
+    }
+
+    println!(
+        "  versions: {}, stars: {}",
+        packument.versions.len(),
+        packument.stars()
+    );
+
+    println!(
+        "  tarball: {}",
+        version.dist.tarball.bright_blue().underline()
+    );
+
+    if !version.dist.integrity.is_empty() {
+        println!("  integrity: {}", version.dist.integrity.bright_blue());
+    }
+
+    let mut dependencies = version.dependencies.iter().collect::<Vec<_>>();
+    dependencies.sort();
+
+    if !dependencies.is_empty() {
+        println!("\ndependencies:");
+
+        for (name, range) in dependencies {
+            println!(
+                "{}{} {}",
+                "  - ".bright_magenta(),
+                name,
+                range.bright_black()
+            );
+        }
+    }
+}
+
+/// Downloads over the last week and the number of dependents, a rough sign of how widely used
+/// and looked after a package is.
+fn print_usage(downloads: Option<u64>, dependents: Option<u64>) {
+    let mut usage = vec![];
+
+    if let Some(downloads) = downloads {
+        usage.push(format!(
+            "weekly downloads: {}",
+            thousands(downloads).bright_green()
+        ));
+    }
+
+    if let Some(dependents) = dependents {
+        usage.push(format!(
+            "dependents: {}",
+            thousands(dependents).bright_green()
+        ));
+    }
+
+    if !usage.is_empty() {
+        println!("  {}", usage.join(", "));
+    }
+}
+
+/// Every dist-tag with its version and when that was published, `latest` first.
+fn print_dist_tags(packument: &Packument) {
+    let mut tags = packument.dist_tags.iter().collect::<Vec<_>>();
+    tags.sort_by_key(|(tag, _)| (*tag != "latest", tag.to_string()));
+
+    if tags.is_empty() {
+        return;
+    }
+
+    println!("\ndist-tags:");
+
+    for (tag, version) in tags {
+        let published = packument
+            .time
+            .get(version)
+            .map(|time| format!(", published {}", date(time)))
+            .unwrap_or_default();
+
+        println!(
+            "{}{}: {}{}",
+            "  - ".bright_magenta(),
+            tag,
+            version.bright_green(),
+            published.bright_black()
+        );
+    }
+}
+
+/// `1234567` as `1,234,567`.
+fn thousands(number: u64) -> String {
+    let digits = number.to_string();
+    let mut grouped = String::new();
+
+    // a group starts every third digit, counted from the end
+    let offset = digits.len() % 3;
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && index % 3 == offset {
+            grouped.push(',');
+        }
+
+        grouped.push(digit);
+    }
+
+    grouped
+}
+
+/// The fields a path is looked up in: those of the version shown, then those of the package,
+/// with `versions` listing every published version like npm does.
+fn view(document: &Value, version: &str) -> Value {
+    let mut view = document["versions"][version]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+
+    if let Some(package) = document.as_object() {
+        for (key, value) in package.iter().filter(|(key, _)| *key != "versions") {
+            view.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    let mut versions = document["versions"]
+        .as_object()
+        .map(|versions| versions.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    versions.sort_by_key(|version| Version::parse(version).ok());
+
+    view.insert(String::from("versions"), Value::from(versions));
+
+    Value::Object(view)
+}
+
+/// The value at a dot path like `dist.tarball`. On an array a segment is an index, or a field
+/// read from every element like `maintainers.name`.
+fn lookup(value: &Value, path: &str) -> Option<Value> {
+    let segments = path.split('.').collect::<Vec<_>>();
+
+    lookup_segments(value, &segments)
+}
+
+fn lookup_segments(value: &Value, segments: &[&str]) -> Option<Value> {
+    if segments.is_empty() {
+        return Some(value.clone());
+    }
+
+    match value {
+        // keys can have dots themselves, `dependencies.lodash.merge`, the longest one wins
+        Value::Object(map) => (1..=segments.len()).rev().find_map(|length| {
+            let child = map.get(&segments[..length].join("."))?;
+
+            lookup_segments(child, &segments[length..])
+        }),
+        Value::Array(items) => match segments[0].parse::<usize>() {
+            Ok(index) => lookup_segments(items.get(index)?, &segments[1..]),
+            Err(_) => Some(Value::Array(
+                items
+                    .iter()
+                    .filter_map(|item| lookup_segments(item, segments))
+                    .collect(),
+            )),
+        },
+        _ => None,
+    }
+}
+
+/// `value` as indented JSON with keys, strings, numbers and literals colored apart.
+fn highlight(value: &Value, depth: usize) -> String {
+    let indent = "  ".repeat(depth + 1);
+    let closing = "  ".repeat(depth);
+
+    match value {
+        Value::Object(map) if map.is_empty() => String::from("{}"),
+        Value::Array(items) if items.is_empty() => String::from("[]"),
+        Value::Object(map) => {
+            let entries = map
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}{}: {}",
+                        indent,
+                        Value::String(key.clone()).to_string().bright_blue(),
+                        highlight(value, depth + 1)
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            format!("{{\n{}\n{}}}", entries.join(",\n"), closing)
+        }
+        Value::Array(items) => {
+            let entries = items
+                .iter()
+                .map(|item| format!("{}{}", indent, highlight(item, depth + 1)))
+                .collect::<Vec<_>>();
+
+            format!("[\n{}\n{}]", entries.join(",\n"), closing)
+        }
+        Value::String(_) => value.to_string().bright_green().to_string(),
+        Value::Number(_) => value.to_string().bright_yellow().to_string(),
+        Value::Bool(_) | Value::Null => value.to_string().bright_magenta().to_string(),
+    }
+}
+
+/// The day of an RFC 3339 timestamp, the timestamp itself when it doesn't parse.
+fn date(time: &str) -> String {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+fn print_maintainers(packument: &Packument) {
+    if packument.maintainers.is_empty() {
+        return;
+    }
+
+    println!("\nmaintainers:");
+
+    for maintainer in packument.maintainers.iter() {
+        println!("{}{}", "  - ".bright_magenta(), maintainer);
+    }
+}
+
+fn print_requirements(title: &str, requirements: &[Requirement]) {
+    if requirements.is_empty() {
+        return;
+    }
+
+    println!("\n{}:", title);
+
+    for requirement in requirements.iter() {
+        let status = match &requirement.status {
+            Compatibility::Satisfied(found) => format!("ok ({})", found).bright_green(),
+            Compatibility::Conflict(found) => format!("conflict ({})", found).bright_red(),
+            Compatibility::Missing => "missing".bright_yellow(),
+            Compatibility::Unchecked => "not checked".bright_black(),
+        };
+
+        println!(
+            "{}{} {} {}",
+            "  - ".bright_magenta(),
+            requirement.name,
+            requirement.range.bright_black(),
+            status
+        );
+    }
+}
@@ -20,12 +20,10 @@ use crate::core::prompt::prompts::Confirm;
 use crate::core::prompt::prompts::Input;
 use crate::core::prompt::prompts::Select;
 use crate::core::utils;
+use crate::core::utils::errors::VoltError;
 use crate::core::VERSION;
-use crate::error;
 
 use std::env;
-use std::fs::File;
-use std::io::Write;
 use std::sync::Arc;
 
 use crate::App;
@@ -80,7 +78,7 @@ Options:
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        let temp = utils::get_basename(&env::current_dir().unwrap().to_string_lossy()).to_string();
+        let temp = utils::get_basename(&app.current_dir.to_string_lossy()).to_string();
         let split: Vec<&str> = temp.split('\\').collect::<Vec<&str>>();
         let cwd: String = split[split.len() - 1].to_string();
         let data = if app.has_flag("yes") {
@@ -137,10 +135,7 @@ Options:
             };
 
             let mut name;
-            name = input.run().unwrap_or_else(|err| {
-                eprintln!("{}", err);
-                std::process::exit(1);
-            });
+            name = input.run().map_err(VoltError::PromptError)?;
 
             let re_name =
                 Regex::new("^(?:@[a-z0-9-*~][a-z0-9-*._~]*/)?[a-z0-9-~][a-z0-9-._~]*$").unwrap();
@@ -158,10 +153,7 @@ Options:
                         allow_empty: false,
                     };
 
-                    name = input.run().unwrap_or_else(|err| {
-                        eprintln!("{}", err);
-                        std::process::exit(1);
-                    });
+                    name = input.run().map_err(VoltError::PromptError)?;
 
                     if re_name.is_match(&name) {
                         break;
@@ -178,10 +170,7 @@ Options:
                 allow_empty: false,
             };
 
-            let version = input.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let version = input.run().map_err(VoltError::PromptError)?;
 
             // Get "description"
             let input: Input = Input {
@@ -190,10 +179,7 @@ Options:
                 allow_empty: true,
             };
 
-            let description = input.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let description = input.run().map_err(VoltError::PromptError)?;
 
             // Get "main"
             let input: Input = Input {
@@ -202,10 +188,7 @@ Options:
                 allow_empty: false,
             };
 
-            let main = input.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let main = input.run().map_err(VoltError::PromptError)?;
 
             // Get "author"
             let git_user_name =
@@ -225,20 +208,14 @@ Options:
                     allow_empty: true,
                 };
 
-                author = input.run().unwrap_or_else(|err| {
-                    error!("{}", err.to_string());
-                    std::process::exit(1);
-                });
+                author = input.run().map_err(VoltError::PromptError)?;
             } else {
                 let input: Input = Input {
                     message: String::from("author"),
                     default: None,
                     allow_empty: true,
                 };
-                author = input.run().unwrap_or_else(|err| {
-                    error!("{}", err.to_string());
-                    std::process::exit(1);
-                });
+                author = input.run().map_err(VoltError::PromptError)?;
             }
 
             // Get "repository"
@@ -248,10 +225,7 @@ Options:
                 allow_empty: true,
             };
 
-            let repository = input.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let repository = input.run().map_err(VoltError::PromptError)?;
 
             let licenses: Vec<String> = License::options();
 
@@ -262,22 +236,16 @@ Options:
                 items: licenses,
             };
 
-            select.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let selected = select.run().map_err(VoltError::PromptError)?;
 
-            let license = License::from_index(select.selected.unwrap()).unwrap();
+            let license = License::from_index(selected).unwrap_or_default();
 
             let input = Confirm {
                 message: String::from("private"),
                 default: false,
             };
 
-            let private = input.run().unwrap_or_else(|err| {
-                error!("{}", err.to_string());
-                std::process::exit(1);
-            });
+            let private = input.run().map_err(VoltError::PromptError)?;
 
             InitData {
                 name,
@@ -291,15 +259,12 @@ Options:
             }
         };
 
-        let mut file = File::create(r"package.json").unwrap();
-        if let Err(error) = file.write(data.dump().as_bytes()) {
-            error!(
-                "{} {}",
-                "Failed to create package.json -",
-                error.to_string().bright_yellow().bold()
-            );
-            std::process::exit(1);
-        }
+        let path = app.current_dir.join("package.json");
+
+        std::fs::write(&path, data.dump()).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
 
         println!("{}", "Successfully Initialized package.json".bright_green());
         Ok(())
@@ -15,18 +15,155 @@
 */
 
 //! Installs dependencies for a project.
+//!
+//! Inside a workspace every member's dependencies are resolved together into the lockfile at
+//! the workspace root, installed into the root node_modules, and members depending on each
+//! other are linked instead of fetched. The top of node_modules gets the first range declared
+//! of every package, the root's first, and a member asking for a version it doesn't allow
+//! gets that one in its own node_modules. Dependencies on `file:` or `link:` directories, like
+//! the ones `volt vendor` writes, are linked the same way and their own dependencies installed.
+//!
+//! Linked packages never go through the store: there is no tarball to verify or extract, the
+//! symlink sees every edit as it's made. `--watch` keeps volt running after the install and
+//! installs again when the dependencies declared by the root, a member or a linked package
+//! change, while registry packages are still verified against volt.lock each time.
 
-use crate::{core::VERSION, App, Command};
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::constants::WATCH_INTERVAL;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::extensions::{apply_extensions, record_extensions};
+use crate::core::utils::fallbacks::apply_native_fallbacks;
+use crate::core::utils::git::{fetch_git_package, install_git_package, parse_git_spec, GitSpec};
+use crate::core::utils::insight::{confirm_install_scripts, new_install_scripts};
+use crate::core::utils::install_scripts::{self, PROJECT_EVENTS};
+use crate::core::utils::npm::{parse_versions, resolve_peer_dependencies};
+use crate::core::utils::output;
+use crate::core::utils::overrides::{apply_overrides, load_overrides, record_overrides};
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::package_lock::{import_package_lock, pinned_versions};
+use crate::core::utils::platform::resolve_platforms;
+use crate::core::utils::progress::InstallProgress;
+use crate::core::utils::signing::{check_lockfile, sign_install};
+use crate::core::utils::voltapi::VoltPackage;
+use crate::core::utils::workspace::{
+    link_workspace_package, local_package, local_path, resolve_workspace_spec, workspace_packages,
+    workspace_range, workspace_root,
+};
+use crate::core::utils::zero_install;
+use crate::core::utils::{fetch_dep_tree, install_packages, satisfies};
+use crate::core::utils::{lock_entry, lock_id, locked_tree, locked_version};
+use crate::core::utils::{optional_package_names, print_elapsed};
+use crate::{commands::add::Package, core::VERSION, App, Command};
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use colored::Colorize;
 use miette::Result;
+use tracing::{error, info, warn};
 
 /// Struct implementation for the `Install` command.
 pub struct Install;
 
+/// Every dependency declared by the package in `directory`, dev and optional ones included.
+fn declared_dependencies(directory: &Path) -> Result<Vec<(String, String)>> {
+    let manifest = PackageJson::read(&directory.join("package.json"))?;
+
+    let mut dependencies = manifest
+        .dependencies
+        .into_iter()
+        .chain(manifest.dev_dependencies)
+        .chain(manifest.optional_dependencies)
+        .collect::<Vec<_>>();
+
+    dependencies.sort();
+
+    Ok(dependencies)
+}
+
+/// The dependencies declared by every package an install from `directory` reads, by their
+/// directory: the root, the workspace members and the `file:` or `link:` packages.
+fn declarations(directory: &Path) -> Result<BTreeMap<PathBuf, Vec<(String, String)>>> {
+    let (root, members) = match workspace_root(directory) {
+        Some(root) => {
+            let members = workspace_packages(&root)?;
+            (root, members)
+        }
+        None => (directory.to_path_buf(), vec![]),
+    };
+
+    let mut pending = std::iter::once(root)
+        .chain(members.into_iter().map(|member| member.path))
+        .collect::<Vec<_>>();
+
+    let mut declarations = BTreeMap::new();
+
+    while let Some(directory) = pending.pop() {
+        if declarations.contains_key(&directory) {
+            continue;
+        }
+
+        let dependencies = declared_dependencies(&directory)?;
+
+        for (name, spec) in dependencies.iter() {
+            if let Some(local) = local_package(&directory, name, spec) {
+                pending.push(local.path);
+            }
+        }
+
+        declarations.insert(directory, dependencies);
+    }
+
+    Ok(declarations)
+}
+
+/// Install again whenever the declared dependencies of the project change, until interrupted.
+async fn watch(app: Arc<App>, frozen: bool) -> Result<()> {
+    let mut last = declarations(&app.current_dir)?;
+
+    info!(
+        "watching {} package.json files, edits to linked packages need no install",
+        last.len()
+    );
+
+    loop {
+        tokio::time::sleep(Duration::from_millis(WATCH_INTERVAL)).await;
+
+        // a manifest being saved may not parse yet, it's read again on the next check
+        let current = match declarations(&app.current_dir) {
+            Ok(current) => current,
+            Err(_) => continue,
+        };
+
+        if current == last {
+            continue;
+        }
+
+        let changed = current
+            .keys()
+            .chain(last.keys())
+            .filter(|directory| current.get(*directory) != last.get(*directory))
+            .collect::<BTreeSet<_>>();
+
+        for directory in changed {
+            info!(
+                "{} changed, installing",
+                directory.join("package.json").display()
+            );
+        }
+
+        // a failed install is reported and waits for the next change
+        if let Err(error) = app.within_deadline(install(app.clone(), frozen)).await {
+            error!("{:?}", error);
+        }
+
+        last = current;
+    }
+}
+
 #[async_trait]
 impl Command for Install {
     /// Display a help menu for the `volt install` command.
@@ -34,13 +171,27 @@ impl Command for Install {
         format!(
             r#"volt {}
         
-Install dependencies for a project.
+Install dependencies for a project, or for every package of a workspace.
 
 Usage: {} {} {}
     
 Options: 
     
   {} {} Accept all prompts while installing dependencies.  
+  {} Don't run the install scripts of dependencies or of the project.
+  {} Fail when node doesn't satisfy the `engines` of a package.
+  {} Only install versions published before the given date.
+  {} {} Only install the workspace packages matching the filter.
+  {} Fail instead of updating volt.lock when it doesn't match package.json.
+  {} Replace files in node_modules that volt didn't create.
+  {} Use less memory at the cost of speed, for small containers.
+  {} Downloads and extractions run at once, a multiple of the CPU count by default.
+  {} Log every package on a line instead of drawing progress bars.
+  {} Resolve from the metadata cache and install from the store only.
+  {} Only ask the registry for packages missing from the cache.
+  {} Registry to install from instead of the configured one.
+  {} Keep running and install again when declared dependencies change.
+  {} Print the resolved packages as a JSON document instead of text.
   {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
@@ -48,6 +199,21 @@ Options:
             "[flags]".white(),
             "--yes".blue(),
             "(-y)".yellow(),
+            "--ignore-scripts".blue(),
+            "--engine-strict".blue(),
+            "--before".blue(),
+            "--filter".blue(),
+            "(-F)".yellow(),
+            "--frozen-lockfile".blue(),
+            "--force-overwrite".blue(),
+            "--low-memory".blue(),
+            "--concurrency <n>".blue(),
+            "--no-progress".blue(),
+            "--offline".blue(),
+            "--prefer-offline".blue(),
+            "--registry <url>".blue(),
+            "--watch".blue(),
+            "--json".blue(),
             "--verbose".blue(),
             "(-v)".yellow()
         )
@@ -55,21 +221,535 @@ Options:
 
     /// Execute the `volt install` command
     ///
-    /// Install dependencies for a project.
+    /// Install dependencies for a project. Inside a workspace, the dependencies of every
+    /// package matched by the root `workspaces` field are installed into the root, or of
+    /// the ones selected with `--filter`.
     /// ## Arguments
     /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
     /// ## Examples
     /// ```
     /// // Install dependencies for a project with logging level verbose
     /// // .exec() is an async call so you need to await it
-    /// Install.exec(app, vec![], vec!["--verbose"]).await;
+    /// Install.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
-        // let package_file
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let frozen = app.has_flag("frozen-lockfile");
+
+        app.within_deadline(install(app.clone(), frozen)).await?;
+
+        if app.has_flag("watch") {
+            watch(app, frozen).await?;
+        }
+
         Ok(())
     }
 }
+
+/// Fail with `diff`, the changes to volt.lock a frozen install would need, unless it's empty.
+fn check_frozen(mut diff: Vec<String>) -> Result<()> {
+    if diff.is_empty() {
+        return Ok(());
+    }
+
+    diff.sort_by(|a, b| a[2..].cmp(&b[2..]));
+
+    Err(VoltError::LockfileOutdated {
+        count: diff.len(),
+        diff: diff.iter().map(|line| format!("\n  {}", line)).collect(),
+    }
+    .into())
+}
+
+/// Install the dependencies of the project or workspace `app` runs in.
+///
+/// While volt.lock pins a version for every range of package.json, the tree is the one it
+/// recorded, dependencies of dependencies included, and only resolved again once it doesn't.
+/// With `frozen` the lockfile has to describe the install already, any difference fails with
+/// a diff before anything is installed and the lockfile is never written.
+pub async fn install(app: Arc<App>, frozen: bool) -> Result<()> {
+    // filters are relative to where volt runs, not to the workspace root
+    let selected = app.filtered_packages()?;
+
+    let (root, members) = match workspace_root(&app.current_dir) {
+        Some(root) => {
+            let members = workspace_packages(&root)?;
+            (root, members)
+        }
+        None => (app.current_dir.clone(), vec![]),
+    };
+
+    let app = Arc::new(app.in_directory(&root)?);
+
+    if !members.is_empty() {
+        info!(
+            "installing {} workspace packages from {}",
+            members.len(),
+            root.display()
+        );
+    }
+
+    // (name, spec, the package declaring it), the root first
+    let mut declared: Vec<(String, String, PathBuf)> = vec![];
+
+    let directories = match &selected {
+        Some(selected) => selected.iter().map(|m| m.path.clone()).collect(),
+        None => std::iter::once(root.clone())
+            .chain(members.iter().map(|m| m.path.clone()))
+            .collect::<Vec<_>>(),
+    };
+
+    for directory in directories.iter() {
+        for (name, spec) in declared_dependencies(directory)? {
+            declared.push((name, spec, directory.clone()));
+        }
+    }
+
+    // `file:` directories are linked like members, what they depend on is installed
+    let mut locals = vec![];
+
+    for (name, spec, origin) in declared.clone() {
+        if let Some(local) = local_package(&origin, &name, &spec) {
+            let manifest = PackageJson::read(&local.path.join("package.json"))?;
+
+            for (name, spec) in manifest
+                .dependencies
+                .into_iter()
+                .chain(manifest.optional_dependencies)
+            {
+                declared.push((name, spec, local.path.clone()));
+            }
+
+            locals.push(local);
+        }
+    }
+
+    // the range of every name at the top of node_modules, the first one declared
+    let mut ranges: BTreeMap<String, String> = BTreeMap::new();
+    // every range of every package declaring it, the ones disagreeing with the top get
+    // their version next to the package
+    let mut own_ranges: BTreeMap<PathBuf, BTreeMap<String, String>> = BTreeMap::new();
+    let mut git_specs: BTreeMap<String, GitSpec> = BTreeMap::new();
+
+    for (name, spec, origin) in declared.iter() {
+        // `workspace:` ranges never leave the workspace
+        if workspace_range(spec).is_some() {
+            resolve_workspace_spec(name, spec, &members)?;
+            continue;
+        }
+
+        if local_path(spec).is_some() && locals.iter().any(|local| &local.name == name) {
+            continue;
+        }
+
+        let member = members.iter().find(|member| {
+            &member.name == name && (spec == "*" || satisfies(&member.version, spec))
+        });
+
+        if member.is_some() {
+            continue;
+        }
+
+        if let Some(spec) = parse_git_spec(spec) {
+            git_specs.entry(name.clone()).or_insert(spec);
+            continue;
+        }
+
+        if parse_versions(&vec![format!("{}@{}", name, spec)]).is_err() {
+            warn!(
+                "skipping {}@{} from {}, add it with `volt add` instead",
+                name.bright_cyan(),
+                spec,
+                origin.join("package.json").display()
+            );
+
+            continue;
+        }
+
+        ranges.entry(name.clone()).or_insert_with(|| spec.clone());
+
+        own_ranges
+            .entry(origin.clone())
+            .or_default()
+            .insert(name.clone(), spec.clone());
+    }
+
+    // the top is the root's, copies the others got earlier go once they agree with it
+    own_ranges.remove(&root);
+
+    let specs = ranges
+        .iter()
+        .chain(own_ranges.values().flatten())
+        .collect::<BTreeSet<_>>();
+
+    // a tampered lockfile must not decide what gets downloaded
+    check_lockfile(&app)?;
+
+    // moving from npm keeps the tree it pinned, a frozen install only trusts volt.lock
+    let imported = match app.lock_file_path.exists() || frozen {
+        true => None,
+        false => import_package_lock(&root, &app.lock_file_path)?,
+    };
+
+    let lock_file = match imported {
+        Some(lock_file) => {
+            info!(
+                "importing {} packages from package-lock.json",
+                lock_file.dependencies.len()
+            );
+
+            lock_file
+        }
+        None => LockFile::load_or_new(&app.lock_file_path)?,
+    };
+
+    let pins = match app.lock_file_path.exists() {
+        true => vec![],
+        false => pinned_versions(&lock_file),
+    };
+
+    let mut packages = vec![];
+    let mut unlocked = vec![];
+    // what every range was resolved to, the locked version when there is one
+    let mut resolved: HashMap<(&String, &String), String> = HashMap::new();
+
+    for (name, spec) in specs {
+        let mut package: Package = parse_versions(&vec![format!("{}@{}", name, spec)])?.remove(0);
+
+        // the lockfile pins whatever it already resolved
+        match locked_version(&lock_file, &package) {
+            Some(version) => package.version = Some(version),
+            // an override may force a version outside of the declared range
+            None if lock_file
+                .dependencies
+                .values()
+                .any(|lock| &lock.name == name && lock.overridden.is_some()) => {}
+            None => unlocked.push(format!("+ {}@{}", name, spec)),
+        }
+
+        resolved.insert((name, spec), package.version.clone().unwrap_or_default());
+
+        packages.push(package);
+    }
+
+    // git dependencies install at the commit volt.lock pins
+    let git_specs = git_specs
+        .into_iter()
+        .map(|(name, spec)| {
+            let locked = lock_file
+                .dependencies
+                .values()
+                .filter(|lock| lock.name == name)
+                .find_map(|lock| Some((lock.clone(), spec.locked_commit(lock)?)));
+
+            if locked.is_none() {
+                unlocked.push(format!("+ {}@{}", name, spec.spec));
+            }
+
+            (spec, locked)
+        })
+        .collect::<Vec<_>>();
+
+    let all_locked = unlocked.is_empty();
+
+    // fail before resolving anything when package.json already disagrees
+    if frozen {
+        check_frozen(unlocked)?;
+    }
+
+    // their own dependencies come from the registry
+    let mut git_packages = vec![];
+
+    for (spec, locked) in git_specs {
+        let package = match &locked {
+            Some((lock, commit)) => {
+                let package = fetch_git_package(&app, &spec.at(commit))?;
+                package.check_integrity(lock)?;
+                package
+            }
+            None => fetch_git_package(&app, &spec)?,
+        };
+
+        let mut ranges = package.dependencies.iter().collect::<Vec<_>>();
+        ranges.sort();
+
+        for (name, range) in ranges {
+            if packages.iter().any(|p| &p.name == name) {
+                continue;
+            }
+
+            match parse_versions(&vec![format!("{}@{}", name, range)]) {
+                Ok(mut parsed) => {
+                    for dependency in parsed.iter_mut() {
+                        if let Some(version) = locked_version(&lock_file, dependency) {
+                            dependency.version = Some(version);
+                        }
+                    }
+
+                    packages.extend(parsed);
+                }
+                Err(_) => warn!(
+                    "skipping {}@{}, only registry dependencies of git packages are installed",
+                    name.bright_cyan(),
+                    range
+                ),
+            }
+        }
+
+        // an entry that's still valid is kept as is, whatever `prepare` built this time
+        let lock = match locked {
+            Some((lock, _)) => lock,
+            None => package.lock(),
+        };
+
+        git_packages.push((package, lock));
+    }
+
+    // a zero-install cache holding everything volt.lock pins installs without any registry
+    let cached = match all_locked {
+        true => zero_install::locked_tree(&app, &lock_file, &packages),
+        false => None,
+    };
+
+    // while package.json agrees with volt.lock, the versions it pins all the way down are
+    // installed instead of whatever the registry has now
+    let locked = match all_locked && cached.is_none() {
+        true => locked_tree(&lock_file, &packages, |lock| {
+            lock.manifest.as_ref()?;
+
+            Some(zero_install::locked_package(lock))
+        }),
+        false => None,
+    };
+
+    let before = app.before()?;
+
+    // only the root package.json can override, like with npm
+    let overrides = load_overrides(&root)?;
+
+    let progress = InstallProgress::new(&app, "Resolving Dependencies", packages.len());
+
+    let (mut dependencies, elapsed) = if packages.is_empty() {
+        (HashMap::new(), 0.0)
+    } else if let Some(cached) = cached {
+        info!(
+            "installing the {} packages of volt.lock from {}",
+            cached.len(),
+            zero_install::cache_dir(&app.current_dir).display()
+        );
+
+        (cached, 0.0)
+    } else if let Some(locked) = locked {
+        info!("installing the {} packages volt.lock pins", locked.len());
+
+        (locked, 0.0)
+    } else {
+        fetch_dep_tree(
+            &packages,
+            &app.registries,
+            before.as_ref(),
+            app.concurrency(),
+            &progress,
+        )
+        .await?
+    };
+
+    // trees from the CDN carry the metadata as published, extensions fix it up
+    apply_extensions(&app, &mut dependencies, before.as_ref()).await?;
+
+    apply_overrides(
+        &mut dependencies,
+        &overrides,
+        &app.registries,
+        before.as_ref(),
+    )
+    .await?;
+
+    // overrides beat whatever npm had pinned
+    let pins = pins
+        .into_iter()
+        .filter(|pin| !overrides.iter().any(|o| o.name == pin.name))
+        .collect::<Vec<_>>();
+
+    apply_overrides(&mut dependencies, &pins, &app.registries, before.as_ref()).await?;
+
+    resolve_peer_dependencies(
+        &app,
+        &mut dependencies,
+        app.config.auto_install_peers,
+        before.as_ref(),
+    )
+    .await?;
+
+    // workspace members and `file:` directories are never fetched, even when something
+    // deeper depends on them
+    dependencies.retain(|_, package| {
+        !members
+            .iter()
+            .chain(locals.iter())
+            .any(|m| m.name == package.name)
+    });
+
+    // native packages without a build for this platform make way for their pure JS build
+    apply_native_fallbacks(&app, &mut dependencies, before.as_ref()).await?;
+
+    progress.finish(&"[OK]".bright_green().to_string());
+
+    if !packages.is_empty() {
+        print_elapsed(dependencies.len(), elapsed);
+    }
+
+    let optional_packages = optional_package_names(&dependencies, &packages);
+
+    // builds for other platforms are recorded in the lockfile, but not installed
+    resolve_platforms(&app, &mut dependencies, &optional_packages).await;
+
+    // scripts showing up in the tree are a common way in for malicious code
+    let introduced = new_install_scripts(&lock_file, &dependencies.values().collect::<Vec<_>>());
+
+    confirm_install_scripts(&app, &introduced)?;
+
+    let locked = lock_file.dependencies.clone();
+
+    // the lockfile describes exactly this install, entries nothing needs anymore are dropped,
+    // unless only some of the workspace was installed
+    let mut lock_file = match selected {
+        Some(_) => lock_file,
+        None => LockFile {
+            meta: lock_file.meta,
+            ..LockFile::new(&app.lock_file_path)
+        },
+    };
+
+    let mut dependencies = dependencies.values().collect::<Vec<&VoltPackage>>();
+
+    dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    output::resolved(dependencies.iter().copied());
+
+    for package in dependencies.iter() {
+        lock_file.dependencies.insert(
+            lock_id(package),
+            lock_entry(package, optional_packages.contains(&package.name)),
+        );
+    }
+
+    for (package, lock) in git_packages.iter() {
+        lock_file
+            .dependencies
+            .insert(package.lock_id(), lock.clone());
+    }
+
+    if frozen {
+        let mut diff = vec![];
+
+        for (id, lock) in lock_file.dependencies.iter() {
+            match locked.get(id) {
+                Some(old) if old.integrity != lock.integrity => {
+                    diff.push(format!("~ {}@{} (integrity changed)", id.0, id.1))
+                }
+                Some(_) => {}
+                None => diff.push(format!("+ {}@{}", id.0, id.1)),
+            }
+        }
+
+        for id in locked.keys() {
+            if !lock_file.dependencies.contains_key(id) {
+                diff.push(format!("- {}@{}", id.0, id.1));
+            }
+        }
+
+        check_frozen(diff)?;
+    }
+
+    let direct = ranges
+        .iter()
+        .map(|(name, spec)| (name.clone(), resolved[&(name, spec)].clone()))
+        .collect::<HashMap<_, _>>();
+
+    let own = own_ranges
+        .iter()
+        .map(|(origin, own)| {
+            let own = own
+                .iter()
+                .map(|(name, spec)| (name.clone(), resolved[&(name, spec)].clone()))
+                .collect::<HashMap<_, _>>();
+
+            (origin.join("node_modules"), own)
+        })
+        .collect::<Vec<_>>();
+
+    install_scripts::run_project(&app, &root, &["preinstall"])?;
+
+    install_packages(&app, &dependencies, &optional_packages, &direct, &own).await?;
+
+    for (package, _) in git_packages.iter() {
+        install_git_package(&app, package)?;
+
+        println!(
+            "{}: installed {}@{} from {}",
+            "success".bright_green(),
+            package.name.bright_cyan(),
+            package.version,
+            package.resolved()
+        );
+    }
+
+    // every member is linked into the root, so members resolve each other like installed packages
+    for member in members.iter() {
+        link_workspace_package(&app.node_modules_dir, member)?;
+
+        println!(
+            "{}: linked {}@{} from the workspace",
+            "success".bright_green(),
+            member.name.bright_cyan(),
+            member.version
+        );
+    }
+
+    for local in locals.iter() {
+        link_workspace_package(&app.node_modules_dir, local)?;
+
+        println!(
+            "{}: linked {}@{} from {}",
+            "success".bright_green(),
+            local.name.bright_cyan(),
+            local.version,
+            local.path.display()
+        );
+    }
+
+    install_scripts::run_project(&app, &root, PROJECT_EVENTS)?;
+
+    if !frozen {
+        record_overrides(&mut lock_file, &overrides);
+        record_extensions(&mut lock_file, &app.registries.extensions);
+
+        // notes of dependencies that were removed from package.json go with them
+        let names = lock_file
+            .dependencies
+            .values()
+            .map(|lock| lock.install_name().to_string())
+            .chain(members.iter().map(|member| member.name.clone()))
+            .chain(locals.iter().map(|local| local.name.clone()))
+            .collect::<HashSet<_>>();
+
+        lock_file.meta.retain(|name, _| names.contains(name));
+
+        lock_file.save()?;
+    }
+
+    sign_install(&app, &lock_file, !frozen)?;
+
+    zero_install::check_size(&app);
+
+    println!(
+        "{}: installed {} packages into {}",
+        "success".bright_green(),
+        dependencies.len(),
+        app.node_modules_dir.display()
+    );
+
+    Ok(())
+}
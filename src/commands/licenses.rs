@@ -0,0 +1,202 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Summarize the licenses of the installed packages.
+//!
+//! Packages are grouped by license and counted. The packages of a group are listed when the
+//! license needs a look: it's unknown, `UNLICENSED`, points at a file with `SEE LICENSE IN`, or
+//! is one `--deny` names. Denied licenses fail the command, so CI can gate on them.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::licenses::{installed, is_denied, InstalledLicense, LicenseSource};
+use crate::core::utils::output;
+use crate::{core::VERSION, App, Command};
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use tracing::info;
+
+/// Group of the packages whose license isn't known.
+const UNKNOWN: &str = "unknown";
+
+/// Struct implementation for the `Licenses` command.
+pub struct Licenses;
+
+#[async_trait]
+impl Command for Licenses {
+    /// Display a help menu for the `volt licenses` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Summarizes the licenses of the installed packages, listing those that are unknown or denied.
+
+Usage: {} {} {}
+
+Options:
+
+  {} Comma-separated licenses that fail the command, e.g. `GPL-3.0,AGPL-3.0`.
+  {} Print the packages of every license as JSON.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "licenses".bright_purple(),
+            "[flags]".white(),
+            "--deny <licenses>".blue(),
+            "--json".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt licenses` command
+    ///
+    /// Reads the license of every package in node_modules, nested ones included.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Licenses.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let denied = app
+            .args
+            .value_of("deny")
+            .map(|deny| {
+                deny.split(',')
+                    .map(str::trim)
+                    .filter(|license| !license.is_empty())
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let packages = installed(&app.node_modules_dir);
+
+        if packages.is_empty() {
+            info!("nothing is installed, run `volt install` first");
+            return Ok(());
+        }
+
+        let mut groups: BTreeMap<String, Vec<&InstalledLicense>> = BTreeMap::new();
+
+        for package in packages.iter() {
+            let license = package.license.as_deref().unwrap_or(UNKNOWN);
+
+            groups.entry(license.to_string()).or_default().push(package);
+        }
+
+        let offending = packages
+            .iter()
+            .filter(|package| {
+                package
+                    .license
+                    .as_deref()
+                    .is_some_and(|license| is_denied(license, &denied))
+            })
+            .map(spec)
+            .collect::<Vec<_>>();
+
+        if app.json() {
+            let listed = groups
+                .iter()
+                .map(|(license, packages)| {
+                    (license.clone(), packages.iter().map(|p| spec(p)).collect())
+                })
+                .collect::<BTreeMap<String, Vec<String>>>();
+
+            output::set("licenses", listed);
+            output::set("denied", &offending);
+        } else {
+            print_groups(&groups, &denied);
+        }
+
+        if !offending.is_empty() {
+            return Err(VoltError::DeniedLicenses {
+                count: offending.len(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Every license with the number of packages using it, most used first, and the packages of
+/// those that need a look.
+fn print_groups(groups: &BTreeMap<String, Vec<&InstalledLicense>>, denied: &[String]) {
+    let mut sorted = groups.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(_, packages)| std::cmp::Reverse(packages.len()));
+
+    let width = sorted
+        .iter()
+        .map(|(license, _)| license.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut from_files = false;
+
+    for (license, packages) in sorted {
+        let denied = license != UNKNOWN && is_denied(license, denied);
+
+        let name = match (denied, needs_review(license)) {
+            (true, _) => license.bright_red().to_string(),
+            (false, true) => license.bright_yellow().to_string(),
+            (false, false) => license.bright_cyan().to_string(),
+        };
+
+        // padded by the plain width, colors would count towards it
+        println!(
+            "{}{} {}",
+            name,
+            " ".repeat(width - license.len()),
+            packages.len()
+        );
+
+        if !denied && !needs_review(license) {
+            from_files |= packages.iter().any(|p| p.source == LicenseSource::File);
+            continue;
+        }
+
+        for package in packages.iter() {
+            println!("{}{}", "  - ".bright_magenta(), spec(package));
+        }
+    }
+
+    if from_files {
+        println!(
+            "\n{}",
+            "some licenses were recognized from a LICENSE file, package.json doesn't name them"
+                .bright_black()
+        );
+    }
+}
+
+/// A license that doesn't say what use it allows, or only does so somewhere else.
+fn needs_review(license: &str) -> bool {
+    license == UNKNOWN || license == "UNLICENSED" || license.starts_with("SEE LICEN")
+}
+
+fn spec(package: &InstalledLicense) -> String {
+    format!("{}@{}", package.name, package.version)
+}
@@ -0,0 +1,210 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Flag dangerous patterns in the lifecycle scripts of the project and its dependencies.
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::installed_version;
+use crate::core::utils::linker::stored_version_path;
+use crate::core::utils::script_lint::{lint_script, Severity};
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Scripts npm runs when installing a dependency.
+const INSTALL_SCRIPTS: &[&str] = &["preinstall", "install", "postinstall"];
+
+/// Scripts that run when installing or publishing the project itself.
+const PROJECT_SCRIPTS: &[&str] = &[
+    "preinstall",
+    "install",
+    "postinstall",
+    "preprepare",
+    "prepare",
+    "postprepare",
+    "prepublish",
+    "prepublishOnly",
+    "prepack",
+    "postpack",
+];
+
+/// Struct implementation for the `LintScripts` command.
+pub struct LintScripts;
+
+/// A finding together with where it was found.
+struct Entry {
+    severity: Severity,
+    message: &'static str,
+    package: String,
+    script: String,
+    command: String,
+}
+
+#[async_trait]
+impl Command for LintScripts {
+    /// Display a help menu for the `volt lint-scripts` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Flags dangerous patterns in the lifecycle scripts of the project and its dependencies.
+
+Usage: {} {} {}
+
+Options:
+
+  {} Only report findings at least this severe (low|medium|high|critical).
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "lint-scripts".bright_purple(),
+            "[flags]".white(),
+            "--min-severity".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt lint-scripts` command
+    ///
+    /// Reads the lifecycle scripts of package.json and of every locked package, from
+    /// node_modules or else the store, and reports what the rules in
+    /// `core::utils::script_lint` find, the most severe first.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// LintScripts.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let min_severity = app
+            .args
+            .value_of("min-severity")
+            .and_then(Severity::parse)
+            .unwrap_or(Severity::Low);
+
+        let mut entries = vec![];
+
+        lint_manifest(
+            &app.current_dir.join("package.json"),
+            "(project)",
+            PROJECT_SCRIPTS,
+            &mut entries,
+        );
+
+        let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+        let mut scanned = 0;
+
+        for lock in lock_file.dependencies.values() {
+            // the installed copy is what runs, the store has the others
+            let directory =
+                if installed_version(&app, lock.install_name()).as_ref() == Some(&lock.version) {
+                    app.node_modules_dir.join(lock.install_name())
+                } else {
+                    stored_version_path(&app, &lock.name, &lock.version, &lock.integrity)
+                };
+
+            if lint_manifest(
+                &directory.join("package.json"),
+                &format!("{}@{}", lock.name, lock.version),
+                INSTALL_SCRIPTS,
+                &mut entries,
+            ) {
+                scanned += 1;
+            }
+        }
+
+        entries.retain(|entry| entry.severity >= min_severity);
+
+        entries.sort_by(|a, b| {
+            (b.severity, &a.package, &a.script).cmp(&(a.severity, &b.package, &b.script))
+        });
+
+        for entry in entries.iter() {
+            println!(
+                "{} {} {} {}",
+                entry.severity.colored(),
+                entry.package.bright_cyan(),
+                entry.script.bright_purple(),
+                entry.message
+            );
+            println!("         {}", entry.command.bright_black());
+        }
+
+        let unread = lock_file.dependencies.len() - scanned;
+
+        if unread > 0 {
+            warn!(
+                "{} locked packages aren't installed or in the store, their scripts weren't checked",
+                unread
+            );
+        }
+
+        if entries.is_empty() {
+            println!(
+                "{}: nothing suspicious in the scripts of the project and {} packages",
+                "success".bright_green(),
+                scanned
+            );
+        } else {
+            println!(
+                "\n{} findings, review them before letting these scripts run",
+                entries.len().to_string().bright_yellow().bold()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Lint the `scripts` of the manifest at `path`, returns whether it could be read.
+fn lint_manifest(path: &Path, package: &str, scripts: &[&str], entries: &mut Vec<Entry>) -> bool {
+    let manifest = match std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+    {
+        Some(manifest) => manifest,
+        None => return false,
+    };
+
+    for script in scripts.iter() {
+        let command = match manifest["scripts"][script].as_str() {
+            Some(command) => command,
+            None => continue,
+        };
+
+        for finding in lint_script(command) {
+            entries.push(Entry {
+                severity: finding.severity,
+                message: finding.message,
+                package: package.to_string(),
+                script: script.to_string(),
+                command: command.to_string(),
+            });
+        }
+    }
+
+    true
+}
@@ -11,15 +11,22 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-//! Compress node_modules into node_modules.pack.
+//! List the direct dependencies of a project.
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::explorer::explore;
+use crate::core::utils::installed_version;
+use crate::core::utils::output;
+use crate::core::utils::package::PackageJson;
 use crate::App;
 use crate::{core::VERSION, Command};
 use async_trait::async_trait;
 use colored::Colorize;
 use miette::Result;
+use serde_json::{json, Value};
 
 pub struct List;
 
@@ -29,17 +36,21 @@ impl Command for List {
     fn help() -> String {
         format!(
             r#"volt {}
-    
-List dependency tree from node_modules.
-Usage: {} {} {} {}
-Options: 
-    
+
+Lists the direct dependencies of the project with their installed version and why they were added.
+Usage: {} {} {}
+Options:
+
+  {} {} Explore the whole dependency tree in the terminal.
+  {} Print the dependencies as a JSON document instead of text.
   {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
-            "clone".bright_purple(),
-            "[repository]".white(),
+            "list".bright_purple(),
             "[flags]".white(),
+            "--interactive".blue(),
+            "(-i)".yellow(),
+            "--json".blue(),
             "--verbose".blue(),
             "(-v)".yellow(),
         )
@@ -47,105 +58,102 @@ Options:
 
     /// Execute the `volt list` command
     ///
-    /// List node_modules into node_modules.pack.
+    /// Lists the dependencies in package.json by section, with the version installed in
+    /// node_modules and the note recorded with `volt add --track`. With `--interactive` the
+    /// whole tree can be browsed instead, and the direct dependencies marked for removal there
+    /// are taken out of package.json on exit. With `--json` the sections go into the document
+    /// instead.
     /// ## Arguments
     /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
-    /// // List node_modules into node_modules.pack
     /// // .exec() is an async call so you need to await it
-    /// Add.exec(app).await;
+    /// List.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        // let flags = &app.flags;
-
-        // let mut depth: u64 = 2;
-
-        // // if flags.contains(&"--depth".to_string()) {
-        // //     depth = app.args.iter().find_map(|s| s.parse().ok()).unwrap_or(2);
-        // // }
-
-        // let dirs = WalkDir::new("node_modules");
-
-        // let dependency_paths: Vec<_> = dirs
-        //     .into_iter()
-        //     .filter_map(Result::ok)
-        //     .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //     .collect();
-
-        // if dependency_paths.len() == 1 {
-        //     println!("{}", "No Dependencies Found!".bright_cyan());
-        //     return Ok(());
-        // } else if dependency_paths.is_empty() {
-        //     println!(
-        //         "{} {} {}",
-        //         "Failed to find".bright_cyan(),
-        //         "node_modules".bright_yellow().bold(),
-        //         "folder".bright_cyan(),
-        //     );
-        //     return Ok(());
-        // }
-
-        // for dep in dependency_paths {
-        //     let dep_path = dep.path().to_str().unwrap();
-        //     let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //     let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //     if dep_name != "node_modules"
-        //         && dep_name != "scripts"
-        //         && !dep_name.starts_with("node_modules")
-        //     {
-        //         println!("{} {}", "-".bright_cyan(), dep_name.bright_blue().bold());
-        //         let dirs = WalkDir::new(format!("node_modules/{}/node_modules", dep_name))
-        //             .follow_links(true)
-        //             .max_depth((depth - 1) as usize);
-        //         let dependency_paths: Vec<_> = dirs
-        //             .into_iter()
-        //             .filter_map(Result::ok)
-        //             .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //             .collect();
-
-        //         for dep in dependency_paths {
-        //             let dep_path = dep.path().to_str().unwrap();
-        //             let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //             let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //             if dep_name != "node_modules"
-        //                 && dep_name != "scripts"
-        //                 && !dep_path.contains("lib")
-        //                 && !dep_path.contains("src")
-        //                 && !dep_path.contains("dist")
-        //                 && !dep_path.contains("test")
-        //                 && !dep_name.starts_with("node_modules")
-        //             {
-        //                 for _ in 0..dep_path_split.len() {
-        //                     print!("  ");
-        //                 }
-        //                 let mut version = "".to_owned();
-        //                 for file in read_dir(std::env::temp_dir().join("volt")).unwrap() {
-        //                     let file_path: PathBuf = file.unwrap().path();
-        //                     let file_name: &str = file_path.to_str().unwrap();
-        //                     let file_split: Vec<&str> = file_name.split('\\').collect();
-        //                     let name: &str = file_split[file_split.len() - 1];
-        //                     if name.starts_with(dep_name) {
-        //                         let file_split: Vec<&str> = name.split('@').collect();
-        //                         let file_end = file_split[1];
-        //                         let file_split: Vec<&str> = file_end.split(".tgz").collect();
-        //                         version = file_split[0].to_owned();
-        //                     }
-        //                 }
-        //                 let padding = 50 - (dep_path_split.len() * 2);
-        //                 print!(
-        //                     "{} {:<width$}",
-        //                     "-".bright_purple(),
-        //                     dep_name,
-        //                     width = padding
-        //                 );
-        //                 println!("{}", version.clone().truecolor(190, 190, 190));
-        //             }
-        //         }
-        //     }
-        // }
+        let manifest = PackageJson::read(&app.current_dir.join("package.json"))?;
+        let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+        if app.args.is_present("interactive") {
+            let removed = explore(&app, &manifest, &lock_file)?;
+
+            if !removed.is_empty() {
+                PackageJson::remove_dependencies(&app.current_dir.join("package.json"), &removed)?;
+
+                for name in removed.iter() {
+                    println!("  {} {}", "-".bright_red().bold(), name.bright_cyan());
+                }
+
+                println!(
+                    "{}: removed {} dependencies from package.json, run `volt install` to update node_modules",
+                    "success".bright_green(),
+                    removed.len()
+                );
+            }
+
+            return Ok(());
+        }
+
+        let sections: [(&str, &HashMap<String, String>); 3] = [
+            ("dependencies", &manifest.dependencies),
+            ("devDependencies", &manifest.dev_dependencies),
+            ("optionalDependencies", &manifest.optional_dependencies),
+        ];
+
+        if app.json() {
+            let mut listed = BTreeMap::new();
+
+            for (section, dependencies) in sections.iter() {
+                let entries = dependencies
+                    .iter()
+                    .map(|(name, range)| {
+                        let entry = json!({
+                            "range": range,
+                            "installed": installed_version(&app, name),
+                            "note": lock_file.meta.get(name).map(|meta| meta.note.clone()),
+                        });
+
+                        (name.clone(), entry)
+                    })
+                    .collect::<BTreeMap<String, Value>>();
+
+                listed.insert(section.to_string(), entries);
+            }
+
+            output::set("dependencies", listed);
+
+            return Ok(());
+        }
+
+        for (section, dependencies) in sections.iter() {
+            if dependencies.is_empty() {
+                continue;
+            }
+
+            println!("{}:", section.bright_purple());
+
+            let mut names = dependencies.keys().collect::<Vec<_>>();
+            names.sort();
+
+            for name in names {
+                let version = match installed_version(&app, name) {
+                    Some(version) => version.bright_yellow(),
+                    None => format!("{} (not installed)", dependencies[name]).bright_red(),
+                };
+
+                match lock_file.meta.get(name) {
+                    Some(meta) => println!(
+                        "  {}@{}  {}",
+                        name.bright_cyan(),
+                        version,
+                        format!("# {}", meta.note).bright_black()
+                    ),
+                    None => println!("  {}@{}", name.bright_cyan(), version),
+                }
+            }
+        }
 
         Ok(())
     }
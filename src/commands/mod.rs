@@ -1,28 +1,53 @@
 pub mod add;
 pub mod audit;
+pub mod bug_report;
+pub mod cache;
 pub mod check;
+pub mod ci;
+pub mod clean;
 pub mod clone;
+pub mod completions;
 pub mod compress;
+pub mod config;
 pub mod create;
 pub mod deploy;
+pub mod deprecate;
+pub mod env_diff;
+pub mod export;
 pub mod fix;
+pub mod graph;
 pub mod help;
 pub mod info;
 pub mod init;
 pub mod install;
+pub mod licenses;
+pub mod lint_scripts;
 pub mod list;
 pub mod login;
 pub mod logout;
 pub mod migrate;
+pub mod node;
 pub mod outdated;
 pub mod owner;
+pub mod pack;
+pub mod ping;
+pub mod policy;
 pub mod publish;
+pub mod query;
 pub mod remove;
 pub mod run;
+pub mod sbom;
 pub mod search;
+pub mod serve;
 pub mod set;
+pub mod sign;
 pub mod stat;
+pub mod store;
 pub mod tag;
 pub mod team;
+pub mod unpublish;
 pub mod update;
+pub mod vendor;
+pub mod verify;
 pub mod watch;
+pub mod why;
@@ -0,0 +1,205 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Install node versions and pin the one a project's scripts run with.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::core::utils::constants::NODE_VERSION_FILE;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::node_runtime::{self, Release};
+use crate::{core::VERSION, App, Command};
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use node_semver::Version;
+use tracing::warn;
+
+/// Struct implementation for the `Node` command.
+pub struct Node;
+
+#[async_trait]
+impl Command for Node {
+    /// Display a help menu for the `volt node` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Installs official builds of node and pins the version `volt run` runs scripts with.
+
+Usage: {} {} {} {}
+
+Commands:
+
+  {}  Install a version, or the pinned one when none is given.
+  {}      Pin a version in .node-version, installing it first.
+  {}     List the installed versions, or with `--remote` the releases.
+
+Versions look like `18`, `18.12.0`, `^18.12`, `lts`, `lts/hydrogen` or `latest`.
+
+Options:
+
+  {} List the newest release of every major version.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "node".bright_purple(),
+            "<command>".white(),
+            "[version]".white(),
+            "install".blue(),
+            "use".blue(),
+            "list".blue(),
+            "--remote".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt node` command
+    ///
+    /// Installs, pins or lists versions of node, kept in `<volt dir>/node`.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Node.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let spec = app.args.value_of("version");
+
+        match app.args.value_of("command") {
+            Some("install") => {
+                let spec = match spec {
+                    Some(spec) => spec.to_string(),
+                    None => node_runtime::pinned(&app)
+                        .map(|(spec, _)| spec)
+                        .ok_or(VoltError::NoNodeVersion)?,
+                };
+
+                let version = node_runtime::ensure(&app, &spec).await?;
+
+                println!(
+                    "node {} is in {}",
+                    format!("v{}", version).bright_cyan(),
+                    node_runtime::bin_dir(&app, &version).display()
+                );
+
+                Ok(())
+            }
+            Some("use") => {
+                let spec = spec.ok_or(VoltError::NoNodeVersion)?.trim();
+                let version = node_runtime::ensure(&app, spec).await?;
+
+                let path = app.current_dir.join(NODE_VERSION_FILE);
+
+                std::fs::write(&path, format!("{}\n", spec)).map_err(|e| {
+                    VoltError::WriteFileError {
+                        source: e,
+                        name: path.display().to_string(),
+                    }
+                })?;
+
+                println!(
+                    "{}: pinned node {} in {}, scripts run with {}",
+                    "success".bright_green(),
+                    spec.bright_cyan(),
+                    NODE_VERSION_FILE,
+                    format!("v{}", version).bright_cyan()
+                );
+
+                if let Some(pinned) = app.config.node.as_deref() {
+                    warn!(
+                        "`node = \"{}\"` in volt.toml takes precedence over {}",
+                        pinned, NODE_VERSION_FILE
+                    );
+                }
+
+                Ok(())
+            }
+            Some("list") if app.has_flag("remote") => {
+                let installed = node_runtime::installed(&app);
+
+                for release in newest_per_major(&node_runtime::releases(&app).await?) {
+                    let version = match release.semver() {
+                        Some(version) => version,
+                        None => continue,
+                    };
+
+                    let marker = match installed.contains(&version) {
+                        true => "*".bright_green().to_string(),
+                        false => String::from(" "),
+                    };
+
+                    println!(
+                        "{} {:<10} {} {}",
+                        marker,
+                        release.version.bright_cyan(),
+                        release.date.bright_black(),
+                        release.lts().unwrap_or_default().bright_blue()
+                    );
+                }
+
+                Ok(())
+            }
+            _ => {
+                let installed = node_runtime::installed(&app);
+
+                if installed.is_empty() {
+                    println!("no versions of node are installed, try `volt node install lts`");
+                    return Ok(());
+                }
+
+                let pinned = node_runtime::pinned(&app);
+                let current = pinned
+                    .as_ref()
+                    .and_then(|(spec, _)| node_runtime::find_installed(&app, spec));
+
+                for version in installed.iter() {
+                    match (&current, &pinned) {
+                        (Some(current), Some((_, origin))) if current == version => println!(
+                            "{} {} {}",
+                            "*".bright_green(),
+                            format!("v{}", version).bright_cyan(),
+                            format!("(pinned by {})", origin).bright_black()
+                        ),
+                        _ => println!("  {}", format!("v{}", version).bright_cyan()),
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The newest release of every major version, newest first.
+fn newest_per_major(releases: &[Release]) -> Vec<&Release> {
+    let mut majors = BTreeSet::new();
+
+    releases
+        .iter()
+        .filter(|release| {
+            release
+                .semver()
+                .is_some_and(|version: Version| majors.insert(version.major))
+        })
+        .collect()
+}
@@ -11,37 +11,191 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Manage the owners of a package, the users who may publish it.
 
+use crate::core::model::http_manager::{
+    get_full_packument, get_user, get_write_document, set_maintainers,
+};
+use crate::core::utils::auth::Credentials;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package::PackageJson;
+use crate::core::VERSION;
 use crate::{App, Command};
 
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use colored::Colorize;
 use miette::Result;
+use tracing::info;
 
 pub struct Owner {}
 
 #[async_trait]
 impl Command for Owner {
     fn help() -> String {
-        todo!()
+        format!(
+            r#"volt {}
+
+Lists, adds or removes the owners of a package on the registry.
+
+Usage: {} {} {} {}
+
+Commands:
+
+  {} {}  Give a registry user access to publish the package.
+  {} {}   Take that access away, the last owner can't be removed.
+  {} {}             List the owners of the package.
+
+The package defaults to the one in the current directory.
+
+Options:
+
+  {} Registry of the package instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "owner".bright_purple(),
+            "<command>".white(),
+            "[user] [package]".white(),
+            "add".blue(),
+            "<user> [package]".white(),
+            "rm".blue(),
+            "<user> [package]".white(),
+            "ls".blue(),
+            "[package]".white(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
     }
 
-    /// Execute the `volt outdated` command
+    /// Execute the `volt owner` command
     ///
-    /// Check for outdated packages
+    /// Lists the maintainers of a package, or adds or removes one through the write document
+    /// of the package, with the token of its registry.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Add a collaborator to the package in the current directory
     /// // .exec() is an async call so you need to await it
     /// Owner.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let mut arguments = app
+            .args
+            .values_of("arguments")
+            .into_iter()
+            .flatten()
+            .map(String::from);
+
+        let command = app.args.value_of("command").unwrap_or("ls");
+
+        if command == "ls" {
+            let package = package_name(&app, arguments.next())?;
+            let packument = get_full_packument(&app.registries, &package).await?;
+
+            for maintainer in packument.maintainers.iter() {
+                println!("{}", maintainer);
+            }
+
+            return Ok(());
+        }
+
+        let user = arguments.next().ok_or_else(|| VoltError::MissingOwner {
+            command: command.to_string(),
+        })?;
+
+        let package = package_name(&app, arguments.next())?;
+
+        let registry = app.registries.for_package(&package).to_string();
+        let credentials = Credentials::new(&app, &registry);
+
+        let document = get_write_document(&registry, &credentials, &package).await?;
+        let rev = document["_rev"].as_str().unwrap_or_default();
+
+        let mut maintainers = document["maintainers"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        let position = maintainers
+            .iter()
+            .position(|maintainer| maintainer["name"].as_str() == Some(user.as_str()));
+
+        match (command, position) {
+            ("add", Some(_)) => {
+                info!(
+                    "{} already owns {}",
+                    user.bright_cyan(),
+                    package.bright_cyan()
+                );
+                return Ok(());
+            }
+            ("add", None) => {
+                let maintainer = get_user(&registry, &credentials, &user).await.map_err(
+                    |report| match report.downcast::<VoltError>() {
+                        Ok(VoltError::RegistryError { code, .. }) if code == "404" => {
+                            VoltError::UnknownRegistryUser {
+                                user: user.clone(),
+                                registry: registry.clone(),
+                            }
+                            .into()
+                        }
+                        Ok(error) => error.into(),
+                        Err(report) => report,
+                    },
+                )?;
+
+                maintainers.push(maintainer);
+            }
+            (_, None) => {
+                return Err(VoltError::NotAnOwner { user, package }.into());
+            }
+            (_, Some(_)) if maintainers.len() == 1 => {
+                return Err(VoltError::LastOwner { user, package }.into());
+            }
+            (_, Some(position)) => {
+                maintainers.remove(position);
+            }
+        }
+
+        set_maintainers(&registry, &credentials, &package, rev, &maintainers).await?;
+
+        let action = match command {
+            "add" => "added",
+            _ => "removed",
+        };
+
+        println!(
+            "{}: {} {} as an owner of {}",
+            "success".bright_green(),
+            action,
+            user.bright_cyan(),
+            package.bright_cyan()
+        );
+
         Ok(())
     }
 }
+
+/// The package given, or the one in the current directory.
+pub fn package_name(app: &App, given: Option<String>) -> Result<String> {
+    if let Some(name) = given {
+        return Ok(name);
+    }
+
+    let manifest = app.current_dir.join("package.json");
+
+    if !manifest.exists() {
+        return Err(VoltError::NoPackageName.into());
+    }
+
+    match PackageJson::read(&manifest)?.name {
+        name if name.is_empty() => Err(VoltError::NoPackageName.into()),
+        name => Ok(name),
+    }
+}
@@ -0,0 +1,138 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Pack the current package into a tarball.
+
+use crate::core::utils::dedup::format_bytes;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::pack::{pack_directory, package_options, tarball_hashes};
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+use std::fs::read_to_string;
+use std::sync::Arc;
+
+pub struct Pack {}
+
+#[async_trait]
+impl Command for Pack {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Pack the current package into a tarball, the same one `volt publish` uploads. Like npm, it
+holds what `files` in package.json lists, or else everything `.npmignore` doesn't ignore.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Add a NOTICE with the licenses of bundled dependencies.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "pack".bright_purple(),
+            "[flags]".white(),
+            "--notice".blue(),
+            "(-n)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt pack` command
+    ///
+    /// Pack the current package, including its `bundleDependencies`, into `<name>-<version>.tgz`
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Pack the package in the current directory
+    /// // .exec() is an async call so you need to await it
+    /// Pack.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let package_path = app.current_dir.join("package.json");
+
+        let data = read_to_string(&package_path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: package_path.display().to_string(),
+        })?;
+
+        let mut manifest: Value = serde_json::from_str(&data).into_diagnostic()?;
+
+        let name = match manifest["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => miette::bail!("package.json is missing a `name` field"),
+        };
+
+        let version = match manifest["version"].as_str() {
+            Some(version) => version.to_string(),
+            None => miette::bail!("package.json is missing a `version` field"),
+        };
+
+        let notice = app.has_flag("notice") || app.config.pack_notice;
+
+        let options = package_options(&app.current_dir, &mut manifest, notice)?;
+
+        let tarball = pack_directory(&app.current_dir, &options)?;
+        let (shasum, integrity) = tarball_hashes(&tarball);
+
+        // @scope/name -> scope-name-1.0.0.tgz, like npm
+        let file_name = format!(
+            "{}-{}.tgz",
+            name.trim_start_matches('@').replace('/', "-"),
+            version
+        );
+
+        let destination = app.current_dir.join(&file_name);
+
+        std::fs::write(&destination, &tarball).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: destination.display().to_string(),
+        })?;
+
+        for bundled in options.bundled.iter() {
+            println!("{} {}", "bundled".bright_cyan(), bundled);
+        }
+
+        if options.extra_files.iter().any(|(file, _)| file == "NOTICE") {
+            println!(
+                "{} NOTICE for {} bundled dependencies",
+                "generated".bright_cyan(),
+                options.bundled.len()
+            );
+        }
+
+        println!("{} {}", "shasum   ".bright_black(), shasum);
+        println!("{} {}", "integrity".bright_black(), integrity);
+
+        println!(
+            "{}: packed {}@{} into {} ({})",
+            "success".bright_green(),
+            name.bright_cyan(),
+            version,
+            file_name.bright_cyan(),
+            format_bytes(tarball.len() as u64)
+        );
+
+        Ok(())
+    }
+}
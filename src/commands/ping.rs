@@ -0,0 +1,358 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Check that the registries and mirrors volt is configured with can be reached.
+
+use crate::core::model::http_manager::{client, NetworkMode};
+use crate::core::utils::constants::{NPM_REGISTRY, VOLT_CDN};
+use crate::core::utils::errors::VoltError;
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use isahc::http::StatusCode;
+use isahc::{AsyncReadResponseExt, Request};
+use miette::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Requests sent to every endpoint, the first one also pays for the connection.
+const SAMPLES: usize = 3;
+
+/// What an endpoint is used for.
+#[derive(PartialEq)]
+enum Role {
+    /// The default registry.
+    Registry,
+    /// The registry of a scope.
+    Scope(String),
+    /// Where tarballs are downloaded from instead, see `tarball-rewrites`.
+    Mirror,
+    /// The CDN serving resolved trees of npm packages.
+    Cdn,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Registry => write!(f, "registry"),
+            Role::Scope(scope) => write!(f, "{} registry", scope),
+            Role::Mirror => write!(f, "tarball mirror"),
+            Role::Cdn => write!(f, "cdn"),
+        }
+    }
+}
+
+/// Who the registry says the token belongs to.
+enum Auth {
+    /// No token is configured for the registry.
+    Anonymous,
+    User(String),
+    Rejected(StatusCode),
+    /// The token couldn't be fetched or the registry doesn't support `whoami`.
+    Unknown(String),
+}
+
+/// How one endpoint answered.
+struct Probe {
+    role: Role,
+    url: String,
+    /// The status of the last response, or why there was none.
+    status: std::result::Result<StatusCode, String>,
+    latencies: Vec<Duration>,
+    auth: Option<Auth>,
+}
+
+impl Probe {
+    /// Whether volt could use the endpoint: registries have to answer their ping, any answer
+    /// from a mirror or the CDN means they're up.
+    fn reachable(&self) -> bool {
+        match (&self.status, &self.role) {
+            (Ok(status), Role::Registry | Role::Scope(_)) => status.is_success(),
+            (Ok(_), _) => true,
+            (Err(_), _) => false,
+        }
+    }
+
+    fn failed(&self) -> bool {
+        !self.reachable() || matches!(self.auth, Some(Auth::Rejected(_)))
+    }
+}
+
+/// Struct implementation for the `Ping` command.
+pub struct Ping;
+
+#[async_trait]
+impl Command for Ping {
+    /// Display a help menu for the `volt ping` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Checks the configured registries and mirrors can be reached, how fast they answer and
+who their tokens belong to. Registries using the `builtin:codeartifact` or `builtin:gcp`
+auth helper need the `aws` or `gcloud` CLI, a missing one is reported with the token.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Only check this registry.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "ping".bright_purple(),
+            "[flags]".white(),
+            "--registry".blue(),
+            "(-r)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt ping` command
+    ///
+    /// Pings the default registry, the registries of scopes, tarball mirrors and the CDN,
+    /// timing a few requests to each and asking every registry with a token who it belongs
+    /// to. Fails when a registry can't be reached or rejects its token.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Ping.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let registries = &app.registries;
+
+        let mut endpoints = vec![(Role::Registry, registries.default.clone())];
+
+        // `--registry` asks about that registry alone
+        if app.args.value_of("registry").is_none() {
+            for (scope, url) in registries.scopes.iter() {
+                endpoints.push((Role::Scope(scope.clone()), url.clone()));
+            }
+
+            for rewrite in app.config.tarball_rewrites.iter() {
+                if !endpoints.iter().any(|(_, url)| *url == rewrite.to) {
+                    endpoints.push((Role::Mirror, rewrite.to.clone()));
+                }
+            }
+
+            if registries.default == NPM_REGISTRY {
+                endpoints.push((Role::Cdn, VOLT_CDN.to_string()));
+            }
+        }
+
+        if registries.network == NetworkMode::Offline {
+            warn!("volt is offline, installs won't reach any of these");
+        }
+
+        let mut probes = vec![];
+
+        for (role, url) in endpoints {
+            let probe = probe(&app, role, url).await;
+
+            print_probe(&probe);
+
+            probes.push(probe);
+        }
+
+        println!();
+        info!("{}", preferred(&app, &probes));
+
+        let failed = probes.iter().filter(|probe| probe.failed()).count();
+
+        if failed > 0 {
+            return Err(VoltError::PingFailed { failed }.into());
+        }
+
+        println!(
+            "{}: {} endpoints reachable",
+            "success".bright_green(),
+            probes.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// Time `SAMPLES` requests to `url`, and ask a registry with a token who it belongs to.
+async fn probe(app: &App, role: Role, url: String) -> Probe {
+    let target = match role {
+        Role::Registry | Role::Scope(_) => format!("{}/-/ping", url.trim_end_matches('/')),
+        _ => url.clone(),
+    };
+
+    let mut status = Err(String::new());
+    let mut latencies = vec![];
+
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+
+        match client().get_async(target.as_str()).await {
+            Ok(mut response) => {
+                latencies.push(start.elapsed());
+                status = Ok(response.status());
+
+                // read what's left so the next sample reuses the connection
+                response.consume().await.ok();
+            }
+            Err(error) => {
+                status = Err(error.to_string());
+                break;
+            }
+        }
+    }
+
+    let auth = match (&role, &status) {
+        (Role::Registry | Role::Scope(_), Ok(_)) => Some(whoami(app, &url).await),
+        _ => None,
+    };
+
+    Probe {
+        role,
+        url,
+        status,
+        latencies,
+        auth,
+    }
+}
+
+/// Ask the registry at `url` who its token belongs to.
+async fn whoami(app: &App, url: &str) -> Auth {
+    let token = match app.registries.token(url).await {
+        Ok(Some(token)) => token,
+        Ok(None) => return Auth::Anonymous,
+        Err(error) => return Auth::Unknown(error.to_string()),
+    };
+
+    let request = Request::get(format!("{}/-/whoami", url.trim_end_matches('/')))
+        .header("authorization", format!("Bearer {}", token))
+        .body(());
+
+    let mut response = match request {
+        Ok(request) => match client().send_async(request).await {
+            Ok(response) => response,
+            Err(error) => return Auth::Unknown(error.to_string()),
+        },
+        Err(error) => return Auth::Unknown(error.to_string()),
+    };
+
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Auth::Rejected(response.status()),
+        status if status.is_success() => {
+            let username = response
+                .text()
+                .await
+                .ok()
+                .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+                .and_then(|body| body["username"].as_str().map(str::to_string));
+
+            match username {
+                Some(username) => Auth::User(username),
+                None => Auth::Unknown("the registry didn't name the user".to_string()),
+            }
+        }
+        status => Auth::Unknown(format!("whoami answered {}", status)),
+    }
+}
+
+fn print_probe(probe: &Probe) {
+    let mark = match probe.failed() {
+        true => "!".bright_red().bold(),
+        false => "+".bright_green().bold(),
+    };
+
+    let status = match &probe.status {
+        Ok(status) if probe.reachable() => status.as_str().bright_green(),
+        Ok(status) => status.as_str().bright_red(),
+        Err(_) => "unreachable".bright_red(),
+    };
+
+    let latency = match (probe.latencies.iter().min(), probe.latencies.len()) {
+        (Some(fastest), count) => format!(
+            "{}ms (avg {}ms)",
+            fastest.as_millis(),
+            (probe.latencies.iter().sum::<Duration>() / count as u32).as_millis()
+        ),
+        (None, _) => "-".to_string(),
+    };
+
+    println!(
+        "  {} {} {} {} {}",
+        mark,
+        probe.url.bright_cyan(),
+        format!("[{}]", probe.role).bright_black(),
+        status,
+        latency.bright_black()
+    );
+
+    if let Err(error) = &probe.status {
+        println!("      {}", error.bright_black());
+    }
+
+    match &probe.auth {
+        Some(Auth::Anonymous) => println!(
+            "      {}",
+            "no token, requests are anonymous".bright_black()
+        ),
+        Some(Auth::User(user)) => println!("      authenticated as {}", user.bright_cyan()),
+        Some(Auth::Rejected(status)) => println!(
+            "      {} ({})",
+            "the token was rejected".bright_red(),
+            status.as_str()
+        ),
+        Some(Auth::Unknown(reason)) => println!(
+            "      {}",
+            format!("couldn't check the token: {}", reason).bright_black()
+        ),
+        None => {}
+    }
+}
+
+/// The endpoint npm packages would be resolved from right now, following the same rules as
+/// an install: the CDN while it's up and npm is the default registry, else the registry.
+fn preferred(app: &App, probes: &[Probe]) -> String {
+    if app.registries.network == NetworkMode::Offline {
+        return "packages would come from the metadata cache".to_string();
+    }
+
+    let find = |role: Role| probes.iter().find(|probe| probe.role == role);
+
+    if app.registries.network == NetworkMode::Online {
+        if let Some(cdn) = find(Role::Cdn).filter(|cdn| cdn.reachable()) {
+            return format!(
+                "packages would be resolved from {}, falling back to {}",
+                cdn.url.bright_cyan(),
+                app.registries.default.bright_cyan()
+            );
+        }
+    }
+
+    match find(Role::Registry) {
+        Some(registry) if registry.reachable() => format!(
+            "packages would be resolved from {}",
+            registry.url.bright_cyan()
+        ),
+        _ => format!(
+            "packages can't be resolved, {} is unreachable",
+            app.registries.default.bright_cyan()
+        ),
+    }
+}
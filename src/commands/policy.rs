@@ -0,0 +1,382 @@
+/*
+Copyright 2021 Volt Contributors
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+    http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Hold the dependencies to the freshness rules under `[policy]` in volt.toml.
+//!
+//! `volt policy check` fails when a rule is broken, so CI can gate on it. A package can be
+//! exempted from a rule until a date, after which the exemption is reported and ignored.
+//! Registries don't say when an advisory was published, so that comes from the GitHub
+//! advisory database and is kept in `<volt dir>/advisories`. An advisory of unknown age
+//! counts as too old.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::core::model::config::{AdvisorySeverity, PolicyExemption, PolicyRule};
+use crate::core::model::http_manager::{client, get_advisories, get_packument};
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::{installed_version, satisfies};
+use crate::App;
+use crate::{core::VERSION, Command};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use futures::{stream, StreamExt};
+use isahc::{AsyncReadResponseExt, Request};
+use miette::Result;
+use node_semver::Version;
+use tracing::{info, warn};
+
+pub struct Policy;
+
+/// A package breaking a rule.
+struct Violation {
+    rule: &'static str,
+    package: String,
+    message: String,
+}
+
+#[async_trait]
+impl Command for Policy {
+    /// Display a help menu for the `volt policy` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Checks the dependencies against the rules under `[policy]` in volt.toml.
+Usage: {} {} {} {}
+Commands:
+
+  {} Report every package breaking a rule, exits non-zero when there is one.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "policy".bright_purple(),
+            "<command>".white(),
+            "[flags]".white(),
+            "check".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt policy` command
+    ///
+    /// `volt policy check` evaluates every rule, prints the packages breaking them and fails
+    /// when any are not exempt.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Policy.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let policy = &app.config.policy;
+
+        if policy.rules.is_empty() {
+            info!("no rules under `[policy]` in volt.toml, nothing to check");
+
+            return Ok(());
+        }
+
+        let today = Utc::now().date_naive();
+
+        let mut violations = vec![];
+
+        for rule in policy.rules.iter() {
+            violations.extend(match rule {
+                PolicyRule::MajorLag { max } => major_lag(&app, *max).await?,
+                PolicyRule::AdvisoryAge { severity, max_days } => {
+                    advisory_age(&app, *severity, *max_days).await?
+                }
+            });
+        }
+
+        for exemption in policy.exemptions.iter() {
+            if let Some(expired) = expired(exemption, today)? {
+                warn!(
+                    "the exemption of {} from {} expired on {}",
+                    exemption.package.bright_cyan(),
+                    exemption.rule,
+                    expired
+                );
+            }
+        }
+
+        let mut failures = 0;
+
+        for violation in violations.iter() {
+            let exemption = policy.exemptions.iter().find(|exemption| {
+                exemption.rule == violation.rule
+                    && exemption.package == violation.package
+                    && matches!(expired(exemption, today), Ok(None))
+            });
+
+            match exemption {
+                Some(exemption) => println!(
+                    "{} {} {}  {}",
+                    "exempt".bright_black(),
+                    violation.rule.bright_purple(),
+                    violation.message,
+                    format!(
+                        "# {}{}",
+                        exemption.reason.as_deref().unwrap_or("no reason given"),
+                        exemption
+                            .expires
+                            .as_ref()
+                            .map(|expires| format!(", until {}", expires))
+                            .unwrap_or_default()
+                    )
+                    .bright_black()
+                ),
+                None => {
+                    failures += 1;
+
+                    println!(
+                        "{} {} {}",
+                        " fail ".black().on_bright_red(),
+                        violation.rule.bright_purple(),
+                        violation.message
+                    );
+                }
+            }
+        }
+
+        if failures > 0 {
+            return Err(VoltError::PolicyViolations { count: failures }.into());
+        }
+
+        println!(
+            "{}: {} rules hold for every dependency",
+            "success".bright_green(),
+            policy.rules.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// The day `exemption` expired on, `None` while it still applies.
+fn expired(exemption: &PolicyExemption, today: NaiveDate) -> Result<Option<NaiveDate>> {
+    let expires = match &exemption.expires {
+        Some(expires) => NaiveDate::parse_from_str(expires, "%Y-%m-%d").map_err(|_| {
+            VoltError::InvalidExemptionDate {
+                package: exemption.package.clone(),
+                date: expires.clone(),
+            }
+        })?,
+        None => return Ok(None),
+    };
+
+    Ok(Some(expires).filter(|expires| *expires < today))
+}
+
+/// Direct dependencies more than `max` major versions behind their `latest` dist-tag.
+async fn major_lag(app: &App, max: u64) -> Result<Vec<Violation>> {
+    let manifest = PackageJson::read(&app.current_dir.join("package.json"))?;
+    let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+    // what node_modules has, or what the lockfile would install
+    let current = manifest
+        .dependencies
+        .iter()
+        .chain(manifest.dev_dependencies.iter())
+        .chain(manifest.optional_dependencies.iter())
+        .filter_map(|(name, range)| {
+            let version = installed_version(app, name).or_else(|| {
+                lock_file
+                    .dependencies
+                    .values()
+                    .filter(|lock| &lock.name == name && lock.alias.is_none())
+                    .filter(|lock| satisfies(&lock.version, range))
+                    .filter_map(|lock| Version::parse(&lock.version).ok())
+                    .max()
+                    .map(|version| version.to_string())
+            })?;
+
+            Some((name.clone(), version))
+        })
+        .collect::<Vec<_>>();
+
+    let lookups = current.into_iter().map(|(name, current)| async move {
+        let packument = get_packument(&app.registries, &name).await.ok()?;
+        let latest = packument.dist_tags.get("latest")?.clone();
+
+        let behind = Version::parse(&latest)
+            .ok()?
+            .major
+            .checked_sub(Version::parse(&current).ok()?.major)?;
+
+        Some(Violation {
+            rule: "major-lag",
+            package: name.clone(),
+            message: format!(
+                "{}@{} is {} majors behind {} (at most {})",
+                name.bright_cyan(),
+                current,
+                behind,
+                latest.bright_green(),
+                max
+            ),
+        })
+        .filter(|_| behind > max)
+    });
+
+    let mut violations = stream::iter(lookups)
+        .buffer_unordered(app.concurrency())
+        .filter_map(|violation| async move { violation })
+        .collect::<Vec<_>>()
+        .await;
+
+    // sorted so the report diffs cleanly between CI runs
+    violations.sort_by(|a, b| a.package.cmp(&b.package));
+
+    Ok(violations)
+}
+
+/// Locked packages affected by an advisory of at least `severity` older than `max_days`.
+async fn advisory_age(
+    app: &App,
+    severity: AdvisorySeverity,
+    max_days: i64,
+) -> Result<Vec<Violation>> {
+    let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for lock in lock_file
+        .dependencies
+        .values()
+        .filter(|lock| lock.tarball.starts_with("http"))
+    {
+        versions
+            .entry(lock.name.clone())
+            .or_default()
+            .push(lock.version.clone());
+    }
+
+    if versions.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let found = get_advisories(&app.registry(), &versions).await?;
+
+    let mut published: HashMap<u64, Option<DateTime<Utc>>> = HashMap::new();
+    let mut violations = vec![];
+
+    let mut found = found.into_iter().collect::<Vec<_>>();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, advisories) in found.iter() {
+        for advisory in advisories.iter() {
+            if AdvisorySeverity::parse(&advisory.severity).is_none_or(|found| found < severity) {
+                continue;
+            }
+
+            let mut affected = versions[name]
+                .iter()
+                .filter(|version| satisfies(version, &advisory.vulnerable_versions))
+                .collect::<Vec<_>>();
+
+            if affected.is_empty() {
+                continue;
+            }
+
+            affected.sort();
+            affected.dedup();
+
+            if let Entry::Vacant(entry) = published.entry(advisory.id) {
+                entry.insert(published_at(app, &advisory.url).await);
+            }
+
+            let age = published[&advisory.id].map(|date| (Utc::now() - date).num_days());
+
+            if age.is_some_and(|age| age <= max_days) {
+                continue;
+            }
+
+            for version in affected {
+                violations.push(Violation {
+                    rule: "advisory-age",
+                    package: name.clone(),
+                    message: format!(
+                        "{}@{} has the {} advisory \"{}\" {} {}",
+                        name.bright_cyan(),
+                        version,
+                        advisory.severity.bright_red(),
+                        advisory.title,
+                        match age {
+                            Some(age) => format!("published {} days ago", age),
+                            None => String::from("of unknown age"),
+                        },
+                        advisory.url.bright_blue().underline()
+                    ),
+                });
+            }
+        }
+    }
+
+    Ok(violations)
+}
+
+/// When the advisory at `url` was published, for advisories of the GitHub database.
+async fn published_at(app: &App, url: &str) -> Option<DateTime<Utc>> {
+    let id = url.strip_prefix("https://github.com/advisories/")?;
+
+    let cached = app.volt_dir.join("advisories").join(id);
+
+    let date = match std::fs::read_to_string(&cached) {
+        Ok(date) => date,
+        Err(_) => {
+            let mut request = Request::get(format!("https://api.github.com/advisories/{}", id))
+                .header("accept", "application/vnd.github+json")
+                .header("user-agent", "volt");
+
+            if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+
+            let mut response = client().send_async(request.body(()).ok()?).await.ok()?;
+
+            if !response.status().is_success() {
+                return None;
+            }
+
+            let advisory =
+                serde_json::from_str::<serde_json::Value>(&response.text().await.ok()?).ok()?;
+            let date = advisory["published_at"].as_str()?.to_string();
+
+            // publish dates don't change, a failed write only costs a request next time
+            if let Some(parent) = cached.parent() {
+                std::fs::create_dir_all(parent).ok();
+            }
+
+            std::fs::write(&cached, &date).ok();
+
+            date
+        }
+    };
+
+    DateTime::parse_from_rfc3339(date.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
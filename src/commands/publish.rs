@@ -11,13 +11,21 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Publish a package to the registry.
 
+use crate::core::model::http_manager::{escape_package_name, publish_package};
+use crate::core::utils::auth::Credentials;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::pack::{pack_directory, package_options, tarball_hashes};
+use crate::core::VERSION;
 use crate::App;
 use crate::Command;
 
 use async_trait::async_trait;
-use miette::Result;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::{json, Value};
+use std::fs::read_to_string;
 use std::sync::Arc;
 
 pub struct Publish {}
@@ -25,23 +33,123 @@ pub struct Publish {}
 #[async_trait]
 impl Command for Publish {
     fn help() -> String {
-        todo!()
+        format!(
+            r#"volt {}
+
+Publish the current package to the registry.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Publish under the given dist-tag (defaults to `latest`).
+  {} Registry to publish to instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "publish".bright_purple(),
+            "[flags]".white(),
+            "--tag".blue(),
+            "(-t)".yellow(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
     }
 
-    /// Execute the `volt outdated` command
+    /// Execute the `volt publish` command
     ///
-    /// Check for outdated packages
+    /// Publish the current package to the registry
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Publish the package in the current directory
     /// // .exec() is an async call so you need to await it
     /// Publish.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let package_path = app.current_dir.join("package.json");
+
+        let data = read_to_string(&package_path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: package_path.display().to_string(),
+        })?;
+
+        let mut manifest: Value = serde_json::from_str(&data).into_diagnostic()?;
+
+        let name = match manifest["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => miette::bail!("package.json is missing a `name` field"),
+        };
+
+        let version = match manifest["version"].as_str() {
+            Some(version) => version.to_string(),
+            None => miette::bail!("package.json is missing a `version` field"),
+        };
+
+        let registry = app.registries.for_package(&name).to_string();
+
+        let credentials = Credentials::new(&app, &registry);
+
+        // fail before packing when there is no way to authenticate
+        credentials.token().await?;
+
+        let tag = app.args.value_of("tag").unwrap_or("latest").to_string();
+
+        let options = package_options(&app.current_dir, &mut manifest, app.config.pack_notice)?;
+
+        let tarball = pack_directory(&app.current_dir, &options)?;
+        let (shasum, integrity) = tarball_hashes(&tarball);
+
+        // @scope/name -> name-1.0.0.tgz
+        let file_name = format!("{}-{}.tgz", name.rsplit('/').next().unwrap(), version);
+
+        manifest["_id"] = json!(format!("{}@{}", name, version));
+        manifest["dist"] = json!({
+            "shasum": shasum,
+            "integrity": integrity,
+            "tarball": format!("{}/{}/-/{}", registry, escape_package_name(&name), file_name),
+        });
+
+        let document = json!({
+            "_id": name,
+            "name": name,
+            "description": manifest["description"],
+            "dist-tags": { tag.as_str(): version },
+            "versions": { version.as_str(): manifest },
+            "_attachments": {
+                file_name: {
+                    "content_type": "application/octet-stream",
+                    "data": base64::encode(&tarball),
+                    "length": tarball.len(),
+                }
+            },
+        });
+
+        let body = serde_json::to_vec(&document).into_diagnostic()?;
+
+        publish_package(
+            &registry,
+            &credentials,
+            &app.registries.retries,
+            &name,
+            &version,
+            &shasum,
+            body,
+        )
+        .await?;
+
+        println!(
+            "{}: published {}@{} with tag {}",
+            "success".bright_green(),
+            name.bright_cyan(),
+            version.bright_yellow(),
+            tag.bright_purple()
+        );
+
         Ok(())
     }
 }
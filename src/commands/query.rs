@@ -0,0 +1,298 @@
+/*
+Copyright 2021 Volt Contributors
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+    http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Answer questions of editor plugins about package.json over stdin and stdout.
+//!
+//! Every line of stdin is a request like `{"id": 1, "method": "hover", "params": {"name": "react"}}`
+//! and gets exactly one line of stdout back, either `{"id": 1, "result": ...}` or
+//! `{"id": 1, "error": {"code": ..., "message": ...}}`. The manifest and lockfile are read
+//! again for every request, so a plugin can keep one process running while package.json is
+//! being edited.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::core::model::http_manager::{get_full_packument, get_packument};
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::npm::resolve_version;
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::{installed_version, satisfies};
+use crate::App;
+use crate::{core::VERSION, Command};
+use async_trait::async_trait;
+use colored::Colorize;
+use futures::{stream, StreamExt};
+use miette::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub struct Query;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Params,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Params {
+    /// Dependency the question is about.
+    name: Option<String>,
+    /// Only for `latest`, every dependency when missing.
+    names: Option<Vec<String>>,
+    /// Only for `satisfies`, the one in package.json when missing.
+    specifier: Option<String>,
+    /// Only for `satisfies`, the installed one when missing.
+    version: Option<String>,
+}
+
+/// A dependency as package.json and node_modules know it.
+struct Dependency {
+    section: Option<&'static str>,
+    specifier: Option<String>,
+    installed: Option<String>,
+}
+
+#[async_trait]
+impl Command for Query {
+    /// Display a help menu for the `volt query` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Answers json queries about package.json on stdin, one per line, for editor plugins.
+Usage: {} {} {}
+Methods:
+
+  {}     Latest and wanted versions of every dependency, or of `names`.
+  {}  Whether the installed `version` of `name` satisfies its `specifier`.
+  {}      Registry and project info about the dependency `name`.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "query".bright_purple(),
+            "[flags]".white(),
+            "latest".bright_blue(),
+            "satisfies".bright_blue(),
+            "hover".bright_blue(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt query` command
+    ///
+    /// Reads json requests from stdin until it is closed and answers each on its own line of
+    /// stdout. A failed request is answered with an error and never ends the command.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Query.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            let read =
+                std::io::stdin()
+                    .read_line(&mut line)
+                    .map_err(|e| VoltError::EnvironmentError {
+                        source: e,
+                        env: String::from("stdin"),
+                    })?;
+
+            if read == 0 {
+                break;
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => match answer(&app, &request).await {
+                    Ok(result) => json!({ "id": request.id, "result": result }),
+                    Err(error) => json!({ "id": request.id, "error": describe(&error) }),
+                },
+                Err(e) => {
+                    json!({ "id": Value::Null, "error": describe(&VoltError::InvalidQuery(e).into()) })
+                }
+            };
+
+            println!("{}", response);
+        }
+
+        Ok(())
+    }
+}
+
+async fn answer(app: &App, request: &Request) -> Result<Value> {
+    let manifest = PackageJson::read(&app.current_dir.join("package.json"))?;
+
+    match request.method.as_str() {
+        "latest" => latest(app, &manifest, request.params.names.clone()).await,
+        "satisfies" => {
+            let name = required(&request.params.name, request)?;
+            let dependency = dependency(app, &manifest, name);
+
+            let specifier = request.params.specifier.clone().or(dependency.specifier);
+            let version = request.params.version.clone().or(dependency.installed);
+
+            let satisfied = match (&specifier, &version) {
+                (Some(specifier), Some(version)) => satisfies(version, specifier),
+                _ => false,
+            };
+
+            Ok(json!({
+                "name": name,
+                "specifier": specifier,
+                "version": version,
+                "satisfied": satisfied,
+            }))
+        }
+        "hover" => hover(app, &manifest, required(&request.params.name, request)?).await,
+        method => Err(VoltError::UnknownQueryMethod {
+            method: method.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Latest and highest matching version of each dependency, a failed lookup only fails its entry.
+async fn latest(app: &App, manifest: &PackageJson, names: Option<Vec<String>>) -> Result<Value> {
+    let names = names.unwrap_or_else(|| {
+        sections(manifest)
+            .iter()
+            .flat_map(|(_, dependencies)| dependencies.keys().cloned())
+            .collect()
+    });
+
+    let lookups = names.into_iter().map(|name| async move {
+        let dependency = dependency(app, manifest, &name);
+
+        let entry = match get_packument(&app.registries, &name).await {
+            Ok(packument) => {
+                let wanted = dependency
+                    .specifier
+                    .as_deref()
+                    .and_then(|specifier| resolve_version(&packument, specifier).ok())
+                    .map(|version| version.version.clone());
+
+                json!({
+                    "section": dependency.section,
+                    "specifier": dependency.specifier,
+                    "installed": dependency.installed,
+                    "wanted": wanted,
+                    "latest": packument.dist_tags.get("latest"),
+                })
+            }
+            Err(e) => json!({
+                "section": dependency.section,
+                "specifier": dependency.specifier,
+                "installed": dependency.installed,
+                "error": describe(&e.into()),
+            }),
+        };
+
+        (name, entry)
+    });
+
+    let entries = stream::iter(lookups)
+        .buffer_unordered(app.concurrency())
+        .collect::<BTreeMap<_, _>>()
+        .await;
+
+    Ok(json!(entries))
+}
+
+async fn hover(app: &App, manifest: &PackageJson, name: &str) -> Result<Value> {
+    let dependency = dependency(app, manifest, name);
+    let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+    let packument = get_full_packument(&app.registries, name).await?;
+
+    let latest = resolve_version(&packument, "latest").ok();
+
+    // describe what is installed, the latest version otherwise
+    let shown = dependency
+        .installed
+        .as_ref()
+        .and_then(|version| packument.versions.get(version))
+        .or(latest);
+
+    Ok(json!({
+        "name": name,
+        "section": dependency.section,
+        "specifier": dependency.specifier,
+        "installed": dependency.installed,
+        "latest": latest.map(|version| &version.version),
+        "description": shown.and_then(|version| version.description.as_ref()),
+        "homepage": shown.and_then(|version| version.homepage.as_ref()),
+        "license": shown.and_then(|version| version.license.as_ref()),
+        "deprecated": shown
+            .and_then(|version| version.deprecated.as_ref())
+            .and_then(Value::as_str),
+        "note": lock_file.meta.get(name).map(|meta| &meta.note),
+    }))
+}
+
+fn sections(manifest: &PackageJson) -> [(&'static str, &HashMap<String, String>); 3] {
+    [
+        ("dependencies", &manifest.dependencies),
+        ("devDependencies", &manifest.dev_dependencies),
+        ("optionalDependencies", &manifest.optional_dependencies),
+    ]
+}
+
+fn dependency(app: &App, manifest: &PackageJson, name: &str) -> Dependency {
+    let declared = sections(manifest)
+        .iter()
+        .find_map(|(section, dependencies)| Some((*section, dependencies.get(name)?.clone())));
+
+    Dependency {
+        section: declared.as_ref().map(|(section, _)| *section),
+        specifier: declared.map(|(_, specifier)| specifier),
+        installed: installed_version(app, name),
+    }
+}
+
+fn required<'a>(param: &'a Option<String>, request: &Request) -> Result<&'a str> {
+    Ok(param
+        .as_deref()
+        .ok_or_else(|| VoltError::MissingQueryParam {
+            method: request.method.clone(),
+            param: String::from("name"),
+        })?)
+}
+
+/// The diagnostic code and message of `error`, for plugins to match on and show.
+fn describe(error: &miette::Report) -> Value {
+    json!({
+        "code": error.code().map(|code| code.to_string()),
+        "message": error.to_string(),
+    })
+}
@@ -14,9 +14,18 @@
     limitations under the License.
 */
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
 
-// use crate::commands::scripts::Script;
+use crate::core::utils::env_snapshot::EnvSnapshot;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::lifecycle::ScriptPlan;
+use crate::core::utils::node_runtime;
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::workspace::{
+    dependency_graph, workspace_packages, workspace_root, WorkspacePackage,
+};
 use crate::core::VERSION;
 use crate::App;
 use crate::Command;
@@ -24,6 +33,7 @@ use crate::Command;
 use async_trait::async_trait;
 use colored::Colorize;
 use miette::Result;
+use tracing::info;
 
 /// Struct implementation for the `Run` command.
 pub struct Run;
@@ -37,15 +47,29 @@ impl Command for Run {
     
 Run a pre-defined package script
 
-Usage: {} {} {}
+Usage: {} {} {} {}
     
 Options:
     
+  {} {} Show what would run without running anything.
+  {} Record the environment, tool versions and bins the script runs with.
+  {} {} Run in the workspace packages matching the filter.
+  {} {} Run in every workspace package, dependencies first.
+  {} Number of packages to run at once with `--recursive`.
   {} {} Output verbose messages on internal operations."#,
             VERSION.bright_green().bold(),
             "volt".bright_green().bold(),
             "run".bright_purple(),
-            "file-name".white(),
+            "<script>".white(),
+            "[-- <args>...]".white(),
+            "--dry-run".blue(),
+            "(-n)".yellow(),
+            "--env-snapshot <file>".blue(),
+            "--filter".blue(),
+            "(-F)".yellow(),
+            "--recursive".blue(),
+            "(-r)".yellow(),
+            "--concurrency".blue(),
             "--verbose".blue(),
             "(-v)".yellow()
         )
@@ -53,77 +77,314 @@ Options:
 
     /// Execute the `volt run` command
     ///
-    /// Interactively create or update a package.json file for a project.
+    /// Run a script defined in package.json, along with its `pre` and `post` hooks. With
+    /// `--recursive` it runs in every workspace package, in dependency order. Scripts run with
+    /// the node pinned by volt.toml or `.node-version`, see `volt node`.
     /// ## Arguments
     /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
     /// ## Examples
     /// ```
     /// // Run a defined script.
     /// // .exec() is an async call so you need to await it
-    /// Run.exec(app, vec![], vec!["--yes"]).await;
+    /// Run.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
     async fn exec(app: Arc<App>) -> Result<()> {
-        // if app.clone().args.len() == 1_usize {
-        //     let package_json = PackageJson::from("package.json");
-
-        //     let args = app.args.clone();
-        //     let command: &str = args[0].as_str();
-
-        //     if package_json.scripts.contains_key(command) {
-        //         Script::exec(app.clone()).await.unwrap();
-        //         std::process::exit(0);
-        //     }
-        // }
-
-        // let path = Path::new("node_modules/scripts");
-
-        // if path.exists() {
-        //     let files = read_dir("node_modules/scripts").unwrap();
-
-        //     let mut files_vec: Vec<String> = vec![];
-
-        //     for f in files {
-        //         let f = f.unwrap();
-        //         let file_name = f.file_name();
-        //         let file_name_str = file_name.to_string_lossy();
-
-        //         files_vec.push(file_name_str.to_string());
-        //     }
-
-        //     if app.clone().args.len() == 1_usize {
-        //         let print_string = files_vec.join(", ");
-        //         println!(
-        //             "{}{} {}",
-        //             "scripts".bright_cyan().bold(),
-        //             ":".bright_magenta().bold(),
-        //             print_string
-        //         );
-        //         std::process::exit(1);
-        //     }
-
-        //     if files_vec.contains(&app.args[1]) {
-        //         let location = format!("node_modules/scripts/{}", &app.args[1]);
-
-        //         let command = format!("scripts/{}", &app.args[1]);
-        //         println!("{} {}", ">".bright_magenta().bold(), command);
-
-        //         std::process::Command::new("cmd.exe")
-        //             .arg("/C")
-        //             .arg(location.replace("/", r"\"))
-        //             .spawn()
-        //             .unwrap();
-        //     } else {
-        //         error!(
-        //             "{} 'is not a valid script.'",
-        //             &app.args[1].bright_yellow().bold(),
-        //         );
-        //     }
-        // }
-
-        Ok(())
+        let arguments = app
+            .args
+            .values_of("script-args")
+            .map(|values| values.map(String::from).collect::<Vec<String>>())
+            .unwrap_or_default();
+
+        let recursive = app.has_flag("recursive");
+
+        // a node pinned by volt.toml or .node-version goes on PATH, installed if it's missing
+        let node = node_runtime::pinned_bin(&app).await?;
+
+        // with `--filter` or `--recursive` the script runs in every selected workspace package that has it
+        let members = match app.filtered_packages()? {
+            Some(members) => Some(members),
+            None if recursive => {
+                let root = workspace_root(&app.current_dir).ok_or(VoltError::NotInWorkspace)?;
+                Some(workspace_packages(&root)?)
+            }
+            None => None,
+        };
+
+        if let Some(members) = members {
+            let name = app
+                .args
+                .value_of("script")
+                .ok_or(VoltError::MissingScript {
+                    name: String::new(),
+                })?;
+
+            if recursive {
+                return run_recursive(&app, &members, name, &arguments, node.as_deref());
+            }
+
+            let mut ran = 0;
+
+            for member in members.iter() {
+                let package = PackageJson::read(&member.path.join("package.json"))?;
+
+                if !package.scripts.contains_key(name) {
+                    continue;
+                }
+
+                println!(
+                    "{} {}",
+                    member.name.bright_cyan().bold(),
+                    member.path.display().to_string().bright_black()
+                );
+
+                run_script(
+                    &app,
+                    &member.path,
+                    &package,
+                    name,
+                    &arguments,
+                    node.as_deref(),
+                )?;
+
+                ran += 1;
+            }
+
+            if ran == 0 {
+                return Err(VoltError::MissingScript {
+                    name: name.to_string(),
+                }
+                .into());
+            }
+
+            return Ok(());
+        }
+
+        let package = PackageJson::read(&app.current_dir.join("package.json"))?;
+
+        let name = match app.args.value_of("script") {
+            Some(name) => name,
+            None => {
+                let mut scripts = package.scripts.iter().collect::<Vec<_>>();
+                scripts.sort();
+
+                for (name, command) in scripts {
+                    println!("{}", name.bright_cyan().bold());
+                    println!("  {}", command);
+                }
+
+                return Ok(());
+            }
+        };
+
+        run_script(
+            &app,
+            &app.current_dir,
+            &package,
+            name,
+            &arguments,
+            node.as_deref(),
+        )
     }
 }
+
+/// Run `name` in every package of `members` that has it, in dependency order.
+///
+/// A package starts once the workspace packages it depends on are done, independent packages run
+/// in parallel up to `--concurrency`. Nothing new starts after a failure, and the first failure is
+/// returned once the running scripts are done.
+fn run_recursive(
+    app: &App,
+    members: &[WorkspacePackage],
+    name: &str,
+    arguments: &[String],
+    node: Option<&Path>,
+) -> Result<()> {
+    let graph = dependency_graph(members);
+
+    // packages without the script are done as soon as their dependencies are
+    let mut plans: HashMap<String, ScriptPlan> = HashMap::new();
+
+    for member in members.iter() {
+        let package = PackageJson::read(&member.path.join("package.json"))?;
+
+        if package.scripts.contains_key(name) {
+            let mut plan = ScriptPlan::new(
+                &member.path,
+                &package,
+                name,
+                arguments,
+                app.config.script_shell.as_deref(),
+            )?;
+
+            plan.path_additions.extend(node.map(Path::to_path_buf));
+
+            plans.insert(member.name.clone(), plan);
+        }
+    }
+
+    if plans.is_empty() {
+        return Err(VoltError::MissingScript {
+            name: name.to_string(),
+        }
+        .into());
+    }
+
+    let dry_run = app.has_flag("dry-run");
+
+    // plans are printed one at a time so they don't interleave
+    let concurrency = match app.args.value_of("concurrency") {
+        _ if dry_run => 1,
+        Some(concurrency) => concurrency.parse::<usize>().unwrap_or(1).max(1),
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    let (sender, receiver) = mpsc::channel::<(String, Result<()>)>();
+
+    let mut started: HashSet<String> = HashSet::new();
+    let mut done: HashSet<String> = HashSet::new();
+    let mut running = 0;
+    let mut failure = None;
+
+    loop {
+        // a package without the script finishing right away can unblock others, so scan until
+        // nothing else can start
+        let mut progressed = failure.is_none();
+
+        while progressed {
+            progressed = false;
+
+            for member in members.iter() {
+                if running >= concurrency {
+                    break;
+                }
+
+                if started.contains(&member.name)
+                    || !graph[&member.name].iter().all(|d| done.contains(d))
+                {
+                    continue;
+                }
+
+                started.insert(member.name.clone());
+                progressed = true;
+
+                let plan = match plans.remove(&member.name) {
+                    Some(plan) => plan,
+                    None => {
+                        done.insert(member.name.clone());
+                        continue;
+                    }
+                };
+
+                let sender = sender.clone();
+                let member = member.clone();
+
+                running += 1;
+
+                std::thread::spawn(move || {
+                    println!(
+                        "{} {}",
+                        member.name.bright_cyan().bold(),
+                        member.path.display().to_string().bright_black()
+                    );
+
+                    let result = if dry_run {
+                        plan.print();
+                        Ok(())
+                    } else {
+                        plan.run()
+                    };
+
+                    sender.send((member.name, result)).unwrap();
+                });
+            }
+        }
+
+        if running == 0 {
+            break;
+        }
+
+        let (package, result) = receiver.recv().unwrap();
+
+        running -= 1;
+
+        match result {
+            Ok(()) => {
+                done.insert(package);
+            }
+            Err(error) => {
+                println!(
+                    "{} `{}` failed in {}",
+                    " error ".black().on_bright_red(),
+                    name,
+                    package.bright_cyan()
+                );
+
+                failure.get_or_insert(error);
+            }
+        }
+    }
+
+    if let Some(error) = failure {
+        return Err(error);
+    }
+
+    if done.len() < members.len() {
+        let mut blocked = members
+            .iter()
+            .filter(|member| !done.contains(&member.name))
+            .map(|member| member.name.as_str())
+            .collect::<Vec<_>>();
+
+        blocked.sort_unstable();
+
+        return Err(VoltError::WorkspaceCycle {
+            names: blocked.join(", "),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Run (or with `--dry-run`, print) the script `name` of the package in `directory`.
+fn run_script(
+    app: &App,
+    directory: &Path,
+    package: &PackageJson,
+    name: &str,
+    arguments: &[String],
+    node: Option<&Path>,
+) -> Result<()> {
+    let mut plan = ScriptPlan::new(
+        directory,
+        package,
+        name,
+        arguments,
+        app.config.script_shell.as_deref(),
+    )?;
+
+    // after the bins of node_modules, a package's own `node` bin still wins
+    plan.path_additions.extend(node.map(Path::to_path_buf));
+
+    // recorded before running, a failing script is what the snapshot is usually wanted for
+    if let Some(path) = app.args.value_of("env-snapshot") {
+        let path = app.current_dir.join(path);
+
+        EnvSnapshot::capture(&plan, name).save(&path)?;
+
+        info!(
+            "recorded the environment of `{}` in {}",
+            name,
+            path.display()
+        );
+    }
+
+    if app.has_flag("dry-run") {
+        plan.print();
+        return Ok(());
+    }
+
+    plan.run()
+}
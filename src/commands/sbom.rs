@@ -0,0 +1,124 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Generate a software bill of materials of the lockfile.
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::sbom::{generate, SbomFormat};
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::sync::Arc;
+use tracing::warn;
+
+pub struct Sbom {}
+
+#[async_trait]
+impl Command for Sbom {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Generate a software bill of materials of the locked packages, with their package URLs,
+integrity hashes and licenses.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Format to write, `cyclonedx` (1.4, the default) or `spdx` (2.3).
+  {} {} Path to write, the bill of materials is printed without one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "sbom".bright_purple(),
+            "[flags]".white(),
+            "--format".blue(),
+            "(-f)".yellow(),
+            "--output".blue(),
+            "(-o)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow(),
+        )
+    }
+
+    /// Execute the `volt sbom` command
+    ///
+    /// Writes the locked packages as a CycloneDX or SPDX JSON document, so security
+    /// tooling can ingest projects installed with volt. Licenses are read from
+    /// node_modules, so install before generating one.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Write an SPDX document to sbom.spdx.json
+    /// // .exec() is an async call so you need to await it
+    /// Sbom.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        if !app.lock_file_path.exists() {
+            return Err(VoltError::MissingLockfile.into());
+        }
+
+        let lock_file = LockFile::load(&app.lock_file_path)?;
+
+        let format = match app.args.value_of("format") {
+            Some("spdx") => SbomFormat::Spdx,
+            _ => SbomFormat::CycloneDx,
+        };
+
+        let (document, unlicensed) = generate(&app, &lock_file, format);
+
+        if unlicensed > 0 {
+            warn!(
+                "the license of {} packages isn't known, install before generating to include it",
+                unlicensed
+            );
+        }
+
+        let mut data = serde_json::to_string_pretty(&document).unwrap();
+        data.push('\n');
+
+        let path = match app.args.value_of("output") {
+            Some(output) => app.current_dir.join(output),
+            None => {
+                print!("{}", data);
+                return Ok(());
+            }
+        };
+
+        std::fs::write(&path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        println!(
+            "{}: wrote a bill of materials of {} packages to {}",
+            "success".bright_green(),
+            lock_file.dependencies.len(),
+            path.display().to_string().bright_cyan()
+        );
+
+        Ok(())
+    }
+}
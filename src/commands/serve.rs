@@ -0,0 +1,452 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Serve a local read-through caching mirror of the npm registry.
+
+use crate::core::model::http_manager::client;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::hex_to_integrity;
+use crate::core::utils::registry_cache::RegistryCache;
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use hyper::header::{ACCEPT, CONTENT_TYPE, HOST};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use isahc::AsyncReadResponseExt;
+use miette::Result;
+use serde_json::Value;
+use ssri::Integrity;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Accept header npm sends for the abbreviated install documents.
+const ABBREVIATED: &str = "application/vnd.npm.install-v1+json";
+
+pub struct ServeRegistry {}
+
+/// Shared by every request the mirror serves.
+struct Mirror {
+    cache: RegistryCache,
+    /// Registry documents and tarballs are fetched from.
+    upstream: String,
+    /// How long a cached document is served without asking the registry again.
+    max_age: Duration,
+    /// Never contact the registry, serve whatever is cached.
+    offline: bool,
+}
+
+/// What a response was served from, for the request log.
+enum Source {
+    Registry,
+    Cache,
+    Stale,
+}
+
+#[async_trait]
+impl Command for ServeRegistry {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Serve a caching mirror of the npm registry that npm, yarn or other volt installs can point at.
+
+Usage: {} {} {}
+
+Options:
+
+  {} Address to listen on, defaults to 127.0.0.1.
+  {} {} Port to listen on, defaults to 4873.
+  {} Seconds a cached document is served without asking the registry, defaults to 300.
+  {} Only serve what is already cached.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "serve-registry".bright_purple(),
+            "[flags]".white(),
+            "--host".blue(),
+            "--port".blue(),
+            "(-p)".yellow(),
+            "--max-age".blue(),
+            "--offline".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt serve-registry` command
+    ///
+    /// Serve package documents and tarballs from the cache in the volt directory, fetching
+    /// and caching what's missing from the npm registry. Tarball URLs in the documents point
+    /// back at the mirror, and when the registry can't be reached cached copies are served
+    /// however old they are.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Serve the mirror on http://127.0.0.1:4873
+    /// // .exec() is an async call so you need to await it
+    /// ServeRegistry.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let host = app.args.value_of("host").unwrap_or("127.0.0.1");
+        let port = app.args.value_of("port").unwrap_or("4873");
+
+        let address = format!("{}:{}", host, port);
+
+        let socket: SocketAddr = address.parse().map_err(|_| VoltError::InvalidAddress {
+            address: address.clone(),
+        })?;
+
+        let max_age = app
+            .args
+            .value_of("max-age")
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(300);
+
+        let mirror = Arc::new(Mirror {
+            cache: RegistryCache::new(&app.volt_dir),
+            upstream: app.registry(),
+            max_age: Duration::from_secs(max_age),
+            offline: app.has_flag("offline"),
+        });
+
+        let service = make_service_fn(move |_| {
+            let mirror = mirror.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let mirror = mirror.clone();
+                    async move { Ok::<_, Infallible>(handle(&mirror, request).await) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&socket)
+            .map_err(|e| VoltError::RegistryServerError {
+                address: address.clone(),
+                source: e,
+            })?
+            .serve(service);
+
+        println!(
+            "{}: serving the registry mirror on {} from {}",
+            "success".bright_green(),
+            format!("http://{}", address).bright_cyan(),
+            app.volt_dir.join("registry").display()
+        );
+
+        info!(
+            "point npm at it with `npm config set registry http://{}/`",
+            address
+        );
+
+        server
+            .await
+            .map_err(|e| VoltError::RegistryServerError { address, source: e })?;
+
+        Ok(())
+    }
+}
+
+async fn handle(mirror: &Mirror, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path().to_string();
+
+    let response = if request.method() != Method::GET {
+        Err(StatusCode::METHOD_NOT_ALLOWED)
+    } else {
+        // `@scope%2fname` and `@scope/name` are the same package
+        let path = path
+            .trim_start_matches('/')
+            .replace("%2f", "/")
+            .replace("%2F", "/");
+
+        match path.split_once("/-/") {
+            Some((name, file)) => serve_tarball(mirror, name, file).await,
+            None if !path.is_empty() => serve_packument(mirror, &request, &path).await,
+            None => Err(StatusCode::NOT_FOUND),
+        }
+    };
+
+    let (response, source) = match response {
+        Ok(served) => served,
+        Err(status) => {
+            let body = serde_json::json!({ "error": status.canonical_reason() }).to_string();
+
+            let response = Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+
+            (response, Source::Registry)
+        }
+    };
+
+    let source = match source {
+        Source::Registry => "",
+        Source::Cache => " (cache)",
+        Source::Stale => " (stale cache)",
+    };
+
+    println!(
+        "{} {} {}{}",
+        request.method(),
+        path,
+        response.status().as_str().bright_black(),
+        source.bright_black()
+    );
+
+    response
+}
+
+/// Fetch `url` from the registry, `Ok(None)` when it has nothing there.
+async fn fetch(url: &str, accept: &str) -> std::result::Result<Option<Vec<u8>>, StatusCode> {
+    let request = Request::get(url)
+        .header(ACCEPT, accept)
+        .body(())
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    let mut response = client()
+        .send_async(request)
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    match response.status() {
+        StatusCode::OK => {
+            let mut body = vec![];
+
+            response
+                .copy_to(&mut body)
+                .await
+                .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+            Ok(Some(body))
+        }
+        StatusCode::NOT_FOUND => Ok(None),
+        _ => Err(StatusCode::BAD_GATEWAY),
+    }
+}
+
+async fn serve_packument(
+    mirror: &Mirror,
+    request: &Request<Body>,
+    name: &str,
+) -> std::result::Result<(Response<Body>, Source), StatusCode> {
+    let abbreviated = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(ABBREVIATED));
+
+    let accept = if abbreviated {
+        ABBREVIATED
+    } else {
+        "application/json"
+    };
+
+    let path = mirror.cache.packument_path(name, abbreviated);
+
+    let fresh = if mirror.offline {
+        None
+    } else {
+        mirror.cache.read(&path, Some(mirror.max_age))
+    };
+
+    let (document, source) = match fresh {
+        Some(document) => (document, Source::Cache),
+        None if mirror.offline => (
+            mirror
+                .cache
+                .read(&path, None)
+                .ok_or(StatusCode::NOT_FOUND)?,
+            Source::Cache,
+        ),
+        None => {
+            let url = format!("{}/{}", mirror.upstream, name.replace('/', "%2f"));
+
+            match fetch(&url, accept).await {
+                Ok(Some(document)) => {
+                    mirror
+                        .cache
+                        .write(&path, &document)
+                        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+                    (document, Source::Registry)
+                }
+                Ok(None) => return Err(StatusCode::NOT_FOUND),
+                // the registry is down or unreachable, old documents beat none
+                Err(status) => (mirror.cache.read(&path, None).ok_or(status)?, Source::Stale),
+            }
+        }
+    };
+
+    let base = match request.headers().get(HOST).and_then(|h| h.to_str().ok()) {
+        Some(host) => format!("http://{}", host),
+        None => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let mut document: Value =
+        serde_json::from_slice(&document).map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    // tarballs are downloaded through the mirror too
+    if let Some(versions) = document["versions"].as_object_mut() {
+        for version in versions.values_mut() {
+            if let Some(tarball) = version["dist"]["tarball"].as_str() {
+                if let Some(rest) = tarball.strip_prefix(mirror.upstream.as_str()) {
+                    version["dist"]["tarball"] = Value::String(format!("{}{}", base, rest));
+                }
+            }
+        }
+    }
+
+    let response = Response::builder()
+        .header(CONTENT_TYPE, accept)
+        .body(Body::from(document.to_string()))
+        .unwrap();
+
+    Ok((response, source))
+}
+
+/// The integrity of the tarball `file` of `name`, from the cached document of the package or
+/// the registry's when no cached one lists it yet.
+async fn expected_integrity(mirror: &Mirror, name: &str, file: &str) -> Option<Integrity> {
+    let cached = [true, false]
+        .iter()
+        .filter_map(|&abbreviated| {
+            mirror
+                .cache
+                .read(&mirror.cache.packument_path(name, abbreviated), None)
+        })
+        .find_map(|document| listed_integrity(&document, file));
+
+    if cached.is_some() {
+        return cached;
+    }
+
+    let url = format!("{}/{}", mirror.upstream, name.replace('/', "%2f"));
+    let document = fetch(&url, ABBREVIATED).await.ok()??;
+
+    listed_integrity(&document, file)
+}
+
+/// The integrity `document` lists for the tarball `file`, or its sha1 when it has none.
+fn listed_integrity(document: &[u8], file: &str) -> Option<Integrity> {
+    let document = serde_json::from_slice::<Value>(document).ok()?;
+    let suffix = format!("/-/{}", file);
+
+    let dist = document["versions"]
+        .as_object()?
+        .values()
+        .map(|version| &version["dist"])
+        .find(|dist| {
+            dist["tarball"]
+                .as_str()
+                .is_some_and(|tarball| tarball.ends_with(&suffix))
+        })?;
+
+    match dist["integrity"].as_str() {
+        Some(integrity) => integrity.parse().ok(),
+        None => hex_to_integrity(dist["shasum"].as_str()?)?.parse().ok(),
+    }
+}
+
+async fn serve_tarball(
+    mirror: &Mirror,
+    name: &str,
+    file: &str,
+) -> std::result::Result<(Response<Body>, Source), StatusCode> {
+    if file.contains('/') || file.contains("..") {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let path = mirror.cache.tarball_path(name, file);
+
+    // published tarballs never change, and one is only cached when it matched its integrity
+    let (tarball, source) = match mirror.cache.read(&path, None) {
+        Some(tarball) => (tarball, Source::Cache),
+        None if mirror.offline => return Err(StatusCode::NOT_FOUND),
+        None => {
+            let url = format!("{}/{}/-/{}", mirror.upstream, name, file);
+
+            let tarball = fetch(&url, "application/octet-stream")
+                .await?
+                .ok_or(StatusCode::NOT_FOUND)?;
+
+            let expected = expected_integrity(mirror, name, file)
+                .await
+                .ok_or(StatusCode::BAD_GATEWAY)?;
+
+            if expected.check(&tarball).is_err() {
+                warn!("{} doesn't match its integrity, not caching it", url);
+
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+
+            mirror
+                .cache
+                .write(&path, &tarball)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            (tarball, Source::Registry)
+        }
+    };
+
+    let response = Response::builder()
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .body(Body::from(tarball))
+        .unwrap();
+
+    Ok((response, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_integrity_of_listed_tarballs() {
+        let document = serde_json::json!({
+            "versions": {
+                "1.0.0": { "dist": {
+                    "tarball": "https://registry.npmjs.org/@scope/pkg/-/pkg-1.0.0.tgz",
+                    "integrity": "sha512-AAAA",
+                    "shasum": "0000",
+                } },
+                "2.0.0": { "dist": {
+                    "tarball": "https://registry.npmjs.org/@scope/pkg/-/pkg-2.0.0.tgz",
+                    "shasum": "0a0b",
+                } },
+            },
+        })
+        .to_string();
+
+        let listed = |file| listed_integrity(document.as_bytes(), file).map(|i| i.to_string());
+
+        assert_eq!(listed("pkg-1.0.0.tgz").as_deref(), Some("sha512-AAAA"));
+        // documents from before integrity existed only have the sha1
+        assert_eq!(listed("pkg-2.0.0.tgz").as_deref(), Some("sha1-Cgs="));
+        assert_eq!(listed("pkg-3.0.0.tgz"), None);
+    }
+}
@@ -0,0 +1,209 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Sign the lockfile and install summary of a project, and check their signatures.
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::signing::{
+    expand_home, sign_install, verify_file, InstallSummary, SecretKey,
+};
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+/// Struct implementation for the `Sign` command.
+pub struct Sign;
+
+#[async_trait]
+impl Command for Sign {
+    /// Display a help menu for the `volt sign` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Signs volt.lock and the install summary with the project key, or checks their signatures.
+
+Usage: {} {} {} {}
+
+Commands:
+
+  {} Create a key pair, the secret key goes unencrypted to `--output`.
+  {} Check volt.lock and the install summary against `trusted-keys`.
+
+Options:
+
+  {} {} Where `keygen` writes the secret key, `~/.volt/keys/<project>.key` by default.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "sign".bright_purple(),
+            "[command]".white(),
+            "[flags]".white(),
+            "keygen".bright_blue(),
+            "verify".bright_blue(),
+            "--output".blue(),
+            "(-o)".yellow(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt sign` command
+    ///
+    /// Without a command, signs volt.lock and writes and signs the install summary with the
+    /// key from `signing.key` or `VOLT_SIGNING_KEY`. `keygen` creates a key pair and prints
+    /// the public key to add to `trusted-keys`, `verify` checks both signatures.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Sign.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        match app.args.value_of("command") {
+            Some("keygen") => keygen(&app),
+            Some("verify") => verify(&app),
+            _ => sign(&app),
+        }
+    }
+}
+
+fn keygen(app: &App) -> Result<()> {
+    let path = match app.args.value_of("output") {
+        Some(path) => expand_home(&app.current_dir, &PathBuf::from(path)),
+        None => {
+            let project = app
+                .current_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| String::from("project"));
+
+            app.volt_dir.join("keys").join(format!("{}.key", project))
+        }
+    };
+
+    // a lost secret key can't be recovered, never replace one
+    if path.exists() {
+        miette::bail!(
+            "{} already exists, pass `--output` to create the key elsewhere",
+            path.display()
+        );
+    }
+
+    let key = SecretKey::generate()?;
+
+    std::fs::create_dir_all(path.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+    let write_error = |e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    };
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    // only the owner may ever read the key, it's created that way rather than narrowed after
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&path).map_err(write_error)?;
+
+    file.write_all(key.encode()?.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(write_error)?;
+
+    println!(
+        "{}: wrote the secret key to {}",
+        "success".bright_green(),
+        path.display()
+    );
+
+    println!(
+        "\nAdd the public key to volt.toml:\n\n  [signing]\n  key = \"{}\"\n  trusted-keys = [\"{}\"]\n",
+        path.display(),
+        key.public_key()?
+    );
+
+    info!("keep the secret key out of the repository, CI can pass it in `VOLT_SIGNING_KEY`.");
+
+    Ok(())
+}
+
+fn sign(app: &App) -> Result<()> {
+    if SecretKey::load(app)?.is_none() {
+        miette::bail!(
+            "no signing key, set `key` under `[signing]` in volt.toml or `VOLT_SIGNING_KEY`"
+        );
+    }
+
+    let lock_file = LockFile::load(&app.lock_file_path)?;
+
+    sign_install(app, &lock_file, true)?;
+
+    println!(
+        "{}: signed volt.lock and {}",
+        "success".bright_green(),
+        InstallSummary::path(app).display()
+    );
+
+    Ok(())
+}
+
+fn verify(app: &App) -> Result<()> {
+    let trusted = &app.config.signing.trusted_keys;
+
+    if trusted.is_empty() {
+        miette::bail!("no `trusted-keys` under `[signing]` in volt.toml to check signatures with");
+    }
+
+    let mut failed = 0;
+
+    for path in [app.lock_file_path.clone(), InstallSummary::path(app)] {
+        match verify_file(&path, trusted) {
+            Ok(comment) => println!(
+                "  {} {} {}",
+                "✓".bright_green(),
+                path.display(),
+                format!("({})", comment.replace('\t', ", ")).bright_black()
+            ),
+            Err(reason) => {
+                failed += 1;
+                println!("  {} {}", "✗".bright_red(), reason);
+            }
+        }
+    }
+
+    if failed > 0 {
+        miette::bail!("{} signatures failed to verify", failed);
+    }
+
+    println!("{}: every signature is valid", "success".bright_green());
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Manage the package store shared by every project on the machine.
+
+use crate::core::utils::virtual_store;
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::sync::Arc;
+
+/// Struct implementation for the `Store` command.
+pub struct Store;
+
+#[async_trait]
+impl Command for Store {
+    /// Display a help menu for the `volt store` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Manages the package store shared by every project on the machine.
+
+Usage: {} {} {}
+
+Commands:
+
+  {} Remove shared virtual store entries no project uses anymore.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "store".bright_purple(),
+            "<command>".white(),
+            "prune".bright_blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt store` command
+    ///
+    /// `prune` counts the references of every entry of the shared virtual store, dropping the
+    /// ones of projects that were deleted or no longer use the entry, and removes the entries
+    /// left without any. Packages in the store itself are left alone, projects hard link them.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Store.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let removed = virtual_store::prune(&app.store_dir()).await?;
+
+        for entry in removed.iter() {
+            println!("  {} {}", "-".bright_red().bold(), entry.bright_black());
+        }
+
+        println!(
+            "{}: removed {} unused entries from the shared virtual store",
+            "success".bright_green(),
+            removed.len()
+        );
+
+        Ok(())
+    }
+}
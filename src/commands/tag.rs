@@ -11,12 +11,18 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Manage the dist-tags of a package, names like `latest` and `next` that point at versions.
 
+use crate::commands::owner::package_name;
+use crate::core::model::http_manager::{get_packument, remove_dist_tag, set_dist_tag};
+use crate::core::utils::auth::Credentials;
+use crate::core::utils::errors::VoltError;
+use crate::core::VERSION;
 use crate::App;
 use crate::Command;
 
 use async_trait::async_trait;
+use colored::Colorize;
 use miette::Result;
 use std::sync::Arc;
 
@@ -25,23 +31,159 @@ pub struct Tag {}
 #[async_trait]
 impl Command for Tag {
     fn help() -> String {
-        todo!()
+        format!(
+            r#"volt {}
+
+Lists, adds or removes the dist-tags of a package on the registry.
+
+Usage: {} {} {} {}
+
+Commands:
+
+  {} {}  Point a tag at a version, `latest` by default.
+  {} {}            Remove a tag, except `latest`.
+  {} {}                  List the tags of the package.
+
+The package defaults to the one in the current directory.
+
+Options:
+
+  {} Registry of the package instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "dist-tag".bright_purple(),
+            "<command>".white(),
+            "[package] [tag]".white(),
+            "add".blue(),
+            "<package>@<version> [tag]".white(),
+            "rm".blue(),
+            "<package> <tag>".white(),
+            "ls".blue(),
+            "[package]".white(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
     }
 
-    /// Execute the `volt outdated` command
+    /// Execute the `volt dist-tag` command
     ///
-    /// Check for outdated packages
+    /// Lists the dist-tags of a package, or moves or removes one with the token of its
+    /// registry. A tag is only pointed at a version the registry has.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `app` - Instance of the command (`Arc<App>`)
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Point `next` at a prerelease
     /// // .exec() is an async call so you need to await it
     /// Tag.exec(app).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(_app: Arc<App>) -> Result<()> {
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let mut arguments = app
+            .args
+            .values_of("arguments")
+            .into_iter()
+            .flatten()
+            .map(String::from);
+
+        let command = app.args.value_of("command").unwrap_or("ls");
+
+        let missing = |needs: &str| VoltError::MissingDistTagArgument {
+            command: command.to_string(),
+            needs: needs.to_string(),
+        };
+
+        match command {
+            "add" => {
+                let spec = arguments
+                    .next()
+                    .ok_or_else(|| missing("a package and version"))?;
+
+                let (name, version) = match spec.rfind('@') {
+                    Some(index) if index > 0 => (&spec[..index], &spec[index + 1..]),
+                    _ => return Err(missing("the version to tag, `<package>@<version>`").into()),
+                };
+
+                let tag = arguments.next().unwrap_or_else(|| String::from("latest"));
+
+                if node_semver::Range::parse(&tag).is_ok() {
+                    return Err(VoltError::InvalidDistTag { tag }.into());
+                }
+
+                let packument = get_packument(&app.registries, name).await?;
+
+                if !packument.versions.contains_key(version) {
+                    return Err(VoltError::NoMatchingVersion {
+                        package_name: name.to_string(),
+                        range: version.to_string(),
+                    }
+                    .into());
+                }
+
+                if packument.dist_tags.get(&tag).map(String::as_str) == Some(version) {
+                    println!("{} already points at {}@{}", tag, name, version);
+                    return Ok(());
+                }
+
+                let registry = app.registries.for_package(name).to_string();
+                let credentials = Credentials::new(&app, &registry);
+
+                set_dist_tag(&registry, &credentials, name, &tag, version).await?;
+
+                println!(
+                    "{}: {} {}@{}",
+                    "success".bright_green(),
+                    format!("+{}:", tag).bright_green(),
+                    name,
+                    version.bright_cyan()
+                );
+            }
+            "rm" => {
+                let name = arguments.next().ok_or_else(|| missing("a package"))?;
+                let tag = arguments
+                    .next()
+                    .ok_or_else(|| missing("the tag to remove"))?;
+
+                if tag == "latest" {
+                    return Err(VoltError::RemoveLatestTag { package: name }.into());
+                }
+
+                let packument = get_packument(&app.registries, &name).await?;
+
+                let version = match packument.dist_tags.get(&tag) {
+                    Some(version) => version,
+                    None => return Err(VoltError::NoSuchDistTag { tag, package: name }.into()),
+                };
+
+                let registry = app.registries.for_package(&name).to_string();
+                let credentials = Credentials::new(&app, &registry);
+
+                remove_dist_tag(&registry, &credentials, &name, &tag).await?;
+
+                println!(
+                    "{}: {} {}@{}",
+                    "success".bright_green(),
+                    format!("-{}:", tag).bright_red(),
+                    name,
+                    version.bright_cyan()
+                );
+            }
+            _ => {
+                let name = package_name(&app, arguments.next())?;
+                let packument = get_packument(&app.registries, &name).await?;
+
+                let mut tags = packument.dist_tags.iter().collect::<Vec<_>>();
+                tags.sort_by_key(|(tag, _)| (*tag != "latest", tag.to_string()));
+
+                for (tag, version) in tags {
+                    println!("{}: {}", tag, version.bright_cyan());
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,170 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Remove a package or a single version of it from the registry.
+
+use crate::core::model::http_manager::unpublish_package;
+use crate::core::model::lock_file::LockFile;
+use crate::core::prompt::prompts::Input;
+use crate::core::utils::auth::Credentials;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::npm::parse_versions;
+use crate::core::VERSION;
+use crate::App;
+use crate::Command;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use std::sync::Arc;
+
+pub struct Unpublish {}
+
+#[async_trait]
+impl Command for Unpublish {
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Remove a package or a single version of it from the registry.
+
+Usage: {} {} {} {}
+
+Options:
+
+  {} {} Confirm that you really want to unpublish.
+  {} Registry to unpublish from instead of the configured one.
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "unpublish".bright_purple(),
+            "<package>[@version]".white(),
+            "[flags]".white(),
+            "--force".blue(),
+            "(-f)".yellow(),
+            "--registry <url>".blue(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt unpublish` command
+    ///
+    /// Remove a package or a single version of it from the registry
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // Unpublish version 1.0.0 of my-package
+    /// // .exec() is an async call so you need to await it
+    /// Unpublish.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let spec = app.args.value_of("package").unwrap().to_string();
+
+        let package = match parse_versions(&vec![spec.clone()])?.pop() {
+            Some(package) => package,
+            None => miette::bail!("`{}` is not a valid package specifier", spec),
+        };
+
+        if !app.has_flag("force") {
+            return Err(VoltError::UnpublishWithoutForce)?;
+        }
+
+        // refuse to pull the rug out from under packages this project depends on
+        let lock_file = LockFile::load(&app.lock_file_path)
+            .unwrap_or_else(|_| LockFile::new(&app.lock_file_path));
+
+        let locked_version = lock_file
+            .dependencies
+            .values()
+            .find(|lock| lock.name == package.name)
+            .map(|lock| lock.version.clone());
+
+        let affected = match &package.version {
+            Some(version) => locked_version.as_ref() == Some(version),
+            None => locked_version.is_some(),
+        };
+
+        if affected {
+            let mut dependents = lock_file
+                .dependencies
+                .values()
+                .filter(|lock| lock.dependencies.contains(&package.name))
+                .map(|lock| format!("{}@{}", lock.name, lock.version))
+                .collect::<Vec<String>>();
+
+            dependents.sort();
+
+            if !dependents.is_empty() {
+                return Err(VoltError::UnpublishDependents {
+                    package_name: package.name,
+                    dependents: dependents.join(", "),
+                })?;
+            }
+        }
+
+        let registry = app.registries.for_package(&package.name).to_string();
+
+        let target = match &package.version {
+            Some(version) => format!("{}@{}", package.name, version),
+            None => format!("{} (every version)", package.name),
+        };
+
+        println!(
+            "{}: this will permanently remove {} from {}",
+            "warning".bright_yellow().bold(),
+            target.bright_cyan(),
+            registry
+        );
+
+        let confirmation = Input {
+            message: format!("type `{}` to confirm", package.name),
+            default: None,
+            allow_empty: false,
+        }
+        .run()
+        .into_diagnostic()?;
+
+        if confirmation.trim() != package.name {
+            miette::bail!("confirmation did not match `{}`, aborting", package.name);
+        }
+
+        let credentials = Credentials::new(&app, &registry);
+
+        unpublish_package(
+            &registry,
+            &credentials,
+            &package.name,
+            package.version.as_deref(),
+        )
+        .await
+        .map_err(|report| match report.downcast::<VoltError>() {
+            Ok(VoltError::RegistryError { code, message, .. }) if code.starts_with('4') => {
+                VoltError::UnpublishRejected {
+                    package_name: package.name.clone(),
+                    message,
+                }
+                .into()
+            }
+            Ok(error) => error.into(),
+            Err(report) => report,
+        })?;
+
+        println!("{}: unpublished {}", "success".bright_green(), target);
+
+        Ok(())
+    }
+}
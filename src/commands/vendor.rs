@@ -0,0 +1,269 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Copy a dependency into the repository and depend on the copy.
+//!
+//! The package is verified against its integrity like any download, copied into
+//! `vendor/<name>`, and package.json is changed to `file:vendor/<name>`. Where the copy came
+//! from is recorded in `vendor/volt-vendor.json`, so a review can tell it apart from a
+//! modified one:
+//!
+//! ```json
+//! {
+//!   "left-pad": {
+//!     "version": "1.3.0",
+//!     "integrity": "sha512-...",
+//!     "tarball": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+//!     "vendored-at": "2026-10-17T09:12:44+00:00"
+//!   }
+//! }
+//! ```
+
+use crate::core::model::http_manager::get_packument;
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::constants::PROGRESS_CHARS;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::fs_utils::{copy_tree, tree_size, Copied, FileMode};
+use crate::core::utils::linker::{self, remove_path};
+use crate::core::utils::npm::{parse_versions, resolve_version, to_volt_package};
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::progress::PackageProgress;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::core::utils::workspace::local_path;
+use crate::core::utils::{download_tarball, satisfies, zero_install};
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use miette::{IntoDiagnostic, Result};
+use node_semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Directory of the project vendored packages are copied into.
+const VENDOR_DIR: &str = "vendor";
+
+/// File in `VENDOR_DIR` recording where every vendored package came from.
+const PROVENANCE_FILE: &str = "volt-vendor.json";
+
+/// Where a vendored package came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Provenance {
+    version: String,
+    integrity: String,
+    tarball: String,
+    vendored_at: String,
+}
+
+/// Struct implementation for the `Vendor` command.
+pub struct Vendor;
+
+#[async_trait]
+impl Command for Vendor {
+    /// Display a help menu for the `volt vendor` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Copies a dependency into vendor/ and depends on the copy instead of the registry.
+
+Usage: {} {} {} {}
+
+Without a version, the one in volt.lock is vendored.
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "vendor".bright_purple(),
+            "[package]".white(),
+            "[flags]".white(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt vendor` command
+    ///
+    /// Downloads the package, checks its integrity, copies it into `vendor/<name>` and
+    /// rewrites its dependency in package.json to `file:vendor/<name>`. The version is the
+    /// one given, else the locked one, else the latest matching the range in package.json.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Vendor.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let spec = app.args.value_of("package").unwrap();
+
+        let requested = parse_versions(&vec![spec.to_string()])?.remove(0);
+
+        let manifest_path = app.current_dir.join("package.json");
+        let manifest = PackageJson::read(&manifest_path)?;
+
+        let declared = manifest
+            .dependencies
+            .get(&requested.name)
+            .or_else(|| manifest.dev_dependencies.get(&requested.name))
+            .or_else(|| manifest.optional_dependencies.get(&requested.name))
+            .filter(|declared| local_path(declared).is_none());
+
+        let package = match &requested.version {
+            Some(version) => resolve(&app, &requested.name, version).await?,
+            None => match locked(&app, &requested.name, declared.map(String::as_str))? {
+                Some(package) => package,
+                None => {
+                    resolve(
+                        &app,
+                        &requested.name,
+                        declared.map(String::as_str).unwrap_or(""),
+                    )
+                    .await?
+                }
+            },
+        };
+
+        // the store only keeps packages whose integrity matched
+        download_tarball(&app, &package, &PackageProgress::hidden()).await?;
+
+        let vendor_dir = app.current_dir.join(VENDOR_DIR);
+        let destination = vendor_dir.join(&package.name);
+
+        // whatever was vendored before is replaced
+        if std::fs::symlink_metadata(&destination).is_ok() {
+            remove_path(&destination).map_err(|e| write_error(&destination, e))?;
+        }
+
+        std::fs::create_dir_all(destination.parent().unwrap())
+            .map_err(VoltError::CreateDirError)?;
+
+        let source = linker::store_path(&app, &package);
+
+        let progress_bar = ProgressBar::new(tree_size(&source).files);
+
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .progress_chars(PROGRESS_CHARS)
+                .template(&format!(
+                    "{} [{{bar:40.magenta/blue}}] {{pos}} / {{len}} files",
+                    "Vendoring".bright_blue()
+                )),
+        );
+
+        let copied = copy_tree(
+            &source,
+            &destination,
+            FileMode::Copy,
+            Some(&|copied: Copied| progress_bar.set_position(copied.files)),
+        );
+
+        progress_bar.finish_and_clear();
+
+        copied.map_err(|e| write_error(&destination, e))?;
+
+        let provenance_path = vendor_dir.join(PROVENANCE_FILE);
+
+        let mut provenance: BTreeMap<String, Provenance> =
+            match provenance_path.exists() {
+                true => serde_json::from_str(&std::fs::read_to_string(&provenance_path).map_err(
+                    |e| VoltError::ReadFileError {
+                        source: e,
+                        name: provenance_path.display().to_string(),
+                    },
+                )?)
+                .into_diagnostic()?,
+                false => BTreeMap::new(),
+            };
+
+        provenance.insert(
+            package.name.clone(),
+            Provenance {
+                version: package.version.clone(),
+                integrity: package.integrity.clone(),
+                tarball: package.tarball.clone(),
+                vendored_at: Utc::now().to_rfc3339(),
+            },
+        );
+
+        let mut data = serde_json::to_string_pretty(&provenance).into_diagnostic()?;
+        data.push('\n');
+
+        std::fs::write(&provenance_path, data).map_err(|e| write_error(&provenance_path, e))?;
+
+        PackageJson::replace_dependency(
+            &manifest_path,
+            &package.name,
+            &format!("file:{}/{}", VENDOR_DIR, package.name),
+        )?;
+
+        println!(
+            "{}: vendored {}@{} into {}, run `volt install` to link it",
+            "success".bright_green(),
+            package.name.bright_cyan(),
+            package.version,
+            destination
+                .strip_prefix(&app.current_dir)
+                .unwrap_or(&destination)
+                .display()
+        );
+
+        Ok(())
+    }
+}
+
+/// The version of `name` in volt.lock, the one matching `declared` when several are.
+fn locked(app: &App, name: &str, declared: Option<&str>) -> Result<Option<VoltPackage>> {
+    let lock_file = LockFile::load_or_new(&app.lock_file_path)?;
+
+    let mut locks = lock_file
+        .dependencies
+        .values()
+        .filter(|lock| lock.name == name && lock.alias.is_none())
+        .collect::<Vec<_>>();
+
+    locks.sort_by_key(|lock| Version::parse(&lock.version).ok());
+
+    let lock = locks
+        .iter()
+        .rev()
+        .find(|lock| declared.is_none_or(|range| satisfies(&lock.version, range)))
+        .or_else(|| locks.last());
+
+    Ok(lock.map(|lock| zero_install::locked_package(lock)))
+}
+
+/// The version of `name` the registry has for `range`.
+async fn resolve(app: &App, name: &str, range: &str) -> Result<VoltPackage> {
+    let packument = get_packument(&app.registries, name).await?;
+
+    to_volt_package(resolve_version(&packument, range)?)
+}
+
+fn write_error(path: &Path, source: std::io::Error) -> VoltError {
+    VoltError::WriteFileError {
+        source,
+        name: path.display().to_string(),
+    }
+}
@@ -0,0 +1,168 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Verify the contents of node_modules against the lockfile.
+
+use crate::core::model::lock_file::{DependencyLock, LockFile};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::installed_version;
+use crate::core::utils::linker::stored_version_path;
+use crate::core::utils::verify::{file_hashes, verify_files};
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Struct implementation for the `Verify` command.
+pub struct Verify;
+
+#[async_trait]
+impl Command for Verify {
+    /// Display a help menu for the `volt verify` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Verifies every installed package against the lockfile, file by file.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "verify".bright_purple(),
+            "[flags]".white(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt verify` command
+    ///
+    /// Reports locked packages that are missing or installed at another version, and hashes
+    /// the files of every installed one. They are compared against the hashes recorded with
+    /// `--file-integrity`, or else against the copy in the store, which was checked against
+    /// the registry's integrity when it was extracted.
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Verify.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let lock_file = LockFile::load(&app.lock_file_path)?;
+
+        // a lockfile can list several versions of a package, only one of them is installed
+        let mut locked: BTreeMap<&str, Vec<&DependencyLock>> = BTreeMap::new();
+
+        for lock in lock_file.dependencies.values() {
+            locked.entry(lock.install_name()).or_default().push(lock);
+        }
+
+        let mut problems = 0;
+        let mut unverified = vec![];
+
+        for (name, locks) in locked.iter() {
+            let installed = match installed_version(&app, name) {
+                Some(installed) => installed,
+                None => {
+                    if locks.iter().all(|lock| lock.optional) {
+                        continue;
+                    }
+
+                    problems += 1;
+                    println!("{} {} is not installed", "missing".bright_red(), name);
+                    continue;
+                }
+            };
+
+            let lock = match locks.iter().find(|lock| lock.version == installed) {
+                Some(lock) => lock,
+                None => {
+                    problems += 1;
+                    println!(
+                        "{} {}@{} is installed, the lockfile has {}",
+                        "version".bright_red(),
+                        name,
+                        installed,
+                        locks
+                            .iter()
+                            .map(|lock| lock.version.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    continue;
+                }
+            };
+
+            let stored = stored_version_path(&app, &lock.name, &lock.version, &lock.integrity);
+
+            let expected = if !lock.files.is_empty() {
+                lock.files.clone()
+            } else if stored.is_dir() {
+                file_hashes(&stored)?
+            } else {
+                unverified.push(format!("{}@{}", name, lock.version));
+                continue;
+            };
+
+            let report = verify_files(&app.node_modules_dir.join(name), &expected)?;
+
+            if report.is_clean() {
+                continue;
+            }
+
+            problems += 1;
+
+            for (label, paths) in [
+                ("modified", &report.modified),
+                ("missing", &report.missing),
+                ("added", &report.added),
+            ] {
+                for path in paths.iter() {
+                    println!("{} {}/{}", label.bright_red(), name, path);
+                }
+            }
+        }
+
+        if !unverified.is_empty() {
+            warn!(
+                "no file hashes or store copy to compare {} against, reinstall with `--file-integrity` to verify them",
+                unverified.join(", ")
+            );
+        }
+
+        if problems > 0 {
+            return Err(VoltError::CheckFailed { problems }.into());
+        }
+
+        println!(
+            "{}: verified {} packages against the lockfile",
+            "success".bright_green(),
+            locked.len() - unverified.len()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Explain why a package is in the dependency tree.
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::package::PackageJson;
+use crate::core::{command::Command, VERSION};
+use crate::App;
+
+use async_trait::async_trait;
+use colored::Colorize;
+use miette::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Struct implementation for the `Why` command.
+pub struct Why;
+
+#[async_trait]
+impl Command for Why {
+    /// Display a help menu for the `volt why` command.
+    fn help() -> String {
+        format!(
+            r#"volt {}
+
+Shows which direct dependencies pull a package in, and why they were added.
+
+Usage: {} {} {}
+
+Options:
+
+  {} {} Output verbose messages on internal operations."#,
+            VERSION.bright_green().bold(),
+            "volt".bright_green().bold(),
+            "why".bright_purple(),
+            "<package>".white(),
+            "--verbose".blue(),
+            "(-v)".yellow()
+        )
+    }
+
+    /// Execute the `volt why` command
+    ///
+    /// Walks the lockfile from the package up to the direct dependencies that depend on it
+    /// and prints the shortest chain from each of them, with the note recorded by
+    /// `volt add --track`.
+    /// ## Arguments
+    /// * `app` - Instance of the command (`Arc<App>`)
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// Why.exec(app).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(app: Arc<App>) -> Result<()> {
+        let name = app.args.value_of("package").unwrap();
+
+        let manifest = PackageJson::read(&app.current_dir.join("package.json"))?;
+        let lock_file = LockFile::load(&app.lock_file_path)?;
+
+        let versions = lock_file
+            .dependencies
+            .values()
+            .filter(|lock| lock.install_name() == name)
+            .map(|lock| lock.version.as_str())
+            .collect::<BTreeSet<_>>();
+
+        if versions.is_empty() {
+            miette::bail!("`{}` is not in volt.lock", name);
+        }
+
+        let mut direct: BTreeMap<&str, &str> = BTreeMap::new();
+
+        for (section, dependencies) in [
+            ("optionalDependencies", &manifest.optional_dependencies),
+            ("devDependencies", &manifest.dev_dependencies),
+            ("dependencies", &manifest.dependencies),
+        ] {
+            for dependency in dependencies.keys() {
+                direct.insert(dependency, section);
+            }
+        }
+
+        let mut dependents: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+
+        for lock in lock_file.dependencies.values() {
+            for dependency in lock.dependencies.iter() {
+                dependents
+                    .entry(dependency)
+                    .or_default()
+                    .insert(lock.install_name());
+            }
+        }
+
+        // breadth first from the package up, so every direct dependency gets its shortest chain
+        let mut via: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from(vec![name]);
+        let mut roots = vec![];
+
+        while let Some(current) = queue.pop_front() {
+            if direct.contains_key(current) {
+                roots.push(current);
+            }
+
+            for dependent in dependents.get(current).into_iter().flatten() {
+                if *dependent != name && !via.contains_key(dependent) {
+                    via.insert(dependent, current);
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        println!(
+            "{}@{}",
+            name.bright_cyan(),
+            versions
+                .into_iter()
+                .collect::<Vec<_>>()
+                .join(", ")
+                .bright_yellow()
+        );
+
+        if roots.is_empty() {
+            println!("  no dependency of the project depends on it anymore");
+        }
+
+        roots.sort_unstable();
+
+        for root in roots {
+            let mut chain = vec![root];
+
+            while let Some(next) = via.get(chain.last().unwrap()) {
+                chain.push(next);
+            }
+
+            println!(
+                "  {} {}",
+                chain.join(" > "),
+                format!("({})", direct[root]).bright_black()
+            );
+
+            if let Some(meta) = lock_file.meta.get(root) {
+                println!(
+                    "    {} {} {}",
+                    "note:".bright_purple(),
+                    meta.note,
+                    format!("(added {})", meta.added).bright_black()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -16,5 +16,4 @@
 
 pub mod create_templates;
 pub mod init_data;
-pub mod meta;
 pub mod package_manager;
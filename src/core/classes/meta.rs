@@ -1,6 +0,0 @@
-pub struct Meta {
-    pub verbose: bool,
-    pub debug: bool,
-    pub silent: bool,
-    pub no_color: bool,
-}
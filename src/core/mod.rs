@@ -18,7 +18,6 @@ extern crate lazy_static;
 
 pub mod classes;
 pub mod command;
-pub mod io;
 pub mod model;
 pub mod prompt;
 pub mod utils;
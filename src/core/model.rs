@@ -14,5 +14,6 @@
     limitations under the License.
 */
 
+pub mod config;
 pub mod http_manager;
 pub mod lock_file;
@@ -0,0 +1,688 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::core::utils::constants::{
+    CONNECT_TIMEOUT, FIRST_BYTE_TIMEOUT, NATIVE_FALLBACKS, RETRY_ATTEMPTS, RETRY_BASE_DELAY,
+    RETRY_MAX_DELAY, TLS_HANDSHAKE_TIMEOUT,
+};
+use crate::core::utils::npmrc::Npmrc;
+use crate::core::utils::package::PeerDependencyMeta;
+
+use miette::Diagnostic;
+use rand::Rng;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ConfigError {
+    #[error("unable to read `{0}`")]
+    #[diagnostic(code(VOLT1200))]
+    IO(String, #[source] std::io::Error),
+    #[error("unable to parse `{0}`")]
+    #[diagnostic(code(VOLT1201), help("check `volt.toml` for typos."))]
+    Decode(String, #[source] toml::de::Error),
+}
+
+/// Project level configuration, read from `volt.toml` next to `package.json`.
+///
+/// ## Examples
+///
+/// ```toml
+/// # volt.toml
+/// auto-install-peers = true
+/// script-shell = "bash"
+/// prefer-dedupe = true
+/// file-integrity = true
+/// save-workspace-protocol = false
+/// pack-notice = true
+/// store-dir = "/mnt/shared/volt-store"
+/// store-layout = "{name}/{version}"
+/// link-mode = "hardlink"
+/// node-linker = "isolated"
+/// shared-virtual-store = true
+/// low-memory = false
+/// concurrency = 16
+/// registry = "https://registry.npmjs.org"
+/// proxy = "http://proxy.internal:3128"
+/// https-proxy = "http://proxy.internal:3128"
+/// no-proxy = "localhost,.internal"
+/// strict-ssl = true
+/// save-exact = false
+/// offline = false
+/// prefer-offline = false
+/// offline-mirror = "npm-packages-offline-cache"
+/// clean = ["dist", ".next", "coverage"]
+/// ignore-scripts = false
+/// engine-strict = false
+/// node = "18"
+/// trusted-dependencies = ["esbuild", "@prisma/*"]
+/// builtin-package-extensions = true
+///
+/// [experiments]
+/// isolated-linker = true
+///
+/// [timeouts]
+/// connect = 10
+/// tls-handshake = 10
+/// first-byte = 30
+/// request = 600
+/// install = 1800
+///
+/// [retries]
+/// attempts = 5
+/// base-delay = 250
+/// max-delay = 10000
+///
+/// [hooks]
+/// before-link = "echo linking $VOLT_PACKAGE_NAME"
+/// after-link = "./scripts/audit-link.sh"
+/// after-unlink = "echo removed $VOLT_LINK_PATH"
+///
+/// [zero-install]
+/// enabled = true
+/// max-size = 500
+/// max-archive-size = 25
+///
+/// [signing]
+/// key = "~/.volt/keys/my-app.key"
+/// trusted-keys = ["RWTkFt0Y8kT1dM6f3gG6Yq1Hq0kVxE4Xb3q3l9cQY2j8J2m1J1b6TQv7"]
+///
+/// [registries]
+/// "@myorg" = "https://npm.pkg.github.com"
+///
+/// [native-fallbacks]
+/// "@napi-rs/canvas" = "canvas-wasm"
+/// "sass-embedded" = ""
+///
+/// [auth-helpers]
+/// "https://npm.pkg.github.com" = "gh auth token"
+/// "https://europe-west1-npm.pkg.dev/my-project/my-repo" = "builtin:gcp"
+///
+/// [package-extensions."react-color@<=2.19.0"]
+/// peer-dependencies = { react = "*" }
+///
+/// [package-extensions."@pm2/agent@<1.0.4"]
+/// dependencies = { debug = "*" }
+///
+/// [[tarball-rewrites]]
+/// from = "https://registry.npmjs.org/"
+/// to = "https://npm-mirror.internal/"
+/// packages = ["@corp/*", "left-pad"]
+///
+/// [[policy.rules]]
+/// rule = "major-lag"
+/// max = 2
+///
+/// [[policy.rules]]
+/// rule = "advisory-age"
+/// severity = "critical"
+/// max-days = 30
+///
+/// [[policy.exemptions]]
+/// rule = "major-lag"
+/// package = "react"
+/// reason = "waiting for the design system to support react 19"
+/// expires = "2026-12-31"
+///
+/// [audit]
+/// level = "high"
+///
+/// [[audit.ignore]]
+/// id = "GHSA-93q8-gq69-wqmw"
+/// reason = "only reachable from the dev server"
+/// expires = "2026-12-31"
+/// ```
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct VoltConfig {
+    /// Resolve and install missing peer dependencies instead of only warning about them.
+    pub auto_install_peers: bool,
+    /// Shell scripts are run with, `sh` (or `cmd.exe` on Windows) when unset.
+    pub script_shell: Option<String>,
+    /// Reuse versions already in the lockfile when they satisfy the requested range.
+    pub prefer_dedupe: bool,
+    /// Record a hash of every installed file in the lockfile, checked by `volt check --deep`.
+    pub file_integrity: bool,
+    /// Save workspace packages as `workspace:^` instead of a concrete range, defaults to `true`.
+    pub save_workspace_protocol: Option<bool>,
+    /// Add a NOTICE with the licenses of bundled dependencies to packed tarballs.
+    pub pack_notice: bool,
+    /// Directory packages are extracted into, `~/.volt` when unset. It can be shared and
+    /// read-only as long as every package the project needs is already in it.
+    pub store_dir: Option<PathBuf>,
+    /// Path of a package inside the store, `{name}`, `{version}` and `{integrity}` are replaced.
+    /// Defaults to `store/{integrity}`, one copy of every tarball shared by all projects.
+    pub store_layout: Option<String>,
+    /// How packages get from the store into node_modules.
+    pub link_mode: LinkMode,
+    /// How node_modules is laid out, every package at the top or only the direct dependencies,
+    /// or whether there is one at all.
+    pub node_linker: NodeLinker,
+    /// Materialize packages once in `<store-dir>/virtual`, shared with every other project
+    /// that opts in, and symlink them into node_modules. See `core::utils::virtual_store`.
+    pub shared_virtual_store: bool,
+    /// Shell commands run around every package that gets linked.
+    pub hooks: LinkHooks,
+    /// Commands printing a token for a registry, keyed by the registry url. See `core::utils::auth`.
+    pub auth_helpers: BTreeMap<String, String>,
+    /// Trade speed for memory in small containers: only a couple of requests run at once,
+    /// tarballs are streamed to disk instead of buffered, and of every resolved tree only the
+    /// packages that get installed are kept. A tarball then takes at most 1 MiB of memory
+    /// while it's downloaded, hashed and unpacked, whatever its size, so downloads stay within
+    /// `concurrency` times that on top of the resolved tree.
+    pub low_memory: bool,
+    /// Downloads and extractions run at once, overridden by `--concurrency`. A few per CPU
+    /// when unset, see `App::concurrency`.
+    pub concurrency: Option<usize>,
+    /// Rules rewriting the urls tarballs are downloaded from, the first matching one applies.
+    pub tarball_rewrites: Vec<TarballRewrite>,
+    /// Registry packages are fetched from and published to unless their scope has its own,
+    /// `https://registry.npmjs.org` when unset.
+    pub registry: Option<String>,
+    /// Registries of scopes that don't live on the default one, keyed by the scope.
+    pub registries: BTreeMap<String, String>,
+    /// Packages installed in place of native ones without a build for the platform, on top of
+    /// the built-in `NATIVE_FALLBACKS`. An empty value turns a built-in one off.
+    pub native_fallbacks: BTreeMap<String, String>,
+    /// Proxy every request goes through, `HTTP_PROXY` when unset.
+    pub proxy: Option<String>,
+    /// Proxy for https requests, `proxy` or `HTTPS_PROXY` when unset.
+    pub https_proxy: Option<String>,
+    /// Comma separated hosts that are connected to directly, `NO_PROXY` when unset.
+    pub no_proxy: Option<String>,
+    /// Check the certificates of registries, defaults to `true`.
+    pub strict_ssl: Option<bool>,
+    /// Save the exact version `volt add` installed instead of a `^` range.
+    pub save_exact: Option<bool>,
+    /// Resolve from the metadata cache and install from the store only, like `--offline`.
+    pub offline: Option<bool>,
+    /// Only ask registries for packages missing from the metadata cache, like `--prefer-offline`.
+    pub prefer_offline: Option<bool>,
+    /// Directory relative to the project every downloaded tarball is copied to and installed
+    /// from first, so it can be committed for builds without network access.
+    pub offline_mirror: Option<PathBuf>,
+    /// Package archives kept in `.volt/cache` and committed with the project.
+    pub zero_install: ZeroInstall,
+    /// Build output directories relative to the project that `volt clean --build` removes.
+    pub clean: Vec<PathBuf>,
+    /// Never run the install scripts of dependencies or the lifecycle scripts of the project,
+    /// like `--ignore-scripts`.
+    pub ignore_scripts: Option<bool>,
+    /// Fail instead of warning when the running node doesn't satisfy the `engines` of the
+    /// project or of a dependency, like `--engine-strict`.
+    pub engine_strict: Option<bool>,
+    /// Version of node `volt run` runs scripts with, installed by volt when missing. Beats a
+    /// `.node-version` file, see `volt node`.
+    pub node: Option<String>,
+    /// Packages whose install scripts run without asking, `*` matches anything. Once set, the
+    /// scripts of other packages only run when they are confirmed at the prompt.
+    pub trusted_dependencies: Vec<String>,
+    /// Fixes to the metadata of published packages, keyed by `name` or `name@range`. They beat
+    /// the built-in ones, see `core::utils::extensions`.
+    pub package_extensions: BTreeMap<String, PackageExtension>,
+    /// Whether the fixes volt ships for packages with known broken metadata apply, true when
+    /// unset.
+    pub builtin_package_extensions: Option<bool>,
+    /// Unstable features turned on, keyed by their name. See `core::utils::experiments`.
+    pub experiments: BTreeMap<String, bool>,
+    /// `_authToken`s from `.npmrc` keyed by `//host/path/`. Never read from volt.toml, which is
+    /// usually committed.
+    #[serde(skip)]
+    pub auth_tokens: BTreeMap<String, String>,
+    /// How long network requests and installs may take.
+    pub timeouts: Timeouts,
+    /// How registry requests and downloads that fail for a passing reason are repeated.
+    pub retries: Retries,
+    /// Rules `volt policy check` holds the dependencies to.
+    pub policy: Policy,
+    /// Which advisories fail `volt audit`.
+    pub audit: Audit,
+    /// Keys the lockfile and install summaries are signed and checked with.
+    pub signing: Signing,
+}
+
+/// The cache of archives in the repository, see `core::utils::zero_install`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ZeroInstall {
+    /// Fill the cache on every install, even before `volt cache vendor` created it.
+    pub enabled: bool,
+    /// Megabytes the cache may take before installs warn about it, 500 by default.
+    pub max_size: Option<u64>,
+    /// Megabytes of the largest archive kept, 25 by default. Larger ones are downloaded.
+    pub max_archive_size: Option<u64>,
+}
+
+/// Retries of requests that timed out, lost their connection or got a 429 or 5xx.
+///
+/// The wait before every retry is random, up to `base-delay` doubled per failed attempt and
+/// capped at `max-delay`, unless the registry said how long to wait with `Retry-After`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Retries {
+    /// Attempts in total, the first one included. 5 by default, 1 turns retries off.
+    pub attempts: Option<u32>,
+    /// Milliseconds, 250 by default.
+    pub base_delay: Option<u64>,
+    /// Milliseconds, 10000 by default.
+    pub max_delay: Option<u64>,
+}
+
+impl Retries {
+    pub fn attempts(&self) -> u32 {
+        self.attempts.unwrap_or(RETRY_ATTEMPTS).max(1)
+    }
+
+    /// How long to wait after the `failures`th failed attempt.
+    pub fn delay(&self, failures: u32, retry_after: Option<Duration>) -> Duration {
+        let max_delay = Duration::from_millis(self.max_delay.unwrap_or(RETRY_MAX_DELAY));
+
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(max_delay);
+        }
+
+        let base_delay = self.base_delay.unwrap_or(RETRY_BASE_DELAY);
+        let ceiling = base_delay
+            .saturating_mul(2u64.saturating_pow(failures.saturating_sub(1)))
+            .min(max_delay.as_millis() as u64);
+
+        // full jitter, so requests that failed together don't all come back at once
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+}
+
+/// Timeouts in seconds, `0` turns one off.
+///
+/// A slow download of a large tarball keeps going as long as the registry keeps answering,
+/// only `request` puts a limit on it. A dead connection is given up on after `connect`,
+/// `tls-handshake` and `first-byte`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Timeouts {
+    /// Opening the connection, 10 seconds by default.
+    pub connect: Option<u64>,
+    /// The TLS handshake after connecting, 10 seconds by default.
+    pub tls_handshake: Option<u64>,
+    /// Waiting for the response headers once the request is sent, 30 seconds by default.
+    pub first_byte: Option<u64>,
+    /// One request from start to end, body included. Unlimited by default.
+    pub request: Option<u64>,
+    /// A whole `volt install`, `volt ci` or `volt add`. Unlimited by default.
+    pub install: Option<u64>,
+}
+
+impl Timeouts {
+    fn seconds(value: Option<u64>, default: Option<u64>) -> Option<Duration> {
+        match value.or(default) {
+            Some(0) | None => None,
+            Some(seconds) => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    /// Opening a connection, TLS handshake included.
+    pub fn connect(&self) -> Option<Duration> {
+        let connect = Self::seconds(self.connect, Some(CONNECT_TIMEOUT));
+        let tls_handshake = Self::seconds(self.tls_handshake, Some(TLS_HANDSHAKE_TIMEOUT));
+
+        // the http clients time the connection and the handshake as one step
+        match (connect, tls_handshake) {
+            (Some(connect), Some(tls_handshake)) => Some(connect + tls_handshake),
+            (connect, None) => connect,
+            (None, _) => None,
+        }
+    }
+
+    /// Getting response headers, from the start of the request.
+    pub fn first_byte(&self) -> Option<Duration> {
+        Some(self.connect()? + Self::seconds(self.first_byte, Some(FIRST_BYTE_TIMEOUT))?)
+    }
+
+    pub fn request(&self) -> Option<Duration> {
+        Self::seconds(self.request, None)
+    }
+
+    pub fn install(&self) -> Option<Duration> {
+        Self::seconds(self.install, None)
+    }
+}
+
+/// Points downloads at another origin, e.g. registry tarballs at an internal mirror.
+///
+/// Rules apply after resolution, the lockfile keeps the original url and every tarball is
+/// still checked against the integrity the registry published.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TarballRewrite {
+    /// Prefix of the urls the rule applies to.
+    pub from: String,
+    /// What the prefix is replaced with.
+    pub to: String,
+    /// Names of the packages the rule applies to, `*` matches anything. Every package when empty.
+    pub packages: Vec<String>,
+}
+
+impl TarballRewrite {
+    fn applies_to(&self, name: &str, url: &str) -> bool {
+        !self.from.is_empty()
+            && url.starts_with(&self.from)
+            && (self.packages.is_empty()
+                || self
+                    .packages
+                    .iter()
+                    .any(|pattern| matches_name(pattern, name)))
+    }
+}
+
+/// Changes to the metadata of a published package, made while resolving without touching its
+/// files. Every field given replaces the one of the package, dependencies are merged into
+/// theirs.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PackageExtension {
+    pub dependencies: BTreeMap<String, String>,
+    pub optional_dependencies: BTreeMap<String, String>,
+    pub peer_dependencies: BTreeMap<String, String>,
+    pub peer_dependencies_meta: BTreeMap<String, PeerDependencyMeta>,
+    pub os: Option<Vec<String>>,
+    pub cpu: Option<Vec<String>>,
+    pub libc: Option<Vec<String>>,
+}
+
+/// Whether the package `name` matches `pattern`, where `*` matches anything.
+fn matches_name(pattern: &str, name: &str) -> bool {
+    Regex::new(&format!(
+        "^{}$",
+        regex::escape(pattern).replace(r"\*", ".*")
+    ))
+    .is_ok_and(|regex| regex.is_match(name))
+}
+
+/// How packages get from the store into node_modules.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkMode {
+    /// Files are hard links into the store, falling back to `reflink` or `copy` when the
+    /// project is on another filesystem.
+    #[default]
+    Hardlink,
+    /// Files are copy-on-write clones of the store's, where the filesystem supports it.
+    Reflink,
+    /// Every project gets its own copy.
+    Copy,
+    /// node_modules points into the store.
+    Symlink,
+}
+
+/// Layout of node_modules.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeLinker {
+    /// Every package is at the top of node_modules, so code can also require the ones it
+    /// never declared.
+    #[default]
+    Hoisted,
+    /// Only the direct dependencies are at the top, symlinked to their entry of the virtual
+    /// store, where each package only sees its own dependencies. See
+    /// `core::utils::virtual_store`.
+    Isolated,
+    /// No node_modules at all, `.pnp.cjs` maps every package to its place in the store. See
+    /// `core::utils::pnp`.
+    Pnp,
+}
+
+/// Freshness rules for the dependencies, checked by `volt policy check`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Policy {
+    pub rules: Vec<PolicyRule>,
+    /// Packages a rule doesn't apply to, until they expire.
+    pub exemptions: Vec<PolicyExemption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+pub enum PolicyRule {
+    /// No direct dependency more than `max` major versions behind its latest release.
+    MajorLag { max: u64 },
+    /// No locked package affected by an advisory of at least `severity` that was published
+    /// more than `max-days` ago.
+    #[serde(rename_all = "kebab-case")]
+    AdvisoryAge {
+        #[serde(default = "AdvisorySeverity::critical")]
+        severity: AdvisorySeverity,
+        max_days: i64,
+    },
+}
+
+/// Severities of registry advisories, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdvisorySeverity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl AdvisorySeverity {
+    fn critical() -> Self {
+        AdvisorySeverity::Critical
+    }
+
+    /// The severity of an advisory as the registry spells it.
+    pub fn parse(severity: &str) -> Option<Self> {
+        match severity {
+            "info" => Some(AdvisorySeverity::Info),
+            "low" => Some(AdvisorySeverity::Low),
+            "moderate" => Some(AdvisorySeverity::Moderate),
+            "high" => Some(AdvisorySeverity::High),
+            "critical" => Some(AdvisorySeverity::Critical),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PolicyExemption {
+    /// Name of the rule, `major-lag` or `advisory-age`.
+    pub rule: String,
+    pub package: String,
+    /// Why the package is exempt, shown next to it.
+    pub reason: Option<String>,
+    /// Last day the exemption applies, `YYYY-MM-DD`. It never expires when unset.
+    pub expires: Option<String>,
+}
+
+/// Which advisories fail `volt audit`, the others are only reported.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Audit {
+    /// Least severe advisories that fail the audit, overridden by `--level`. Every advisory
+    /// does when unset.
+    pub level: Option<AdvisorySeverity>,
+    /// Advisories that don't fail the audit, until they expire.
+    pub ignore: Vec<IgnoredAdvisory>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IgnoredAdvisory {
+    /// The GitHub id of the advisory, `GHSA-...`, or the number the registry gives it.
+    pub id: String,
+    /// Why the advisory is ignored, shown next to it.
+    pub reason: Option<String>,
+    /// Last day the advisory is ignored, `YYYY-MM-DD`. It's ignored for good when unset.
+    pub expires: Option<String>,
+}
+
+/// Signatures of volt.lock and the install summary, see `core::utils::signing`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Signing {
+    /// Secret key installs sign with, `~/` is the home directory. Keep it out of the
+    /// repository, `VOLT_SIGNING_KEY` with the key itself takes precedence.
+    pub key: Option<PathBuf>,
+    /// Public keys from `volt sign keygen`. When any are listed, installs refuse a volt.lock
+    /// that isn't signed by one of them.
+    pub trusted_keys: Vec<String>,
+}
+
+/// Shell commands run while linking, see `core::utils::linker` for their environment.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LinkHooks {
+    /// Runs before a package is linked into node_modules.
+    pub before_link: Option<String>,
+    /// Runs after a package was linked into node_modules.
+    pub after_link: Option<String>,
+    /// Runs after a previously installed copy was removed from node_modules to make way.
+    pub after_unlink: Option<String>,
+}
+
+impl VoltConfig {
+    /// Loads the configuration from the given path, falling back to the defaults when the file doesn't exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data =
+            read_to_string(path).map_err(|e| ConfigError::IO(path.display().to_string(), e))?;
+
+        toml::from_str(&data).map_err(|e| ConfigError::Decode(path.display().to_string(), e))
+    }
+
+    /// Loads `volt.toml` in `directory`, with what it leaves unset taken from `.npmrc` files.
+    pub fn load_project(directory: &Path, home_dir: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::load(directory.join("volt.toml"))?;
+
+        Npmrc::load(directory, home_dir).apply(&mut config);
+
+        Ok(config)
+    }
+
+    /// Package installed in place of `name` when it has no build for the platform.
+    pub fn native_fallback(&self, name: &str) -> Option<&str> {
+        let fallback = match self.native_fallbacks.get(name) {
+            Some(fallback) => fallback.as_str(),
+            None => NATIVE_FALLBACKS
+                .iter()
+                .find(|(native, _)| *native == name)
+                .map(|(_, fallback)| *fallback)?,
+        };
+
+        Some(fallback).filter(|fallback| !fallback.is_empty())
+    }
+
+    /// Url the tarball of `name` at `url` is downloaded from, after `tarball-rewrites`.
+    pub fn tarball_url(&self, name: &str, url: &str) -> String {
+        match self
+            .tarball_rewrites
+            .iter()
+            .find(|rule| rule.applies_to(name, url))
+        {
+            Some(rule) => format!("{}{}", rule.to, &url[rule.from.len()..]),
+            None => url.to_string(),
+        }
+    }
+
+    /// Whether `name` is listed under `trusted-dependencies`.
+    pub fn is_trusted(&self, name: &str) -> bool {
+        self.trusted_dependencies
+            .iter()
+            .any(|pattern| matches_name(pattern, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARBALL: &str = "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz";
+
+    #[test]
+    fn rewrites_tarball_urls() {
+        let config = toml::from_str::<VoltConfig>(
+            r#"
+            [[tarball-rewrites]]
+            from = "https://registry.npmjs.org/"
+            to = "https://npm-mirror.internal/"
+            packages = ["@corp/*", "left-pad"]
+
+            [[tarball-rewrites]]
+            from = "https://registry.npmjs.org/"
+            to = "https://fallback.internal/npm/"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.tarball_url("left-pad", TARBALL),
+            "https://npm-mirror.internal/left-pad/-/left-pad-1.3.0.tgz"
+        );
+        assert_eq!(
+            config.tarball_url(
+                "@corp/ui",
+                "https://registry.npmjs.org/@corp/ui/-/ui-2.0.0.tgz"
+            ),
+            "https://npm-mirror.internal/@corp/ui/-/ui-2.0.0.tgz"
+        );
+
+        // the first rule that applies wins, the next one has no package list
+        assert_eq!(
+            config.tarball_url(
+                "right-pad",
+                "https://registry.npmjs.org/right-pad/-/right-pad-1.0.1.tgz"
+            ),
+            "https://fallback.internal/npm/right-pad/-/right-pad-1.0.1.tgz"
+        );
+
+        // other origins are left alone
+        assert_eq!(
+            config.tarball_url("left-pad", "https://registry.yarnpkg.com/left-pad.tgz"),
+            "https://registry.yarnpkg.com/left-pad.tgz"
+        );
+    }
+
+    #[test]
+    fn leaves_tarball_urls_without_rules() {
+        let config = VoltConfig::default();
+
+        assert_eq!(config.tarball_url("left-pad", TARBALL), TARBALL);
+
+        // an empty prefix would apply to every url
+        let config = toml::from_str::<VoltConfig>(
+            r#"
+            [[tarball-rewrites]]
+            from = ""
+            to = "https://npm-mirror.internal/"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.tarball_url("left-pad", TARBALL), TARBALL);
+    }
+}
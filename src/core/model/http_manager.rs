@@ -14,37 +14,133 @@
     limitations under the License.
 */
 
-use crate::core::utils::package::NpmPackage;
+use crate::core::model::config::{Retries, VoltConfig};
+use crate::core::utils::auth::Credentials;
+use crate::core::utils::constants::{NPMS_API, NPM_DOWNLOADS_API, NPM_REGISTRY, TCP_KEEPALIVE};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::extensions::Extensions;
+use crate::core::utils::package::{NpmPackage, Packument};
+use crate::core::utils::registry_cache::{RegistryCache, Validators};
+use crate::core::utils::retry::{
+    is_transient_isahc, is_transient_status, retry, retry_after, Failure,
+};
+use isahc::config::{Configurable, SslOption, VersionNegotiation};
 use isahc::http::StatusCode;
 use isahc::AsyncReadResponseExt;
+use isahc::{AsyncBody, HttpClient, Request, Response};
+use lazy_static::lazy_static;
+use miette::Diagnostic;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+lazy_static! {
+    /// The client every registry request and download goes through, see `configure_client`.
+    static ref CLIENT: RwLock<HttpClient> =
+        RwLock::new(HttpClient::new().expect("failed to set up the http client"));
+}
+
+#[derive(Error, Debug, Diagnostic)]
 pub enum GetPackageError {
     #[error("network request failed with registry")]
+    #[diagnostic(code(VOLT1000))]
     Request(isahc::Error),
     #[error("unable to read network response")]
+    #[diagnostic(code(VOLT1001))]
     IO(io::Error),
     #[error("unable to deserialize network response: {0:?}")]
+    #[diagnostic(code(VOLT1002))]
     Json(serde_json::Error),
+    #[error("GET {0} - 404 - the package was not found in the registry")]
+    #[diagnostic(code(VOLT1003))]
+    NotFound(String),
+    #[error("GET {0} - {1} - the registry returned an error")]
+    #[diagnostic(code(VOLT1004))]
+    Status(String, String),
+    #[error("{registry} requires authentication ({status})")]
+    #[diagnostic(
+        code(VOLT1005),
+        help("add an `_authToken` for the registry to `.npmrc`, or configure an auth helper for it in `volt.toml`. `NPM_TOKEN` is only sent to the default registry.")
+    )]
+    AuthRequired { registry: String, status: String },
+    #[error("{registry} rejected the token it was sent ({status})")]
+    #[diagnostic(
+        code(VOLT1006),
+        help("the token may have expired or lack read access to the package, check `.npmrc`, `NPM_TOKEN` or the auth helper of the registry.")
+    )]
+    TokenRejected { registry: String, status: String },
+    #[error("unable to get a token for {0}: {1}")]
+    #[diagnostic(code(VOLT1007))]
+    Credentials(String, String),
+    #[error("{0} is not in the metadata cache")]
+    #[diagnostic(
+        code(VOLT1008),
+        help("install once without `--offline` to fill the cache.")
+    )]
+    NotCached(String),
+}
+
+/// Set up the shared client with the `timeouts` and `strict-ssl` of `config`.
+///
+/// Sharing one client shares its connections: they are kept alive and reused from one
+/// package to the next, and a registry speaking HTTP/2 gets every request multiplexed over
+/// a single connection instead of a handshake per document or tarball.
+pub fn configure_client(config: &VoltConfig) -> Result<(), isahc::Error> {
+    let timeouts = &config.timeouts;
+
+    let mut builder = HttpClient::builder()
+        .version_negotiation(VersionNegotiation::latest_compatible())
+        .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE));
+
+    if let Some(connect) = timeouts.connect() {
+        builder = builder.connect_timeout(connect);
+    }
+
+    if let Some(request) = timeouts.request() {
+        builder = builder.timeout(request);
+    }
+
+    if !config.strict_ssl.unwrap_or(true) {
+        builder = builder.ssl_options(
+            SslOption::DANGER_ACCEPT_INVALID_CERTS | SslOption::DANGER_ACCEPT_INVALID_HOSTS,
+        );
+    }
+
+    *CLIENT.write().unwrap() = builder.build()?;
+
+    Ok(())
+}
+
+/// The shared client, a handle to the same connection pool every time.
+pub fn client() -> HttpClient {
+    CLIENT.read().unwrap().clone()
 }
 
 #[allow(dead_code)]
-/// Request a package from `registry.yarnpkg.com`
+/// Request a package from `registry`
 ///
-/// Uses `chttp` async implementation to send a `get` request for the package
+/// Sends a `get` request for the package with the shared client
 /// ## Arguments
-/// * `name` - Name of the package to request from `registry.yarnpkg.com`
+/// * `registry` - Registry to request the package from, e.g. `App::registry`
+/// * `name` - Name of the package to request from `registry`
 /// ## Examples
 /// ```
 /// // Await an async response
-/// get_package("react").await;
+/// get_package(&app.registry(), "react").await;
 /// ```
 /// ## Returns
 /// * `Result<Option<Package>, GetPackageError>`
-pub async fn get_package(name: &str) -> Result<Option<NpmPackage>, GetPackageError> {
-    let mut resp = isahc::get_async(format!("http://registry.yarnpkg.com/{}", name))
+pub async fn get_package(
+    registry: &str,
+    name: &str,
+) -> Result<Option<NpmPackage>, GetPackageError> {
+    let mut resp = client()
+        .get_async(format!("{}/{}", registry, escape_package_name(name)))
         .await
         .map_err(GetPackageError::Request)?;
 
@@ -62,3 +158,969 @@ pub async fn get_package(name: &str) -> Result<Option<NpmPackage>, GetPackageErr
 
     Ok(Some(package))
 }
+
+/// Which registry every package is fetched from.
+///
+/// Packages of a scope with its own registry (`[registries]` in volt.toml or
+/// `@scope:registry` in `.npmrc`) go there, everything else to the default one.
+#[derive(Debug, Clone)]
+pub struct Registries {
+    pub default: String,
+    /// Registry urls keyed by scope, `@myorg`.
+    pub scopes: BTreeMap<String, String>,
+    /// Credentials of the default registry followed by those of the scoped ones.
+    pub credentials: Vec<Arc<Credentials>>,
+    /// How failed requests to them are repeated.
+    pub retries: Retries,
+    /// Whether documents come from the registries or from `cache`.
+    pub network: NetworkMode,
+    /// Every document fetched is kept here for `--offline` and `--prefer-offline`.
+    pub cache: Option<RegistryCache>,
+    /// Fixes applied to the documents of packages with broken metadata.
+    pub extensions: Arc<Extensions>,
+}
+
+/// Where package documents come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// Always ask the registry.
+    Online,
+    /// Use the cached document of a package, only ask the registry for the ones not cached.
+    PreferOffline,
+    /// Never touch the network, a package that isn't cached fails.
+    Offline,
+}
+
+impl Default for Registries {
+    fn default() -> Self {
+        Registries {
+            default: NPM_REGISTRY.to_string(),
+            scopes: BTreeMap::new(),
+            credentials: vec![],
+            retries: Retries::default(),
+            network: NetworkMode::Online,
+            cache: None,
+            extensions: Arc::default(),
+        }
+    }
+}
+
+impl Registries {
+    /// The registry of the scope of `name`, when it has one of its own.
+    pub fn scoped(&self, name: &str) -> Option<&str> {
+        let scope = name.split_once('/')?.0;
+
+        self.scopes.get(scope).map(String::as_str)
+    }
+
+    /// Registry `name` is fetched from and published to.
+    pub fn for_package(&self, name: &str) -> &str {
+        self.scoped(name).unwrap_or(&self.default)
+    }
+
+    /// Whether `name` comes from the public registry, the only one npm's other APIs know.
+    pub fn is_public(&self, name: &str) -> bool {
+        without_scheme(self.for_package(name)) == without_scheme(NPM_REGISTRY)
+    }
+
+    /// Credentials of the registry `url` belongs to, the one with the longest matching path.
+    pub fn credentials(&self, url: &str) -> Option<&Credentials> {
+        let location = format!("{}/", without_scheme(url));
+
+        self.credentials
+            .iter()
+            .filter(|credentials| {
+                location.starts_with(&format!("{}/", without_scheme(&credentials.registry)))
+            })
+            .max_by_key(|credentials| credentials.registry.len())
+            .map(Arc::as_ref)
+    }
+
+    /// Token to send with a request to `url`, if its registry has one.
+    ///
+    /// Registries are matched without their scheme, but a token of an https registry is never
+    /// sent over plain http, where anyone on the way could read it.
+    pub async fn token(&self, url: &str) -> Result<Option<String>, GetPackageError> {
+        let credentials = match self.credentials(url) {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+
+        if !url.starts_with("https://") && credentials.registry.starts_with("https://") {
+            return Ok(None);
+        }
+
+        let is_default = without_scheme(&credentials.registry) == without_scheme(&self.default);
+
+        credentials
+            .optional_token(is_default)
+            .await
+            .map_err(|e| GetPackageError::Credentials(credentials.registry.clone(), e.to_string()))
+    }
+
+    /// The error for a 401 or 403 from the registry `url` belongs to.
+    pub fn rejected(&self, url: &str, status: StatusCode, token_sent: bool) -> GetPackageError {
+        let registry = self
+            .credentials(url)
+            .map(|credentials| credentials.registry.clone())
+            .unwrap_or_else(|| url.to_string());
+
+        let status = status.as_str().to_string();
+
+        if token_sent {
+            GetPackageError::TokenRejected { registry, status }
+        } else {
+            GetPackageError::AuthRequired { registry, status }
+        }
+    }
+}
+
+fn without_scheme(url: &str) -> &str {
+    url.split_once("//")
+        .map_or(url, |(_, rest)| rest)
+        .trim_end_matches('/')
+}
+
+/// Request the abbreviated package document for `name` from its registry.
+///
+/// The abbreviated document only carries what's needed to install a package, which
+/// makes it a fraction of the size of the full document for packages with long histories.
+pub async fn get_packument(
+    registries: &Registries,
+    name: &str,
+) -> Result<Packument, GetPackageError> {
+    let mut packument: Packument = fetch_packument(registries, name, true).await?;
+    registries.extensions.extend(&mut packument);
+    Ok(packument)
+}
+
+/// Fetch the full document of a package, which unlike the abbreviated one carries
+/// the publish time of every version.
+pub async fn get_full_packument(
+    registries: &Registries,
+    name: &str,
+) -> Result<Packument, GetPackageError> {
+    let mut packument: Packument = fetch_packument(registries, name, false).await?;
+    registries.extensions.extend(&mut packument);
+    Ok(packument)
+}
+
+/// The full document of a package as the registry sent it, with every field `Packument`
+/// leaves out like `readme` and `keywords`. Package extensions aren't applied.
+pub async fn get_full_document(
+    registries: &Registries,
+    name: &str,
+) -> Result<serde_json::Value, GetPackageError> {
+    fetch_packument(registries, name, false).await
+}
+
+async fn fetch_packument<T: DeserializeOwned>(
+    registries: &Registries,
+    name: &str,
+    abbreviated: bool,
+) -> Result<T, GetPackageError> {
+    if registries.network != NetworkMode::Online {
+        match cached_packument(registries, name, abbreviated) {
+            Some(document) => {
+                return serde_json::from_slice(&document).map_err(GetPackageError::Json)
+            }
+            None if registries.network == NetworkMode::Offline => {
+                return Err(GetPackageError::NotCached(name.to_string()))
+            }
+            None => {}
+        }
+    }
+
+    let accept = if abbreviated {
+        "application/vnd.npm.install-v1+json"
+    } else {
+        "application/json"
+    };
+
+    let url = format!(
+        "{}/{}",
+        registries.for_package(name),
+        escape_package_name(name)
+    );
+
+    // a document cached with validators is only downloaded again when it changed
+    let cached = registries.cache.as_ref().and_then(|cache| {
+        let path = cache.packument_path(name, abbreviated);
+        let validators = cache.read_validators(&path)?;
+        let document = cache.read(&path, None)?;
+
+        Some((document, validators))
+    });
+
+    let mut refreshed = false;
+
+    let (status, body_string, validators, token_sent) = loop {
+        let (url, cached) = (&url, &cached);
+
+        let (status, body, validators, token_sent) = retry(&registries.retries, || async move {
+            let mut request = Request::get(url).header("accept", accept);
+
+            if let Some((_, validators)) = cached {
+                if let Some(etag) = &validators.etag {
+                    request = request.header("if-none-match", etag);
+                }
+
+                if let Some(last_modified) = &validators.last_modified {
+                    request = request.header("if-modified-since", last_modified);
+                }
+            }
+
+            let token = registries.token(url).await.map_err(Failure::Fatal)?;
+
+            if let Some(token) = &token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+
+            let request = request
+                .body(())
+                .map_err(|e| Failure::Fatal(GetPackageError::Request(e.into())))?;
+
+            let mut resp = client().send_async(request).await.map_err(isahc_failure)?;
+
+            let status = resp.status();
+
+            if is_transient_status(status) {
+                return Err(Failure::Transient(
+                    GetPackageError::Status(url.clone(), status.as_str().to_string()),
+                    retry_after(resp.headers()),
+                ));
+            }
+
+            let header = |name: &str| {
+                resp.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+
+            let validators = Validators {
+                etag: header("etag"),
+                last_modified: header("last-modified"),
+            };
+
+            // a connection dropped halfway through the body is as good a reason to retry
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| Failure::Transient(GetPackageError::IO(e), None))?;
+
+            Ok((status, body, validators, token.is_some()))
+        })
+        .await?;
+
+        // a helper may hand out a fresh token for one that expired
+        if status == StatusCode::UNAUTHORIZED && token_sent && !refreshed {
+            if let Some(credentials) = registries.credentials(url) {
+                if credentials.invalidate().await {
+                    refreshed = true;
+                    continue;
+                }
+            }
+        }
+
+        break (status, body, validators, token_sent);
+    };
+
+    match status {
+        StatusCode::OK => {}
+        StatusCode::NOT_MODIFIED => {
+            if let Some((document, _)) = cached {
+                return serde_json::from_slice(&document).map_err(GetPackageError::Json);
+            }
+
+            return Err(GetPackageError::Status(url, status.as_str().to_string()));
+        }
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Err(registries.rejected(&url, status, token_sent))
+        }
+        StatusCode::NOT_FOUND => return Err(GetPackageError::NotFound(url)),
+        status => return Err(GetPackageError::Status(url, status.as_str().to_string())),
+    }
+
+    let packument = serde_json::from_str(&body_string).map_err(GetPackageError::Json)?;
+
+    // a cache that can't be written only costs the next offline install
+    if let Some(cache) = &registries.cache {
+        let path = cache.packument_path(name, abbreviated);
+
+        if cache.write(&path, body_string.as_bytes()).is_ok() && !validators.is_empty() {
+            cache.write_validators(&path, &validators).ok();
+        }
+    }
+
+    Ok(packument)
+}
+
+/// The cached document of `name`, a full one serves for an abbreviated one too.
+fn cached_packument(registries: &Registries, name: &str, abbreviated: bool) -> Option<Vec<u8>> {
+    let cache = registries.cache.as_ref()?;
+
+    let mut paths = vec![cache.packument_path(name, false)];
+
+    if abbreviated {
+        paths.insert(0, cache.packument_path(name, true));
+    }
+
+    paths.iter().find_map(|path| cache.read(path, None))
+}
+
+fn isahc_failure(error: isahc::Error) -> Failure<GetPackageError> {
+    if is_transient_isahc(&error) {
+        Failure::Transient(GetPackageError::Request(error), None)
+    } else {
+        Failure::Fatal(GetPackageError::Request(error))
+    }
+}
+
+/// A public key the registry signs packages with, from `/-/npm/v1/keys`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryKey {
+    /// Packages published after this moment can't be signed with the key.
+    pub expires: Option<String>,
+    pub keyid: String,
+    pub keytype: String,
+    /// Base64 encoded DER `SubjectPublicKeyInfo`.
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryKeys {
+    keys: Vec<RegistryKey>,
+}
+
+/// Fetch the keys `registry` signs packages with.
+pub async fn get_registry_keys(registry: &str) -> Result<Vec<RegistryKey>, GetPackageError> {
+    let url = format!("{}/-/npm/v1/keys", registry);
+
+    let mut resp = client()
+        .get_async(&url)
+        .await
+        .map_err(GetPackageError::Request)?;
+
+    match resp.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(GetPackageError::NotFound(url)),
+        status => return Err(GetPackageError::Status(url, status.as_str().to_string())),
+    }
+
+    let body_string = resp.text().await.map_err(GetPackageError::IO)?;
+
+    let keys: RegistryKeys = serde_json::from_str(&body_string).map_err(GetPackageError::Json)?;
+
+    Ok(keys.keys)
+}
+
+/// A security advisory from the registry's bulk advisory endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Advisory {
+    pub id: u64,
+    pub title: String,
+    pub severity: String,
+    #[serde(default)]
+    pub url: String,
+    /// Range of the versions the advisory applies to.
+    pub vulnerable_versions: String,
+}
+
+/// Fetch the advisories that apply to any of the given versions of each package.
+pub async fn get_advisories(
+    registry: &str,
+    versions: &BTreeMap<String, Vec<String>>,
+) -> Result<HashMap<String, Vec<Advisory>>, GetPackageError> {
+    let url = format!("{}/-/npm/v1/security/advisories/bulk", registry);
+
+    let body = serde_json::to_vec(versions).map_err(GetPackageError::Json)?;
+
+    let request = Request::post(&url)
+        .header("content-type", "application/json")
+        .body(body)
+        .map_err(|e| GetPackageError::Request(e.into()))?;
+
+    let mut resp = client()
+        .send_async(request)
+        .await
+        .map_err(GetPackageError::Request)?;
+
+    match resp.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(GetPackageError::NotFound(url)),
+        status => return Err(GetPackageError::Status(url, status.as_str().to_string())),
+    }
+
+    let body_string = resp.text().await.map_err(GetPackageError::IO)?;
+
+    serde_json::from_str(&body_string).map_err(GetPackageError::Json)
+}
+
+/// Downloads of a public package over the last week, `None` when they can't be fetched.
+pub async fn get_weekly_downloads(name: &str) -> Option<u64> {
+    let url = format!("{}/point/last-week/{}", NPM_DOWNLOADS_API, name);
+
+    get_json(&url).await?["downloads"].as_u64()
+}
+
+/// How many packages depend on a public package, `None` when it can't be fetched.
+pub async fn get_dependents_count(name: &str) -> Option<u64> {
+    let url = format!(
+        "{}/package/{}",
+        NPMS_API,
+        name.replace('@', "%40").replace('/', "%2F")
+    );
+
+    get_json(&url).await?["collected"]["npm"]["dependentsCount"].as_u64()
+}
+
+async fn get_json(url: &str) -> Option<serde_json::Value> {
+    let mut response = match client().get_async(url).await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return None,
+    };
+
+    let text = response.text().await.ok()?;
+
+    serde_json::from_str(&text).ok()
+}
+
+/// Escape a package name for use in a registry url (`@types/node` -> `@types%2fnode`).
+pub fn escape_package_name(name: &str) -> String {
+    name.replace('/', "%2f")
+}
+
+/// Extract the human readable error message from a failed registry response.
+async fn registry_error_message(response: &mut Response<AsyncBody>) -> String {
+    let text = response.text().await.unwrap_or_default();
+
+    serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|body| {
+            body.get("error")
+                .or_else(|| body.get("message"))
+                .and_then(|message| message.as_str())
+                .map(|message| message.to_string())
+        })
+        .unwrap_or_else(|| {
+            response
+                .status()
+                .canonical_reason()
+                .unwrap_or("unknown error")
+                .to_string()
+        })
+}
+
+/// Check if `version` of `name` is present on the registry with the given shasum.
+///
+/// Used to find out whether a publish request that failed (or whose response was lost)
+/// actually landed on the registry.
+pub async fn version_published(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    version: &str,
+    shasum: &str,
+) -> bool {
+    let url = format!("{}/{}", registry, escape_package_name(name));
+
+    // private and scoped registries hide the package from anonymous requests
+    let request = match credentials.token().await {
+        Ok(token) => Request::get(url)
+            .header("authorization", format!("Bearer {}", token))
+            .body(()),
+        Err(_) => return false,
+    };
+
+    let request = match request {
+        Ok(request) => request,
+        Err(_) => return false,
+    };
+
+    let mut response = match client().send_async(request).await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return false,
+    };
+
+    let text = match response.text().await {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+
+    serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|packument| {
+            packument["versions"][version]["dist"]["shasum"]
+                .as_str()
+                .map(|published| published == shasum)
+        })
+        .unwrap_or(false)
+}
+
+/// Send an authenticated request to the registry and return the response body.
+///
+/// A 401 is retried once with a fresh token when the registry has an auth helper. Error
+/// responses are turned into a `VoltError::RegistryError` carrying the registry's own message.
+pub async fn registry_request(
+    method: &str,
+    url: &str,
+    credentials: &Credentials,
+    body: Option<Vec<u8>>,
+) -> miette::Result<String> {
+    let body = body.unwrap_or_default();
+
+    let mut refreshed = false;
+
+    let mut response = loop {
+        let request = Request::builder()
+            .method(method)
+            .uri(url)
+            .header(
+                "authorization",
+                format!("Bearer {}", credentials.token().await?),
+            )
+            .header("content-type", "application/json")
+            .body(body.clone())
+            .map_err(VoltError::RequestBuilderError)?;
+
+        let response = client()
+            .send_async(request)
+            .await
+            .map_err(VoltError::NetworkError)?;
+
+        if response.status() == StatusCode::UNAUTHORIZED
+            && !refreshed
+            && credentials.invalidate().await
+        {
+            refreshed = true;
+            continue;
+        }
+
+        break response;
+    };
+
+    if !response.status().is_success() {
+        let message = registry_error_message(&mut response).await;
+
+        return Err(VoltError::RegistryError {
+            method: method.to_string(),
+            url: url.to_string(),
+            code: response.status().as_str().to_string(),
+            message,
+        })?;
+    }
+
+    Ok(response.text().await.map_err(GetPackageError::IO)?)
+}
+
+/// Fetch the full document of a package the way the registry stores it, including its `_rev`.
+pub async fn get_write_document(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+) -> miette::Result<serde_json::Value> {
+    let url = format!("{}/{}?write=true", registry, escape_package_name(name));
+
+    let text = registry_request("GET", &url, credentials, None).await?;
+
+    Ok(serde_json::from_str(&text).map_err(GetPackageError::Json)?)
+}
+
+/// Remove a single version, or the whole package when `version` is `None`, from the registry.
+///
+/// Removing the last remaining version removes the whole package, the same way npm does.
+pub async fn unpublish_package(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    version: Option<&str>,
+) -> miette::Result<()> {
+    let base = format!("{}/{}", registry, escape_package_name(name));
+
+    let mut document = get_write_document(registry, credentials, name).await?;
+    let rev = document["_rev"].as_str().unwrap_or_default().to_string();
+
+    let version = match version {
+        Some(version) => version,
+        None => {
+            registry_request(
+                "DELETE",
+                &format!("{}/-rev/{}", base, rev),
+                credentials,
+                None,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let tarball = document["versions"][version]["dist"]["tarball"]
+        .as_str()
+        .and_then(|tarball| tarball.rsplit('/').next())
+        .map(str::to_string)
+        .ok_or_else(|| VoltError::NoMatchingVersion {
+            package_name: name.to_string(),
+            range: version.to_string(),
+        })?;
+
+    let remaining = document["versions"].as_object_mut().and_then(|versions| {
+        versions.remove(version);
+        versions
+            .keys()
+            .filter_map(|v| node_semver::Version::parse(v).ok())
+            .max()
+    });
+
+    let remaining = match remaining {
+        Some(remaining) => remaining.to_string(),
+        None => {
+            registry_request(
+                "DELETE",
+                &format!("{}/-rev/{}", base, rev),
+                credentials,
+                None,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    // move dist-tags off the removed version, `latest` has to keep pointing somewhere
+    if let Some(tags) = document["dist-tags"].as_object_mut() {
+        let stale = tags
+            .iter()
+            .filter(|(_, v)| v.as_str() == Some(version))
+            .map(|(tag, _)| tag.clone())
+            .collect::<Vec<String>>();
+
+        for tag in stale {
+            if tag == "latest" {
+                tags.insert(tag, serde_json::json!(remaining));
+            } else {
+                tags.remove(&tag);
+            }
+        }
+    }
+
+    let body = serde_json::to_vec(&document).map_err(GetPackageError::Json)?;
+
+    registry_request(
+        "PUT",
+        &format!("{}/-rev/{}", base, rev),
+        credentials,
+        Some(body),
+    )
+    .await?;
+
+    // the tarball is removed separately, against the revision created by the update above
+    let document = get_write_document(registry, credentials, name).await?;
+    let rev = document["_rev"].as_str().unwrap_or_default();
+
+    registry_request(
+        "DELETE",
+        &format!("{}/-/{}/-rev/{}", base, tarball, rev),
+        credentials,
+        None,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set the deprecation message of the versions of `name` matching `range` or the dist-tag it
+/// names, an empty message takes the deprecation back. Returns the versions changed.
+pub async fn deprecate_versions(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    range: &str,
+    message: &str,
+) -> miette::Result<Vec<String>> {
+    let mut document = get_write_document(registry, credentials, name).await?;
+    let rev = document["_rev"].as_str().unwrap_or_default().to_string();
+
+    let tagged = document["dist-tags"][range].as_str().map(str::to_string);
+    let parsed = node_semver::Range::parse(range).ok();
+
+    let mut changed = vec![];
+
+    if let Some(versions) = document["versions"].as_object_mut() {
+        for (version, manifest) in versions.iter_mut() {
+            let matches = match (&tagged, &parsed) {
+                (Some(tagged), _) => tagged == version,
+                (None, Some(parsed)) => node_semver::Version::parse(version)
+                    .is_ok_and(|version| version.satisfies(parsed)),
+                (None, None) => false,
+            };
+
+            if matches {
+                manifest["deprecated"] = serde_json::json!(message);
+                changed.push(version.clone());
+            }
+        }
+    }
+
+    if changed.is_empty() {
+        return Err(VoltError::NoMatchingVersion {
+            package_name: name.to_string(),
+            range: range.to_string(),
+        }
+        .into());
+    }
+
+    let body = serde_json::to_vec(&document).map_err(GetPackageError::Json)?;
+
+    registry_request(
+        "PUT",
+        &format!("{}/{}/-rev/{}", registry, escape_package_name(name), rev),
+        credentials,
+        Some(body),
+    )
+    .await?;
+
+    changed.sort_by_key(|version| node_semver::Version::parse(version).ok());
+
+    Ok(changed)
+}
+
+/// Point the dist-tag `tag` of `name` at `version`.
+pub async fn set_dist_tag(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    tag: &str,
+    version: &str,
+) -> miette::Result<()> {
+    let url = format!(
+        "{}/-/package/{}/dist-tags/{}",
+        registry,
+        escape_package_name(name),
+        tag
+    );
+
+    let body = serde_json::to_vec(version).map_err(GetPackageError::Json)?;
+
+    registry_request("PUT", &url, credentials, Some(body)).await?;
+
+    Ok(())
+}
+
+/// Remove the dist-tag `tag` of `name`.
+pub async fn remove_dist_tag(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    tag: &str,
+) -> miette::Result<()> {
+    let url = format!(
+        "{}/-/package/{}/dist-tags/{}",
+        registry,
+        escape_package_name(name),
+        tag
+    );
+
+    registry_request("DELETE", &url, credentials, None).await?;
+
+    Ok(())
+}
+
+/// The `name` and `email` of a registry user, like a package lists its maintainers.
+pub async fn get_user(
+    registry: &str,
+    credentials: &Credentials,
+    username: &str,
+) -> miette::Result<serde_json::Value> {
+    let url = format!("{}/-/user/org.couchdb.user:{}", registry, username);
+
+    let text = registry_request("GET", &url, credentials, None).await?;
+    let user: serde_json::Value = serde_json::from_str(&text).map_err(GetPackageError::Json)?;
+
+    Ok(serde_json::json!({
+        "name": user["name"].as_str().unwrap_or(username),
+        "email": user["email"],
+    }))
+}
+
+/// Replace the maintainers of a package, `rev` is the revision of its write document.
+pub async fn set_maintainers(
+    registry: &str,
+    credentials: &Credentials,
+    name: &str,
+    rev: &str,
+    maintainers: &[serde_json::Value],
+) -> miette::Result<()> {
+    let url = format!("{}/{}/-rev/{}", registry, escape_package_name(name), rev);
+
+    let body = serde_json::json!({
+        "_id": name,
+        "_rev": rev,
+        "maintainers": maintainers,
+    });
+
+    let body = serde_json::to_vec(&body).map_err(GetPackageError::Json)?;
+
+    registry_request("PUT", &url, credentials, Some(body)).await?;
+
+    Ok(())
+}
+
+/// Publish a package document to the registry.
+///
+/// Server errors and dropped connections are retried like every other request, but before
+/// every retry the registry is checked for the version, so a request that went through
+/// before its response was lost is never reported as a conflict.
+/// ## Arguments
+/// * `registry` - Base url of the registry
+/// * `credentials` - Source of the token used to authenticate the request
+/// * `retries` - How often and how fast failed attempts are repeated
+/// * `name` - Name of the package being published
+/// * `version` - Version of the package being published
+/// * `shasum` - sha1 of the tarball, used to recognize our own earlier publish
+/// * `body` - Serialized publish document
+/// ## Returns
+/// * `Result<()>`
+pub async fn publish_package(
+    registry: &str,
+    credentials: &Credentials,
+    retries: &Retries,
+    name: &str,
+    version: &str,
+    shasum: &str,
+    body: Vec<u8>,
+) -> miette::Result<()> {
+    let url = format!("{}/{}", registry, escape_package_name(name));
+
+    let mut refreshed = false;
+
+    loop {
+        // set once the first attempt is on its way, any later one may find it landed
+        let attempted = AtomicBool::new(false);
+        let (url, body, attempted) = (&url, &body, &attempted);
+
+        // `false` when the token was rejected and the helper may hand out a new one
+        let published = retry(retries, || async move {
+            // the registry may have stored the version even though the request failed
+            if attempted.swap(true, Ordering::SeqCst)
+                && version_published(registry, credentials, name, version, shasum).await
+            {
+                return Ok(true);
+            }
+
+            let token = credentials.token().await.map_err(Failure::Fatal)?;
+
+            let request = Request::put(url)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(body.clone())
+                .map_err(|e| Failure::Fatal(VoltError::RequestBuilderError(e).into()))?;
+
+            let mut response =
+                client()
+                    .send_async(request)
+                    .await
+                    .map_err(|e| match is_transient_isahc(&e) {
+                        true => Failure::Transient(VoltError::NetworkError(e).into(), None),
+                        false => Failure::Fatal(VoltError::NetworkError(e).into()),
+                    })?;
+
+            let status = response.status();
+
+            if status.is_success() {
+                return Ok(true);
+            }
+
+            // short-lived tokens from an auth helper may have run out
+            if status == StatusCode::UNAUTHORIZED && !refreshed && credentials.invalidate().await {
+                return Ok(false);
+            }
+
+            let message = registry_error_message(&mut response).await;
+
+            let conflict = status == StatusCode::CONFLICT
+                || (status == StatusCode::FORBIDDEN
+                    && (message.contains("cannot publish over")
+                        || message.contains("previously published")));
+
+            if conflict {
+                return Err(Failure::Fatal(
+                    VoltError::PublishConflict {
+                        url: url.clone(),
+                        package_name: name.to_string(),
+                        version: version.to_string(),
+                    }
+                    .into(),
+                ));
+            }
+
+            let error = VoltError::PublishError {
+                url: url.clone(),
+                code: status.as_str().to_string(),
+                message,
+            }
+            .into();
+
+            match is_transient_status(status) {
+                true => Err(Failure::Transient(error, retry_after(response.headers()))),
+                false => Err(Failure::Fatal(error)),
+            }
+        })
+        .await?;
+
+        if published {
+            return Ok(());
+        }
+
+        refreshed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_credentials(registry: &str) -> Registries {
+        Registries {
+            credentials: vec![Arc::new(Credentials::with_token(registry, "secret"))],
+            ..Registries::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_tokens_to_their_registry() {
+        let registries = with_credentials("https://npm.example.com/private");
+
+        let token = registries
+            .token("https://npm.example.com/private/pkg/-/pkg-1.0.0.tgz")
+            .await
+            .unwrap();
+
+        assert_eq!(token.as_deref(), Some("secret"));
+
+        let token = registries
+            .token("https://npm.example.com/public/pkg")
+            .await
+            .unwrap();
+
+        assert_eq!(token, None);
+    }
+
+    #[tokio::test]
+    async fn never_sends_https_tokens_over_http() {
+        let registries = with_credentials("https://npm.example.com");
+
+        let token = registries
+            .token("http://npm.example.com/pkg/-/pkg-1.0.0.tgz")
+            .await
+            .unwrap();
+
+        assert_eq!(token, None);
+
+        // a registry configured over http gets what it asked for
+        let registries = with_credentials("http://localhost:4873");
+
+        let token = registries.token("http://localhost:4873/pkg").await.unwrap();
+
+        assert_eq!(token.as_deref(), Some("secret"));
+    }
+}
@@ -19,21 +19,63 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::hash::{Hash, Hasher};
-use std::io;
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
+use crate::core::utils::file_lock::FileLock;
+use crate::core::utils::platform::Platform;
+
+use miette::Diagnostic;
 use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
 use thiserror::Error;
+use tracing::debug;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Diagnostic)]
 pub enum LockFileError {
     #[error("unable to read lock file")]
-    IO(io::Error),
+    #[diagnostic(code(VOLT1100))]
+    IO(#[source] io::Error),
     #[error("unable to deserialize lock file")]
+    #[diagnostic(code(VOLT1101))]
     #[allow(dead_code)]
-    Decode(serde_json::Error),
-    // #[error("unable to serialize lock file")]
-    // Encode(serde_json::Error),
+    Decode(#[source] serde_json::Error),
+    #[error("unable to serialize lock file")]
+    #[diagnostic(code(VOLT1102))]
+    Encode(#[source] serde_json::Error),
+    #[error("lock file version {0} is newer than this version of volt supports")]
+    #[diagnostic(
+        code(VOLT1103),
+        help("upgrade volt, the lock file was written by a newer release.")
+    )]
+    UnsupportedVersion(u64),
+    #[error("unable to lock the lock file")]
+    #[diagnostic(
+        code(VOLT1104),
+        help("another volt process is writing it, try again once that one is done.")
+    )]
+    Locked(#[source] io::Error),
+}
+
+/// Version of the format [`LockFile::save()`] writes.
+pub const LOCKFILE_VERSION: u64 = 2;
+
+/// Upgrades between lock file versions, the first one turns a version 1 file into a version 2 one.
+const MIGRATIONS: &[fn(Value) -> Value] = &[migrate_v1];
+
+/// Version 1 lock files are nothing but the map of dependencies.
+fn migrate_v1(dependencies: Value) -> Value {
+    json!({ "lockfileVersion": 2, "dependencies": dependencies })
+}
+
+/// The lock file as it is stored on disk.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockFileDocument {
+    lockfile_version: u64,
+    dependencies: BTreeMap<DependencyID, DependencyLock>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    meta: BTreeMap<String, DependencyMeta>,
 }
 
 /// The lock file is responsible for locking/pinning dependency versions in a given project.
@@ -65,6 +107,17 @@ pub struct LockFile {
     pub path: PathBuf,
     #[serde(serialize_with = "sorted_dependencies")]
     pub dependencies: HashMap<DependencyID, DependencyLock>,
+    /// Why the direct dependencies were added, keyed by the name in package.json.
+    pub meta: BTreeMap<String, DependencyMeta>,
+}
+
+/// What `volt add --track` recorded about a direct dependency.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DependencyMeta {
+    /// Ticket url or justification given when the dependency was added.
+    pub note: String,
+    /// Date the note was recorded, `YYYY-MM-DD`.
+    pub added: String,
 }
 
 // #[derive(Clone, Serialize, Deserialize, Debug, Default)]
@@ -96,14 +149,12 @@ impl<'de> de::Deserialize<'de> for DependencyID {
     where
         D: Deserializer<'de>,
     {
-        let s: &str = Deserialize::deserialize(deserializer)?;
-        let mut parts = s.split('@');
-        let name = parts
-            .next()
-            .ok_or_else(|| de::Error::custom("missing dependency name"))?;
-        let version = parts
-            .next()
-            .ok_or_else(|| de::Error::custom("missing dependency version"))?;
+        let s: String = Deserialize::deserialize(deserializer)?;
+        // the first `@` after a scope separates the version, aliases (`x@npm:y@1.0.0`) carry more
+        let (name, version) = match s.char_indices().skip(1).find(|(_, c)| *c == '@') {
+            Some((index, _)) => (&s[..index], &s[index + 1..]),
+            _ => return Err(de::Error::custom("missing dependency version")),
+        };
         Ok(DependencyID(name.to_string(), version.to_string()))
     }
 }
@@ -142,6 +193,55 @@ pub struct DependencyLock {
     pub tarball: String,
     pub integrity: String,
     pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub optional: bool,
+    /// Hash of every file in the installed package, only recorded with `file-integrity` enabled.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+    /// Name the package is installed under when it was added as `alias@npm:name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Whether the package runs `preinstall`, `install` or `postinstall` scripts.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub has_install_script: bool,
+    /// Range from the `overrides` or `resolutions` of package.json that forced this version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overridden: Option<String>,
+    /// Keys of the `package-extensions` that changed the metadata of this package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extended: Vec<String>,
+    /// Platforms the package installs on, kept for every platform so one lockfile works on all.
+    #[serde(default, skip_serializing_if = "Platform::is_any")]
+    pub platform: Platform,
+    /// The `engines` of the package, so installs from the lockfile check them too.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub engines: BTreeMap<String, String>,
+    /// The rest of the manifest an install needs, `None` for entries written by older
+    /// versions or imported from package-lock.json, which are resolved again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<LockedManifest>,
+}
+
+/// What installing a package takes from its manifest besides the names of its dependencies,
+/// so the lockfile alone installs it again.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct LockedManifest {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bin: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peer_dependencies: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub optional_dependencies: Vec<String>,
+    /// Ranges of the dependencies, only known for packages resolved from registry metadata.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ranges: BTreeMap<String, String>,
+}
+
+impl DependencyLock {
+    /// Directory name of the package inside node_modules.
+    pub fn install_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
 }
 
 impl LockFile {
@@ -151,26 +251,56 @@ impl LockFile {
         Self {
             path: path.as_ref().to_owned(),
             dependencies: HashMap::with_capacity(1), // We will be installing at least 1 dependency
+            meta: BTreeMap::new(),
         }
     }
 
-    /// Loads a lock file from the given path.
+    /// Loads a lock file from the given path, upgrading it when it was written in an older format.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LockFileError> {
         let path = path.as_ref();
 
-        let dependencies = if path.exists() {
+        let (dependencies, meta) = if path.exists() {
             let f = File::open(path).map_err(LockFileError::IO)?;
-            serde_json::from_reader(f).map_err(LockFileError::Decode)?
+            let mut document: Value = serde_json::from_reader(f).map_err(LockFileError::Decode)?;
+
+            let mut version = document["lockfileVersion"].as_u64().unwrap_or(1);
+
+            if version > LOCKFILE_VERSION {
+                return Err(LockFileError::UnsupportedVersion(version));
+            }
+
+            while version < LOCKFILE_VERSION {
+                document = MIGRATIONS[version as usize - 1](document);
+                version += 1;
+            }
+
+            let document = serde_json::from_value::<LockFileDocument>(document)
+                .map_err(LockFileError::Decode)?;
+
+            (document.dependencies.into_iter().collect(), document.meta)
         } else {
-            HashMap::with_capacity(1)
+            (HashMap::with_capacity(1), BTreeMap::new())
         };
 
         Ok(Self {
             path: path.to_owned(),
             dependencies,
+            meta,
         })
     }
 
+    /// Loads a lock file, starting a new one when it can't be read. A lock file written by a
+    /// newer volt is still an error, overwriting it would lose what that version recorded.
+    pub fn load_or_new<P: AsRef<Path>>(path: P) -> Result<Self, LockFileError> {
+        match Self::load(&path) {
+            Err(LockFileError::UnsupportedVersion(version)) => {
+                Err(LockFileError::UnsupportedVersion(version))
+            }
+            Ok(lock_file) => Ok(lock_file),
+            Err(_) => Ok(Self::new(path)),
+        }
+    }
+
     // Saves a lock file dumping pretty, formatted json
     // pub fn save_pretty(&self) -> Result<(), LockFileError> {
     //     let lock_file = File::create(&self.path).map_err(LockFileError::IO)?;
@@ -178,10 +308,74 @@ impl LockFile {
     //     serde_json::to_writer_pretty(writer, &self.dependencies).map_err(LockFileError::Encode)
     // }
 
-    // Saves a lock file to the same path it was opened from.
-    // pub fn save(&self) -> Result<(), LockFileError> {
-    //     let lock_file = File::create(&self.path).map_err(LockFileError::IO)?;
-    //     let writer = BufWriter::new(lock_file);
-    //     serde_json::to_writer(writer, &self.dependencies).map_err(LockFileError::Encode)
-    // }
+    /// Saves a lock file to the same path it was opened from.
+    ///
+    /// Entries and their dependencies are sorted and the file is pretty printed, so the same
+    /// dependencies always give the same file and changes show up as small diffs.
+    pub fn save(&self) -> Result<(), LockFileError> {
+        self.write()
+    }
+
+    /// Save the lock file shared by several projects, like the global one.
+    ///
+    /// Entries other processes wrote since it was loaded are kept, the ones in `self` win
+    /// where both have the same package.
+    pub async fn save_merged(&mut self) -> Result<(), LockFileError> {
+        let _lock = FileLock::acquire(&self.path)
+            .await
+            .map_err(LockFileError::Locked)?;
+
+        let current = Self::load(&self.path)?;
+
+        for (id, dependency) in current.dependencies {
+            self.dependencies.entry(id).or_insert(dependency);
+        }
+
+        for (name, meta) in current.meta {
+            self.meta.entry(name).or_insert(meta);
+        }
+
+        self.write()
+    }
+
+    /// Write the lock file, `save_merged()` holds its `FileLock` meanwhile.
+    fn write(&self) -> Result<(), LockFileError> {
+        let document = LockFileDocument {
+            lockfile_version: LOCKFILE_VERSION,
+            dependencies: self
+                .dependencies
+                .iter()
+                .map(|(id, lock)| {
+                    let mut lock = lock.clone();
+                    lock.dependencies.sort();
+                    lock.dependencies.dedup();
+                    (id.clone(), lock)
+                })
+                .collect(),
+            meta: self.meta.clone(),
+        };
+
+        // readers that don't lock never see a half written file
+        let partial = PathBuf::from(format!("{}.partial", self.path.display()));
+
+        let lock_file = File::create(&partial).map_err(LockFileError::IO)?;
+        let mut writer = BufWriter::new(lock_file);
+
+        serde_json::to_writer_pretty(&mut writer, &document).map_err(LockFileError::Encode)?;
+
+        writer.write_all(b"\n").map_err(LockFileError::IO)?;
+        writer.flush().map_err(LockFileError::IO)?;
+
+        drop(writer);
+
+        std::fs::rename(&partial, &self.path).map_err(LockFileError::IO)?;
+
+        debug!(
+            "wrote {} packages to {}",
+            self.dependencies.len(),
+            self.path.display()
+        );
+
+        Ok(())
+    }
 }
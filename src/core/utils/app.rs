@@ -1,30 +1,55 @@
 use crate::{
     commands::add::Package,
-    core::utils::{enable_ansi_support, errors::VoltError},
+    core::model::config::VoltConfig,
+    core::model::http_manager::{client, configure_client, NetworkMode, Registries},
+    core::utils::{enable_ansi_support, engines, errors::VoltError},
 };
+use chrono::{DateTime, Utc};
 use clap::ArgMatches;
 use dirs::home_dir;
+use isahc::http::Uri;
+use isahc::{AsyncBody, Request, Response};
 use miette::Result;
-use sha1::Digest;
-use sha2::Sha512;
-use ssri::{Algorithm, Integrity};
-use std::{env, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::debug;
 
-use super::npm::parse_versions;
+use super::auth::Credentials;
+use super::constants::{
+    CONCURRENCY_PER_CPU, LOW_MEMORY_CONCURRENCY, MIN_CONCURRENCY, NPM_REGISTRY,
+};
+use super::extensions::Extensions;
+use super::git::{parse_git_spec, GitSpec};
+use super::npm::{parse_before, parse_versions};
+use super::registry_cache::RegistryCache;
+use super::workspace::{
+    filter_workspace, workspace_packages, workspace_root, WorkspaceFilter, WorkspacePackage,
+};
+use super::zero_install;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct App {
     pub current_dir: PathBuf,
     pub home_dir: PathBuf,
     pub node_modules_dir: PathBuf,
     pub volt_dir: PathBuf,
+    pub global_dir: PathBuf,
     pub lock_file_path: PathBuf,
+    pub config: VoltConfig,
+    /// Where every package is fetched from and the credentials for it, see `Registries`.
+    pub registries: Registries,
     pub args: ArgMatches,
 }
 
 impl App {
     pub fn initialize(args: &ArgMatches) -> Result<App> {
-        enable_ansi_support().unwrap();
+        // without it colors print as escape codes, which beats not running at all
+        enable_ansi_support().ok();
 
         // Current Directory
         let current_directory = env::current_dir().map_err(|e| VoltError::EnvironmentError {
@@ -44,17 +69,36 @@ impl App {
         // Create volt directory if it doesn't exist
         std::fs::create_dir_all(&volt_dir).map_err(VoltError::CreateDirError)?;
 
+        // Global Installs: /username/.volt/.global (package names can't start with a dot)
+        let global_dir = volt_dir.join(".global");
+
         // ./volt.lock
         let lock_file_path = current_directory.join("volt.lock");
 
-        Ok(App {
+        // ./volt.toml
+        let config = VoltConfig::load_project(&current_directory, &home_directory)?;
+
+        Self::export_proxy(&config)?;
+
+        configure_client(&config).map_err(VoltError::HttpClientError)?;
+
+        let app = App {
             current_dir: current_directory,
             home_dir: home_directory,
             node_modules_dir: node_modules_directory,
             volt_dir,
+            global_dir,
             lock_file_path,
+            config,
+            registries: Registries::default(),
             args: args.to_owned(),
-        })
+        }
+        .with_registries();
+
+        // the project's own engines hold for every command, not only installs
+        engines::check_project(&app)?;
+
+        Ok(app)
     }
 
     /// Retrieve packages passed in
@@ -62,7 +106,9 @@ impl App {
         let mut args = self
             .args
             .values_of("package-names")
-            .unwrap()
+            .into_iter()
+            .flatten()
+            .filter(|v| parse_git_spec(v).is_none())
             .map(|v| v.to_string())
             .collect::<Vec<String>>();
 
@@ -71,53 +117,272 @@ impl App {
         Ok(parse_versions(&args)?)
     }
 
+    /// Retrieve the packages passed in that point at git repositories
+    pub fn get_git_packages(&self) -> Vec<GitSpec> {
+        self.args
+            .values_of("package-names")
+            .map(|values| values.filter_map(parse_git_spec).collect())
+            .unwrap_or_default()
+    }
+
+    /// The same app, installing into the global node_modules instead of the project's.
+    pub fn global(&self) -> App {
+        App {
+            node_modules_dir: self.global_dir.join("node_modules"),
+            ..self.clone()
+        }
+    }
+
+    /// The same app, working on the project in `directory`, e.g. the root of a workspace.
+    pub fn in_directory(&self, directory: &Path) -> Result<App> {
+        let config = VoltConfig::load_project(directory, &self.home_dir)?;
+
+        Self::export_proxy(&config)?;
+
+        configure_client(&config).map_err(VoltError::HttpClientError)?;
+
+        Ok(App {
+            current_dir: directory.to_path_buf(),
+            node_modules_dir: directory.join("node_modules"),
+            lock_file_path: directory.join("volt.lock"),
+            config,
+            ..self.clone()
+        }
+        .with_registries())
+    }
+
+    /// Set up `registries` from the configuration, every registry gets one set of credentials
+    /// so helpers run once per registry rather than once per request.
+    fn with_registries(mut self) -> App {
+        let default = self.registry();
+
+        let scopes = self
+            .config
+            .registries
+            .iter()
+            .map(|(scope, registry)| (scope.clone(), registry.trim_end_matches('/').to_string()))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut urls = vec![default.clone()];
+
+        for registry in scopes.values() {
+            if !urls.contains(registry) {
+                urls.push(registry.clone());
+            }
+        }
+
+        let credentials = urls
+            .iter()
+            .map(|registry| Arc::new(Credentials::new(&self, registry)))
+            .collect();
+
+        self.registries = Registries {
+            default,
+            scopes,
+            credentials,
+            retries: self.config.retries.clone(),
+            network: self.network(),
+            cache: Some(RegistryCache::new(&self.volt_dir)),
+            extensions: Arc::new(Extensions::load(&self.config, &self.volt_dir)),
+        };
+
+        debug!(
+            "registry {}, {} scoped registries",
+            self.registries.default,
+            self.registries.scopes.len()
+        );
+
+        self
+    }
+
+    /// Where the tarball of `name@version` is kept in the `offline-mirror`, or the
+    /// zero-install cache without one, named like yarn names them (`@scope-name-1.0.0.tgz`)
+    /// so existing mirrors can be reused.
+    pub fn mirror_path(&self, name: &str, version: &str) -> Option<PathBuf> {
+        let mirror = match &self.config.offline_mirror {
+            Some(mirror) => self.current_dir.join(mirror),
+            None if zero_install::is_enabled(self) => zero_install::cache_dir(&self.current_dir),
+            None => return None,
+        };
+
+        Some(mirror.join(format!("{}-{}.tgz", name.replace('/', "-"), version)))
+    }
+
+    /// `--offline` and `--prefer-offline`, or `offline` and `prefer-offline` in volt.toml.
+    pub fn network(&self) -> NetworkMode {
+        if self.has_flag("offline") || self.config.offline == Some(true) {
+            NetworkMode::Offline
+        } else if self.has_flag("prefer-offline") || self.config.prefer_offline == Some(true) {
+            NetworkMode::PreferOffline
+        } else {
+            NetworkMode::Online
+        }
+    }
+
+    /// Workspace packages selected with `--filter`, `None` when there's no filter.
+    pub fn filtered_packages(&self) -> Result<Option<Vec<WorkspacePackage>>> {
+        let filters = match self.args.values_of("filter") {
+            Some(filters) => filters
+                .map(|filter| WorkspaceFilter::parse(filter, &self.current_dir))
+                .collect::<Result<Vec<_>>>()?,
+            None => return Ok(None),
+        };
+
+        let root = workspace_root(&self.current_dir).ok_or(VoltError::NotInWorkspace)?;
+
+        let packages = filter_workspace(&root, &workspace_packages(&root)?, &filters)?;
+
+        if packages.is_empty() {
+            return Err(VoltError::NoFilterMatch.into());
+        }
+
+        Ok(Some(packages))
+    }
+
+    /// Directory global executables are linked into, this is the one that has to be on PATH.
+    pub fn global_bin_dir(&self) -> PathBuf {
+        self.global_dir.join("bin")
+    }
+
+    /// Directory packages are extracted into, `store-dir` or `~/.volt` when unset.
+    pub fn store_dir(&self) -> PathBuf {
+        self.config
+            .store_dir
+            .clone()
+            .unwrap_or_else(|| self.volt_dir.clone())
+    }
+
+    /// The default registry: `--registry`, else `registry` in volt.toml or `.npmrc`, else npm.
+    pub fn registry(&self) -> String {
+        self.args
+            .value_of("registry")
+            .or(self.config.registry.as_deref())
+            .unwrap_or(NPM_REGISTRY)
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Make the proxy settings of volt.toml and `.npmrc` apply to every request.
+    ///
+    /// They are exported as `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, which the http
+    /// client reads, and so do git and lifecycle scripts. Both spellings are set since curl
+    /// only reads a lowercase `http_proxy`.
+    fn export_proxy(config: &VoltConfig) -> Result<()> {
+        let https_proxy = config.https_proxy.as_ref().or(config.proxy.as_ref());
+
+        let settings = [
+            ("HTTP_PROXY", config.proxy.as_ref()),
+            ("HTTPS_PROXY", https_proxy),
+            ("NO_PROXY", config.no_proxy.as_ref()),
+        ];
+
+        for (name, configured) in settings.iter() {
+            let value = match configured {
+                Some(value) => value.to_string(),
+                None => match env::var(name).or_else(|_| env::var(name.to_lowercase())) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                },
+            };
+
+            if *name != "NO_PROXY" && !value.is_empty() {
+                value.parse::<Uri>().map_err(|e| VoltError::InvalidProxy {
+                    proxy: value.clone(),
+                    source: e,
+                })?;
+            }
+
+            env::set_var(name, &value);
+            env::set_var(name.to_lowercase(), &value);
+        }
+
+        Ok(())
+    }
+
+    /// Send `request` to `url` with the shared client, giving up when the response headers
+    /// take longer than `timeouts.first-byte`.
+    pub async fn send(&self, request: Request<()>, url: &str) -> Result<Response<AsyncBody>> {
+        let download_error = |e: isahc::Error| VoltError::TarballDownloadError {
+            url: url.to_string(),
+            source: e.into(),
+        };
+
+        debug!("{} {}", request.method(), url);
+
+        let client = client();
+
+        match self.config.timeouts.first_byte() {
+            Some(limit) => Ok(tokio::time::timeout(limit, client.send_async(request))
+                .await
+                .map_err(|_| VoltError::RequestTimedOut {
+                    url: url.to_string(),
+                    seconds: limit.as_secs(),
+                })?
+                .map_err(download_error)?),
+            None => Ok(client.send_async(request).await.map_err(download_error)?),
+        }
+    }
+
+    /// Run an install, failing once it takes longer than `timeouts.install`.
+    pub async fn within_deadline<T>(&self, install: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.config.timeouts.install() {
+            Some(limit) => tokio::time::timeout(limit, install).await.map_err(|_| {
+                VoltError::InstallDeadlineExceeded {
+                    seconds: limit.as_secs(),
+                }
+            })?,
+            None => install.await,
+        }
+    }
+
+    /// Moment given to `--before`, only versions published before it are installed.
+    pub fn before(&self) -> Result<Option<DateTime<Utc>>> {
+        self.args.value_of("before").map(parse_before).transpose()
+    }
+
     /// Check if the app arguments contain the flags specified
     pub fn has_flag(&self, flag: &str) -> bool {
         self.args.is_present(flag)
     }
 
-    /// Calculate the hash of a tarball
-    ///
-    /// ## Examples
-    /// ```rs
-    /// calc_hash(bytes::Bytes::new(), ssri::Algorithm::Sha1)?;
-    /// ```
-    /// ## Returns
-    /// * Result<String>
-    pub fn calc_hash(data: &bytes::Bytes, algorithm: Algorithm) -> Result<String> {
-        match algorithm {
-            Algorithm::Sha1 => {
-                let mut hasher = sha1::Sha1::new();
-                std::io::copy(&mut &**data, &mut hasher).map_err(VoltError::HasherCopyError)?;
-
-                let integrity: Integrity = format!(
-                    "sha1-{}",
-                    base64::encode(format!("{:x}", hasher.clone().finalize()))
-                )
-                .parse()
-                .map_err(|_| VoltError::HashParseError {
-                    hash: format!(
-                        "sha1-{}",
-                        base64::encode(format!("{:x}", hasher.clone().finalize()))
-                    ),
-                })?;
+    /// `--json`, results are recorded for the document of `core::utils::output` instead of
+    /// printed.
+    pub fn json(&self) -> bool {
+        self.has_flag("json")
+    }
 
-                let hash = integrity
-                    .hashes
-                    .into_iter()
-                    .find(|h| h.algorithm == algorithm)
-                    .map(|h| Integrity { hashes: vec![h] })
-                    .map(|i| i.to_hex().1)
-                    .unwrap();
+    /// Whether `--ignore-scripts` was passed or `ignore-scripts` is set in volt.toml or `.npmrc`.
+    pub fn ignore_scripts(&self) -> bool {
+        self.has_flag("ignore-scripts") || self.config.ignore_scripts == Some(true)
+    }
 
-                return Ok(format!("sha1-{}", hash));
-            }
-            Algorithm::Sha512 => {
-                let mut hasher = Sha512::new();
-                std::io::copy(&mut &**data, &mut hasher).map_err(VoltError::HasherCopyError)?;
-                return Ok(format!("sha512-{:x}", hasher.finalize()));
-            }
-            _ => Ok(String::new()),
+    /// Whether `--engine-strict` was passed or `engine-strict` is set in volt.toml or `.npmrc`.
+    pub fn engine_strict(&self) -> bool {
+        self.has_flag("engine-strict") || self.config.engine_strict == Some(true)
+    }
+
+    /// Whether `low-memory` is set in volt.toml or `--low-memory` was passed.
+    pub fn low_memory(&self) -> bool {
+        self.config.low_memory || self.has_flag("low-memory")
+    }
+
+    /// Requests, downloads and extractions run at once: `--concurrency`, else `concurrency` in
+    /// volt.toml, else a couple in low-memory mode and a few per CPU otherwise.
+    pub fn concurrency(&self) -> usize {
+        let configured = self
+            .args
+            .value_of("concurrency")
+            .and_then(|concurrency| concurrency.parse::<usize>().ok())
+            .or(self.config.concurrency)
+            .filter(|concurrency| *concurrency > 0);
+
+        match configured {
+            Some(concurrency) => concurrency,
+            None if self.low_memory() => LOW_MEMORY_CONCURRENCY,
+            None => std::thread::available_parallelism()
+                .map_or(1, |cpus| cpus.get())
+                .saturating_mul(CONCURRENCY_PER_CPU)
+                .max(MIN_CONCURRENCY),
         }
     }
 }
@@ -0,0 +1,488 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Tokens for authenticated registry requests.
+//!
+//! A registry with an auth helper in `volt.toml` gets its tokens from that command, which is
+//! how short-lived tokens for Artifactory, CodeArtifact or Artifact Registry are obtained:
+//!
+//! ```toml
+//! [auth-helpers]
+//! "https://npm.pkg.github.com" = "gh auth token"
+//! "https://my-domain-123456789012.d.codeartifact.us-east-1.amazonaws.com/npm/repo" = "./scripts/codeartifact-token.sh"
+//! ```
+//!
+//! The helper runs with `VOLT_REGISTRY` set to the registry and prints either the bare token or
+//! `{ "token": "...", "expires": "2021-09-01T12:00:00Z" }`, `expires` may also be seconds since
+//! the epoch. Tokens are reused until they expire or the registry answers 401, then the helper
+//! runs again.
+//!
+//! Two helpers are built in, they exchange the ambient cloud credentials for a registry token
+//! through the cloud's CLI, which has to be installed and on PATH:
+//!
+//! * `builtin:codeartifact` runs `aws codeartifact get-authorization-token`, the domain, owner
+//!   and region come from the registry url.
+//! * `builtin:gcp` runs `gcloud auth application-default print-access-token` for Artifact
+//!   Registry.
+//!
+//! CI images without those CLIs need an auth helper command or an `_authToken` instead,
+//! `volt ping` reports a missing one.
+//!
+//! Registries without a helper use the `_authToken` `.npmrc` has for them, then `NPM_TOKEN`,
+//! and when neither is set CodeArtifact
+//! (`*.codeartifact.*.amazonaws.com`) and Artifact Registry (`*-npm.pkg.dev`) urls get their
+//! built-in helper.
+//!
+//! Package documents and tarballs are fetched with the same credentials, except that
+//! `NPM_TOKEN` is only ever sent to the default registry.
+
+use std::process::Command;
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::lifecycle::script_shell;
+use crate::core::utils::npmrc::normalize_registry;
+use crate::App;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use miette::Result;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+/// Where the tokens for one registry come from.
+#[derive(Debug)]
+pub struct Credentials {
+    pub registry: String,
+    helper: Option<Helper>,
+    /// `_authToken` from `.npmrc`, used when there's no helper.
+    configured: Option<String>,
+    shell: Option<String>,
+    cached: Mutex<Option<Token>>,
+}
+
+/// Where a registry's tokens come from.
+#[derive(Debug, Clone, PartialEq)]
+enum Helper {
+    /// A shell command from `[auth-helpers]`.
+    Command(String),
+    /// AWS CodeArtifact, with the credentials the AWS CLI finds.
+    CodeArtifact,
+    /// GCP Artifact Registry, with the application default credentials.
+    ArtifactRegistry,
+}
+
+impl Helper {
+    fn configured(helper: &str) -> Self {
+        match helper {
+            "builtin:codeartifact" => Self::CodeArtifact,
+            "builtin:gcp" => Self::ArtifactRegistry,
+            command => Self::Command(command.to_string()),
+        }
+    }
+
+    /// The built-in helper for a registry url, if it belongs to a known cloud registry.
+    fn detect(registry: &str) -> Option<Self> {
+        let host = host(registry)?;
+
+        if host.contains(".codeartifact.") && host.ends_with(".amazonaws.com") {
+            Some(Self::CodeArtifact)
+        } else if host.ends_with("-npm.pkg.dev") {
+            Some(Self::ArtifactRegistry)
+        } else {
+            None
+        }
+    }
+}
+
+fn host(registry: &str) -> Option<&str> {
+    let rest = registry.split_once("://")?.1;
+    rest.split('/').next()
+}
+
+/// `get-authorization-token` arguments for `https://<domain>-<owner>.d.codeartifact.<region>.amazonaws.com/npm/<repo>/`.
+fn codeartifact_arguments(registry: &str) -> Option<Vec<String>> {
+    let mut labels = host(registry)?.split('.');
+
+    let (domain, owner) = labels.next()?.rsplit_once('-')?;
+
+    if labels.next()? != "d" || labels.next()? != "codeartifact" {
+        return None;
+    }
+
+    let region = labels.next()?;
+
+    Some(
+        [
+            "codeartifact",
+            "get-authorization-token",
+            "--domain",
+            domain,
+            "--domain-owner",
+            owner,
+            "--region",
+            region,
+            "--query",
+            "{token: authorizationToken, expires: expiration}",
+            "--output",
+            "json",
+        ]
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect(),
+    )
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    token: String,
+    expires: Option<DateTime<Utc>>,
+}
+
+/// What a helper prints when it knows when its token expires.
+#[derive(Deserialize)]
+struct HelperOutput {
+    token: String,
+    expires: Option<Expiry>,
+}
+
+/// An RFC 3339 timestamp, or seconds since the epoch the way AWS CLI v1 prints them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Expiry {
+    Timestamp(String),
+    Seconds(f64),
+}
+
+impl Expiry {
+    fn parse(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Expiry::Timestamp(timestamp) => DateTime::parse_from_rfc3339(timestamp)
+                .ok()
+                .map(|expires| expires.with_timezone(&Utc)),
+            Expiry::Seconds(seconds) => Utc
+                .timestamp_opt(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+                .single(),
+        }
+    }
+}
+
+/// The token a helper printed, either bare or as `HelperOutput`.
+fn parse_helper_output(stdout: String) -> Token {
+    match serde_json::from_str::<HelperOutput>(&stdout) {
+        Ok(output) => Token {
+            token: output.token,
+            // an unreadable expiry is treated like none, a 401 still gets a new token
+            expires: output.expires.as_ref().and_then(Expiry::parse),
+        },
+        Err(_) => Token {
+            token: stdout,
+            expires: None,
+        },
+    }
+}
+
+impl Credentials {
+    pub fn new(app: &App, registry: &str) -> Self {
+        let configured = app
+            .config
+            .auth_tokens
+            .get(&normalize_registry(registry))
+            .cloned();
+
+        let helper = app
+            .config
+            .auth_helpers
+            .iter()
+            .find(|(url, _)| url.trim_end_matches('/') == registry.trim_end_matches('/'))
+            .map(|(_, helper)| Helper::configured(helper))
+            .or_else(|| {
+                if configured.is_some() || std::env::var_os("NPM_TOKEN").is_some() {
+                    None
+                } else {
+                    Helper::detect(registry)
+                }
+            });
+
+        Self {
+            registry: registry.to_string(),
+            helper,
+            configured,
+            shell: app.config.script_shell.clone(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Credentials that always send `token`.
+    #[cfg(test)]
+    pub fn with_token(registry: &str, token: &str) -> Self {
+        Self {
+            registry: registry.to_string(),
+            helper: None,
+            configured: Some(token.to_string()),
+            shell: None,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Token for reading from the registry, which works without one too. `NPM_TOKEN` is only
+    /// used with `env`, it's meant for the default registry and mustn't leak to others.
+    pub async fn optional_token(&self, env: bool) -> Result<Option<String>> {
+        if self.helper.is_none()
+            && self.configured.is_none()
+            && (!env || std::env::var_os("NPM_TOKEN").is_none())
+        {
+            return Ok(None);
+        }
+
+        self.token().await.map(Some)
+    }
+
+    /// Token to send with the next request, the helper runs when there is no valid one.
+    ///
+    /// Requests to the registry wait for a running helper instead of starting their own, the
+    /// helper itself runs off the runtime since SSO and cloud CLIs can take seconds.
+    pub async fn token(&self) -> Result<String> {
+        let helper = match &self.helper {
+            Some(helper) => helper,
+            None => {
+                if let Some(token) = &self.configured {
+                    return Ok(token.clone());
+                }
+
+                return Ok(std::env::var("NPM_TOKEN").map_err(|_| {
+                    VoltError::MissingAuthToken {
+                        registry: self.registry.clone(),
+                    }
+                })?);
+            }
+        };
+
+        let mut cached = self.cached.lock().await;
+
+        // a token about to expire could run out while the request is on its way
+        let valid = cached.as_ref().is_some_and(|token| {
+            token
+                .expires
+                .is_none_or(|expires| expires > Utc::now() + Duration::seconds(30))
+        });
+
+        if !valid {
+            let (program, arguments) = self.helper_command(helper)?;
+            let (registry, helper) = (self.registry.clone(), helper.clone());
+            let shell = program.clone();
+
+            let token = tokio::task::spawn_blocking(move || {
+                run_helper(&registry, &helper, &program, &arguments)
+            })
+            .await
+            .map_err(|e| VoltError::ScriptSpawnError {
+                shell,
+                source: std::io::Error::new(std::io::ErrorKind::Interrupted, e),
+            })??;
+
+            *cached = Some(token);
+        }
+
+        Ok(cached.as_ref().unwrap().token.clone())
+    }
+
+    /// Forget the token after the registry rejected it. Returns whether asking the helper
+    /// again can give a different one.
+    pub async fn invalidate(&self) -> bool {
+        if self.helper.is_none() {
+            return false;
+        }
+
+        *self.cached.lock().await = None;
+
+        true
+    }
+
+    /// The program `helper` runs and its arguments.
+    fn helper_command(&self, helper: &Helper) -> Result<(String, Vec<String>)> {
+        Ok(match helper {
+            Helper::Command(command) => {
+                let (shell, mut flags) = script_shell(self.shell.as_deref());
+                flags.push(command.clone());
+                (shell, flags)
+            }
+            Helper::CodeArtifact => (
+                String::from("aws"),
+                codeartifact_arguments(&self.registry).ok_or_else(|| {
+                    VoltError::InvalidCodeArtifactUrl {
+                        registry: self.registry.clone(),
+                    }
+                })?,
+            ),
+            // the SDK installs a batch file on Windows, which has to be named in full
+            Helper::ArtifactRegistry => (
+                String::from(if cfg!(windows) {
+                    "gcloud.cmd"
+                } else {
+                    "gcloud"
+                }),
+                vec![
+                    String::from("auth"),
+                    String::from("application-default"),
+                    String::from("print-access-token"),
+                ],
+            ),
+        })
+    }
+}
+
+/// Run `program` for a token of `registry`, which blocks until the helper exits.
+fn run_helper(
+    registry: &str,
+    helper: &Helper,
+    program: &str,
+    arguments: &[String],
+) -> Result<Token> {
+    let output = Command::new(program)
+        .args(arguments)
+        .env("VOLT_REGISTRY", registry)
+        .output()
+        .map_err(|e| match helper {
+            Helper::CodeArtifact | Helper::ArtifactRegistry
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                VoltError::MissingCloudCli {
+                    registry: registry.to_string(),
+                    program: program.to_string(),
+                }
+            }
+            _ => VoltError::ScriptSpawnError {
+                shell: program.to_string(),
+                source: e,
+            },
+        })?;
+
+    if !output.status.success() {
+        return Err(VoltError::AuthHelperFailed {
+            registry: registry.to_string(),
+            code: output
+                .status
+                .code()
+                .map(|code| format!("code {}", code))
+                .unwrap_or_else(|| String::from("a signal")),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let token = parse_helper_output(stdout);
+
+    if token.token.is_empty() {
+        return Err(VoltError::MissingAuthToken {
+            registry: registry.to_string(),
+        }
+        .into());
+    }
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration as StdDuration;
+
+    #[test]
+    fn reads_expiry_timestamps() {
+        let token = parse_helper_output(String::from(
+            r#"{ "token": "abc", "expires": "2021-09-01T12:00:00Z" }"#,
+        ));
+
+        assert_eq!(token.token, "abc");
+        assert_eq!(
+            token.expires,
+            Some(Utc.with_ymd_and_hms(2021, 9, 1, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn reads_expiry_seconds() {
+        // `aws codeartifact get-authorization-token` with AWS CLI v1
+        let token = parse_helper_output(String::from(
+            r#"{ "token": "abc", "expires": 1630497600.0 }"#,
+        ));
+
+        assert_eq!(token.token, "abc");
+        assert_eq!(
+            token.expires,
+            Some(Utc.with_ymd_and_hms(2021, 9, 1, 12, 0, 0).unwrap())
+        );
+    }
+
+    /// A slow helper runs once for concurrent requests, and the runtime keeps going meanwhile.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn helper_runs_off_the_runtime() {
+        let runs = std::env::temp_dir().join(format!("volt-auth-helper-{}", std::process::id()));
+
+        let credentials = Credentials {
+            registry: String::from("https://registry.example.com"),
+            helper: Some(Helper::Command(format!(
+                "echo run >> {}; sleep 1; echo abc",
+                runs.display()
+            ))),
+            configured: None,
+            shell: None,
+            cached: Mutex::new(None),
+        };
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+
+        let ticker = {
+            let ticks = ticks.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(StdDuration::from_millis(50)).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        let (first, second, third) = tokio::join!(
+            credentials.token(),
+            credentials.token(),
+            credentials.token()
+        );
+
+        ticker.abort();
+
+        assert_eq!(
+            [first.unwrap(), second.unwrap(), third.unwrap()],
+            ["abc", "abc", "abc"]
+        );
+        assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 1);
+        assert!(ticks.load(Ordering::SeqCst) >= 10);
+
+        std::fs::remove_file(&runs).ok();
+    }
+
+    #[test]
+    fn reads_bare_tokens() {
+        let token = parse_helper_output(String::from("abc"));
+
+        assert_eq!(token.token, "abc");
+        assert_eq!(token.expires, None);
+    }
+}
@@ -0,0 +1,200 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Link the executables of installed packages into `node_modules/.bin`.
+//!
+//! Every package in node_modules gets its bins in the `.bin` of the node_modules it's in, a
+//! nested package in the one of the package it's nested in, where node and `volt run` look
+//! for them first. On unix a bin is a relative symlink to its file, which is made executable.
+//! Windows can't run those, it gets the same shims npm writes: a `.cmd` for cmd.exe, a `.ps1`
+//! for PowerShell and a shell script for Git Bash, all running the file with node.
+//!
+//! ```text
+//! node_modules/.bin/tsc -> ../typescript/bin/tsc
+//! ```
+
+use crate::core::utils::voltapi::VoltPackage;
+
+use std::path::{Path, PathBuf};
+
+/// The `.bin` directory of `node_modules`.
+pub fn bin_dir(node_modules: &Path) -> PathBuf {
+    node_modules.join(".bin")
+}
+
+/// node_modules directory `package` is in when it's installed at `destination`.
+pub fn node_modules_of<'a>(destination: &'a Path, package: &VoltPackage) -> Option<&'a Path> {
+    destination
+        .ancestors()
+        .nth(package.install_name().split('/').count())
+}
+
+/// The bins of `package`, by the name they're linked as and the file they run.
+///
+/// Scoped names lose their scope like with npm, and names that would land outside `.bin`
+/// are left out.
+fn bins(package: &VoltPackage) -> Vec<(String, String)> {
+    let mut bins = package
+        .bin
+        .iter()
+        .flatten()
+        .filter_map(|(name, file)| {
+            let name = name.rsplit('/').next().unwrap_or(name);
+
+            if name.is_empty() || name == "." || name == ".." || name.contains('\\') {
+                return None;
+            }
+
+            Some((name.to_string(), file.trim_start_matches("./").to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    bins.sort();
+
+    bins
+}
+
+/// Paths linking the bins of `package` into `node_modules` writes.
+pub fn bin_targets(node_modules: &Path, package: &VoltPackage) -> Vec<PathBuf> {
+    let directory = bin_dir(node_modules);
+
+    bins(package)
+        .into_iter()
+        .flat_map(|(name, _)| {
+            let mut targets = vec![directory.join(&name)];
+
+            if cfg!(windows) {
+                targets.push(directory.join(format!("{}.cmd", name)));
+                targets.push(directory.join(format!("{}.ps1", name)));
+            }
+
+            targets
+        })
+        .collect()
+}
+
+/// Link the bins of `package`, installed in `node_modules`, into its `.bin`, replacing the
+/// ones of an earlier version.
+/// ## Returns
+/// * `Result<Vec<PathBuf>>` - the paths written
+pub fn link_bins(node_modules: &Path, package: &VoltPackage) -> std::io::Result<Vec<PathBuf>> {
+    let bins = bins(package);
+
+    if bins.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let directory = bin_dir(node_modules);
+
+    std::fs::create_dir_all(&directory)?;
+
+    let mut written = vec![];
+
+    for (name, file) in bins.iter() {
+        // relative to .bin, so the links survive moving the project
+        let relative = Path::new("..").join(package.install_name()).join(file);
+        let target = node_modules.join(package.install_name()).join(file);
+
+        // a package can declare a bin it doesn't ship
+        if !target.is_file() {
+            continue;
+        }
+
+        for path in link(&directory, name, &relative, &target)? {
+            written.push(path);
+        }
+    }
+
+    Ok(written)
+}
+
+#[cfg(unix)]
+fn link(
+    directory: &Path,
+    name: &str,
+    relative: &Path,
+    target: &Path,
+) -> std::io::Result<Vec<PathBuf>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = directory.join(name);
+
+    replace(&path)?;
+
+    std::os::unix::fs::symlink(relative, &path)?;
+
+    let mut permissions = std::fs::metadata(target)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+
+    std::fs::set_permissions(target, permissions)?;
+
+    Ok(vec![path])
+}
+
+#[cfg(windows)]
+fn link(
+    directory: &Path,
+    name: &str,
+    relative: &Path,
+    _target: &Path,
+) -> std::io::Result<Vec<PathBuf>> {
+    let windows = relative.display().to_string().replace('/', "\\");
+    let posix = relative.display().to_string().replace('\\', "/");
+
+    let shims = [
+        (
+            directory.join(format!("{}.cmd", name)),
+            format!(
+                "@SETLOCAL\r\n@IF EXIST \"%~dp0\\node.exe\" (\r\n  \"%~dp0\\node.exe\" \"%~dp0\\{0}\" %*\r\n) ELSE (\r\n  @SET PATHEXT=%PATHEXT:;.JS;=;%\r\n  node \"%~dp0\\{0}\" %*\r\n)\r\n",
+                windows
+            ),
+        ),
+        (
+            directory.join(format!("{}.ps1", name)),
+            format!(
+                "#!/usr/bin/env pwsh\n$basedir = Split-Path $MyInvocation.MyCommand.Definition -Parent\n& node \"$basedir/{}\" $args\nexit $LASTEXITCODE\n",
+                posix
+            ),
+        ),
+        (
+            directory.join(name),
+            format!(
+                "#!/bin/sh\nbasedir=$(dirname \"$(echo \"$0\" | sed -e 's,\\\\,/,g')\")\nexec node \"$basedir/{}\" \"$@\"\n",
+                posix
+            ),
+        ),
+    ];
+
+    let mut written = vec![];
+
+    for (path, contents) in shims.iter() {
+        replace(path)?;
+
+        std::fs::write(path, contents)?;
+
+        written.push(path.clone());
+    }
+
+    Ok(written)
+}
+
+/// Remove what an earlier install left at `path`.
+fn replace(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(_) => std::fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
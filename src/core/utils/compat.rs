@@ -0,0 +1,153 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Check whether a package version fits the current project, its `engines` against the
+//! tools installed and its peer dependencies against the packages installed.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::core::utils::package::PackumentVersion;
+use crate::core::utils::{installed_version, satisfies};
+use crate::core::VERSION;
+use crate::App;
+
+/// How a single requirement of a package compares to the project.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compatibility {
+    /// The version found satisfies the range.
+    Satisfied(String),
+    /// The version found doesn't satisfy the range.
+    Conflict(String),
+    /// Nothing to compare against was found.
+    Missing,
+    /// Volt has no way to check this requirement.
+    Unchecked,
+}
+
+/// A range a package requires, e.g. `node >=14` or `react ^17.0.0`.
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    pub name: String,
+    pub range: String,
+    pub status: Compatibility,
+}
+
+/// `process.platform` of node on this machine, what the `os` field of packages lists.
+pub fn node_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    }
+}
+
+/// `process.arch` of node on this machine, what the `cpu` field of packages lists.
+pub fn node_cpu() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64",
+        "loongarch64" => "loong64",
+        arch => arch,
+    }
+}
+
+/// Whether an `os` or `cpu` field lets `current` in, npm reads `!name` as everything but `name`.
+pub fn platform_allows(list: &[String], current: &str) -> bool {
+    let (excluded, included): (Vec<&String>, Vec<&String>) =
+        list.iter().partition(|entry| entry.starts_with('!'));
+
+    !excluded.iter().any(|entry| &entry[1..] == current)
+        && (included.is_empty() || included.iter().any(|entry| *entry == current))
+}
+
+/// Version of the `node` on PATH, without the leading `v`.
+pub fn node_version() -> Option<String> {
+    let output = Command::new("node").arg("--version").output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout);
+
+    Some(version.trim().trim_start_matches('v').to_string())
+}
+
+fn compare(version: Option<String>, range: &str) -> Compatibility {
+    match version {
+        Some(version) if satisfies(&version, range) => Compatibility::Satisfied(version),
+        Some(version) => Compatibility::Conflict(version),
+        None => Compatibility::Missing,
+    }
+}
+
+/// The `engines` of `version` against the running `node` and volt itself.
+pub fn check_engines(version: &PackumentVersion) -> Vec<Requirement> {
+    check_engine_map(version.engines_map())
+}
+
+/// An `engines` field, keyed by engine, against the running `node` and volt itself.
+pub fn check_engine_map(engines: BTreeMap<String, String>) -> Vec<Requirement> {
+    engines
+        .into_iter()
+        .map(|(name, range)| {
+            let status = match name.as_str() {
+                "node" => compare(node_version(), &range),
+                "volt" => compare(Some(VERSION.to_string()), &range),
+                _ => Compatibility::Unchecked,
+            };
+
+            Requirement {
+                name,
+                range,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// The peer dependencies of `version` against the packages installed in the project.
+///
+/// Optional peers are only reported when they are installed.
+pub fn check_peer_dependencies(app: &App, version: &PackumentVersion) -> Vec<Requirement> {
+    let required = version
+        .required_peer_dependencies()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+
+    let mut peers = version.peer_dependencies.iter().collect::<Vec<_>>();
+    peers.sort();
+
+    peers
+        .into_iter()
+        .filter_map(|(name, range)| {
+            let status = compare(installed_version(app, name), range);
+
+            if status == Compatibility::Missing && !required.contains(&name.as_str()) {
+                return None;
+            }
+
+            Some(Requirement {
+                name: name.clone(),
+                range: range.clone(),
+                status,
+            })
+        })
+        .collect()
+}
@@ -1,2 +1,56 @@
 pub static PROGRESS_CHARS: &str = "=> ";
-pub static MAX_RETRIES: u8 = 4;
+pub static NPM_REGISTRY: &str = "https://registry.npmjs.org";
+/// Where resolved trees of npm packages are served from, `<cdn>/<name>/data.json`.
+pub static VOLT_CDN: &str = "https://cdn.jsdelivr.net/npm/@voltpkg";
+/// Downloads and registry requests in flight at once with `low-memory`.
+pub static LOW_MEMORY_CONCURRENCY: usize = 2;
+/// Downloads and registry requests in flight at once per CPU when `concurrency` is unset.
+pub static CONCURRENCY_PER_CPU: usize = 4;
+/// Fewest downloads in flight by default, they mostly wait on the network.
+pub static MIN_CONCURRENCY: usize = 16;
+/// Bytes a tarball needs before it's downloaded to disk, where an interrupted download resumes.
+pub static RESUMABLE_MIN_SIZE: u64 = 4 * 1024 * 1024;
+/// Attempts a request gets when `retries.attempts` is unset.
+pub static RETRY_ATTEMPTS: u32 = 5;
+/// Milliseconds before the first retry when `retries.base-delay` is unset, doubling after that.
+pub static RETRY_BASE_DELAY: u64 = 250;
+/// Longest wait between two attempts in milliseconds when `retries.max-delay` is unset.
+pub static RETRY_MAX_DELAY: u64 = 10_000;
+/// Pure JS or WASM builds of packages shipping a native one per platform, see `core::utils::fallbacks`.
+pub static NATIVE_FALLBACKS: &[(&str, &str)] = &[
+    ("esbuild", "esbuild-wasm"),
+    ("@swc/core", "@swc/wasm"),
+    ("rollup", "@rollup/wasm-node"),
+    ("lightningcss", "lightningcss-wasm"),
+    ("sass-embedded", "sass"),
+    ("@parcel/watcher", "@parcel/watcher-wasm"),
+];
+/// Seconds between keep-alive probes on pooled connections waiting for their next request.
+pub static TCP_KEEPALIVE: u64 = 60;
+/// How many times slower than usual a phase has to be to get hints, see `core::utils::timings`.
+pub static SLOW_FACTOR: f64 = 5.0;
+/// Seconds a phase has to take at least before it is called slow.
+pub static SLOW_PHASE_MIN: u64 = 2;
+/// Seconds to wait for other volt processes to finish writing a shared file.
+pub static FILE_LOCK_TIMEOUT: u64 = 60;
+/// Seconds to open a connection when `timeouts.connect` is unset.
+pub static CONNECT_TIMEOUT: u64 = 10;
+/// Seconds for the TLS handshake when `timeouts.tls-handshake` is unset.
+pub static TLS_HANDSHAKE_TIMEOUT: u64 = 10;
+/// Seconds to wait for a response once connected when `timeouts.first-byte` is unset.
+pub static FIRST_BYTE_TIMEOUT: u64 = 30;
+/// Megabytes the zero-install cache may take when `zero-install.max-size` is unset.
+pub static ZERO_INSTALL_MAX_SIZE: u64 = 500;
+/// Megabytes of the largest archive kept in the zero-install cache when
+/// `zero-install.max-archive-size` is unset.
+pub static ZERO_INSTALL_MAX_ARCHIVE_SIZE: u64 = 25;
+/// Milliseconds between two checks of the manifests `volt install --watch` watches.
+pub static WATCH_INTERVAL: u64 = 500;
+/// Where official builds of node and their checksums are downloaded from, see `core::utils::node_runtime`.
+pub static NODE_DIST: &str = "https://nodejs.org/dist";
+/// File pinning the node a project's scripts run with, in the project or a directory above it.
+pub static NODE_VERSION_FILE: &str = ".node-version";
+/// npm's API counting the downloads of public packages.
+pub static NPM_DOWNLOADS_API: &str = "https://api.npmjs.org/downloads";
+/// npms.io, which counts the packages depending on each public package.
+pub static NPMS_API: &str = "https://api.npms.io/v2";
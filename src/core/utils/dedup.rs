@@ -0,0 +1,182 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Measure how much disk space the store saves compared to a plain copy of every file.
+//!
+//! Only file metadata is read: a file with more than one link shares its storage with the copy
+//! in the store, or with another package in the tree, so its bytes aren't taken twice. Copies
+//! and reflinks have a single link and count as taking their full size.
+
+use crate::core::utils::voltapi::VoltPackage;
+
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::path::Path;
+use tracing::info;
+use walkdir::WalkDir;
+
+/// Number of packages listed in the shared files report.
+const TOP_PACKAGES: usize = 5;
+
+/// Disk usage of a set of installed packages.
+#[derive(Debug, Default)]
+pub struct DedupReport {
+    /// Files linked into node_modules.
+    pub files: usize,
+    /// Bytes a plain copy of every file would take.
+    pub naive_bytes: u64,
+    /// Bytes of files sharing their storage with the store or another file in the tree.
+    pub shared_bytes: u64,
+    /// (package, shared bytes, shared files), most shared first.
+    pub packages: Vec<(String, u64, usize)>,
+}
+
+/// Identity of the file on disk and its number of links, `None` where the platform has neither.
+#[cfg(unix)]
+fn file_links(metadata: &Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+
+#[cfg(not(unix))]
+fn file_links(_metadata: &Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
+
+impl DedupReport {
+    /// Walk the installed copy of every package in `node_modules` and tally its files.
+    pub fn collect(node_modules: &Path, packages: &[&VoltPackage]) -> DedupReport {
+        let mut report = DedupReport::default();
+
+        let mut seen_files = HashSet::new();
+        let mut shared: HashMap<String, (u64, usize)> = HashMap::new();
+
+        let mut names = packages
+            .iter()
+            .map(|p| p.install_name())
+            .collect::<Vec<_>>();
+
+        names.sort_unstable();
+        names.dedup();
+
+        for name in names {
+            let entries = WalkDir::new(node_modules.join(name))
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file());
+
+            for entry in entries {
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+
+                let size = metadata.len();
+
+                report.files += 1;
+                report.naive_bytes += size;
+
+                // a second link in the tree is as shared as one into the store
+                let is_shared = match file_links(&metadata) {
+                    Some((id, links)) => links > 1 || !seen_files.insert(id),
+                    None => false,
+                };
+
+                if is_shared {
+                    report.shared_bytes += size;
+
+                    let package = shared.entry(name.to_string()).or_default();
+                    package.0 += size;
+                    package.1 += 1;
+                }
+            }
+        }
+
+        report.packages = shared
+            .into_iter()
+            .map(|(name, (bytes, files))| (name, bytes, files))
+            .collect();
+
+        report
+            .packages
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        report
+    }
+
+    /// Print the summary and the packages sharing the most storage.
+    pub fn print(&self) {
+        if self.files == 0 {
+            return;
+        }
+
+        // copied files share nothing, claiming savings for them would be wrong
+        if self.shared_bytes == 0 {
+            println!(
+                "{}: installed {} files ({}), none of them share storage with the store.",
+                "success".bright_green(),
+                self.files,
+                format_bytes(self.naive_bytes)
+            );
+
+            return;
+        }
+
+        let percent = self.shared_bytes as f64 / self.naive_bytes.max(1) as f64 * 100.0;
+
+        println!(
+            "{}: linked {} files ({}), the store saved {} ({:.1}%) over plain copies.",
+            "success".bright_green(),
+            self.files,
+            format_bytes(self.naive_bytes),
+            format_bytes(self.shared_bytes).bright_green(),
+            percent
+        );
+
+        info!("the packages sharing the most with the store:");
+
+        for (name, bytes, files) in self.packages.iter().take(TOP_PACKAGES) {
+            println!(
+                "  {} {} in {} files",
+                name.bright_cyan(),
+                format_bytes(*bytes),
+                files
+            );
+        }
+    }
+}
+
+/// Format a byte count the way file managers do, e.g. `12.3 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64;
+    let mut unit = "B";
+
+    for next in UNITS.iter() {
+        if value < 1000.0 {
+            break;
+        }
+
+        value /= 1000.0;
+        unit = next;
+    }
+
+    format!("{:.1} {}", value, unit)
+}
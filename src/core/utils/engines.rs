@@ -0,0 +1,150 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Hold the running node to the `engines` of the project and of the packages installed.
+//!
+//! A package whose `engines.node` the `node` on PATH doesn't satisfy usually works anyway, so
+//! like npm volt only warns about it. With `engine-strict` set in volt.toml or `.npmrc`, or
+//! `--engine-strict`, it fails instead. The engines of packages are known for trees resolved
+//! from registry metadata and kept in volt.lock, the CDN doesn't list them. The `engines` of
+//! the project itself are checked on every command, against node and volt.
+
+use std::collections::BTreeMap;
+
+use crate::core::utils::compat::{check_engine_map, node_version, Compatibility};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::satisfies;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::App;
+
+use colored::Colorize;
+use miette::Result;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+/// Check the `engines` of package.json in the current directory, if it has any.
+pub fn check_project(app: &App) -> Result<()> {
+    let manifest = std::fs::read_to_string(app.current_dir.join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok());
+
+    let engines = match manifest
+        .as_ref()
+        .and_then(|manifest| manifest["engines"].as_object())
+    {
+        Some(engines) => engines
+            .iter()
+            .filter_map(|(name, range)| {
+                let range = range.as_str().filter(|range| is_range(range))?;
+
+                Some((name.clone(), range.to_string()))
+            })
+            .collect::<BTreeMap<_, _>>(),
+        None => return Ok(()),
+    };
+
+    for requirement in check_engine_map(engines) {
+        let current = match requirement.status {
+            Compatibility::Conflict(current) => current,
+            Compatibility::Missing => {
+                debug!(
+                    "{} isn't on PATH, its engine isn't checked",
+                    requirement.name
+                );
+                continue;
+            }
+            _ => continue,
+        };
+
+        if app.engine_strict() {
+            return Err(VoltError::UnsupportedProjectEngine {
+                engine: requirement.name,
+                range: requirement.range,
+                current,
+            }
+            .into());
+        }
+
+        warn!(
+            "the project needs {} {}, this is {} {}",
+            requirement.name,
+            requirement.range.bright_cyan(),
+            requirement.name,
+            current.bright_yellow()
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the `engines.node` of the packages about to be installed.
+pub fn check_packages(app: &App, packages: &[&VoltPackage]) -> Result<()> {
+    let wanted = packages
+        .iter()
+        .filter_map(|package| {
+            let range = package.engines.as_ref()?.get("node")?;
+
+            is_range(range).then_some((package, range))
+        })
+        .collect::<Vec<_>>();
+
+    if wanted.is_empty() {
+        return Ok(());
+    }
+
+    let current = match node_version() {
+        Some(current) => current,
+        None => {
+            debug!("node isn't on PATH, the engines of packages aren't checked");
+            return Ok(());
+        }
+    };
+
+    let mut conflicts = wanted
+        .into_iter()
+        .filter(|(_, range)| !satisfies(&current, range))
+        .map(|(package, range)| {
+            format!("{}@{} needs node {}", package.name, package.version, range)
+        })
+        .collect::<Vec<_>>();
+
+    conflicts.sort();
+    conflicts.dedup();
+
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    if app.engine_strict() {
+        return Err(VoltError::UnsupportedEngine {
+            current,
+            count: conflicts.len(),
+            packages: conflicts.join("\n  "),
+        }
+        .into());
+    }
+
+    for conflict in conflicts.iter() {
+        warn!("{}, this is node {}", conflict, current.bright_yellow());
+    }
+
+    Ok(())
+}
+
+/// Ranges that don't parse are ignored like npm does, some packages put prose in `engines`.
+fn is_range(range: &str) -> bool {
+    node_semver::Range::parse(range).is_ok()
+}
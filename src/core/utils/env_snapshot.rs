@@ -0,0 +1,364 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Record the environment a script ran in with `volt run --env-snapshot`, and compare two
+//! records with `volt env-diff` to find out why a script behaves differently on two machines.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use colored::Colorize;
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::lifecycle::ScriptPlan;
+use crate::core::VERSION;
+
+/// Tools whose `--version` is recorded, when they are on PATH.
+const TOOLS: &[&str] = &[
+    "node", "npm", "npx", "yarn", "pnpm", "git", "python3", "tsc",
+];
+
+/// Parts of variable names that mark them as secrets, their values are only recorded as a hash.
+const SECRETS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "KEY",
+    "AUTH",
+    "CREDENTIAL",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct EnvSnapshot {
+    pub volt_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Script the snapshot was taken for.
+    pub script: String,
+    pub directory: PathBuf,
+    /// Shell executable and its flags.
+    pub shell: Vec<String>,
+    /// Every variable the main step runs with, secrets replaced by a hash of their value.
+    pub env: BTreeMap<String, String>,
+    /// `--version` of the tools on PATH.
+    pub tools: BTreeMap<String, String>,
+    /// Where the commands the steps start with and the tools resolve to on PATH.
+    pub bins: BTreeMap<String, PathBuf>,
+}
+
+impl EnvSnapshot {
+    /// Take a snapshot of what running `script` with `plan` would see.
+    pub fn capture(plan: &ScriptPlan, script: &str) -> EnvSnapshot {
+        let step = plan
+            .steps
+            .iter()
+            .find(|step| step.event == script)
+            .unwrap_or(&plan.steps[0]);
+
+        let mut env = std::env::vars().collect::<BTreeMap<_, _>>();
+        env.extend(plan.step_env(step));
+
+        for (name, value) in env.iter_mut() {
+            *value = redact(name, value);
+        }
+
+        let path = plan.path();
+
+        let commands = plan
+            .steps
+            .iter()
+            .filter_map(|step| first_command(&step.command))
+            .chain(TOOLS.iter().copied());
+
+        let bins = commands
+            .filter_map(|command| Some((command.to_string(), which(command, &path)?)))
+            .collect::<BTreeMap<_, _>>();
+
+        let tools = TOOLS
+            .iter()
+            .filter(|tool| bins.contains_key(**tool))
+            .filter_map(|tool| Some((tool.to_string(), tool_version(&bins[*tool], &path)?)))
+            .collect();
+
+        EnvSnapshot {
+            volt_version: VERSION.to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            script: script.to_string(),
+            directory: plan.directory.clone(),
+            shell: std::iter::once(plan.shell.0.clone())
+                .chain(plan.shell.1.iter().cloned())
+                .collect(),
+            env,
+            tools,
+            bins,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<EnvSnapshot> {
+        let data = std::fs::read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(
+            serde_json::from_str(&data).map_err(|e| VoltError::InvalidEnvSnapshot {
+                path: path.display().to_string(),
+                source: e,
+            })?,
+        )
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).unwrap();
+
+        std::fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Everything that differs from `other`, by section.
+    pub fn diff(&self, other: &EnvSnapshot) -> Vec<(&'static str, Vec<Change>)> {
+        let platform = |snapshot: &EnvSnapshot| {
+            BTreeMap::from([
+                (String::from("volt"), snapshot.volt_version.clone()),
+                (String::from("os"), snapshot.os.clone()),
+                (String::from("arch"), snapshot.arch.clone()),
+                (String::from("shell"), snapshot.shell.join(" ")),
+                (
+                    String::from("directory"),
+                    snapshot.directory.display().to_string(),
+                ),
+            ])
+        };
+
+        let bins = |snapshot: &EnvSnapshot| {
+            snapshot
+                .bins
+                .iter()
+                .map(|(name, path)| (name.clone(), path.display().to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let env = |snapshot: &EnvSnapshot| {
+            let mut env = snapshot.env.clone();
+
+            // PATH is compared entry by entry below
+            env.remove("PATH");
+            env.remove("Path");
+            env
+        };
+
+        vec![
+            ("platform", changes(&platform(self), &platform(other))),
+            ("tools", changes(&self.tools, &other.tools)),
+            ("bins", changes(&bins(self), &bins(other))),
+            ("path", path_changes(self.path(), other.path())),
+            ("env", changes(&env(self), &env(other))),
+        ]
+        .into_iter()
+        .filter(|(_, changes)| !changes.is_empty())
+        .collect()
+    }
+
+    fn path(&self) -> Vec<String> {
+        let path = self.env.get("PATH").or_else(|| self.env.get("Path"));
+
+        path.map(|path| {
+            std::env::split_paths(OsStr::new(path))
+                .map(|entry| entry.display().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+/// How one entry differs between two snapshots.
+#[derive(Debug, Clone)]
+pub enum Change {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+}
+
+impl Change {
+    pub fn print(&self) {
+        // PATH entries have no name of their own
+        let entry = |name: &str, value: &str| match name.is_empty() {
+            true => value.to_string(),
+            false => format!("{} {}", name, value),
+        };
+
+        match self {
+            Change::Added(name, value) => println!(
+                "  {} {}",
+                "+".bright_green().bold(),
+                entry(name, value).bright_green()
+            ),
+            Change::Removed(name, value) => println!(
+                "  {} {}",
+                "-".bright_red().bold(),
+                entry(name, value).bright_red()
+            ),
+            Change::Changed(name, before, after) => println!(
+                "  {} {} {} {} {}",
+                "~".bright_yellow().bold(),
+                name,
+                before.bright_red(),
+                "->".bright_black(),
+                after.bright_green()
+            ),
+        }
+    }
+}
+
+fn changes(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> Vec<Change> {
+    let names = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+
+    names
+        .into_iter()
+        .filter_map(|name| match (before.get(name), after.get(name)) {
+            (Some(a), Some(b)) if a != b => {
+                Some(Change::Changed(name.clone(), a.clone(), b.clone()))
+            }
+            (Some(a), None) => Some(Change::Removed(name.clone(), a.clone())),
+            (None, Some(b)) => Some(Change::Added(name.clone(), b.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// PATH entries added or removed, and the ones both have but in another order.
+fn path_changes(before: Vec<String>, after: Vec<String>) -> Vec<Change> {
+    let mut changes = vec![];
+
+    for entry in before.iter().filter(|entry| !after.contains(entry)) {
+        changes.push(Change::Removed(String::new(), entry.clone()));
+    }
+
+    for entry in after.iter().filter(|entry| !before.contains(entry)) {
+        changes.push(Change::Added(String::new(), entry.clone()));
+    }
+
+    let common = |path: &[String], other: &[String]| {
+        path.iter()
+            .filter(|entry| other.contains(entry))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    // a directory moving ahead of another changes which binary wins
+    if common(&before, &after) != common(&after, &before) {
+        changes.push(Change::Changed(
+            String::from("order"),
+            common(&before, &after).join(" "),
+            common(&after, &before).join(" "),
+        ));
+    }
+
+    changes
+}
+
+/// Whether the name of a variable or setting marks its value as a secret.
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_uppercase();
+
+    SECRETS.iter().any(|secret| name.contains(secret))
+}
+
+/// `value` of `name`, or a hash of it when it's a secret so equal secrets still compare equal.
+pub fn redact(name: &str, value: &str) -> String {
+    if is_secret(name) {
+        format!("<secret sha256:{}>", &hash(value)[..12])
+    } else {
+        value.to_string()
+    }
+}
+
+/// `--version` of the tools found on `path`.
+pub fn tool_versions(path: &OsStr) -> BTreeMap<String, String> {
+    TOOLS
+        .iter()
+        .filter_map(|tool| {
+            let program = which(tool, path)?;
+
+            Some((tool.to_string(), tool_version(&program, path)?))
+        })
+        .collect()
+}
+
+fn hash(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The program a shell command starts with, skipping `NAME=value` assignments in front of it.
+fn first_command(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|word| !word.contains('='))
+        .filter(|word| !word.starts_with(['(', '{', '!']))
+}
+
+/// Where `command` resolves to on `path`, trying the `PATHEXT` extensions on Windows.
+fn which(command: &str, path: &OsStr) -> Option<PathBuf> {
+    if command.contains('/') || command.contains('\\') {
+        return None;
+    }
+
+    let extensions = match std::env::var("PATHEXT") {
+        Ok(extensions) if cfg!(windows) => std::iter::once(String::new())
+            .chain(extensions.split(';').map(str::to_lowercase))
+            .collect(),
+        _ => vec![String::new()],
+    };
+
+    std::env::split_paths(path).find_map(|directory| {
+        extensions
+            .iter()
+            .map(|extension| directory.join(format!("{}{}", command, extension)))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// First line `<program> --version` prints.
+fn tool_version(program: &Path, path: &OsStr) -> Option<String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .env("PATH", path)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout);
+
+    version.lines().next().map(|line| line.trim().to_string())
+}
@@ -1,3 +1,17 @@
+//! Every error volt reports.
+//!
+//! Each error has a code like `VOLT0012`, printed with it so it can be searched for and told
+//! apart from the others in bug reports. Codes are stable: an error keeps its code when its
+//! message changes, a new error takes the next free code of its range and the code of a
+//! removed error isn't given out again.
+//!
+//! | Codes       | Errors                                             |
+//! |-------------|----------------------------------------------------|
+//! | `VOLT0001`… | `VoltError`                                        |
+//! | `VOLT1000`… | `GetPackageError`, answers of the registry         |
+//! | `VOLT1100`… | `LockFileError`, reading and writing volt.lock     |
+//! | `VOLT1200`… | `ConfigError`, loading volt.toml                   |
+
 use miette::Diagnostic;
 use thiserror::Error;
 
@@ -7,11 +21,11 @@ pub enum VoltError {
     // #[diagnostic(code(volt::environment::enable_ansi_support))]
     // EnableAnsiSupport(),
     #[error("failed to detect `{env}`")]
-    #[diagnostic(code(volt::environment::get))]
+    #[diagnostic(code(VOLT0001))]
     EnvironmentError { source: std::io::Error, env: String },
 
     #[error("failed to detect your home directory")]
-    #[diagnostic(code(volt::environment::home_dir))]
+    #[diagnostic(code(VOLT0002))]
     GetHomeDirError,
 
     // #[error("failed to initialize lz4 decoder")]
@@ -21,30 +35,36 @@ pub enum VoltError {
     // #[diagnostic(code(volt::decode::lz4::decode))]
     // DecodeError(#[source] std::io::Error),
     #[error("failed to recieve response from the registry")]
-    #[diagnostic(code(volt::network))]
+    #[diagnostic(
+        code(VOLT0003),
+        help("check your connection and run `volt ping` to see which registries can be reached.")
+    )]
     NetworkError(isahc::Error),
 
     // #[error("failed to recieve byte response")]
     // #[diagnostic(code(volt::network::rec))]
     // NetworkRecError(#[source] std::io::Error),
     #[error("failed to create directory")]
-    #[diagnostic(code(volt::io::create_dir))]
+    #[diagnostic(
+        code(VOLT0004),
+        help("check that you can write to the project directory and to ~/.volt.")
+    )]
     CreateDirError(#[source] std::io::Error),
 
     #[error("GET {url} - 404 - {package_name} was not found in the volt registry, or you don't have the permission to request it.")]
-    #[diagnostic(code(volt::registry::volt::package_not_found))]
+    #[diagnostic(code(VOLT0005))]
     PackageNotFound { url: String, package_name: String },
 
     #[error("GET {url} - 429 - Too many requests has been sent to {url} on the volt registry. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::too_many_requests))]
+    #[diagnostic(code(VOLT0006))]
     TooManyRequests { url: String, package_name: String },
 
     #[error("GET {url} - 400 - Bad request. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::bad_request))]
+    #[diagnostic(code(VOLT0007))]
     BadRequest { url: String, package_name: String },
 
     #[error("GET {url} - {} - An unknown error occured. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::unknown_error))]
+    #[diagnostic(code(VOLT0008))]
     NetworkUnknownError {
         url: String,
         package_name: String,
@@ -52,23 +72,35 @@ pub enum VoltError {
     },
 
     #[error("failed to parse {hash} integrity hash.")]
-    #[diagnostic(code(volt::integrity::parse))]
+    #[diagnostic(code(VOLT0009))]
     HashParseError { hash: String },
 
     #[error("failed to copy bytes to hasher.")]
-    #[diagnostic(code(volt::hasher::copy))]
+    #[diagnostic(code(VOLT0010))]
     HasherCopyError(#[source] std::io::Error),
 
-    #[error("failed to verify tarball checksum")]
-    #[diagnostic(code(volt::integrity::verify))]
-    ChecksumVerificationError,
+    #[error("the tarball of {name}@{version} from {origin} doesn't match volt.lock, expected {expected} but it hashes to {actual}")]
+    #[diagnostic(
+        code(VOLT0011),
+        help("nothing was extracted. A mismatching tarball in the offline mirror or zero-install cache is usually corrupted, delete it and install again. When a fresh download from the registry mismatches, the published tarball changed since it was locked, which can mean a compromised registry or mirror, so find out why before updating volt.lock.")
+    )]
+    IntegrityMismatch {
+        name: String,
+        version: String,
+        origin: String,
+        expected: String,
+        actual: String,
+    },
 
     #[error("failed to convert integrity into hex")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[diagnostic(code(VOLT0012))]
     IntegrityConversionError,
 
     #[error("failed to deserialize slice to `JSONVoltResponse`")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[diagnostic(
+        code(VOLT0013),
+        help("the CDN sent a response volt doesn't understand, try again or set `registry` in volt.toml to install from the registry.")
+    )]
     DeserializeError,
 
     // #[error("failed to build request client")]
@@ -87,18 +119,658 @@ pub enum VoltError {
     // #[diagnostic(code(volt::io::rec::text))]
     // VersionLookupError { name: String },
     #[error("failed to read `{name}`")]
-    #[diagnostic(code(volt::io::file::read))]
+    #[diagnostic(code(VOLT0014))]
     ReadFileError {
         source: std::io::Error,
         name: String,
     },
 
     #[error("failed to write to `{name}`")]
-    #[diagnostic(code(volt::io::file::write))]
+    #[diagnostic(code(VOLT0015))]
     WriteFileError {
         source: std::io::Error,
         name: String,
     },
+
+    #[error("failed to download tarball from {url}")]
+    #[diagnostic(code(VOLT0016))]
+    TarballDownloadError { url: String, source: std::io::Error },
+
+    #[error("failed to parse the query")]
+    #[diagnostic(
+        code(VOLT0017),
+        help("send one json object like `{{\"id\": 1, \"method\": \"latest\"}}` per line.")
+    )]
+    InvalidQuery(#[source] serde_json::Error),
+
+    #[error("unknown query method `{method}`")]
+    #[diagnostic(
+        code(VOLT0018),
+        help("the methods are `latest`, `satisfies` and `hover`.")
+    )]
+    UnknownQueryMethod { method: String },
+
+    #[error("the `{method}` query needs a `{param}` parameter")]
+    #[diagnostic(code(VOLT0019))]
+    MissingQueryParam { method: String, param: String },
+
+    #[error("failed to download tarball from {url}, the registry answered {code}")]
+    #[diagnostic(code(VOLT0020))]
+    TarballStatusError { url: String, code: String },
+
+    #[error("no response from {url} within {seconds}s")]
+    #[diagnostic(
+        code(VOLT0021),
+        help("raise `connect`, `tls-handshake` or `first-byte` under `[timeouts]` in volt.toml.")
+    )]
+    RequestTimedOut { url: String, seconds: u64 },
+
+    #[error("the install did not finish within {seconds}s")]
+    #[diagnostic(
+        code(VOLT0022),
+        help("raise `install` under `[timeouts]` in volt.toml.")
+    )]
+    InstallDeadlineExceeded { seconds: u64 },
+
+    #[error("invalid proxy `{proxy}`")]
+    #[diagnostic(
+        code(VOLT0023),
+        help("check `proxy` and `https-proxy` in volt.toml or .npmrc, and the `HTTP_PROXY` and `HTTPS_PROXY` environment variables.")
+    )]
+    InvalidProxy {
+        proxy: String,
+        source: isahc::http::uri::InvalidUri,
+    },
+
+    #[error("failed to set up the http client")]
+    #[diagnostic(code(VOLT0024))]
+    HttpClientError(#[source] isahc::Error),
+
+    #[error("failed to extract `{name}`")]
+    #[diagnostic(code(VOLT0025))]
+    ExtractError {
+        name: String,
+        source: std::io::Error,
+    },
+
+    #[error("`{range}` is not a valid version range for {package_name}")]
+    #[diagnostic(
+        code(VOLT0026),
+        help("ranges look like `^1.2.3`, `~1.2`, `>=2 <3`, `1.x` or `*`.")
+    )]
+    InvalidVersionRange { package_name: String, range: String },
+
+    #[error("invalid alias `{spec}`")]
+    #[diagnostic(
+        code(VOLT0027),
+        help("aliases look like `my-lodash@npm:lodash` or `my-lodash@npm:lodash@^4`.")
+    )]
+    InvalidAlias { spec: String },
+
+    #[error("no version of {package_name} satisfies `{range}`")]
+    #[diagnostic(
+        code(VOLT0028),
+        help("run `volt info <package> versions` to see the published versions.")
+    )]
+    NoMatchingVersion { package_name: String, range: String },
+
+    #[error("`{value}` is not a valid date")]
+    #[diagnostic(
+        code(VOLT0029),
+        help("use a date like `2021-06-01` or a timestamp like `2021-06-01T12:00:00Z`.")
+    )]
+    InvalidDate { value: String },
+
+    #[error("{package_name} has no dist-tag named `{tag}`")]
+    #[diagnostic(
+        code(VOLT0030),
+        help("run `volt info <package> dist-tags` to see the tags of a package.")
+    )]
+    UnknownDistTag { package_name: String, tag: String },
+
+    #[error("no version of {package_name} satisfies every peer dependency range: {requirements}")]
+    #[diagnostic(
+        code(VOLT0031),
+        help("add a version compatible with every package listed above to your dependencies, or upgrade the packages that depend on it.")
+    )]
+    UnsatisfiablePeerDependency {
+        package_name: String,
+        requirements: String,
+    },
+
+    #[error("{method} {url} - {code} - {message}")]
+    #[diagnostic(code(VOLT0032))]
+    RegistryError {
+        method: String,
+        url: String,
+        code: String,
+        message: String,
+    },
+
+    #[error("refusing to unpublish without `--force`")]
+    #[diagnostic(
+        code(VOLT0033),
+        help("unpublishing breaks every project that depends on the package, consider deprecating it instead.")
+    )]
+    UnpublishWithoutForce,
+
+    #[error("refusing to unpublish {package_name}, it is depended on by {dependents}")]
+    #[diagnostic(
+        code(VOLT0034),
+        help("remove the dependency from these packages (see volt.lock) before unpublishing.")
+    )]
+    UnpublishDependents {
+        package_name: String,
+        dependents: String,
+    },
+
+    #[error("the registry refused to unpublish {package_name}: {message}")]
+    #[diagnostic(
+        code(VOLT0035),
+        help("registries usually only allow unpublishing shortly after publishing, and only when no other published package depends on it.")
+    )]
+    UnpublishRejected {
+        package_name: String,
+        message: String,
+    },
+
+    #[error("failed to build request to the registry")]
+    #[diagnostic(code(VOLT0036))]
+    RequestBuilderError(#[source] isahc::http::Error),
+
+    #[error("PUT {url} - {package_name}@{version} has already been published.")]
+    #[diagnostic(
+        code(VOLT0037),
+        help("registries never allow a version to be published twice, bump the `version` field in package.json and try again.")
+    )]
+    PublishConflict {
+        url: String,
+        package_name: String,
+        version: String,
+    },
+
+    #[error("PUT {url} - {code} - {message}")]
+    #[diagnostic(code(VOLT0038))]
+    PublishError {
+        url: String,
+        code: String,
+        message: String,
+    },
+
+    #[error("no authentication token found for {registry}")]
+    #[diagnostic(
+        code(VOLT0039),
+        help("add an `_authToken` for the registry to `.npmrc`, set the `NPM_TOKEN` environment variable, or configure an auth helper for it in `volt.toml`.")
+    )]
+    MissingAuthToken { registry: String },
+
+    #[error("{registry} is not a CodeArtifact repository url")]
+    #[diagnostic(
+        code(VOLT0040),
+        help("CodeArtifact urls look like `https://<domain>-<owner>.d.codeartifact.<region>.amazonaws.com/npm/<repository>/`.")
+    )]
+    InvalidCodeArtifactUrl { registry: String },
+
+    #[error("the auth helper for {registry} exited with {code}: {stderr}")]
+    #[diagnostic(
+        code(VOLT0041),
+        help("check the command under `[auth-helpers]` in `volt.toml`.")
+    )]
+    AuthHelperFailed {
+        registry: String,
+        code: String,
+        stderr: String,
+    },
+
+    #[error("`{command}` failed: {message}")]
+    #[diagnostic(
+        code(VOLT0042),
+        help("git dependencies need `git` on PATH and access to the repository.")
+    )]
+    GitError { command: String, message: String },
+
+    #[error("{url} has no branch, tag or commit named `{reference}`")]
+    #[diagnostic(code(VOLT0043))]
+    GitRefNotFound { url: String, reference: String },
+
+    #[error("node_modules doesn't match the lockfile, {problems} packages have problems")]
+    #[diagnostic(
+        code(VOLT0044),
+        help("reinstall the affected packages with `volt add` to restore them.")
+    )]
+    CheckFailed { problems: usize },
+
+    #[error("missing script: `{name}`")]
+    #[diagnostic(
+        code(VOLT0045),
+        help("run `volt run` without arguments to list the scripts in package.json.")
+    )]
+    MissingScript { name: String },
+
+    #[error("failed to start `{shell}`")]
+    #[diagnostic(code(VOLT0046))]
+    ScriptSpawnError {
+        shell: String,
+        source: std::io::Error,
+    },
+
+    #[error("script `{event}` exited with {code}")]
+    #[diagnostic(code(VOLT0047))]
+    ScriptFailed { event: String, code: String },
+
+    #[error("`{event}` script of `{name}` exited with {code}:{output}")]
+    #[diagnostic(
+        code(VOLT0048),
+        help("the package may need build tools like python and a C++ compiler, see its README.")
+    )]
+    InstallScriptFailed {
+        name: String,
+        event: String,
+        code: String,
+        output: String,
+    },
+
+    #[error("bundled dependency `{name}` is not installed")]
+    #[diagnostic(
+        code(VOLT0049),
+        help("install your dependencies with `volt add` before packing.")
+    )]
+    MissingBundledDependency { name: String },
+
+    #[error("invalid filter `{filter}`")]
+    #[diagnostic(
+        code(VOLT0050),
+        help("filters look like `app`, `@scope/*`, `./packages/app`, `[origin/main]`, `app...` or `...^lib`.")
+    )]
+    InvalidFilter { filter: String },
+
+    #[error("no workspace packages match the filters")]
+    #[diagnostic(
+        code(VOLT0051),
+        help("filters match package names, paths relative to the current directory or `[<git ref>]`.")
+    )]
+    NoFilterMatch,
+
+    #[error("`--filter` only works inside a workspace")]
+    #[diagnostic(
+        code(VOLT0052),
+        help("declare the workspace packages in the `workspaces` field of the root package.json.")
+    )]
+    NotInWorkspace,
+
+    #[error("{count} packages have invalid registry signatures")]
+    #[diagnostic(
+        code(VOLT0053),
+        help("the packages may have been tampered with, reinstall them from the registry and audit again.")
+    )]
+    InvalidSignatures { count: usize },
+
+    #[error("{count} dependencies break the policy in volt.toml")]
+    #[diagnostic(
+        code(VOLT0054),
+        help("update them, or exempt them under `[[policy.exemptions]]` with a reason and an expiry date.")
+    )]
+    PolicyViolations { count: usize },
+
+    #[error(
+        "the policy exemption of {package} expires on `{date}`, which isn't a `YYYY-MM-DD` date"
+    )]
+    #[diagnostic(code(VOLT0055))]
+    InvalidExemptionDate { package: String, date: String },
+
+    #[error("invalid override `{key}` in package.json")]
+    #[diagnostic(
+        code(VOLT0056),
+        help("overrides map package names to version ranges, `$name` refers to a dependency of the root package.")
+    )]
+    InvalidOverride { key: String },
+
+    #[error("`{address}` is not an address to listen on")]
+    #[diagnostic(
+        code(VOLT0057),
+        help("pass an IP address to `--host` and a number to `--port`.")
+    )]
+    InvalidAddress { address: String },
+
+    #[error("the registry mirror failed on {address}")]
+    #[diagnostic(
+        code(VOLT0058),
+        help("check that nothing else is listening on the same port.")
+    )]
+    RegistryServerError {
+        address: String,
+        source: hyper::Error,
+    },
+
+    #[error("{name} has {count} conflicts with the current project")]
+    #[diagnostic(
+        code(VOLT0059),
+        help("install versions that satisfy the ranges above, or pick another version of the package.")
+    )]
+    IncompatiblePackage { name: String, count: usize },
+
+    #[error("no workspace package matches {name}@{spec}")]
+    #[diagnostic(
+        code(VOLT0060),
+        help("`workspace:` ranges have to be satisfied by a package of the same workspace.")
+    )]
+    WorkspaceDependencyNotFound { name: String, spec: String },
+
+    #[error("workspace packages depend on each other in a cycle: {names}")]
+    #[diagnostic(
+        code(VOLT0061),
+        help("break the cycle or run the script without `--recursive`.")
+    )]
+    WorkspaceCycle { names: String },
+
+    #[error("declined to install {count} packages that introduce install scripts")]
+    #[diagnostic(code(VOLT0062), help("pass `--yes` to install them without asking."))]
+    InstallScriptsDeclined { count: usize },
+
+    #[error("`{hook}` hook for `{name}` exited with {code}")]
+    #[diagnostic(code(VOLT0063), help("check the hooks configured in `volt.toml`."))]
+    LinkHookFailed {
+        hook: String,
+        name: String,
+        code: String,
+    },
+
+    #[error("volt.lock is out of date, {count} changes are needed:{diff}")]
+    #[diagnostic(
+        code(VOLT0064),
+        help("run `volt install` without `--frozen-lockfile` and commit the updated volt.lock.")
+    )]
+    LockfileOutdated { count: usize, diff: String },
+
+    #[error(
+        "{name} can't be extracted faithfully on this machine, {count} of its files don't fit:{problems}"
+    )]
+    #[diagnostic(
+        code(VOLT0065),
+        help("report it to the package's maintainers. Paths that are too long fit when the project is closer to the root of the drive, or with long paths enabled in Windows.")
+    )]
+    UnportablePackage {
+        name: String,
+        count: usize,
+        problems: String,
+    },
+
+    #[error("linking would overwrite {count} paths volt didn't create:{paths}")]
+    #[diagnostic(
+        code(VOLT0066),
+        help("move them out of the way, or pass `--force-overwrite` to replace them.")
+    )]
+    WouldOverwrite { count: usize, paths: String },
+
+    #[error("{} packages are not cached for an offline install:\n  {}", .packages.len(), .packages.join("\n  "))]
+    #[diagnostic(
+        code(VOLT0067),
+        help("install once online, or with `--prefer-offline`, to cache them.")
+    )]
+    NotCached { packages: Vec<String> },
+
+    #[error("failed to write the dependency graph to {path}: {message}")]
+    #[diagnostic(code(VOLT0068))]
+    SqliteExportFailed { path: String, message: String },
+
+    #[error("`{path}` is not an environment snapshot")]
+    #[diagnostic(
+        code(VOLT0069),
+        help("snapshots are written by `volt run <script> --env-snapshot <file>`.")
+    )]
+    InvalidEnvSnapshot {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("volt.lock can't be trusted: {reason}")]
+    #[diagnostic(
+        code(VOLT0070),
+        help("check how volt.lock changed since it was last signed, then sign it again with `volt sign`.")
+    )]
+    LockfileSignature { reason: String },
+
+    #[error("{origin} isn't a usable signing key: {reason}")]
+    #[diagnostic(code(VOLT0071), help("create a key with `volt sign keygen`."))]
+    InvalidSigningKey { origin: String, reason: String },
+
+    #[error("failed to sign: {reason}")]
+    #[diagnostic(code(VOLT0072))]
+    SigningFailed { reason: String },
+
+    #[error("{path} has no package.json, it isn't the root of a project")]
+    #[diagnostic(
+        code(VOLT0073),
+        help("run `volt clean` from the directory holding package.json.")
+    )]
+    NotProjectRoot { path: String },
+
+    #[error("{failed} endpoints can't be used")]
+    #[diagnostic(
+        code(VOLT0074),
+        help("check the urls and tokens in volt.toml and `.npmrc`, and any proxy between volt and them.")
+    )]
+    PingFailed { failed: usize },
+
+    #[error("{name}@{version} only installs on {wanted}, this machine is {current}")]
+    #[diagnostic(
+        code(VOLT0075),
+        help("make it an optional dependency if the project works without it on this platform.")
+    )]
+    UnsupportedPlatform {
+        name: String,
+        version: String,
+        wanted: String,
+        current: String,
+    },
+    #[error(
+        "{feature} is experimental, turn on the `{experiment}` experiment or set {env}=1 to use it"
+    )]
+    #[diagnostic(
+        code(VOLT0076),
+        help("experiments may change in any release, turn one on for the project with `volt config set experiments.<name> true`.")
+    )]
+    ExperimentDisabled {
+        feature: String,
+        experiment: String,
+        env: String,
+    },
+
+    #[error("`{path}` isn't valid package.json")]
+    #[diagnostic(
+        code(VOLT0077),
+        help(
+            "package.json has to be a JSON object, check it for trailing commas and unquoted keys."
+        )
+    )]
+    InvalidPackageJson {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("failed to read your answer")]
+    #[diagnostic(
+        code(VOLT0078),
+        help("pass `--yes` to use the defaults when volt can't ask, like in CI.")
+    )]
+    PromptError(#[source] std::io::Error),
+
+    #[error("node {current} doesn't satisfy the `engines` of {count} packages:\n  {packages}")]
+    #[diagnostic(
+        code(VOLT0079),
+        help("switch to a node version they support, or unset `engine-strict` to only warn about them.")
+    )]
+    UnsupportedEngine {
+        current: String,
+        count: usize,
+        packages: String,
+    },
+
+    #[error("the project needs {engine} {range}, this is {engine} {current}")]
+    #[diagnostic(
+        code(VOLT0080),
+        help("switch to a version the `engines` of package.json allow, or unset `engine-strict` to only warn.")
+    )]
+    UnsupportedProjectEngine {
+        engine: String,
+        range: String,
+        current: String,
+    },
+
+    #[error("no release of node matches `{spec}`")]
+    #[diagnostic(
+        code(VOLT0081),
+        help("pin a version like `18` or `18.12.0`, `lts` or `latest`. `volt node list --remote` lists the releases.")
+    )]
+    UnknownNodeVersion { spec: String },
+
+    #[error("failed to download node from {url}, the server answered {code}")]
+    #[diagnostic(
+        code(VOLT0082),
+        help(
+            "node may not publish a build for this platform, check the release on nodejs.org/dist."
+        )
+    )]
+    NodeDownloadFailed { url: String, code: String },
+
+    #[error("SHASUMS256.txt doesn't list {file}")]
+    #[diagnostic(
+        code(VOLT0083),
+        help("node doesn't publish this build for the platform, nothing was installed.")
+    )]
+    NodeChecksumMissing { file: String },
+
+    #[error("{file} doesn't match SHASUMS256.txt, expected {expected} but it hashes to {actual}")]
+    #[diagnostic(
+        code(VOLT0084),
+        help("nothing was installed. Try again, a mismatch that persists means something between volt and nodejs.org alters downloads.")
+    )]
+    NodeChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("no version of node given and none is pinned")]
+    #[diagnostic(
+        code(VOLT0085),
+        help("pass a version, or pin one with `volt node use <version>`.")
+    )]
+    NoNodeVersion,
+
+    #[error("{package} has no `{field}`")]
+    #[diagnostic(
+        code(VOLT0086),
+        help("fields are read from the version shown, then the package, e.g. `dist.tarball`, `versions` or `dependencies.<name>`.")
+    )]
+    MissingInfoField { package: String, field: String },
+
+    #[error("{registry} has no user named {user}")]
+    #[diagnostic(
+        code(VOLT0087),
+        help("owners are added by their registry username, not their email.")
+    )]
+    UnknownRegistryUser { user: String, registry: String },
+
+    #[error("{user} is not an owner of {package}")]
+    #[diagnostic(code(VOLT0088), help("`volt owner ls {}` lists its owners.", package))]
+    NotAnOwner { user: String, package: String },
+
+    #[error("refusing to remove {user}, the last owner of {package}")]
+    #[diagnostic(
+        code(VOLT0089),
+        help("add another owner first, a package without owners can't be published or managed.")
+    )]
+    LastOwner { user: String, package: String },
+
+    #[error("no package given and there is no package.json here to take its name from")]
+    #[diagnostic(code(VOLT0090), help("pass the name of the package."))]
+    NoPackageName,
+
+    #[error("`volt owner {command}` needs the username of the owner")]
+    #[diagnostic(
+        code(VOLT0091),
+        help("e.g. `volt owner {} <user> [package]`.", command)
+    )]
+    MissingOwner { command: String },
+
+    #[error("`{tag}` can't be a dist-tag, it parses as a version range")]
+    #[diagnostic(
+        code(VOLT0092),
+        help("tags are names like `next` or `beta`, a range would make `volt add <package>@{}` ambiguous.", tag)
+    )]
+    InvalidDistTag { tag: String },
+
+    #[error("refusing to remove the `latest` dist-tag of {package}")]
+    #[diagnostic(
+        code(VOLT0093),
+        help(
+            "move it instead, e.g. `volt dist-tag add {}@<version> latest`.",
+            package
+        )
+    )]
+    RemoveLatestTag { package: String },
+
+    #[error("{package} has no `{tag}` dist-tag")]
+    #[diagnostic(code(VOLT0094), help("`volt dist-tag ls {}` lists its tags.", package))]
+    NoSuchDistTag { tag: String, package: String },
+
+    #[error("`volt dist-tag {command}` needs {needs}")]
+    #[diagnostic(
+        code(VOLT0095),
+        help("e.g. `volt dist-tag add <package>@<version> [tag]` or `volt dist-tag rm <package> <tag>`.")
+    )]
+    MissingDistTagArgument { command: String, needs: String },
+
+    #[error("{count} installed packages use a denied license")]
+    #[diagnostic(
+        code(VOLT0096),
+        help("replace them, or drop their license from `--deny` once it's been cleared.")
+    )]
+    DeniedLicenses { count: usize },
+
+    #[error("there's no volt.lock to generate a bill of materials from")]
+    #[diagnostic(code(VOLT0097), help("run `volt install` first."))]
+    MissingLockfile,
+
+    #[error("{count} advisories of at least {level} severity affect the locked packages")]
+    #[diagnostic(
+        code(VOLT0098),
+        help("update the affected packages, or ignore an advisory under `[[audit.ignore]]` in volt.toml with a reason and an expiry date.")
+    )]
+    AdvisoriesFound { count: usize, level: String },
+
+    #[error("the ignored advisory {id} expires on `{date}`, which isn't a `YYYY-MM-DD` date")]
+    #[diagnostic(code(VOLT0099))]
+    InvalidIgnoreDate { id: String, date: String },
+
+    #[error(
+        "the built-in auth helper for {registry} needs the `{program}` CLI, which isn't on PATH"
+    )]
+    #[diagnostic(
+        code(VOLT0100),
+        help("install it and sign in with it, or configure an auth helper command for the registry under `[auth-helpers]` in `volt.toml`.")
+    )]
+    MissingCloudCli { registry: String, program: String },
+
+    #[error("unable to lock the shared virtual store")]
+    #[diagnostic(
+        code(VOLT0101),
+        help("installs using the store and `volt store prune` wait for each other, try again once the other one is done.")
+    )]
+    StoreLocked(#[source] std::io::Error),
+
+    #[error("{name} at commit {commit} doesn't match volt.lock, expected {expected} but it packs to {actual}")]
+    #[diagnostic(
+        code(VOLT0102),
+        help("nothing was installed. The commit is pinned, so its files only change when the repository's history was rewritten or volt.lock was edited, find out why before adding the dependency again.")
+    )]
+    GitIntegrityMismatch {
+        name: String,
+        commit: String,
+        expected: String,
+        actual: String,
+    },
     // #[error("an unknown error occured.")]
     // #[diagnostic(code(volt::unknown))]
     // UnknownError,
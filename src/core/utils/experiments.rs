@@ -0,0 +1,186 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Features still taking shape, off until turned on.
+//!
+//! An experiment is turned on under `[experiments]` in volt.toml, with
+//! `volt config set experiments.isolated-linker true`, or for a single run with the
+//! environment variable named after it, which beats volt.toml either way:
+//!
+//! ```sh
+//! VOLT_EXPERIMENT_ISOLATED_LINKER=1 volt install
+//! ```
+//!
+//! Using a feature whose experiment is off fails and says how to turn it on. The first time
+//! an experiment is used on a machine a notice says it may still change, the experiments
+//! noticed are kept in `<volt dir>/experiments.json`.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::core::model::config::{NodeLinker, VoltConfig};
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+
+use colored::Colorize;
+use miette::Result;
+use tracing::info;
+
+/// File in the volt directory listing the experiments a notice was printed for.
+const NOTICES_FILE: &str = "experiments.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Experiment {
+    /// `node-linker = "isolated"`, see `core::utils::virtual_store`.
+    IsolatedLinker,
+    /// `node-linker = "pnp"`, see `core::utils::pnp`.
+    PnpLinker,
+    /// `shared-virtual-store = true`.
+    SharedVirtualStore,
+}
+
+/// Every experiment, in the order they're listed.
+pub const EXPERIMENTS: &[Experiment] = &[
+    Experiment::IsolatedLinker,
+    Experiment::PnpLinker,
+    Experiment::SharedVirtualStore,
+];
+
+impl Experiment {
+    /// Key of the experiment under `[experiments]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Experiment::IsolatedLinker => "isolated-linker",
+            Experiment::PnpLinker => "pnp-linker",
+            Experiment::SharedVirtualStore => "shared-virtual-store",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Experiment> {
+        EXPERIMENTS
+            .iter()
+            .copied()
+            .find(|experiment| experiment.name() == name)
+    }
+
+    /// `VOLT_EXPERIMENT_ISOLATED_LINKER` for `isolated-linker`.
+    pub fn env_var(self) -> String {
+        format!(
+            "VOLT_EXPERIMENT_{}",
+            self.name().to_uppercase().replace('-', "_")
+        )
+    }
+
+    /// Whether the environment variable turns the experiment on or off, `None` when it's unset
+    /// or neither.
+    fn env_override(self) -> Option<bool> {
+        match std::env::var(self.env_var()).ok()?.to_lowercase().as_str() {
+            "1" | "true" | "on" => Some(true),
+            "0" | "false" | "off" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Whether the experiment is on for this run.
+    pub fn enabled(self, config: &VoltConfig) -> bool {
+        self.env_override().unwrap_or_else(|| {
+            config
+                .experiments
+                .get(self.name())
+                .copied()
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// The experiments that are on, with where they're turned on.
+pub fn active(config: &VoltConfig) -> Vec<(Experiment, String)> {
+    EXPERIMENTS
+        .iter()
+        .filter(|experiment| experiment.enabled(config))
+        .map(|&experiment| {
+            let origin = match experiment.env_override() {
+                Some(_) => experiment.env_var(),
+                None => String::from("volt.toml"),
+            };
+
+            (experiment, origin)
+        })
+        .collect()
+}
+
+/// Fail unless `experiment` is on, `feature` says what needed it.
+pub fn require(app: &App, experiment: Experiment, feature: &str) -> Result<()> {
+    if !experiment.enabled(&app.config) {
+        return Err(VoltError::ExperimentDisabled {
+            feature: feature.to_string(),
+            experiment: experiment.name().to_string(),
+            env: experiment.env_var(),
+        }
+        .into());
+    }
+
+    notice(&app.volt_dir, experiment);
+
+    Ok(())
+}
+
+/// Check the experiments behind what volt.toml turns on, before installing.
+pub fn check_config(app: &App) -> Result<()> {
+    match app.config.node_linker {
+        NodeLinker::Isolated => require(
+            app,
+            Experiment::IsolatedLinker,
+            "node-linker = \"isolated\"",
+        )?,
+        NodeLinker::Pnp => require(app, Experiment::PnpLinker, "node-linker = \"pnp\"")?,
+        NodeLinker::Hoisted => {}
+    }
+
+    if app.config.shared_virtual_store {
+        require(
+            app,
+            Experiment::SharedVirtualStore,
+            "shared-virtual-store = true",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Tell that `experiment` may change, the first time it's used on this machine.
+fn notice(volt_dir: &Path, experiment: Experiment) {
+    let path = volt_dir.join(NOTICES_FILE);
+
+    let mut noticed: BTreeSet<String> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+
+    if !noticed.insert(experiment.name().to_string()) {
+        return;
+    }
+
+    info!(
+        "{} is an experiment, it may change or go away in any release",
+        experiment.name().bright_cyan()
+    );
+
+    // failing to record it only prints the notice again
+    if let Ok(data) = serde_json::to_string_pretty(&noticed) {
+        std::fs::write(&path, data).ok();
+    }
+}
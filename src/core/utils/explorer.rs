@@ -0,0 +1,672 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Terminal explorer of the installed dependency tree, behind `volt ls --interactive`.
+//!
+//! The tree comes from the lockfile and shows up right away. The size and license of every
+//! package are read from node_modules on a background thread and filled in as they arrive,
+//! the direct dependencies first, so large trees are usable before everything is measured.
+//!
+//! Marking a direct dependency for removal only takes effect once the explorer is closed.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use console::{Key, Term};
+use miette::Result;
+use walkdir::WalkDir;
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::app::App;
+use crate::core::utils::dedup::format_bytes;
+use crate::core::utils::installed_version;
+use crate::core::utils::package::PackageJson;
+
+/// Lines kept for the header and the status line.
+const CHROME: usize = 4;
+
+/// How often the background thread redraws while measuring packages outside the view.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Dependency tree of the project, by installed name.
+struct Tree {
+    /// Direct dependencies with the section of package.json they are in.
+    roots: Vec<(String, &'static str)>,
+    children: HashMap<String, Vec<String>>,
+    versions: HashMap<String, String>,
+}
+
+impl Tree {
+    fn new(app: &App, manifest: &PackageJson, lock_file: &LockFile) -> Tree {
+        let mut roots = vec![];
+
+        for (section, dependencies) in [
+            ("dependencies", &manifest.dependencies),
+            ("devDependencies", &manifest.dev_dependencies),
+            ("optionalDependencies", &manifest.optional_dependencies),
+        ] {
+            let mut names = dependencies.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+
+            roots.extend(names.into_iter().map(|name| (name, section)));
+        }
+
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        let mut versions = HashMap::new();
+
+        for lock in lock_file.dependencies.values() {
+            let name = lock.install_name();
+
+            // with several versions locked, the installed one is the one worth exploring
+            if versions.contains_key(name)
+                && installed_version(app, name).as_ref() != Some(&lock.version)
+            {
+                continue;
+            }
+
+            let mut dependencies = lock.dependencies.clone();
+            dependencies.sort();
+            dependencies.dedup();
+
+            children.insert(name.to_string(), dependencies);
+            versions.insert(name.to_string(), lock.version.clone());
+        }
+
+        Tree {
+            roots,
+            children,
+            versions,
+        }
+    }
+
+    fn children(&self, name: &str) -> &[String] {
+        self.children.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn is_direct(&self, name: &str) -> bool {
+        self.roots.iter().any(|(root, _)| root == name)
+    }
+
+    /// Every name in the tree, closest to the direct dependencies first.
+    fn breadth_first(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut queue = self
+            .roots
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<VecDeque<_>>();
+        let mut order = vec![];
+
+        while let Some(name) = queue.pop_front() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            queue.extend(self.children(&name).iter().cloned());
+            order.push(name);
+        }
+
+        order
+    }
+
+    /// Shortest chain from a direct dependency down to `name`, both included.
+    fn chain(&self, name: &str) -> Option<Vec<String>> {
+        let mut via: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for (root, _) in self.roots.iter() {
+            if via.insert(root, "").is_none() {
+                queue.push_back(root.as_str());
+            }
+        }
+
+        while let Some(current) = queue.pop_front() {
+            if current == name {
+                let mut chain = vec![current.to_string()];
+
+                while let Some(previous) = via.get(chain.last().unwrap().as_str()) {
+                    if previous.is_empty() {
+                        break;
+                    }
+
+                    chain.push(previous.to_string());
+                }
+
+                chain.reverse();
+                return Some(chain);
+            }
+
+            for child in self.children(current) {
+                if !via.contains_key(child.as_str()) {
+                    via.insert(child, current);
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// What node_modules tells about a package, read in the background.
+#[derive(Debug, Clone, Default)]
+struct Details {
+    size: u64,
+    license: String,
+    homepage: Option<String>,
+}
+
+impl Details {
+    fn read(directory: &Path) -> Details {
+        let size = WalkDir::new(directory)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        let manifest = std::fs::read_to_string(directory.join("package.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+            .unwrap_or_default();
+
+        // old packages have `{ "type": "MIT" }` or a list of those
+        let license = match &manifest["license"] {
+            serde_json::Value::String(license) => license.clone(),
+            serde_json::Value::Object(license) => license
+                .get("type")
+                .and_then(|license| license.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            _ => manifest["licenses"]
+                .as_array()
+                .map(|licenses| {
+                    licenses
+                        .iter()
+                        .filter_map(|license| license["type"].as_str())
+                        .collect::<Vec<_>>()
+                        .join(" OR ")
+                })
+                .unwrap_or_default(),
+        };
+
+        Details {
+            size,
+            license,
+            homepage: manifest["homepage"].as_str().map(String::from),
+        }
+    }
+}
+
+/// One line of the tree, the chain of names from a direct dependency down to it.
+struct Row {
+    path: Vec<String>,
+    /// The package already appears higher up in its own chain.
+    cycle: bool,
+}
+
+impl Row {
+    fn name(&self) -> &str {
+        self.path.last().unwrap()
+    }
+
+    fn key(&self) -> String {
+        self.path.join(">")
+    }
+}
+
+struct Explorer {
+    tree: Tree,
+    node_modules: PathBuf,
+    details: HashMap<String, Details>,
+    /// Keys of the expanded rows.
+    expanded: HashSet<String>,
+    cursor: usize,
+    offset: usize,
+    marked: BTreeSet<String>,
+    /// Search being typed, `None` outside of search.
+    query: Option<String>,
+    matches: Vec<String>,
+    next_match: usize,
+    status: String,
+    closed: bool,
+}
+
+impl Explorer {
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        let mut pending = self
+            .tree
+            .roots
+            .iter()
+            .rev()
+            .map(|(name, _)| vec![name.clone()])
+            .collect::<Vec<_>>();
+
+        while let Some(path) = pending.pop() {
+            let name = path.last().unwrap();
+            let cycle = path[..path.len() - 1].contains(name);
+
+            let row = Row { path, cycle };
+
+            if !cycle && self.expanded.contains(&row.key()) {
+                for child in self.tree.children(row.name()).iter().rev() {
+                    let mut path = row.path.clone();
+                    path.push(child.clone());
+                    pending.push(path);
+                }
+            }
+
+            rows.push(row);
+        }
+
+        rows
+    }
+
+    fn render(&mut self, term: &Term) {
+        let rows = self.rows();
+        let (height, width) = term.size();
+        let (height, width) = (height as usize, width as usize);
+        let visible = height.saturating_sub(CHROME).max(1);
+
+        self.cursor = self.cursor.min(rows.len().saturating_sub(1));
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + visible {
+            self.offset = self.cursor + 1 - visible;
+        }
+
+        let mut lines = vec![
+            format!(
+                "{} {} packages, {} measured",
+                "volt ls".bright_green().bold(),
+                self.tree.versions.len(),
+                self.details.len()
+            ),
+            format!(
+                "{}",
+                "↑↓ move  →← expand/collapse  / search  n next  o homepage  w why  d remove  q quit"
+                    .bright_black()
+            ),
+        ];
+
+        // the name column is as wide as the deepest visible row needs, within the terminal
+        let name_width = rows
+            .iter()
+            .skip(self.offset)
+            .take(visible)
+            .map(|row| row.path.len() * 2 + row.name().len() + self.version(row.name()).len() + 4)
+            .max()
+            .unwrap_or(0)
+            .min(width.saturating_sub(24));
+
+        for (index, row) in rows.iter().enumerate().skip(self.offset).take(visible) {
+            lines.push(self.render_row(row, index == self.cursor, name_width));
+        }
+
+        for _ in lines.len()..height.saturating_sub(1) {
+            lines.push(String::new());
+        }
+
+        lines.push(match &self.query {
+            Some(query) => format!("/{}", query),
+            None => self.status.clone(),
+        });
+
+        term.clear_screen().ok();
+        term.write_str(&lines.join("\n")).ok();
+    }
+
+    fn render_row(&self, row: &Row, selected: bool, name_width: usize) -> String {
+        let name = row.name();
+
+        let marker = if row.cycle {
+            "↻"
+        } else if self.tree.children(name).is_empty() {
+            " "
+        } else if self.expanded.contains(&row.key()) {
+            "▾"
+        } else {
+            "▸"
+        };
+
+        let label = format!(
+            "{}{} {}@{}",
+            "  ".repeat(row.path.len() - 1),
+            marker,
+            name,
+            self.version(name)
+        );
+
+        let label = format!("{:width$}", label, width = name_width)
+            .chars()
+            .take(name_width)
+            .collect::<String>();
+
+        let (size, license) = match self.details.get(name) {
+            Some(details) => (format_bytes(details.size), details.license.clone()),
+            None => (String::from("…"), String::from("…")),
+        };
+
+        let mut label = if self.marked.contains(name) {
+            label.bright_red().strikethrough()
+        } else if self.matches.iter().any(|found| found == name) {
+            label.bright_yellow()
+        } else if row.path.len() == 1 {
+            label.bright_cyan()
+        } else {
+            label.normal()
+        };
+
+        if selected {
+            label = label.reversed();
+        }
+
+        format!("{} {:>10}  {}", label, size, license.bright_black())
+    }
+
+    fn version(&self, name: &str) -> &str {
+        self.tree
+            .versions
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or("?")
+    }
+
+    /// Expand the rows leading to `name` and move the cursor onto it.
+    fn reveal(&mut self, name: &str) -> bool {
+        let chain = match self.tree.chain(name) {
+            Some(chain) => chain,
+            None => return false,
+        };
+
+        for length in 1..chain.len() {
+            self.expanded.insert(chain[..length].join(">"));
+        }
+
+        let key = chain.join(">");
+
+        if let Some(index) = self.rows().iter().position(|row| row.key() == key) {
+            self.cursor = index;
+        }
+
+        true
+    }
+
+    fn search(&mut self, query: &str) {
+        self.matches = self
+            .tree
+            .breadth_first()
+            .into_iter()
+            .filter(|name| name.contains(query))
+            .collect();
+        self.next_match = 0;
+
+        match self.matches.len() {
+            0 => self.status = format!("nothing matches `{}`", query),
+            count => {
+                self.status = format!("{} packages match `{}`", count, query);
+                self.jump_to_match();
+            }
+        }
+    }
+
+    fn jump_to_match(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        let name = self.matches[self.next_match % self.matches.len()].clone();
+        self.next_match += 1;
+
+        self.reveal(&name);
+    }
+
+    fn why(&mut self, name: &str) {
+        let chain = self.tree.chain(name);
+
+        let section = chain
+            .as_ref()
+            .and_then(|chain| {
+                self.tree
+                    .roots
+                    .iter()
+                    .find(|(root, _)| chain.first() == Some(root))
+            })
+            .map(|(_, section)| *section)
+            .unwrap_or("dependencies");
+
+        self.status = match chain {
+            Some(chain) if chain.len() == 1 => format!("{} is in {}", name, section),
+            Some(chain) => format!("{} ({})", chain.join(" > "), section),
+            None => format!("nothing depends on {}", name),
+        };
+    }
+
+    fn toggle_removal(&mut self, name: &str) {
+        if !self.tree.is_direct(name) {
+            let root = self
+                .tree
+                .chain(name)
+                .and_then(|chain| chain.first().cloned())
+                .unwrap_or_default();
+
+            self.status = format!("{} isn't a direct dependency, it comes with {}", name, root);
+
+            return;
+        }
+
+        if self.marked.remove(name) {
+            self.status = format!("{} will be kept", name);
+        } else {
+            self.marked.insert(name.to_string());
+            self.status = format!("{} will be removed on exit", name);
+        }
+    }
+
+    fn open_homepage(&mut self, name: &str) {
+        let url = self
+            .details
+            .get(name)
+            .and_then(|details| details.homepage.clone())
+            .unwrap_or_else(|| format!("https://www.npmjs.com/package/{}", name));
+
+        self.status = match open_url(&url) {
+            Ok(_) => format!("opened {}", url),
+            Err(_) => format!("couldn't open a browser, the homepage is {}", url),
+        };
+    }
+
+    /// Act on `key`, returns whether the explorer should close.
+    fn handle(&mut self, key: Key) -> bool {
+        if let Some(query) = self.query.as_mut() {
+            match key {
+                Key::Char(c) if !c.is_control() => query.push(c),
+                Key::Backspace => {
+                    query.pop();
+                }
+                Key::Enter => {
+                    let query = self.query.take().unwrap();
+
+                    if !query.is_empty() {
+                        self.search(&query);
+                    }
+                }
+                Key::Escape => self.query = None,
+                _ => {}
+            }
+
+            return false;
+        }
+
+        let rows = self.rows();
+
+        let row = match rows.get(self.cursor) {
+            Some(row) => row,
+            None => return matches!(key, Key::Char('q') | Key::Escape),
+        };
+
+        let name = row.name().to_string();
+
+        match key {
+            Key::ArrowUp | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => self.cursor = (self.cursor + 1).min(rows.len() - 1),
+            Key::ArrowRight | Key::Enter | Key::Char('l')
+                if !row.cycle && !self.tree.children(&name).is_empty() =>
+            {
+                self.expanded.insert(row.key());
+            }
+            Key::ArrowLeft | Key::Char('h') => {
+                let collapsed = self.expanded.remove(&row.key());
+
+                // collapsing a closed row moves up to its parent
+                if !collapsed && row.path.len() > 1 {
+                    let parent = row.path[..row.path.len() - 1].join(">");
+
+                    if let Some(index) = rows.iter().position(|row| row.key() == parent) {
+                        self.cursor = index;
+                    }
+                }
+            }
+            Key::Char('/') => self.query = Some(String::new()),
+            Key::Char('n') => self.jump_to_match(),
+            Key::Char('o') => self.open_homepage(&name),
+            Key::Char('w') => self.why(&name),
+            Key::Char('d') => self.toggle_removal(&name),
+            Key::Char('q') | Key::Escape => return true,
+            _ => {}
+        }
+
+        false
+    }
+}
+
+/// Open `url` in the default browser.
+fn open_url(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+/// Explore the dependency tree of the project until the user quits.
+///
+/// Returns the direct dependencies that were marked for removal.
+pub fn explore(app: &App, manifest: &PackageJson, lock_file: &LockFile) -> Result<Vec<String>> {
+    let term = Term::stdout();
+
+    if !term.is_term() {
+        miette::bail!("`volt ls --interactive` needs a terminal, use `volt ls` instead");
+    }
+
+    let tree = Tree::new(app, manifest, lock_file);
+    let order = tree.breadth_first();
+
+    let explorer = Arc::new(Mutex::new(Explorer {
+        tree,
+        node_modules: app.node_modules_dir.clone(),
+        details: HashMap::new(),
+        expanded: HashSet::new(),
+        cursor: 0,
+        offset: 0,
+        marked: BTreeSet::new(),
+        query: None,
+        matches: vec![],
+        next_match: 0,
+        status: String::new(),
+        closed: false,
+    }));
+
+    term.hide_cursor().ok();
+    explorer.lock().unwrap().render(&term);
+
+    let measuring = {
+        let explorer = explorer.clone();
+        let term = term.clone();
+
+        std::thread::spawn(move || {
+            let node_modules = explorer.lock().unwrap().node_modules.clone();
+            let mut rendered = Instant::now();
+
+            for name in order {
+                let details = Details::read(&node_modules.join(&name));
+
+                let mut explorer = explorer.lock().unwrap();
+
+                if explorer.closed {
+                    return;
+                }
+
+                explorer.details.insert(name, details);
+
+                if rendered.elapsed() >= REDRAW_INTERVAL {
+                    explorer.render(&term);
+                    rendered = Instant::now();
+                }
+            }
+
+            let mut explorer = explorer.lock().unwrap();
+
+            if !explorer.closed {
+                explorer.render(&term);
+            }
+        })
+    };
+
+    loop {
+        let key = term.read_key();
+
+        let mut explorer = explorer.lock().unwrap();
+
+        let close = match key {
+            Ok(key) => explorer.handle(key),
+            Err(_) => true,
+        };
+
+        if close {
+            explorer.closed = true;
+            break;
+        }
+
+        explorer.render(&term);
+    }
+
+    measuring.join().ok();
+
+    term.clear_screen().ok();
+    term.show_cursor().ok();
+
+    let explorer = explorer.lock().unwrap();
+
+    Ok(explorer.marked.iter().cloned().collect())
+}
@@ -0,0 +1,310 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Fix the metadata of published packages without touching their files.
+//!
+//! Some packages import a dependency they don't declare, declare a peer range leaving out the
+//! versions they work with, or forget the `os` of a platform build. Extensions patch the
+//! documents of these packages as they're fetched, so resolution, peer checks and platform
+//! checks all see the fixed metadata while the tarball stays what was published, like yarn's
+//! `packageExtensions`:
+//!
+//! ```toml
+//! # volt.toml
+//! [package-extensions."react-color@<=2.19.0"]
+//! peer-dependencies = { react = "*" }
+//! ```
+//!
+//! volt ships fixes for packages with known broken metadata in `package_extensions.json`. A
+//! list with a higher `revision` in `~/.volt/package-extensions.json` replaces it without
+//! upgrading volt, `builtin-package-extensions = false` turns them off. The extensions that
+//! changed a package are recorded with it in volt.lock.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::commands::add::Package;
+use crate::core::model::config::{PackageExtension, VoltConfig};
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::npm::get_npm_response;
+use crate::core::utils::package::{Packument, PackumentVersion};
+use crate::core::utils::satisfies;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::App;
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use miette::Result;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// File in the volt directory a newer list of the built-in extensions can be put into.
+const EXTENSIONS_FILE: &str = "package-extensions.json";
+
+/// The built-in extensions volt was released with.
+static BUILTIN: &str = include_str!("package_extensions.json");
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ExtensionList {
+    /// Raised with every change, the list with the highest one is used.
+    revision: u64,
+    extensions: BTreeMap<String, PackageExtension>,
+}
+
+/// An extension and the versions it applies to.
+#[derive(Debug, Clone)]
+struct Rule {
+    /// `name` or `name@range`, as it's configured.
+    key: String,
+    name: String,
+    range: Option<String>,
+    extension: PackageExtension,
+}
+
+impl Rule {
+    fn new(key: &str, extension: PackageExtension) -> Rule {
+        // the first `@` of a scope isn't a separator
+        let (name, range) = match key.char_indices().skip(1).find(|(_, c)| *c == '@') {
+            Some((index, _)) => (&key[..index], Some(key[index + 1..].to_string())),
+            None => (key, None),
+        };
+
+        Rule {
+            key: key.to_string(),
+            name: name.to_string(),
+            range,
+            extension,
+        }
+    }
+
+    fn applies_to(&self, name: &str, version: &str) -> bool {
+        self.name == name
+            && self
+                .range
+                .as_deref()
+                .is_none_or(|range| satisfies(version, range))
+    }
+}
+
+/// The extensions of a project, the ones of volt.toml before the built-in ones.
+#[derive(Debug, Clone, Default)]
+pub struct Extensions {
+    rules: Vec<Rule>,
+}
+
+impl Extensions {
+    pub fn load(config: &VoltConfig, volt_dir: &Path) -> Extensions {
+        let mut rules = config
+            .package_extensions
+            .iter()
+            .map(|(key, extension)| Rule::new(key, extension.clone()))
+            .collect::<Vec<_>>();
+
+        if config.builtin_package_extensions != Some(false) {
+            let builtin = builtin(volt_dir).extensions;
+
+            rules.extend(
+                builtin
+                    .into_iter()
+                    .map(|(key, extension)| Rule::new(&key, extension)),
+            );
+        }
+
+        Extensions { rules }
+    }
+
+    /// The extensions applying to `name@version`, in the order they're configured.
+    fn matching<'a>(&'a self, name: &'a str, version: &'a str) -> impl Iterator<Item = &'a Rule> {
+        self.rules
+            .iter()
+            .filter(move |rule| rule.applies_to(name, version))
+    }
+
+    /// Keys of the extensions applying to `name@version`.
+    pub fn applied(&self, name: &str, version: &str) -> Vec<String> {
+        self.matching(name, version)
+            .map(|rule| rule.key.clone())
+            .collect()
+    }
+
+    /// Patch every version of `packument` the extensions apply to.
+    pub fn extend(&self, packument: &mut Packument) {
+        for (version, manifest) in packument.versions.iter_mut() {
+            // the first rule wins, so it's applied last
+            let rules = self.matching(&packument.name, version).collect::<Vec<_>>();
+
+            for rule in rules.into_iter().rev() {
+                extend_manifest(manifest, &rule.extension);
+            }
+        }
+    }
+}
+
+fn extend_manifest(manifest: &mut PackumentVersion, extension: &PackageExtension) {
+    let merge = |into: &mut HashMap<String, String>, from: &BTreeMap<String, String>| {
+        for (name, range) in from.iter() {
+            into.insert(name.clone(), range.clone());
+        }
+    };
+
+    merge(&mut manifest.dependencies, &extension.dependencies);
+    merge(
+        &mut manifest.optional_dependencies,
+        &extension.optional_dependencies,
+    );
+    merge(
+        &mut manifest.peer_dependencies,
+        &extension.peer_dependencies,
+    );
+
+    for (name, meta) in extension.peer_dependencies_meta.iter() {
+        manifest
+            .peer_dependencies_meta
+            .insert(name.clone(), meta.clone());
+    }
+
+    let list = |values: &Vec<String>| Some(serde_json::Value::from(values.clone()));
+
+    if let Some(os) = &extension.os {
+        manifest.os = list(os);
+    }
+
+    if let Some(cpu) = &extension.cpu {
+        manifest.cpu = list(cpu);
+    }
+
+    if let Some(libc) = &extension.libc {
+        manifest.libc = list(libc);
+    }
+}
+
+/// The built-in extensions, or the newer list in the volt directory.
+fn builtin(volt_dir: &Path) -> ExtensionList {
+    let bundled: ExtensionList = serde_json::from_str(BUILTIN).unwrap_or_default();
+
+    let path = volt_dir.join(EXTENSIONS_FILE);
+
+    let installed = match std::fs::read_to_string(&path) {
+        Ok(data) => match serde_json::from_str::<ExtensionList>(&data) {
+            Ok(list) => Some(list),
+            Err(error) => {
+                warn!("ignoring {}, {}", path.display(), error);
+
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    match installed {
+        Some(list) if list.revision > bundled.revision => list,
+        _ => bundled,
+    }
+}
+
+/// Add the dependencies extensions declare to the packages of the tree, which trees from the
+/// CDN don't know about, resolving the ones the tree doesn't have yet.
+pub async fn apply_extensions(
+    app: &App,
+    dependencies: &mut HashMap<String, VoltPackage>,
+    before: Option<&DateTime<Utc>>,
+) -> Result<()> {
+    let extensions = &app.registries.extensions;
+
+    // (name, range, dependent)
+    let mut missing = vec![];
+
+    for package in dependencies.values_mut() {
+        let (name, version) = (package.name.clone(), package.version.clone());
+
+        let rules = extensions.matching(&name, &version).collect::<Vec<_>>();
+
+        for rule in rules.into_iter().rev() {
+            let extension = &rule.extension;
+
+            let add = |names: &mut Option<Vec<String>>, from: &BTreeMap<String, String>| {
+                for name in from.keys() {
+                    let names = names.get_or_insert_with(Vec::new);
+
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            };
+
+            add(&mut package.dependencies, &extension.dependencies);
+            add(
+                &mut package.optional_dependencies,
+                &extension.optional_dependencies,
+            );
+            add(&mut package.peer_dependencies, &extension.peer_dependencies);
+
+            if let Some(ranges) = package.ranges.as_mut() {
+                for (dependency, range) in extension.dependencies.iter() {
+                    ranges.insert(dependency.clone(), range.clone());
+                }
+            }
+
+            for (dependency, range) in extension.dependencies.iter() {
+                missing.push((dependency.clone(), range.clone(), name.clone()));
+            }
+        }
+    }
+
+    for (name, range, dependent) in missing {
+        let present = dependencies
+            .values()
+            .any(|package| package.name == name && satisfies(&package.version, &range));
+
+        if present {
+            continue;
+        }
+
+        let response = get_npm_response(
+            &Package {
+                name: name.clone(),
+                version: Some(range.clone()),
+                alias: None,
+            },
+            &app.registries,
+            before,
+        )
+        .await?;
+
+        info!(
+            "adding {}@{}, an undeclared dependency of {}",
+            name.bright_cyan(),
+            response.version.bright_yellow(),
+            dependent.bright_cyan()
+        );
+
+        for (key, package) in response.versions.get(&response.version).unwrap() {
+            if !dependencies.values().any(|p| p.name == package.name) {
+                dependencies.insert(key.clone(), package.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark the lockfile entries with the extensions that changed their metadata.
+pub fn record_extensions(lock_file: &mut LockFile, extensions: &Extensions) {
+    for lock in lock_file.dependencies.values_mut() {
+        lock.extended = extensions.applied(&lock.name, &lock.version);
+    }
+}
@@ -0,0 +1,158 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Install pure JS or WASM builds in place of native packages on platforms they have no build for.
+//!
+//! Packages like `esbuild` ship their binary as one optional dependency per platform, each
+//! limited to it with `os` and `cpu`. When none of them fits the machine, the package only
+//! fails once it runs, so its fallback (`esbuild-wasm`) is installed under its name instead.
+//! The built-in fallbacks are in `NATIVE_FALLBACKS`, `[native-fallbacks]` in volt.toml changes them.
+
+use std::collections::HashMap;
+
+use crate::commands::add::Package;
+use crate::core::model::http_manager::get_packument;
+use crate::core::utils::compat::{node_cpu, node_os};
+use crate::core::utils::npm::get_npm_response;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::App;
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use miette::Result;
+use tracing::warn;
+
+/// Replace native packages of the tree that have no build for this platform with their fallback,
+/// installed under the native package's name.
+///
+/// Returns the fallbacks keyed by the name of the package they replaced.
+pub async fn apply_native_fallbacks(
+    app: &App,
+    dependencies: &mut HashMap<String, VoltPackage>,
+    before: Option<&DateTime<Utc>>,
+) -> Result<HashMap<String, VoltPackage>> {
+    let natives = dependencies
+        .iter()
+        .filter(|(_, package)| package.alias.is_none() && package.optional_dependencies.is_some())
+        .filter_map(|(key, package)| {
+            let fallback = app.config.native_fallback(&package.name)?;
+            Some((key.clone(), package.clone(), fallback.to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut replaced = HashMap::new();
+
+    for (key, package, fallback) in natives {
+        let variants = package.optional_dependencies.clone().unwrap_or_default();
+
+        if has_platform_build(app, dependencies, &variants).await {
+            continue;
+        }
+
+        // fallbacks usually follow the versions of the native package, the latest one otherwise
+        let response = match get_npm_response(
+            &Package {
+                name: fallback.clone(),
+                version: Some(package.version.clone()),
+                alias: None,
+            },
+            &app.registries,
+            before,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(_) => {
+                get_npm_response(
+                    &Package {
+                        name: fallback.clone(),
+                        version: None,
+                        alias: None,
+                    },
+                    &app.registries,
+                    before,
+                )
+                .await?
+            }
+        };
+
+        let mut tree = response.versions.get(&response.version).unwrap().clone();
+
+        let root = tree.values().find(|p| p.name == fallback).cloned().unwrap();
+
+        tree.retain(|_, p| p.name != fallback);
+
+        warn!(
+            "{}@{} has no build for {}-{}, installing {}@{} in its place",
+            package.name.bright_cyan(),
+            package.version,
+            node_os(),
+            node_cpu(),
+            fallback.bright_cyan(),
+            root.version.bright_yellow()
+        );
+
+        dependencies.remove(&key);
+        dependencies.retain(|_, p| p.alias.is_some() || !variants.contains(&p.name));
+
+        for (key, package) in tree {
+            if !dependencies.values().any(|p| p.name == package.name) {
+                dependencies.insert(key, package);
+            }
+        }
+
+        let root = VoltPackage {
+            alias: Some(package.name.clone()),
+            ..root
+        };
+
+        dependencies.insert(
+            format!("{}@npm:{}@{}", package.name, root.name, root.version),
+            root.clone(),
+        );
+
+        replaced.insert(package.name, root);
+    }
+
+    Ok(replaced)
+}
+
+/// Whether one of the platform packages `variants` resolved in the tree fits this machine.
+/// Lookups that fail count as fitting, a flaky registry shouldn't swap out a working build.
+async fn has_platform_build(
+    app: &App,
+    dependencies: &HashMap<String, VoltPackage>,
+    variants: &[String],
+) -> bool {
+    for variant in dependencies
+        .values()
+        .filter(|p| p.alias.is_none() && variants.contains(&p.name))
+    {
+        let supported = match get_packument(&app.registries, &variant.name).await {
+            Ok(packument) => packument
+                .versions
+                .get(&variant.version)
+                .is_none_or(|version| version.supports_platform()),
+            Err(_) => true,
+        };
+
+        if supported {
+            return true;
+        }
+    }
+
+    false
+}
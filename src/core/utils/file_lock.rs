@@ -0,0 +1,398 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Serialize writes to state that several volt processes share, like the global lockfile.
+//!
+//! A [`FileLock`] is held around reading the file again, merging the changes into it and
+//! writing it back, so two installs finishing at the same time can't drop each other's
+//! entries. The OS lock on `<file>.lck` keeps other processes out, tasks of the same process
+//! wait for each other on a mutex first since some platforms don't lock within a process.
+//!
+//! Holders that only need the state to stay put, like installs linking from the shared
+//! virtual store, take the lock shared: they run side by side and writers wait for them all.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use crate::core::utils::constants::{FILE_LOCK_TIMEOUT, RETRY_BASE_DELAY};
+
+lazy_static! {
+    /// Files locked by this process, released in `Drop`.
+    static ref HELD: (Mutex<HashMap<PathBuf, Holders>>, Condvar) = (Mutex::new(HashMap::new()), Condvar::new());
+}
+
+/// Who in this process holds the lock of a file.
+enum Holders {
+    Exclusive,
+    /// The number of shared holders.
+    Shared(usize),
+}
+
+/// Access to a shared file until dropped, exclusive unless taken with
+/// [`FileLock::acquire_shared()`].
+pub struct FileLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl FileLock {
+    /// Wait until no other task or process writes `path`, retrying for `FILE_LOCK_TIMEOUT`
+    /// seconds before failing with `ErrorKind::TimedOut`.
+    ///
+    /// The wait happens on a blocking thread, the runtime's workers keep running other tasks.
+    pub async fn acquire(path: &Path) -> io::Result<FileLock> {
+        FileLock::spawn_acquire(path, true).await
+    }
+
+    /// Like [`FileLock::acquire()`], but only waits for exclusive holders and only keeps
+    /// those out.
+    pub async fn acquire_shared(path: &Path) -> io::Result<FileLock> {
+        FileLock::spawn_acquire(path, false).await
+    }
+
+    async fn spawn_acquire(path: &Path, exclusive: bool) -> io::Result<FileLock> {
+        let path = path.to_path_buf();
+
+        tokio::task::spawn_blocking(move || FileLock::acquire_blocking(&path, exclusive))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Interrupted, e))?
+    }
+
+    fn acquire_blocking(path: &Path, exclusive: bool) -> io::Result<FileLock> {
+        let deadline = Instant::now() + Duration::from_secs(FILE_LOCK_TIMEOUT);
+
+        let path = lock_path(path);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        claim(&path, exclusive, deadline)?;
+
+        match lock_file(&path, exclusive, deadline) {
+            Ok(file) => Ok(FileLock { path, _file: file }),
+            Err(e) => {
+                release(&path);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // the OS lock goes with the file handle, dropped right after this
+        release(&self.path);
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lck");
+    PathBuf::from(name)
+}
+
+fn timed_out(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "{} was held by another volt process for over {}s",
+            path.display(),
+            FILE_LOCK_TIMEOUT
+        ),
+    )
+}
+
+/// Wait for other tasks of this process to let go of `path`, shared holders only wait for an
+/// exclusive one.
+fn claim(path: &Path, exclusive: bool, deadline: Instant) -> io::Result<()> {
+    let (held, released) = &*HELD;
+
+    let mut held = held.lock().unwrap();
+
+    loop {
+        match (held.get_mut(path), exclusive) {
+            (None, true) => {
+                held.insert(path.to_path_buf(), Holders::Exclusive);
+                return Ok(());
+            }
+            (None, false) => {
+                held.insert(path.to_path_buf(), Holders::Shared(1));
+                return Ok(());
+            }
+            (Some(Holders::Shared(count)), false) => {
+                *count += 1;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let remaining = deadline
+            .checked_duration_since(Instant::now())
+            .ok_or_else(|| timed_out(path))?;
+
+        held = released.wait_timeout(held, remaining).unwrap().0;
+    }
+}
+
+fn release(path: &Path) {
+    let (held, released) = &*HELD;
+
+    let mut held = held.lock().unwrap();
+
+    if let Some(Holders::Shared(count)) = held.get_mut(path) {
+        if *count > 1 {
+            *count -= 1;
+            return;
+        }
+    }
+
+    held.remove(path);
+    released.notify_all();
+}
+
+/// Take the OS lock on `path`, backing off between attempts while another process holds it.
+fn lock_file(path: &Path, exclusive: bool, deadline: Instant) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    let mut delay = Duration::from_millis(RETRY_BASE_DELAY / 10);
+
+    loop {
+        if try_lock(&file, exclusive)? {
+            return Ok(file);
+        }
+
+        if Instant::now() + delay > deadline {
+            return Err(timed_out(path));
+        }
+
+        std::thread::sleep(delay);
+
+        delay = (delay * 2).min(Duration::from_millis(RETRY_BASE_DELAY));
+    }
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File, exclusive: bool) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = match exclusive {
+        true => libc::LOCK_EX,
+        false => libc::LOCK_SH,
+    };
+
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+
+    match io::Error::last_os_error() {
+        e if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        e => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn try_lock(file: &File, exclusive: bool) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+
+    let flags = match exclusive {
+        true => LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+        false => LOCKFILE_FAIL_IMMEDIATELY,
+    };
+
+    let locked =
+        unsafe { LockFileEx(file.as_raw_handle() as _, flags, 0, !0, !0, &mut overlapped) };
+
+    if locked != 0 {
+        return Ok(true);
+    }
+
+    match io::Error::last_os_error() {
+        e if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) => Ok(false),
+        e => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::lock_file::{DependencyID, DependencyLock, LockFile};
+    use std::process::{Command, Stdio};
+
+    /// Installs every writer finishes, each adding a package of its own.
+    const INSTALLS: usize = 25;
+
+    /// Lockfile a writer started by `processes_lose_no_updates` saves to.
+    const WRITER_PATH: &str = "VOLT_FILE_LOCK_TEST_PATH";
+
+    fn dependency(name: &str) -> DependencyLock {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": "1.0.0",
+            "tarball": "",
+            "integrity": "",
+            "dependencies": [],
+        }))
+        .unwrap()
+    }
+
+    /// Run `INSTALLS` global installs against the lockfile at `path`, each loading it, adding
+    /// a package named after `writer` and saving it merged.
+    fn install(path: &Path, writer: &str) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        for index in 0..INSTALLS {
+            let mut lock_file = LockFile::load(path).unwrap();
+
+            let name = format!("{}-{}", writer, index);
+
+            lock_file.dependencies.insert(
+                DependencyID(name.clone(), "1.0.0".to_string()),
+                dependency(&name),
+            );
+
+            runtime.block_on(lock_file.save_merged()).unwrap();
+        }
+    }
+
+    /// A lockfile no other test writes.
+    fn lock_file_path(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("volt-file-lock-{}-{}", test, std::process::id()));
+
+        std::fs::remove_dir_all(&directory).ok();
+        std::fs::create_dir_all(&directory).unwrap();
+
+        directory.join("volt.lock")
+    }
+
+    /// Packages in the lockfile at `path`, removing its directory.
+    fn installed(path: &Path) -> usize {
+        let installed = LockFile::load(path).unwrap().dependencies.len();
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        installed
+    }
+
+    #[test]
+    fn threads_lose_no_updates() {
+        let path = lock_file_path("threads");
+
+        let writers = (0..4)
+            .map(|thread| {
+                let path = path.clone();
+                std::thread::spawn(move || install(&path, &format!("thread{}", thread)))
+            })
+            .collect::<Vec<_>>();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(installed(&path), 4 * INSTALLS);
+    }
+
+    #[test]
+    fn processes_lose_no_updates() {
+        let path = lock_file_path("processes");
+
+        // test names leave out the crate
+        let writer = format!(
+            "{}::writer_process",
+            module_path!().splitn(2, "::").nth(1).unwrap()
+        );
+
+        let processes = (0..3)
+            .map(|_| {
+                Command::new(std::env::current_exe().unwrap())
+                    .args(["--exact", &writer, "--ignored"])
+                    .env(WRITER_PATH, &path)
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        install(&path, "parent");
+
+        for mut process in processes {
+            assert!(process.wait().unwrap().success());
+        }
+
+        assert_eq!(installed(&path), 4 * INSTALLS);
+    }
+
+    #[test]
+    fn shared_holders_keep_writers_out() {
+        let path = lock_file_path("shared");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let first = runtime.block_on(FileLock::acquire_shared(&path)).unwrap();
+        let second = runtime.block_on(FileLock::acquire_shared(&path)).unwrap();
+
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap();
+
+                runtime.block_on(FileLock::acquire(&path)).unwrap();
+
+                Instant::now()
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(200));
+        drop(first);
+
+        std::thread::sleep(Duration::from_millis(200));
+        let released = Instant::now();
+        drop(second);
+
+        assert!(writer.join().unwrap() >= released);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    /// One of the processes of `processes_lose_no_updates`.
+    #[test]
+    #[ignore]
+    fn writer_process() {
+        if let Some(path) = std::env::var_os(WRITER_PATH) {
+            install(Path::new(&path), &format!("process{}", std::process::id()));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Copy and link directory trees, the way every package gets out of the store.
+//!
+//! Directories are created first, then files are copied, hard linked or reflinked on a few
+//! threads at once, which is what makes packages with thousands of files fast to install.
+//! Symlinks inside a tree are recreated pointing where they pointed, never followed. Sparse
+//! files keep their holes when copied. Every error names the path it happened at, and keeps
+//! the OS error code of the failure underneath.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use walkdir::WalkDir;
+
+/// Files a tree needs before it's worth copying on more than one thread.
+const PARALLEL_MIN_FILES: usize = 64;
+
+/// Threads a single tree is copied on at most.
+const MAX_THREADS: usize = 8;
+
+/// Bytes a file needs before volt looks for holes in it.
+const SPARSE_MIN_SIZE: u64 = 1024 * 1024;
+
+/// How the files of a tree get to the destination.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileMode {
+    Copy,
+    /// Hard link every file, the destination must be on the same filesystem.
+    Hardlink,
+    /// Clone every file where the filesystem supports it, copy it where it doesn't.
+    Reflink,
+}
+
+/// How much of a tree is done, passed to the progress callback after every file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Copied {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Called from the copying threads after every file.
+pub type Progress<'a> = &'a (dyn Fn(Copied) + Sync);
+
+/// An error of a filesystem operation and the path it failed at.
+#[derive(Debug)]
+struct PathError {
+    path: PathBuf,
+    source: io::Error,
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.source)
+    }
+}
+
+impl std::error::Error for PathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// `error` with the path it happened at, of the same kind.
+pub fn with_path(path: &Path, error: io::Error) -> io::Error {
+    // errors that already know where they happened are passed on
+    if error.get_ref().is_some_and(|inner| inner.is::<PathError>()) {
+        return error;
+    }
+
+    io::Error::new(
+        error.kind(),
+        PathError {
+            path: path.to_path_buf(),
+            source: error,
+        },
+    )
+}
+
+/// Recreate the tree at `source` at `destination`, its files brought over as `mode` says.
+///
+/// `progress` is called after every file, from whichever thread handled it.
+/// ## Returns
+/// * `io::Result<Copied>` - the files and bytes of the tree
+pub fn copy_tree(
+    source: &Path,
+    destination: &Path,
+    mode: FileMode,
+    progress: Option<Progress>,
+) -> io::Result<Copied> {
+    // (from, to, length)
+    let mut files = vec![];
+    let mut links = vec![];
+
+    for entry in WalkDir::new(source) {
+        let entry = entry.map_err(|e| {
+            let path = e.path().unwrap_or(source).to_path_buf();
+            with_path(&path, e.into())
+        })?;
+
+        let target = destination.join(entry.path().strip_prefix(source).unwrap());
+        let file_type = entry.file_type();
+
+        if file_type.is_dir() {
+            std::fs::create_dir_all(&target).map_err(|e| with_path(&target, e))?;
+        } else if file_type.is_symlink() {
+            links.push((entry.into_path(), target));
+        } else {
+            let length = entry
+                .metadata()
+                .map_err(|e| with_path(entry.path(), e.into()))?
+                .len();
+
+            files.push((entry.into_path(), target, length));
+        }
+    }
+
+    for (link, target) in links.iter() {
+        copy_symlink(link, target, mode)?;
+    }
+
+    let threads = match files.len() < PARALLEL_MIN_FILES {
+        true => 1,
+        false => std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(MAX_THREADS),
+    };
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let copied_files = AtomicU64::new(0);
+    let copied_bytes = AtomicU64::new(0);
+
+    let work = || -> io::Result<()> {
+        while !failed.load(Ordering::Relaxed) {
+            let (from, to, length) = match files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                Some(file) => file,
+                None => break,
+            };
+
+            if let Err(error) = copy_file(from, to, *length, mode) {
+                failed.store(true, Ordering::Relaxed);
+                return Err(error);
+            }
+
+            let done = Copied {
+                files: copied_files.fetch_add(1, Ordering::Relaxed) + 1,
+                bytes: copied_bytes.fetch_add(*length, Ordering::Relaxed) + length,
+            };
+
+            if let Some(progress) = progress {
+                progress(done);
+            }
+        }
+
+        Ok(())
+    };
+
+    match threads {
+        1 => work()?,
+        _ => std::thread::scope(|scope| {
+            let workers = (0..threads).map(|_| scope.spawn(work)).collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().expect("a copying thread panicked"))
+        })?,
+    }
+
+    Ok(Copied {
+        files: copied_files.into_inner(),
+        bytes: copied_bytes.into_inner(),
+    })
+}
+
+/// The files and bytes of the tree at `path`, symlinks aren't followed.
+pub fn tree_size(path: &Path) -> Copied {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .fold(Copied::default(), |total, metadata| Copied {
+            files: total.files + 1,
+            bytes: total.bytes + metadata.len(),
+        })
+}
+
+fn copy_file(from: &Path, to: &Path, length: u64, mode: FileMode) -> io::Result<()> {
+    let linked = match mode {
+        FileMode::Hardlink => std::fs::hard_link(from, to),
+        FileMode::Reflink => reflink(from, to).or_else(|_| copy(from, to, length)),
+        FileMode::Copy => copy(from, to, length),
+    };
+
+    linked.map_err(|e| with_path(from, e))
+}
+
+fn copy(from: &Path, to: &Path, length: u64) -> io::Result<()> {
+    match length >= SPARSE_MIN_SIZE && is_sparse(from, length) {
+        true => sparse_copy(from, to),
+        false => std::fs::copy(from, to).map(|_| ()),
+    }
+}
+
+/// Whether the file at `path` takes less space on disk than its length.
+#[cfg(unix)]
+fn is_sparse(path: &Path, length: u64) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.blocks() * 512 < length)
+}
+
+#[cfg(not(unix))]
+fn is_sparse(_path: &Path, _length: u64) -> bool {
+    false
+}
+
+/// Copy a file, skipping over blocks of zeros instead of writing them.
+fn sparse_copy(from: &Path, to: &Path) -> io::Result<()> {
+    let mut input = File::open(from)?;
+    let mut output = File::create(to).map_err(|e| with_path(to, e))?;
+
+    let mut buffer = vec![0; 64 * 1024];
+    let mut length = 0;
+
+    loop {
+        let read = input.read(&mut buffer)?;
+
+        if read == 0 {
+            break;
+        }
+
+        if buffer[..read].iter().all(|byte| *byte == 0) {
+            output.seek(SeekFrom::Current(read as i64))?;
+        } else {
+            output.write_all(&buffer[..read])?;
+        }
+
+        length += read as u64;
+    }
+
+    // a hole at the end is only there once the length says so
+    output.set_len(length)?;
+    output.set_permissions(input.metadata()?.permissions())
+}
+
+/// Recreate the symlink `link` at `target`, pointing where it points.
+fn copy_symlink(link: &Path, target: &Path, mode: FileMode) -> io::Result<()> {
+    let original = std::fs::read_link(link).map_err(|e| with_path(link, e))?;
+
+    #[cfg(unix)]
+    let created = std::os::unix::fs::symlink(&original, target);
+
+    #[cfg(windows)]
+    let created = match link.is_dir() {
+        true => std::os::windows::fs::symlink_dir(&original, target),
+        false => std::os::windows::fs::symlink_file(&original, target),
+    };
+
+    match created {
+        Ok(()) => Ok(()),
+        // creating symlinks takes developer mode on Windows, copy what it points to instead
+        Err(_) if cfg!(windows) && link.is_dir() => copy_tree(link, target, mode, None).map(|_| ()),
+        Err(_) if cfg!(windows) && link.is_file() => std::fs::copy(link, target)
+            .map(|_| ())
+            .map_err(|e| with_path(link, e)),
+        Err(error) => Err(with_path(target, error)),
+    }
+}
+
+/// Files this process may have open at once, the soft `RLIMIT_NOFILE` on unix.
+#[cfg(unix)]
+pub fn open_file_limit() -> usize {
+    use std::convert::TryFrom;
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    match unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } {
+        0 => usize::try_from(limit.rlim_cur).unwrap_or(usize::MAX),
+        _ => 256,
+    }
+}
+
+/// Windows has no limit of its own, this is the one of the C runtime.
+#[cfg(not(unix))]
+pub fn open_file_limit() -> usize {
+    512
+}
+
+/// Clone `source` into a new file at `destination` sharing its blocks (btrfs, xfs, apfs).
+#[cfg(target_os = "linux")]
+pub fn reflink(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // _IOW(0x94, 9, int), not exported by the libc crate
+    const FICLONE: u64 = 0x4004_9409;
+
+    let from = File::open(source)?;
+    let to = File::create(destination)?;
+
+    let cloned = unsafe { libc::ioctl(to.as_raw_fd(), FICLONE as _, from.as_raw_fd()) };
+
+    if cloned == 0 {
+        to.set_permissions(from.metadata()?.permissions())
+    } else {
+        let error = io::Error::last_os_error();
+        drop(to);
+        std::fs::remove_file(destination).ok();
+        Err(error)
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn reflink(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(source.as_os_str().as_bytes())?;
+    let to = CString::new(destination.as_os_str().as_bytes())?;
+
+    if unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn reflink(_source: &Path, _destination: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks aren't supported on this platform",
+    ))
+}
@@ -0,0 +1,549 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Fetch packages straight from git repositories.
+//!
+//! Repositories are cloned once into `~/.volt/.git-cache` and every commit that gets installed
+//! is exported into its own checkout, so the same commit is only ever prepared once. A
+//! checkout whose `prepare` script wasn't allowed to run is exported again next time.
+//!
+//! The lockfile pins the commit and the integrity of the packed checkout, which packs the same
+//! on every machine unless its `prepare` script doesn't build reproducibly.
+
+use crate::core::model::lock_file::{DependencyID, DependencyLock};
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::install_scripts::allow_prepare;
+use crate::core::utils::lifecycle::ScriptPlan;
+use crate::core::utils::pack::{pack_directory, tarball_hashes, PackOptions};
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::platform::Platform;
+
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use miette::Result;
+use sha1::Digest;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tar::Archive;
+use tracing::warn;
+
+/// A dependency specified as a git repository, e.g. `git+https://github.com/user/repo#v1.2.0`
+/// or the GitHub shorthand `user/repo#main`.
+#[derive(Debug, Clone)]
+pub struct GitSpec {
+    /// The specifier exactly as it was given, this is what ends up in package.json.
+    pub spec: String,
+    /// Url git can clone from.
+    pub url: String,
+    /// Branch, tag or commit, the default branch when `None`.
+    pub reference: Option<String>,
+}
+
+impl GitSpec {
+    /// The same repository at `commit`, e.g. the one volt.lock pins.
+    pub fn at(&self, commit: &str) -> GitSpec {
+        GitSpec {
+            reference: Some(commit.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// The commit `lock` pins this dependency to, when it was locked from the same repository.
+    pub fn locked_commit(&self, lock: &DependencyLock) -> Option<String> {
+        let locked = parse_git_spec(&lock.tarball)?;
+
+        match locked.url == self.url {
+            true => locked.reference,
+            false => None,
+        }
+    }
+}
+
+/// A git dependency checked out at an exact commit.
+#[derive(Debug, Clone)]
+pub struct GitPackage {
+    pub spec: GitSpec,
+    pub name: String,
+    pub version: String,
+    pub commit: String,
+    /// Packed contents of the checkout.
+    pub tarball: Vec<u8>,
+    pub integrity: String,
+    pub dependencies: HashMap<String, String>,
+    /// Whether it has a `prepare` script, whose build may differ between machines.
+    pub prepare: bool,
+}
+
+impl GitPackage {
+    /// What the lockfile records as the source, pinned to the exact commit.
+    pub fn resolved(&self) -> String {
+        if self.spec.url.starts_with("git://") {
+            format!("{}#{}", self.spec.url, self.commit)
+        } else {
+            format!("git+{}#{}", self.spec.url, self.commit)
+        }
+    }
+
+    pub fn lock_id(&self) -> DependencyID {
+        DependencyID(self.name.clone(), self.version.clone())
+    }
+
+    /// The lockfile entry, pinning the commit and the integrity of the packed checkout.
+    pub fn lock(&self) -> DependencyLock {
+        let mut dependencies = self.dependencies.keys().cloned().collect::<Vec<_>>();
+        dependencies.sort();
+
+        DependencyLock {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            tarball: self.resolved(),
+            integrity: self.integrity.clone(),
+            dependencies,
+            optional: false,
+            files: BTreeMap::new(),
+            alias: None,
+            has_install_script: false,
+            overridden: None,
+            extended: vec![],
+            platform: Platform::default(),
+            engines: BTreeMap::new(),
+            manifest: None,
+        }
+    }
+
+    /// Check the packed checkout against the integrity `lock` recorded. What `prepare` builds
+    /// isn't always reproducible, a mismatch only warns for packages that have one.
+    pub fn check_integrity(&self, lock: &DependencyLock) -> Result<()> {
+        if self.integrity == lock.integrity {
+            return Ok(());
+        }
+
+        if self.prepare {
+            warn!(
+                "{} at commit {} packs differently than volt.lock recorded, its prepare script may not build reproducibly",
+                self.name.bright_cyan(),
+                &self.commit[..self.commit.len().min(10)]
+            );
+
+            return Ok(());
+        }
+
+        Err(VoltError::GitIntegrityMismatch {
+            name: self.name.clone(),
+            commit: self.commit.clone(),
+            expected: lock.integrity.clone(),
+            actual: self.integrity.clone(),
+        }
+        .into())
+    }
+}
+
+/// Recognize git specifiers, anything else is left to the registry.
+pub fn parse_git_spec(spec: &str) -> Option<GitSpec> {
+    let spec = spec.trim();
+
+    let (source, reference) = match spec.split_once('#') {
+        Some((source, reference)) if !reference.is_empty() => (source, Some(reference.to_string())),
+        Some((source, _)) => (source, None),
+        None => (spec, None),
+    };
+
+    let url = if let Some(url) = source.strip_prefix("git+") {
+        url.to_string()
+    } else if source.starts_with("git://") {
+        source.to_string()
+    } else if let Some(repository) = source.strip_prefix("github:") {
+        format!(
+            "https://github.com/{}.git",
+            repository.trim_end_matches(".git")
+        )
+    } else if is_github_shorthand(source) {
+        format!("https://github.com/{}.git", source.trim_end_matches(".git"))
+    } else {
+        return None;
+    };
+
+    // git reads anything starting with `-` as an option, e.g. `--upload-pack=<command>`
+    if is_option(&url) || reference.as_deref().is_some_and(is_option) {
+        return None;
+    }
+
+    Some(GitSpec {
+        spec: spec.to_string(),
+        url,
+        reference,
+    })
+}
+
+/// Whether git would read `value` as an option rather than a url or ref.
+pub fn is_option(value: &str) -> bool {
+    value.starts_with('-')
+}
+
+/// `user/repo`, which unlike `@scope/name` has no leading `@` and no version.
+fn is_github_shorthand(source: &str) -> bool {
+    let mut parts = source.split('/');
+
+    let valid = |part: Option<&str>| {
+        part.is_some_and(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        })
+    };
+
+    !source.starts_with('@') && valid(parts.next()) && valid(parts.next()) && parts.next().is_none()
+}
+
+/// Run git and return its stdout.
+pub fn git<I, S>(directory: Option<&Path>, args: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args = args
+        .into_iter()
+        .map(|arg| arg.as_ref().to_os_string())
+        .collect::<Vec<_>>();
+
+    let command = format!(
+        "git {}",
+        args.iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    let mut git = Command::new("git");
+
+    if let Some(directory) = directory {
+        git.current_dir(directory);
+    }
+
+    let output = git
+        .args(&args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| VoltError::GitError {
+            command: command.clone(),
+            message: e.to_string(),
+        })?;
+
+    if !output.status.success() {
+        return Err(VoltError::GitError {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+
+    Ok(output.stdout)
+}
+
+/// Resolve `reference` to a commit hash, trying remote branches before giving up.
+fn resolve_commit(repository: &Path, spec: &GitSpec) -> Result<String> {
+    let candidates = match &spec.reference {
+        Some(reference) if is_option(reference) => vec![],
+        Some(reference) => vec![
+            format!("{}^{{commit}}", reference),
+            format!("origin/{}^{{commit}}", reference),
+        ],
+        None => vec![String::from("origin/HEAD^{commit}"), String::from("HEAD")],
+    };
+
+    for candidate in candidates {
+        let args = ["rev-parse", "--verify", "--quiet", candidate.as_str()];
+
+        if let Ok(commit) = git(Some(repository), args) {
+            return Ok(String::from_utf8_lossy(&commit).trim().to_string());
+        }
+    }
+
+    Err(VoltError::GitRefNotFound {
+        url: spec.url.clone(),
+        reference: spec
+            .reference
+            .clone()
+            .unwrap_or_else(|| String::from("HEAD")),
+    })?
+}
+
+/// Unpack a package tarball into `destination`, dropping the leading `package/` directory.
+pub fn unpack_tarball(tarball: impl std::io::Read, destination: &Path) -> Result<()> {
+    let unpack = || -> std::io::Result<()> {
+        let mut archive = Archive::new(GzDecoder::new(tarball));
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            let path = entry_path(&entry.path()?);
+
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+
+            let target = destination.join(path);
+
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            entry.unpack(target)?;
+        }
+
+        Ok(())
+    };
+
+    unpack().map_err(|e| VoltError::ExtractError {
+        name: destination.display().to_string(),
+        source: e,
+    })?;
+
+    Ok(())
+}
+
+/// Paths of the files in a package tarball, relative to the package like `unpack_tarball`
+/// writes them.
+pub fn tarball_files(tarball: impl std::io::Read, name: &str) -> Result<Vec<PathBuf>> {
+    let list = || -> std::io::Result<Vec<PathBuf>> {
+        let mut archive = Archive::new(GzDecoder::new(tarball));
+        let mut files = vec![];
+
+        for entry in archive.entries()? {
+            let entry = entry?;
+
+            if entry.header().entry_type().is_dir() {
+                continue;
+            }
+
+            let path = entry_path(&entry.path()?);
+
+            if !path.as_os_str().is_empty() {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    };
+
+    Ok(list().map_err(|e| VoltError::ExtractError {
+        name: name.to_string(),
+        source: e,
+    })?)
+}
+
+/// Path of a tarball entry inside the package, without the leading `package/` directory or
+/// anything that could climb out of it.
+fn entry_path(path: &Path) -> PathBuf {
+    path.components()
+        .skip(1)
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Clone (or update) the repository, check out the requested ref and prepare it for installing.
+pub fn fetch_git_package(app: &App, spec: &GitSpec) -> Result<GitPackage> {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(spec.url.as_bytes());
+    let key = format!("{:x}", hasher.finalize());
+
+    let cache = app.volt_dir.join(".git-cache");
+    let repository = cache.join(&key);
+
+    std::fs::create_dir_all(&cache).map_err(VoltError::CreateDirError)?;
+
+    if repository.join("HEAD").exists() {
+        git(
+            Some(&repository),
+            ["fetch", "--quiet", "--tags", "--force", "origin"],
+        )?;
+    } else {
+        git(
+            None,
+            [
+                OsStr::new("clone"),
+                OsStr::new("--quiet"),
+                OsStr::new("--bare"),
+                OsStr::new("--"),
+                OsStr::new(&spec.url),
+                repository.as_os_str(),
+            ],
+        )?;
+
+        // bare clones don't map remote branches, which `origin/<branch>` lookups rely on
+        git(
+            Some(&repository),
+            [
+                "config",
+                "remote.origin.fetch",
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+        )?;
+
+        git(Some(&repository), ["fetch", "--quiet", "origin"])?;
+        git(
+            Some(&repository),
+            ["remote", "set-head", "origin", "--auto"],
+        )
+        .ok();
+    }
+
+    let commit = resolve_commit(&repository, spec)?;
+
+    let checkout = app
+        .volt_dir
+        .join(".git-checkouts")
+        .join(format!("{}-{}", key, commit));
+
+    let mut prepared = true;
+
+    if !checkout.join("package.json").exists() {
+        // a half prepared checkout must not be mistaken for a finished one next time
+        match export_commit(app, spec, &repository, &commit, &checkout) {
+            Ok(complete) => prepared = complete,
+            Err(error) => {
+                std::fs::remove_dir_all(&checkout).ok();
+                return Err(error);
+            }
+        }
+    }
+
+    let manifest = PackageJson::read(&checkout.join("package.json"))?;
+
+    let tarball = pack_directory(&checkout, &PackOptions::default());
+
+    if !prepared {
+        std::fs::remove_dir_all(&checkout).ok();
+    }
+
+    let tarball = tarball?;
+    let (_, integrity) = tarball_hashes(&tarball);
+
+    Ok(GitPackage {
+        spec: spec.clone(),
+        name: manifest.name,
+        version: manifest.version,
+        commit,
+        tarball,
+        integrity,
+        prepare: manifest.scripts.contains_key("prepare"),
+        dependencies: manifest.dependencies,
+    })
+}
+
+/// Export `commit` into `checkout` and run its `prepare` script, returning whether the
+/// checkout is complete: it has no such script or it was allowed to run.
+///
+/// Like npm, packages that need building are expected to build themselves in `prepare`.
+/// Only tools already on PATH are available to it, the package's devDependencies aren't
+/// installed into the checkout.
+fn export_commit(
+    app: &App,
+    spec: &GitSpec,
+    repository: &Path,
+    commit: &str,
+    checkout: &Path,
+) -> Result<bool> {
+    let archive = git(
+        Some(repository),
+        ["archive", "--format=tar", "--prefix=package/", commit],
+    )?;
+
+    std::fs::create_dir_all(checkout).map_err(VoltError::CreateDirError)?;
+
+    Archive::new(archive.as_slice())
+        .entries()
+        .and_then(|entries| {
+            for entry in entries {
+                let mut entry = entry?;
+                let path = entry.path()?.components().skip(1).collect::<PathBuf>();
+
+                if !path.as_os_str().is_empty() {
+                    entry.unpack(checkout.join(path))?;
+                }
+            }
+
+            Ok(())
+        })
+        .map_err(|e| VoltError::ExtractError {
+            name: spec.url.clone(),
+            source: e,
+        })?;
+
+    let manifest = PackageJson::read(&checkout.join("package.json"))?;
+
+    if !manifest.scripts.contains_key("prepare") {
+        return Ok(true);
+    }
+
+    let resolved = format!("{}#{}", spec.url, commit);
+
+    if !allow_prepare(app, &manifest.name, &resolved) {
+        return Ok(false);
+    }
+
+    ScriptPlan::new(
+        checkout,
+        &manifest,
+        "prepare",
+        &[],
+        app.config.script_shell.as_deref(),
+    )?
+    .run()?;
+
+    Ok(true)
+}
+
+/// Install a fetched git package into node_modules, replacing whatever was there.
+pub fn install_git_package(app: &App, package: &GitPackage) -> Result<()> {
+    let destination = app.node_modules_dir.join(&package.name);
+
+    if destination.exists() {
+        std::fs::remove_dir_all(&destination).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: destination.display().to_string(),
+        })?;
+    }
+
+    unpack_tarball(&package.tarball[..], &destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_specs() {
+        let spec = parse_git_spec("git+https://example.com/repo.git#v1.2.0").unwrap();
+        assert_eq!(spec.url, "https://example.com/repo.git");
+        assert_eq!(spec.reference.as_deref(), Some("v1.2.0"));
+
+        let spec = parse_git_spec("user/repo").unwrap();
+        assert_eq!(spec.url, "https://github.com/user/repo.git");
+        assert_eq!(spec.reference, None);
+
+        assert!(parse_git_spec("@scope/name").is_none());
+        assert!(parse_git_spec("^1.0.0").is_none());
+    }
+
+    #[test]
+    fn rejects_specs_git_reads_as_options() {
+        assert!(parse_git_spec("git+--upload-pack=touch /tmp/pwned").is_none());
+        assert!(parse_git_spec("git+-c core.sshCommand=id#main").is_none());
+        assert!(parse_git_spec("git+https://example.com/repo.git#--output=/tmp/x").is_none());
+    }
+}
@@ -0,0 +1,744 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Which version of a dependency every package gets, and where it goes in node_modules.
+//!
+//! A tree can hold several versions of a package when the ranges asking for it don't agree.
+//! `Graph` works out the version each dependent gets: the highest one in the tree its range
+//! allows, or the one the project depends on when the range isn't known, since trees from the
+//! CDN only list names.
+//!
+//! `Graph::layout` places the packages like npm does. Starting from the direct dependencies,
+//! every dependency goes as high up as nothing else of its name is in the way, at the top of
+//! node_modules when it can, but never where it would hide the version a package below
+//! already found further up. A version that conflicts with one further up is nested in the
+//! node_modules of the package needing it, where node finds it first:
+//!
+//! ```text
+//! node_modules/debug                          debug@4, what the project and most packages want
+//! node_modules/send/node_modules/debug        debug@2, only send asks for it
+//! ```
+
+use crate::core::utils::satisfies;
+use crate::core::utils::voltapi::VoltPackage;
+
+use node_semver::Version;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+/// Versions of the packages of one install and who depends on which.
+pub struct Graph<'a> {
+    packages: Vec<&'a VoltPackage>,
+    /// Packages every name resolves to, aliases aside, which only the project can depend on.
+    by_name: HashMap<&'a str, Vec<usize>>,
+    /// The direct dependencies.
+    direct: Vec<usize>,
+}
+
+/// Where a package goes, relative to node_modules.
+pub struct Placement<'a> {
+    /// `<name>` at the top, `<parent path>/node_modules/<name>` below.
+    pub path: PathBuf,
+    pub package: &'a VoltPackage,
+    /// How many packages it's nested in, 0 at the top.
+    pub depth: usize,
+    /// Whether other packages are nested in it.
+    pub has_nested: bool,
+}
+
+impl<'a> Graph<'a> {
+    /// The graph of `packages`, of which `direct` are the direct dependencies, keyed by the name
+    /// they're installed under and with the range they were asked for.
+    pub fn new(packages: &[&'a VoltPackage], direct: &HashMap<String, String>) -> Graph<'a> {
+        let packages = packages.to_vec();
+
+        let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+
+        for (index, package) in packages.iter().enumerate() {
+            if package.alias.is_none() {
+                by_name
+                    .entry(package.name.as_str())
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut direct = direct
+            .iter()
+            .filter_map(|(name, range)| {
+                let candidates = packages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, package)| package.install_name() == name)
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>();
+
+                pick(&packages, &candidates, Some(range))
+                    .or_else(|| highest(&packages, &candidates))
+            })
+            .collect::<Vec<_>>();
+
+        direct.sort_by(|a, b| packages[*a].install_name().cmp(packages[*b].install_name()));
+
+        Graph {
+            packages,
+            by_name,
+            direct,
+        }
+    }
+
+    /// The direct dependencies.
+    pub fn direct(&self) -> impl Iterator<Item = &'a VoltPackage> + '_ {
+        self.direct.iter().map(move |index| self.packages[*index])
+    }
+
+    /// Whether `package` is one of the direct dependencies.
+    pub fn is_direct(&self, package: &VoltPackage) -> bool {
+        self.direct().any(|direct| key(direct) == key(package))
+    }
+
+    /// Every dependency of `package` that is part of the install, peers included, by name.
+    pub fn dependencies(&self, package: &VoltPackage) -> Vec<(&'a str, &'a VoltPackage)> {
+        self.dependency_indices(package)
+            .into_iter()
+            .map(|index| {
+                let dependency = self.packages[index];
+
+                (dependency.name.as_str(), dependency)
+            })
+            .collect()
+    }
+
+    fn dependency_indices(&self, package: &VoltPackage) -> Vec<usize> {
+        let mut names = package
+            .dependencies
+            .iter()
+            .chain(package.optional_dependencies.iter())
+            .chain(package.peer_dependencies.iter())
+            .flatten()
+            .filter(|name| **name != package.name)
+            .collect::<Vec<_>>();
+
+        names.sort();
+        names.dedup();
+
+        names
+            .into_iter()
+            .filter_map(|name| self.resolve(package, name))
+            .collect()
+    }
+
+    fn resolve(&self, package: &VoltPackage, name: &str) -> Option<usize> {
+        let candidates = self.by_name.get(name)?;
+
+        let range = package.ranges.as_ref().and_then(|ranges| ranges.get(name));
+
+        if let Some(index) = pick(&self.packages, candidates, range.map(String::as_str)) {
+            return Some(index);
+        }
+
+        // without a range, whatever the project itself uses
+        candidates
+            .iter()
+            .copied()
+            .find(|index| self.direct.contains(index))
+            .or_else(|| highest(&self.packages, candidates))
+    }
+
+    /// Packages at the top of node_modules.
+    pub fn top_level(&self) -> HashSet<String> {
+        self.layout()
+            .into_iter()
+            .filter(|placement| placement.depth == 0)
+            .map(|placement| key(placement.package))
+            .collect()
+    }
+
+    /// Where every package goes in node_modules, parents before the packages nested in them.
+    /// A package can be in more than one place, or nowhere when nothing reaches it and the
+    /// top already has another version.
+    pub fn layout(&self) -> Vec<Placement<'a>> {
+        self.place(&self.direct, true)
+    }
+
+    /// Where the packages go in the node_modules of a workspace package declaring `ranges`,
+    /// keyed like the direct dependencies. Only what it gets another version of than the top
+    /// of the project's node_modules is placed, it finds everything else there.
+    pub fn own_layout(&self, ranges: &HashMap<String, String>) -> Vec<Placement<'a>> {
+        let own = Graph::new(&self.packages, ranges);
+
+        if own.direct.iter().all(|index| self.direct.contains(index)) {
+            return vec![];
+        }
+
+        let top_level = self.top_level();
+
+        let placements = self.place(&own.direct, false);
+
+        // what the top already has is left out with everything nested in it
+        let shared = placements
+            .iter()
+            .filter(|placement| placement.depth == 0)
+            .filter(|placement| top_level.contains(&key(placement.package)))
+            .map(|placement| placement.path.clone())
+            .collect::<Vec<_>>();
+
+        placements
+            .into_iter()
+            .filter(|placement| !shared.iter().any(|path| placement.path.starts_with(path)))
+            .collect()
+    }
+
+    /// Layout of the packages reached from `direct`, and of the ones nothing reaches with
+    /// `unreached`.
+    fn place(&self, direct: &[usize], unreached: bool) -> Vec<Placement<'a>> {
+        let mut nodes = vec![Node::default()];
+        let mut queue = VecDeque::new();
+
+        // nodes already placed whose dependency of a name was settled, with the version they got
+        let mut dependents: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+
+        for index in direct.iter() {
+            let name = self.packages[*index].install_name();
+
+            if !nodes[0].children.contains_key(name) {
+                queue.push_back(add_node(&mut nodes, 0, name, *index));
+            }
+        }
+
+        loop {
+            while let Some(node) = queue.pop_front() {
+                let package = self.packages[nodes[node].package.unwrap()];
+
+                for dependency in self.dependency_indices(package) {
+                    let name = self.packages[dependency].name.as_str();
+
+                    // the levels the dependency can go to without anything in the way, lowest first
+                    let mut level = Some(node);
+                    let mut free = vec![];
+                    let mut found = false;
+
+                    while let Some(current) = level {
+                        match nodes[current].children.get(name) {
+                            Some(&child) => {
+                                found = nodes[child].package == Some(dependency);
+                                break;
+                            }
+                            None => free.push(current),
+                        }
+
+                        level = nodes[current].parent;
+                    }
+
+                    let placed = dependents.entry(name).or_default();
+
+                    placed.push((node, dependency));
+
+                    if found || free.is_empty() {
+                        continue;
+                    }
+
+                    // the highest of them where no package below finds another version through it
+                    let target = free
+                        .iter()
+                        .rev()
+                        .copied()
+                        .find(|&level| {
+                            !placed.iter().any(|&(dependent, version)| {
+                                version != dependency
+                                    && resolves_through(&nodes, dependent, level, name)
+                            })
+                        })
+                        .unwrap_or(node);
+
+                    // a cycle of conflicting versions would nest forever
+                    if ancestors(&nodes, target).any(|package| package == dependency) {
+                        continue;
+                    }
+
+                    queue.push_back(add_node(&mut nodes, target, name, dependency));
+                }
+            }
+
+            if !unreached {
+                break;
+            }
+
+            // packages nothing reaches, like peers installed for the project, go at the top
+            let placed = nodes
+                .iter()
+                .filter_map(|node| node.package)
+                .collect::<HashSet<_>>();
+
+            for (index, package) in self.packages.iter().enumerate() {
+                if !placed.contains(&index)
+                    && !nodes[0].children.contains_key(package.install_name())
+                {
+                    queue.push_back(add_node(&mut nodes, 0, package.install_name(), index));
+                }
+            }
+
+            if queue.is_empty() {
+                break;
+            }
+        }
+
+        let mut placements = nodes
+            .iter()
+            .skip(1)
+            .map(|node| Placement {
+                path: node.path.clone(),
+                package: self.packages[node.package.unwrap()],
+                depth: node.depth,
+                has_nested: !node.children.is_empty(),
+            })
+            .collect::<Vec<_>>();
+
+        placements.sort_by(|a, b| a.depth.cmp(&b.depth).then_with(|| a.path.cmp(&b.path)));
+
+        placements
+    }
+}
+
+/// `name@version` of `package`, under the name it's installed as.
+pub fn key(package: &VoltPackage) -> String {
+    format!("{}@{}", package.install_name(), package.version)
+}
+
+#[derive(Default)]
+struct Node {
+    package: Option<usize>,
+    parent: Option<usize>,
+    path: PathBuf,
+    depth: usize,
+    children: HashMap<String, usize>,
+}
+
+fn add_node(nodes: &mut Vec<Node>, parent: usize, name: &str, package: usize) -> usize {
+    let (path, depth) = match nodes[parent].package {
+        Some(_) => (
+            nodes[parent].path.join("node_modules").join(name),
+            nodes[parent].depth + 1,
+        ),
+        None => (PathBuf::from(name), 0),
+    };
+
+    nodes.push(Node {
+        package: Some(package),
+        parent: Some(parent),
+        path,
+        depth,
+        children: HashMap::new(),
+    });
+
+    let index = nodes.len() - 1;
+
+    nodes[parent].children.insert(name.to_string(), index);
+
+    index
+}
+
+/// Whether `dependent` finds `name` in the node_modules of `level`, because it's placed at or
+/// below `level` and nothing of that name is in between.
+fn resolves_through(nodes: &[Node], dependent: usize, level: usize, name: &str) -> bool {
+    let mut current = Some(dependent);
+
+    while let Some(node) = current {
+        if node == level {
+            return true;
+        }
+
+        if nodes[node].children.contains_key(name) {
+            return false;
+        }
+
+        current = nodes[node].parent;
+    }
+
+    false
+}
+
+/// Packages of `node` and the nodes above it.
+fn ancestors(nodes: &[Node], node: usize) -> impl Iterator<Item = usize> + '_ {
+    std::iter::successors(Some(node), move |node| nodes[*node].parent)
+        .filter_map(move |node| nodes[node].package)
+}
+
+/// The highest of `candidates` that `range` allows, `None` without a range or when none fits.
+fn pick(packages: &[&VoltPackage], candidates: &[usize], range: Option<&str>) -> Option<usize> {
+    match range {
+        Some(range) if !range.is_empty() && range != "latest" => {
+            let fitting = candidates
+                .iter()
+                .copied()
+                .filter(|index| satisfies(&packages[*index].version, range))
+                .collect::<Vec<_>>();
+
+            highest(packages, &fitting)
+        }
+        _ if candidates.len() == 1 => Some(candidates[0]),
+        _ => None,
+    }
+}
+
+fn highest(packages: &[&VoltPackage], candidates: &[usize]) -> Option<usize> {
+    candidates.iter().copied().max_by(|a, b| {
+        match (
+            Version::parse(&packages[*a].version),
+            Version::parse(&packages[*b].version),
+        ) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => packages[*a].version.cmp(&packages[*b].version),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `name@version` depending on `dependencies`, given as `name@range`.
+    fn package(spec: &str, dependencies: &[&str]) -> VoltPackage {
+        package_with_peers(spec, dependencies, &[])
+    }
+
+    fn package_with_peers(spec: &str, dependencies: &[&str], peers: &[&str]) -> VoltPackage {
+        let (name, version) = spec.rsplit_once('@').unwrap();
+
+        let split = |specs: &[&str]| {
+            specs
+                .iter()
+                .map(|spec| spec.rsplit_once('@').unwrap())
+                .map(|(name, range)| (name.to_string(), range.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let (dependencies, peers) = (split(dependencies), split(peers));
+
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": version,
+            "tarball": "",
+            "bin": null,
+            "integrity": "",
+            "dependencies": dependencies.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            "peer_dependencies": peers.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            "ranges": dependencies.iter().chain(peers.iter()).cloned().collect::<HashMap<_, _>>(),
+        }))
+        .unwrap()
+    }
+
+    /// Every placement as `path -> name@version`, sorted by path.
+    fn layout(packages: &[VoltPackage], direct: &[&str]) -> Vec<(String, String)> {
+        let packages = packages.iter().collect::<Vec<_>>();
+
+        let direct = direct
+            .iter()
+            .map(|spec| spec.rsplit_once('@').unwrap())
+            .map(|(name, range)| (name.to_string(), range.to_string()))
+            .collect();
+
+        let mut placements = Graph::new(&packages, &direct)
+            .layout()
+            .into_iter()
+            .map(|placement| {
+                (
+                    placement.path.to_string_lossy().replace('\\', "/"),
+                    key(placement.package),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        placements.sort();
+
+        placements
+    }
+
+    /// The version `path` finds of `name`, the way node looks it up from there.
+    fn finds(layout: &[(String, String)], path: &str, name: &str) -> Option<String> {
+        let mut directory = Some(path);
+
+        loop {
+            let candidate = match directory {
+                Some(directory) => format!("{}/node_modules/{}", directory, name),
+                None => name.to_string(),
+            };
+
+            if let Some((_, package)) = layout.iter().find(|(path, _)| *path == candidate) {
+                return Some(package.clone());
+            }
+
+            directory = directory?
+                .rsplit_once("/node_modules/")
+                .map(|(parent, _)| parent);
+
+            // a top level package looks in the top of node_modules next
+            if directory.is_none() && !path.is_empty() {
+                let top = layout.iter().find(|(path, _)| *path == name)?;
+                return Some(top.1.clone());
+            }
+        }
+    }
+
+    fn entries(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(path, package)| (path.to_string(), package.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn hoists_what_doesnt_conflict() {
+        let packages = [
+            package("express@4.17.1", &["debug@^2.6.9", "send@0.17.1"]),
+            package("send@0.17.1", &["debug@^2.6.9", "ms@^2.1.1"]),
+            package("debug@2.6.9", &["ms@^2.0.0"]),
+            package("ms@2.1.3", &[]),
+        ];
+
+        assert_eq!(
+            layout(&packages, &["express@^4.17.1"]),
+            entries(&[
+                ("debug", "debug@2.6.9"),
+                ("express", "express@4.17.1"),
+                ("ms", "ms@2.1.3"),
+                ("send", "send@0.17.1"),
+            ])
+        );
+    }
+
+    #[test]
+    fn nests_conflicting_versions() {
+        let packages = [
+            package("debug@4.3.2", &["ms@^2.1.1"]),
+            package("send@0.17.1", &["debug@^2.6.9"]),
+            package("debug@2.6.9", &["ms@2.0.0"]),
+            package("ms@2.1.3", &[]),
+            package("ms@2.0.0", &[]),
+        ];
+
+        let layout = layout(&packages, &["debug@^4.3.2", "send@^0.17.1"]);
+
+        assert_eq!(
+            layout,
+            entries(&[
+                ("debug", "debug@4.3.2"),
+                ("ms", "ms@2.1.3"),
+                ("send", "send@0.17.1"),
+                ("send/node_modules/debug", "debug@2.6.9"),
+                ("send/node_modules/ms", "ms@2.0.0"),
+            ])
+        );
+
+        assert_eq!(
+            finds(&layout, "send/node_modules/debug", "ms").as_deref(),
+            Some("ms@2.0.0")
+        );
+        assert_eq!(finds(&layout, "debug", "ms").as_deref(), Some("ms@2.1.3"));
+    }
+
+    #[test]
+    fn never_shadows_what_a_package_below_found() {
+        // b@1 finds d@1 at the top through a, so d@2 for c@1 can't go into a's node_modules
+        let packages = [
+            package("a@1.0.0", &["b@^1.0.0", "c@^1.0.0"]),
+            package("b@1.0.0", &["d@^1.0.0"]),
+            package("b@2.0.0", &[]),
+            package("c@1.0.0", &["d@^2.0.0"]),
+            package("c@2.0.0", &[]),
+            package("d@1.0.0", &[]),
+            package("d@2.0.0", &[]),
+        ];
+
+        let layout = layout(&packages, &["a@^1.0.0", "b@^2.0.0", "c@^2.0.0", "d@^1.0.0"]);
+
+        assert_eq!(
+            finds(&layout, "a/node_modules/b", "d").as_deref(),
+            Some("d@1.0.0")
+        );
+        assert_eq!(
+            finds(&layout, "a/node_modules/c", "d").as_deref(),
+            Some("d@2.0.0")
+        );
+        assert!(layout.contains(&(
+            String::from("a/node_modules/c/node_modules/d"),
+            String::from("d@2.0.0")
+        )));
+    }
+
+    #[test]
+    fn every_dependent_finds_its_version() {
+        let packages = [
+            package("a@1.0.0", &["b@^1.0.0", "c@^1.0.0", "e@^1.0.0"]),
+            package("b@1.0.0", &["d@^1.0.0", "e@^2.0.0"]),
+            package("c@1.0.0", &["d@^2.0.0", "e@^1.0.0"]),
+            package("d@1.0.0", &["e@^3.0.0"]),
+            package("d@2.0.0", &["e@^1.0.0"]),
+            package("e@1.0.0", &[]),
+            package("e@2.0.0", &[]),
+            package("e@3.0.0", &[]),
+        ];
+
+        let layout = layout(&packages, &["a@^1.0.0", "e@^3.0.0"]);
+
+        for (path, package) in layout.iter() {
+            let package = packages.iter().find(|p| key(p) == *package).unwrap();
+
+            for (name, range) in package.ranges.iter().flatten() {
+                let found = finds(&layout, path, name)
+                    .unwrap_or_else(|| panic!("{} doesn't find {}", path, name));
+
+                assert!(
+                    satisfies(found.rsplit_once('@').unwrap().1, range),
+                    "{} finds {} for {}@{}",
+                    path,
+                    found,
+                    name,
+                    range
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn places_what_a_workspace_package_needs_of_its_own() {
+        let packages = [
+            package("react@17.0.2", &[]),
+            package("react@16.14.0", &[]),
+            package("legacy@1.0.0", &["react@^16.0.0"]),
+            package("debug@4.3.2", &["ms@^2.1.1"]),
+            package("debug@2.6.9", &["ms@2.0.0"]),
+            package("ms@2.1.3", &[]),
+            package("ms@2.0.0", &[]),
+        ];
+
+        let packages = packages.iter().collect::<Vec<_>>();
+
+        let ranges = |ranges: &[(&str, &str)]| {
+            ranges
+                .iter()
+                .map(|(name, range)| (name.to_string(), range.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let graph = Graph::new(
+            &packages,
+            &ranges(&[("react", "^17.0.2"), ("debug", "^4.3.2")]),
+        );
+
+        let own = |declared: &[(&str, &str)]| {
+            graph
+                .own_layout(&ranges(declared))
+                .into_iter()
+                .map(|placement| {
+                    (
+                        placement.path.to_string_lossy().replace('\\', "/"),
+                        key(placement.package),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // what the top has is found there
+        assert!(own(&[("react", "^17.0.0"), ("debug", "^4.0.0")]).is_empty());
+
+        // the other versions come with what they need of their own
+        assert_eq!(
+            own(&[
+                ("react", "^16.0.0"),
+                ("debug", "^2.6.0"),
+                ("legacy", "^1.0.0")
+            ]),
+            entries(&[
+                ("debug", "debug@2.6.9"),
+                ("ms", "ms@2.0.0"),
+                ("react", "react@16.14.0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn stops_at_cycles() {
+        // each version needs the other version of the other package, which never settles
+        let packages = [
+            package("a@1.0.0", &["b@^1.0.0"]),
+            package("a@2.0.0", &["b@^2.0.0"]),
+            package("b@1.0.0", &["a@^2.0.0"]),
+            package("b@2.0.0", &["a@^1.0.0"]),
+        ];
+
+        assert_eq!(
+            layout(&packages, &["a@^1.0.0"]),
+            entries(&[
+                ("a", "a@1.0.0"),
+                ("b", "b@1.0.0"),
+                ("b/node_modules/a", "a@2.0.0"),
+                ("b/node_modules/b", "b@2.0.0"),
+                ("b/node_modules/b/node_modules/a", "a@1.0.0"),
+            ])
+        );
+
+        // a cycle of the same versions is placed once
+        let packages = [
+            package("a@1.0.0", &["b@^1.0.0"]),
+            package("b@1.0.0", &["a@^1.0.0"]),
+        ];
+
+        assert_eq!(
+            layout(&packages, &["a@^1.0.0"]),
+            entries(&[("a", "a@1.0.0"), ("b", "b@1.0.0")])
+        );
+    }
+
+    #[test]
+    fn places_peers_next_to_their_dependents() {
+        let packages = [
+            package("react@17.0.2", &[]),
+            package("react@16.14.0", &[]),
+            package_with_peers("react-dom@17.0.2", &[], &["react@^17.0.2"]),
+            package("legacy@1.0.0", &["react@^16.0.0", "react-dom@^17.0.2"]),
+            // installed for the project's peers, nothing depends on it
+            package("typescript@4.4.3", &[]),
+        ];
+
+        let layout = layout(&packages, &["legacy@^1.0.0", "react@^17.0.2"]);
+
+        // react-dom at the top finds the react its peer range allows
+        assert_eq!(
+            finds(&layout, "react-dom", "react").as_deref(),
+            Some("react@17.0.2")
+        );
+        assert_eq!(
+            finds(&layout, "legacy", "react").as_deref(),
+            Some("react@16.14.0")
+        );
+        assert!(layout.contains(&(String::from("typescript"), String::from("typescript@4.4.3"))));
+
+        let graph_packages = packages.iter().collect::<Vec<_>>();
+        let direct = vec![(String::from("legacy"), String::from("^1.0.0"))]
+            .into_iter()
+            .collect();
+        let graph = Graph::new(&graph_packages, &direct);
+
+        let peers = graph
+            .dependencies(&packages[2])
+            .into_iter()
+            .map(|(name, package)| format!("{} {}", name, key(package)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(peers, ["react react@17.0.2"]);
+    }
+}
@@ -0,0 +1,118 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Call out changes to the dependency tree that deserve a closer look before installing.
+
+use std::io::IsTerminal;
+
+use colored::Colorize;
+use miette::Result;
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::prompt::prompts::Confirm;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::install_scripts;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::App;
+
+/// Packages that bring install scripts the lockfile hasn't seen before.
+///
+/// A package is reported when it is new to the lockfile, or when it changes version and none of
+/// its locked versions had install scripts. Already locked versions are never reported.
+pub fn new_install_scripts<'a>(
+    lock_file: &LockFile,
+    packages: &[&'a VoltPackage],
+) -> Vec<&'a VoltPackage> {
+    let mut introduced = packages
+        .iter()
+        .filter(|package| package.has_install_script)
+        .filter(|package| {
+            let mut locked = lock_file
+                .dependencies
+                .values()
+                .filter(|lock| lock.name == package.name);
+
+            locked.clone().all(|lock| lock.version != package.version)
+                && locked.all(|lock| !lock.has_install_script)
+        })
+        .copied()
+        .collect::<Vec<_>>();
+
+    introduced.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    introduced.dedup_by(|a, b| a.name == b.name && a.version == b.version);
+
+    introduced
+}
+
+/// Print the packages that introduce install scripts and, when running interactively without
+/// `--yes`, ask before going on.
+///
+/// Trusted packages aren't listed, and nothing is when scripts are ignored. Confirmed
+/// packages aren't asked about again before their scripts run.
+pub fn confirm_install_scripts(app: &App, introduced: &[&VoltPackage]) -> Result<()> {
+    let introduced = introduced
+        .iter()
+        .filter(|package| !app.config.is_trusted(&package.name))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if introduced.is_empty() || app.ignore_scripts() {
+        return Ok(());
+    }
+
+    println!(
+        "\n{} {} packages introduce install scripts:",
+        " warn ".black().bright_yellow(),
+        introduced.len().to_string().bright_yellow().bold()
+    );
+
+    for package in introduced.iter() {
+        println!(
+            "  {} {}@{}",
+            "!".bright_yellow().bold(),
+            package.name.bright_cyan(),
+            package.version
+        );
+    }
+
+    println!(
+        "  {}\n",
+        "install scripts run arbitrary code, review these packages before trusting them."
+            .bright_black()
+    );
+
+    if app.has_flag("yes") || !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    let proceed = Confirm {
+        message: String::from("Install them anyway?"),
+        default: false,
+    }
+    .run()
+    .unwrap_or(false);
+
+    if !proceed {
+        return Err(VoltError::InstallScriptsDeclined {
+            count: introduced.len(),
+        }
+        .into());
+    }
+
+    install_scripts::approve(&introduced);
+
+    Ok(())
+}
@@ -0,0 +1,338 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Run the `preinstall`, `install` and `postinstall` scripts of installed packages.
+//!
+//! Scripts run once every package is in node_modules, a package's only after the ones of
+//! everything it depends on, since builds often need their dependencies built first. They run
+//! one at a time in the directory the package was installed to, with their output captured
+//! and only shown when they fail. Packages with scripts are always copied into node_modules,
+//! whatever `link-mode` says, so what they build never ends up in the store.
+//!
+//! Nothing runs with `ignore-scripts`. Otherwise the packages listed under
+//! `trusted-dependencies` run their scripts, and the rest are listed together and asked about
+//! once before any script starts. Without a terminal to ask on, or with `--yes`, they run when
+//! there is no allowlist and are skipped when there is one. The `prepare` script of a git
+//! dependency is asked about the same way before it builds the checkout.
+
+use crate::core::prompt::prompts::Confirm;
+use crate::core::utils::app::App;
+use crate::core::utils::hoist::{self, Graph};
+use crate::core::utils::lifecycle::ScriptPlan;
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::voltapi::VoltPackage;
+
+use colored::Colorize;
+use miette::Result;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Scripts run after a dependency is installed, in order.
+pub const INSTALL_EVENTS: &[&str] = &["preinstall", "install", "postinstall"];
+
+/// Scripts of the project itself run after its dependencies are installed, in order. Its
+/// `preinstall` runs before anything is installed.
+pub const PROJECT_EVENTS: &[&str] = &["install", "postinstall", "prepare"];
+
+/// Keys of packages whose scripts were already confirmed in this run, see `approve`.
+static APPROVED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Scripts that ran for one package.
+pub struct ScriptRun<'a> {
+    pub package: &'a VoltPackage,
+    pub events: Vec<String>,
+    pub elapsed: Duration,
+    /// Whether they failed, only optional packages are allowed to.
+    pub failed: bool,
+}
+
+/// Run the install scripts of `installed`, every package with the directories it was
+/// installed to, dependencies first. A failing script stops the install unless its package
+/// is `optional`.
+pub fn run<'a>(
+    app: &App,
+    graph: &Graph,
+    installed: &[(&'a VoltPackage, Vec<PathBuf>)],
+    optional: &HashSet<String>,
+) -> Result<Vec<ScriptRun<'a>>> {
+    if app.ignore_scripts() {
+        if !installed.is_empty() {
+            info!(
+                "skipped the install scripts of {} packages, scripts are ignored",
+                installed.len()
+            );
+        }
+
+        return Ok(vec![]);
+    }
+
+    let allowed = allowed(app, installed.iter().map(|(package, _)| *package).collect());
+
+    let directories = installed
+        .iter()
+        .map(|(package, directories)| (hoist::key(package), (*package, directories)))
+        .collect::<HashMap<_, _>>();
+
+    let mut runs = vec![];
+
+    for key in order(graph, installed.iter().map(|(package, _)| *package)) {
+        let (package, directories) = match directories.get(&key) {
+            Some(entry) if allowed.contains(&key) => *entry,
+            _ => continue,
+        };
+
+        for directory in directories.iter() {
+            let manifest = match PackageJson::read(&directory.join("package.json")) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+
+            let plan = match ScriptPlan::lifecycle(
+                directory,
+                &manifest,
+                INSTALL_EVENTS,
+                app.config.script_shell.as_deref(),
+            ) {
+                Some(plan) => plan,
+                None => continue,
+            };
+
+            let start = Instant::now();
+
+            let failed = match plan.run_captured(&package.name) {
+                Ok(_) => false,
+                Err(error) if optional.contains(&package.name) => {
+                    warn!(
+                        "skipping optional dependency {}: {:?}",
+                        package.name.bright_cyan(),
+                        error
+                    );
+
+                    true
+                }
+                Err(error) => return Err(error),
+            };
+
+            runs.push(ScriptRun {
+                package,
+                events: plan.steps.iter().map(|step| step.event.clone()).collect(),
+                elapsed: start.elapsed(),
+                failed,
+            });
+        }
+    }
+
+    Ok(runs)
+}
+
+/// Run the `events` scripts of the project in `directory`, with their output shown.
+pub fn run_project(app: &App, directory: &Path, events: &[&str]) -> Result<()> {
+    if app.ignore_scripts() {
+        return Ok(());
+    }
+
+    let manifest = PackageJson::read(&directory.join("package.json"))?;
+
+    match ScriptPlan::lifecycle(
+        directory,
+        &manifest,
+        events,
+        app.config.script_shell.as_deref(),
+    ) {
+        Some(plan) => plan.run(),
+        None => Ok(()),
+    }
+}
+
+/// Let the scripts of `packages` run without asking again, once they were confirmed earlier
+/// in the run.
+pub fn approve(packages: &[&VoltPackage]) {
+    let mut approved = APPROVED.lock().unwrap();
+
+    for package in packages.iter() {
+        approved.insert(hoist::key(package));
+    }
+}
+
+/// Keys of the packages out of `packages` that may run their scripts.
+fn allowed(app: &App, mut packages: Vec<&VoltPackage>) -> HashSet<String> {
+    packages.sort_by_key(|package| hoist::key(package));
+    packages.dedup_by_key(|package| hoist::key(package));
+
+    let approved = APPROVED.lock().unwrap().clone();
+
+    let (mut allowed, untrusted): (Vec<_>, Vec<_>) = packages.into_iter().partition(|package| {
+        app.config.is_trusted(&package.name) || approved.contains(&hoist::key(package))
+    });
+
+    if untrusted.is_empty() {
+        return allowed.iter().map(|package| hoist::key(package)).collect();
+    }
+
+    let specs = untrusted
+        .iter()
+        .map(|package| (package.name.as_str(), package.version.as_str()))
+        .collect::<Vec<_>>();
+
+    if confirm(app, &specs, "install scripts") {
+        allowed.extend(untrusted);
+    }
+
+    allowed.iter().map(|package| hoist::key(package)).collect()
+}
+
+/// Whether the `prepare` script of the git dependency `name`, checked out from `resolved`,
+/// may build it. Asked about like install scripts, unless scripts are ignored.
+pub fn allow_prepare(app: &App, name: &str, resolved: &str) -> bool {
+    if app.ignore_scripts() {
+        info!(
+            "skipped the prepare script of {}, scripts are ignored",
+            name.bright_cyan()
+        );
+
+        return false;
+    }
+
+    app.config.is_trusted(name) || confirm(app, &[(name, resolved)], "prepare scripts")
+}
+
+/// Whether the `scripts` of the `untrusted` packages, `(name, version)`, may run. Without a
+/// terminal to ask on, or with `--yes`, they run unless there is an allowlist.
+fn confirm(app: &App, untrusted: &[(&str, &str)], scripts: &str) -> bool {
+    let allowlist = !app.config.trusted_dependencies.is_empty();
+
+    let run = if app.has_flag("yes") || !std::io::stdin().is_terminal() {
+        !allowlist
+    } else {
+        println!(
+            "\n{} {} packages want to run {}:",
+            " warn ".black().bright_yellow(),
+            untrusted.len().to_string().bright_yellow().bold(),
+            scripts
+        );
+
+        for (name, version) in untrusted.iter() {
+            println!(
+                "  {} {}@{}",
+                "!".bright_yellow().bold(),
+                name.bright_cyan(),
+                version
+            );
+        }
+
+        println!(
+            "  {}\n",
+            format!(
+                "{} run arbitrary code, packages listed under `trusted-dependencies` in volt.toml aren't asked about.",
+                scripts
+            )
+            .bright_black()
+        );
+
+        Confirm {
+            message: format!("Run their {}?", scripts),
+            default: false,
+        }
+        .run()
+        .unwrap_or(false)
+    };
+
+    if !run {
+        warn!(
+            "skipped the {} of {}, they may not work until they run",
+            scripts,
+            untrusted
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+                .bright_cyan()
+        );
+    }
+
+    run
+}
+
+/// Print which packages ran scripts, how long they took and which failed.
+pub fn print_summary(runs: &[ScriptRun]) {
+    if runs.is_empty() {
+        return;
+    }
+
+    info!("ran the install scripts of {} packages", runs.len());
+
+    for run in runs.iter() {
+        let (mark, status) = match run.failed {
+            true => ("!".bright_red().bold(), "failed".bright_red()),
+            false => (
+                "+".bright_green().bold(),
+                format!("{:.1}s", run.elapsed.as_secs_f64()).bright_black(),
+            ),
+        };
+
+        println!(
+            "  {} {}@{} {} {}",
+            mark,
+            run.package.name.bright_cyan(),
+            run.package.version,
+            run.events.join(", ").bright_black(),
+            status
+        );
+    }
+}
+
+/// Keys of `packages` with every package after the ones it depends on. Packages depending on
+/// each other in a cycle come in the order they're reached.
+fn order<'a>(graph: &Graph, packages: impl Iterator<Item = &'a VoltPackage>) -> Vec<String> {
+    let mut packages = packages.collect::<Vec<_>>();
+
+    packages.sort_by_key(|package| hoist::key(package));
+
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+
+    for package in packages {
+        // (package, whether its dependencies are done)
+        let mut stack = vec![(package, false)];
+
+        while let Some((package, done)) = stack.pop() {
+            let key = hoist::key(package);
+
+            if done {
+                order.push(key);
+                continue;
+            }
+
+            if !visited.insert(key) {
+                continue;
+            }
+
+            stack.push((package, true));
+
+            for (_, dependency) in graph.dependencies(package).into_iter().rev() {
+                if !visited.contains(&hoist::key(dependency)) {
+                    stack.push((dependency, false));
+                }
+            }
+        }
+    }
+
+    order
+}
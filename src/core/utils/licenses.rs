@@ -0,0 +1,203 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The licenses of the packages installed in node_modules.
+//!
+//! Every package in node_modules and the nested node_modules below it is read once, symlinks
+//! included. The license is the `license` field of package.json, or the older `licenses`. A
+//! package without either is recognized by the text of its LICENSE, LICENCE or COPYING file
+//! when it's one of the common ones, and otherwise has no known license.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::core::utils::package::License;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Files a license is looked for in, matched case-insensitively by their start.
+const LICENSE_FILES: &[&str] = &["license", "licence", "copying"];
+
+/// Phrases recognizing the text of a license, the first one matching wins.
+const LICENSE_TEXTS: &[(&str, &[&str])] = &[
+    ("AGPL-3.0", &["GNU AFFERO GENERAL PUBLIC LICENSE"]),
+    (
+        "LGPL-3.0",
+        &["GNU LESSER GENERAL PUBLIC LICENSE", "Version 3"],
+    ),
+    ("LGPL-2.1", &["GNU LESSER GENERAL PUBLIC LICENSE"]),
+    ("GPL-3.0", &["GNU GENERAL PUBLIC LICENSE", "Version 3"]),
+    ("GPL-2.0", &["GNU GENERAL PUBLIC LICENSE", "Version 2"]),
+    ("MPL-2.0", &["Mozilla Public License", "2.0"]),
+    ("Apache-2.0", &["Apache License", "Version 2.0"]),
+    ("Unlicense", &["This is free and unencumbered software"]),
+    (
+        "ISC",
+        &["Permission to use, copy, modify, and/or distribute"],
+    ),
+    ("MIT", &["Permission is hereby granted, free of charge"]),
+    (
+        "BSD-3-Clause",
+        &[
+            "Redistribution and use in source and binary forms",
+            "Neither the name",
+        ],
+    ),
+    (
+        "BSD-2-Clause",
+        &["Redistribution and use in source and binary forms"],
+    ),
+];
+
+/// Where the license of a package was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LicenseSource {
+    /// `license` or `licenses` of package.json.
+    Manifest,
+    /// Recognized from the text of a license file.
+    File,
+    /// Nowhere.
+    Unknown,
+}
+
+/// A package in node_modules and its license.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledLicense {
+    pub name: String,
+    pub version: String,
+    /// An SPDX expression, `None` when it's unknown.
+    pub license: Option<String>,
+    pub source: LicenseSource,
+}
+
+/// Every package installed in `node_modules`, once per name and version, sorted.
+pub fn installed(node_modules: &Path) -> Vec<InstalledLicense> {
+    let mut seen = HashSet::new();
+    let mut packages = BTreeMap::new();
+
+    walk(node_modules, &mut seen, &mut packages);
+
+    packages.into_values().collect()
+}
+
+fn walk(
+    node_modules: &Path,
+    seen: &mut HashSet<PathBuf>,
+    packages: &mut BTreeMap<(String, String), InstalledLicense>,
+) {
+    let entries = match std::fs::read_dir(node_modules) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // `.bin`, `.volt` and the like aren't packages
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let directories: Vec<PathBuf> = match name.starts_with('@') {
+            true => std::fs::read_dir(entry.path())
+                .map(|scoped| scoped.flatten().map(|entry| entry.path()).collect())
+                .unwrap_or_default(),
+            false => vec![entry.path()],
+        };
+
+        for directory in directories {
+            // symlinked packages can lead back up the tree
+            let canonical = std::fs::canonicalize(&directory).unwrap_or_else(|_| directory.clone());
+
+            if !seen.insert(canonical) {
+                continue;
+            }
+
+            if let Some(package) = read(&directory) {
+                packages
+                    .entry((package.name.clone(), package.version.clone()))
+                    .or_insert(package);
+            }
+
+            walk(&directory.join("node_modules"), seen, packages);
+        }
+    }
+}
+
+/// The package installed in `directory` and its license, `None` without a package.json.
+fn read(directory: &Path) -> Option<InstalledLicense> {
+    let data = std::fs::read_to_string(directory.join("package.json")).ok()?;
+    let manifest = serde_json::from_str::<Value>(&data).ok()?;
+
+    let name = manifest["name"].as_str()?.to_string();
+    let version = manifest["version"].as_str().unwrap_or_default().to_string();
+
+    let declared = [&manifest["license"], &manifest["licenses"]]
+        .iter()
+        .filter(|field| !field.is_null())
+        .find_map(|field| {
+            serde_json::from_value::<License>((*field).clone())
+                .ok()?
+                .expression()
+        })
+        .filter(|license| !license.trim().is_empty());
+
+    let (license, source) = match declared {
+        Some(license) => (Some(license), LicenseSource::Manifest),
+        None => match from_file(directory) {
+            Some(license) => (Some(license), LicenseSource::File),
+            None => (None, LicenseSource::Unknown),
+        },
+    };
+
+    Some(InstalledLicense {
+        name,
+        version,
+        license,
+        source,
+    })
+}
+
+/// The license a LICENSE file in `directory` holds the text of.
+fn from_file(directory: &Path) -> Option<String> {
+    let file = std::fs::read_dir(directory).ok()?.flatten().find(|entry| {
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+
+        entry.path().is_file() && LICENSE_FILES.iter().any(|prefix| name.starts_with(prefix))
+    })?;
+
+    let text = std::fs::read_to_string(file.path()).ok()?;
+
+    LICENSE_TEXTS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|phrase| text.contains(phrase)))
+        .map(|(license, _)| license.to_string())
+}
+
+/// Whether `license` only allows use under licenses in `denied`. An expression with `OR`
+/// leaves a choice, so it's only denied when every alternative names a denied license.
+pub fn is_denied(license: &str, denied: &[String]) -> bool {
+    license
+        .split(" OR ")
+        .map(|alternative| alternative.trim_matches(['(', ')', ' ']))
+        .all(|alternative| {
+            alternative
+                .split([' ', '(', ')'])
+                .any(|id| denied.iter().any(|denied| denied.eq_ignore_ascii_case(id)))
+        })
+}
@@ -0,0 +1,517 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Work out how package.json scripts run: hooks, shell, PATH and environment.
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::pnp;
+use crate::core::VERSION;
+
+use colored::Colorize;
+use miette::Result;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// A single script to run, e.g. `prebuild`.
+#[derive(Debug, Clone)]
+pub struct ScriptStep {
+    pub event: String,
+    pub command: String,
+}
+
+/// Everything needed to run a script together with its `pre` and `post` hooks.
+#[derive(Debug, Clone)]
+pub struct ScriptPlan {
+    /// Shell executable and the flags that make it run a command string.
+    pub shell: (String, Vec<String>),
+    /// Directory the scripts run in.
+    pub directory: PathBuf,
+    /// Steps in the order they run.
+    pub steps: Vec<ScriptStep>,
+    /// Directories prepended to PATH, closest first.
+    pub path_additions: Vec<PathBuf>,
+    /// Variables set on top of the inherited environment, except `npm_lifecycle_*`
+    /// which differ per step.
+    pub env: BTreeMap<String, String>,
+}
+
+/// The shell scripts run with, `script_shell` overrides the platform default.
+pub fn script_shell(script_shell: Option<&str>) -> (String, Vec<String>) {
+    match script_shell {
+        Some(shell) => {
+            let flags = if is_cmd(shell) {
+                vec!["/d", "/s", "/c"]
+            } else {
+                vec!["-c"]
+            };
+
+            (
+                shell.to_string(),
+                flags.into_iter().map(String::from).collect(),
+            )
+        }
+        None if cfg!(target_os = "windows") => (
+            std::env::var("ComSpec").unwrap_or_else(|_| String::from("cmd.exe")),
+            vec![String::from("/d"), String::from("/s"), String::from("/c")],
+        ),
+        None => (String::from("sh"), vec![String::from("-c")]),
+    }
+}
+
+fn is_cmd(shell: &str) -> bool {
+    Path::new(shell)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().eq_ignore_ascii_case("cmd"))
+        .unwrap_or(false)
+}
+
+/// Quote an argument passed after `--` so the shell sees it as a single word.
+fn quote_argument(argument: &str, cmd: bool) -> String {
+    let plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@,+%".contains(c));
+
+    if plain {
+        argument.to_string()
+    } else if cmd {
+        format!("\"{}\"", argument.replace('"', "\"\""))
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+impl ScriptPlan {
+    /// Plan running the script `name` of the package in `directory`.
+    ///
+    /// `arguments` are appended to the main script only, the same way npm passes
+    /// `npm run <name> -- <arguments>` on.
+    pub fn new(
+        directory: &Path,
+        package: &PackageJson,
+        name: &str,
+        arguments: &[String],
+        script_shell: Option<&str>,
+    ) -> Result<ScriptPlan> {
+        let command = package
+            .scripts
+            .get(name)
+            .ok_or_else(|| VoltError::MissingScript {
+                name: name.to_string(),
+            })?;
+
+        let cmd = is_cmd(&self::script_shell(script_shell).0);
+
+        let mut main = command.clone();
+
+        for argument in arguments {
+            main.push(' ');
+            main.push_str(&quote_argument(argument, cmd));
+        }
+
+        let mut steps = vec![];
+
+        if let Some(command) = package.scripts.get(&format!("pre{}", name)) {
+            steps.push(ScriptStep {
+                event: format!("pre{}", name),
+                command: command.clone(),
+            });
+        }
+
+        steps.push(ScriptStep {
+            event: name.to_string(),
+            command: main,
+        });
+
+        if let Some(command) = package.scripts.get(&format!("post{}", name)) {
+            steps.push(ScriptStep {
+                event: format!("post{}", name),
+                command: command.clone(),
+            });
+        }
+
+        Ok(ScriptPlan::with_steps(
+            directory,
+            package,
+            steps,
+            script_shell,
+        ))
+    }
+
+    /// Plan running the lifecycle `events` the package in `directory` has scripts for, in
+    /// order, e.g. `preinstall`, `install` and `postinstall` once it's installed. `None` when
+    /// it has none of them.
+    ///
+    /// A package with a `binding.gyp` and neither an `install` nor a `preinstall` script is
+    /// built with `node-gyp rebuild` on `install`, like npm does.
+    pub fn lifecycle(
+        directory: &Path,
+        package: &PackageJson,
+        events: &[&str],
+        script_shell: Option<&str>,
+    ) -> Option<ScriptPlan> {
+        let gyp = !package.scripts.contains_key("install")
+            && !package.scripts.contains_key("preinstall")
+            && directory.join("binding.gyp").is_file();
+
+        let steps = events
+            .iter()
+            .filter_map(|event| {
+                let command = match package.scripts.get(*event) {
+                    Some(command) => command.clone(),
+                    None if *event == "install" && gyp => String::from("node-gyp rebuild"),
+                    None => return None,
+                };
+
+                Some(ScriptStep {
+                    event: event.to_string(),
+                    command,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if steps.is_empty() {
+            return None;
+        }
+
+        Some(ScriptPlan::with_steps(
+            directory,
+            package,
+            steps,
+            script_shell,
+        ))
+    }
+
+    fn with_steps(
+        directory: &Path,
+        package: &PackageJson,
+        steps: Vec<ScriptStep>,
+        script_shell: Option<&str>,
+    ) -> ScriptPlan {
+        // every node_modules/.bin up to the root, like npm
+        let mut path_additions = directory
+            .ancestors()
+            .map(|ancestor| ancestor.join("node_modules").join(".bin"))
+            .collect::<Vec<PathBuf>>();
+
+        let mut env = BTreeMap::new();
+
+        // Plug'n'Play projects have no node_modules, node finds packages through the runtime
+        if let Some(runtime) = pnp::runtime_for(directory) {
+            path_additions.insert(0, pnp::bin_dir(runtime.parent().unwrap()));
+
+            let inherited = std::env::var("NODE_OPTIONS").unwrap_or_default();
+
+            env.insert(
+                String::from("NODE_OPTIONS"),
+                format!("--require \"{}\" {}", runtime.display(), inherited)
+                    .trim_end()
+                    .to_string(),
+            );
+        }
+
+        env.insert(String::from("npm_package_name"), package.name.clone());
+        env.insert(String::from("npm_package_version"), package.version.clone());
+        env.insert(
+            String::from("npm_package_json"),
+            directory.join("package.json").display().to_string(),
+        );
+        env.insert(
+            String::from("npm_config_user_agent"),
+            format!(
+                "volt/{} {} {}",
+                VERSION,
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+        );
+        env.insert(
+            String::from("INIT_CWD"),
+            std::env::current_dir()
+                .unwrap_or_else(|_| directory.to_path_buf())
+                .display()
+                .to_string(),
+        );
+
+        if let Ok(executable) = std::env::current_exe() {
+            env.insert(
+                String::from("npm_execpath"),
+                executable.display().to_string(),
+            );
+        }
+
+        ScriptPlan {
+            shell: self::script_shell(script_shell),
+            directory: directory.to_path_buf(),
+            steps,
+            path_additions,
+            env,
+        }
+    }
+
+    /// PATH with the plan's additions in front of the inherited one.
+    pub fn path(&self) -> OsString {
+        let inherited = std::env::var_os("PATH").unwrap_or_default();
+
+        let paths = self
+            .path_additions
+            .iter()
+            .cloned()
+            .chain(std::env::split_paths(&inherited));
+
+        std::env::join_paths(paths).unwrap_or(inherited)
+    }
+
+    /// Environment a step runs with, on top of the inherited one.
+    pub fn step_env(&self, step: &ScriptStep) -> BTreeMap<String, String> {
+        let mut env = self.env.clone();
+
+        env.insert(String::from("npm_lifecycle_event"), step.event.clone());
+        env.insert(String::from("npm_lifecycle_script"), step.command.clone());
+        env.insert(
+            String::from("PATH"),
+            self.path().to_string_lossy().to_string(),
+        );
+
+        env
+    }
+
+    /// Expand the environment variables in `command` the way the shell will.
+    ///
+    /// Only used for previews, the shell does the real expansion when the step runs.
+    pub fn expand(&self, step: &ScriptStep) -> String {
+        let env = self.step_env(step);
+
+        let lookup = |name: &str| {
+            env.get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+                .unwrap_or_default()
+        };
+
+        if is_cmd(&self.shell.0) {
+            expand_cmd(&step.command, lookup)
+        } else {
+            expand_posix(&step.command, lookup)
+        }
+    }
+
+    /// Print every step with its expansion, without running anything.
+    pub fn print(&self) {
+        println!(
+            "{} {} {}",
+            "shell".bright_cyan().bold(),
+            ":".bright_magenta().bold(),
+            format!("{} {}", self.shell.0, self.shell.1.join(" ")).trim_end()
+        );
+
+        println!(
+            "{} {} {}",
+            "cwd".bright_cyan().bold(),
+            ":".bright_magenta().bold(),
+            self.directory.display()
+        );
+
+        println!(
+            "{} {}",
+            "path".bright_cyan().bold(),
+            ":".bright_magenta().bold()
+        );
+
+        for path in self.path_additions.iter() {
+            println!("  + {}", path.display());
+        }
+
+        println!(
+            "{} {}",
+            "env".bright_cyan().bold(),
+            ":".bright_magenta().bold()
+        );
+
+        for (key, value) in self.env.iter() {
+            println!("  {}={}", key, value);
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            println!();
+            println!(
+                "{} {}",
+                format!("{}.", index + 1).bright_magenta().bold(),
+                step.event.bright_yellow().bold()
+            );
+            println!("  {} {}", "script  ".bright_black(), step.command);
+            println!("  {} {}", "expanded".bright_black(), self.expand(step));
+        }
+    }
+
+    /// Run every step in order, stopping at the first one that fails.
+    pub fn run(&self) -> Result<()> {
+        for step in self.steps.iter() {
+            println!(
+                "{} {}",
+                ">".bright_magenta().bold(),
+                format!("{} {}", step.event, step.command).bright_black()
+            );
+
+            let status = std::process::Command::new(&self.shell.0)
+                .args(&self.shell.1)
+                .arg(&step.command)
+                .current_dir(&self.directory)
+                .envs(self.step_env(step))
+                .status()
+                .map_err(|e| VoltError::ScriptSpawnError {
+                    shell: self.shell.0.clone(),
+                    source: e,
+                })?;
+
+            if !status.success() {
+                return Err(VoltError::ScriptFailed {
+                    event: step.event.clone(),
+                    code: status
+                        .code()
+                        .map(|code| format!("code {}", code))
+                        .unwrap_or_else(|| String::from("a signal")),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run every step in order with their output captured, stopping at the first one that
+    /// fails. `name` is the package the scripts belong to, failures carry what they printed.
+    /// ## Returns
+    /// * `Result<String>` - what the steps printed
+    pub fn run_captured(&self, name: &str) -> Result<String> {
+        let mut printed = String::new();
+
+        for step in self.steps.iter() {
+            let output = std::process::Command::new(&self.shell.0)
+                .args(&self.shell.1)
+                .arg(&step.command)
+                .current_dir(&self.directory)
+                .envs(self.step_env(step))
+                .stdin(std::process::Stdio::null())
+                .output()
+                .map_err(|e| VoltError::ScriptSpawnError {
+                    shell: self.shell.0.clone(),
+                    source: e,
+                })?;
+
+            printed.push_str(&String::from_utf8_lossy(&output.stdout));
+            printed.push_str(&String::from_utf8_lossy(&output.stderr));
+
+            if !output.status.success() {
+                return Err(VoltError::InstallScriptFailed {
+                    name: name.to_string(),
+                    event: step.event.clone(),
+                    code: output
+                        .status
+                        .code()
+                        .map(|code| format!("code {}", code))
+                        .unwrap_or_else(|| String::from("a signal")),
+                    output: printed
+                        .lines()
+                        .map(|line| format!("\n  | {}", line))
+                        .collect(),
+                })?;
+            }
+        }
+
+        Ok(printed)
+    }
+}
+
+/// Expand `$NAME` and `${NAME}` outside of single quotes.
+fn expand_posix(command: &str, lookup: impl Fn(&str) -> String) -> String {
+    let mut expanded = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+    let mut single_quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                single_quoted = !single_quoted;
+                expanded.push(c);
+            }
+            '$' if !single_quoted => {
+                let braced = chars.peek() == Some(&'{');
+
+                if braced {
+                    chars.next();
+                }
+
+                let mut name = String::new();
+
+                while let Some(&next) = chars.peek() {
+                    if next.is_ascii_alphanumeric() || next == '_' {
+                        name.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                if braced && chars.peek() == Some(&'}') {
+                    chars.next();
+                }
+
+                if name.is_empty() {
+                    expanded.push('$');
+                } else {
+                    expanded.push_str(&lookup(&name));
+                }
+            }
+            _ => expanded.push(c),
+        }
+    }
+
+    expanded
+}
+
+/// Expand `%NAME%`, leaving unknown variables untouched like `cmd.exe` does.
+fn expand_cmd(command: &str, lookup: impl Fn(&str) -> String) -> String {
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+
+        let after = &rest[start + 1..];
+
+        match after.find('%') {
+            Some(end) if end > 0 => {
+                let name = &after[..end];
+                let value = lookup(name);
+
+                if value.is_empty() {
+                    expanded.push_str(&rest[start..start + end + 2]);
+                } else {
+                    expanded.push_str(&value);
+                }
+
+                rest = &after[end + 1..];
+            }
+            _ => {
+                expanded.push('%');
+                rest = after;
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+
+    expanded
+}
@@ -0,0 +1,439 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Lay out packages from the store into node_modules.
+//!
+//! Packages are extracted once into the store and then linked into every project that uses
+//! them. By default the store is content addressed, a package lives in
+//! `<store dir>/store/<integrity>` so every project with the same tarball shares one copy. Where the store lives, how its directories are named and how a package ends up in
+//! node_modules are all configurable through `volt.toml`, together with shell hooks that run
+//! around every package that gets linked.
+//!
+//! Hard links only work within a filesystem, so the first install of a project probes whether
+//! the store can be hard linked from its node_modules and records the answer in `.volt/state`.
+
+use crate::core::model::config::LinkMode;
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::fs_utils::{copy_tree, reflink, FileMode};
+use crate::core::utils::lifecycle::script_shell;
+use crate::core::utils::voltapi::VoltPackage;
+
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Directory layout used when `store-layout` isn't set.
+pub const DEFAULT_STORE_LAYOUT: &str = "store/{integrity}";
+
+/// Layout of packages without an integrity, which can't be content addressed.
+const FALLBACK_STORE_LAYOUT: &str = "{name}-{version}";
+
+/// Puts a package that was extracted into the store into node_modules.
+pub trait Linker: Send + Sync {
+    /// Make the package stored in `source` available at `destination`.
+    ///
+    /// `destination` never exists when this is called, its parent always does.
+    fn link(&self, source: &Path, destination: &Path) -> std::io::Result<()>;
+}
+
+/// Gives every project its own copy of the package, the store is only read.
+pub struct CopyLinker;
+
+impl Linker for CopyLinker {
+    fn link(&self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        copy_tree(source, destination, FileMode::Copy, None).map(|_| ())
+    }
+}
+
+/// Hard links every file to the store's copy, so the files take no extra space.
+pub struct HardlinkLinker;
+
+impl Linker for HardlinkLinker {
+    fn link(&self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        copy_tree(source, destination, FileMode::Hardlink, None).map(|_| ())
+    }
+}
+
+/// Clones every file copy-on-write, copying the ones the filesystem can't clone.
+pub struct ReflinkLinker;
+
+impl Linker for ReflinkLinker {
+    fn link(&self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        copy_tree(source, destination, FileMode::Reflink, None).map(|_| ())
+    }
+}
+
+/// Points node_modules at the store, e.g. a read-only store shared over NFS.
+pub struct SymlinkLinker;
+
+impl Linker for SymlinkLinker {
+    fn link(&self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(source, destination);
+
+        #[cfg(windows)]
+        return std::os::windows::fs::symlink_dir(source, destination);
+    }
+}
+
+/// The linker for `mode`.
+pub fn linker(mode: LinkMode) -> Box<dyn Linker> {
+    match mode {
+        LinkMode::Hardlink => Box::new(HardlinkLinker),
+        LinkMode::Reflink => Box::new(ReflinkLinker),
+        LinkMode::Copy => Box::new(CopyLinker),
+        LinkMode::Symlink => Box::new(SymlinkLinker),
+    }
+}
+
+/// Link mode used for the current project, decided once per run.
+static LINK_MODE: OnceLock<LinkMode> = OnceLock::new();
+
+/// What volt knows about the project in `.volt/state`: how linking was probed, so the store
+/// and node_modules are only probed again when the store moves, how far an unfinished
+/// install got and which entries of the shared virtual store it uses.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ProjectState {
+    /// Store the link mode was probed against, `None` when it never was.
+    pub store_dir: Option<PathBuf>,
+    pub link_mode: LinkMode,
+    /// Why hard links weren't used, `None` when they are.
+    pub fallback_reason: Option<String>,
+    /// `name@version` of the packages an install that didn't finish fully installed.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub installed: BTreeSet<String>,
+    /// Entries of the shared virtual store node_modules links to.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub virtual_entries: BTreeSet<String>,
+}
+
+impl ProjectState {
+    pub fn path(app: &App) -> PathBuf {
+        ProjectState::path_in(&app.current_dir)
+    }
+
+    fn path_in(project: &Path) -> PathBuf {
+        project.join(".volt").join("state")
+    }
+
+    /// The recorded state, `None` when there's none or it's unreadable.
+    pub fn load(app: &App) -> Option<ProjectState> {
+        ProjectState::load_in(&app.current_dir)
+    }
+
+    /// The recorded state of the project in `project`.
+    pub fn load_in(project: &Path) -> Option<ProjectState> {
+        let data = std::fs::read_to_string(ProjectState::path_in(project)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Record the state, failing silently since everything in it is found out again next time.
+    ///
+    /// The file is replaced in one step, an interrupted save leaves the previous state behind.
+    pub fn save(&self, app: &App) {
+        let path = ProjectState::path(app);
+        let partial = path.with_extension("partial");
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            if std::fs::write(&partial, data).is_ok() {
+                std::fs::rename(&partial, &path).ok();
+            }
+        }
+    }
+
+    /// Advice on getting hard links back, shown by `volt check`.
+    pub fn hint(&self) -> Option<String> {
+        self.fallback_reason.as_ref().map(|reason| {
+            format!(
+                "{}, so packages are {} instead of hard linked. Move the store onto the same \
+                 filesystem as the project, or set `store-dir` in volt.toml to a store on this device.",
+                reason,
+                match self.link_mode {
+                    LinkMode::Reflink => "cloned",
+                    _ => "copied",
+                }
+            )
+        })
+    }
+}
+
+/// Link mode for the project: `link-mode`, unless hard links into the store are impossible.
+///
+/// Hard links can't cross filesystems, when the store is on another device the project falls
+/// back to reflinks where the filesystem supports them and to plain copies everywhere else.
+pub fn link_mode(app: &App) -> LinkMode {
+    *LINK_MODE.get_or_init(|| {
+        if app.config.link_mode != LinkMode::Hardlink {
+            return app.config.link_mode;
+        }
+
+        let store_dir = app.store_dir();
+
+        let previous = ProjectState::load(app).unwrap_or_default();
+
+        if previous.store_dir.as_ref() == Some(&store_dir) {
+            return previous.link_mode;
+        }
+
+        let state = ProjectState {
+            // the progress of an interrupted install outlives a moved store
+            installed: previous.installed,
+            virtual_entries: previous.virtual_entries,
+            ..probe_link_mode(&store_dir, &app.node_modules_dir)
+        };
+
+        if let Some(reason) = &state.fallback_reason {
+            warn!(
+                "{}, falling back to {}",
+                reason,
+                match state.link_mode {
+                    LinkMode::Reflink => "reflinks",
+                    _ => "copies",
+                }
+            );
+        }
+
+        state.save(app);
+
+        state.link_mode
+    })
+}
+
+/// Try hard linking, then reflinking, a file from the store into node_modules.
+fn probe_link_mode(store_dir: &Path, node_modules: &Path) -> ProjectState {
+    let state = |link_mode, fallback_reason: Option<&str>| ProjectState {
+        store_dir: Some(store_dir.to_path_buf()),
+        link_mode,
+        fallback_reason: fallback_reason.map(String::from),
+        installed: BTreeSet::new(),
+        virtual_entries: BTreeSet::new(),
+    };
+
+    let name = format!(".volt-probe-{}", std::process::id());
+    let source = store_dir.join(&name);
+    let target = node_modules.join(&name);
+
+    let prepared = std::fs::create_dir_all(store_dir)
+        .and_then(|_| std::fs::create_dir_all(node_modules))
+        .and_then(|_| std::fs::write(&source, b"volt"));
+
+    if prepared.is_err() {
+        // nothing to decide from, linking reports the real problem
+        return state(LinkMode::Hardlink, None);
+    }
+
+    let probed = match std::fs::hard_link(&source, &target) {
+        Ok(_) => state(LinkMode::Hardlink, None),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            let reason = "the store is on a different filesystem than node_modules";
+
+            std::fs::remove_file(&target).ok();
+
+            if reflink(&source, &target).is_ok() {
+                state(LinkMode::Reflink, Some(reason))
+            } else {
+                state(LinkMode::Copy, Some(reason))
+            }
+        }
+        Err(_) => state(
+            LinkMode::Copy,
+            Some("the filesystem doesn't support hard links into the store"),
+        ),
+    };
+
+    std::fs::remove_file(&target).ok();
+    std::fs::remove_file(&source).ok();
+
+    probed
+}
+
+/// Directory `package` is extracted into, `store-layout` relative to the store.
+///
+/// `{name}`, `{version}` and `{integrity}` are replaced, a scoped name keeps its `@scope/`
+/// directory. Packages without an integrity use `{name}-{version}` instead.
+pub fn store_path(app: &App, package: &VoltPackage) -> PathBuf {
+    stored_version_path(app, &package.name, &package.version, &package.integrity)
+}
+
+/// Directory `name@version` with `integrity` is extracted into, see [`store_path()`].
+pub fn stored_version_path(app: &App, name: &str, version: &str, integrity: &str) -> PathBuf {
+    let mut layout = app
+        .config
+        .store_layout
+        .as_deref()
+        .unwrap_or(DEFAULT_STORE_LAYOUT);
+
+    if integrity.is_empty() && layout.contains("{integrity}") {
+        layout = FALLBACK_STORE_LAYOUT;
+    }
+
+    // integrities in base64 may hold a `/`, which would nest directories
+    let integrity = integrity.replace('/', "_");
+
+    app.store_dir().join(
+        layout
+            .replace("{name}", name)
+            .replace("{version}", version)
+            .replace("{integrity}", &integrity),
+    )
+}
+
+/// Remove whatever is at `path`, following neither symlinks nor junctions.
+pub fn remove_path(path: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path).or_else(|_| std::fs::remove_dir(path))
+    }
+}
+
+/// Run a `before-link`, `after-link` or `after-unlink` hook for `package`.
+///
+/// The hook gets `VOLT_PACKAGE_NAME`, `VOLT_PACKAGE_VERSION`, `VOLT_STORE_PATH` and
+/// `VOLT_LINK_PATH` in its environment and runs in the project directory.
+fn run_hook(
+    app: &App,
+    hook: &str,
+    command: Option<&String>,
+    package: &VoltPackage,
+    source: &Path,
+    destination: &Path,
+) -> Result<()> {
+    let command = match command {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+
+    let (shell, flags) = script_shell(app.config.script_shell.as_deref());
+
+    let status = std::process::Command::new(&shell)
+        .args(&flags)
+        .arg(command)
+        .current_dir(&app.current_dir)
+        .env("VOLT_PACKAGE_NAME", &package.name)
+        .env("VOLT_PACKAGE_VERSION", &package.version)
+        .env("VOLT_STORE_PATH", source)
+        .env("VOLT_LINK_PATH", destination)
+        .status()
+        .map_err(|e| VoltError::ScriptSpawnError { shell, source: e })?;
+
+    if !status.success() {
+        return Err(VoltError::LinkHookFailed {
+            hook: hook.to_string(),
+            name: package.name.clone(),
+            code: status
+                .code()
+                .map(|code| format!("code {}", code))
+                .unwrap_or_else(|| String::from("a signal")),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Link the stored copy of `package` to `destination` in node_modules, replacing the one
+/// installed before. A package other packages get nested in is never a symlink into the
+/// store, they would end up in the store. Neither is a package with install scripts, or
+/// hard linked, what they build would.
+pub fn link_package(
+    app: &App,
+    package: &VoltPackage,
+    destination: &Path,
+    has_nested: bool,
+) -> Result<()> {
+    let source = store_path(app, package);
+
+    let mode = match link_mode(app) {
+        LinkMode::Hardlink | LinkMode::Symlink if package.has_install_script => LinkMode::Copy,
+        LinkMode::Symlink if has_nested => LinkMode::Copy,
+        mode => mode,
+    };
+
+    place(app, package, &source, destination, &*linker(mode))
+}
+
+/// Symlink the shared virtual store entry of `package` at `source` to `destination`.
+pub fn link_virtual(
+    app: &App,
+    package: &VoltPackage,
+    source: &Path,
+    destination: &Path,
+) -> Result<()> {
+    place(app, package, source, destination, &SymlinkLinker)
+}
+
+/// Put `source` at `destination` in node_modules with `linker`, running the hooks.
+fn place(
+    app: &App,
+    package: &VoltPackage,
+    source: &Path,
+    destination: &Path,
+    linker: &dyn Linker,
+) -> Result<()> {
+    let hooks = &app.config.hooks;
+
+    run_hook(
+        app,
+        "before-link",
+        hooks.before_link.as_ref(),
+        package,
+        source,
+        destination,
+    )?;
+
+    if std::fs::symlink_metadata(destination).is_ok() {
+        remove_path(destination).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: destination.display().to_string(),
+        })?;
+
+        run_hook(
+            app,
+            "after-unlink",
+            hooks.after_unlink.as_ref(),
+            package,
+            source,
+            destination,
+        )?;
+    }
+
+    std::fs::create_dir_all(destination.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+    linker
+        .link(source, destination)
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: destination.display().to_string(),
+        })?;
+
+    run_hook(
+        app,
+        "after-link",
+        hooks.after_link.as_ref(),
+        package,
+        source,
+        destination,
+    )
+}
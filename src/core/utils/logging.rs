@@ -0,0 +1,241 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! What volt logs while it works, through `tracing`.
+//!
+//! Messages are logged with `error!`, `warn!`, `info!`, `debug!` and `trace!` and printed with
+//! the prefix of their level. Errors, warnings and info are printed by default, `--quiet` keeps
+//! only errors and warnings, `--verbose` adds debug messages and `-vv` trace messages too.
+//! `VOLT_LOG` takes a filter like `RUST_LOG` does and beats both, e.g.
+//! `VOLT_LOG=volt::core::model=trace` traces only the registry client.
+//!
+//! While progress bars are drawn messages are printed above them, and with `--json` warnings
+//! also go into the document.
+//!
+//! Every message printed is also appended to `<volt dir>/volt.log` with the time it was
+//! logged, `volt bug-report` attaches the end of it. Once the log grows past `LOG_MAX_SIZE` it
+//! moves to `volt.log.old`, replacing the one before.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::core::utils::output;
+
+use chrono::Local;
+use colored::Colorize;
+use dirs::home_dir;
+use indicatif::ProgressBar;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable with a filter replacing the level of the flags.
+const FILTER_VAR: &str = "VOLT_LOG";
+
+/// Size the log can grow to before it's rotated.
+const LOG_MAX_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Bar the messages are printed above while progress is drawn.
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Start logging, at the level `--quiet` and every `--verbose` given ask for.
+pub fn init(quiet: bool, verbose: u64) {
+    let level = match (quiet, verbose) {
+        (true, _) => Level::WARN,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+
+    // only volt's own messages, dependencies log a lot at debug
+    let directive = format!("volt={}", level.to_string().to_lowercase());
+
+    let filter = EnvFilter::try_from_env(FILTER_VAR).unwrap_or_else(|_| EnvFilter::new(directive));
+
+    rotate();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(LogWriter)
+        .event_format(LogFormat)
+        .init();
+}
+
+/// Print messages above `bar` until `detach`, drawing them around it would garble both.
+pub fn attach(bar: &ProgressBar) {
+    *PROGRESS.lock().unwrap() = Some(bar.clone());
+}
+
+pub fn detach() {
+    PROGRESS.lock().unwrap().take();
+}
+
+/// The log every message is appended to.
+pub fn log_path() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".volt").join("volt.log"))
+}
+
+/// Move the log to `volt.log.old` once it's too large.
+fn rotate() {
+    let path = match log_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let size = std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+
+    if size > LOG_MAX_SIZE {
+        std::fs::rename(&path, path.with_extension("log.old")).ok();
+    }
+}
+
+/// Append `message` to the log without its colors, losing a line beats failing the command.
+fn persist(message: &str) {
+    let path = match log_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let file = OpenOptions::new().create(true).append(true).open(path);
+
+    if let Ok(mut file) = file {
+        let time = Local::now().format("%Y-%m-%dT%H:%M:%S%.3f");
+        let plain = console::strip_ansi_codes(message);
+
+        for line in plain.lines() {
+            writeln!(file, "{} {}", time, line).ok();
+        }
+    }
+}
+
+/// `info: message`, with the module for debug and trace messages.
+struct LogFormat;
+
+impl<S, N> FormatEvent<S, N> for LogFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut message = Message::default();
+        event.record(&mut message);
+        let message = message.0;
+
+        let metadata = event.metadata();
+
+        match *metadata.level() {
+            Level::ERROR => write!(writer, "{}: ", " error ".black().bright_red())?,
+            Level::WARN => {
+                output::record_warning(&message);
+
+                write!(writer, "{}: ", " warn ".black().bright_yellow())?
+            }
+            Level::INFO => write!(writer, "{}: ", " info ".black().bright_blue())?,
+            level => write!(
+                writer,
+                "{} {}: ",
+                level.to_string().to_lowercase().bright_black(),
+                metadata
+                    .target()
+                    .trim_start_matches("volt::")
+                    .bright_black()
+            )?,
+        }
+
+        writeln!(writer, "{}", message)
+    }
+}
+
+/// The text of an event, `message` first and the other fields after it as `name=value`.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.0.insert_str(0, value),
+            name => self.0.push_str(&format!(" {}={}", name, value)),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.0.insert_str(0, &format!("{:?}", value)),
+            name => self.0.push_str(&format!(" {}={:?}", name, value)),
+        }
+    }
+}
+
+/// Hands out a `LineWriter` for every message.
+struct LogWriter;
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LineWriter;
+
+    fn make_writer(&'a self) -> LineWriter {
+        LineWriter { buffer: vec![] }
+    }
+}
+
+/// Collects a message and prints it once complete, above the progress bars if any.
+struct LineWriter {
+    buffer: Vec<u8>,
+}
+
+impl Write for LineWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let message = String::from_utf8_lossy(&self.buffer);
+
+        persist(&message);
+
+        match PROGRESS.lock().unwrap().as_ref() {
+            Some(bar) => bar.println(message.trim_end()),
+            None => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+
+                stdout.write_all(message.as_bytes()).ok();
+                stdout.flush().ok();
+            }
+        }
+    }
+}
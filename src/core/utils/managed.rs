@@ -0,0 +1,203 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Keep track of the paths volt created, so linking never replaces files it doesn't own.
+//!
+//! Every package directory, bin script and global shim volt writes is recorded in
+//! `.volt/managed` next to the node_modules it belongs to. Before linking, the paths a package
+//! would be written to are checked against it, and anything else in the way stops the install
+//! unless `--force-overwrite` is passed.
+
+use crate::core::utils::app::App;
+use crate::core::utils::bins;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::file_lock::FileLock;
+use crate::core::utils::voltapi::VoltPackage;
+
+use miette::Result;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Paths volt created for one node_modules.
+pub struct ManagedPaths {
+    path: PathBuf,
+    /// Paths are recorded relative to this directory, the parent of node_modules.
+    root: PathBuf,
+    paths: BTreeSet<PathBuf>,
+    /// Whether anything was recorded before, bins of earlier installs are adopted when not.
+    recorded: bool,
+}
+
+impl ManagedPaths {
+    /// The paths recorded for `app.node_modules_dir`.
+    pub fn load(app: &App) -> ManagedPaths {
+        let root = app
+            .node_modules_dir
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| app.current_dir.clone());
+
+        let path = root.join(".volt").join("managed");
+
+        let data = std::fs::read_to_string(&path).ok();
+
+        ManagedPaths {
+            paths: data
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            recorded: data.is_some(),
+            path,
+            root,
+        }
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// Record that volt wrote `path`.
+    pub fn insert(&mut self, path: &Path) {
+        self.paths.insert(self.relative(path).to_path_buf());
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.contains(self.relative(path))
+    }
+
+    /// Paths in `targets` that exist but weren't written by volt.
+    ///
+    /// A package directory holding a package of the same name is an earlier install of it,
+    /// e.g. by npm, and is replaced like any other version.
+    pub fn conflicts(&self, targets: &[(PathBuf, &VoltPackage)]) -> Vec<PathBuf> {
+        let mut conflicts = targets
+            .iter()
+            .filter(|(path, _)| std::fs::symlink_metadata(path).is_ok() && !self.contains(path))
+            .filter(|(path, package)| {
+                if *path == self.root.join("node_modules").join(package.install_name()) {
+                    manifest_name(path).as_deref() != Some(package.name.as_str())
+                } else {
+                    self.recorded
+                }
+            })
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        conflicts.sort();
+        conflicts.dedup();
+
+        conflicts
+    }
+
+    /// Fail with the paths in `targets` volt would overwrite without owning them, unless
+    /// `--force-overwrite` was passed.
+    pub fn check(&self, app: &App, targets: &[(PathBuf, &VoltPackage)]) -> Result<()> {
+        if app.has_flag("force-overwrite") {
+            return Ok(());
+        }
+
+        let conflicts = self.conflicts(targets);
+
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+
+        Err(VoltError::WouldOverwrite {
+            count: conflicts.len(),
+            paths: conflicts
+                .iter()
+                .map(|path| format!("\n  {}", path.display()))
+                .collect(),
+        }
+        .into())
+    }
+
+    /// Write the recorded paths, failing silently like the rest of `.volt`.
+    ///
+    /// Paths recorded by other processes in the meantime, e.g. global installs running side
+    /// by side, are kept.
+    pub async fn save(&mut self) {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+
+        let _lock = match FileLock::acquire(&self.path).await {
+            Ok(lock) => lock,
+            Err(_) => return,
+        };
+
+        if let Ok(data) = std::fs::read_to_string(&self.path) {
+            self.paths.extend(
+                data.lines()
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from),
+            );
+        }
+
+        let data = self
+            .paths
+            .iter()
+            .map(|path| format!("{}\n", path.display()))
+            .collect::<String>();
+
+        std::fs::write(&self.path, data).ok();
+    }
+}
+
+/// `name` from the package.json in `directory`.
+fn manifest_name(directory: &Path) -> Option<String> {
+    let data = std::fs::read_to_string(directory.join("package.json")).ok()?;
+
+    serde_json::from_str::<serde_json::Value>(&data)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(String::from)
+}
+
+/// Paths linking `package` into node_modules writes: its directory and, for local installs,
+/// its bin scripts.
+pub fn package_targets(app: &App, package: &VoltPackage) -> Vec<PathBuf> {
+    let mut targets = vec![app.node_modules_dir.join(package.install_name())];
+
+    if app.has_flag("global") {
+        return targets;
+    }
+
+    targets.extend(bins::bin_targets(&app.node_modules_dir, package));
+
+    targets
+}
+
+/// Shims a global install of `package` writes into the global bin directory.
+pub fn shim_targets(app: &App, package: &VoltPackage) -> Vec<PathBuf> {
+    let bin_dir = app.global_bin_dir();
+
+    package
+        .bin
+        .iter()
+        .flat_map(|bin| bin.keys())
+        .flat_map(|name| {
+            let mut shims = vec![bin_dir.join(name)];
+
+            if cfg!(target_os = "windows") {
+                shims.push(bin_dir.join(format!("{}.cmd", name)));
+            }
+
+            shims
+        })
+        .collect()
+}
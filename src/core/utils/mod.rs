@@ -1,46 +1,101 @@
 pub mod app;
+pub mod auth;
+pub mod bins;
+pub mod compat;
 pub mod constants;
+pub mod dedup;
+pub mod engines;
+pub mod env_snapshot;
 pub mod errors;
+pub mod experiments;
+pub mod explorer;
+pub mod extensions;
+pub mod fallbacks;
+pub mod file_lock;
+pub mod fs_utils;
+pub mod git;
 pub mod helper;
+pub mod hoist;
+pub mod insight;
+pub mod install_scripts;
+pub mod licenses;
+pub mod lifecycle;
+pub mod linker;
+pub mod logging;
+pub mod managed;
+pub mod node_runtime;
 pub mod npm;
+pub mod npmrc;
+pub mod onboarding;
+pub mod output;
+pub mod overrides;
+pub mod pack;
 pub mod package;
+pub mod package_lock;
+pub mod platform;
+pub mod pnp;
+pub mod portability;
+pub mod progress;
+pub mod registry_cache;
+pub mod retry;
+pub mod sbom;
+pub mod script_lint;
 pub mod scripts;
+pub mod signatures;
+pub mod signing;
+pub mod timings;
+pub mod verify;
+pub mod virtual_store;
 pub mod voltapi;
+pub mod workspace;
+pub mod zero_install;
 
 use crate::commands::add::Package;
+use crate::core::model::http_manager::{client, NetworkMode, Registries};
+use crate::core::model::lock_file::{DependencyID, DependencyLock, LockFile, LockedManifest};
 use crate::core::utils::voltapi::{VoltPackage, VoltResponse};
 use crate::Instant;
 use app::App;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 use errors::VoltError;
-use flate2::read::GzDecoder;
-use futures_util::{stream::FuturesUnordered, StreamExt};
+use futures::AsyncReadExt;
+use futures_util::{stream, StreamExt, TryStreamExt};
 use git_config::{file::GitConfig, parser::Parser};
-use indicatif::ProgressBar;
-use isahc::AsyncReadResponseExt;
+use isahc::http::StatusCode;
+use isahc::{AsyncReadResponseExt, Request};
 use miette::Result;
 use package::NpmPackage;
-use reqwest::StatusCode;
-use ssri::{Algorithm, Integrity};
+use ssri::{Algorithm, Integrity, IntegrityOpts};
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::TryFrom,
     env::temp_dir,
-    ffi::OsStr,
     fs::{read_to_string, File},
     io::Write,
-    path::{Component, Path, PathBuf},
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
+use tracing::{debug, info, trace, warn};
 
 use jwalk::WalkDir;
 use tokio::fs::hard_link;
+use tokio::sync::Semaphore;
 
-use tar::Archive;
 use tokio::fs::create_dir_all;
 
-use crate::core::utils::constants::MAX_RETRIES;
+use crate::core::model::config::NodeLinker;
+use crate::core::utils::constants::{NPM_REGISTRY, RESUMABLE_MIN_SIZE, VOLT_CDN};
+use crate::core::utils::hoist::{Graph, Placement};
+use crate::core::utils::linker::ProjectState;
+use crate::core::utils::managed::{package_targets, ManagedPaths};
+use crate::core::utils::progress::{InstallProgress, PackageProgress};
+use crate::core::utils::retry::{
+    classify, is_transient_isahc, is_transient_status, retry, retry_after, Failure,
+};
+use crate::core::utils::timings::Phase;
+use crate::core::utils::virtual_store::VirtualStore;
 use crate::core::utils::voltapi::JSONVoltResponse;
 
 /// convert a JSONVoltResponse -> VoltResponse
@@ -49,7 +104,12 @@ pub fn convert(deserialized: JSONVoltResponse) -> Result<VoltResponse> {
     let mut converted_versions: HashMap<String, VoltPackage> = HashMap::new();
 
     // iterate through all listed dependencies of the latest version of the response
-    for version in deserialized.versions.get(&deserialized.latest).unwrap() {
+    let latest = deserialized
+        .versions
+        .get(&deserialized.latest)
+        .ok_or(VoltError::DeserializeError)?;
+
+    for version in latest {
         // access data in the hashmap, not name@version
         let data = version.1;
 
@@ -70,35 +130,9 @@ pub fn convert(deserialized: JSONVoltResponse) -> Result<VoltResponse> {
         }
 
         // @codemirror/state@1.2.3 -> 1.2.3
-        let package_version = version.0.split("@").last().unwrap();
-
-        let integrity: Integrity =
-            data.integrity
-                .clone()
-                .parse()
-                .map_err(|_| VoltError::HashParseError {
-                    hash: data.integrity.clone(),
-                })?;
+        let package_version = version.0.rsplit('@').next().unwrap_or_default();
 
-        let algo = integrity.pick_algorithm();
-
-        let mut hash = integrity
-            .hashes
-            .into_iter()
-            .find(|h| h.algorithm == algo)
-            .map(|h| Integrity { hashes: vec![h] })
-            .map(|i| i.to_hex().1)
-            .ok_or(VoltError::IntegrityConversionError)?;
-
-        match algo {
-            Algorithm::Sha1 => {
-                hash = format!("sha1-{}", hash);
-            }
-            Algorithm::Sha512 => {
-                hash = format!("sha512-{}", hash);
-            }
-            _ => {}
-        }
+        let hash = integrity_to_hex(&data.integrity)?;
 
         converted_versions.insert(
             version.0.to_string(), // name@version
@@ -110,6 +144,12 @@ pub fn convert(deserialized: JSONVoltResponse) -> Result<VoltResponse> {
                 integrity: hash,
                 peer_dependencies: data.peer_dependencies.clone(),
                 dependencies: data.dependencies.clone(),
+                optional_dependencies: data.optional_dependencies.clone(),
+                has_install_script: data.has_install_script,
+                alias: None,
+                ranges: None,
+                platform: None,
+                engines: None,
             },
         );
     }
@@ -126,82 +166,121 @@ pub fn convert(deserialized: JSONVoltResponse) -> Result<VoltResponse> {
     })
 }
 
+/// convert a subresource integrity string into the `algorithm-hex` form used by `VoltPackage`
+pub fn integrity_to_hex(sri: &str) -> Result<String> {
+    let integrity: Integrity = sri.parse().map_err(|_| VoltError::HashParseError {
+        hash: sri.to_string(),
+    })?;
+
+    let algo = integrity.pick_algorithm();
+
+    let mut hash = integrity
+        .hashes
+        .into_iter()
+        .find(|h| h.algorithm == algo)
+        .map(|h| Integrity { hashes: vec![h] })
+        .map(|i| i.to_hex().1)
+        .ok_or(VoltError::IntegrityConversionError)?;
+
+    match algo {
+        Algorithm::Sha1 => {
+            hash = format!("sha1-{}", hash);
+        }
+        Algorithm::Sha512 => {
+            hash = format!("sha512-{}", hash);
+        }
+        _ => {}
+    }
+
+    Ok(hash)
+}
+
+/// convert the `algorithm-hex` form used by `VoltPackage` back into a subresource integrity string
+pub fn hex_to_integrity(hash: &str) -> Option<String> {
+    // lockfiles written before the algorithm was recorded only hold sha1 hashes
+    let (algorithm, hex) = hash.split_once('-').unwrap_or(("sha1", hash));
+
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(format!("{}-{}", algorithm, base64::encode(bytes)))
+}
+
 // Get response from volt CDN
-pub async fn get_volt_response(package: Package) -> Result<VoltResponse> {
-    // number of retries
-    let mut retries = 0;
+pub async fn get_volt_response(package: Package, registries: &Registries) -> Result<VoltResponse> {
+    let package_name = &package.name;
+    let url = format!("http://registry.voltpkg.com/{}", package_name);
 
-    // loop until MAX_RETRIES reached.
-    loop {
-        let package_name = package.name.clone();
+    let body = retry(&registries.retries, || async {
         // get a response
-        let mut response = isahc::get_async(format!(
-            "https://cdn.jsdelivr.net/npm/@voltpkg/{}/data.json",
-            package_name
-        ))
-        .await
-        .map_err(VoltError::NetworkError)?;
+        let mut response = client()
+            .get_async(format!("{}/{}/data.json", VOLT_CDN, package_name))
+            .await
+            .map_err(|e| {
+                if is_transient_isahc(&e) {
+                    Failure::Transient(VoltError::NetworkError(e), None)
+                } else {
+                    Failure::Fatal(VoltError::NetworkError(e))
+                }
+            })?;
+
+        let status = response.status();
 
         // check the status of the response
-        match response.status() {
+        match status {
             // 200 (OK)
-            StatusCode::OK => {
-                let deserialized: JSONVoltResponse =
-                    serde_json::from_str(response.text().await.unwrap().as_str())
-                        .map_err(|_| VoltError::DeserializeError)?;
-
-                let converted = convert(deserialized)?;
-
-                return Ok(converted);
-            }
+            StatusCode::OK => response
+                .text()
+                .await
+                .map_err(|_| Failure::Transient(VoltError::DeserializeError, None)),
             // 429 (TOO_MANY_REQUESTS)
-            StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests {
-                url: format!("http://registry.voltpkg.com/{}", package_name),
-                package_name: package_name.to_string(),
-            })?,
+            StatusCode::TOO_MANY_REQUESTS => Err(Failure::Transient(
+                VoltError::TooManyRequests {
+                    url: url.clone(),
+                    package_name: package_name.to_string(),
+                },
+                retry_after(response.headers()),
+            )),
             // 400 (BAD_REQUEST)
-            StatusCode::BAD_REQUEST => Err(VoltError::BadRequest {
-                url: format!("http://registry.voltpkg.com/{}", package_name),
+            StatusCode::BAD_REQUEST => Err(Failure::Fatal(VoltError::BadRequest {
+                url: url.clone(),
                 package_name: package_name.to_string(),
-            })?,
+            })),
             // 404 (NOT_FOUND)
-            StatusCode::NOT_FOUND => {
-                if retries == MAX_RETRIES {
-                    Err(VoltError::PackageNotFound {
-                        url: format!("http://registry.voltpkg.com/{}", package_name),
-                        package_name: package_name.to_string(),
-                    })?
-                }
-            }
+            StatusCode::NOT_FOUND => Err(Failure::Fatal(VoltError::PackageNotFound {
+                url: url.clone(),
+                package_name: package_name.to_string(),
+            })),
             // Other Errors
             _ => {
-                // Stop at MAX_RETRIES
-                if retries == MAX_RETRIES {
-                    Err(VoltError::NetworkUnknownError {
-                        url: format!("http://registry.voltpkg.com/{}", package_name),
-                        package_name: package_name.to_string(),
-                        code: response.status().as_str().to_string(),
-                    })?
+                let error = VoltError::NetworkUnknownError {
+                    url: url.clone(),
+                    package_name: package_name.to_string(),
+                    code: status.as_str().to_string(),
+                };
+
+                if is_transient_status(status) {
+                    Err(Failure::Transient(error, retry_after(response.headers())))
+                } else {
+                    Err(Failure::Fatal(error))
                 }
             }
         }
+    })
+    .await?;
 
-        // Increment no. retries
-        retries += 1;
-    }
-}
+    let deserialized: JSONVoltResponse =
+        serde_json::from_str(&body).map_err(|_| VoltError::DeserializeError)?;
 
-pub async fn get_volt_response_multi(
-    packages: Vec<Package>,
-    pb: &ProgressBar,
-) -> Vec<Result<VoltResponse>> {
-    packages
-        .into_iter()
-        .map(|name| get_volt_response(name))
-        .collect::<FuturesUnordered<_>>()
-        .inspect(|_| pb.inc(1))
-        .collect::<Vec<Result<VoltResponse>>>()
-        .await
+    let converted = convert(deserialized)?;
+
+    npm::select_version(converted, &package, registries).await
 }
 
 // #[cfg(windows)]
@@ -353,194 +432,469 @@ pub async fn get_volt_response_multi(
 //     }
 // }
 
-/// downloads tarball file from package
-pub async fn download_tarball(app: &App, package: &VoltPackage, secure: bool) -> Result<()> {
-    let package_instance = package.clone();
+/// A downloaded tarball, buffered or streamed to disk in low-memory mode.
+enum Tarball {
+    Bytes(bytes::Bytes),
+    File(PathBuf),
+    /// Already in the `offline-mirror`, kept there.
+    Mirrored(PathBuf),
+}
 
-    // @types/eslint
-    if package_instance.name.starts_with('@') && package_instance.name.contains("/") {
-        let package_directory_location = app
-            .volt_dir
-            .join(&package.name.split("/").collect::<Vec<&str>>()[0]);
+impl Tarball {
+    /// The integrity of the tarball, hashed with `algorithm`.
+    fn integrity(&self, algorithm: Algorithm) -> Result<Integrity> {
+        let mut hasher = IntegrityOpts::new().algorithm(algorithm);
 
-        if !Path::new(&package_directory_location).exists() {
-            create_dir_all(&package_directory_location)
-                .await
-                .map_err(VoltError::CreateDirError)?;
+        match self {
+            Tarball::Bytes(bytes) => hasher.input(&bytes[..]),
+            Tarball::File(path) | Tarball::Mirrored(path) => {
+                let mut file = File::open(path).map_err(VoltError::HasherCopyError)?;
+                let mut chunk = vec![0; 64 * 1024];
+
+                loop {
+                    let read = std::io::Read::read(&mut file, &mut chunk)
+                        .map_err(VoltError::HasherCopyError)?;
+
+                    if read == 0 {
+                        break;
+                    }
+
+                    hasher.input(&chunk[..read]);
+                }
+            }
+        }
+
+        Ok(hasher.result())
+    }
+
+    /// Where the tarball came from, as integrity errors name it.
+    fn origin(&self) -> String {
+        match self {
+            Tarball::Mirrored(path) => path.display().to_string(),
+            _ => String::from("the registry"),
         }
     }
 
+    fn unpack(&self, destination: &Path) -> Result<()> {
+        match self {
+            Tarball::Bytes(bytes) => git::unpack_tarball(&bytes[..], destination),
+            Tarball::File(path) | Tarball::Mirrored(path) => {
+                let file = File::open(path).map_err(|e| VoltError::ExtractError {
+                    name: path.display().to_string(),
+                    source: e,
+                })?;
+
+                git::unpack_tarball(std::io::BufReader::new(file), destination)
+            }
+        }
+    }
+
+    /// Paths of the files in the tarball, see `git::tarball_files`.
+    fn files(&self, name: &str) -> Result<Vec<PathBuf>> {
+        match self {
+            Tarball::Bytes(bytes) => git::tarball_files(&bytes[..], name),
+            Tarball::File(path) | Tarball::Mirrored(path) => {
+                let file = File::open(path).map_err(|e| VoltError::ExtractError {
+                    name: path.display().to_string(),
+                    source: e,
+                })?;
+
+                git::tarball_files(std::io::BufReader::new(file), name)
+            }
+        }
+    }
+
+    /// Remove a tarball that was streamed to disk.
+    fn discard(&self) {
+        if let Tarball::File(path) = self {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match self {
+            Tarball::Bytes(bytes) => bytes.len() as u64,
+            Tarball::File(path) | Tarball::Mirrored(path) => {
+                std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+            }
+        }
+    }
+
+    /// Keep a copy at `path` in the `offline-mirror`.
+    fn copy_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(VoltError::CreateDirError)?;
+        }
+
+        // a build reading the mirror meanwhile never sees half a tarball
+        let partial = PathBuf::from(format!("{}.partial", path.display()));
+
+        let copied = match self {
+            Tarball::Bytes(bytes) => std::fs::write(&partial, bytes),
+            Tarball::File(source) | Tarball::Mirrored(source) => {
+                std::fs::copy(source, &partial).map(|_| ())
+            }
+        };
+
+        copied
+            .and_then(|_| std::fs::rename(&partial, path))
+            .map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: path.display().to_string(),
+            })?;
+
+        Ok(())
+    }
+}
+
+/// downloads tarball file from package and extracts it into the store
+pub async fn download_tarball(
+    app: &App,
+    package: &VoltPackage,
+    progress: &PackageProgress<'_>,
+) -> Result<()> {
     // location of extracted package
-    let loc = app.volt_dir.join(&package.name);
+    let loc = linker::store_path(app, package);
+
+    let mirrored = app.mirror_path(&package.name, &package.version);
 
-    // if package is not already installed
-    if !Path::new(&loc).exists() {
-        // Url to download tarball code files from
-        let mut url = package_instance.tarball;
-        // let registries = vec!["yarnpkg.com"];
-        // let random_registry = registries.choose(&mut rand::thread_rng()).unwrap();
+    // the mirror should hold every tarball the project needs, also the ones already in the store,
+    // except the ones too large for the zero-install cache
+    let mirror_missing = mirrored
+        .as_ref()
+        .is_some_and(|path| !path.exists() && !zero_install::is_oversized(path))
+        && app.registries.network != NetworkMode::Offline;
 
-        // url = url.replace("npmjs.org", random_registry);
+    if loc.exists() && !mirror_missing {
+        trace!("{}@{} is in the store", package.name, package.version);
+
+        return Ok(());
+    }
 
-        if !secure {
-            url = url.replace("https", "http")
+    let tarball = match mirrored.as_ref().filter(|path| path.exists()) {
+        Some(path) => {
+            debug!("extracting {} from {}", package.name, path.display());
+
+            Tarball::Mirrored(path.clone())
         }
+        None => {
+            if app.registries.network == NetworkMode::Offline {
+                return Err(VoltError::NotCached {
+                    packages: vec![format!("{}@{}", package.name, package.version)],
+                })?;
+            }
 
-        // Get Tarball File
-        let res = reqwest::get(url).await.unwrap();
+            progress.phase(Phase::Download);
 
-        // Tarball bytes response
-        let bytes: bytes::Bytes = res.bytes().await.unwrap();
+            let start = Instant::now();
+            let tarball = fetch_tarball(app, package, &loc, progress).await?;
+            timings::record(Phase::Download, start, 1);
 
-        let algorithm;
+            tarball
+        }
+    };
 
-        // there are only 2 supported algorithms
-        // sha1 and sha512
-        // so we can be sure that if it doesn't start with sha1, it's going to have to be sha512
-        if package.integrity.starts_with("sha1") {
-            algorithm = Algorithm::Sha1;
-        } else {
-            algorithm = Algorithm::Sha512;
+    // nothing of a tarball is extracted, mirrored or cached before it's known to be the locked one
+    if let Err(error) = verify_integrity(package, &tarball) {
+        tarball.discard();
+
+        return Err(error);
+    }
+
+    if let Some(path) = mirrored.filter(|_| mirror_missing) {
+        if !zero_install::leave_out(app, &path, tarball.size()) {
+            tarball.copy_to(&path)?;
         }
+    }
 
-        // Verify If Bytes == (Sha 512 | Sha 1) of Tarball
-        if package.integrity == App::calc_hash(&bytes, algorithm).unwrap() {
-            // Create node_modules
-            create_dir_all(&app.node_modules_dir).await.unwrap();
+    if loc.exists() {
+        tarball.discard();
 
-            // Delete package from node_modules
-            let node_modules_dep_path = app.node_modules_dir.join(&package.name);
+        return Ok(());
+    }
 
-            // TODO: fix this
-            // if node_modules_dep_path.exists() {
-            //     remove_dir_all(&node_modules_dep_path).unwrap();
-            // }
+    // files that can't all be written here would leave a package with some of them missing
+    let case_insensitive = portability::is_case_insensitive(&app.store_dir());
 
-            // Directory to extract tarball to
-            let mut extract_directory = PathBuf::from(&app.volt_dir);
+    if case_insensitive || cfg!(windows) {
+        let roots = [
+            loc.clone(),
+            app.node_modules_dir.join(package.install_name()),
+        ];
 
-            // @types/eslint
-            if package.clone().name.starts_with('@') && package.clone().name.contains("/") {
-                if cfg!(target_os = "windows") {
-                    let name = package.clone().name.replace(r"/", r"\");
+        let problems = portability::check(&tarball.files(&package.name)?, &roots, case_insensitive);
 
-                    let split = name.split(r"\").collect::<Vec<&str>>();
+        if !problems.is_empty() {
+            tarball.discard();
 
-                    // C:\Users\xtrem\.volt\@types
-                    extract_directory = extract_directory.join(split[0]);
-                } else {
-                    let name = package.clone().name;
-                    let split = name.split('/').collect::<Vec<&str>>();
+            return Err(VoltError::UnportablePackage {
+                name: format!("{}@{}", package.name, package.version),
+                count: problems.len(),
+                problems: problems
+                    .iter()
+                    .map(|problem| format!("\n  {}", problem))
+                    .collect(),
+            }
+            .into());
+        }
+    }
 
-                    // ~/.volt/@types
-                    extract_directory = extract_directory.join(split[0]);
-                }
+    // extract next to the final location first, so a shared store never
+    // exposes a half extracted package to other projects
+    let partial = PathBuf::from(format!("{}.partial", loc.display()));
+
+    let partial_instance = partial.clone();
+
+    progress.phase(Phase::Extract);
+
+    let start = Instant::now();
+
+    tokio::task::spawn_blocking(move || {
+        if partial_instance.exists() {
+            std::fs::remove_dir_all(&partial_instance).ok();
+        }
+
+        let unpacked = tarball.unpack(&partial_instance);
+        tarball.discard();
+        unpacked
+    })
+    .await
+    .map_err(|_| VoltError::ExtractError {
+        name: package.name.clone(),
+        source: std::io::Error::new(
+            std::io::ErrorKind::Interrupted,
+            "extraction task was cancelled",
+        ),
+    })??;
+
+    timings::record(Phase::Extract, start, 1);
+
+    // another project may have finished extracting the same package in the meantime
+    if let Err(e) = std::fs::rename(&partial, &loc) {
+        std::fs::remove_dir_all(&partial).ok();
+
+        if !loc.exists() {
+            return Err(VoltError::ExtractError {
+                name: package.name.clone(),
+                source: e,
             }
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check `tarball` hashes to the integrity `package` was locked with, in the strongest
+/// algorithm the lock has a hash for.
+fn verify_integrity(package: &VoltPackage, tarball: &Tarball) -> Result<()> {
+    let expected = hex_to_integrity(&package.integrity)
+        .and_then(|sri| sri.parse::<Integrity>().ok())
+        .filter(|integrity| !integrity.hashes.is_empty());
+
+    let algorithm = expected
+        .as_ref()
+        .map_or(Algorithm::Sha512, |integrity| integrity.pick_algorithm());
+
+    let actual = tarball.integrity(algorithm)?;
+
+    if expected
+        .as_ref()
+        .and_then(|expected| expected.matches(&actual))
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    Err(VoltError::IntegrityMismatch {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        origin: tarball.origin(),
+        expected: expected.map_or_else(|| String::from("none"), |e| e.to_string()),
+        actual: actual.to_string(),
+    }
+    .into())
+}
+
+/// Download the tarball of `package`, picking up where the connection dropped when the server
+/// supports range requests and starting over when it doesn't.
+async fn fetch_tarball(
+    app: &App,
+    package: &VoltPackage,
+    loc: &Path,
+    progress: &PackageProgress<'_>,
+) -> Result<Tarball> {
+    // tarballs resolved against npm come from the configured registry, at the same path
+    let registry = app.registries.for_package(&package.name);
+
+    let tarball = match package.tarball.strip_prefix(NPM_REGISTRY) {
+        Some(path) if registry != NPM_REGISTRY => format!("{}{}", registry, path),
+        _ => package.tarball.clone(),
+    };
+
+    // Url to download tarball code files from, the integrity stays the registry's
+    let url = app.config.tarball_url(&package.name, &tarball);
+
+    // tarballs of private registries need the same token as their documents
+    let token = app.registries.token(&url).await?;
+
+    let (url, token) = (&url, &token);
+
+    // bytes of an earlier attempt, or an earlier run, are kept here and picked up from
+    let partial = PathBuf::from(format!("{}.tgz.partial", loc.display()));
+    let partial = &partial;
+
+    // Get Tarball File
+    retry(&app.registries.retries, || async move {
+        let resume_from = std::fs::metadata(partial).map_or(0, |metadata| metadata.len());
+
+        let mut request = Request::get(url);
+
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+
+        if resume_from > 0 {
+            debug!("resuming {} from byte {}", url, resume_from);
+
+            request = request.header("range", format!("bytes={}-", resume_from));
+        }
+
+        let request = request
+            .body(())
+            .map_err(|e| Failure::Fatal(VoltError::RequestBuilderError(e).into()))?;
 
-            extract_directory = extract_directory.join(format!(
-                "{}-{}",
-                package.clone().name,
-                package.clone().version
+        let mut res = app.send(request, url).await.map_err(classify)?;
+
+        let status = res.status();
+
+        if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            return Err(Failure::Fatal(
+                app.registries.rejected(url, status, token.is_some()).into(),
             ));
+        }
 
-            // Initialize tarfile decoder while directly passing in bytes
+        // the partial file doesn't fit what the server has, start over
+        let misplaced = status == StatusCode::PARTIAL_CONTENT
+            && content_range_start(res.headers()) != Some(resume_from);
 
-            let bytes = Arc::new(bytes);
+        if status == StatusCode::RANGE_NOT_SATISFIABLE || misplaced {
+            std::fs::remove_file(partial).ok();
 
-            let bytes_ref = bytes.clone();
+            let error = VoltError::TarballStatusError {
+                url: url.clone(),
+                code: status.as_str().to_string(),
+            };
 
-            let extract_directory_instance = extract_directory.clone();
+            return Err(Failure::Transient(error.into(), None));
+        }
 
-            let node_modules_dep_path_instance = app.clone().node_modules_dir.clone();
-            let pkg_name = package.clone().name;
-            let pkg_name_instance = package.clone().name;
+        if !status.is_success() {
+            let error = VoltError::TarballStatusError {
+                url: url.clone(),
+                code: status.as_str().to_string(),
+            }
+            .into();
 
-            futures::try_join!(
-                tokio::task::spawn_blocking(move || {
-                    // Extract the data into extract_directory
+            return Err(if is_transient_status(status) {
+                Failure::Transient(error, retry_after(res.headers()))
+            } else {
+                Failure::Fatal(error)
+            });
+        }
 
-                    let node_gz_decoder = GzDecoder::new(&**bytes_ref);
+        let download_error = |e| {
+            classify(
+                VoltError::TarballDownloadError {
+                    url: url.clone(),
+                    source: e,
+                }
+                .into(),
+            )
+        };
 
-                    let mut node_archive = Archive::new(node_gz_decoder);
+        // a server ignoring the range sends the whole tarball again
+        let resumed = status == StatusCode::PARTIAL_CONTENT;
 
-                    for entry in node_archive.entries().unwrap() {
-                        let mut entry = entry.unwrap();
-                        let path = entry.path().unwrap();
-                        let mut new_path = PathBuf::new();
+        let length = res.body().len();
+        let large = length.is_some_and(|length| length >= RESUMABLE_MIN_SIZE);
 
-                        for component in path.components() {
-                            if component.as_os_str() == "package" {
-                                new_path.push(Component::Normal(OsStr::new(&pkg_name)));
-                            } else {
-                                new_path.push(component)
-                            }
-                        }
+        progress.downloading(if resumed { resume_from } else { 0 }, length);
 
-                        std::fs::create_dir_all(
-                            node_modules_dep_path_instance
-                                .to_path_buf()
-                                .join(&new_path)
-                                .parent()
-                                .unwrap(),
-                        )
-                        .unwrap();
-
-                        match entry
-                            .unpack(node_modules_dep_path_instance.to_path_buf().join(&new_path))
-                        {
-                            Ok(_v) => {}
-                            Err(_err) => {}
-                        }
-                    }
-                }),
-                tokio::task::spawn_blocking(move || {
-                    let gz_decoder = GzDecoder::new(&**bytes);
-
-                    let mut archive = Archive::new(gz_decoder);
-
-                    for entry in archive.entries().unwrap() {
-                        let mut entry = entry.unwrap();
-                        let path = entry.path().unwrap();
-                        let mut new_path = PathBuf::new();
-
-                        for component in path.components() {
-                            if component.as_os_str() == "package" {
-                                new_path.push(Component::Normal(OsStr::new(&pkg_name_instance)));
-                            } else {
-                                new_path.push(component)
-                            }
-                        }
+        let body = res.body_mut();
+        let mut chunk = vec![0; 64 * 1024];
 
-                        std::fs::create_dir_all(
-                            extract_directory_instance
-                                .to_path_buf()
-                                .join(&new_path)
-                                .parent()
-                                .unwrap(),
-                        )
-                        .unwrap();
-
-                        match entry.unpack(extract_directory_instance.to_path_buf().join(&new_path))
-                        {
-                            Ok(_v) => {}
-                            Err(err) => {
-                                let code = err.raw_os_error().unwrap();
-
-                                if code == 5 {
-                                    continue;
-                                }
-                            }
-                        }
+        // large tarballs, and every one in low-memory mode, go to disk chunk by chunk so an
+        // interrupted download can resume where it stopped
+        if app.low_memory() || large || resumed {
+            if let Some(parent) = partial.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| Failure::Fatal(VoltError::CreateDirError(e).into()))?;
+            }
+
+            let write_error = |e| {
+                Failure::Fatal(
+                    VoltError::WriteFileError {
+                        source: e,
+                        name: partial.display().to_string(),
                     }
-                })
-            )
-            .unwrap();
+                    .into(),
+                )
+            };
+
+            let mut file = match resumed {
+                true => std::fs::OpenOptions::new().append(true).open(partial),
+                false => File::create(partial),
+            }
+            .map_err(write_error)?;
+
+            loop {
+                let read = body.read(&mut chunk).await.map_err(download_error)?;
+
+                if read == 0 {
+                    break;
+                }
+
+                file.write_all(&chunk[..read]).map_err(write_error)?;
+                progress.received(read as u64);
+            }
+
+            Ok(Tarball::File(partial.clone()))
         } else {
-            return Err(VoltError::ChecksumVerificationError)?;
+            let mut bytes = Vec::with_capacity(length.unwrap_or_default() as usize);
+
+            loop {
+                let read = body.read(&mut chunk).await.map_err(download_error)?;
+
+                if read == 0 {
+                    break;
+                }
+
+                bytes.extend_from_slice(&chunk[..read]);
+                progress.received(read as u64);
+            }
+
+            // whatever an earlier run left behind is of no use anymore
+            std::fs::remove_file(partial).ok();
+
+            Ok(Tarball::Bytes(bytes.into()))
         }
-    }
+    })
+    .await
+}
 
-    Ok(())
+/// Where the body of a `206 Partial Content` response starts, from `Content-Range`.
+fn content_range_start(headers: &isahc::http::HeaderMap) -> Option<u64> {
+    headers
+        .get("content-range")?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
 }
 
 pub async fn download_tarball_create(
@@ -613,9 +967,10 @@ pub async fn download_tarball_create(
 
     let tarball = package_version.dist.tarball.replace("https", "http");
 
-    let res = reqwest::get(tarball).await.unwrap();
+    let mut res = client().get_async(tarball).await.unwrap();
 
-    let bytes = res.bytes().await.unwrap();
+    let mut bytes = vec![];
+    res.copy_to(&mut bytes).await.unwrap();
 
     // App::calc_hash(&bytes)?;
 
@@ -743,98 +1098,806 @@ pub fn enable_ansi_support() -> Result<(), u32> {
     Ok(())
 }
 
-#[cfg(windows)]
-/// Generates the binary and other required scripts for the package
-pub fn generate_script(app: &Arc<App>, package: &VoltPackage) {
-    // Create node_modules/scripts if it doesn't exist
-    if !Path::new("node_modules/.bin").exists() {
-        // Create the binary directory
-        std::fs::create_dir_all("node_modules/.bin").unwrap();
+// Unix functions
+#[cfg(unix)]
+pub fn enable_ansi_support() -> Result<(), u32> {
+    Ok(())
+}
+
+/// Link the bins of `package`, installed in `node_modules`, into its `.bin`, see `bins`.
+fn link_bins(node_modules: &Path, package: &VoltPackage) -> Result<()> {
+    bins::link_bins(node_modules, package).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: bins::bin_dir(node_modules).display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// Link the executables of a globally installed package into the global bin directory.
+///
+/// Every platform gets a POSIX shell shim, Windows additionally gets a `.cmd` wrapper so the
+/// command also works from `cmd.exe` and PowerShell.
+/// ## Returns
+/// * `Result<Vec<String>>` - names of the linked executables
+pub fn create_global_shims(app: &App, package: &VoltPackage) -> Result<Vec<String>> {
+    let bin = match &package.bin {
+        Some(bin) => bin,
+        None => return Ok(vec![]),
+    };
+
+    let bin_dir = app.global_bin_dir();
+
+    std::fs::create_dir_all(&bin_dir).map_err(VoltError::CreateDirError)?;
+
+    let mut linked = vec![];
+
+    for (name, target) in bin {
+        let target = app
+            .node_modules_dir
+            .join(package.install_name())
+            .join(target);
+
+        let write = |path: PathBuf, contents: String| {
+            std::fs::write(&path, contents).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: path.display().to_string(),
+            })
+        };
+
+        let shim = bin_dir.join(name);
+
+        write(
+            shim.clone(),
+            format!("#!/bin/sh\nexec node \"{}\" \"$@\"\n", target.display()),
+        )?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755)).map_err(
+                |e| VoltError::WriteFileError {
+                    source: e,
+                    name: shim.display().to_string(),
+                },
+            )?;
+        }
+
+        if cfg!(target_os = "windows") {
+            write(
+                bin_dir.join(format!("{}.cmd", name)),
+                format!("@ECHO off\r\nnode \"{}\" %*\r\n", target.display()),
+            )?;
+        }
+
+        linked.push(name.clone());
     }
 
-    // Create binary scripts for the package if they exist.
-    if package.bin.is_some() {
-        let bin = package.bin.as_ref().unwrap();
+    linked.sort();
 
-        let k = bin.keys().next().unwrap();
-        let v = bin.values().next().unwrap();
+    Ok(linked)
+}
 
-        let command = format!(
-            r#"
-            @IF EXIST "%~dp0\node.exe" (
-                "%~dp0\node.exe"  "%~dp0\..\{}\{}" %*
-                ) ELSE (
-                    @SETLOCAL
-                    @SET PATHEXT=%PATHEXT:;.JS;=;%
-                    node  "%~dp0\..\{}\{}" %*
-                    )"#,
-            k, v, k, v
-        )
-        .replace(r"%~dp0\..", format!("{}", app.volt_dir.display()).as_str());
+/// Lockfile key of a resolved package, aliases are keyed as `alias@npm:name@version`.
+pub fn lock_id(package: &VoltPackage) -> DependencyID {
+    match &package.alias {
+        Some(alias) => DependencyID(
+            alias.clone(),
+            format!("npm:{}@{}", package.name, package.version),
+        ),
+        None => DependencyID(package.name.clone(), package.version.clone()),
+    }
+}
 
-        let mut f = File::create(format!(r"node_modules/.bin/{}.cmd", k)).unwrap();
-        f.write_all(command.as_bytes()).unwrap();
+/// Lockfile entry of a resolved package.
+pub fn lock_entry(package: &VoltPackage, optional: bool) -> DependencyLock {
+    DependencyLock {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        tarball: package.tarball.clone(),
+        integrity: package.integrity.clone(),
+        dependencies: package.dependencies.clone().unwrap_or_default(),
+        optional,
+        files: BTreeMap::new(),
+        alias: package.alias.clone(),
+        has_install_script: package.has_install_script,
+        overridden: None,
+        extended: vec![],
+        platform: package.platform.clone().unwrap_or_default(),
+        engines: package.engines.clone().unwrap_or_default(),
+        manifest: Some(LockedManifest {
+            bin: package.bin.iter().flatten().map(clone_pair).collect(),
+            peer_dependencies: package.peer_dependencies.clone().unwrap_or_default(),
+            optional_dependencies: package.optional_dependencies.clone().unwrap_or_default(),
+            ranges: package.ranges.iter().flatten().map(clone_pair).collect(),
+        }),
     }
 }
 
-#[cfg(unix)]
-pub fn generate_script(app: &Arc<App>, package: &VoltPackage) {
-    // Create node_modules/scripts if it doesn't exist
-    if !Path::new("node_modules/scripts").exists() {
-        std::fs::create_dir_all("node_modules/scripts").unwrap();
-    }
-
-    // If the package has binary scripts, create them
-    if package.bin.is_some() {
-        let bin = package.bin.as_ref().unwrap();
-
-        let k = bin.keys().next().unwrap();
-        let v = bin.values().next().unwrap();
-
-        let command = format!(
-            r#"
-            node  "{}/.volt/{}/{}" %*
-            "#,
-            app.volt_dir.to_string_lossy(),
-            k,
-            v,
+fn clone_pair((key, value): (&String, &String)) -> (String, String) {
+    (key.clone(), value.clone())
+}
+
+/// The tree of `packages`, every one of them pinned by `lock_file`, with the versions of their
+/// dependencies it pins. Keyed like a resolved tree, by `name@version`.
+///
+/// `package` gives the package of a locked entry, `None` when it can't and the tree has to
+/// be resolved again.
+pub fn locked_tree(
+    lock_file: &LockFile,
+    packages: &[Package],
+    package: impl Fn(&DependencyLock) -> Option<VoltPackage>,
+) -> Option<HashMap<String, VoltPackage>> {
+    let mut by_name: HashMap<&str, Vec<&DependencyLock>> = HashMap::new();
+
+    for lock in lock_file.dependencies.values() {
+        if lock.alias.is_none() {
+            by_name.entry(lock.name.as_str()).or_default().push(lock);
+        }
+    }
+
+    let mut pending = packages
+        .iter()
+        .map(|package| {
+            lock_file.dependencies.values().find(|lock| {
+                lock.name == package.name
+                    && lock.alias == package.alias
+                    && package.version.as_deref() == Some(lock.version.as_str())
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut tree = HashMap::new();
+
+    while let Some(lock) = pending.pop() {
+        let id = format!("{}@{}", lock.install_name(), lock.version);
+
+        if tree.contains_key(&id) {
+            continue;
+        }
+
+        let package = package(lock)?;
+
+        let names = package
+            .dependencies
+            .iter()
+            .chain(package.optional_dependencies.iter())
+            .chain(package.peer_dependencies.iter())
+            .flatten();
+
+        // the version its range asks for when several are locked, see `hoist`
+        for name in names {
+            let candidates = match by_name.get(name.as_str()) {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+
+            let range = package.ranges.as_ref().and_then(|ranges| ranges.get(name));
+
+            let fitting = candidates
+                .iter()
+                .filter(|candidate| range.is_some_and(|range| satisfies(&candidate.version, range)))
+                .collect::<Vec<_>>();
+
+            let candidates = match fitting.is_empty() {
+                true => candidates.iter().collect(),
+                false => fitting,
+            };
+
+            let dependency = candidates
+                .into_iter()
+                .max_by(|a, b| {
+                    match (
+                        node_semver::Version::parse(&a.version),
+                        node_semver::Version::parse(&b.version),
+                    ) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        _ => a.version.cmp(&b.version),
+                    }
+                })
+                .unwrap();
+
+            pending.push(dependency);
+        }
+
+        tree.insert(id, package);
+    }
+
+    Some(tree)
+}
+
+/// Highest locked version of `package` that satisfies the range it was requested with.
+pub fn locked_version(lock_file: &LockFile, package: &Package) -> Option<String> {
+    lock_file
+        .dependencies
+        .values()
+        .filter(|lock| lock.name == package.name && lock.alias == package.alias)
+        .filter(|lock| {
+            package
+                .version
+                .as_deref()
+                .is_none_or(|range| satisfies(&lock.version, range))
+        })
+        .filter_map(|lock| node_semver::Version::parse(&lock.version).ok())
+        .max()
+        .map(|version| version.to_string())
+}
+
+/// Whether `version` satisfies `range`, `false` when either doesn't parse.
+pub fn satisfies(version: &str, range: &str) -> bool {
+    match (
+        node_semver::Version::parse(version),
+        node_semver::Range::parse(range),
+    ) {
+        (Ok(version), Ok(range)) => version.satisfies(&range),
+        _ => false,
+    }
+}
+
+/// Version of `package_name` currently installed in the project's node_modules.
+pub fn installed_version(app: &App, package_name: &str) -> Option<String> {
+    let manifest =
+        read_to_string(app.node_modules_dir.join(package_name).join("package.json")).ok()?;
+
+    serde_json::from_str::<serde_json::Value>(&manifest)
+        .ok()?
+        .get("version")?
+        .as_str()
+        .map(|version| version.to_string())
+}
+
+/// Names of packages that are only reachable through `optionalDependencies`.
+///
+/// A failure to install one of these should never abort the whole installation.
+pub fn optional_package_names(
+    packages: &HashMap<String, VoltPackage>,
+    requested: &[Package],
+) -> HashSet<String> {
+    let mut required: HashSet<&str> = requested.iter().map(|p| p.name.as_str()).collect();
+    let mut optional: HashSet<String> = HashSet::new();
+
+    for package in packages.values() {
+        if let Some(dependencies) = &package.dependencies {
+            required.extend(dependencies.iter().map(|d| d.as_str()));
+        }
+
+        if let Some(optional_dependencies) = &package.optional_dependencies {
+            optional.extend(optional_dependencies.iter().cloned());
+        }
+    }
+
+    optional.retain(|name| !required.contains(name.as_str()));
+
+    optional
+}
+
+/// Key of a package in the progress recorded in `.volt/state`.
+fn progress_key(package: &VoltPackage) -> String {
+    format!("{}@{}", package.install_name(), package.version)
+}
+
+/// Record which packages are fully installed, so an interrupted install can resume.
+fn save_progress(app: &App, installed: &BTreeSet<String>) {
+    let mut state = ProjectState::load(app).unwrap_or_default();
+    state.installed = installed.clone();
+    state.save(app);
+}
+
+/// Install every package concurrently, showing the progress of each one, see `progress`.
+///
+/// Packages in `optional` only print a warning when they fail to install. Every package that
+/// is installed is recorded in `.volt/state` until all of them are, and packages an
+/// interrupted install already finished are skipped once node_modules has their version.
+/// The `direct` dependencies are keyed by the name they're installed under, with the range
+/// they were asked for. Versions that conflict are nested where they're needed, see `hoist`.
+/// Workspace packages in `own`, by their node_modules and with the ranges they declare, get
+/// the versions the top doesn't have in their own node_modules.
+/// With `node-linker = "isolated"` only the direct dependencies end up at the top of
+/// node_modules. Optional packages built for other platforms are left out, see `platform`.
+pub async fn install_packages(
+    app: &Arc<App>,
+    packages: &[&VoltPackage],
+    optional: &HashSet<String>,
+    direct: &HashMap<String, String>,
+    own: &[(PathBuf, HashMap<String, String>)],
+) -> Result<()> {
+    // the linkers still taking shape are only used when their experiment is on
+    experiments::check_config(app)?;
+
+    let installable = platform::installable(packages, optional)?;
+    let packages = installable.as_slice();
+
+    engines::check_packages(app, packages)?;
+
+    let graph = Graph::new(packages, direct);
+
+    // Plug'n'Play leaves every package in the store and maps where to find them instead
+    if app.config.node_linker == NodeLinker::Pnp && !app.has_flag("global") {
+        if !own.is_empty() {
+            warn!(
+                "{} workspace packages get the versions at the top, Plug'n'Play maps one version of every package",
+                own.len()
+            );
+        }
+
+        return install_pnp(app, packages, optional, &graph).await;
+    }
+
+    let mut managed = ManagedPaths::load(app);
+
+    let isolated = app.config.node_linker == NodeLinker::Isolated;
+
+    // global installs keep to their own node_modules
+    let virtual_store = ((app.config.shared_virtual_store || isolated) && !app.has_flag("global"))
+        .then(|| VirtualStore::plan(app, packages, &graph));
+
+    // entries of the virtual store carry their own dependencies, nothing is nested
+    let layout = match virtual_store {
+        Some(_) => vec![],
+        None => graph.layout(),
+    };
+
+    let hoisted = layout
+        .iter()
+        .filter(|placement| placement.depth == 0)
+        .map(|placement| (hoist::key(placement.package), placement))
+        .collect::<HashMap<_, _>>();
+
+    let top_level = |package: &VoltPackage| match &virtual_store {
+        Some(virtual_store) => virtual_store.is_top_level(package),
+        None => hoisted.contains_key(&hoist::key(package)),
+    };
+
+    let mut targets = packages
+        .iter()
+        .filter(|&&package| top_level(package))
+        .flat_map(|&package| {
+            package_targets(app, package)
+                .into_iter()
+                .map(move |path| (path, package))
+        })
+        .collect::<Vec<_>>();
+
+    // nested copies get their bins next to them
+    for placement in layout.iter().filter(|placement| placement.depth > 0) {
+        let destination = app.node_modules_dir.join(&placement.path);
+
+        if let Some(node_modules) = bins::node_modules_of(&destination, placement.package) {
+            for path in bins::bin_targets(node_modules, placement.package) {
+                targets.push((path, placement.package));
+            }
+        }
+
+        targets.push((destination, placement.package));
+    }
+
+    // placed in the node_modules of the workspace packages, parents first like the layout
+    let own_layouts = own
+        .iter()
+        .flat_map(|(node_modules, ranges)| {
+            let placements = match &virtual_store {
+                // their entries carry their dependencies, only what they ask for is linked
+                Some(virtual_store) => Graph::new(packages, ranges)
+                    .direct()
+                    .filter(|package| !virtual_store.is_top_level(package))
+                    .map(|package| Placement {
+                        path: PathBuf::from(package.install_name()),
+                        package,
+                        depth: 0,
+                        has_nested: false,
+                    })
+                    .collect(),
+                None => graph.own_layout(ranges),
+            };
+
+            placements
+                .into_iter()
+                .map(move |placement| (node_modules.join(&placement.path), placement))
+        })
+        .collect::<Vec<_>>();
+
+    for (destination, placement) in own_layouts.iter() {
+        if let Some(node_modules) = bins::node_modules_of(destination, placement.package) {
+            for path in bins::bin_targets(node_modules, placement.package) {
+                targets.push((path, placement.package));
+            }
+        }
+
+        targets.push((destination.clone(), placement.package));
+    }
+
+    // copies an earlier install gave them would shadow the top once their ranges agree
+    for (node_modules, ranges) in own.iter() {
+        for name in ranges.keys() {
+            let path = node_modules.join(name);
+
+            if managed.contains(&path) && !own_layouts.iter().any(|(p, _)| *p == path) {
+                linker::remove_path(&path).ok();
+            }
+        }
+    }
+
+    // copies hoisted by an earlier install would keep undeclared packages requirable
+    for package in packages.iter().filter(|&&package| !top_level(package)) {
+        let hoisted = app.node_modules_dir.join(package.install_name());
+
+        // another version goes there instead
+        if packages
+            .iter()
+            .any(|&other| other.install_name() == package.install_name() && top_level(other))
+        {
+            continue;
+        }
+
+        if managed.contains(&hoisted) {
+            linker::remove_path(&hoisted).ok();
+        }
+    }
+
+    // global installs don't belong to the project the state is kept in
+    let state = match app.has_flag("global") {
+        true => ProjectState::default(),
+        false => ProjectState::load(app).unwrap_or_default(),
+    };
+
+    let (resumed, packages): (Vec<&VoltPackage>, Vec<&VoltPackage>) =
+        packages.iter().partition(|package| {
+            state.installed.contains(&progress_key(package))
+                && installed_version(app, package.install_name()).as_deref()
+                    == Some(package.version.as_str())
+        });
+
+    if !resumed.is_empty() {
+        info!(
+            "resuming an interrupted install, {} packages are already installed",
+            resumed.len()
         );
-        // .replace(r"%~dp0\..", format!("{}", app.volt_dir.display()).as_str());
-        let p = format!(r"node_modules/scripts/{}.sh", k);
-        let mut f = File::create(p.clone()).unwrap();
-        std::process::Command::new("chmod")
-            .args(&["+x", &p])
-            .spawn()
-            .unwrap();
-        f.write_all(command.as_bytes()).unwrap();
     }
+
+    // what the interrupted install left behind is volt's, only new paths can clobber
+    let pending = targets
+        .iter()
+        .filter(|(_, package)| !resumed.contains(package))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    managed.check(app, &pending)?;
+
+    // nothing is downloaded offline, every package has to be in the store already
+    if app.registries.network == NetworkMode::Offline {
+        let uncached = packages
+            .iter()
+            .filter(|package| !optional.contains(&package.name))
+            .filter(|package| !linker::store_path(app, package).exists())
+            .filter(|package| {
+                !app.mirror_path(&package.name, &package.version)
+                    .is_some_and(|path| path.exists())
+            })
+            .map(|package| format!("{}@{}", package.name, package.version))
+            .collect::<Vec<_>>();
+
+        if !uncached.is_empty() {
+            return Err(VoltError::NotCached { packages: uncached }.into());
+        }
+    }
+
+    // prune mustn't take the entries away before they're referenced at the end
+    let _store_lock = match &virtual_store {
+        Some(virtual_store) => virtual_store.lock().await?,
+        None => None,
+    };
+
+    let progress = Mutex::new(
+        resumed
+            .iter()
+            .map(|package| progress_key(package))
+            .collect::<BTreeSet<_>>(),
+    );
+
+    let display = InstallProgress::new(app, "Installing Packages", packages.len());
+
+    let installs = packages
+        .iter()
+        .map(|&v| {
+            let display = &display;
+            let progress = &progress;
+            let optional = optional.contains(&v.name);
+            let virtual_store = virtual_store.as_ref();
+            let placement = hoisted.get(&hoist::key(v)).copied();
+
+            async move {
+                let line = display.package(&progress_key(v));
+
+                match install_extract_package(app, v, virtual_store, placement, &line).await {
+                    Ok(()) => {
+                        line.done();
+
+                        if !app.has_flag("global") {
+                            let mut installed = progress.lock().unwrap();
+                            installed.insert(progress_key(v));
+                            save_progress(app, &installed);
+                        }
+
+                        Ok(())
+                    }
+                    // a broken optional dependency shouldn't take the whole install down with it
+                    Err(error) if optional => {
+                        warn!(
+                            "skipping optional dependency {}: {}",
+                            v.name.bright_cyan(),
+                            error
+                        );
+
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    stream::iter(installs)
+        .buffer_unordered(app.concurrency())
+        .inspect(|_| display.inc())
+        .try_collect::<()>()
+        .await?;
+
+    display.finish("");
+
+    // nested copies go into the packages above them, once those are in place
+    // collected first, a filter held across the awaits below keeps the future from being `Send`
+    let nested = layout
+        .iter()
+        .filter(|placement| placement.depth > 0)
+        .collect::<Vec<_>>();
+
+    let mut nested = nested.into_iter().peekable();
+
+    while let Some(depth) = nested.peek().map(|placement| placement.depth) {
+        let mut level = vec![];
+
+        while let Some(placement) = nested.next_if(|placement| placement.depth == depth) {
+            level.push(placement);
+        }
+
+        let links = level
+            .into_iter()
+            .filter(|placement| linker::store_path(app, placement.package).exists())
+            .map(|placement| async move {
+                let destination = app.node_modules_dir.join(&placement.path);
+
+                linker::link_package(app, placement.package, &destination, placement.has_nested)?;
+
+                match bins::node_modules_of(&destination, placement.package) {
+                    Some(node_modules) => link_bins(node_modules, placement.package),
+                    None => Ok(()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        stream::iter(links)
+            .buffer_unordered(app.concurrency())
+            .try_collect::<()>()
+            .await?;
+    }
+
+    for (destination, placement) in own_layouts.iter() {
+        if !linker::store_path(app, placement.package).exists() {
+            continue;
+        }
+
+        match &virtual_store {
+            Some(virtual_store) => {
+                let entry = virtual_store.package_dir(placement.package).unwrap();
+
+                linker::link_virtual(app, placement.package, &entry, destination)?;
+            }
+            None => {
+                linker::link_package(app, placement.package, destination, placement.has_nested)?
+            }
+        }
+
+        if let Some(node_modules) = bins::node_modules_of(destination, placement.package) {
+            link_bins(node_modules, placement.package)?;
+        }
+    }
+
+    // every package is in place, builds can find their dependencies
+    let installed = packages
+        .iter()
+        .filter(|package| package.has_install_script)
+        .map(|&package| {
+            let directories: Vec<PathBuf> = match &virtual_store {
+                Some(virtual_store) => virtual_store.package_dir(package).into_iter().collect(),
+                None => layout
+                    .iter()
+                    .filter(|placement| hoist::key(placement.package) == hoist::key(package))
+                    .map(|placement| app.node_modules_dir.join(&placement.path))
+                    .collect(),
+            };
+
+            (package, directories)
+        })
+        .collect::<Vec<_>>();
+
+    let runs = install_scripts::run(app, &graph, &installed, optional)?;
+
+    install_scripts::print_summary(&runs);
+
+    // nothing left to resume
+    if !app.has_flag("global") {
+        save_progress(app, &BTreeSet::new());
+    }
+
+    if let Some(virtual_store) = &virtual_store {
+        virtual_store.reference(app);
+    }
+
+    for (path, _) in targets.iter() {
+        if std::fs::symlink_metadata(path).is_ok() {
+            managed.insert(path);
+        }
+    }
+
+    managed.save().await;
+
+    Ok(())
 }
 
-// Unix functions
-#[cfg(unix)]
-pub fn enable_ansi_support() -> Result<(), u32> {
+/// Download `packages` into the store and write `.pnp.cjs` for them, see `pnp`.
+async fn install_pnp(
+    app: &Arc<App>,
+    packages: &[&VoltPackage],
+    optional: &HashSet<String>,
+    graph: &Graph<'_>,
+) -> Result<()> {
+    let display = InstallProgress::new(app, "Fetching Packages", packages.len());
+
+    let downloads = packages
+        .iter()
+        .map(|&package| {
+            let display = &display;
+            let optional = optional.contains(&package.name);
+
+            async move {
+                let line = display.package(&progress_key(package));
+
+                match download_tarball(app, package, &line).await {
+                    Ok(()) => {
+                        line.done();
+
+                        Ok(())
+                    }
+                    Err(error) if optional => {
+                        warn!(
+                            "skipping optional dependency {}: {}",
+                            package.name.bright_cyan(),
+                            error
+                        );
+
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    stream::iter(downloads)
+        .buffer_unordered(app.concurrency())
+        .inspect(|_| display.inc())
+        .try_collect::<()>()
+        .await?;
+
+    display.finish("");
+
+    let stored = packages
+        .iter()
+        .copied()
+        .filter(|package| linker::store_path(app, package).exists())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+
+    pnp::write(app, &stored, graph)?;
+
+    // their directories are the store's, what they build would end up in it
+    let scripted = stored
+        .iter()
+        .filter(|package| package.has_install_script)
+        .count();
+
+    if scripted > 0 {
+        warn!(
+            "the install scripts of {} packages didn't run, Plug'n'Play installs don't run them",
+            scripted
+        );
+    }
+
+    timings::record(Phase::Link, start, stored.len() as u32);
+
+    if app.node_modules_dir.exists() {
+        info!(
+            "{} is left from an earlier install, Plug'n'Play installs don't use it",
+            app.node_modules_dir.display()
+        );
+    }
+
     Ok(())
 }
 
-pub fn check_peer_dependency(_package_name: &str) -> bool {
-    false
+/// Files the install of one package holds open at most: its connection, its tarball and two
+/// for every thread copying it into node_modules.
+const FILES_PER_INSTALL: usize = 20;
+
+/// Files left to everything else, the lockfile, the store lock and stdio among them.
+const RESERVED_FILES: usize = 64;
+
+/// Packages installed at once, as many as the open file limit leaves room for.
+fn file_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+    PERMITS.get_or_init(|| {
+        let available = fs_utils::open_file_limit().saturating_sub(RESERVED_FILES);
+
+        Semaphore::new((available / FILES_PER_INSTALL).clamp(1, 1024))
+    })
 }
 
 /// package all steps for installation into 1 convinient function.
-pub async fn install_extract_package(app: &Arc<App>, package: &VoltPackage) -> Result<()> {
-    // if there's an error (most likely a checksum verification error) while using insecure http, retry.
-    if download_tarball(&app, &package, false).await.is_err() {
-        // use https instead
-        download_tarball(&app, &package, true)
-            .await
-            .unwrap_or_else(|_| {
-                println!("failed to download tarball");
-                std::process::exit(1);
-            });
-    }
+///
+/// With `virtual_store` the package is symlinked to its entry there instead of linked from
+/// the store, if it belongs at the top of node_modules at all. Otherwise it's linked to its
+/// `placement` at the top, and only downloaded without one.
+pub async fn install_extract_package(
+    app: &Arc<App>,
+    package: &VoltPackage,
+    virtual_store: Option<&VirtualStore>,
+    placement: Option<&Placement<'_>>,
+    progress: &PackageProgress<'_>,
+) -> Result<()> {
+    // however high the concurrency, installs wait here instead of running out of files
+    let _permit = file_permits().acquire().await;
+
+    download_tarball(app, package, progress).await?;
+
+    progress.phase(Phase::Link);
+
+    let start = Instant::now();
+
+    let top_level = match virtual_store {
+        Some(virtual_store) => {
+            let entry = virtual_store.materialize(app, package)?;
+            let top_level = virtual_store.is_top_level(package);
 
-    // generate the package's script
-    generate_script(&app, package);
+            if top_level {
+                let destination = app.node_modules_dir.join(package.install_name());
+
+                linker::link_virtual(app, package, &entry, &destination)?;
+            }
+
+            top_level
+        }
+        None => match placement {
+            Some(placement) => {
+                let destination = app.node_modules_dir.join(&placement.path);
+
+                linker::link_package(app, package, &destination, placement.has_nested)?;
+
+                true
+            }
+            None => false,
+        },
+    };
+
+    timings::record(Phase::Link, start, 1);
+
+    // global installs get their bins from `create_global_shims`, nested packages get theirs
+    // once they're placed
+    if top_level && !app.has_flag("global") {
+        link_bins(&app.node_modules_dir, package)?;
+    }
 
     // let directory = &app
     //     .volt_dir
@@ -848,25 +1911,94 @@ pub async fn install_extract_package(app: &Arc<App>, package: &VoltPackage) -> R
     Ok(())
 }
 
+/// Resolve the trees of `packages`, with at most `concurrency` requests in flight.
+///
+/// Responses are merged as they arrive and only their resolved version is kept, so the other
+/// versions a response may carry never pile up. Packages the volt CDN doesn't have or fails
+/// to serve are resolved from registry metadata instead.
 pub async fn fetch_dep_tree(
-    packages: &Vec<Package>,
-    progress_bar: &ProgressBar,
-) -> Result<(Vec<VoltResponse>, f32)> {
+    packages: &[Package],
+    registries: &Registries,
+    before: Option<&DateTime<Utc>>,
+    concurrency: usize,
+    progress: &InstallProgress,
+) -> Result<(HashMap<String, VoltPackage>, f32)> {
     let start = Instant::now();
-    if packages.len() > 1 {
-        Ok((
-            get_volt_response_multi(packages.clone(), progress_bar)
-                .await
-                .into_iter()
-                .collect::<Result<Vec<_>>>()?,
-            start.elapsed().as_secs_f32(),
-        ))
-    } else {
-        Ok((
-            vec![get_volt_response(packages[0].clone()).await?],
-            start.elapsed().as_secs_f32(),
-        ))
+
+    let requests = packages
+        .iter()
+        .map(|package| async move {
+            let line = progress.package(&package.name);
+            line.phase(Phase::Resolve);
+
+            // the CDN only serves current trees of public npm packages, history, other
+            // registries and the metadata cache are resolved from the registry
+            if before.is_some()
+                || registries.for_package(&package.name) != NPM_REGISTRY
+                || registries.network != NetworkMode::Online
+            {
+                return npm::get_npm_response(package, registries, before)
+                    .await
+                    .map(|response| (response, false));
+            }
+
+            // the CDN lags behind the registry and misses packages, the registry has them all
+            match get_volt_response(package.clone(), registries).await {
+                Ok(response) => Ok((response, false)),
+                Err(_) => npm::get_npm_response(package, registries, before)
+                    .await
+                    .map(|response| (response, true)),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut responses = stream::iter(requests).buffer_unordered(concurrency);
+
+    let mut dependencies = HashMap::new();
+    let mut uncached = BTreeSet::new();
+    let mut fallbacks = 0;
+
+    while let Some(response) = responses.next().await {
+        let mut response = match response {
+            Ok((response, fallback)) => {
+                fallbacks += fallback as usize;
+                response
+            }
+            // list everything an offline install misses instead of only the first package
+            Err(error) => match error.downcast::<VoltError>() {
+                Ok(VoltError::NotCached { packages }) => {
+                    uncached.extend(packages);
+                    continue;
+                }
+                Ok(error) => return Err(error.into()),
+                Err(error) => return Err(error),
+            },
+        };
+
+        progress.inc();
+
+        if let Some(tree) = response.versions.remove(&response.version) {
+            dependencies.extend(tree);
+        }
     }
+
+    if !uncached.is_empty() {
+        return Err(VoltError::NotCached {
+            packages: uncached.into_iter().collect(),
+        }
+        .into());
+    }
+
+    if fallbacks > 0 {
+        info!(
+            "{} packages weren't on the volt CDN and were resolved from the registry",
+            fallbacks
+        );
+    }
+
+    timings::record(Phase::Resolve, start, dependencies.len() as u32);
+
+    Ok((dependencies, start.elapsed().as_secs_f32()))
 }
 
 pub fn print_elapsed(length: usize, elapsed: f32) {
@@ -902,3 +2034,280 @@ pub fn print_elapsed(length: usize, elapsed: f32) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// The memory a tarball may take in low-memory mode, as `VoltConfig::low_memory` documents.
+    const LOW_MEMORY_CEILING: usize = 1024 * 1024;
+
+    /// Counts the heap of every thread separately, other tests running meanwhile don't count.
+    struct Counting;
+
+    thread_local! {
+        static CURRENT: Cell<usize> = const { Cell::new(0) };
+        static PEAK: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // thread locals can be gone while a thread shuts down, that memory isn't counted
+            let _ = CURRENT.try_with(|current| {
+                current.set(current.get() + layout.size());
+                let _ = PEAK.try_with(|peak| peak.set(peak.get().max(current.get())));
+            });
+
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = CURRENT
+                .try_with(|current| current.set(current.get().saturating_sub(layout.size())));
+
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    /// The most memory `f` took at once on this thread.
+    fn peak_memory(f: impl FnOnce()) -> usize {
+        let start = CURRENT.with(Cell::get);
+        PEAK.with(|peak| peak.set(start));
+
+        f();
+
+        PEAK.with(Cell::get) - start
+    }
+
+    /// A gzipped package of `size` bytes that don't compress, like the binaries in large ones.
+    fn write_tarball(path: &Path, size: usize) {
+        use rand::{Rng, SeedableRng};
+
+        let mut data = vec![0; size];
+        rand::rngs::StdRng::seed_from_u64(1).fill(&mut data[..]);
+
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        builder
+            .append_data(&mut header, "package/bin/large.node", &data[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn streamed_tarball_stays_under_the_low_memory_ceiling() {
+        let directory =
+            std::env::temp_dir().join(format!("volt-low-memory-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let path = directory.join("large.tgz");
+        write_tarball(&path, 8 * 1024 * 1024);
+
+        // reading it whole is what streaming avoids, and shows the counting works
+        let buffered = peak_memory(|| {
+            std::fs::read(&path).unwrap();
+        });
+
+        let tarball = Tarball::File(path);
+
+        let hashing = peak_memory(|| {
+            tarball.integrity(Algorithm::Sha512).unwrap();
+        });
+
+        let unpacking = peak_memory(|| {
+            tarball.unpack(&directory.join("package")).unwrap();
+        });
+
+        std::fs::remove_dir_all(&directory).ok();
+
+        assert!(
+            buffered > LOW_MEMORY_CEILING,
+            "reading took {} bytes",
+            buffered
+        );
+        assert!(
+            hashing < LOW_MEMORY_CEILING,
+            "hashing took {} bytes",
+            hashing
+        );
+        assert!(
+            unpacking < LOW_MEMORY_CEILING,
+            "unpacking took {} bytes",
+            unpacking
+        );
+    }
+
+    /// A package locked with `integrity`, as the lockfile records it.
+    fn locked(integrity: &str) -> VoltPackage {
+        serde_json::from_value(serde_json::json!({
+            "name": "pkg",
+            "version": "1.0.0",
+            "tarball": "https://registry.npmjs.org/pkg/-/pkg-1.0.0.tgz",
+            "bin": null,
+            "integrity": integrity,
+            "peer_dependencies": null,
+            "dependencies": null,
+        }))
+        .unwrap()
+    }
+
+    /// `integrity` in the hex form the lockfile stores.
+    fn hex(integrity: &Integrity) -> String {
+        let (algorithm, hex) = integrity.to_hex();
+
+        format!("{}-{}", algorithm, hex)
+    }
+
+    #[test]
+    fn converts_hex_to_integrity() {
+        assert_eq!(
+            hex_to_integrity("sha512-0a0b").as_deref(),
+            Some("sha512-Cgs=")
+        );
+        // lockfiles from before the algorithm was recorded
+        assert_eq!(hex_to_integrity("0a0b").as_deref(), Some("sha1-Cgs="));
+        assert_eq!(hex_to_integrity("sha512-0a0"), None);
+        assert_eq!(hex_to_integrity("sha512-zz"), None);
+    }
+
+    #[test]
+    fn verifies_tarballs_against_their_integrity() {
+        let tarball = Tarball::Bytes(bytes::Bytes::from_static(b"the tarball"));
+
+        let sha512 = tarball.integrity(Algorithm::Sha512).unwrap();
+        assert!(verify_integrity(&locked(&hex(&sha512)), &tarball).is_ok());
+
+        let sha1 = tarball.integrity(Algorithm::Sha1).unwrap();
+        assert!(verify_integrity(&locked(&hex(&sha1)), &tarball).is_ok());
+        assert!(verify_integrity(&locked(&sha1.to_hex().1), &tarball).is_ok());
+
+        let other = Tarball::Bytes(bytes::Bytes::from_static(b"another tarball"));
+        let other = hex(&other.integrity(Algorithm::Sha512).unwrap());
+
+        for integrity in [other.as_str(), "", "sha512-zz"] {
+            let error = verify_integrity(&locked(integrity), &tarball).unwrap_err();
+
+            assert!(matches!(
+                error.downcast_ref::<VoltError>(),
+                Some(VoltError::IntegrityMismatch { .. })
+            ));
+        }
+    }
+
+    /// The abbreviated document of `name` with `versions`, each depending on `dependencies`.
+    fn document(name: &str, versions: &[&str], dependencies: serde_json::Value) -> Vec<u8> {
+        let versions = versions
+            .iter()
+            .map(|version| {
+                let manifest = serde_json::json!({
+                    "name": name,
+                    "version": version,
+                    "dependencies": dependencies,
+                    "dist": {
+                        "tarball": format!("https://registry.npmjs.org/{0}/-/{0}-{1}.tgz", name, version),
+                        "integrity": "sha512-AAAA",
+                    },
+                });
+
+                (version.to_string(), manifest)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::to_vec(&serde_json::json!({
+            "name": name,
+            "dist-tags": { "latest": versions.keys().next_back().unwrap() },
+            "versions": versions,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn installs_the_transitive_versions_volt_lock_pins() {
+        let directory =
+            std::env::temp_dir().join(format!("volt-locked-tree-{}", std::process::id()));
+        let cache = registry_cache::RegistryCache::new(&directory);
+
+        let publish = |name: &str, versions: &[&str], dependencies: serde_json::Value| {
+            let path = cache.packument_path(name, true);
+            let document = document(name, versions, dependencies);
+
+            cache.write(&path, &document).unwrap();
+        };
+
+        publish("dep", &["1.0.0"], serde_json::json!({ "ms": "^1.0.0" }));
+        publish("ms", &["1.0.0"], serde_json::json!({}));
+
+        let registries = Registries {
+            network: NetworkMode::Offline,
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+
+        let packages = [Package {
+            name: String::from("dep"),
+            version: Some(String::from("1.0.0")),
+            alias: None,
+        }];
+
+        let resolve = || async {
+            let mut response = npm::get_npm_response(&packages[0], &registries, None)
+                .await
+                .unwrap();
+
+            response.versions.remove("1.0.0").unwrap()
+        };
+
+        let mut lock_file = LockFile::new(directory.join("volt.lock"));
+
+        for package in resolve().await.values() {
+            lock_file
+                .dependencies
+                .insert(lock_id(package), lock_entry(package, false));
+        }
+
+        // resolving again picks up what was published since
+        publish("ms", &["1.0.0", "1.1.0"], serde_json::json!({}));
+
+        assert!(resolve().await.contains_key("ms@1.1.0"));
+
+        let tree = locked_tree(&lock_file, &packages, |lock| {
+            lock.manifest.as_ref()?;
+
+            Some(zero_install::locked_package(lock))
+        })
+        .unwrap();
+
+        let mut installed = tree.keys().collect::<Vec<_>>();
+        installed.sort();
+
+        assert_eq!(installed, ["dep@1.0.0", "ms@1.0.0"]);
+        assert_eq!(tree["dep@1.0.0"].ranges.as_ref().unwrap()["ms"], "^1.0.0");
+
+        // entries from before the manifest was recorded are resolved again
+        for lock in lock_file.dependencies.values_mut() {
+            lock.manifest = None;
+        }
+
+        assert!(locked_tree(&lock_file, &packages, |lock| {
+            lock.manifest.as_ref()?;
+
+            Some(zero_install::locked_package(lock))
+        })
+        .is_none());
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,410 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Versions of node volt installs and runs scripts with.
+//!
+//! Official builds are downloaded from `NODE_DIST`, checked against the `SHASUMS256.txt`
+//! published next to them and kept in `<volt dir>/node/v<version>`. A project pins the node
+//! its scripts run with by `node` in volt.toml or a `.node-version` file in the project or a
+//! directory above it, `volt run` puts that version first on PATH after the bins of
+//! node_modules and installs it when it's missing. A pin is a version like `18.12.0`, a prefix
+//! like `18`, any range, `lts`, `lts/<codename>` or `latest`. A version or range is satisfied
+//! by an installed version without asking the dist server, `lts` and `latest` are looked up.
+//!
+//! Linux and macOS get the `.tar.gz` build with npm and npx, Windows only `node.exe`.
+
+use std::path::{Path, PathBuf};
+
+use crate::core::model::http_manager::client;
+use crate::core::utils::compat::{node_cpu, node_os};
+use crate::core::utils::constants::{NODE_DIST, NODE_VERSION_FILE, PROGRESS_CHARS};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::logging;
+use crate::core::utils::retry::{
+    is_transient_isahc, is_transient_status, retry, retry_after, Failure,
+};
+use crate::App;
+
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use futures::AsyncReadExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use isahc::AsyncReadResponseExt;
+use miette::Result;
+use node_semver::{Range, Version};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use tracing::{debug, info};
+
+/// A release listed in `index.json` of the dist server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    /// `v18.12.0`.
+    pub version: String,
+    pub date: String,
+    /// Codename of an LTS release, `false` for the others.
+    #[serde(default)]
+    lts: serde_json::Value,
+}
+
+impl Release {
+    /// The version without its `v`, if it parses.
+    pub fn semver(&self) -> Option<Version> {
+        Version::parse(self.version.trim_start_matches('v')).ok()
+    }
+
+    pub fn lts(&self) -> Option<&str> {
+        self.lts.as_str()
+    }
+}
+
+/// Where the installed versions are kept.
+pub fn versions_dir(app: &App) -> PathBuf {
+    app.volt_dir.join("node")
+}
+
+/// Directory of an installed version that goes on PATH.
+pub fn bin_dir(app: &App, version: &Version) -> PathBuf {
+    let directory = versions_dir(app).join(format!("v{}", version));
+
+    if cfg!(windows) {
+        directory
+    } else {
+        directory.join("bin")
+    }
+}
+
+/// Installed versions, newest first.
+pub fn installed(app: &App) -> Vec<Version> {
+    let mut versions = std::fs::read_dir(versions_dir(app))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+
+                    Version::parse(name.strip_prefix('v')?).ok()
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    versions
+}
+
+/// The version the project pins and where it's pinned, volt.toml beats `.node-version`.
+pub fn pinned(app: &App) -> Option<(String, String)> {
+    if let Some(spec) = app.config.node.as_deref().map(str::trim) {
+        if !spec.is_empty() {
+            return Some((spec.to_string(), String::from("volt.toml")));
+        }
+    }
+
+    app.current_dir.ancestors().find_map(|directory| {
+        let path = directory.join(NODE_VERSION_FILE);
+        let data = std::fs::read_to_string(&path).ok()?;
+        let spec = data.lines().next()?.trim();
+
+        (!spec.is_empty()).then(|| (spec.to_string(), path.display().to_string()))
+    })
+}
+
+/// Whether `release` is one `spec` asks for.
+fn matches(spec: &str, release: &Release) -> bool {
+    let version = match release.semver() {
+        Some(version) => version,
+        None => return false,
+    };
+
+    match spec.to_lowercase().as_str() {
+        "latest" | "current" | "node" => true,
+        "lts" | "lts/*" => release.lts().is_some(),
+        lts if lts.starts_with("lts/") => release
+            .lts()
+            .is_some_and(|codename| codename.eq_ignore_ascii_case(&lts[4..])),
+        _ => satisfies(spec, &version),
+    }
+}
+
+/// Whether `version` satisfies a version or range, with or without a leading `v`.
+fn satisfies(spec: &str, version: &Version) -> bool {
+    Range::parse(spec.trim_start_matches('v')).is_ok_and(|range| version.satisfies(&range))
+}
+
+/// The newest installed version satisfying `spec`, `lts` and `latest` need the release list.
+pub fn find_installed(app: &App, spec: &str) -> Option<Version> {
+    installed(app)
+        .into_iter()
+        .find(|version| satisfies(spec, version))
+}
+
+/// Every release of node, newest first.
+pub async fn releases(app: &App) -> Result<Vec<Release>> {
+    let url = format!("{}/index.json", NODE_DIST);
+    let data = download(app, &url, None).await?;
+
+    Ok(serde_json::from_slice(&data).map_err(|_| VoltError::DeserializeError)?)
+}
+
+/// The newest release `spec` asks for.
+pub async fn resolve(app: &App, spec: &str) -> Result<Version> {
+    releases(app)
+        .await?
+        .iter()
+        .find(|release| matches(spec, release))
+        .and_then(Release::semver)
+        .ok_or_else(|| {
+            VoltError::UnknownNodeVersion {
+                spec: spec.to_string(),
+            }
+            .into()
+        })
+}
+
+/// The installed version `spec` asks for, downloading the newest one when none is installed.
+pub async fn ensure(app: &App, spec: &str) -> Result<Version> {
+    if let Some(version) = find_installed(app, spec) {
+        debug!("node {} satisfies `{}`", version, spec);
+        return Ok(version);
+    }
+
+    let version = resolve(app, spec).await?;
+
+    install(app, &version).await?;
+
+    Ok(version)
+}
+
+/// Directory of the pinned version to put on PATH, installing it first when it's missing.
+pub async fn pinned_bin(app: &App) -> Result<Option<PathBuf>> {
+    let (spec, origin) = match pinned(app) {
+        Some(pin) => pin,
+        None => return Ok(None),
+    };
+
+    let version = ensure(app, &spec).await?;
+
+    debug!("running with node {}, pinned by {}", version, origin);
+
+    Ok(Some(bin_dir(app, &version)))
+}
+
+/// Name of the build for this machine under `<dist>/v<version>/`.
+fn artifact(version: &Version) -> String {
+    // node calls 32-bit arm `armv7l` in its builds
+    let cpu = match node_cpu() {
+        "arm" => "armv7l",
+        cpu => cpu,
+    };
+
+    match node_os() {
+        "win32" => format!("win-{}/node.exe", cpu),
+        os => format!("node-v{}-{}-{}.tar.gz", version, os, cpu),
+    }
+}
+
+/// Download, verify and unpack `version`, unless it's installed already.
+pub async fn install(app: &App, version: &Version) -> Result<()> {
+    let directory = versions_dir(app).join(format!("v{}", version));
+
+    if directory.exists() {
+        return Ok(());
+    }
+
+    let artifact = artifact(version);
+    let base = format!("{}/v{}", NODE_DIST, version);
+
+    info!("installing node {}", format!("v{}", version).bright_cyan());
+
+    let sums = download(app, &format!("{}/SHASUMS256.txt", base), None).await?;
+    let expected = String::from_utf8_lossy(&sums)
+        .lines()
+        .find_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+
+            (name.trim() == artifact).then(|| hash.to_string())
+        })
+        .ok_or_else(|| VoltError::NodeChecksumMissing {
+            file: artifact.clone(),
+        })?;
+
+    let bar = ProgressBar::new(0);
+
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .progress_chars(PROGRESS_CHARS)
+            .template(&format!(
+                "{} [{{bar:40.magenta/blue}}] {{bytes:.blue}}/{{total_bytes:.blue}}",
+                "Downloading Node".bright_blue()
+            )),
+    );
+
+    logging::attach(&bar);
+
+    let data = download(app, &format!("{}/{}", base, artifact), Some(&bar)).await;
+
+    bar.finish_and_clear();
+
+    logging::detach();
+
+    let data = data?;
+
+    let actual = Sha256::digest(&data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    if actual != expected {
+        return Err(VoltError::NodeChecksumMismatch {
+            file: artifact,
+            expected,
+            actual,
+        }
+        .into());
+    }
+
+    unpack(&directory, &artifact, &data)?;
+
+    println!(
+        "{}: installed node {} in {}",
+        "success".bright_green(),
+        format!("v{}", version).bright_cyan(),
+        directory.display()
+    );
+
+    Ok(())
+}
+
+/// Put a verified build in `directory`, through a staging directory so an interrupted
+/// install never looks complete.
+fn unpack(directory: &Path, artifact: &str, data: &[u8]) -> Result<()> {
+    let parent = directory.parent().unwrap_or(directory);
+    let staging = parent.join(format!(".staging-{}", std::process::id()));
+
+    std::fs::remove_dir_all(&staging).ok();
+    std::fs::create_dir_all(&staging).map_err(VoltError::CreateDirError)?;
+
+    // the tarball holds a single `node-v<version>-<os>-<cpu>` directory
+    let unpacked = match artifact.strip_suffix(".tar.gz") {
+        Some(name) => {
+            Archive::new(GzDecoder::new(data))
+                .unpack(&staging)
+                .map_err(|e| VoltError::ExtractError {
+                    name: artifact.to_string(),
+                    source: e,
+                })?;
+
+            staging.join(name)
+        }
+        None => {
+            let path = staging.join("node.exe");
+
+            std::fs::write(&path, data).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: path.display().to_string(),
+            })?;
+
+            staging.clone()
+        }
+    };
+
+    let renamed = std::fs::rename(&unpacked, directory);
+
+    std::fs::remove_dir_all(&staging).ok();
+
+    // another volt may have installed the same version meanwhile
+    match renamed {
+        Err(_) if directory.exists() => Ok(()),
+        Err(e) => Err(VoltError::WriteFileError {
+            source: e,
+            name: directory.display().to_string(),
+        }
+        .into()),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// The body of `url`, retried like registry requests, counted on `bar` when given.
+async fn download(app: &App, url: &str, bar: Option<&ProgressBar>) -> Result<Vec<u8>> {
+    let bytes = retry(&app.registries.retries, || async {
+        let mut response = client().get_async(url).await.map_err(|e| {
+            if is_transient_isahc(&e) {
+                Failure::Transient(VoltError::NetworkError(e), None)
+            } else {
+                Failure::Fatal(VoltError::NetworkError(e))
+            }
+        })?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let error = VoltError::NodeDownloadFailed {
+                url: url.to_string(),
+                code: status.as_str().to_string(),
+            };
+
+            return Err(if is_transient_status(status) {
+                Failure::Transient(error, retry_after(response.headers()))
+            } else {
+                Failure::Fatal(error)
+            });
+        }
+
+        let download_error = |e| {
+            Failure::Transient(
+                VoltError::TarballDownloadError {
+                    url: url.to_string(),
+                    source: e,
+                },
+                None,
+            )
+        };
+
+        let mut bytes = vec![];
+
+        let bar = match bar {
+            Some(bar) => bar,
+            None => {
+                response.copy_to(&mut bytes).await.map_err(download_error)?;
+                return Ok(bytes);
+            }
+        };
+
+        bar.set_position(0);
+        bar.set_length(response.body().len().unwrap_or_default());
+
+        let body = response.body_mut();
+        let mut chunk = vec![0; 64 * 1024];
+
+        loop {
+            let read = body.read(&mut chunk).await.map_err(download_error)?;
+
+            if read == 0 {
+                break;
+            }
+
+            bytes.extend_from_slice(&chunk[..read]);
+            bar.inc(read as u64);
+        }
+
+        Ok(bytes)
+    })
+    .await?;
+
+    Ok(bytes)
+}
@@ -1,50 +1,758 @@
-use miette::Result;
-
 use crate::commands::add::Package;
+use crate::core::model::http_manager::{
+    get_full_packument, get_packument, GetPackageError, Registries,
+};
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package::{Packument, PackumentVersion};
+use crate::core::utils::voltapi::{VoltPackage, VoltResponse};
+use crate::core::utils::{installed_version, integrity_to_hex, satisfies};
 
-// use crate::core::utils::constants::MAX_RETRIES;
-// use crate::core::utils::errors::VoltError;
-// use crate::core::utils::voltapi::VoltPackage;
-// use futures::stream::FuturesOrdered;
-// use futures::TryStreamExt;
-// use isahc::http::StatusCode;
-// use isahc::AsyncReadResponseExt;
-// use isahc::Request;
-// use isahc::RequestExt;
-// use miette::Result;
-// use semver_rs::Version;
-// use serde_json::Value;
-// use ssri::{Algorithm, Integrity};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use colored::Colorize;
+use futures::stream::{self, FuturesUnordered};
+use futures::StreamExt;
+use miette::Result;
+use node_semver::{Range, Version};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tracing::{info, warn};
 
+/// Parse `name@range` specifiers, e.g. `react`, `react@^17.0.1`, `@types/node@>=14 <16`,
+/// `lodash@4.x`, `next@canary` or the alias `my-lodash@npm:lodash@^4`.
+///
+/// Ranges are validated up front so a typo fails before anything is fetched.
 pub fn parse_versions(packages: &Vec<String>) -> Result<Vec<Package>> {
     let mut parsed: Vec<Package> = vec![];
 
     for package in packages.iter() {
-        let split = package.split("@").map(|s| s.trim()).collect::<Vec<&str>>();
-        let length = split.len();
-
-        if length == 1 {
-            parsed.push(Package {
-                name: split[0].to_string(),
-                version: None,
-            });
-        } else if length == 2 && !package.contains("/") {
-            parsed.push(Package {
-                name: split[0].to_string(),
-                version: Some(split[1].to_string()),
-            });
-        } else if length == 2 && package.contains("/") {
-            parsed.push(Package {
-                name: format!("@{}", split[1]),
-                version: None,
-            });
-        } else if length == 3 && package.contains("/") {
-            parsed.push(Package {
-                name: format!("@{}", split[1]),
-                version: Some(split[2].to_string()),
-            });
+        let spec = package.trim();
+
+        // my-lodash@npm:lodash@^4 installs lodash as `my-lodash`
+        let (alias, package) = match spec.find("@npm:") {
+            Some(index) if index > 0 => (Some(&spec[..index]), &spec[index + 5..]),
+            _ => (None, spec),
+        };
+
+        if alias.is_some()
+            && (package.is_empty() || package.starts_with('@') && !package.contains('/'))
+        {
+            return Err(VoltError::InvalidAlias {
+                spec: spec.to_string(),
+            }
+            .into());
         }
+
+        // the `@` of a scope is never a version separator
+        let (name, version) = match package.rfind('@') {
+            Some(index) if index > 0 => (&package[..index], Some(package[index + 1..].trim())),
+            _ => (package, None),
+        };
+
+        let version = version.filter(|version| !version.is_empty() && *version != "latest");
+
+        if let Some(version) = version {
+            if !is_dist_tag(version) {
+                Range::parse(version).map_err(|_| VoltError::InvalidVersionRange {
+                    package_name: name.to_string(),
+                    range: version.to_string(),
+                })?;
+            }
+        }
+
+        parsed.push(Package {
+            name: name.to_string(),
+            version: version.map(|version| version.to_string()),
+            alias: alias.map(|alias| alias.to_string()),
+        });
     }
 
     Ok(parsed)
 }
+
+/// Whether the version part of a specifier names a dist-tag (`next`, `beta`) rather than a range.
+///
+/// Like npm, anything that parses as a range is a range, tags can't shadow them.
+pub fn is_dist_tag(spec: &str) -> bool {
+    Range::parse(spec).is_err()
+        && spec.starts_with(|c: char| c.is_ascii_alphabetic())
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Narrow a tree served by the Volt CDN down to the version `package` asked for.
+///
+/// The CDN only carries the trees of recent versions, when none of them satisfies the range
+/// the tree is resolved from the npm registry instead.
+pub async fn select_version(
+    response: VoltResponse,
+    package: &Package,
+    registries: &Registries,
+) -> Result<VoltResponse> {
+    let range = match package.version.as_deref() {
+        Some(range) => range,
+        None => return Ok(response),
+    };
+
+    // the CDN only knows what `latest` points to, other tags come from the registry
+    if is_dist_tag(range) {
+        return get_npm_response(package, registries, None).await;
+    }
+
+    let parsed = Range::parse(range).map_err(|_| VoltError::InvalidVersionRange {
+        package_name: package.name.clone(),
+        range: range.to_string(),
+    })?;
+
+    let satisfies = |version: &str| {
+        Version::parse(version)
+            .map(|v| v.satisfies(&parsed))
+            .unwrap_or(false)
+    };
+
+    // like npm, prefer the latest version whenever it is in range
+    if satisfies(&response.version) {
+        return Ok(response);
+    }
+
+    let version = response
+        .versions
+        .keys()
+        .filter_map(|key| Version::parse(key).ok().map(|version| (version, key)))
+        .filter(|(version, _)| version.satisfies(&parsed))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, key)| key.clone());
+
+    match version {
+        Some(version) => Ok(VoltResponse {
+            version,
+            versions: response.versions,
+        }),
+        None => get_npm_response(package, registries, None).await,
+    }
+}
+
+/// Parse the timestamp given to `--before`, either RFC 3339 or a plain `YYYY-MM-DD` date
+/// (midnight UTC).
+pub fn parse_before(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| Utc.from_utc_datetime(&midnight))
+        .ok_or_else(|| {
+            VoltError::InvalidDate {
+                value: value.to_string(),
+            }
+            .into()
+        })
+}
+
+/// Drop every version published after `before`, the same way npm's `before` option does.
+///
+/// Dist-tags pointing at dropped versions are removed, `latest` falls back to the newest
+/// remaining stable version.
+pub fn restrict_before(packument: &mut Packument, before: &DateTime<Utc>) {
+    let time = &packument.time;
+
+    packument.versions.retain(|version, _| {
+        time.get(version)
+            .and_then(|published| DateTime::parse_from_rfc3339(published).ok())
+            .is_some_and(|published| published <= *before)
+    });
+
+    let versions = &packument.versions;
+
+    packument
+        .dist_tags
+        .retain(|_, version| versions.contains_key(version));
+
+    if !packument.dist_tags.contains_key("latest") {
+        let latest = packument
+            .versions
+            .keys()
+            .filter_map(|version| Version::parse(version).ok().map(|v| (v, version)))
+            .filter(|(v, _)| v.pre_release.is_empty())
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, version)| version.clone());
+
+        if let Some(latest) = latest {
+            packument.dist_tags.insert(String::from("latest"), latest);
+        }
+    }
+}
+
+/// Fetch the document of a package, restricted to what was published before `before`.
+async fn fetch_packument(
+    registries: &Registries,
+    name: &str,
+    before: Option<&DateTime<Utc>>,
+) -> Result<Packument, GetPackageError> {
+    match before {
+        Some(before) => {
+            let mut packument = get_full_packument(registries, name).await?;
+            restrict_before(&mut packument, before);
+            Ok(packument)
+        }
+        None => get_packument(registries, name).await,
+    }
+}
+
+/// Pick the version of a package that satisfies `range`.
+///
+/// The `latest` dist-tag is preferred when it satisfies the range, otherwise the highest matching
+/// version is used, which is the same choice npm makes.
+pub fn resolve_version<'a>(packument: &'a Packument, range: &str) -> Result<&'a PackumentVersion> {
+    let latest = packument
+        .dist_tags
+        .get("latest")
+        .and_then(|latest| packument.versions.get(latest));
+
+    if range.is_empty() || range == "latest" {
+        return Ok(latest.ok_or_else(|| VoltError::NoMatchingVersion {
+            package_name: packument.name.clone(),
+            range: String::from("latest"),
+        })?);
+    }
+
+    if is_dist_tag(range) {
+        return Ok(packument
+            .dist_tags
+            .get(range)
+            .and_then(|version| packument.versions.get(version))
+            .ok_or_else(|| VoltError::UnknownDistTag {
+                package_name: packument.name.clone(),
+                tag: range.to_string(),
+            })?);
+    }
+
+    let parsed = Range::parse(range).map_err(|_| VoltError::InvalidVersionRange {
+        package_name: packument.name.clone(),
+        range: range.to_string(),
+    })?;
+
+    if let Some(latest) = latest {
+        if satisfies(&latest.version, range) {
+            return Ok(latest);
+        }
+    }
+
+    Ok(
+        max_satisfying(packument, &[parsed]).ok_or_else(|| VoltError::NoMatchingVersion {
+            package_name: packument.name.clone(),
+            range: range.to_string(),
+        })?,
+    )
+}
+
+/// Highest version of a package that satisfies every one of `ranges`.
+pub fn max_satisfying<'a>(
+    packument: &'a Packument,
+    ranges: &[Range],
+) -> Option<&'a PackumentVersion> {
+    packument
+        .versions
+        .values()
+        .filter_map(|version| Version::parse(&version.version).ok().map(|v| (v, version)))
+        .filter(|(v, _)| ranges.iter().all(|range| v.satisfies(range)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, version)| version)
+}
+
+/// Convert a version from a registry document into the `VoltPackage` format served by the Volt CDN.
+pub fn to_volt_package(version: &PackumentVersion) -> Result<VoltPackage> {
+    let integrity = if version.dist.integrity.is_empty() {
+        format!("sha1-{}", version.dist.shasum)
+    } else {
+        integrity_to_hex(&version.dist.integrity)?
+    };
+
+    let names = |map: &HashMap<String, String>| {
+        if map.is_empty() {
+            None
+        } else {
+            Some(map.keys().cloned().collect::<Vec<String>>())
+        }
+    };
+
+    let ranges = version
+        .dependencies
+        .iter()
+        .chain(version.optional_dependencies.iter())
+        .map(|(name, range)| (name.clone(), range.clone()))
+        .collect::<HashMap<_, _>>();
+
+    Ok(VoltPackage {
+        name: version.name.clone(),
+        version: version.version.clone(),
+        tarball: version.dist.tarball.clone(),
+        bin: version.bin_map(),
+        integrity,
+        peer_dependencies: names(&version.peer_dependencies),
+        dependencies: names(&version.dependencies),
+        optional_dependencies: names(&version.optional_dependencies),
+        has_install_script: version.runs_install_scripts(),
+        alias: None,
+        ranges: (!ranges.is_empty()).then_some(ranges),
+        platform: Some(version.platform()),
+        engines: Some(version.engines_map()),
+    })
+}
+
+/// Resolve the dependency tree of a package from npm registry metadata.
+///
+/// A range already met by a resolved version reuses it, another version of the same package
+/// is only resolved for a range none of them meets, and `core::utils::hoist` nests it where
+/// it's needed. With `before` set, only versions published before that moment are considered.
+pub async fn get_npm_response(
+    package: &Package,
+    registries: &Registries,
+    before: Option<&DateTime<Utc>>,
+) -> Result<VoltResponse> {
+    let mut packuments: HashMap<String, Packument> = HashMap::new();
+    let mut failed = HashMap::new();
+    // packages missing from the metadata cache, all of them are listed in the end
+    let mut uncached: HashSet<String> = HashSet::new();
+    let mut missing_required: BTreeSet<String> = BTreeSet::new();
+    // keyed by `name@version`
+    let mut resolved: HashMap<String, VoltPackage> = HashMap::new();
+    // versions resolved of every name
+    let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+    let mut root_version: Option<String> = None;
+
+    // (name, range, optional)
+    let mut queue: Vec<(String, String, bool)> = vec![(
+        package.name.clone(),
+        package.version.clone().unwrap_or_default(),
+        false,
+    )];
+
+    while !queue.is_empty() {
+        let missing: HashSet<String> = queue
+            .iter()
+            .map(|(name, _, _)| name)
+            .filter(|name| !packuments.contains_key(*name) && !uncached.contains(*name))
+            .cloned()
+            .collect();
+
+        let fetched = missing
+            .into_iter()
+            .map(|name| async move {
+                let packument = fetch_packument(registries, &name, before).await;
+                (name, packument)
+            })
+            .collect::<FuturesUnordered<_>>()
+            .collect::<Vec<_>>()
+            .await;
+
+        for (name, packument) in fetched {
+            match packument {
+                Ok(packument) => {
+                    packuments.insert(name, packument);
+                }
+                Err(GetPackageError::NotCached(_)) => {
+                    uncached.insert(name);
+                }
+                Err(error) => {
+                    failed.insert(name, error);
+                }
+            }
+        }
+
+        let mut next = vec![];
+
+        for (name, range, optional) in queue.drain(..) {
+            if by_name
+                .get(&name)
+                .is_some_and(|versions| satisfied(versions, &range))
+            {
+                continue;
+            }
+
+            if uncached.contains(&name) {
+                if !optional {
+                    missing_required.insert(name);
+                }
+
+                continue;
+            }
+
+            let version = match packuments.get(&name) {
+                Some(packument) => resolve_version(packument, &range),
+                None => Err(failed.remove(&name).map(Into::into).unwrap_or_else(|| {
+                    miette::miette!("failed to fetch registry metadata for {}", name)
+                })),
+            };
+
+            let version = match version {
+                Ok(version) => version,
+                // optional dependencies are allowed to be missing from the registry
+                Err(_) if optional => continue,
+                Err(error) => return Err(error),
+            };
+
+            if root_version.is_none() {
+                root_version = Some(version.version.clone());
+            }
+
+            let id = format!("{}@{}", name, version.version);
+
+            if resolved.contains_key(&id) {
+                continue;
+            }
+
+            for (dependency, range) in version.dependencies.iter() {
+                next.push((dependency.clone(), range.clone(), optional));
+            }
+
+            for (dependency, range) in version.optional_dependencies.iter() {
+                next.push((dependency.clone(), range.clone(), true));
+            }
+
+            by_name
+                .entry(name)
+                .or_default()
+                .push(version.version.clone());
+
+            resolved.insert(id, to_volt_package(version)?);
+        }
+
+        queue = next;
+    }
+
+    if !missing_required.is_empty() {
+        return Err(VoltError::NotCached {
+            packages: missing_required.into_iter().collect(),
+        }
+        .into());
+    }
+
+    let version = root_version.unwrap_or_default();
+
+    let mut versions = HashMap::new();
+
+    versions.insert(version.clone(), resolved);
+
+    Ok(VoltResponse { version, versions })
+}
+
+/// Whether one of the resolved `versions` of a package does for `range`. Dist-tags and
+/// anything else that isn't a range are met by whichever version was resolved first.
+fn satisfied(versions: &[String], range: &str) -> bool {
+    match Range::parse(range) {
+        Ok(_) if !range.is_empty() => versions.iter().any(|version| satisfies(version, range)),
+        _ => !versions.is_empty(),
+    }
+}
+
+/// Check the peer dependencies of every package in the tree against the tree itself and the
+/// project's node_modules.
+///
+/// Unmet peers are reported as warnings. With `auto_install` enabled, peers that aren't
+/// installed at all are resolved to the highest version satisfying every declared range and
+/// added to the tree, failing when no such version exists. Packages whose registry document
+/// can't be fetched are skipped with a warning.
+pub async fn resolve_peer_dependencies(
+    app: &App,
+    dependencies: &mut HashMap<String, VoltPackage>,
+    auto_install: bool,
+    before: Option<&DateTime<Utc>>,
+) -> Result<()> {
+    let dependents = dependencies
+        .values()
+        .filter(|package| package.peer_dependencies.is_some())
+        .map(|package| (package.name.clone(), package.version.clone()))
+        .collect::<Vec<_>>();
+
+    if dependents.is_empty() {
+        return Ok(());
+    }
+
+    let registries = &app.registries;
+
+    // the CDN only lists the names of peer dependencies, the ranges come from the registry
+    let manifests = dependents
+        .into_iter()
+        .map(|(name, version)| async move {
+            let packument = get_packument(registries, &name).await;
+            (name, version, packument)
+        })
+        .collect::<Vec<_>>();
+
+    let manifests = stream::iter(manifests)
+        .buffer_unordered(app.concurrency())
+        .collect::<Vec<_>>()
+        .await;
+
+    // peer -> [(dependent, range)]
+    let mut requirements: HashMap<String, Vec<(String, String)>> = HashMap::new();
+
+    for (name, version, packument) in manifests {
+        // one registry hiccup shouldn't fail the install, only that package's peers go unchecked
+        let packument = match packument {
+            Ok(packument) => packument,
+            Err(error) => {
+                warn!(
+                    "skipping the peer dependencies of {}: {}",
+                    name.bright_cyan(),
+                    error
+                );
+
+                continue;
+            }
+        };
+
+        if let Some(manifest) = packument.versions.get(&version) {
+            for (peer, range) in manifest.required_peer_dependencies() {
+                requirements
+                    .entry(peer.to_string())
+                    .or_default()
+                    .push((name.clone(), range.to_string()));
+            }
+        }
+    }
+
+    let mut sorted = requirements.into_iter().collect::<Vec<_>>();
+    sorted.sort();
+
+    for (peer, ranges) in sorted {
+        let current = dependencies
+            .values()
+            .find(|package| package.name == peer)
+            .map(|package| package.version.clone())
+            .or_else(|| installed_version(app, &peer));
+
+        if let Some(current) = current {
+            for (dependent, range) in ranges.iter() {
+                if !satisfies(&current, range) {
+                    warn!(
+                        "{} has unmet peer dependency {}@{} ({} is installed)",
+                        dependent.bright_cyan(),
+                        peer.bright_yellow(),
+                        range,
+                        current
+                    );
+                }
+            }
+
+            continue;
+        }
+
+        if !auto_install {
+            for (dependent, range) in ranges.iter() {
+                warn!(
+                    "{} has unmet peer dependency {}@{}",
+                    dependent.bright_cyan(),
+                    peer.bright_yellow(),
+                    range
+                );
+            }
+
+            continue;
+        }
+
+        let describe = || {
+            ranges
+                .iter()
+                .map(|(dependent, range)| format!("{} (required by {})", range, dependent))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        let parsed = ranges
+            .iter()
+            .map(|(_, range)| Range::parse(range))
+            .collect::<Result<Vec<Range>, _>>()
+            .map_err(|_| VoltError::UnsatisfiablePeerDependency {
+                package_name: peer.clone(),
+                requirements: describe(),
+            })?;
+
+        let packument = fetch_packument(registries, &peer, before).await?;
+
+        let version = max_satisfying(&packument, &parsed)
+            .ok_or_else(|| VoltError::UnsatisfiablePeerDependency {
+                package_name: peer.clone(),
+                requirements: describe(),
+            })?
+            .version
+            .clone();
+
+        info!(
+            "installing peer dependency {}@{}",
+            peer.bright_cyan(),
+            version.bright_yellow()
+        );
+
+        let response = get_npm_response(
+            &Package {
+                name: peer.clone(),
+                version: Some(version.clone()),
+                alias: None,
+            },
+            registries,
+            before,
+        )
+        .await?;
+
+        for (key, package) in response.versions.get(&response.version).unwrap() {
+            if !dependencies.values().any(|p| p.name == package.name) {
+                dependencies.insert(key.clone(), package.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::http_manager::NetworkMode;
+
+    /// A tree the CDN served, with `latest` and the other versions it carries.
+    fn cdn_response(latest: &str, versions: &[&str]) -> VoltResponse {
+        VoltResponse {
+            version: latest.to_string(),
+            versions: versions
+                .iter()
+                .map(|version| (version.to_string(), HashMap::new()))
+                .collect(),
+        }
+    }
+
+    fn package(spec: &str) -> Package {
+        parse_versions(&vec![spec.to_string()]).unwrap().remove(0)
+    }
+
+    /// Registries that fail every request, to tell when a tree would be fetched again.
+    fn offline() -> Registries {
+        Registries {
+            network: NetworkMode::Offline,
+            ..Registries::default()
+        }
+    }
+
+    #[test]
+    fn parses_dist_tags_and_ranges() {
+        assert!(is_dist_tag("next"));
+        assert!(is_dist_tag("canary-2021.09"));
+        assert!(!is_dist_tag("^1.2.0"));
+        assert!(!is_dist_tag("1.x"));
+        assert!(!is_dist_tag(">=14 <16"));
+
+        assert_eq!(package("react@next").version.as_deref(), Some("next"));
+        assert_eq!(package("react@latest").version, None);
+        assert_eq!(package("@types/node@>=14").name, "@types/node");
+        assert!(parse_versions(&vec![String::from("react@^1.x.y")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn prefers_latest_when_in_range() {
+        let response = cdn_response("2.1.0", &["1.0.0", "2.0.0", "2.1.0"]);
+
+        let selected = select_version(response, &package("pkg@^2.0.0"), &offline())
+            .await
+            .unwrap();
+
+        assert_eq!(selected.version, "2.1.0");
+    }
+
+    #[tokio::test]
+    async fn selects_the_newest_version_in_range() {
+        let response = cdn_response("3.0.0", &["1.0.0", "1.4.2", "1.10.0", "2.0.0", "3.0.0"]);
+
+        let selected = select_version(response, &package("pkg@^1.2.0"), &offline())
+            .await
+            .unwrap();
+
+        assert_eq!(selected.version, "1.10.0");
+    }
+
+    #[tokio::test]
+    async fn asks_the_registry_for_what_the_cdn_lacks() {
+        let fetched_again = |error: miette::Report| {
+            matches!(
+                error.downcast_ref::<VoltError>(),
+                Some(VoltError::NotCached { .. })
+            )
+        };
+
+        // a range no version the CDN has satisfies
+        let response = cdn_response("3.0.0", &["2.0.0", "3.0.0"]);
+        let error = select_version(response, &package("pkg@^1.0.0"), &offline())
+            .await
+            .unwrap_err();
+
+        assert!(fetched_again(error));
+
+        // the CDN only knows `latest`, other tags come from the registry
+        let response = cdn_response("3.0.0", &["3.0.0"]);
+        let error = select_version(response, &package("pkg@next"), &offline())
+            .await
+            .unwrap_err();
+
+        assert!(fetched_again(error));
+    }
+
+    #[test]
+    fn parses_before_dates() {
+        assert_eq!(
+            parse_before("2021-09-01").unwrap(),
+            Utc.with_ymd_and_hms(2021, 9, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_before("2021-09-01T12:30:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2021, 9, 1, 10, 30, 0).unwrap()
+        );
+
+        for invalid in ["yesterday", "2021-13-01", "01/09/2021"] {
+            let error = parse_before(invalid).unwrap_err();
+
+            assert!(matches!(
+                error.downcast_ref::<VoltError>(),
+                Some(VoltError::InvalidDate { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn restricts_documents_to_before() {
+        let mut packument = serde_json::from_value::<Packument>(serde_json::json!({
+            "name": "pkg",
+            "dist-tags": { "latest": "2.0.0", "next": "2.1.0-beta.1", "legacy": "1.0.0" },
+            "versions": {
+                "1.0.0": { "name": "pkg", "version": "1.0.0" },
+                "1.1.0": { "name": "pkg", "version": "1.1.0" },
+                "1.2.0-rc.1": { "name": "pkg", "version": "1.2.0-rc.1" },
+                "2.0.0": { "name": "pkg", "version": "2.0.0" },
+                "2.1.0-beta.1": { "name": "pkg", "version": "2.1.0-beta.1" },
+                "3.0.0": { "name": "pkg", "version": "3.0.0" },
+            },
+            "time": {
+                "1.0.0": "2020-01-01T00:00:00.000Z",
+                "1.1.0": "2020-06-01T00:00:00.000Z",
+                "1.2.0-rc.1": "2020-07-01T00:00:00.000Z",
+                "2.0.0": "2021-01-01T00:00:00.000Z",
+                "2.1.0-beta.1": "2021-02-01T00:00:00.000Z",
+            },
+        }))
+        .unwrap();
+
+        restrict_before(&mut packument, &parse_before("2020-12-31").unwrap());
+
+        let mut versions = packument.versions.keys().cloned().collect::<Vec<_>>();
+        versions.sort();
+
+        // versions without a publish time can't be shown to be old enough
+        assert_eq!(versions, ["1.0.0", "1.1.0", "1.2.0-rc.1"]);
+
+        // `latest` falls back to the newest stable version, tags of dropped versions go
+        assert_eq!(packument.dist_tags.get("latest").unwrap(), "1.1.0");
+        assert_eq!(packument.dist_tags.get("legacy").unwrap(), "1.0.0");
+        assert!(!packument.dist_tags.contains_key("next"));
+    }
+}
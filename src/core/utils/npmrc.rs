@@ -0,0 +1,232 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Keep track of the paths volt created, so linking never replaces files it doesn't own.
+//!
+//! Settings from `.npmrc` files, so projects set up for npm work without a `volt.toml`.
+//!
+//! Files are read the way npm reads them, earlier ones win:
+//!
+//! * `npm_config_*` environment variables
+//! * `.npmrc` in the project, the workspace root when in a workspace
+//! * the user's, `NPM_CONFIG_USERCONFIG` or `~/.npmrc`
+//! * the global one, `NPM_CONFIG_GLOBALCONFIG` or `etc/npmrc` in node's prefix
+//!
+//! Only `registry`, `@<scope>:registry`, `//<registry>/:_authToken`, `proxy`, `https-proxy`,
+//! `noproxy`, `strict-ssl` and `save-exact` are used, and only where `volt.toml` doesn't set
+//! the same thing.
+
+use crate::core::model::config::VoltConfig;
+use crate::core::utils::workspace::workspace_root;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The merged settings of every `.npmrc` that applies to a project.
+#[derive(Default, Debug, Clone)]
+pub struct Npmrc {
+    values: BTreeMap<String, String>,
+}
+
+impl Npmrc {
+    /// Settings for the project in `directory`.
+    pub fn load(directory: &Path, home_dir: &Path) -> Npmrc {
+        let project = workspace_root(directory).unwrap_or_else(|| directory.to_path_buf());
+
+        let user = env::var_os("NPM_CONFIG_USERCONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home_dir.join(".npmrc"));
+
+        let mut npmrc = Npmrc::default();
+
+        // lowest precedence first, later files replace what earlier ones set
+        for path in global_config()
+            .into_iter()
+            .chain([user, project.join(".npmrc")])
+        {
+            if let Ok(data) = std::fs::read_to_string(&path) {
+                npmrc.values.extend(parse(&data));
+            }
+        }
+
+        for (key, value) in env::vars() {
+            if let Some(key) = key
+                .strip_prefix("npm_config_")
+                .or_else(|| key.strip_prefix("NPM_CONFIG_"))
+            {
+                npmrc
+                    .values
+                    .insert(key.to_lowercase().replace('_', "-"), value);
+            }
+        }
+
+        npmrc
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    fn flag(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|value| value == "true")
+    }
+
+    /// `_authToken`s keyed by the registry they are for, as `//host/path/`.
+    pub fn auth_tokens(&self) -> BTreeMap<String, String> {
+        self.values
+            .iter()
+            .filter_map(|(key, value)| {
+                let registry = key.strip_suffix(":_authToken")?;
+
+                registry
+                    .starts_with("//")
+                    .then(|| (normalize_registry(registry), value.clone()))
+            })
+            .collect()
+    }
+
+    /// Fill in what `config` leaves unset.
+    pub fn apply(&self, config: &mut VoltConfig) {
+        if config.registry.is_none() {
+            config.registry = self
+                .get("registry")
+                .map(|registry| registry.trim_end_matches('/').to_string());
+        }
+
+        for (key, registry) in self.values.iter() {
+            if let Some(scope) = key.strip_suffix(":registry").filter(|s| s.starts_with('@')) {
+                config
+                    .registries
+                    .entry(scope.to_string())
+                    .or_insert_with(|| registry.trim_end_matches('/').to_string());
+            }
+        }
+
+        if config.proxy.is_none() {
+            config.proxy = self.get("proxy").map(str::to_string);
+        }
+
+        if config.https_proxy.is_none() {
+            config.https_proxy = self.get("https-proxy").map(str::to_string);
+        }
+
+        if config.no_proxy.is_none() {
+            config.no_proxy = self.get("noproxy").map(str::to_string);
+        }
+
+        if config.strict_ssl.is_none() {
+            config.strict_ssl = self.flag("strict-ssl");
+        }
+
+        if config.save_exact.is_none() {
+            config.save_exact = self.flag("save-exact");
+        }
+
+        if config.offline.is_none() {
+            config.offline = self.flag("offline");
+        }
+
+        if config.prefer_offline.is_none() {
+            config.prefer_offline = self.flag("prefer-offline");
+        }
+
+        if config.ignore_scripts.is_none() {
+            config.ignore_scripts = self.flag("ignore-scripts");
+        }
+
+        if config.engine_strict.is_none() {
+            config.engine_strict = self.flag("engine-strict");
+        }
+
+        config.auth_tokens = self.auth_tokens();
+    }
+}
+
+/// `key=value` pairs of an npmrc, `${VAR}` replaced with the environment variable.
+fn parse(data: &str) -> Vec<(String, String)> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) => quoted,
+                None => value,
+            };
+
+            Some((expand(key.trim()), expand(value)))
+        })
+        .collect()
+}
+
+fn expand(value: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        expanded.push_str(&rest[..start]);
+
+        // `${VAR?}` is npm's way of saying the variable may be unset
+        let name = rest[start + 2..end].trim_end_matches('?');
+        expanded.push_str(&env::var(name).unwrap_or_default());
+
+        rest = &rest[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// `//host/path/` for a registry url, the form npmrc keys credentials by.
+pub fn normalize_registry(registry: &str) -> String {
+    let registry = registry
+        .split_once("//")
+        .map_or(registry, |(_, rest)| rest)
+        .trim_end_matches('/');
+
+    format!("//{}/", registry)
+}
+
+/// `etc/npmrc` under the prefix node is installed in, like npm's default `globalconfig`.
+fn global_config() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NPM_CONFIG_GLOBALCONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let prefix = match env::var_os("NPM_CONFIG_PREFIX") {
+        Some(prefix) => PathBuf::from(prefix),
+        None => {
+            let node = if cfg!(windows) { "node.exe" } else { "node" };
+
+            let directory = env::split_paths(&env::var_os("PATH")?)
+                .find(|directory| directory.join(node).is_file())?;
+
+            // node sits in `<prefix>/bin` on unix and directly in the prefix on Windows
+            if cfg!(windows) {
+                directory
+            } else {
+                directory.parent()?.to_path_buf()
+            }
+        }
+    };
+
+    Some(prefix.join("etc").join("npmrc"))
+}
@@ -0,0 +1,236 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! What to run next, for `volt` run without a command.
+//!
+//! Instead of the list of every command, volt looks at the directory it runs in: whether there
+//! is a package.json, which package manager's lockfile sits next to it, whether it is part of
+//! a workspace and whether anything was installed yet, and suggests the few commands that
+//! make sense from there.
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::core::utils::package::PackageJson;
+use crate::core::utils::workspace::{workspace_packages, workspace_root};
+use crate::core::VERSION;
+
+use colored::Colorize;
+use serde_json::Value;
+use tracing::info;
+
+/// Scripts listed before the suggestions point at `volt run` for the rest.
+const LISTED_SCRIPTS: usize = 4;
+
+/// Lockfiles of other package managers volt doesn't read.
+const FOREIGN_LOCKFILES: &[(&str, &str)] = &[
+    ("yarn.lock", "yarn"),
+    ("pnpm-lock.yaml", "pnpm"),
+    ("bun.lockb", "bun"),
+    ("npm-shrinkwrap.json", "npm"),
+];
+
+/// What volt found in the directory it runs in.
+struct Project {
+    manifest: Option<PackageJson>,
+    /// `lockfileVersion` of the package-lock.json, when there is one.
+    package_lock: Option<u64>,
+    /// Other package managers whose lockfile is present.
+    foreign: Vec<(&'static str, &'static str)>,
+    volt_lock: bool,
+    node_modules: bool,
+    /// The workspace root and how many packages it has, when inside a workspace.
+    workspace: Option<(PathBuf, usize)>,
+}
+
+impl Project {
+    fn detect(directory: &Path) -> Project {
+        let package_lock = read_to_string(directory.join("package-lock.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+            .map(|document| document["lockfileVersion"].as_u64().unwrap_or(1));
+
+        let workspace = workspace_root(directory).map(|root| {
+            let members = workspace_packages(&root).map_or(0, |members| members.len());
+            (root, members)
+        });
+
+        // inside a workspace, what is installed lives at its root
+        let installed = workspace
+            .as_ref()
+            .map_or(directory, |(root, _)| root.as_path());
+
+        Project {
+            manifest: PackageJson::read(&directory.join("package.json")).ok(),
+            package_lock,
+            foreign: FOREIGN_LOCKFILES
+                .iter()
+                .filter(|(file, _)| directory.join(file).exists())
+                .copied()
+                .collect(),
+            volt_lock: installed.join("volt.lock").exists(),
+            node_modules: installed.join("node_modules").exists(),
+            workspace,
+        }
+    }
+}
+
+/// Print what volt found in `directory` and the commands to run next.
+pub fn guide(directory: &Path) {
+    let project = Project::detect(directory);
+
+    let mut suggestions: Vec<(String, String)> = vec![];
+
+    let mut suggest = |command: &str, reason: &str| {
+        suggestions.push((command.to_string(), reason.to_string()));
+    };
+
+    println!(
+        "{} {}\n",
+        "volt".bright_green().bold(),
+        VERSION.bright_green()
+    );
+
+    let manifest = match &project.manifest {
+        Some(manifest) => manifest,
+        None => {
+            println!("There is no package.json in {}.\n", directory.display());
+
+            suggest("volt init", "create a package.json for a new project");
+            suggest("volt add <package>", "add a first dependency");
+
+            print_suggestions(&suggestions);
+            return;
+        }
+    };
+
+    let name = match manifest.name.as_str() {
+        "" => directory.display().to_string(),
+        name => name.to_string(),
+    };
+
+    match &project.workspace {
+        Some((root, members)) if root == directory => println!(
+            "{} is the root of a workspace with {} packages.",
+            name.bright_cyan(),
+            members
+        ),
+        Some((root, _)) => println!(
+            "{} is a package of the workspace at {}.",
+            name.bright_cyan(),
+            root.display()
+        ),
+        None => println!(
+            "{} declares {} dependencies.",
+            name.bright_cyan(),
+            manifest.dependencies.len()
+                + manifest.dev_dependencies.len()
+                + manifest.optional_dependencies.len()
+        ),
+    }
+
+    for (file, manager) in project.foreign.iter() {
+        info!("found {} from {}, volt doesn't read it", file, manager);
+    }
+
+    println!();
+
+    if !project.volt_lock {
+        match project.package_lock {
+            Some(2) | Some(3) => suggest(
+                "volt install",
+                "install, keeping the versions pinned in package-lock.json",
+            ),
+            Some(_) => {
+                suggest(
+                    "npm install --package-lock-only --lockfile-version 3",
+                    "upgrade package-lock.json so volt can import its versions",
+                );
+                suggest("volt install", "then install from it");
+            }
+            None if !project.foreign.is_empty() => suggest(
+                "volt install",
+                "install, resolving versions from the ranges in package.json",
+            ),
+            None => suggest("volt install", "install the dependencies of the project"),
+        }
+
+        if !project.foreign.is_empty() {
+            suggest(
+                "volt list",
+                "compare what was installed with what the old lockfile had",
+            );
+        }
+    } else if !project.node_modules {
+        suggest("volt install", "install the versions in volt.lock");
+        suggest(
+            "volt ci",
+            "or install exactly them, failing if package.json changed",
+        );
+    } else {
+        suggest("volt add <package>", "add a dependency");
+        suggest("volt list", "show the dependencies of the project");
+        suggest("volt verify", "check node_modules against volt.lock");
+    }
+
+    if let Some((root, members)) = &project.workspace {
+        if root == directory && *members > 0 {
+            suggest(
+                "volt install --filter <package>",
+                "install only some packages of the workspace",
+            );
+        }
+    }
+
+    let mut scripts = manifest.scripts.keys().collect::<Vec<_>>();
+    scripts.sort();
+
+    for script in scripts.iter().take(LISTED_SCRIPTS) {
+        suggest(
+            &format!("volt run {}", script),
+            "run a script of package.json",
+        );
+    }
+
+    if scripts.len() > LISTED_SCRIPTS {
+        suggest(
+            "volt run <script>",
+            &format!("{} more scripts", scripts.len() - LISTED_SCRIPTS),
+        );
+    }
+
+    print_suggestions(&suggestions);
+}
+
+fn print_suggestions(suggestions: &[(String, String)]) {
+    println!("Next steps:");
+
+    let width = suggestions
+        .iter()
+        .map(|(command, _)| command.len())
+        .max()
+        .unwrap_or_default();
+
+    for (command, reason) in suggestions {
+        println!(
+            "  {}  {}",
+            format!("{:width$}", command, width = width).bright_blue(),
+            reason
+        );
+    }
+
+    println!("\nRun `volt --help` for every command.");
+}
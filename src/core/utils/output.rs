@@ -0,0 +1,225 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! `--json`: a single JSON document on stdout instead of colored text, for scripts and tools
+//! wrapping volt.
+//!
+//! While a command runs it records its results here, the packages it resolved, the warnings
+//! it logged and anything of its own like the dependencies `volt list` found. Once it
+//! finishes the document is written with the time every phase took:
+//!
+//! ```json
+//! {
+//!   "command": "install",
+//!   "success": true,
+//!   "packages": [{ "name": "react", "version": "17.0.2", "integrity": "sha512-..." }],
+//!   "warnings": [],
+//!   "timings": { "total": 1.52, "phases": { "resolving": { "seconds": 0.8, "operations": 3 } } }
+//! }
+//! ```
+//!
+//! A failed command also has the `error` it failed with and its `code`, like `VOLT0012`.
+//!
+//! The text volt prints anyway, like progress and success messages, goes to stderr instead so
+//! stdout only holds the document.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::core::utils::timings;
+use crate::core::utils::voltapi::VoltPackage;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// What the current command recorded for the document.
+static REPORT: Mutex<Report> = Mutex::new(Report {
+    packages: vec![],
+    warnings: vec![],
+    fields: BTreeMap::new(),
+});
+
+/// Stdout from before `enable` moved it, the document is written there.
+#[cfg(unix)]
+static STDOUT: Mutex<Option<std::fs::File>> = Mutex::new(None);
+
+struct Report {
+    packages: Vec<ResolvedPackage>,
+    warnings: Vec<String>,
+    /// Results of the command itself, merged into the document.
+    fields: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ResolvedPackage {
+    name: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    integrity: String,
+}
+
+/// Write the document instead of text, for the rest of the run.
+pub fn enable() {
+    JSON.store(true, Ordering::SeqCst);
+
+    redirect_stdout();
+}
+
+/// Whether volt runs with `--json`.
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// Keep a warning for the document, `core::utils::logging` passes every one it prints.
+pub fn record_warning(message: &str) {
+    if is_json() {
+        let plain = console::strip_ansi_codes(message).to_string();
+
+        REPORT.lock().unwrap().warnings.push(plain);
+    }
+}
+
+/// Record the packages a command resolved.
+pub fn resolved<'a>(packages: impl IntoIterator<Item = &'a VoltPackage>) {
+    if !is_json() {
+        return;
+    }
+
+    let mut report = REPORT.lock().unwrap();
+
+    report
+        .packages
+        .extend(packages.into_iter().map(|package| ResolvedPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            alias: package.alias.clone(),
+            integrity: package.integrity.clone(),
+        }));
+
+    report
+        .packages
+        .sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    report
+        .packages
+        .dedup_by(|a, b| a.name == b.name && a.version == b.version);
+}
+
+/// Record `value` under `key` in the document.
+pub fn set(key: &str, value: impl Serialize) {
+    if !is_json() {
+        return;
+    }
+
+    if let Ok(value) = serde_json::to_value(value) {
+        REPORT.lock().unwrap().fields.insert(key.to_string(), value);
+    }
+}
+
+/// Write the document of `command`, which took `total` and failed with `error` if any.
+pub fn emit(command: &str, total: Duration, error: Option<&miette::Report>) {
+    let report = std::mem::replace(
+        &mut *REPORT.lock().unwrap(),
+        Report {
+            packages: vec![],
+            warnings: vec![],
+            fields: BTreeMap::new(),
+        },
+    );
+
+    let phases = timings::spent()
+        .into_iter()
+        .map(|(phase, (time, operations))| {
+            (
+                phase.name().to_string(),
+                json!({ "seconds": time.as_secs_f64(), "operations": operations }),
+            )
+        })
+        .collect::<Map<_, _>>();
+
+    let mut document = json!({
+        "command": command,
+        "success": error.is_none(),
+        "packages": report.packages,
+        "warnings": report.warnings,
+        "timings": { "total": total.as_secs_f64(), "phases": phases },
+    });
+
+    if let Some(error) = error {
+        let message = console::strip_ansi_codes(&error.to_string()).to_string();
+
+        document["error"] = Value::String(message);
+
+        // the stable `VOLT0001` code, see `core::utils::errors`
+        if let Some(code) = miette::Diagnostic::code(&**error) {
+            document["code"] = Value::String(code.to_string());
+        }
+    }
+
+    for (key, value) in report.fields {
+        document[key.as_str()] = value;
+    }
+
+    let data = serde_json::to_string_pretty(&document).unwrap_or_default();
+
+    write_document(&data);
+}
+
+/// Point stdout at stderr, keeping the real stdout for the document.
+#[cfg(unix)]
+fn redirect_stdout() {
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    std::io::stdout().flush().ok();
+
+    unsafe {
+        let saved = libc::dup(libc::STDOUT_FILENO);
+
+        if saved < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return;
+        }
+
+        *STDOUT.lock().unwrap() = Some(std::fs::File::from_raw_fd(saved));
+    }
+}
+
+/// Only unix can move stdout, elsewhere the text stays on it ahead of the document.
+#[cfg(not(unix))]
+fn redirect_stdout() {}
+
+#[cfg(unix)]
+fn write_document(data: &str) {
+    use std::io::Write;
+
+    std::io::stdout().flush().ok();
+
+    match STDOUT.lock().unwrap().as_mut() {
+        Some(stdout) => {
+            writeln!(stdout, "{}", data).ok();
+        }
+        None => println!("{}", data),
+    }
+}
+
+#[cfg(not(unix))]
+fn write_document(data: &str) {
+    println!("{}", data);
+}
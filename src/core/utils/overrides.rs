@@ -0,0 +1,269 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Force versions of transitive dependencies with the `overrides` (npm) or `resolutions` (yarn)
+//! field of the root package.json.
+//!
+//! ```json
+//! {
+//!   "overrides": { "minimist": "^1.2.6", "glob@<8": "8.0.3", "react": "$react" },
+//!   "resolutions": { "**/lodash": "4.17.21" }
+//! }
+//! ```
+//!
+//! node_modules holds a single copy of every package, so overrides scoped to a dependent
+//! (`{ "a": { "b": "1.0.0" } }` or `"a/b"`) apply to every copy of `b`.
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::commands::add::Package;
+use crate::core::model::http_manager::Registries;
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::npm::get_npm_response;
+use crate::core::utils::satisfies;
+use crate::core::utils::voltapi::VoltPackage;
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+use tracing::{info, warn};
+
+/// A version forced on every resolved copy of a package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub name: String,
+    /// Only versions satisfying this range are replaced, `glob@<8` in `overrides`.
+    pub selector: Option<String>,
+    pub range: String,
+}
+
+/// The overrides declared in the package.json in `directory`, `overrides` before `resolutions`.
+pub fn load_overrides(directory: &Path) -> Result<Vec<Override>> {
+    let path = directory.join("package.json");
+
+    let manifest: Value = match read_to_string(&path) {
+        Ok(data) => serde_json::from_str(&data).into_diagnostic()?,
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut overrides = vec![];
+
+    if let Some(map) = manifest["overrides"].as_object() {
+        for (key, value) in map {
+            npm_overrides(&manifest, key, value, None, &mut overrides)?;
+        }
+    }
+
+    if let Some(map) = manifest["resolutions"].as_object() {
+        for (key, value) in map {
+            let range = value
+                .as_str()
+                .ok_or_else(|| VoltError::InvalidOverride { key: key.clone() })?;
+
+            // `**/a`, `a/b` and `a/**/b` all end in the package that's overridden
+            let segments = key.split('/').collect::<Vec<_>>();
+
+            let name = match segments.as_slice() {
+                [.., scope, name] if scope.starts_with('@') => format!("{}/{}", scope, name),
+                [.., name] => name.to_string(),
+                [] => unreachable!(),
+            };
+
+            if name != key.as_str() && !key.starts_with("**/") {
+                warn_scoped(key, &name);
+            }
+
+            push(
+                &mut overrides,
+                Override {
+                    name,
+                    selector: None,
+                    range: range.to_string(),
+                },
+            );
+        }
+    }
+
+    Ok(overrides)
+}
+
+fn npm_overrides(
+    manifest: &Value,
+    key: &str,
+    value: &Value,
+    parent: Option<&str>,
+    overrides: &mut Vec<Override>,
+) -> Result<()> {
+    // `name@range` only overrides matching versions, the first `@` of a scope isn't a separator
+    let (name, selector) = match key.char_indices().skip(1).find(|(_, c)| *c == '@') {
+        Some((index, _)) => (&key[..index], Some(key[index + 1..].to_string())),
+        None => (key, None),
+    };
+
+    let range = match value {
+        Value::String(range) => Some(range.as_str()),
+        // `{ ".": "1.0.0", "child": "2.0.0" }` overrides the package itself and its dependencies
+        Value::Object(children) => {
+            for (child, value) in children.iter().filter(|(child, _)| *child != ".") {
+                let parent = match parent {
+                    Some(parent) => format!("{} > {}", parent, key),
+                    None => key.to_string(),
+                };
+
+                npm_overrides(manifest, child, value, Some(&parent), overrides)?;
+            }
+
+            children.get(".").and_then(Value::as_str)
+        }
+        _ => {
+            return Err(VoltError::InvalidOverride {
+                key: key.to_string(),
+            }
+            .into())
+        }
+    };
+
+    let range = match range {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    if let Some(parent) = parent {
+        warn_scoped(&format!("{} > {}", parent, key), name);
+    }
+
+    // `$name` refers to the range the root package depends on
+    let range = match range.strip_prefix('$') {
+        Some(reference) => ["dependencies", "devDependencies", "optionalDependencies"]
+            .iter()
+            .find_map(|field| manifest[*field][reference].as_str())
+            .ok_or_else(|| VoltError::InvalidOverride {
+                key: key.to_string(),
+            })?,
+        None => range,
+    };
+
+    push(
+        overrides,
+        Override {
+            name: name.to_string(),
+            selector,
+            range: range.to_string(),
+        },
+    );
+
+    Ok(())
+}
+
+fn warn_scoped(key: &str, name: &str) {
+    warn!(
+        "the override for {} applies to every copy of {}",
+        key.bright_cyan(),
+        name.bright_cyan()
+    );
+}
+
+/// The first override of a package wins, later conflicting ones are reported and dropped.
+fn push(overrides: &mut Vec<Override>, new: Override) {
+    match overrides
+        .iter()
+        .find(|o| o.name == new.name && o.selector == new.selector)
+    {
+        Some(kept) if kept.range != new.range => warn!(
+            "ignoring override {}@{}, {} is already overridden with {}",
+            new.name.bright_cyan(),
+            new.range,
+            kept.name,
+            kept.range
+        ),
+        Some(_) => {}
+        None => overrides.push(new),
+    }
+}
+
+/// Replace the resolved packages `overrides` apply to with the forced versions, together with
+/// the dependencies of those versions the tree doesn't have yet.
+pub async fn apply_overrides(
+    dependencies: &mut HashMap<String, VoltPackage>,
+    overrides: &[Override],
+    registries: &Registries,
+    before: Option<&DateTime<Utc>>,
+) -> Result<()> {
+    for item in overrides.iter() {
+        let replaced = dependencies
+            .iter()
+            .filter(|(_, package)| package.name == item.name && package.alias.is_none())
+            .filter(|(_, package)| {
+                item.selector
+                    .as_deref()
+                    .is_none_or(|selector| satisfies(&package.version, selector))
+            })
+            .filter(|(_, package)| {
+                package.version != item.range && !satisfies(&package.version, &item.range)
+            })
+            .map(|(key, package)| (key.clone(), package.version.clone()))
+            .collect::<Vec<_>>();
+
+        if replaced.is_empty() {
+            continue;
+        }
+
+        let response = get_npm_response(
+            &Package {
+                name: item.name.clone(),
+                version: Some(item.range.clone()),
+                alias: None,
+            },
+            registries,
+            before,
+        )
+        .await?;
+
+        for (key, version) in replaced.iter() {
+            info!(
+                "replacing {}@{} with {}",
+                item.name.bright_cyan(),
+                version,
+                response.version.bright_yellow()
+            );
+
+            dependencies.remove(key);
+        }
+
+        for (key, package) in response.versions.get(&response.version).unwrap() {
+            if !dependencies.values().any(|p| p.name == package.name) {
+                dependencies.insert(key.clone(), package.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark the lockfile entries `overrides` apply to with the range that forced their version.
+pub fn record_overrides(lock_file: &mut LockFile, overrides: &[Override]) {
+    for lock in lock_file.dependencies.values_mut() {
+        lock.overridden = overrides
+            .iter()
+            .find(|item| item.name == lock.name && lock.alias.is_none())
+            .filter(|item| satisfies(&lock.version, &item.range) || lock.version == item.range)
+            .map(|item| item.range.clone());
+    }
+}
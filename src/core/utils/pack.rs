@@ -0,0 +1,883 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Create package tarballs in the layout registries expect.
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::workspace::replace_workspace_ranges;
+
+use colored::Colorize;
+use flate2::{write::GzEncoder, Compression};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use miette::{IntoDiagnostic, Result};
+use serde_json::Value;
+use sha1::Digest;
+use ssri::{Algorithm, IntegrityOpts};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tar::{Builder, Header, HeaderMode, DETERMINISTIC_TIMESTAMP};
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// What npm never packs, wherever it is, and volt's own state, settings and secrets, which
+/// can hold registry tokens.
+const IGNORED: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    "CVS",
+    ".DS_Store",
+    "._*",
+    ".*.swp",
+    ".wafpickle-*",
+    ".lock-wscript",
+    "config.gypi",
+    "npm-debug.log",
+    "*.orig",
+    ".npmrc",
+    "package-lock.json",
+    ".npmignore",
+    ".gitignore",
+    ".env",
+    ".env.*",
+    ".volt",
+    "volt.toml",
+    "volt.lock",
+    "volt.lock.sig",
+    "*.tgz",
+];
+
+/// First line of a volt secret key file, see [`crate::core::utils::signing`].
+const SECRET_KEY_HEADER: &[u8] = b"untrusted comment: volt secret key";
+
+/// Files whose name (without extension) marks them as a license text.
+const LICENSE_FILES: &[&str] = &["license", "licence", "copying"];
+
+/// What goes into a tarball besides the package's own files.
+#[derive(Debug, Default, Clone)]
+pub struct PackOptions {
+    /// Dependencies shipped inside the tarball (`bundleDependencies`).
+    pub bundled: Vec<String>,
+    /// Files added at the root of the package, e.g. a generated `NOTICE`.
+    pub extra_files: Vec<(String, Vec<u8>)>,
+}
+
+/// Names listed in `bundleDependencies` (or `bundledDependencies`), `true` bundles every dependency.
+pub fn bundled_dependencies(manifest: &Value) -> Vec<String> {
+    let bundled = match manifest.get("bundleDependencies") {
+        Some(bundled) => bundled,
+        None => &manifest["bundledDependencies"],
+    };
+
+    let mut names = match bundled {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(|name| name.as_str().map(String::from))
+            .collect(),
+        Value::Bool(true) => manifest["dependencies"]
+            .as_object()
+            .map(|dependencies| dependencies.keys().cloned().collect())
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+/// Options for packing the package in `directory`, bundling its `bundleDependencies` and,
+/// when `notice` is set, a generated NOTICE crediting them.
+///
+/// `workspace:` ranges in `manifest` are rewritten to the ranges they are published as, and
+/// the rewritten manifest replaces package.json in the tarball.
+pub fn package_options(
+    directory: &Path,
+    manifest: &mut Value,
+    notice: bool,
+) -> Result<PackOptions> {
+    let bundled = bundled_dependencies(manifest);
+
+    let mut extra_files = vec![];
+
+    if replace_workspace_ranges(directory, manifest)? {
+        let mut contents = serde_json::to_string_pretty(manifest).into_diagnostic()?;
+        contents.push('\n');
+
+        extra_files.push((String::from("package.json"), contents.into_bytes()));
+    }
+
+    if notice && !bundled.is_empty() {
+        extra_files.push((
+            String::from("NOTICE"),
+            generate_notice(directory, &bundled).into_bytes(),
+        ));
+    }
+
+    Ok(PackOptions {
+        bundled,
+        extra_files,
+    })
+}
+
+/// Decides which files of a package directory go into its tarball, the way npm does.
+///
+/// With `files` in package.json only what it lists is packed, else everything `.npmignore`
+/// doesn't ignore, or `.gitignore` in directories without one. Ignore files in nested
+/// directories apply below them, the top level ones don't when there's a `files` list.
+/// package.json, the readme, the license and the `main` and `bin` files are always packed,
+/// what `IGNORED` names and volt secret keys never are.
+struct Packlist {
+    root: PathBuf,
+    /// Everything but `IGNORED` and secret keys is packed without these, like the contents
+    /// of bundled dependencies.
+    rules: Option<Rules>,
+    ignored: Gitignore,
+}
+
+struct Rules {
+    /// The `files` of package.json.
+    files: Option<Gitignore>,
+    /// `main` and `bin`, relative to the package.
+    always: Vec<PathBuf>,
+}
+
+impl Packlist {
+    fn new(root: &Path, rules: bool) -> Result<Packlist> {
+        let mut ignored = GitignoreBuilder::new(root);
+
+        for pattern in IGNORED {
+            ignored.add_line(None, pattern).unwrap();
+        }
+
+        let rules = match rules {
+            true => Some(Rules::read(root)?),
+            false => None,
+        };
+
+        Ok(Packlist {
+            root: root.to_path_buf(),
+            rules,
+            ignored: ignored.build().unwrap(),
+        })
+    }
+
+    /// Paths of the packed files relative to the package, in sorted order.
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+
+        self.collect(&self.root, &mut vec![], &mut files)
+            .map_err(|e| VoltError::ReadFileError {
+                name: self.root.display().to_string(),
+                source: e,
+            })?;
+
+        Ok(files)
+    }
+
+    fn collect(
+        &self,
+        directory: &Path,
+        ignores: &mut Vec<Gitignore>,
+        files: &mut Vec<PathBuf>,
+    ) -> io::Result<()> {
+        let ignore = match &self.rules {
+            // a `files` list replaces the top level ignore files
+            Some(rules) if directory != self.root || rules.files.is_none() => {
+                ignore_file(directory)
+            }
+            _ => None,
+        };
+
+        let pushed = ignore.is_some();
+        ignores.extend(ignore);
+
+        let mut entries = std::fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+
+            let relative = path.strip_prefix(&self.root).unwrap().to_path_buf();
+
+            if file_type.is_dir() {
+                if !self.excluded(&path, &relative, true, ignores) {
+                    self.collect(&path, ignores, files)?;
+                }
+            } else if file_type.is_file() && self.includes(&path, &relative, ignores) {
+                files.push(relative);
+            }
+        }
+
+        if pushed {
+            ignores.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Whether the file or directory at `path` is left out whatever `files` says.
+    fn excluded(&self, path: &Path, relative: &Path, is_dir: bool, ignores: &[Gitignore]) -> bool {
+        if self.ignored.matched(path, is_dir).is_ignore() {
+            return true;
+        }
+
+        let rules = match &self.rules {
+            Some(rules) => rules,
+            None => return false,
+        };
+
+        // bundled dependencies are added separately, `node_modules` of the package never
+        if is_dir && path.file_name().is_some_and(|name| name == "node_modules") {
+            return true;
+        }
+
+        // `main` and `bin` are packed even from an ignored directory
+        if rules
+            .always
+            .iter()
+            .any(|always| always.starts_with(relative))
+        {
+            return false;
+        }
+
+        // directories kept for `main` and `bin` can be ignored themselves
+        for ignore in ignores.iter().rev() {
+            let matched = ignore.matched_path_or_any_parents(path, is_dir);
+
+            if matched.is_ignore() {
+                return true;
+            }
+
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    fn includes(&self, path: &Path, relative: &Path, ignores: &[Gitignore]) -> bool {
+        if is_secret_key(path) {
+            warn!(
+                "leaving the volt secret key {} out of the tarball",
+                relative.display().to_string().bright_cyan()
+            );
+
+            return false;
+        }
+
+        if self.excluded(path, relative, false, ignores) {
+            return false;
+        }
+
+        let rules = match &self.rules {
+            Some(rules) => rules,
+            None => return true,
+        };
+
+        if relative.parent() == Some(Path::new("")) && always_packed(relative) {
+            return true;
+        }
+
+        match &rules.files {
+            Some(files) => {
+                rules.always.iter().any(|always| always == relative)
+                    || files.matched_path_or_any_parents(path, false).is_ignore()
+            }
+            None => true,
+        }
+    }
+}
+
+impl Rules {
+    fn read(root: &Path) -> Result<Rules> {
+        let path = root.join("package.json");
+
+        let manifest = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+            .unwrap_or_default();
+
+        let files = match manifest["files"].as_array() {
+            Some(patterns) => {
+                let mut files = GitignoreBuilder::new(root);
+
+                for pattern in patterns.iter().filter_map(|pattern| pattern.as_str()) {
+                    // relative to the package, not anywhere in it like in an ignore file
+                    let (negated, pattern) = match pattern.strip_prefix('!') {
+                        Some(pattern) => ("!", pattern),
+                        None => ("", pattern),
+                    };
+
+                    let pattern = pattern.trim_start_matches("./").trim_start_matches('/');
+
+                    if let Err(e) = files.add_line(None, &format!("{}/{}", negated, pattern)) {
+                        warn!("ignoring `{}` in `files` of package.json: {}", pattern, e);
+                    }
+                }
+
+                Some(files.build().map_err(|e| VoltError::ReadFileError {
+                    name: path.display().to_string(),
+                    source: io::Error::new(io::ErrorKind::InvalidData, e),
+                })?)
+            }
+            None => None,
+        };
+
+        let bins = match &manifest["bin"] {
+            Value::String(bin) => vec![bin.as_str()],
+            Value::Object(bins) => bins.values().filter_map(|bin| bin.as_str()).collect(),
+            _ => vec![],
+        };
+
+        let always = manifest["main"]
+            .as_str()
+            .into_iter()
+            .chain(bins)
+            .map(|file| {
+                Path::new(file)
+                    .components()
+                    .filter(|component| matches!(component, Component::Normal(_)))
+                    .collect::<PathBuf>()
+            })
+            .collect();
+
+        Ok(Rules { files, always })
+    }
+}
+
+/// The `.npmignore` of `directory`, else its `.gitignore`.
+fn ignore_file(directory: &Path) -> Option<Gitignore> {
+    [".npmignore", ".gitignore"]
+        .iter()
+        .map(|name| directory.join(name))
+        .find(|path| path.is_file())
+        .map(|path| Gitignore::new(path).0)
+}
+
+/// package.json, the readme and the license, which npm packs whatever `files` lists.
+fn always_packed(file: &Path) -> bool {
+    let name = file.to_string_lossy().to_lowercase();
+
+    let stem = name.split('.').next().unwrap_or_default();
+
+    name == "package.json" || ["readme", "license", "licence"].contains(&stem)
+}
+
+/// Whether `path` holds a volt secret key, whatever it's called.
+fn is_secret_key(path: &Path) -> bool {
+    let mut header = [0; SECRET_KEY_HEADER.len()];
+
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| header == SECRET_KEY_HEADER)
+}
+
+/// Add the files `list` packs to the tarball under `prefix`, but the top level ones named in
+/// `skip`.
+fn append_tree<W: Write>(
+    builder: &mut Builder<W>,
+    list: &Packlist,
+    prefix: &Path,
+    skip: &[&str],
+) -> Result<()> {
+    for relative in list.files()? {
+        if relative.parent() == Some(Path::new(""))
+            && skip.contains(&relative.to_string_lossy().as_ref())
+        {
+            continue;
+        }
+
+        let path = list.root.join(&relative);
+
+        builder
+            .append_path_with_name(&path, prefix.join(&relative))
+            .map_err(|e| VoltError::ReadFileError {
+                name: path.display().to_string(),
+                source: e,
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Pack `directory` into a gzipped tarball with every entry nested under `package/`, leaving
+/// out what [`Packlist`] does.
+///
+/// Entries are added in sorted order, with the same mtime and owner and only the executable
+/// bit of their mode, so the same files give the same tarball on any machine.
+pub fn pack_directory(directory: &Path, options: &PackOptions) -> Result<Vec<u8>> {
+    let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+    builder.mode(HeaderMode::Deterministic);
+
+    let package = Path::new("package");
+
+    // extra files replace package files of the same name
+    let extra_names = options
+        .extra_files
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<&str>>();
+
+    append_tree(
+        &mut builder,
+        &Packlist::new(directory, true)?,
+        package,
+        &extra_names,
+    )?;
+
+    for name in options.bundled.iter() {
+        let source = directory.join("node_modules").join(name);
+
+        if !source.exists() {
+            return Err(VoltError::MissingBundledDependency { name: name.clone() }.into());
+        }
+
+        append_tree(
+            &mut builder,
+            &Packlist::new(&source, false)?,
+            &package.join("node_modules").join(name),
+            &[],
+        )?;
+    }
+
+    for (name, contents) in options.extra_files.iter() {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(DETERMINISTIC_TIMESTAMP);
+        header.set_cksum();
+
+        builder
+            .append_data(&mut header, package.join(name), contents.as_slice())
+            .map_err(|e| VoltError::WriteFileError {
+                name: String::from("package tarball"),
+                source: e,
+            })?;
+    }
+
+    let encoder = builder
+        .into_inner()
+        .map_err(|e| VoltError::WriteFileError {
+            name: String::from("package tarball"),
+            source: e,
+        })?;
+
+    Ok(encoder.finish().map_err(|e| VoltError::WriteFileError {
+        name: String::from("package tarball"),
+        source: e,
+    })?)
+}
+
+/// Build a NOTICE file crediting every bundled dependency, and every package nested inside
+/// them, with its license text.
+///
+/// An existing `NOTICE` in `directory` is kept at the top.
+pub fn generate_notice(directory: &Path, bundled: &[String]) -> String {
+    let mut notice = String::new();
+
+    if let Ok(existing) = std::fs::read_to_string(directory.join("NOTICE")) {
+        notice.push_str(existing.trim_end());
+        notice.push_str("\n\n");
+    }
+
+    notice.push_str("This package bundles the following third-party software.\n");
+
+    let mut packages = vec![];
+
+    for name in bundled {
+        let root = directory.join("node_modules").join(name);
+
+        for entry in WalkDir::new(&root)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() == "package.json")
+        {
+            let package_dir = entry.path().parent().unwrap();
+
+            // only package roots, not package.json files that happen to be nested in sources
+            let is_root = package_dir == root
+                || package_dir
+                    .parent()
+                    .and_then(|parent| parent.file_name())
+                    .is_some_and(|parent| parent == "node_modules")
+                || package_dir
+                    .parent()
+                    .and_then(|parent| parent.parent())
+                    .and_then(|parent| parent.file_name())
+                    .is_some_and(|parent| parent == "node_modules");
+
+            if is_root {
+                packages.push(package_dir.to_path_buf());
+            }
+        }
+    }
+
+    packages.sort();
+    packages.dedup();
+
+    for package_dir in packages {
+        let manifest = std::fs::read_to_string(package_dir.join("package.json"))
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+            .unwrap_or_default();
+
+        let name = manifest["name"].as_str().unwrap_or("unknown");
+        let version = manifest["version"].as_str().unwrap_or("unknown");
+
+        let license = match &manifest["license"] {
+            Value::String(license) => license.clone(),
+            Value::Object(license) => license
+                .get("type")
+                .and_then(|license| license.as_str())
+                .unwrap_or("UNKNOWN")
+                .to_string(),
+            _ => String::from("UNKNOWN"),
+        };
+
+        notice.push_str(&format!("\n{}\n{}@{}\n", "-".repeat(72), name, version));
+        notice.push_str(&format!("License: {}\n", license));
+
+        let repository = match &manifest["repository"] {
+            Value::String(repository) => Some(repository.as_str()),
+            Value::Object(repository) => repository.get("url").and_then(|url| url.as_str()),
+            _ => None,
+        };
+
+        if let Some(repository) = repository {
+            notice.push_str(&format!("Repository: {}\n", repository));
+        }
+
+        if let Some(text) = license_text(&package_dir) {
+            notice.push('\n');
+            notice.push_str(text.trim_end());
+            notice.push('\n');
+        }
+    }
+
+    notice
+}
+
+/// Contents of the license file shipped with a package, if it has one.
+fn license_text(package_dir: &Path) -> Option<String> {
+    let mut candidates = std::fs::read_dir(package_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+                .is_some_and(|stem| {
+                    LICENSE_FILES.iter().any(|license| {
+                        stem == *license || stem.starts_with(&format!("{}-", license))
+                    })
+                })
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort();
+
+    candidates
+        .first()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+}
+
+/// Calculate the hex encoded sha1 shasum and the sha512 subresource integrity of a tarball.
+pub fn tarball_hashes(tarball: &[u8]) -> (String, String) {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(tarball);
+
+    let shasum = format!("{:x}", hasher.finalize());
+
+    let integrity = IntegrityOpts::new()
+        .algorithm(Algorithm::Sha512)
+        .chain(tarball)
+        .result()
+        .to_string();
+
+    (shasum, integrity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+
+    /// A package directory for `test` holding `files`, each with its contents.
+    fn package(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("volt-pack-{}-{}", test, std::process::id()));
+
+        std::fs::remove_dir_all(&directory).ok();
+
+        for (name, contents) in files {
+            let path = directory.join(name);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        directory
+    }
+
+    /// What the packlist of `directory` packs, as `/` separated paths.
+    fn packed(directory: &Path) -> Vec<String> {
+        Packlist::new(directory, true)
+            .unwrap()
+            .files()
+            .unwrap()
+            .iter()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn never_packs_secrets_or_volt_state() {
+        let secret_key = format!(
+            "{}\nRWQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\n",
+            String::from_utf8_lossy(SECRET_KEY_HEADER)
+        );
+
+        let directory = package(
+            "secrets",
+            &[
+                ("package.json", r#"{ "name": "pkg", "version": "1.0.0" }"#),
+                ("index.js", ""),
+                (".npmrc", "//registry.npmjs.org/:_authToken=secret"),
+                (".env", "TOKEN=secret"),
+                (".env.production", "TOKEN=secret"),
+                ("volt.toml", ""),
+                ("volt.lock", ""),
+                ("volt.lock.sig", ""),
+                (".volt/state", ""),
+                (".git/config", ""),
+                ("pkg-0.9.0.tgz", ""),
+                ("lib/.npmrc", "//registry.npmjs.org/:_authToken=secret"),
+                // secret keys are recognized by their contents, whatever they're called
+                ("keys/release.txt", &secret_key),
+            ],
+        );
+
+        assert_eq!(packed(&directory), ["index.js", "package.json"]);
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn files_limits_what_is_packed() {
+        let directory = package(
+            "files",
+            &[
+                (
+                    "package.json",
+                    r#"{
+                        "name": "pkg",
+                        "version": "1.0.0",
+                        "main": "./lib/main.js",
+                        "bin": { "pkg": "bin/cli.js" },
+                        "files": ["dist", "!dist/*.map"]
+                    }"#,
+                ),
+                ("README.md", ""),
+                ("LICENSE", ""),
+                ("CHANGELOG.md", ""),
+                ("dist/index.js", ""),
+                ("dist/index.js.map", ""),
+                ("dist/.env", "TOKEN=secret"),
+                ("lib/main.js", ""),
+                ("lib/other.js", ""),
+                ("bin/cli.js", ""),
+                ("src/index.ts", ""),
+                // ignore files at the top don't apply with `files`, nested ones still do
+                (".npmignore", "dist"),
+                ("dist/nested/.npmignore", "*.txt"),
+                ("dist/nested/notes.txt", ""),
+                ("dist/nested/index.js", ""),
+                ("node_modules/dep/index.js", ""),
+            ],
+        );
+
+        assert_eq!(
+            packed(&directory),
+            [
+                "LICENSE",
+                "README.md",
+                "bin/cli.js",
+                "dist/index.js",
+                "dist/nested/index.js",
+                "lib/main.js",
+                "package.json",
+            ]
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn npmignore_replaces_gitignore() {
+        let directory = package(
+            "npmignore",
+            &[
+                (
+                    "package.json",
+                    r#"{ "name": "pkg", "main": "build/index.js" }"#,
+                ),
+                (".gitignore", "build\n"),
+                (".npmignore", "*.log\ntest/\n"),
+                ("build/index.js", ""),
+                ("build/other.js", ""),
+                ("debug.log", ""),
+                ("test/index.js", ""),
+                ("sub/.npmignore", "private.js\n"),
+                ("sub/private.js", ""),
+                ("sub/public.js", ""),
+            ],
+        );
+
+        assert_eq!(
+            packed(&directory),
+            [
+                "build/index.js",
+                "build/other.js",
+                "package.json",
+                "sub/public.js"
+            ]
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn gitignore_applies_without_npmignore() {
+        let directory = package(
+            "gitignore",
+            &[
+                (
+                    "package.json",
+                    r#"{ "name": "pkg", "main": "dist/index.js" }"#,
+                ),
+                (".gitignore", "dist\ncoverage/\n"),
+                ("dist/index.js", ""),
+                ("dist/other.js", ""),
+                ("coverage/lcov.info", ""),
+                ("index.js", ""),
+            ],
+        );
+
+        // `main` is packed from an ignored directory, the rest of it isn't
+        assert_eq!(
+            packed(&directory),
+            ["dist/index.js", "index.js", "package.json"]
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn tarballs_are_reproducible() {
+        let files = [
+            ("package.json", r#"{ "name": "pkg", "version": "1.0.0" }"#),
+            ("index.js", "module.exports = 1;\n"),
+            ("lib/util.js", ""),
+        ];
+
+        let first =
+            pack_directory(&package("reproducible", &files), &PackOptions::default()).unwrap();
+
+        // written again, later and with other permissions
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+
+        let directory = package("reproducible", &files);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(
+                directory.join("index.js"),
+                std::fs::Permissions::from_mode(0o600),
+            )
+            .unwrap();
+        }
+
+        let second = pack_directory(&directory, &PackOptions::default()).unwrap();
+
+        assert_eq!(tarball_hashes(&first), tarball_hashes(&second));
+
+        let mut archive = Archive::new(GzDecoder::new(second.as_slice()));
+
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+
+            assert_eq!(header.mtime().unwrap(), DETERMINISTIC_TIMESTAMP);
+            assert_eq!(header.uid().unwrap(), 0);
+            assert_eq!(header.mode().unwrap(), 0o644);
+        }
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn bundled_dependencies_leave_out_secrets() {
+        let directory = package(
+            "bundled",
+            &[
+                (
+                    "package.json",
+                    r#"{ "name": "pkg", "bundleDependencies": ["dep"] }"#,
+                ),
+                ("node_modules/dep/package.json", r#"{ "name": "dep" }"#),
+                ("node_modules/dep/index.js", ""),
+                (
+                    "node_modules/dep/.npmrc",
+                    "//registry.npmjs.org/:_authToken=secret",
+                ),
+                ("node_modules/dep/node_modules/nested/index.js", ""),
+            ],
+        );
+
+        let options = PackOptions {
+            bundled: vec![String::from("dep")],
+            extra_files: vec![],
+        };
+
+        let tarball = pack_directory(&directory, &options).unwrap();
+
+        let mut archive = Archive::new(GzDecoder::new(tarball.as_slice()));
+
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            [
+                "package/package.json",
+                "package/node_modules/dep/index.js",
+                "package/node_modules/dep/node_modules/nested/index.js",
+                "package/node_modules/dep/package.json",
+            ]
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
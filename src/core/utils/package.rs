@@ -14,17 +14,21 @@
     limitations under the License.
 */
 
-use std::path::PathBuf;
-use std::{collections::HashMap, fs::read_to_string};
+use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::read_to_string,
+};
 
 use super::errors::VoltError;
+use super::platform::Platform;
 use miette::{IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::commands::add::Package;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(default, rename_all = "camelCase")]
 pub struct NpmPackage {
     #[serde(rename = "_id")]
     pub id: String,
@@ -34,16 +38,320 @@ pub struct NpmPackage {
     #[serde(rename = "dist-tags")]
     pub dist_tags: HashMap<String, String>,
     pub versions: HashMap<String, Version>,
-    pub time: HashMap<String, String>,
-    pub maintainers: Vec<Maintainer>,
+    pub time: PackumentTime,
+    pub maintainers: Vec<Person>,
     pub description: Option<String>,
     pub homepage: Option<String>,
     pub repository: Option<Repository>,
-    pub author: Option<Author>,
+    pub author: Option<Person>,
     pub keywords: Option<Vec<String>>,
     pub bugs: Option<Bugs>,
-    pub license: Option<String>,
+    pub license: Option<License>,
     pub readme: Option<String>,
+    pub users: HashMap<String, serde_json::Value>,
+}
+
+/// Abbreviated package document (`application/vnd.npm.install-v1+json`), which only
+/// carries the fields needed to resolve and install a package.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Packument {
+    pub name: String,
+    #[serde(rename = "dist-tags")]
+    pub dist_tags: HashMap<String, String>,
+    pub versions: HashMap<String, PackumentVersion>,
+    pub modified: String,
+    /// Publish time of every version, only present in full documents like everything below.
+    pub time: PackumentTime,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<Repository>,
+    pub author: Option<Person>,
+    pub license: Option<License>,
+    pub maintainers: Vec<Person>,
+    /// Users who starred the package, usually all `true`.
+    pub users: HashMap<String, serde_json::Value>,
+}
+
+impl Packument {
+    /// How many users starred the package.
+    pub fn stars(&self) -> usize {
+        self.users
+            .values()
+            .filter(|starred| starred.as_bool().unwrap_or(true))
+            .count()
+    }
+}
+
+/// `time` of a full document: when the package was created and last modified, and when every
+/// version was published. Packages whose versions were all unpublished only have
+/// `unpublished`, an object.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackumentTime {
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unpublished: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub versions: HashMap<String, serde_json::Value>,
+}
+
+impl PackumentTime {
+    /// When `version` was published.
+    pub fn get(&self, version: &str) -> Option<&String> {
+        match self.versions.get(version)? {
+            serde_json::Value::String(time) => Some(time),
+            _ => None,
+        }
+    }
+}
+
+/// `repository`, a url, a shorthand like `github:user/repo` or `user/repo`, or an object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Repository {
+    Url(String),
+    Object {
+        #[serde(default, rename = "type")]
+        kind: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+        /// Directory of the package in a monorepo.
+        #[serde(default)]
+        directory: Option<String>,
+    },
+    Other(serde_json::Value),
+}
+
+impl Repository {
+    /// A url to browse the repository at, shorthands expanded and `git+` and `.git` dropped.
+    pub fn url(&self) -> Option<String> {
+        let url = match self {
+            Repository::Url(url) => url,
+            Repository::Object { url: Some(url), .. } => url,
+            _ => return None,
+        };
+
+        let hosts = [
+            ("github:", "https://github.com/"),
+            ("gitlab:", "https://gitlab.com/"),
+            ("bitbucket:", "https://bitbucket.org/"),
+            ("gist:", "https://gist.github.com/"),
+        ];
+
+        for (prefix, host) in hosts.iter() {
+            if let Some(path) = url.strip_prefix(prefix) {
+                return Some(format!("{}{}", host, path));
+            }
+        }
+
+        // `user/repo` is short for a GitHub repository
+        if !url.contains(':') && url.matches('/').count() == 1 {
+            return Some(format!("https://github.com/{}", url));
+        }
+
+        let url = url
+            .trim_start_matches("git+")
+            .trim_end_matches(".git")
+            .replacen("git://", "https://", 1)
+            .replacen("ssh://git@", "https://", 1)
+            .replacen("git@github.com:", "https://github.com/", 1);
+
+        Some(url)
+    }
+}
+
+/// `license`, an SPDX expression, an old `{ "type": ... }` object, or a list of either from the
+/// even older `licenses`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum License {
+    Spdx(String),
+    Object {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        url: Option<String>,
+    },
+    List(Vec<License>),
+    Other(serde_json::Value),
+}
+
+impl License {
+    /// The license as an SPDX expression, a list is read as a choice between its licenses.
+    pub fn expression(&self) -> Option<String> {
+        match self {
+            License::Spdx(license) => Some(license.clone()),
+            License::Object { kind, .. } => Some(kind.clone()),
+            License::List(licenses) => {
+                let licenses = licenses
+                    .iter()
+                    .filter_map(License::expression)
+                    .collect::<Vec<_>>();
+
+                match licenses.len() {
+                    0 => None,
+                    1 => Some(licenses[0].clone()),
+                    _ => Some(format!("({})", licenses.join(" OR "))),
+                }
+            }
+            License::Other(_) => None,
+        }
+    }
+}
+
+/// `author`, `maintainers` and `contributors` entries, `"Name <email> (url)"` or an object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Person {
+    Text(String),
+    Object {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        email: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+    },
+    Other(serde_json::Value),
+}
+
+impl Person {
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Person::Text(text) => text
+                .split(['<', '('])
+                .next()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+            Person::Object { name, .. } => name.clone(),
+            Person::Other(_) => None,
+        }
+    }
+
+    pub fn email(&self) -> Option<String> {
+        match self {
+            Person::Text(text) => {
+                let (_, rest) = text.split_once('<')?;
+                let (email, _) = rest.split_once('>')?;
+
+                Some(email.trim().to_string())
+            }
+            Person::Object { email, .. } => email.clone(),
+            Person::Other(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Person {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.name(), self.email()) {
+            (Some(name), Some(email)) => write!(f, "{} <{}>", name, email),
+            (Some(name), None) => write!(f, "{}", name),
+            (None, Some(email)) => write!(f, "<{}>", email),
+            (None, None) => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PackumentVersion {
+    pub name: String,
+    pub version: String,
+    /// Only in full documents, like `homepage` and `license`.
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    /// Usually an SPDX expression, some old packages have a `{ "type": ... }` object.
+    pub license: Option<serde_json::Value>,
+    /// The deprecation message, some registries send `false` for versions that aren't.
+    pub deprecated: Option<serde_json::Value>,
+    pub dependencies: HashMap<String, String>,
+    pub optional_dependencies: HashMap<String, String>,
+    pub peer_dependencies: HashMap<String, String>,
+    pub peer_dependencies_meta: HashMap<String, PeerDependencyMeta>,
+    pub bin: Option<serde_json::Value>,
+    pub dist: Dist,
+    /// Usually a map like `{ "node": ">=14" }`, some old packages have an array of strings.
+    pub engines: Option<serde_json::Value>,
+    /// Platforms and architectures the version is limited to, usually an array like
+    /// `["linux"]` or `["!arm"]`, some old packages have a single string.
+    pub os: Option<serde_json::Value>,
+    pub cpu: Option<serde_json::Value>,
+    /// `glibc` or `musl` for packages with a build per libc, on Linux only.
+    pub libc: Option<serde_json::Value>,
+    /// Set by the registry in abbreviated documents, full documents carry the `scripts` instead.
+    pub has_install_script: bool,
+    pub scripts: HashMap<String, String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PeerDependencyMeta {
+    pub optional: bool,
+}
+
+impl PackumentVersion {
+    /// Whether installing this version runs `preinstall`, `install` or `postinstall` scripts.
+    pub fn runs_install_scripts(&self) -> bool {
+        self.has_install_script
+            || ["preinstall", "install", "postinstall"]
+                .iter()
+                .any(|script| self.scripts.contains_key(*script))
+    }
+
+    /// The `bin` field normalized to a map, `"bin": "cli.js"` is shorthand for `{ "<name>": "cli.js" }`.
+    pub fn bin_map(&self) -> Option<HashMap<String, String>> {
+        match self.bin.as_ref()? {
+            serde_json::Value::String(path) => {
+                let mut bin = HashMap::new();
+                bin.insert(
+                    self.name.rsplit('/').next().unwrap().to_string(),
+                    path.to_string(),
+                );
+                Some(bin)
+            }
+            serde_json::Value::Object(map) => Some(
+                map.iter()
+                    .filter_map(|(k, v)| v.as_str().map(|v| (k.to_string(), v.to_string())))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The platforms `os`, `cpu` and `libc` let this version be installed on.
+    pub fn platform(&self) -> Platform {
+        Platform::from_fields(self.os.as_ref(), self.cpu.as_ref(), self.libc.as_ref())
+    }
+
+    /// Whether `os`, `cpu` and `libc` let this version be installed on this machine.
+    pub fn supports_platform(&self) -> bool {
+        self.platform().supported()
+    }
+
+    /// The `engines` field as a map, anything that isn't one is ignored.
+    pub fn engines_map(&self) -> BTreeMap<String, String> {
+        match self.engines.as_ref() {
+            Some(serde_json::Value::Object(map)) => map
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.to_string(), v.to_string())))
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Peer dependencies that aren't marked optional in `peerDependenciesMeta`.
+    pub fn required_peer_dependencies(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.peer_dependencies.iter().filter(move |(name, _)| {
+            !self
+                .peer_dependencies_meta
+                .get(*name)
+                .map(|meta| meta.optional)
+                .unwrap_or(false)
+        })
+    }
 }
 
 // #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -82,7 +390,7 @@ pub struct Version {
     #[serde(rename = "_npmVersion")]
     pub npm_version: String,
     pub dist: Dist,
-    pub maintainers: Vec<Maintainer>,
+    pub maintainers: Vec<Person>,
     #[serde(rename = "_npmUser")]
     pub npm_user: NpmUser,
     pub directories: Directories,
@@ -93,20 +401,6 @@ pub struct Version {
     pub readme: Option<String>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(default, rename_all = "camelCase")]
-pub struct Author {
-    pub name: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(default, rename_all = "camelCase")]
-pub struct Repository {
-    #[serde(rename = "type")]
-    pub type_field: String,
-    pub url: String,
-}
-
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Engines {
@@ -143,13 +437,15 @@ pub struct Dist {
     pub unpacked_size: i64,
     #[serde(rename = "npm-signature")]
     pub npm_signature: String,
+    /// ECDSA signatures of `name@version:integrity` by the registry's keys.
+    pub signatures: Vec<DistSignature>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(default, rename_all = "camelCase")]
-pub struct Maintainer {
-    pub name: String,
-    pub email: String,
+#[serde(default)]
+pub struct DistSignature {
+    pub keyid: String,
+    pub sig: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -172,46 +468,67 @@ pub struct NpmOperationalInternal {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PackageJson {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub version: String,
     pub main: Option<String>,
-    pub repository: Option<String>,
-    pub author: Option<String>,
-    pub license: Option<String>,
+    /// Either a url or a `{ "type": "git", "url": ... }` object.
+    pub repository: Option<serde_json::Value>,
+    /// Either `"Name <email> (url)"` or a `{ "name": ..., "email": ... }` object.
+    pub author: Option<serde_json::Value>,
+    pub license: Option<License>,
     #[serde(default)]
     pub dependencies: HashMap<String, String>,
     #[serde(rename = "devDependencies")]
     #[serde(default)]
     pub dev_dependencies: HashMap<String, String>,
+    #[serde(rename = "optionalDependencies")]
+    #[serde(default)]
+    pub optional_dependencies: HashMap<String, String>,
     #[serde(default)]
     pub scripts: HashMap<String, String>,
 }
 
 impl PackageJson {
-    pub fn open(_path: &str) -> Result<(Self, PathBuf)> {
-        for parent in std::env::current_exe()
-            .map_err(|e| VoltError::EnvironmentError {
-                env: String::from("CURRENT_DIR"),
+    /// Read the package.json at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        serde_json::from_str(&data).map_err(|e| {
+            VoltError::InvalidPackageJson {
+                path: path.display().to_string(),
                 source: e,
-            })?
-            .parent()
-            .expect("this should have a parent!")
-            .ancestors()
-        {
+            }
+            .into()
+        })
+    }
+
+    pub fn open(_path: &str) -> Result<(Self, PathBuf)> {
+        let exe = std::env::current_exe().map_err(|e| VoltError::EnvironmentError {
+            env: String::from("CURRENT_DIR"),
+            source: e,
+        })?;
+
+        for parent in exe.parent().into_iter().flat_map(Path::ancestors) {
             let pkg_path = parent.join("package.json");
 
             if pkg_path.exists() {
                 let data = read_to_string(&pkg_path).map_err(|e| VoltError::ReadFileError {
                     source: e,
-                    name: pkg_path.to_str().unwrap().to_string(),
+                    name: pkg_path.display().to_string(),
                 })?;
 
-                return Ok((
-                    serde_json::from_str(data.as_str()).into_diagnostic()?,
-                    pkg_path,
-                ));
-            } else {
-                miette::bail!("No package.json found!")
+                let package =
+                    serde_json::from_str(&data).map_err(|e| VoltError::InvalidPackageJson {
+                        path: pkg_path.display().to_string(),
+                        source: e,
+                    })?;
+
+                return Ok((package, pkg_path));
             }
         }
 
@@ -234,6 +551,110 @@ impl PackageJson {
     //     Ok(())
     // }
 
+    /// Write `dependencies` into the package.json at `path`, leaving everything else
+    /// (including the order of its keys) untouched.
+    pub fn save_dependencies(path: &Path, dependencies: &[(String, String)]) -> Result<()> {
+        let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        let mut manifest = parse_manifest(path, &data)?;
+
+        if !manifest["dependencies"].is_object() {
+            manifest["dependencies"] = serde_json::json!({});
+        }
+
+        let section = manifest["dependencies"].as_object_mut().unwrap();
+
+        for (name, spec) in dependencies {
+            section.insert(name.clone(), serde_json::Value::String(spec.clone()));
+        }
+
+        let mut data = serde_json::to_string_pretty(&manifest).into_diagnostic()?;
+        data.push('\n');
+
+        std::fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Set the spec of `name` to `spec` in every dependency section of the package.json at
+    /// `path` declaring it, or add it to `dependencies` when none does.
+    pub fn replace_dependency(path: &Path, name: &str, spec: &str) -> Result<()> {
+        let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        let mut manifest = parse_manifest(path, &data)?;
+
+        let mut declared = false;
+
+        for section in ["dependencies", "devDependencies", "optionalDependencies"] {
+            if let Some(dependency) = manifest[section]
+                .as_object_mut()
+                .and_then(|section| section.get_mut(name))
+            {
+                *dependency = serde_json::Value::String(spec.to_string());
+                declared = true;
+            }
+        }
+
+        if !declared {
+            if !manifest["dependencies"].is_object() {
+                manifest["dependencies"] = serde_json::json!({});
+            }
+
+            manifest["dependencies"].as_object_mut().unwrap().insert(
+                name.to_string(),
+                serde_json::Value::String(spec.to_string()),
+            );
+        }
+
+        let mut data = serde_json::to_string_pretty(&manifest).into_diagnostic()?;
+        data.push('\n');
+
+        std::fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Remove `names` from every dependency section of the package.json at `path`, leaving
+    /// everything else untouched.
+    pub fn remove_dependencies(path: &Path, names: &[String]) -> Result<()> {
+        let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        let mut manifest = parse_manifest(path, &data)?;
+
+        for section in ["dependencies", "devDependencies", "optionalDependencies"] {
+            if let Some(section) = manifest[section].as_object_mut() {
+                for name in names {
+                    section.remove(name);
+                }
+            }
+        }
+
+        let mut data = serde_json::to_string_pretty(&manifest).into_diagnostic()?;
+        data.push('\n');
+
+        std::fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+
     pub fn add_dependency(&mut self, package: Package) {
         self.dependencies
             .insert(package.name, package.version.unwrap_or_default());
@@ -265,3 +686,22 @@ impl PackageJson {
     //     }
     // }
 }
+
+/// The package.json at `path` parsed from `data`, it has to be an object to be edited.
+fn parse_manifest(path: &Path, data: &str) -> Result<serde_json::Value> {
+    let invalid = |source| VoltError::InvalidPackageJson {
+        path: path.display().to_string(),
+        source,
+    };
+
+    let manifest: serde_json::Value = serde_json::from_str(data).map_err(invalid)?;
+
+    if !manifest.is_object() {
+        let error =
+            <serde_json::Error as serde::de::Error>::custom("expected an object at the top level");
+
+        return Err(invalid(error).into());
+    }
+
+    Ok(manifest)
+}
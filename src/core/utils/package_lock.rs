@@ -0,0 +1,412 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Convert between volt's lockfile and npm's `package-lock.json`.
+//!
+//! Importing keeps the versions npm pinned for a project moving to volt. Only lockfile versions
+//! 2 and 3 are read, they list every installed package under `packages` keyed by its path in
+//! node_modules. Workspace links, bundled packages and packages that didn't come from a
+//! registry tarball are left for the resolver.
+//!
+//! Exporting writes a version 3 lockfile for tools that only understand npm's format.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::core::model::lock_file::{DependencyID, DependencyLock, LockFile};
+use crate::core::utils::app::App;
+use crate::core::utils::overrides::Override;
+use crate::core::utils::platform::Platform;
+use crate::core::utils::{hex_to_integrity, integrity_to_hex, satisfies};
+
+use miette::{IntoDiagnostic, Result};
+use serde_json::{json, Map, Value};
+use tracing::warn;
+
+/// A lockfile seeded from the `package-lock.json` in `directory`, `None` when there is none
+/// or it is in a format volt can't read.
+pub fn import_package_lock(directory: &Path, lock_file_path: &Path) -> Result<Option<LockFile>> {
+    let data = match read_to_string(directory.join("package-lock.json")) {
+        Ok(data) => data,
+        Err(_) => return Ok(None),
+    };
+
+    let document: Value = serde_json::from_str(&data).into_diagnostic()?;
+
+    match document["lockfileVersion"].as_u64() {
+        Some(2) | Some(3) => {}
+        version => {
+            warn!(
+                "not importing package-lock.json, lockfile version {} isn't supported (run `npm install --lockfile-version 3` first)",
+                version.unwrap_or(1)
+            );
+
+            return Ok(None);
+        }
+    }
+
+    let packages = match document["packages"].as_object() {
+        Some(packages) => packages,
+        None => return Ok(None),
+    };
+
+    let mut lock_file = LockFile::new(lock_file_path);
+
+    for (path, entry) in packages.iter() {
+        // `node_modules/a/node_modules/@b/c` is installed as `@b/c`
+        let install_name = match path.rsplit_once("node_modules/") {
+            Some((_, name)) => name,
+            None => continue,
+        };
+
+        if entry["link"].as_bool() == Some(true) || entry["inBundle"].as_bool() == Some(true) {
+            continue;
+        }
+
+        let (version, tarball, integrity) = match (
+            entry["version"].as_str(),
+            entry["resolved"].as_str(),
+            entry["integrity"].as_str(),
+        ) {
+            (Some(version), Some(tarball), Some(integrity)) if tarball.starts_with("http") => {
+                (version, tarball, integrity)
+            }
+            _ => continue,
+        };
+
+        let integrity = match integrity_to_hex(integrity) {
+            Ok(integrity) => integrity,
+            Err(_) => continue,
+        };
+
+        // aliases (`"a": "npm:b@1.0.0"`) carry the real name in `name`
+        let name = entry["name"].as_str().unwrap_or(install_name);
+        let alias = (name != install_name).then(|| install_name.to_string());
+
+        let mut dependencies = vec![];
+
+        for field in ["dependencies", "optionalDependencies"] {
+            if let Some(section) = entry[field].as_object() {
+                dependencies.extend(section.keys().cloned());
+            }
+        }
+
+        let id = match &alias {
+            Some(alias) => DependencyID(alias.clone(), format!("npm:{}@{}", name, version)),
+            None => DependencyID(name.to_string(), version.to_string()),
+        };
+
+        lock_file.dependencies.insert(
+            id,
+            DependencyLock {
+                name: name.to_string(),
+                version: version.to_string(),
+                tarball: tarball.to_string(),
+                integrity,
+                dependencies,
+                optional: entry["optional"].as_bool().unwrap_or(false),
+                files: BTreeMap::new(),
+                alias,
+                has_install_script: entry["hasInstallScript"].as_bool().unwrap_or(false),
+                overridden: None,
+                extended: vec![],
+                platform: Platform::from_fields(
+                    entry.get("os"),
+                    entry.get("cpu"),
+                    entry.get("libc"),
+                ),
+                engines: entry["engines"]
+                    .as_object()
+                    .map(|engines| {
+                        engines
+                            .iter()
+                            .filter_map(|(name, range)| {
+                                Some((name.clone(), range.as_str()?.to_string()))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                manifest: None,
+            },
+        );
+    }
+
+    Ok(Some(lock_file))
+}
+
+/// Exact versions of the packages `lock_file` has a single version of, applied like overrides
+/// to keep the tree npm resolved. Packages npm installed several versions of are left to the
+/// resolver, node_modules only holds one of them.
+pub fn pinned_versions(lock_file: &LockFile) -> Vec<Override> {
+    let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for lock in lock_file.dependencies.values() {
+        if lock.alias.is_none() {
+            versions.entry(&lock.name).or_default().push(&lock.version);
+        }
+    }
+
+    let mut pins = versions
+        .into_iter()
+        .filter(|(_, versions)| versions.len() == 1)
+        .map(|(name, versions)| Override {
+            name: name.to_string(),
+            selector: None,
+            range: versions[0].to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    pins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    pins
+}
+
+/// Dependency sections of a manifest, in the order npm writes them.
+const DEPENDENCY_FIELDS: [&str; 4] = [
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "peerDependencies",
+];
+
+/// The manifest of `path`, `Value::Null` when it can't be read.
+fn read_manifest(path: &Path) -> Value {
+    read_to_string(path.join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or(Value::Null)
+}
+
+/// Ranges a manifest declares for its dependencies and optional dependencies.
+fn declared_ranges(manifest: &Value) -> HashMap<String, String> {
+    ["dependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|field| manifest[field].as_object())
+        .flat_map(|section| section.iter())
+        .filter_map(|(name, range)| Some((name.clone(), range.as_str()?.to_string())))
+        .collect()
+}
+
+/// Install names reachable from `roots` through the dependencies `lock_file` records.
+pub fn reachable<'a>(lock_file: &'a LockFile, roots: Vec<&'a str>) -> HashSet<&'a str> {
+    let mut by_name: HashMap<&str, Vec<&DependencyLock>> = HashMap::new();
+
+    for lock in lock_file.dependencies.values() {
+        by_name.entry(lock.install_name()).or_default().push(lock);
+    }
+
+    let mut seen = HashSet::new();
+    let mut queue = roots;
+
+    while let Some(name) = queue.pop() {
+        if !seen.insert(name) {
+            continue;
+        }
+
+        for lock in by_name.get(name).into_iter().flatten() {
+            queue.extend(lock.dependencies.iter().map(String::as_str));
+        }
+    }
+
+    seen
+}
+
+/// `lock_file` as a version 3 `package-lock.json` for the project in `app.current_dir`.
+///
+/// node_modules holds one version of every package, the others are nested under the
+/// packages whose declared ranges they satisfy. Returns the document and the number of
+/// versions that couldn't be placed anywhere.
+pub fn export_package_lock(app: &App, lock_file: &LockFile) -> (Value, usize) {
+    let root = read_manifest(&app.current_dir);
+
+    let mut locks = lock_file.dependencies.values().collect::<Vec<_>>();
+
+    // the installed version of a name is hoisted, otherwise the newest one
+    locks.sort_by_cached_key(|lock| {
+        let installed = read_manifest(&app.node_modules_dir.join(lock.install_name()))["version"]
+            .as_str()
+            == Some(lock.version.as_str());
+
+        let version = node_semver::Version::parse(&lock.version).ok();
+
+        (
+            lock.install_name().to_string(),
+            std::cmp::Reverse((installed, version)),
+        )
+    });
+
+    let mut hoisted: HashMap<&str, &DependencyLock> = HashMap::new();
+    let mut nested = vec![];
+
+    for lock in locks.iter() {
+        if hoisted.contains_key(lock.install_name()) {
+            nested.push(*lock);
+        } else {
+            hoisted.insert(lock.install_name(), lock);
+        }
+    }
+
+    let production = reachable(
+        lock_file,
+        ["dependencies", "optionalDependencies", "peerDependencies"]
+            .iter()
+            .filter_map(|field| root[field].as_object())
+            .flat_map(|section| section.keys().map(String::as_str))
+            .collect(),
+    );
+
+    let ranges = |lock: &DependencyLock| {
+        read_manifest(&app.node_modules_dir.join(lock.install_name()))
+            .as_object()
+            .filter(|manifest| {
+                manifest.get("version").and_then(Value::as_str) == Some(&lock.version)
+            })
+            .map(|manifest| declared_ranges(&Value::Object(manifest.clone())))
+            .unwrap_or_default()
+    };
+
+    let entry = |lock: &DependencyLock| {
+        let declared = ranges(lock);
+
+        let mut entry = Map::new();
+
+        if lock.alias.is_some() {
+            entry.insert(String::from("name"), json!(lock.name));
+        }
+
+        entry.insert(String::from("version"), json!(lock.version));
+        entry.insert(String::from("resolved"), json!(lock.tarball));
+
+        if let Some(integrity) = hex_to_integrity(&lock.integrity) {
+            entry.insert(String::from("integrity"), json!(integrity));
+        }
+
+        if !production.contains(lock.install_name()) {
+            entry.insert(String::from("dev"), json!(true));
+        }
+
+        if lock.optional {
+            entry.insert(String::from("optional"), json!(true));
+        }
+
+        if lock.has_install_script {
+            entry.insert(String::from("hasInstallScript"), json!(true));
+        }
+
+        let mut dependencies = lock.dependencies.clone();
+        dependencies.sort();
+        dependencies.dedup();
+
+        let dependencies = dependencies
+            .into_iter()
+            .map(|name| {
+                // without the installed manifest the locked version is the tightest range
+                let range = declared
+                    .get(&name)
+                    .cloned()
+                    .or_else(|| hoisted.get(name.as_str()).map(|lock| lock.version.clone()));
+
+                (name, json!(range.unwrap_or_else(|| String::from("*"))))
+            })
+            .collect::<Map<String, Value>>();
+
+        if !dependencies.is_empty() {
+            entry.insert(String::from("dependencies"), Value::Object(dependencies));
+        }
+
+        Value::Object(entry)
+    };
+
+    let mut packages = BTreeMap::new();
+
+    let mut root_entry = Map::new();
+
+    for field in ["name", "version", "license"] {
+        if let Some(value) = root.get(field) {
+            root_entry.insert(field.to_string(), value.clone());
+        }
+    }
+
+    for field in DEPENDENCY_FIELDS {
+        if let Some(value) = root.get(field) {
+            root_entry.insert(field.to_string(), value.clone());
+        }
+    }
+
+    for (name, lock) in hoisted.iter() {
+        packages.insert(format!("node_modules/{}", name), entry(lock));
+    }
+
+    let mut unplaced = 0;
+
+    for lock in nested {
+        let dependents = hoisted
+            .values()
+            .filter(|dependent| {
+                dependent
+                    .dependencies
+                    .iter()
+                    .any(|name| name == lock.install_name())
+            })
+            .filter(|dependent| {
+                let hoisted_version = &hoisted[lock.install_name()].version;
+
+                match ranges(dependent).get(lock.install_name()) {
+                    Some(range) => {
+                        satisfies(&lock.version, range) && !satisfies(hoisted_version, range)
+                    }
+                    None => false,
+                }
+            })
+            .map(|dependent| dependent.install_name())
+            .collect::<Vec<_>>();
+
+        if dependents.is_empty() {
+            unplaced += 1;
+        }
+
+        for dependent in dependents {
+            packages.insert(
+                format!(
+                    "node_modules/{}/node_modules/{}",
+                    dependent,
+                    lock.install_name()
+                ),
+                entry(lock),
+            );
+        }
+    }
+
+    let mut document = Map::new();
+
+    for field in ["name", "version"] {
+        if let Some(value) = root.get(field) {
+            document.insert(field.to_string(), value.clone());
+        }
+    }
+
+    document.insert(String::from("lockfileVersion"), json!(3));
+    document.insert(String::from("requires"), json!(true));
+
+    let mut entries = Map::new();
+    entries.insert(String::new(), Value::Object(root_entry));
+    entries.extend(packages);
+
+    document.insert(String::from("packages"), Value::Object(entries));
+
+    (Value::Object(document), unplaced)
+}
@@ -0,0 +1,204 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Leave out packages built for another platform.
+//!
+//! Packages shipping native binaries usually publish one package per platform, limited to it
+//! with `os`, `cpu` and `libc`, and depend on all of them as optional dependencies. Only the
+//! ones fitting the machine are downloaded, the others stay in volt.lock so the same lockfile
+//! installs everywhere. A required package that doesn't fit fails the install like with npm.
+
+use crate::core::model::http_manager::get_packument;
+use crate::core::utils::compat::{node_cpu, node_os, platform_allows};
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::voltapi::VoltPackage;
+use crate::App;
+
+use futures::{stream, StreamExt};
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use tracing::info;
+
+/// The platforms a package installs on, from its `os`, `cpu` and `libc` fields. Empty fields
+/// allow anything, `!name` allows everything but `name`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Platform {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cpu: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub libc: Vec<String>,
+}
+
+impl Platform {
+    /// The fields of a manifest, which are usually arrays and sometimes a single string.
+    pub fn from_fields(
+        os: Option<&serde_json::Value>,
+        cpu: Option<&serde_json::Value>,
+        libc: Option<&serde_json::Value>,
+    ) -> Platform {
+        let list = |field: Option<&serde_json::Value>| match field {
+            Some(serde_json::Value::String(entry)) => vec![entry.clone()],
+            Some(serde_json::Value::Array(entries)) => entries
+                .iter()
+                .filter_map(|entry| entry.as_str().map(String::from))
+                .collect(),
+            _ => vec![],
+        };
+
+        Platform {
+            os: list(os),
+            cpu: list(cpu),
+            libc: list(libc),
+        }
+    }
+
+    /// Whether the package installs anywhere.
+    pub fn is_any(&self) -> bool {
+        self.os.is_empty() && self.cpu.is_empty() && self.libc.is_empty()
+    }
+
+    /// Whether the package installs on this machine. Only Linux has a libc packages tell
+    /// apart, elsewhere a package limited to one doesn't fit.
+    pub fn supported(&self) -> bool {
+        platform_allows(&self.os, node_os())
+            && platform_allows(&self.cpu, node_cpu())
+            && (self.libc.is_empty() || platform_allows(&self.libc, node_libc().unwrap_or("")))
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [("os", &self.os), ("cpu", &self.cpu), ("libc", &self.libc)];
+
+        let described = fields
+            .iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(field, values)| format!("{} {}", field, values.join(", ")))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", described.join("; "))
+    }
+}
+
+/// The libc of this machine as the `libc` field names it, `glibc` or `musl`. `None` outside
+/// of Linux.
+pub fn node_libc() -> Option<&'static str> {
+    static LIBC: OnceLock<Option<&'static str>> = OnceLock::new();
+
+    *LIBC.get_or_init(|| {
+        if node_os() != "linux" {
+            return None;
+        }
+
+        // musl distributions ship its dynamic loader, e.g. `/lib/ld-musl-x86_64.so.1`
+        let musl = std::fs::read_dir("/lib")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"));
+
+        Some(if musl { "musl" } else { "glibc" })
+    })
+}
+
+/// This machine, the way platform errors name it, e.g. `linux x64 glibc`.
+fn current() -> String {
+    [Some(node_os()), Some(node_cpu()), node_libc()]
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Look up the platforms of the `optional` packages of `dependencies` that don't know theirs,
+/// trees from the CDN don't carry them. A package whose document can't be fetched is assumed
+/// to install anywhere.
+pub async fn resolve_platforms(
+    app: &App,
+    dependencies: &mut HashMap<String, VoltPackage>,
+    optional: &HashSet<String>,
+) {
+    let unknown = dependencies
+        .iter()
+        .filter(|(_, package)| package.platform.is_none() && optional.contains(&package.name))
+        .map(|(key, package)| (key.clone(), package.name.clone(), package.version.clone()))
+        .collect::<Vec<_>>();
+
+    let requests = unknown.into_iter().map(|(key, name, version)| async move {
+        let platform = get_packument(&app.registries, &name)
+            .await
+            .ok()
+            .and_then(|packument| packument.versions.get(&version).map(|v| v.platform()));
+
+        (key, platform)
+    });
+
+    let resolved = stream::iter(requests)
+        .buffer_unordered(app.concurrency())
+        .collect::<Vec<_>>()
+        .await;
+
+    for (key, platform) in resolved {
+        if let Some(package) = dependencies.get_mut(&key) {
+            package.platform = platform;
+        }
+    }
+}
+
+/// The packages out of `packages` that install on this machine. `optional` ones that don't
+/// are left out, any other one fails.
+pub fn installable<'a>(
+    packages: &[&'a VoltPackage],
+    optional: &HashSet<String>,
+) -> Result<Vec<&'a VoltPackage>> {
+    let mut installable = vec![];
+    let mut skipped = 0;
+
+    for &package in packages.iter() {
+        match &package.platform {
+            Some(platform) if !platform.supported() => {
+                if !optional.contains(&package.name) {
+                    return Err(VoltError::UnsupportedPlatform {
+                        name: package.name.clone(),
+                        version: package.version.clone(),
+                        wanted: platform.to_string(),
+                        current: current(),
+                    }
+                    .into());
+                }
+
+                skipped += 1;
+            }
+            _ => installable.push(package),
+        }
+    }
+
+    if skipped > 0 {
+        info!(
+            "skipping {} optional packages built for other platforms than {}",
+            skipped,
+            current()
+        );
+    }
+
+    Ok(installable)
+}
@@ -0,0 +1,256 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Plug'n'Play installs, with `node-linker = "pnp"`.
+//!
+//! Packages stay in the store and no node_modules is written. `.pnp.cjs` next to package.json
+//! maps every package to its directory in the store and to the packages its dependencies
+//! resolve to, and hooks `require()` to resolve through that map. `volt run` preloads it with
+//! `NODE_OPTIONS`, anything else can with `node -r ./.pnp.cjs`. The bins of the direct
+//! dependencies get shims in `.volt/bin`, which `volt run` puts on PATH.
+//!
+//! A package can only require what it declares. ES module `import`s and the `exports` of a
+//! package aren't mapped, those need a node_modules layout.
+
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::hoist::Graph;
+use crate::core::utils::linker::store_path;
+use crate::core::utils::voltapi::VoltPackage;
+
+use miette::Result;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// File the resolution map and its runtime are written to, next to package.json.
+pub const RUNTIME_FILE: &str = ".pnp.cjs";
+
+/// Resolves bare `require()`s of a package through `MAP`, everything else is left to node.
+const RUNTIME: &str = r#"
+const path = require("path");
+const Module = require("module");
+
+// longest locations first, so nested directories win
+const LOCATIONS = Object.entries(MAP.packages)
+  .map(([id, entry]) => [entry.location, id])
+  .sort((a, b) => b[0].length - a[0].length);
+
+function owner(file) {
+  for (const [location, id] of LOCATIONS) {
+    if (file === location || file.startsWith(location + path.sep)) {
+      return id;
+    }
+  }
+
+  return null;
+}
+
+function isBare(request) {
+  return !(
+    request.startsWith(".") ||
+    path.isAbsolute(request) ||
+    request.startsWith("node:") ||
+    Module.builtinModules.includes(request)
+  );
+}
+
+const resolveFilename = Module._resolveFilename;
+
+Module._resolveFilename = function (request, parent, isMain, options) {
+  if (!isBare(request)) {
+    return resolveFilename.call(this, request, parent, isMain, options);
+  }
+
+  const issuer = parent && parent.filename ? parent.filename : path.join(process.cwd(), "-");
+  const id = owner(issuer);
+
+  // files outside the project and its packages, like global tools, resolve as usual
+  if (id === null && !issuer.startsWith(MAP.project + path.sep)) {
+    return resolveFilename.call(this, request, parent, isMain, options);
+  }
+
+  const parts = request.split("/");
+  const length = request.startsWith("@") ? 2 : 1;
+  const name = parts.slice(0, length).join("/");
+  const subpath = parts.slice(length).join("/");
+
+  const dependencies = id === null ? MAP.dependencies : MAP.packages[id].dependencies;
+  const target = dependencies[name];
+
+  if (target === undefined) {
+    const error = new Error(
+      `${id === null ? "the project" : id} requires ${name}, which isn't one of its dependencies ` +
+        `(required from ${issuer})`
+    );
+    error.code = "MODULE_NOT_FOUND";
+    throw error;
+  }
+
+  const location = MAP.packages[target].location;
+
+  return resolveFilename.call(
+    this,
+    subpath ? path.join(location, subpath) : location,
+    parent,
+    isMain,
+    options
+  );
+};
+"#;
+
+/// `.pnp.cjs` that applies to `directory`, the closest one up from it.
+pub fn runtime_for(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|ancestor| ancestor.join(RUNTIME_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Directory of the bin shims of the project in `project`.
+pub fn bin_dir(project: &Path) -> PathBuf {
+    project.join(".volt").join("bin")
+}
+
+/// Write `.pnp.cjs` for `packages`, every one of them already in the store, and the shims
+/// of the bins of the direct dependencies of `graph`.
+pub fn write(app: &App, packages: &[&VoltPackage], graph: &Graph) -> Result<()> {
+    let id = |package: &VoltPackage| format!("{}@{}", package.name, package.version);
+
+    let stored = packages
+        .iter()
+        .map(|package| id(package))
+        .collect::<HashSet<_>>();
+
+    let mut entries = Map::new();
+
+    for package in packages.iter() {
+        let mut dependencies = Map::new();
+
+        for (name, dependency) in graph.dependencies(package) {
+            if stored.contains(&id(dependency)) {
+                dependencies.insert(name.to_string(), Value::String(id(dependency)));
+            }
+        }
+
+        entries.insert(
+            id(package),
+            json!({
+                "location": store_path(app, package).display().to_string(),
+                "dependencies": dependencies,
+            }),
+        );
+    }
+
+    let mut root = Map::new();
+
+    for package in graph.direct() {
+        if stored.contains(&id(package)) {
+            root.insert(
+                package.install_name().to_string(),
+                Value::String(id(package)),
+            );
+        }
+    }
+
+    let map = json!({
+        "project": app.current_dir.display().to_string(),
+        "dependencies": root,
+        "packages": entries,
+    });
+
+    let runtime = format!(
+        "#!/usr/bin/env node\n/* generated by volt install, do not edit */\n\"use strict\";\n\nconst MAP = {};\n{}",
+        serde_json::to_string_pretty(&map).unwrap(),
+        RUNTIME
+    );
+
+    let path = app.current_dir.join(RUNTIME_FILE);
+    let partial = path.with_extension("cjs.partial");
+
+    std::fs::write(&partial, runtime)
+        .and_then(|_| std::fs::rename(&partial, &path))
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+    let bins = packages
+        .iter()
+        .filter(|package| graph.is_direct(package))
+        .flat_map(|package| {
+            package
+                .bin
+                .iter()
+                .flatten()
+                .map(move |(name, file)| (name.clone(), store_path(app, package).join(file)))
+        })
+        .collect::<Vec<_>>();
+
+    write_bins(&bin_dir(&app.current_dir), &path, &bins)
+}
+
+/// Replace the shims in `directory` with ones running `bins` with `runtime` preloaded.
+fn write_bins(directory: &Path, runtime: &Path, bins: &[(String, PathBuf)]) -> Result<()> {
+    let write_error = |path: &Path| {
+        let name = path.display().to_string();
+        move |e: std::io::Error| VoltError::WriteFileError { source: e, name }
+    };
+
+    if directory.exists() {
+        std::fs::remove_dir_all(directory).map_err(write_error(directory))?;
+    }
+
+    std::fs::create_dir_all(directory).map_err(VoltError::CreateDirError)?;
+
+    for (name, file) in bins.iter() {
+        if cfg!(windows) {
+            let shim = directory.join(format!("{}.cmd", name));
+
+            std::fs::write(
+                &shim,
+                format!(
+                    "@node --require \"{}\" \"{}\" %*\r\n",
+                    runtime.display(),
+                    file.display()
+                ),
+            )
+            .map_err(write_error(&shim))?;
+        } else {
+            let shim = directory.join(name);
+
+            std::fs::write(
+                &shim,
+                format!(
+                    "#!/bin/sh\nexec node --require \"{}\" \"{}\" \"$@\"\n",
+                    runtime.display(),
+                    file.display()
+                ),
+            )
+            .map_err(write_error(&shim))?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755))
+                    .map_err(write_error(&shim))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Find the files of a tarball that can't be extracted as they are on this machine.
+//!
+//! On a case-insensitive filesystem, the default on macOS and Windows, `README.md` and
+//! `readme.md` are the same file and extracting both leaves whichever came last. On Windows a
+//! path longer than `MAX_PATH` can't be written by most tools, even when volt manages to. Both
+//! are checked before a tarball is extracted, so the install fails naming the files instead of
+//! leaving a package that is missing some of them.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Characters in a path Windows handles without long path support.
+const MAX_PATH: usize = 260;
+
+/// What's wrong with one file of a tarball.
+pub enum Problem {
+    /// Two files are the same on a case-insensitive filesystem.
+    Collision(PathBuf, PathBuf),
+    /// A file is too long a path once installed, with its length.
+    TooLong(PathBuf, usize),
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Collision(a, b) => {
+                write!(f, "{} and {} only differ in case", a.display(), b.display())
+            }
+            Problem::TooLong(path, length) => write!(
+                f,
+                "{} is {} characters long once installed, over {}",
+                path.display(),
+                length,
+                MAX_PATH
+            ),
+        }
+    }
+}
+
+/// Whether `directory` is on a case-insensitive filesystem, probed once per run.
+pub fn is_case_insensitive(directory: &Path) -> bool {
+    static CASE_INSENSITIVE: OnceLock<bool> = OnceLock::new();
+
+    *CASE_INSENSITIVE.get_or_init(|| {
+        let probe = directory.join(format!(".volt-case-probe-{}", std::process::id()));
+
+        if std::fs::create_dir_all(directory).is_err() || std::fs::write(&probe, "").is_err() {
+            // where nothing can be written, assume the platform's default
+            return cfg!(any(windows, target_os = "macos"));
+        }
+
+        let upper = directory.join(format!(".VOLT-CASE-PROBE-{}", std::process::id()));
+        let insensitive = upper.exists();
+
+        std::fs::remove_file(&probe).ok();
+
+        insensitive
+    })
+}
+
+/// Problems of `files`, the files of one package, when they're extracted on a case-insensitive
+/// filesystem (`case_insensitive`) and installed into every directory of `roots`.
+pub fn check(files: &[PathBuf], roots: &[PathBuf], case_insensitive: bool) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    if case_insensitive {
+        // the lowercased paths of every file and the directories above them
+        let mut seen: HashMap<String, (&Path, bool)> = HashMap::new();
+        let mut reported = HashSet::new();
+
+        for file in files.iter() {
+            let paths = file
+                .ancestors()
+                .filter(|path| !path.as_os_str().is_empty())
+                .map(|path| (path, path == file.as_path()));
+
+            for (path, is_file) in paths {
+                let folded = path.to_string_lossy().to_lowercase();
+
+                match seen.get(&folded) {
+                    // directories only differing in case merge, files get lost
+                    Some((other, other_is_file)) => {
+                        if *other != path && (is_file || *other_is_file) && reported.insert(folded)
+                        {
+                            problems
+                                .push(Problem::Collision(other.to_path_buf(), path.to_path_buf()));
+                        }
+                    }
+                    None => {
+                        seen.insert(folded, (path, is_file));
+                    }
+                }
+            }
+        }
+    }
+
+    if cfg!(windows) {
+        let longest = roots
+            .iter()
+            .map(|root| root.as_os_str().len())
+            .max()
+            .unwrap_or(0);
+
+        for file in files.iter() {
+            // the separator between the root and the file
+            let length = longest + 1 + file.as_os_str().len();
+
+            if length > MAX_PATH {
+                problems.push(Problem::TooLong(file.clone(), length));
+            }
+        }
+    }
+
+    problems
+}
@@ -0,0 +1,265 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Progress of resolving and installing: an overall bar with a line for every package below.
+//!
+//! The package lines are set up front, one for every package worked on at once up to
+//! `MAX_LINES`, and taken by packages as they start, so the display keeps its height however
+//! many packages there are. A line shows the phase of its package, and while downloading the
+//! bytes received and the speed.
+//!
+//! With `--no-progress`, or when stderr isn't a terminal, nothing is drawn and every finished
+//! package is logged on a line of its own instead. With `--json` nothing is drawn or logged.
+//!
+//! Messages logged while the bars are drawn are printed above them, see `core::utils::logging`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::core::utils::app::App;
+use crate::core::utils::constants::PROGRESS_CHARS;
+use crate::core::utils::logging;
+use crate::core::utils::timings::Phase;
+
+use colored::Colorize;
+use console::Term;
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use tracing::info;
+
+/// Most package lines drawn below the overall bar.
+const MAX_LINES: usize = 8;
+
+const PHASE_TEMPLATE: &str = "  {msg:>11.blue} {prefix:.cyan}";
+const DOWNLOAD_TEMPLATE: &str =
+    "  {msg:>11.blue} {prefix:.cyan} [{bar:20.magenta/blue}] {bytes}/{total_bytes} {bytes_per_sec}";
+/// Downloads of a size the server didn't send.
+const STREAM_TEMPLATE: &str = "  {msg:>11.blue} {prefix:.cyan} {bytes} {bytes_per_sec}";
+/// Lines no package is on.
+const IDLE_TEMPLATE: &str = " ";
+
+fn line_style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .progress_chars(PROGRESS_CHARS)
+        .template(template)
+}
+
+/// The display of a resolve or an install, see the module documentation.
+pub struct InstallProgress {
+    overall: ProgressBar,
+    /// Package lines no package is on.
+    lines: Mutex<Vec<ProgressBar>>,
+    /// Thread drawing the bars, until all of them are finished.
+    drawer: Option<JoinHandle<()>>,
+    plain: bool,
+    /// `--json`, the packages end up in its document instead of the log.
+    quiet: bool,
+}
+
+impl InstallProgress {
+    /// Start showing the progress of `total` packages under `title`.
+    pub fn new(app: &App, title: &str, total: usize) -> InstallProgress {
+        let quiet = app.json();
+        let plain = quiet || app.has_flag("no-progress") || !Term::stderr().is_term();
+
+        let style = ProgressStyle::default_bar()
+            .progress_chars(PROGRESS_CHARS)
+            .template(&format!(
+                "{} [{{bar:40.magenta/blue}}] {{pos}}/{{len}} {{msg:.blue}}",
+                title.bright_blue()
+            ));
+
+        if plain {
+            if !quiet {
+                println!("{}: {} packages", title, total);
+            }
+
+            let overall = ProgressBar::hidden();
+            overall.set_length(total as u64);
+
+            return InstallProgress {
+                overall,
+                lines: Mutex::new(vec![]),
+                drawer: None,
+                plain,
+                quiet,
+            };
+        }
+
+        let multi = MultiProgress::new();
+
+        let overall = multi.add(ProgressBar::new(total as u64));
+        overall.set_style(style);
+
+        let lines = (0..app.concurrency().min(MAX_LINES))
+            .map(|_| {
+                let line = multi.add(ProgressBar::new(0));
+                line.set_style(line_style(IDLE_TEMPLATE));
+                line
+            })
+            .collect();
+
+        logging::attach(&overall);
+
+        // indicatif draws the bars of a `MultiProgress` on the thread joining it
+        let drawer = std::thread::spawn(move || {
+            multi.join().ok();
+        });
+
+        InstallProgress {
+            overall,
+            lines: Mutex::new(lines),
+            drawer: Some(drawer),
+            plain,
+            quiet,
+        }
+    }
+
+    /// Show `label`, usually `name@version`, on a line of its own until the returned progress
+    /// is dropped. Packages starting while every line is taken are only counted.
+    pub fn package(&self, label: &str) -> PackageProgress<'_> {
+        let line = self.lines.lock().unwrap().pop();
+
+        if let Some(line) = &line {
+            line.reset();
+            line.set_prefix(label.to_string());
+        }
+
+        PackageProgress {
+            install: Some(self),
+            line,
+            label: label.to_string(),
+            start: Instant::now(),
+            received: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a package as done.
+    pub fn inc(&self) {
+        self.overall.inc(1);
+    }
+
+    /// Stop drawing, leaving the overall bar with `message`.
+    pub fn finish(mut self, message: &str) {
+        for line in self.lines.lock().unwrap().iter() {
+            line.finish_and_clear();
+        }
+
+        self.overall.finish_with_message(message.to_string());
+
+        if let Some(drawer) = self.drawer.take() {
+            drawer.join().ok();
+        }
+    }
+}
+
+impl Drop for InstallProgress {
+    fn drop(&mut self) {
+        if !self.plain {
+            logging::detach();
+        }
+    }
+}
+
+/// The line of a package being resolved or installed, given back when dropped.
+pub struct PackageProgress<'a> {
+    install: Option<&'a InstallProgress>,
+    line: Option<ProgressBar>,
+    label: String,
+    start: Instant,
+    /// Bytes downloaded, also by attempts that failed.
+    received: AtomicU64,
+}
+
+impl PackageProgress<'_> {
+    /// Progress for work nothing is displayed for, like the downloads of `volt cache`.
+    pub fn hidden() -> PackageProgress<'static> {
+        PackageProgress {
+            install: None,
+            line: None,
+            label: String::new(),
+            start: Instant::now(),
+            received: AtomicU64::new(0),
+        }
+    }
+
+    pub fn phase(&self, phase: Phase) {
+        if let Some(line) = &self.line {
+            line.set_style(line_style(PHASE_TEMPLATE));
+            line.set_message(phase.name());
+        }
+    }
+
+    /// A download of `length` bytes starts, the first `from` of them are already on disk.
+    pub fn downloading(&self, from: u64, length: Option<u64>) {
+        if let Some(line) = &self.line {
+            let template = match length {
+                Some(_) => DOWNLOAD_TEMPLATE,
+                None => STREAM_TEMPLATE,
+            };
+
+            line.set_style(line_style(template));
+            line.set_message(Phase::Download.name());
+            line.reset();
+            line.set_length(length.map_or(0, |length| from + length));
+            line.set_position(from);
+        }
+    }
+
+    /// `bytes` more of the download arrived.
+    pub fn received(&self, bytes: u64) {
+        self.received.fetch_add(bytes, Ordering::Relaxed);
+
+        if let Some(line) = &self.line {
+            line.inc(bytes);
+        }
+    }
+
+    /// The package is done, logged when nothing is drawn.
+    pub fn done(self) {
+        match self.install {
+            Some(install) if install.plain && !install.quiet => {}
+            _ => return,
+        }
+
+        let received = self.received.load(Ordering::Relaxed);
+
+        let downloaded = match received {
+            0 => String::new(),
+            bytes => format!(", downloaded {}", HumanBytes(bytes)),
+        };
+
+        info!(
+            "{} done in {:.2}s{}",
+            self.label.bright_cyan(),
+            self.start.elapsed().as_secs_f32(),
+            downloaded
+        );
+    }
+}
+
+impl Drop for PackageProgress<'_> {
+    fn drop(&mut self) {
+        if let (Some(install), Some(line)) = (self.install, self.line.take()) {
+            line.set_style(line_style(IDLE_TEMPLATE));
+            line.set_prefix(String::new());
+            line.set_message(String::new());
+
+            install.lines.lock().unwrap().push(line);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! On-disk cache of registry documents and tarballs, as served by `volt serve-registry`.
+//!
+//! ```text
+//! <volt dir>/registry/packuments/<name>.json        full documents
+//! <volt dir>/registry/packuments/<name>.install.json abbreviated documents
+//! <volt dir>/registry/packuments/<name>[.install].validators  their `ETag` and `Last-Modified`
+//! <volt dir>/registry/tarballs/<name>/<file>.tgz
+//! ```
+//!
+//! Scoped names are stored with the `/` escaped, e.g. `@types%2fnode.json`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::core::utils::errors::VoltError;
+
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+/// Writes started by this process, numbering their temporary files.
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// What the registry served a cached document with, sent back to ask whether it changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryCache {
+    pub directory: PathBuf,
+}
+
+impl RegistryCache {
+    pub fn new(volt_dir: &Path) -> Self {
+        Self {
+            directory: volt_dir.join("registry"),
+        }
+    }
+
+    /// Where the document of `name` is cached, `abbreviated` picks the install document.
+    pub fn packument_path(&self, name: &str, abbreviated: bool) -> PathBuf {
+        let suffix = if abbreviated {
+            ".install.json"
+        } else {
+            ".json"
+        };
+
+        self.directory
+            .join("packuments")
+            .join(format!("{}{}", escape(name), suffix))
+    }
+
+    /// Where the tarball `file` of `name` is cached.
+    pub fn tarball_path(&self, name: &str, file: &str) -> PathBuf {
+        self.directory
+            .join("tarballs")
+            .join(escape(name))
+            .join(file)
+    }
+
+    /// Contents of `path` when it was cached within `max_age`, or at any time with `None`.
+    pub fn read(&self, path: &Path, max_age: Option<Duration>) -> Option<Vec<u8>> {
+        if let Some(max_age) = max_age {
+            let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+
+            if age > max_age {
+                return None;
+            }
+        }
+
+        let contents = fs::read(path).ok()?;
+
+        trace!("cached {}", path.display());
+
+        Some(contents)
+    }
+
+    /// Store `contents` at `path`, replacing it in one step so readers never see half a file.
+    ///
+    /// Every write goes through a file of its own, concurrent writers of the same path each
+    /// replace it whole. The validators of the previous contents are dropped, they no longer
+    /// describe the file.
+    pub fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+        fs::remove_file(validators_path(path)).ok();
+
+        let partial = path.with_file_name(format!(
+            "{}.{}-{}.partial",
+            path.file_name().unwrap().to_string_lossy(),
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        fs::write(&partial, contents)
+            .and_then(|_| fs::rename(&partial, path))
+            .map_err(|e| {
+                fs::remove_file(&partial).ok();
+
+                VoltError::WriteFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                }
+            })?;
+
+        Ok(())
+    }
+
+    /// Validators of the document cached at `path`, `None` when the registry sent none.
+    pub fn read_validators(&self, path: &Path) -> Option<Validators> {
+        let contents = fs::read(validators_path(path)).ok()?;
+
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Remember what the registry served the document at `path` with.
+    pub fn write_validators(&self, path: &Path, validators: &Validators) -> Result<()> {
+        let validators_path = validators_path(path);
+
+        fs::write(&validators_path, serde_json::to_vec(validators).unwrap()).map_err(|e| {
+            VoltError::WriteFileError {
+                source: e,
+                name: validators_path.display().to_string(),
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+/// `<name>.json` keeps its validators in `<name>.validators`.
+fn validators_path(path: &Path) -> PathBuf {
+    path.with_extension("validators")
+}
+
+fn escape(name: &str) -> String {
+    name.replace('/', "%2f")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn cache(test: &str) -> RegistryCache {
+        let directory = std::env::temp_dir().join(format!(
+            "volt-registry-cache-{}-{}",
+            test,
+            std::process::id()
+        ));
+
+        fs::remove_dir_all(&directory).ok();
+
+        RegistryCache::new(&directory)
+    }
+
+    #[test]
+    fn escapes_scoped_names() {
+        let cache = cache("paths");
+
+        assert!(cache
+            .packument_path("@types/node", true)
+            .ends_with("packuments/@types%2fnode.install.json"));
+        assert!(cache
+            .tarball_path("@types/node", "node-1.0.0.tgz")
+            .ends_with("tarballs/@types%2fnode/node-1.0.0.tgz"));
+    }
+
+    #[test]
+    fn reads_what_was_written() {
+        let cache = cache("round-trip");
+        let path = cache.packument_path("left-pad", false);
+
+        assert_eq!(cache.read(&path, None), None);
+
+        cache.write(&path, b"{}").unwrap();
+        cache
+            .write_validators(
+                &path,
+                &Validators {
+                    etag: Some(String::from("\"abc\"")),
+                    last_modified: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(cache.read(&path, None).as_deref(), Some(&b"{}"[..]));
+        assert_eq!(
+            cache.read(&path, Some(Duration::from_secs(60))).as_deref(),
+            Some(&b"{}"[..])
+        );
+        assert_eq!(
+            cache.read_validators(&path).unwrap().etag.as_deref(),
+            Some("\"abc\"")
+        );
+
+        // new contents aren't described by the old validators
+        cache.write(&path, b"{ }").unwrap();
+
+        assert!(cache.read_validators(&path).is_none());
+
+        fs::remove_dir_all(&cache.directory).ok();
+    }
+
+    #[test]
+    fn concurrent_writes_never_interleave() {
+        let cache = Arc::new(cache("concurrent"));
+        let path = cache.tarball_path("left-pad", "left-pad-1.0.0.tgz");
+
+        let writers = (0..8u8)
+            .map(|writer| {
+                let (cache, path) = (cache.clone(), path.clone());
+
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        cache.write(&path, &vec![writer; 256 * 1024]).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let contents = cache.read(&path, None).unwrap();
+
+        assert_eq!(contents.len(), 256 * 1024);
+        assert!(contents.iter().all(|&byte| byte == contents[0]));
+
+        // nothing is left behind next to the tarball
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        fs::remove_dir_all(&cache.directory).ok();
+    }
+}
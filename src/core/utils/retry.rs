@@ -0,0 +1,116 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Repeat requests that failed for a passing reason, see `Retries` for the policy.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use isahc::http::{HeaderMap, StatusCode};
+
+use crate::core::model::config::Retries;
+use crate::core::utils::errors::VoltError;
+
+/// Why an attempt failed.
+pub enum Failure<E> {
+    /// Another attempt may work, after the delay the server asked for if it did.
+    Transient(E, Option<Duration>),
+    /// Trying again won't help.
+    Fatal(E),
+}
+
+/// Run `attempt` until it succeeds, fails for good or runs out of attempts.
+pub async fn retry<T, E, F, Fut>(retries: &Retries, mut attempt: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure<E>>>,
+{
+    let mut failures = 0;
+
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(Failure::Fatal(error)) => return Err(error),
+            Err(Failure::Transient(error, retry_after)) => {
+                failures += 1;
+
+                if failures >= retries.attempts() {
+                    return Err(error);
+                }
+
+                tokio::time::sleep(retries.delay(failures, retry_after)).await;
+            }
+        }
+    }
+}
+
+/// Whether a response with `status` is worth asking for again.
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+/// `Retry-After` in seconds, the http date form isn't worth the parsing.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Failures of isahc that come from the connection rather than the request.
+pub fn is_transient_isahc(error: &isahc::Error) -> bool {
+    error.is_network() || error.is_timeout()
+}
+
+/// Failures reading a response, the connection dropping or stalling halfway through.
+pub fn is_transient_io(error: &io::Error) -> bool {
+    match error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<isahc::Error>())
+    {
+        Some(error) => is_transient_isahc(error),
+        None => matches!(
+            error.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+        ),
+    }
+}
+
+/// Sort a failed download by the `VoltError` behind it.
+pub fn classify(report: miette::Report) -> Failure<miette::Report> {
+    let transient = match report.downcast_ref::<VoltError>() {
+        Some(VoltError::RequestTimedOut { .. }) => true,
+        Some(VoltError::TarballDownloadError { source, .. }) => is_transient_io(source),
+        _ => false,
+    };
+
+    if transient {
+        Failure::Transient(report, None)
+    } else {
+        Failure::Fatal(report)
+    }
+}
@@ -0,0 +1,423 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Software bills of materials of the lockfile, as CycloneDX 1.4 or SPDX 2.3 JSON.
+//!
+//! Every locked version is a component with its package URL (`pkg:npm/...`), the hash volt.lock
+//! pins it to and the license its installed package.json declares. The project itself is the
+//! root component, the edges between components are the dependencies volt.lock records, each
+//! name resolving to the version node_modules holds.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::read_to_string;
+
+use crate::core::model::lock_file::{DependencyLock, LockFile};
+use crate::core::utils::app::App;
+use crate::core::utils::installed_version;
+use crate::core::utils::licenses::installed;
+use crate::core::utils::package_lock::reachable;
+use crate::core::VERSION;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+/// Formats a bill of materials can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    CycloneDx,
+    Spdx,
+}
+
+/// A package of the bill of materials.
+struct Component {
+    name: String,
+    version: String,
+    purl: String,
+    /// Where the tarball came from, `None` for the project itself.
+    tarball: Option<String>,
+    /// Algorithm and hex digest of the locked integrity.
+    hash: Option<(String, String)>,
+    /// The declared SPDX expression, `None` when it isn't known.
+    license: Option<String>,
+    /// Only the dev dependencies of the project need it.
+    dev: bool,
+    optional: bool,
+    /// Package URLs of the components this one depends on.
+    dependencies: BTreeSet<String>,
+}
+
+/// The bill of materials of `lock_file` for the project in `app.current_dir`, along with the
+/// number of components whose license isn't known.
+pub fn generate(app: &App, lock_file: &LockFile, format: SbomFormat) -> (Value, usize) {
+    let (root, components) = components(app, lock_file);
+
+    let unlicensed = components
+        .iter()
+        .filter(|component| component.license.is_none())
+        .count();
+
+    let document = match format {
+        SbomFormat::CycloneDx => cyclonedx(&root, &components),
+        SbomFormat::Spdx => spdx(&root, &components),
+    };
+
+    (document, unlicensed)
+}
+
+/// The project and every locked version, sorted by name and version.
+fn components(app: &App, lock_file: &LockFile) -> (Component, Vec<Component>) {
+    let manifest = read_to_string(app.current_dir.join("package.json"))
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+        .unwrap_or(Value::Null);
+
+    let licenses = installed(&app.node_modules_dir)
+        .into_iter()
+        .map(|package| ((package.name, package.version), package.license))
+        .collect::<HashMap<_, _>>();
+
+    let mut locks = lock_file.dependencies.values().collect::<Vec<_>>();
+    locks.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    // the version every install name resolves to, the installed one when there are several
+    let mut resolved: HashMap<&str, &DependencyLock> = HashMap::new();
+
+    for lock in locks.iter() {
+        let current = resolved.entry(lock.install_name()).or_insert(lock);
+
+        if installed_version(app, lock.install_name()).as_deref() == Some(&lock.version) {
+            *current = lock;
+        }
+    }
+
+    let production = reachable(
+        lock_file,
+        ["dependencies", "optionalDependencies", "peerDependencies"]
+            .iter()
+            .filter_map(|field| manifest[field].as_object())
+            .flat_map(|section| section.keys().map(String::as_str))
+            .collect(),
+    );
+
+    let name = manifest["name"]
+        .as_str()
+        .map(String::from)
+        .or_else(|| {
+            let directory = app.current_dir.file_name()?;
+            Some(directory.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| String::from("project"));
+
+    let version = manifest["version"].as_str().unwrap_or("0.0.0").to_string();
+
+    let root = Component {
+        purl: purl(&name, &version),
+        tarball: None,
+        hash: None,
+        license: manifest["license"].as_str().map(String::from),
+        dev: false,
+        optional: false,
+        dependencies: purls(
+            &resolved,
+            [
+                "dependencies",
+                "devDependencies",
+                "optionalDependencies",
+                "peerDependencies",
+            ]
+            .iter()
+            .filter_map(|field| manifest[field].as_object())
+            .flat_map(|section| section.keys().map(String::as_str)),
+        ),
+        name,
+        version,
+    };
+
+    let mut seen = BTreeSet::new();
+
+    let components = locks
+        .iter()
+        // an alias and the package it points at are the same component
+        .filter(|lock| seen.insert(purl(&lock.name, &lock.version)))
+        .map(|lock| {
+            let hash = match lock.integrity.split_once('-') {
+                _ if lock.integrity.is_empty() => None,
+                Some((algorithm, hex)) => Some((algorithm.to_string(), hex.to_string())),
+                // lockfiles written before the algorithm was recorded only hold sha1 hashes
+                None => Some((String::from("sha1"), lock.integrity.clone())),
+            };
+
+            Component {
+                name: lock.name.clone(),
+                version: lock.version.clone(),
+                purl: purl(&lock.name, &lock.version),
+                tarball: Some(lock.tarball.clone()).filter(|tarball| !tarball.is_empty()),
+                hash,
+                license: licenses
+                    .get(&(lock.name.clone(), lock.version.clone()))
+                    .cloned()
+                    .flatten(),
+                dev: !production.contains(lock.install_name()),
+                optional: lock.optional,
+                dependencies: purls(&resolved, lock.dependencies.iter().map(String::as_str)),
+            }
+        })
+        .collect();
+
+    (root, components)
+}
+
+/// Package URLs of the versions `names` resolve to.
+fn purls<'a>(
+    resolved: &HashMap<&str, &DependencyLock>,
+    names: impl Iterator<Item = &'a str>,
+) -> BTreeSet<String> {
+    names
+        .filter_map(|name| resolved.get(name))
+        .map(|lock| purl(&lock.name, &lock.version))
+        .collect()
+}
+
+/// The package URL of an npm package, the `@` of a scope is encoded.
+pub fn purl(name: &str, version: &str) -> String {
+    let name = match name.strip_prefix('@') {
+        Some(scoped) => format!("%40{}", scoped),
+        None => name.to_string(),
+    };
+
+    format!("pkg:npm/{}@{}", name, version.replace('+', "%2B"))
+}
+
+/// A random version 4 UUID, bills of materials are told apart by one.
+fn uuid() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Whether `license` is an SPDX expression rather than `UNLICENSED` or a pointer at a file.
+fn is_expression(license: &str) -> bool {
+    license != "UNLICENSED"
+        && !license.starts_with("SEE LICEN")
+        && license.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+' | ':' | ' ' | '(' | ')')
+        })
+}
+
+fn cyclonedx(root: &Component, components: &[Component]) -> Value {
+    let component = |component: &Component, kind: &str| {
+        let (group, name) = match component.name.split_once('/') {
+            Some((scope, name)) if component.name.starts_with('@') => (Some(scope), name),
+            _ => (None, component.name.as_str()),
+        };
+
+        let mut entry = json!({
+            "type": kind,
+            "bom-ref": component.purl,
+            "name": name,
+            "version": component.version,
+            "purl": component.purl,
+        });
+
+        if let Some(group) = group {
+            entry["group"] = json!(group);
+        }
+
+        if component.tarball.is_some() {
+            entry["scope"] = match (component.dev, component.optional) {
+                (true, _) => json!("excluded"),
+                (false, true) => json!("optional"),
+                (false, false) => json!("required"),
+            };
+        }
+
+        if let Some((algorithm, hex)) = &component.hash {
+            let algorithm = match algorithm.as_str() {
+                "sha1" => "SHA-1",
+                "sha256" => "SHA-256",
+                "sha384" => "SHA-384",
+                _ => "SHA-512",
+            };
+
+            entry["hashes"] = json!([{ "alg": algorithm, "content": hex }]);
+        }
+
+        match &component.license {
+            Some(license) if is_expression(license) => {
+                entry["licenses"] = json!([{ "expression": license }]);
+            }
+            Some(license) => {
+                entry["licenses"] = json!([{ "license": { "name": license } }]);
+            }
+            None => {}
+        }
+
+        if let Some(tarball) = &component.tarball {
+            entry["externalReferences"] = json!([{ "type": "distribution", "url": tarball }]);
+        }
+
+        entry
+    };
+
+    let dependencies = std::iter::once(root)
+        .chain(components.iter())
+        .map(|component| json!({ "ref": component.purl, "dependsOn": component.dependencies }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.4",
+        "serialNumber": format!("urn:uuid:{}", uuid()),
+        "version": 1,
+        "metadata": {
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "tools": [{ "vendor": "Volt", "name": "volt", "version": VERSION }],
+            "component": component(root, "application"),
+        },
+        "components": components
+            .iter()
+            .map(|entry| component(entry, "library"))
+            .collect::<Vec<_>>(),
+        "dependencies": dependencies,
+    })
+}
+
+fn spdx(root: &Component, components: &[Component]) -> Value {
+    // identifiers may only hold letters, numbers, `.` and `-`
+    let id = |component: &Component| {
+        let sanitized = format!("{}-{}", component.name, component.version)
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    true => c,
+                    false => '-',
+                },
+            )
+            .collect::<String>();
+
+        format!("SPDXRef-Package-{}", sanitized)
+    };
+
+    let ids = std::iter::once(root)
+        .chain(components.iter())
+        .map(|component| (component.purl.as_str(), id(component)))
+        .collect::<HashMap<_, _>>();
+
+    let package = |component: &Component| {
+        let mut entry = json!({
+            "name": component.name,
+            "SPDXID": ids[component.purl.as_str()],
+            "versionInfo": component.version,
+            "downloadLocation": component.tarball.as_deref().unwrap_or("NOASSERTION"),
+            "filesAnalyzed": false,
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": component
+                .license
+                .as_deref()
+                .filter(|license| is_expression(license))
+                .unwrap_or("NOASSERTION"),
+            "copyrightText": "NOASSERTION",
+            "externalRefs": [{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": component.purl,
+            }],
+        });
+
+        if let Some((algorithm, hex)) = &component.hash {
+            entry["checksums"] = json!([{
+                "algorithm": algorithm.to_uppercase(),
+                "checksumValue": hex,
+            }]);
+        }
+
+        entry
+    };
+
+    let dev = components
+        .iter()
+        .filter(|component| component.dev)
+        .map(|component| component.purl.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": ids[root.purl.as_str()],
+    })];
+
+    for component in std::iter::once(root).chain(components.iter()) {
+        for dependency in component.dependencies.iter() {
+            let from = &ids[component.purl.as_str()];
+            let to = &ids[dependency.as_str()];
+
+            // only the project has dev dependencies, deeper ones are dev through it
+            relationships.push(
+                match component.tarball.is_none() && dev.contains(dependency.as_str()) {
+                    true => json!({
+                        "spdxElementId": to,
+                        "relationshipType": "DEV_DEPENDENCY_OF",
+                        "relatedSpdxElement": from,
+                    }),
+                    false => json!({
+                        "spdxElementId": from,
+                        "relationshipType": "DEPENDS_ON",
+                        "relatedSpdxElement": to,
+                    }),
+                },
+            );
+        }
+    }
+
+    // unique for every document, as the specification asks
+    let namespace = format!(
+        "https://voltpkg.com/spdx/{}-{}",
+        root.name.replace('@', ""),
+        uuid()
+    );
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}@{}", root.name, root.version),
+        "documentNamespace": namespace,
+        "creationInfo": {
+            "created": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "creators": [format!("Tool: volt-{}", VERSION)],
+        },
+        "packages": std::iter::once(root)
+            .chain(components.iter())
+            .map(package)
+            .collect::<Vec<_>>(),
+        "relationships": relationships,
+    })
+}
@@ -0,0 +1,165 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Static checks for lifecycle scripts, flagging what a malicious install script tends to do.
+//!
+//! The checks only look at the command text. They can't see what a script file does once it
+//! runs, so a clean report is no proof a package is safe, only that nothing obvious stands out.
+
+use std::fmt;
+
+use colored::{ColoredString, Colorize};
+use regex::Regex;
+
+/// How bad a finding is, reports list the worst first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Parse the value given to `--min-severity`.
+    pub fn parse(value: &str) -> Option<Severity> {
+        match value {
+            "low" => Some(Severity::Low),
+            "medium" => Some(Severity::Medium),
+            "high" => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn colored(&self) -> ColoredString {
+        let label = format!("{:>8}", self.to_string());
+
+        match self {
+            Severity::Low => label.bright_black(),
+            Severity::Medium => label.bright_yellow(),
+            Severity::High => label.bright_red(),
+            Severity::Critical => label.black().on_bright_red(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+/// A pattern worth a closer look.
+struct Rule {
+    severity: Severity,
+    message: &'static str,
+    pattern: &'static str,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        severity: Severity::Critical,
+        message: "pipes a download into a shell",
+        pattern: r"\b(curl|wget)\b[^;&]*\|\s*(sudo\s+)?(sh|bash|zsh|dash|node|python[0-9.]*|perl|ruby)\b",
+    },
+    Rule {
+        severity: Severity::Critical,
+        message: "decodes base64 and runs the result",
+        pattern: r"base64\s+(-d|-D|--decode)\b[^;&]*\|\s*(sh|bash|zsh|node|python[0-9.]*|perl)\b",
+    },
+    Rule {
+        severity: Severity::Critical,
+        message: "decodes base64 and runs the result",
+        pattern: r"\beval\b.*(\batob\b|base64)|(\batob\b|base64).*\beval\b",
+    },
+    Rule {
+        severity: Severity::Critical,
+        message: "deletes files outside the package",
+        pattern: r"\brm\s+(-[a-zA-Z]*\s+)*(/|~|\$HOME|\$\{HOME\}|\.\./)",
+    },
+    Rule {
+        severity: Severity::High,
+        message: "writes outside the package directory",
+        pattern: r">>?\s*(/|~|\$HOME|\$\{HOME\}|\.\./|%[A-Za-z]+%)",
+    },
+    Rule {
+        severity: Severity::High,
+        message: "writes outside the package directory",
+        pattern: r"\b(cp|mv|tee|ln|install|chmod|chown)\b[^;&|]*\s(/|~|\$HOME|\$\{HOME\}|\.\./)",
+    },
+    Rule {
+        severity: Severity::High,
+        message: "reads credentials",
+        pattern: r"(NPM_TOKEN|_authToken|\.npmrc|\.ssh/|\.aws/|AWS_SECRET|GITHUB_TOKEN|\.git-credentials)",
+    },
+    Rule {
+        severity: Severity::Medium,
+        message: "downloads files",
+        pattern: r"\b(curl|wget|Invoke-WebRequest|iwr)\b",
+    },
+    Rule {
+        severity: Severity::Medium,
+        message: "evaluates generated code",
+        pattern: r"\beval\b",
+    },
+    Rule {
+        severity: Severity::Low,
+        message: "runs inline code",
+        pattern: r"\b(node|python[0-9.]*|perl|ruby)\s+(-e|-c|--eval)\b",
+    },
+];
+
+/// What one rule found in one script.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: &'static str,
+}
+
+/// Every rule `command` breaks, one finding per message, worst first.
+pub fn lint_script(command: &str) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = vec![];
+
+    for rule in RULES.iter() {
+        let regex = Regex::new(rule.pattern).unwrap();
+
+        // discarding output is the one write outside the package every script may do
+        let matched = regex
+            .find_iter(command)
+            .any(|found| !command[found.end() - 1..].starts_with("/dev/null"));
+
+        if matched
+            && !findings
+                .iter()
+                .any(|finding| finding.message == rule.message)
+        {
+            findings.push(Finding {
+                severity: rule.severity,
+                message: rule.message,
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+
+    findings
+}
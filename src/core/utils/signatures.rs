@@ -0,0 +1,126 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Verify the signatures the registry attaches to every version it serves.
+//!
+//! The registry signs `<name>@<version>:<integrity>` with ECDSA P-256 over SHA-256, the
+//! signatures ship in `dist.signatures` and the public keys at `/-/npm/v1/keys`.
+
+use crate::core::model::http_manager::RegistryKey;
+use crate::core::utils::package::DistSignature;
+
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Verifier;
+
+/// Outcome of checking the signatures of a single version.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureStatus {
+    /// Signed by a known key, and the signature matches.
+    Verified,
+    /// The registry serves the version without a signature.
+    Missing,
+    /// The signature doesn't match, or was made with an unknown or expired key.
+    Invalid(String),
+}
+
+/// Check `signatures` of `name@version` with the given `integrity` against the registry's `keys`.
+///
+/// `published` is when the version was published, a key that expired before that can't have
+/// signed it. It's only needed when the signing key has an expiry.
+pub fn verify_signatures(
+    keys: &[RegistryKey],
+    name: &str,
+    version: &str,
+    integrity: &str,
+    signatures: &[DistSignature],
+    published: Option<&DateTime<Utc>>,
+) -> SignatureStatus {
+    if signatures.is_empty() {
+        return SignatureStatus::Missing;
+    }
+
+    let message = format!("{}@{}:{}", name, version, integrity);
+
+    for signature in signatures {
+        let key = match keys.iter().find(|key| key.keyid == signature.keyid) {
+            Some(key) => key,
+            None => {
+                return SignatureStatus::Invalid(format!(
+                    "signed with unknown key {}",
+                    signature.keyid
+                ))
+            }
+        };
+
+        if let Some(expires) = key.expires.as_deref() {
+            let expires = DateTime::parse_from_rfc3339(expires).map(|e| e.with_timezone(&Utc));
+
+            match (expires, published) {
+                (Ok(expires), Some(published)) if *published > expires => {
+                    return SignatureStatus::Invalid(format!(
+                        "published after key {} expired",
+                        key.keyid
+                    ))
+                }
+                (Ok(_), Some(_)) => {}
+                _ => {
+                    return SignatureStatus::Invalid(format!(
+                        "key {} expires, and the publish time is unknown",
+                        key.keyid
+                    ))
+                }
+            }
+        }
+
+        if let Err(reason) = verify_ecdsa(key, &message, &signature.sig) {
+            return SignatureStatus::Invalid(reason);
+        }
+    }
+
+    SignatureStatus::Verified
+}
+
+/// Verify a base64 DER encoded ECDSA signature of `message`.
+fn verify_ecdsa(key: &RegistryKey, message: &str, signature: &str) -> Result<(), String> {
+    if key.keytype != "ecdsa-sha2-nistp256" {
+        return Err(format!(
+            "key {} has unsupported type {}",
+            key.keyid, key.keytype
+        ));
+    }
+
+    let der =
+        base64::decode(&key.key).map_err(|_| format!("key {} isn't valid base64", key.keyid))?;
+
+    let public_key = PKey::public_key_from_der(&der)
+        .map_err(|_| format!("key {} can't be parsed", key.keyid))?;
+
+    let signature =
+        base64::decode(signature).map_err(|_| String::from("signature isn't valid base64"))?;
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key)
+        .map_err(|e| format!("unable to verify with key {}: {}", key.keyid, e))?;
+
+    let valid = verifier
+        .update(message.as_bytes())
+        .and_then(|_| verifier.verify(&signature))
+        .unwrap_or(false);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("signature doesn't match key {}", key.keyid))
+    }
+}
@@ -0,0 +1,392 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Ed25519 signatures of the lockfile and the install summary, laid out like minisign's.
+//!
+//! A project that lists `trusted-keys` under `[signing]` in volt.toml only installs from a
+//! volt.lock signed by one of them, so a lockfile edited outside of a signed install, say by a
+//! compromised CI job, is caught before anything is downloaded. Installs that have the secret
+//! key, from `signing.key` or `VOLT_SIGNING_KEY`, sign the lockfile they write and the summary
+//! in `.volt/install-summary.json`.
+//!
+//! Unlike minisign's, the secret key isn't encrypted with a password: its file holds the key in
+//! plaintext, readable only by its owner, and `VOLT_SIGNING_KEY` holds it as is.
+//!
+//! Signature files look like this, the trusted comment's fields separated by a tab:
+//!
+//! ```text
+//! volt.lock.sig
+//!   untrusted comment: signature from volt secret key <key id>
+//!   base64("Ed" <key id> <signature of the file>)
+//!   trusted comment: timestamp:<unix time><tab>file:volt.lock
+//!   base64(<signature of the file signature and the trusted comment>)
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use dirs::home_dir;
+use miette::Result;
+use openssl::pkey::{PKey, Private, Public};
+use openssl::sign::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::core::model::lock_file::LockFile;
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::VERSION;
+
+/// Environment variable holding the secret key itself, for CI where it's a secret.
+pub const SIGNING_KEY_VAR: &str = "VOLT_SIGNING_KEY";
+
+/// Marks Ed25519 keys and signatures, like minisign does.
+const ALGORITHM: &[u8; 2] = b"Ed";
+
+/// DER of an Ed25519 private key (PKCS#8) and of a public key, up to the raw 32 bytes.
+const PKCS8_PREFIX: &[u8] = &[
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+const SPKI_PREFIX: &[u8] = &[
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// Summary of an install, signed next to the lockfile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct InstallSummary {
+    pub volt_version: String,
+    pub installed_at: String,
+    /// sha256 of the lockfile the install went by.
+    pub lockfile: String,
+    /// `name@version integrity` of every installed package, sorted.
+    pub packages: Vec<String>,
+}
+
+impl InstallSummary {
+    pub fn path(app: &App) -> PathBuf {
+        app.current_dir.join(".volt").join("install-summary.json")
+    }
+}
+
+pub struct SecretKey {
+    id: [u8; 8],
+    key: PKey<Private>,
+}
+
+impl SecretKey {
+    pub fn generate() -> Result<SecretKey> {
+        let key = PKey::generate_ed25519().map_err(signing_error)?;
+        let public = raw_public_key(&key)?;
+
+        Ok(SecretKey {
+            id: key_id(&public),
+            key,
+        })
+    }
+
+    /// The key from `VOLT_SIGNING_KEY`, else from the file `signing.key` points to.
+    pub fn load(app: &App) -> Result<Option<SecretKey>> {
+        if let Ok(data) = std::env::var(SIGNING_KEY_VAR) {
+            return SecretKey::parse(&data, SIGNING_KEY_VAR).map(Some);
+        }
+
+        let path = match app.config.signing.key.as_ref() {
+            Some(path) => expand_home(&app.current_dir, path),
+            None => return Ok(None),
+        };
+
+        let data = std::fs::read_to_string(&path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        SecretKey::parse(&data, &path.display().to_string()).map(Some)
+    }
+
+    fn parse(data: &str, origin: &str) -> Result<SecretKey> {
+        let invalid = |reason: &str| VoltError::InvalidSigningKey {
+            origin: origin.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let bytes = data
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+            .and_then(|line| base64::decode(line).ok())
+            .ok_or_else(|| invalid("it isn't a volt secret key"))?;
+
+        if bytes.len() != 42 || &bytes[..2] != ALGORITHM {
+            return Err(invalid("it isn't an Ed25519 volt secret key").into());
+        }
+
+        let key = PKey::private_key_from_der(&[PKCS8_PREFIX, &bytes[10..]].concat())
+            .map_err(|_| invalid("the key is damaged"))?;
+
+        let mut id = [0; 8];
+        id.copy_from_slice(&bytes[2..10]);
+
+        Ok(SecretKey { id, key })
+    }
+
+    /// The key as written to its file.
+    pub fn encode(&self) -> Result<String> {
+        let der = self.key.private_key_to_der().map_err(signing_error)?;
+        let raw = &der[der.len() - 32..];
+
+        Ok(format!(
+            "untrusted comment: volt secret key {}\n{}\n",
+            hex(&self.id),
+            base64::encode([&ALGORITHM[..], &self.id[..], raw].concat())
+        ))
+    }
+
+    /// The public key, as listed in `trusted-keys`.
+    pub fn public_key(&self) -> Result<String> {
+        let raw = raw_public_key(&self.key)?;
+
+        Ok(base64::encode(
+            [&ALGORITHM[..], &self.id[..], &raw[..]].concat(),
+        ))
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut signer = Signer::new_without_digest(&self.key).map_err(signing_error)?;
+
+        Ok(signer.sign_oneshot_to_vec(data).map_err(signing_error)?)
+    }
+
+    /// Write the signature of `path` to `<path>.sig`.
+    pub fn sign_file(&self, path: &Path) -> Result<()> {
+        let data = std::fs::read(path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        let file_name = path.file_name().unwrap().to_string_lossy();
+
+        let signature = [&ALGORITHM[..], &self.id[..], &self.sign(&data)?[..]].concat();
+        let comment = format!("timestamp:{}\tfile:{}", Utc::now().timestamp(), file_name);
+        let global = self.sign(&[&signature[..], comment.as_bytes()].concat())?;
+
+        let signature_path = signature_path(path);
+
+        std::fs::write(
+            &signature_path,
+            format!(
+                "untrusted comment: signature from volt secret key {}\n{}\ntrusted comment: {}\n{}\n",
+                hex(&self.id),
+                base64::encode(&signature),
+                comment,
+                base64::encode(&global)
+            ),
+        )
+        .map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: signature_path.display().to_string(),
+        })?;
+
+        Ok(())
+    }
+}
+
+struct PublicKey {
+    id: [u8; 8],
+    key: PKey<Public>,
+}
+
+impl PublicKey {
+    fn parse(value: &str) -> Option<PublicKey> {
+        let bytes = base64::decode(value.trim()).ok()?;
+
+        if bytes.len() != 42 || &bytes[..2] != ALGORITHM {
+            return None;
+        }
+
+        let key = PKey::public_key_from_der(&[SPKI_PREFIX, &bytes[10..]].concat()).ok()?;
+
+        let mut id = [0; 8];
+        id.copy_from_slice(&bytes[2..10]);
+
+        Some(PublicKey { id, key })
+    }
+
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        Verifier::new_without_digest(&self.key)
+            .and_then(|mut verifier| verifier.verify_oneshot(signature, data))
+            .unwrap_or(false)
+    }
+}
+
+/// Check `<path>.sig` against `trusted` keys, returning its trusted comment.
+pub fn verify_file(path: &Path, trusted: &[String]) -> std::result::Result<String, String> {
+    let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+    let data = std::fs::read(path).map_err(|_| format!("{} can't be read", file_name))?;
+
+    let signature_file = std::fs::read_to_string(signature_path(path))
+        .map_err(|_| format!("{} isn't signed, {}.sig is missing", file_name, file_name))?;
+
+    let lines = signature_file.lines().map(str::trim).collect::<Vec<_>>();
+
+    let (signature, comment, global) = match lines.as_slice() {
+        [_, signature, comment, global, ..] => (
+            base64::decode(signature).ok(),
+            comment.strip_prefix("trusted comment: "),
+            base64::decode(global).ok(),
+        ),
+        _ => (None, None, None),
+    };
+
+    let (signature, comment, global) = match (signature, comment, global) {
+        (Some(signature), Some(comment), Some(global)) if signature.len() == 74 => {
+            (signature, comment, global)
+        }
+        _ => return Err(format!("{}.sig is damaged", file_name)),
+    };
+
+    if &signature[..2] != ALGORITHM {
+        return Err(format!("{}.sig isn't an Ed25519 signature", file_name));
+    }
+
+    let key = trusted
+        .iter()
+        .filter_map(|key| PublicKey::parse(key))
+        .find(|key| key.id[..] == signature[2..10])
+        .ok_or_else(|| {
+            format!(
+                "{} was signed with key {}, which isn't in `trusted-keys`",
+                file_name,
+                hex(&signature[2..10])
+            )
+        })?;
+
+    if !key.verify(&data, &signature[10..]) {
+        return Err(format!(
+            "{} doesn't match its signature, it changed after it was signed",
+            file_name
+        ));
+    }
+
+    if !key.verify(&[&signature[..], comment.as_bytes()].concat(), &global) {
+        return Err(format!(
+            "the trusted comment of {}.sig was changed",
+            file_name
+        ));
+    }
+
+    // a signature moved over from another file still has that file's name
+    if !comment
+        .split('\t')
+        .any(|field| field == format!("file:{}", file_name))
+    {
+        return Err(format!("{}.sig was made for another file", file_name));
+    }
+
+    Ok(comment.to_string())
+}
+
+/// Refuse a lockfile that isn't signed by a trusted key, when the project trusts any.
+pub fn check_lockfile(app: &App) -> Result<()> {
+    let trusted = &app.config.signing.trusted_keys;
+
+    if trusted.is_empty() || !app.lock_file_path.exists() {
+        return Ok(());
+    }
+
+    verify_file(&app.lock_file_path, trusted)
+        .map_err(|reason| VoltError::LockfileSignature { reason })?;
+
+    Ok(())
+}
+
+/// Sign what an install wrote when there is a secret key: the lockfile when `lockfile` is set,
+/// and a summary of `lock_file` in `.volt/install-summary.json`.
+pub fn sign_install(app: &App, lock_file: &LockFile, lockfile: bool) -> Result<()> {
+    let key = match SecretKey::load(app)? {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+
+    if lockfile {
+        key.sign_file(&lock_file.path)?;
+    }
+
+    let mut packages = lock_file
+        .dependencies
+        .values()
+        .map(|lock| format!("{}@{} {}", lock.name, lock.version, lock.integrity))
+        .collect::<Vec<_>>();
+
+    packages.sort();
+
+    let summary = InstallSummary {
+        volt_version: VERSION.to_string(),
+        installed_at: Utc::now().to_rfc3339(),
+        lockfile: std::fs::read(&lock_file.path)
+            .map(|data| format!("{:x}", Sha256::digest(&data)))
+            .unwrap_or_default(),
+        packages,
+    };
+
+    let path = InstallSummary::path(app);
+
+    std::fs::create_dir_all(path.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+    std::fs::write(&path, serde_json::to_string_pretty(&summary).unwrap()).map_err(|e| {
+        VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        }
+    })?;
+
+    key.sign_file(&path)
+}
+
+pub fn signature_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.sig", path.display()))
+}
+
+/// `path` with a leading `~/` in the home directory, otherwise relative to `directory`.
+pub fn expand_home(directory: &Path, path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => directory.join(path),
+    }
+}
+
+fn key_id(public: &[u8]) -> [u8; 8] {
+    let mut id = [0; 8];
+    id.copy_from_slice(&Sha256::digest(public)[..8]);
+    id
+}
+
+fn raw_public_key(key: &PKey<Private>) -> Result<Vec<u8>> {
+    let der = key.public_key_to_der().map_err(signing_error)?;
+
+    Ok(der[der.len() - 32..].to_vec())
+}
+
+fn signing_error(e: openssl::error::ErrorStack) -> VoltError {
+    VoltError::SigningFailed {
+        reason: e.to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
@@ -0,0 +1,314 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Where the time of a command goes, and hints when part of it is much slower than usual.
+//!
+//! Every phase adds up the time its operations take while the command runs. Once it finishes
+//! each phase is compared against its average over earlier runs, kept in
+//! `<volt dir>/timings.json`, and a phase that took `SLOW_FACTOR` times as long per operation
+//! gets a hint about the likely cause.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::core::utils::constants::{SLOW_FACTOR, SLOW_PHASE_MIN};
+use crate::core::utils::output;
+
+/// Runs a command needs before its own history is trusted to say what is slow.
+const MIN_RUNS: u32 = 3;
+
+/// Weight of the latest run in the averages, older runs fade out.
+const WEIGHT: f64 = 0.2;
+
+/// Time spent and operations run per phase by the current command.
+static SPENT: Mutex<BTreeMap<Phase, (Duration, u32)>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    /// Fetching package documents and trees.
+    Resolve,
+    /// Downloading tarballs.
+    Download,
+    /// Unpacking tarballs into the store.
+    Extract,
+    /// Linking packages from the store into node_modules.
+    Link,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Resolve => "resolving",
+            Phase::Download => "downloading",
+            Phase::Extract => "extracting",
+            Phase::Link => "linking",
+        }
+    }
+}
+
+/// Add the time since `start` to `phase`, for `operations` packages.
+pub fn record(phase: Phase, start: Instant, operations: u32) {
+    let mut spent = SPENT.lock().unwrap();
+    let entry = spent.entry(phase).or_default();
+
+    entry.0 += start.elapsed();
+    entry.1 += operations;
+}
+
+/// Time spent and operations run per phase so far by the current command.
+pub fn spent() -> BTreeMap<Phase, (Duration, u32)> {
+    SPENT.lock().unwrap().clone()
+}
+
+/// Averages of earlier runs of one command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct CommandHistory {
+    runs: u32,
+    /// Seconds the whole command took.
+    total: f64,
+    /// Seconds per operation of every phase.
+    phases: BTreeMap<Phase, f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct History {
+    commands: BTreeMap<String, CommandHistory>,
+}
+
+impl History {
+    fn path() -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".volt").join("timings.json"))
+    }
+
+    fn load() -> History {
+        History::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Record the history, failing silently since it only costs the next run its hints.
+    fn save(&self) {
+        let path = match History::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let partial = path.with_extension("partial");
+
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            if std::fs::write(&partial, data).is_ok() {
+                std::fs::rename(&partial, &path).ok();
+            }
+        }
+    }
+}
+
+/// Compare the phases of `command` against its history, print hints for the ones that were
+/// unusually slow and add this run to the history.
+pub fn report(command: &str, total: Duration) {
+    let spent = std::mem::take(&mut *SPENT.lock().unwrap());
+
+    let mut history = History::load();
+    let previous = history.commands.entry(command.to_string()).or_default();
+
+    // hints would only be noise around the json document
+    if previous.runs >= MIN_RUNS && !output::is_json() {
+        let slow = spent
+            .iter()
+            .filter(|(_, (time, operations))| time.as_secs() >= SLOW_PHASE_MIN && *operations > 0)
+            .filter_map(|(phase, (time, operations))| {
+                let usual = *previous.phases.get(phase)?;
+                let factor = time.as_secs_f64() / *operations as f64 / usual;
+
+                (usual > 0.0 && factor >= SLOW_FACTOR).then_some((*phase, factor))
+            })
+            .collect::<Vec<_>>();
+
+        if !slow.is_empty() {
+            println!(
+                "\n{} volt {} took {:.1}s, it usually takes {:.1}s.",
+                " slow ".black().bright_yellow(),
+                command,
+                total.as_secs_f64(),
+                previous.total
+            );
+
+            for (phase, factor) in slow.iter() {
+                println!(
+                    "  {} {} was {:.0}x slower than usual",
+                    "!".bright_yellow().bold(),
+                    phase.name(),
+                    factor
+                );
+
+                for hint in hints(*phase) {
+                    println!("    {}", hint.bright_black());
+                }
+            }
+
+            println!();
+        }
+    }
+
+    let average = |previous: f64, current: f64| previous * (1.0 - WEIGHT) + current * WEIGHT;
+
+    previous.total = match previous.runs {
+        0 => total.as_secs_f64(),
+        _ => average(previous.total, total.as_secs_f64()),
+    };
+
+    for (phase, (time, operations)) in spent.iter().filter(|(_, (_, n))| *n > 0) {
+        let current = time.as_secs_f64() / *operations as f64;
+
+        previous
+            .phases
+            .entry(*phase)
+            .and_modify(|usual| *usual = average(*usual, current))
+            .or_insert(current);
+    }
+
+    previous.runs += 1;
+
+    history.save();
+}
+
+/// Likely causes of `phase` being slow on this machine.
+fn hints(phase: Phase) -> Vec<String> {
+    let mut hints = vec![];
+
+    let directories = home_dir()
+        .map(|home| home.join(".volt"))
+        .into_iter()
+        .chain(std::env::current_dir().ok())
+        .collect::<Vec<_>>();
+
+    match phase {
+        Phase::Resolve | Phase::Download => {
+            hints.push(String::from(
+                "the registry or the network was slow, check the proxy and `timeouts` in \
+                 volt.toml, or try again with `--prefer-offline`.",
+            ));
+        }
+        Phase::Extract | Phase::Link => {
+            if cfg!(windows) {
+                hints.push(String::from(
+                    "antivirus software scans every file volt writes, excluding the .volt \
+                     directory and the project's node_modules from real-time scanning usually \
+                     helps.",
+                ));
+            }
+
+            for directory in directories.iter() {
+                if let Some(filesystem) = network_filesystem(directory) {
+                    hints.push(format!(
+                        "{} is on a network filesystem ({}), every file written is a round \
+                         trip to the server. Keep the store on a local disk with `store-dir`.",
+                        directory.display(),
+                        filesystem
+                    ));
+                }
+            }
+
+            for directory in directories.iter() {
+                if let Some(free) = low_disk_space(directory) {
+                    hints.push(format!(
+                        "only {} MB are free on the disk of {}, a nearly full disk slows \
+                         every write down.",
+                        free / 1024 / 1024,
+                        directory.display()
+                    ));
+                }
+            }
+        }
+    }
+
+    hints.dedup();
+    hints
+}
+
+/// Type of the filesystem `path` is on, when it's one mounted over the network.
+#[cfg(target_os = "linux")]
+fn network_filesystem(path: &Path) -> Option<String> {
+    const NETWORK: &[&str] = &[
+        "nfs",
+        "nfs4",
+        "cifs",
+        "smbfs",
+        "smb3",
+        "fuse.sshfs",
+        "9p",
+        "afs",
+    ];
+
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    let path = path.canonicalize().ok()?;
+
+    // the mount point closest to `path` is the one it's on
+    let (_, filesystem) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let filesystem = fields.next()?;
+
+            Some((PathBuf::from(mount_point), filesystem))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())?;
+
+    NETWORK
+        .contains(&filesystem)
+        .then(|| filesystem.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn network_filesystem(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Bytes free on the disk of `path`, when that's under a gigabyte or 5% of the disk.
+#[cfg(unix)]
+fn low_disk_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    let size = stat.f_blocks as u64 * stat.f_frsize as u64;
+
+    (free < 1024 * 1024 * 1024 || free * 20 < size).then_some(free)
+}
+
+#[cfg(not(unix))]
+fn low_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,105 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Per-file hashes of installed packages, used to detect modified files in node_modules.
+
+use crate::core::utils::errors::VoltError;
+
+use miette::Result;
+use ssri::{Algorithm, IntegrityOpts};
+use std::collections::BTreeMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Differences between the files of an installed package and its recorded hashes.
+#[derive(Debug, Default)]
+pub struct FileReport {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl FileReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.added.is_empty()
+    }
+}
+
+/// Hash every file of the package installed in `directory`.
+///
+/// Keys are paths relative to the package with `/` separators, values are sha512
+/// subresource integrity strings. Nested `node_modules` belong to other packages and are skipped.
+pub fn file_hashes(directory: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+
+    let entries = WalkDir::new(directory)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "node_modules");
+
+    for entry in entries {
+        let entry = entry.map_err(|e| VoltError::ReadFileError {
+            name: directory.display().to_string(),
+            source: e.into(),
+        })?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let contents = std::fs::read(entry.path()).map_err(|e| VoltError::ReadFileError {
+            name: entry.path().display().to_string(),
+            source: e,
+        })?;
+
+        let relative = entry
+            .path()
+            .strip_prefix(directory)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let integrity = IntegrityOpts::new()
+            .algorithm(Algorithm::Sha512)
+            .chain(&contents)
+            .result()
+            .to_string();
+
+        hashes.insert(relative, integrity);
+    }
+
+    Ok(hashes)
+}
+
+/// Compare the package installed in `directory` against the hashes recorded for it.
+pub fn verify_files(directory: &Path, expected: &BTreeMap<String, String>) -> Result<FileReport> {
+    let actual = file_hashes(directory)?;
+
+    let mut report = FileReport::default();
+
+    for (path, integrity) in expected.iter() {
+        match actual.get(path) {
+            Some(found) if found == integrity => {}
+            Some(_) => report.modified.push(path.clone()),
+            None => report.missing.push(path.clone()),
+        }
+    }
+
+    report.added = actual
+        .keys()
+        .filter(|path| !expected.contains_key(*path))
+        .cloned()
+        .collect();
+
+    Ok(report)
+}
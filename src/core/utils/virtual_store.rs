@@ -0,0 +1,374 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! node_modules directories materialized once for every project that sets
+//! `shared-virtual-store`, and symlinked into their node_modules.
+//!
+//! Projects with `node-linker = "isolated"` get the same layout, in `node_modules/.volt`
+//! unless the virtual store is shared, and only their direct dependencies are symlinked to
+//! the top of node_modules. A package can then only require what it declares.
+//!
+//! ```text
+//! <store dir>/virtual/<name>@<version>+<hash>/node_modules/<name>   the package, hard linked
+//!                                                                    from the store
+//! <store dir>/virtual/<name>@<version>+<hash>/node_modules/<dep>    symlinks to the entries of
+//!                                                                    its dependencies
+//! <store dir>/virtual/.refs/<entry>/<project hash>                  one per project using the
+//!                                                                    entry, holding its path
+//! ```
+//!
+//! Node resolves the dependencies of a symlinked package next to its real location, so every
+//! entry carries its own dependencies. The hash covers the versions of everything the package
+//! reaches, two projects share an entry only when it resolves to exactly the same packages.
+//! `volt store prune` removes the entries no project references anymore.
+//!
+//! An install holds [`VirtualStore::lock()`] from putting its entries together until it
+//! references them, and prune holds it exclusively, so prune never sees an entry an install
+//! uses but hasn't referenced yet.
+
+use crate::core::model::config::NodeLinker;
+use crate::core::utils::app::App;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::file_lock::FileLock;
+use crate::core::utils::hoist::{self, Graph};
+use crate::core::utils::linker::{
+    link_mode, linker, store_path, CopyLinker, HardlinkLinker, Linker, ProjectState, SymlinkLinker,
+};
+use crate::core::utils::voltapi::VoltPackage;
+
+use miette::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Directory of the virtual store of an isolated project that doesn't share it.
+const LOCAL_DIRECTORY: &str = ".volt";
+
+/// Entries of the virtual store for the packages of one install.
+pub struct VirtualStore {
+    directory: PathBuf,
+    /// Whether the entries live in the store, shared with other projects.
+    shared: bool,
+    /// `name@version` of the packages put at the top of node_modules.
+    top_level: HashSet<String>,
+    /// Entry of every package, keyed by `name@version`.
+    entries: HashMap<String, String>,
+    /// Dependencies of every package that are part of the install, keyed by `name@version`.
+    dependencies: HashMap<String, Vec<(String, String)>>,
+}
+
+impl VirtualStore {
+    pub fn directory(store_dir: &Path) -> PathBuf {
+        store_dir.join("virtual")
+    }
+
+    /// Work out the entries of `packages`, with the versions of their dependencies and the
+    /// direct dependencies from `graph`.
+    pub fn plan(app: &App, packages: &[&VoltPackage], graph: &Graph) -> VirtualStore {
+        let dependencies = packages
+            .iter()
+            .map(|package| {
+                let mut resolved = graph
+                    .dependencies(package)
+                    .into_iter()
+                    .map(|(name, dependency)| (name.to_string(), dependency.version.clone()))
+                    .collect::<Vec<_>>();
+
+                resolved.sort();
+
+                (key(&package.name, &package.version), resolved)
+            })
+            .collect::<HashMap<_, Vec<(String, String)>>>();
+
+        let entries = packages
+            .iter()
+            .map(|package| {
+                let id = key(&package.name, &package.version);
+
+                (id.clone(), entry_name(&id, &reachable(&id, &dependencies)))
+            })
+            .collect();
+
+        let shared = app.config.shared_virtual_store;
+
+        let directory = match shared {
+            true => VirtualStore::directory(&app.store_dir()),
+            false => app.node_modules_dir.join(LOCAL_DIRECTORY),
+        };
+
+        // without isolation, one version of every name is still at the top
+        let top_level = match app.config.node_linker {
+            NodeLinker::Isolated => graph.direct().map(hoist::key).collect(),
+            _ => graph.top_level(),
+        };
+
+        VirtualStore {
+            directory,
+            shared,
+            top_level,
+            entries,
+            dependencies,
+        }
+    }
+
+    /// Keep `volt store prune` out of a shared virtual store until dropped, other installs
+    /// can hold it at the same time. `None` when the entries are the project's own.
+    pub async fn lock(&self) -> Result<Option<FileLock>> {
+        if !self.shared {
+            return Ok(None);
+        }
+
+        let lock = FileLock::acquire_shared(&self.directory)
+            .await
+            .map_err(VoltError::StoreLocked)?;
+
+        Ok(Some(lock))
+    }
+
+    /// Whether `package` goes at the top of node_modules.
+    pub fn is_top_level(&self, package: &VoltPackage) -> bool {
+        self.top_level.contains(&hoist::key(package))
+    }
+
+    /// Directory of `package` inside its entry.
+    pub fn package_dir(&self, package: &VoltPackage) -> Option<PathBuf> {
+        let entry = self.entry_path(&package.name, &package.version)?;
+
+        Some(entry.join("node_modules").join(&package.name))
+    }
+
+    fn entry_path(&self, name: &str, version: &str) -> Option<PathBuf> {
+        let entry = self.entries.get(&key(name, version))?;
+
+        Some(self.directory.join(entry))
+    }
+
+    /// Directory of `package` inside its entry, creating the entry when no project has yet.
+    ///
+    /// Entries are put together next to their final place and renamed into it, so other
+    /// projects never see half an entry.
+    pub fn materialize(&self, app: &App, package: &VoltPackage) -> Result<PathBuf> {
+        let entry = self
+            .entry_path(&package.name, &package.version)
+            .expect("every installed package is planned");
+
+        let installed = self.package_dir(package).unwrap();
+
+        if installed.exists() {
+            return Ok(installed);
+        }
+
+        let write_error = |path: &Path| {
+            let name = path.display().to_string();
+            move |e: std::io::Error| VoltError::WriteFileError { source: e, name }
+        };
+
+        let partial = PathBuf::from(format!(
+            "{}.partial-{}",
+            entry.display(),
+            std::process::id()
+        ));
+
+        if partial.exists() {
+            std::fs::remove_dir_all(&partial).map_err(write_error(&partial))?;
+        }
+
+        let target = partial.join("node_modules").join(&package.name);
+
+        std::fs::create_dir_all(target.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+        let source = store_path(app, package);
+
+        let linked = match self.shared {
+            // install scripts build into the entry, which mustn't reach the store
+            _ if package.has_install_script => CopyLinker.link(&source, &target),
+            // the virtual store is inside the store, hard links only fail where they aren't
+            // supported
+            true => HardlinkLinker.link(&source, &target).or_else(|_| {
+                std::fs::remove_dir_all(&target).ok();
+                CopyLinker.link(&source, &target)
+            }),
+            false => linker(link_mode(app)).link(&source, &target),
+        };
+
+        linked.map_err(write_error(&target))?;
+
+        for (name, version) in self.dependencies[&key(&package.name, &package.version)].iter() {
+            let dependency = match self.entry_path(name, version) {
+                Some(dependency) => dependency.join("node_modules").join(name),
+                None => continue,
+            };
+
+            let link = partial.join("node_modules").join(name);
+
+            std::fs::create_dir_all(link.parent().unwrap()).map_err(VoltError::CreateDirError)?;
+
+            SymlinkLinker
+                .link(&dependency, &link)
+                .map_err(write_error(&link))?;
+        }
+
+        // another project may have put the same entry together in the meantime
+        if let Err(e) = std::fs::rename(&partial, &entry) {
+            std::fs::remove_dir_all(&partial).ok();
+
+            if !installed.exists() {
+                return Err(write_error(&entry)(e).into());
+            }
+        }
+
+        Ok(installed)
+    }
+
+    /// Record that the project of `app` uses the planned entries, and drop its references to
+    /// the ones it used before and doesn't anymore.
+    ///
+    /// Called with [`VirtualStore::lock()`] held since the entries were materialized.
+    pub fn reference(&self, app: &App) {
+        // nobody else uses the entries in node_modules
+        if !self.shared {
+            return;
+        }
+
+        let mut state = ProjectState::load(app).unwrap_or_default();
+
+        let used = self.entries.values().cloned().collect::<BTreeSet<_>>();
+        let project = project_hash(&app.current_dir);
+
+        for entry in state.virtual_entries.difference(&used) {
+            std::fs::remove_file(refs_dir(&self.directory, entry).join(&project)).ok();
+        }
+
+        for entry in used.iter() {
+            let refs = refs_dir(&self.directory, entry);
+
+            if std::fs::create_dir_all(&refs).is_ok() {
+                std::fs::write(refs.join(&project), app.current_dir.display().to_string()).ok();
+            }
+        }
+
+        state.virtual_entries = used;
+        state.save(app);
+    }
+}
+
+/// Remove the entries of the virtual store in `store_dir` no project uses anymore.
+///
+/// A reference counts while the project it names still lists the entry in `.volt/state`,
+/// the others are dropped first. Returns the names of the removed entries.
+///
+/// Waits for the installs using the store to reference their entries, and keeps new ones
+/// out until it's done.
+pub async fn prune(store_dir: &Path) -> Result<Vec<String>> {
+    let directory = VirtualStore::directory(store_dir);
+
+    let _lock = FileLock::acquire(&directory)
+        .await
+        .map_err(VoltError::StoreLocked)?;
+
+    let entries = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut removed = vec![];
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // references and entries other installs are putting together
+        if name.starts_with('.') || name.contains(".partial-") {
+            continue;
+        }
+
+        let refs = refs_dir(&directory, &name);
+
+        let mut live = 0;
+
+        for reference in std::fs::read_dir(&refs).into_iter().flatten().flatten() {
+            let project = std::fs::read_to_string(reference.path()).unwrap_or_default();
+
+            let used = ProjectState::load_in(Path::new(&project))
+                .is_some_and(|state| state.virtual_entries.contains(&name));
+
+            if used {
+                live += 1;
+            } else {
+                std::fs::remove_file(reference.path()).ok();
+            }
+        }
+
+        if live == 0 {
+            std::fs::remove_dir_all(entry.path()).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: entry.path().display().to_string(),
+            })?;
+
+            std::fs::remove_dir_all(&refs).ok();
+
+            removed.push(name);
+        }
+    }
+
+    removed.sort();
+
+    Ok(removed)
+}
+
+fn key(name: &str, version: &str) -> String {
+    format!("{}@{}", name, version)
+}
+
+/// `name@version` of everything `id` reaches through its dependencies, itself included.
+fn reachable(id: &str, dependencies: &HashMap<String, Vec<(String, String)>>) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![id.to_string()];
+
+    while let Some(id) = pending.pop() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+
+        for (name, version) in dependencies.get(&id).into_iter().flatten() {
+            pending.push(key(name, version));
+        }
+    }
+
+    seen
+}
+
+/// Directory name of the entry of `id`, scoped names get a `+` in place of the `/`.
+fn entry_name(id: &str, reachable: &BTreeSet<String>) -> String {
+    let listing = reachable.iter().cloned().collect::<Vec<_>>().join("\n");
+
+    format!("{}+{}", id.replace('/', "+"), &hex(&listing)[..16])
+}
+
+fn refs_dir(directory: &Path, entry: &str) -> PathBuf {
+    directory.join(".refs").join(entry)
+}
+
+/// File name of the reference of the project in `project`.
+fn project_hash(project: &Path) -> String {
+    hex(&project.display().to_string())[..16].to_string()
+}
+
+fn hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -11,10 +11,9 @@
     limitations under the License.
 */
 
-use std::io::Write;
-
+use crate::core::utils::platform::Platform;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct VoltResponse {
@@ -32,6 +31,33 @@ pub struct VoltPackage {
     pub integrity: String,
     pub peer_dependencies: Option<Vec<String>>,
     pub dependencies: Option<Vec<String>>,
+    #[serde(default)]
+    pub optional_dependencies: Option<Vec<String>>,
+    /// Whether the package runs `preinstall`, `install` or `postinstall` scripts.
+    #[serde(default)]
+    pub has_install_script: bool,
+    /// Name the package is installed under when it was added as `alias@npm:name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Ranges of the dependencies and optional dependencies, keyed by name. Only trees resolved
+    /// from registry metadata have them, the CDN's list names.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ranges: Option<HashMap<String, String>>,
+    /// Platforms the package installs on, `None` when the tree came from the CDN, which
+    /// doesn't list them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    /// The `engines` of the package, like `node: >=14`. `None` when the tree came from the CDN,
+    /// which doesn't list them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engines: Option<BTreeMap<String, String>>,
+}
+
+impl VoltPackage {
+    /// Directory name of the package inside node_modules.
+    pub fn install_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,12 +75,8 @@ pub struct JSONVoltPackage {
     pub bin: Option<HashMap<String, String>>,
     pub dependencies: Option<Vec<String>>,
     pub peer_dependencies: Option<Vec<String>>,
-}
-
-impl VoltResponse {
-    pub fn save(self, path: String) {
-        let mut file = std::fs::File::create(path).unwrap();
-        file.write_all(serde_json::to_string(&self).unwrap().as_bytes())
-            .unwrap();
-    }
+    #[serde(default)]
+    pub optional_dependencies: Option<Vec<String>>,
+    #[serde(default)]
+    pub has_install_script: bool,
 }
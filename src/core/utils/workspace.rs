@@ -0,0 +1,594 @@
+/*
+    Copyright 2021 Volt Contributors
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+        http://www.apache.org/licenses/LICENSE-2.0
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Discover the packages of a workspace declared through the `workspaces` field of package.json.
+
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::git::{git, is_option};
+use crate::core::utils::satisfies;
+
+use miette::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A package that lives inside the workspace.
+#[derive(Debug, Clone)]
+pub struct WorkspacePackage {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+}
+
+/// Read a package.json as raw json, `None` when there is none.
+fn read_manifest(directory: &Path) -> Option<Value> {
+    let data = read_to_string(directory.join("package.json")).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// `workspaces` is either a list of patterns or `{ "packages": [...] }`.
+fn workspace_patterns(manifest: &Value) -> Option<Vec<String>> {
+    let workspaces = match &manifest["workspaces"] {
+        Value::Array(_) => &manifest["workspaces"],
+        Value::Object(_) => &manifest["workspaces"]["packages"],
+        _ => return None,
+    };
+
+    workspaces.as_array().map(|patterns| {
+        patterns
+            .iter()
+            .filter_map(|pattern| pattern.as_str().map(String::from))
+            .collect()
+    })
+}
+
+/// Turn a workspace pattern like `packages/*` or `apps/**` into a regex over relative paths.
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+
+    let mut regex = String::from("^");
+
+    for (index, segment) in pattern.split('/').enumerate() {
+        if index > 0 {
+            regex.push('/');
+        }
+
+        if segment == "**" {
+            regex.push_str(".*");
+        } else {
+            regex.push_str(&regex::escape(segment).replace(r"\*", "[^/]*"));
+        }
+    }
+
+    regex.push('$');
+
+    Regex::new(&regex).ok()
+}
+
+/// Closest directory at or above `start` whose package.json declares `workspaces`.
+pub fn workspace_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|directory| {
+            read_manifest(directory)
+                .as_ref()
+                .and_then(workspace_patterns)
+                .is_some()
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Every package matched by the `workspaces` patterns of the package.json in `root`.
+///
+/// Patterns starting with `!` exclude directories matched by the others.
+pub fn workspace_packages(root: &Path) -> Result<Vec<WorkspacePackage>> {
+    let manifest = read_manifest(root).ok_or_else(|| VoltError::ReadFileError {
+        source: std::io::Error::new(std::io::ErrorKind::NotFound, "no readable package.json"),
+        name: root.join("package.json").display().to_string(),
+    })?;
+
+    let patterns = workspace_patterns(&manifest).unwrap_or_default();
+
+    let (excludes, includes): (Vec<_>, Vec<_>) = patterns
+        .iter()
+        .partition(|pattern| pattern.starts_with('!'));
+
+    let includes = includes
+        .iter()
+        .filter_map(|pattern| pattern_regex(pattern))
+        .collect::<Vec<Regex>>();
+
+    let excludes = excludes
+        .iter()
+        .filter_map(|pattern| pattern_regex(&pattern[1..]))
+        .collect::<Vec<Regex>>();
+
+    let mut packages = vec![];
+
+    let directories = WalkDir::new(root)
+        .min_depth(1)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.file_type().is_dir() && name != "node_modules" && !name.starts_with('.')
+        })
+        .filter_map(|entry| entry.ok());
+
+    for directory in directories {
+        let relative = directory
+            .path()
+            .strip_prefix(root)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let matched = includes.iter().any(|regex| regex.is_match(&relative))
+            && !excludes.iter().any(|regex| regex.is_match(&relative));
+
+        if !matched {
+            continue;
+        }
+
+        if let Some(manifest) = read_manifest(directory.path()) {
+            if let Some(name) = manifest["name"].as_str() {
+                packages.push(WorkspacePackage {
+                    name: name.to_string(),
+                    version: manifest["version"].as_str().unwrap_or("0.0.0").to_string(),
+                    path: directory.path().to_path_buf(),
+                });
+            }
+        }
+    }
+
+    Ok(packages)
+}
+
+/// Link a workspace package into `node_modules` so it resolves like an installed one.
+pub fn link_workspace_package(node_modules: &Path, package: &WorkspacePackage) -> Result<()> {
+    let link = node_modules.join(&package.name);
+
+    let parent = link.parent().unwrap();
+
+    std::fs::create_dir_all(parent).map_err(VoltError::CreateDirError)?;
+
+    if std::fs::symlink_metadata(&link).is_ok() {
+        let removed = if link.is_dir() && link.read_link().is_err() {
+            std::fs::remove_dir_all(&link)
+        } else {
+            std::fs::remove_file(&link).or_else(|_| std::fs::remove_dir(&link))
+        };
+
+        removed.map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: link.display().to_string(),
+        })?;
+    }
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&package.path, &link);
+
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(&package.path, &link);
+
+    linked.map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: link.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// The directory of a `file:` or `link:` specifier, e.g. `vendor/left-pad` for
+/// `file:vendor/left-pad`.
+pub fn local_path(spec: &str) -> Option<&str> {
+    spec.strip_prefix("file:")
+        .or_else(|| spec.strip_prefix("link:"))
+}
+
+/// The package `name` depends on with `spec` when it is a `file:` or `link:` directory relative
+/// to `origin`, the directory of the package.json declaring it. Such packages are linked like
+/// workspace packages, under the name they are depended on with.
+pub fn local_package(origin: &Path, name: &str, spec: &str) -> Option<WorkspacePackage> {
+    let path = origin.join(local_path(spec)?);
+    let manifest = read_manifest(&path)?;
+
+    Some(WorkspacePackage {
+        name: name.to_string(),
+        version: manifest["version"].as_str().unwrap_or_default().to_string(),
+        path,
+    })
+}
+
+/// The range of a `workspace:` specifier, e.g. `^1.0.0` for `workspace:^1.0.0`.
+pub fn workspace_range(spec: &str) -> Option<&str> {
+    spec.strip_prefix("workspace:")
+}
+
+/// The workspace package a `workspace:` specifier of `name` points at.
+///
+/// `workspace:*`, `workspace:^` and `workspace:~` take the member whatever its version, any
+/// other range has to be satisfied by it.
+pub fn resolve_workspace_spec<'a>(
+    name: &str,
+    spec: &str,
+    members: &'a [WorkspacePackage],
+) -> Result<&'a WorkspacePackage> {
+    let range = workspace_range(spec).unwrap_or(spec);
+
+    members
+        .iter()
+        .find(|member| {
+            member.name == name
+                && (matches!(range, "*" | "^" | "~" | "") || satisfies(&member.version, range))
+        })
+        .ok_or_else(|| {
+            VoltError::WorkspaceDependencyNotFound {
+                name: name.to_string(),
+                spec: spec.to_string(),
+            }
+            .into()
+        })
+}
+
+/// The range a `workspace:` specifier is published as, `workspace:^` becomes `^<version>`
+/// and `workspace:*` the exact version of the member.
+pub fn published_range(spec: &str, version: &str) -> String {
+    match workspace_range(spec).unwrap_or(spec) {
+        "*" | "" => version.to_string(),
+        "^" => format!("^{}", version),
+        "~" => format!("~{}", version),
+        range => range.to_string(),
+    }
+}
+
+/// Rewrite the `workspace:` specifiers in the manifest of the package in `directory` to the
+/// ranges they are published as. Returns whether anything was rewritten.
+pub fn replace_workspace_ranges(directory: &Path, manifest: &mut Value) -> Result<bool> {
+    let mut members = None;
+    let mut replaced = false;
+
+    for field in [
+        "dependencies",
+        "devDependencies",
+        "optionalDependencies",
+        "peerDependencies",
+    ] {
+        let section = match manifest.get_mut(field).and_then(Value::as_object_mut) {
+            Some(section) => section,
+            None => continue,
+        };
+
+        for (name, spec) in section.iter_mut() {
+            let range = match spec.as_str() {
+                Some(range) if workspace_range(range).is_some() => range.to_string(),
+                _ => continue,
+            };
+
+            if members.is_none() {
+                let root = workspace_root(directory).ok_or(VoltError::NotInWorkspace)?;
+                members = Some(workspace_packages(&root)?);
+            }
+
+            let member = resolve_workspace_spec(name, &range, members.as_deref().unwrap())?;
+
+            *spec = Value::String(published_range(&range, &member.version));
+            replaced = true;
+        }
+    }
+
+    Ok(replaced)
+}
+
+/// Which packages a `--filter` selector matches on.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// Package names, `*` matches anything, e.g. `@scope/*`.
+    Name(Regex),
+    /// Packages in or below a directory, e.g. `./packages/app` or `{packages/*}`.
+    Path(PathBuf),
+    /// Packages with files changed since a git ref, e.g. `[origin/main]`.
+    ChangedSince(String),
+}
+
+/// A pnpm style `--filter`, e.g. `app`, `app...` (with its dependencies), `...lib` (with its
+/// dependents), `...^lib` (only its dependents) or `!legacy` (excluded).
+#[derive(Debug, Clone)]
+pub struct WorkspaceFilter {
+    pub selector: Selector,
+    pub exclude: bool,
+    /// Also select the workspace packages the matched ones depend on.
+    pub dependencies: bool,
+    /// Also select the workspace packages depending on the matched ones.
+    pub dependents: bool,
+    /// Select the matched packages themselves, `false` with `^`.
+    pub include_self: bool,
+}
+
+impl WorkspaceFilter {
+    /// Parse a filter, paths are relative to `current_dir`.
+    pub fn parse(filter: &str, current_dir: &Path) -> Result<WorkspaceFilter> {
+        let mut rest = filter.trim();
+
+        let exclude = rest.starts_with('!');
+        rest = rest.trim_start_matches('!');
+
+        let dependents = rest.starts_with("...");
+        rest = rest.trim_start_matches("...");
+
+        let dependencies = rest.ends_with("...");
+        rest = rest.trim_end_matches("...");
+
+        let mut include_self = true;
+
+        if dependents && rest.starts_with('^') {
+            include_self = false;
+            rest = &rest[1..];
+        }
+
+        if dependencies && rest.ends_with('^') {
+            include_self = false;
+            rest = &rest[..rest.len() - 1];
+        }
+
+        let selector = if rest.starts_with('[') && rest.ends_with(']') && rest.len() > 2 {
+            Selector::ChangedSince(rest[1..rest.len() - 1].to_string())
+        } else if rest.starts_with('{') && rest.ends_with('}') && rest.len() > 2 {
+            Selector::Path(current_dir.join(&rest[1..rest.len() - 1]))
+        } else if rest.starts_with('.') || rest.starts_with('/') {
+            Selector::Path(current_dir.join(rest))
+        } else if !rest.is_empty() {
+            let regex = format!("^{}$", regex::escape(rest).replace(r"\*", ".*"));
+
+            Selector::Name(Regex::new(&regex).map_err(|_| VoltError::InvalidFilter {
+                filter: filter.to_string(),
+            })?)
+        } else {
+            return Err(VoltError::InvalidFilter {
+                filter: filter.to_string(),
+            }
+            .into());
+        };
+
+        Ok(WorkspaceFilter {
+            selector,
+            exclude,
+            dependencies,
+            dependents,
+            include_self,
+        })
+    }
+}
+
+/// Names of the other workspace packages `package` depends on, dev and peer dependencies included.
+fn workspace_dependencies(package: &WorkspacePackage, names: &BTreeSet<&str>) -> Vec<String> {
+    let manifest = match read_manifest(&package.path) {
+        Some(manifest) => manifest,
+        None => return vec![],
+    };
+
+    let mut dependencies = vec![];
+
+    for field in [
+        "dependencies",
+        "devDependencies",
+        "optionalDependencies",
+        "peerDependencies",
+    ] {
+        if let Some(section) = manifest[field].as_object() {
+            dependencies.extend(
+                section
+                    .keys()
+                    .filter(|name| names.contains(name.as_str()))
+                    .cloned(),
+            );
+        }
+    }
+
+    dependencies.sort();
+    dependencies.dedup();
+
+    dependencies
+}
+
+/// Every package in `packages` mapped to the names of the others in `packages` it depends on.
+pub fn dependency_graph(packages: &[WorkspacePackage]) -> HashMap<String, Vec<String>> {
+    let names = packages
+        .iter()
+        .map(|package| package.name.as_str())
+        .collect::<BTreeSet<_>>();
+
+    packages
+        .iter()
+        .map(|package| {
+            (
+                package.name.clone(),
+                workspace_dependencies(package, &names),
+            )
+        })
+        .collect()
+}
+
+/// Paths relative to `root` of every file changed since `reference`, untracked files included.
+fn changed_files(root: &Path, reference: &str) -> Result<Vec<String>> {
+    if is_option(reference) {
+        return Err(VoltError::GitError {
+            command: format!("git diff {}", reference),
+            message: String::from("the reference to compare against can't start with `-`"),
+        }
+        .into());
+    }
+
+    let changed = git(
+        Some(root),
+        ["diff", "--name-only", "--relative", reference, "--"],
+    )?;
+    let untracked = git(Some(root), ["ls-files", "--others", "--exclude-standard"])?;
+
+    Ok(String::from_utf8_lossy(&changed)
+        .lines()
+        .chain(String::from_utf8_lossy(&untracked).lines())
+        .map(String::from)
+        .collect())
+}
+
+/// Walk the workspace graph from `start` through `edges`, `start` itself excluded.
+fn reachable(start: &str, edges: &HashMap<String, Vec<String>>) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+    let mut queue = VecDeque::from(vec![start.to_string()]);
+
+    while let Some(name) = queue.pop_front() {
+        for next in edges.get(&name).into_iter().flatten() {
+            if next != start && found.insert(next.clone()) {
+                queue.push_back(next.clone());
+            }
+        }
+    }
+
+    found
+}
+
+/// The packages of the workspace at `root` selected by `filters`.
+///
+/// Packages matched by any filter are selected, except those matched by a `!` filter. With
+/// only `!` filters every other package is selected. The order of `packages` is kept.
+pub fn filter_workspace(
+    root: &Path,
+    packages: &[WorkspacePackage],
+    filters: &[WorkspaceFilter],
+) -> Result<Vec<WorkspacePackage>> {
+    let dependencies = dependency_graph(packages);
+    let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (name, edges) in dependencies.iter() {
+        for dependency in edges {
+            dependents
+                .entry(dependency.clone())
+                .or_default()
+                .push(name.clone());
+        }
+    }
+
+    let mut changed: HashMap<String, Vec<String>> = HashMap::new();
+
+    let mut select = |filter: &WorkspaceFilter| -> Result<BTreeSet<String>> {
+        let mut matched = BTreeSet::new();
+
+        for package in packages {
+            let is_match = match &filter.selector {
+                Selector::Name(regex) => regex.is_match(&package.name),
+                Selector::Path(path) => path_matches(root, path, &package.path),
+                Selector::ChangedSince(reference) => {
+                    if !changed.contains_key(reference) {
+                        changed.insert(reference.clone(), changed_files(root, reference)?);
+                    }
+
+                    let relative = package
+                        .path
+                        .strip_prefix(root)
+                        .unwrap_or(&package.path)
+                        .to_string_lossy()
+                        .replace('\\', "/");
+
+                    changed[reference]
+                        .iter()
+                        .any(|file| file.starts_with(&format!("{}/", relative)))
+                }
+            };
+
+            if !is_match {
+                continue;
+            }
+
+            if filter.include_self {
+                matched.insert(package.name.clone());
+            }
+
+            if filter.dependencies {
+                matched.extend(reachable(&package.name, &dependencies));
+            }
+
+            if filter.dependents {
+                matched.extend(reachable(&package.name, &dependents));
+            }
+        }
+
+        Ok(matched)
+    };
+
+    let mut included = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+
+    for filter in filters {
+        if filter.exclude {
+            excluded.extend(select(filter)?);
+        } else {
+            included.extend(select(filter)?);
+        }
+    }
+
+    if filters.iter().all(|filter| filter.exclude) {
+        included = packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect();
+    }
+
+    Ok(packages
+        .iter()
+        .filter(|package| included.contains(&package.name) && !excluded.contains(&package.name))
+        .cloned()
+        .collect())
+}
+
+/// Whether the package in `package_dir` is in or below `selected`, which may use `*` and `**`.
+fn path_matches(root: &Path, selected: &Path, package_dir: &Path) -> bool {
+    let selected = normalize(selected);
+
+    if package_dir.starts_with(&selected) {
+        return true;
+    }
+
+    let pattern = match selected.strip_prefix(root) {
+        Ok(pattern) => pattern.to_string_lossy().replace('\\', "/"),
+        Err(_) => return false,
+    };
+
+    let relative = package_dir
+        .strip_prefix(root)
+        .unwrap_or(package_dir)
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    pattern_regex(&pattern).is_some_and(|regex| regex.is_match(&relative))
+}
+
+/// Resolve `.` and `..` without touching the filesystem, the path may contain globs.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
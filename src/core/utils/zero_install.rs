@@ -0,0 +1,364 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Package archives committed to the repository, so a fresh clone installs without network.
+//!
+//! `.volt/cache` works like an `offline-mirror` kept inside `.volt`: every tarball an install
+//! downloads is copied there and installed from it first. It's used once `volt cache vendor`
+//! created it or `[zero-install]` is enabled in volt.toml, and an `offline-mirror` takes its
+//! place when one is set.
+//!
+//! The repository shouldn't grow without anyone noticing. Archives above
+//! `max-archive-size` stay out of the cache, with a `<archive>.oversized` marker so they
+//! aren't downloaded again on every install, and installs warn once the whole cache is above
+//! `max-size`. `.volt/.gitignore` keeps the rest of `.volt` out of the repository.
+//!
+//! When volt.lock already pins everything package.json asks for and the cache holds every
+//! archive of it, `volt install` takes the tree from the lockfile and the archives instead of
+//! resolving it against the registry, so nothing needs the network.
+
+use crate::commands::add::Package;
+use crate::core::model::lock_file::{DependencyLock, LockFile, LockedManifest};
+use crate::core::utils;
+use crate::core::utils::app::App;
+use crate::core::utils::constants::{ZERO_INSTALL_MAX_ARCHIVE_SIZE, ZERO_INSTALL_MAX_SIZE};
+use crate::core::utils::dedup::format_bytes;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::voltapi::VoltPackage;
+
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use miette::Result;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use tracing::{info, warn};
+
+/// Extension of the marker left for an archive that was too large to keep.
+const OVERSIZED: &str = "oversized";
+
+/// What git should see of `.volt`, everything else in it is local to the machine.
+const GITIGNORE: &str =
+    "# generated by volt, the cache is committed for zero-installs\n/*\n!/.gitignore\n!/cache/\n";
+
+/// Directory of the cache of the project in `project`.
+pub fn cache_dir(project: &Path) -> PathBuf {
+    project.join(".volt").join("cache")
+}
+
+/// Whether installs of the project of `app` read and fill the cache.
+pub fn is_enabled(app: &App) -> bool {
+    app.config.zero_install.enabled || cache_dir(&app.current_dir).is_dir()
+}
+
+/// Whether `archive` was left out of the cache for its size.
+pub fn is_oversized(archive: &Path) -> bool {
+    marker(archive).exists()
+}
+
+/// Whether an archive of `size` bytes at `archive` is too large to keep, recording it when
+/// it is. Archives in an `offline-mirror` are always kept.
+pub fn leave_out(app: &App, archive: &Path, size: u64) -> bool {
+    let limit = app
+        .config
+        .zero_install
+        .max_archive_size
+        .unwrap_or(ZERO_INSTALL_MAX_ARCHIVE_SIZE)
+        * 1_000_000;
+
+    if !archive.starts_with(cache_dir(&app.current_dir)) || size <= limit {
+        return false;
+    }
+
+    std::fs::write(marker(archive), size.to_string()).ok();
+
+    warn!(
+        "{} is {}, above `max-archive-size`, it's downloaded instead of kept in .volt/cache",
+        archive
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .bright_cyan(),
+        format_bytes(size)
+    );
+
+    true
+}
+
+/// Warn when the cache of the project of `app` takes more than `max-size`, after an install.
+pub fn check_size(app: &App) {
+    if !is_enabled(app) {
+        return;
+    }
+
+    // `enabled` creates the cache on the first install, without `volt cache vendor`
+    let directory = cache_dir(&app.current_dir);
+
+    if directory.is_dir() && !directory.parent().unwrap().join(".gitignore").exists() {
+        create(app).ok();
+    }
+
+    let limit = app
+        .config
+        .zero_install
+        .max_size
+        .unwrap_or(ZERO_INSTALL_MAX_SIZE)
+        * 1_000_000;
+
+    let mut archives = archives(&directory);
+
+    let total = archives.iter().map(|(_, size)| size).sum::<u64>();
+
+    if total <= limit {
+        return;
+    }
+
+    archives.sort_by_key(|archive| std::cmp::Reverse(archive.1));
+
+    warn!(
+        "the zero-install cache takes {}, above `max-size` ({}), the largest archives are:",
+        format_bytes(total).bright_yellow(),
+        format_bytes(limit)
+    );
+
+    for (path, size) in archives.iter().take(5) {
+        println!(
+            "  {} {}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            format_bytes(*size).bright_black()
+        );
+    }
+
+    info!(
+        "lower `max-archive-size` under `[zero-install]` in volt.toml, or drop the cache with `volt cache unvendor`"
+    );
+}
+
+/// Create the cache of the project of `app` and write `.volt/.gitignore`.
+pub fn create(app: &App) -> Result<PathBuf> {
+    let directory = cache_dir(&app.current_dir);
+
+    std::fs::create_dir_all(&directory).map_err(VoltError::CreateDirError)?;
+
+    let gitignore = directory.parent().unwrap().join(".gitignore");
+
+    std::fs::write(&gitignore, GITIGNORE).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: gitignore.display().to_string(),
+    })?;
+
+    Ok(directory)
+}
+
+/// Remove the archives and markers of packages `lock_file` no longer has, returns the names of
+/// the removed archives.
+pub fn prune(app: &App, lock_file: &LockFile) -> Result<Vec<String>> {
+    let used = lock_file
+        .dependencies
+        .values()
+        .filter_map(|lock| app.mirror_path(&lock.name, &lock.version))
+        .filter_map(|path| path.file_name().map(|name| name.to_os_string()))
+        .collect::<HashSet<_>>();
+
+    let mut removed = vec![];
+
+    for (path, _) in archives(&cache_dir(&app.current_dir)) {
+        let name = path.file_name().unwrap().to_os_string();
+
+        if used.contains(&name) {
+            continue;
+        }
+
+        std::fs::remove_file(&path).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        removed.push(name.to_string_lossy().to_string());
+    }
+
+    for entry in std::fs::read_dir(cache_dir(&app.current_dir))
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let path = entry.path();
+
+        let archive = path.with_extension("");
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == OVERSIZED)
+            && !archive
+                .file_name()
+                .is_some_and(|name| used.contains(&name.to_os_string()))
+        {
+            std::fs::remove_file(&path).ok();
+        }
+    }
+
+    removed.sort();
+
+    Ok(removed)
+}
+
+/// Remove the cache of the project of `app`, returns how much it took.
+pub fn remove(app: &App) -> Result<u64> {
+    let directory = cache_dir(&app.current_dir);
+
+    let size = archives(&directory).iter().map(|(_, size)| size).sum();
+
+    std::fs::remove_dir_all(&directory).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: directory.display().to_string(),
+    })?;
+
+    std::fs::remove_file(directory.parent().unwrap().join(".gitignore")).ok();
+
+    Ok(size)
+}
+
+/// The locked package `lock`, with what volt.lock recorded of its manifest.
+pub fn locked_package(lock: &DependencyLock) -> VoltPackage {
+    let LockedManifest {
+        bin,
+        peer_dependencies,
+        optional_dependencies,
+        ranges,
+    } = lock.manifest.clone().unwrap_or_default();
+
+    VoltPackage {
+        name: lock.name.clone(),
+        version: lock.version.clone(),
+        tarball: lock.tarball.clone(),
+        bin: (!bin.is_empty()).then(|| bin.into_iter().collect()),
+        integrity: lock.integrity.clone(),
+        peer_dependencies: (!peer_dependencies.is_empty()).then_some(peer_dependencies),
+        dependencies: Some(lock.dependencies.clone()),
+        optional_dependencies: (!optional_dependencies.is_empty()).then_some(optional_dependencies),
+        has_install_script: lock.has_install_script,
+        alias: lock.alias.clone(),
+        ranges: (!ranges.is_empty()).then(|| ranges.into_iter().collect()),
+        platform: Some(lock.platform.clone()),
+        engines: (!lock.engines.is_empty()).then(|| lock.engines.clone()),
+    }
+}
+
+/// The tree of `packages`, every one of them pinned by `lock_file`, when the cache holds the
+/// archives of all the packages they reach. Keyed like a resolved tree, by `name@version`.
+pub fn locked_tree(
+    app: &App,
+    lock_file: &LockFile,
+    packages: &[Package],
+) -> Option<HashMap<String, VoltPackage>> {
+    if !is_enabled(app) || packages.is_empty() {
+        return None;
+    }
+
+    utils::locked_tree(lock_file, packages, |lock| {
+        let archive = app
+            .mirror_path(&lock.name, &lock.version)
+            .filter(|path| path.starts_with(cache_dir(&app.current_dir)) && path.is_file())?;
+
+        cached_package(lock, &archive)
+    })
+}
+
+/// The locked package `lock`, with the bins and the kinds of dependencies from the manifest
+/// in its archive.
+fn cached_package(lock: &DependencyLock, archive: &Path) -> Option<VoltPackage> {
+    let manifest = read_manifest(archive)?;
+
+    let ranges = ["dependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|field| manifest[*field].as_object())
+        .flatten()
+        .filter_map(|(name, range)| Some((name.clone(), range.as_str()?.to_string())))
+        .collect::<HashMap<_, _>>();
+
+    let names = |field: &str| {
+        manifest[field]
+            .as_object()
+            .map(|dependencies| dependencies.keys().cloned().collect::<Vec<_>>())
+    };
+
+    let bin = match &manifest["bin"] {
+        Value::String(file) => {
+            let name = lock.name.rsplit('/').next().unwrap_or(&lock.name);
+
+            Some(HashMap::from([(name.to_string(), file.clone())]))
+        }
+        Value::Object(bins) => Some(
+            bins.iter()
+                .filter_map(|(name, file)| Some((name.clone(), file.as_str()?.to_string())))
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    Some(VoltPackage {
+        bin,
+        peer_dependencies: names("peerDependencies"),
+        optional_dependencies: names("optionalDependencies"),
+        ranges: (!ranges.is_empty()).then_some(ranges),
+        ..locked_package(lock)
+    })
+}
+
+/// `package.json` of the package in `archive`.
+fn read_manifest(archive: &Path) -> Option<Value> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(archive).ok()?));
+
+    for entry in archive.entries().ok()? {
+        let mut entry = entry.ok()?;
+
+        // the top directory is `package` in registry tarballs, but not in every one
+        let is_manifest = entry.path().ok()?.components().count() == 2
+            && entry.path().ok()?.ends_with("package.json");
+
+        if is_manifest {
+            let mut data = String::new();
+            entry.read_to_string(&mut data).ok()?;
+
+            return serde_json::from_str(&data).ok();
+        }
+    }
+
+    None
+}
+
+/// Archives in `directory` and their sizes.
+pub fn archives(directory: &Path) -> Vec<(PathBuf, u64)> {
+    std::fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "tgz"))
+        .filter_map(|path| {
+            let size = std::fs::metadata(&path).ok()?.len();
+
+            Some((path, size))
+        })
+        .collect()
+}
+
+fn marker(archive: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.{}", archive.display(), OVERSIZED))
+}
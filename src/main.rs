@@ -19,10 +19,22 @@ mod core;
 use std::{sync::Arc, time::Instant};
 
 use crate::core::command::Command;
+use crate::core::model::config::VoltConfig;
 use crate::core::utils::app::App;
+use crate::core::utils::compat::{node_cpu, node_os};
+use crate::core::utils::{experiments, logging, onboarding, output, timings};
+use crate::core::VERSION;
 use clap::{Arg, ArgMatches};
 use colored::Colorize;
-use commands::{compress::Compress, init::Init};
+use commands::{
+    audit::Audit, bug_report::BugReport, cache::Cache, check::Check, ci::Ci, clean::Clean,
+    completions::Completions, compress::Compress, config::Config, deprecate::Deprecate,
+    env_diff::EnvDiff, export::ExportLockfile, graph::Graph, info::Info, init::Init,
+    install::Install, licenses::Licenses, lint_scripts::LintScripts, list::List, node::Node,
+    owner::Owner, pack::Pack, ping::Ping, policy::Policy, publish::Publish, query::Query, run::Run,
+    sbom::Sbom, serve::ServeRegistry, sign::Sign, store::Store, tag::Tag, unpublish::Unpublish,
+    vendor::Vendor, verify::Verify, why::Why,
+};
 
 use crate::commands::add::*;
 
@@ -32,18 +44,192 @@ pub async fn map_subcommand(matches: ArgMatches) -> miette::Result<()> {
             let app = Arc::new(App::initialize(args)?);
             Add::exec(app).await
         }
+        Some(("audit", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Audit::exec(app).await
+        }
+        Some(("bug-report", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            BugReport::exec(app).await
+        }
+        Some(("cache", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Cache::exec(app).await
+        }
+        Some(("clean", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Clean::exec(app).await
+        }
+        Some(("env-diff", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            EnvDiff::exec(app).await
+        }
+        Some(("export-lockfile", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            ExportLockfile::exec(app).await
+        }
+        Some(("sbom", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Sbom::exec(app).await
+        }
+        Some(("graph", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Graph::exec(app).await
+        }
+        Some(("info", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Info::exec(app).await
+        }
+        Some(("install", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Install::exec(app).await
+        }
         Some(("init", args)) => {
             let app = Arc::new(App::initialize(args)?);
             Init::exec(app).await
         }
+        Some(("check", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Check::exec(app).await
+        }
+        Some(("ci", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Ci::exec(app).await
+        }
         Some(("compress", args)) => {
             let app = Arc::new(App::initialize(args)?);
             Compress::exec(app).await
         }
+        Some(("dist-tag", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Tag::exec(app).await
+        }
+        Some(("deprecate", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Deprecate::exec(app).await
+        }
+        Some(("config", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Config::exec(app).await
+        }
+        Some(("licenses", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Licenses::exec(app).await
+        }
+        Some(("lint-scripts", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            LintScripts::exec(app).await
+        }
+        Some(("list", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            List::exec(app).await
+        }
+        Some(("node", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Node::exec(app).await
+        }
+        Some(("owner", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Owner::exec(app).await
+        }
+        Some(("pack", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Pack::exec(app).await
+        }
+        Some(("ping", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Ping::exec(app).await
+        }
+        Some(("policy", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Policy::exec(app).await
+        }
+        Some(("publish", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Publish::exec(app).await
+        }
+        Some(("query", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Query::exec(app).await
+        }
+        Some(("run", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Run::exec(app).await
+        }
+        Some(("serve-registry", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            ServeRegistry::exec(app).await
+        }
+        Some(("sign", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Sign::exec(app).await
+        }
+        Some(("store", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Store::exec(app).await
+        }
+        Some(("unpublish", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Unpublish::exec(app).await
+        }
+        Some(("vendor", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Vendor::exec(app).await
+        }
+        Some(("verify", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Verify::exec(app).await
+        }
+        Some(("why", args)) => {
+            let app = Arc::new(App::initialize(args)?);
+            Why::exec(app).await
+        }
+        None => {
+            let directory = std::env::current_dir().unwrap_or_default();
+            onboarding::guide(&directory);
+            Ok(())
+        }
         _ => Ok(()),
     }
 }
 
+/// How often `flag` was given, global flags land in the matches of the subcommand when given
+/// after it.
+fn occurrences(matches: &ArgMatches, flag: &str) -> u64 {
+    let after = matches
+        .subcommand()
+        .map_or(0, |(_, args)| args.occurrences_of(flag));
+
+    matches.occurrences_of(flag).max(after)
+}
+
+/// `volt --version`, with `--verbose` also the platform and the experiments that are on.
+fn print_version(verbose: bool) {
+    println!("volt {}", VERSION);
+
+    if !verbose {
+        return;
+    }
+
+    println!("platform: {} {}", node_os(), node_cpu());
+
+    let config = std::env::current_dir()
+        .ok()
+        .zip(dirs::home_dir())
+        .and_then(|(directory, home)| VoltConfig::load_project(&directory, &home).ok())
+        .unwrap_or_default();
+
+    let active = experiments::active(&config);
+
+    if active.is_empty() {
+        println!("experiments: none");
+    }
+
+    for (experiment, origin) in active {
+        println!("experiment: {} (from {})", experiment.name(), origin);
+    }
+}
+
 #[tokio::main]
 async fn main() -> miette::Result<()> {
     let start = Instant::now();
@@ -76,26 +262,375 @@ Commands:
         "[flags]".bright_blue(),
     );
 
+    let ci_usage = format!(
+        "{} ci {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let completions_usage = format!(
+        "{} completions {}",
+        "volt".bright_green().bold(),
+        "<shell>".bright_blue(),
+    );
+
     let compress_usage = format!(
         "{} compress {}",
         "volt".bright_green().bold(),
         "[flags]".bright_blue(),
     );
 
-    let app = clap::App::new("volt")
-        .version("1.0.0")
+    let config_usage = format!(
+        "{} config {} {} {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+        "<key>".bright_blue(),
+        "[value]".bright_blue(),
+    );
+
+    let audit_usage = format!(
+        "{} audit {} {}",
+        "volt".bright_green().bold(),
+        "[command]".bright_blue(),
+        "[flags]".bright_blue(),
+    );
+
+    let bug_report_usage = format!(
+        "{} bug-report {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let env_diff_usage = format!(
+        "{} env-diff {} {}",
+        "volt".bright_green().bold(),
+        "<before>".bright_blue(),
+        "<after>".bright_blue(),
+    );
+
+    let export_lockfile_usage = format!(
+        "{} export-lockfile {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let sbom_usage = format!(
+        "{} sbom {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let graph_usage = format!(
+        "{} graph {} {}",
+        "volt".bright_green().bold(),
+        "[export]".bright_blue(),
+        "[flags]".bright_blue(),
+    );
+
+    let info_usage = format!(
+        "{} info {} {}",
+        "volt".bright_green().bold(),
+        "<package>".bright_blue(),
+        "[field]".bright_blue(),
+    );
+
+    let serve_registry_usage = format!(
+        "{} serve-registry {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let cache_usage = format!(
+        "{} cache {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+    );
+
+    let clean_usage = format!(
+        "{} clean {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let sign_usage = format!(
+        "{} sign {} {}",
+        "volt".bright_green().bold(),
+        "[command]".bright_blue(),
+        "[flags]".bright_blue(),
+    );
+
+    let store_usage = format!(
+        "{} store {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+    );
+
+    let install_usage = format!(
+        "{} install {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let pack_usage = format!(
+        "{} pack {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let ping_usage = format!(
+        "{} ping {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let publish_usage = format!(
+        "{} publish {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let unpublish_usage = format!(
+        "{} unpublish {} {}",
+        "volt".bright_green().bold(),
+        "<package>[@version]".bright_blue(),
+        "[flags]".bright_blue(),
+    );
+
+    let vendor_usage = format!(
+        "{} vendor {}",
+        "volt".bright_green().bold(),
+        "[package]".bright_blue(),
+    );
+
+    let verify_usage = format!(
+        "{} verify {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let licenses_usage = format!(
+        "{} licenses {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let lint_scripts_usage = format!(
+        "{} lint-scripts {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let list_usage = format!(
+        "{} list {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let node_usage = format!(
+        "{} node {} {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+        "[version]".bright_blue(),
+    );
+
+    let owner_usage = format!(
+        "{} owner {} {} {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+        "[user]".bright_blue(),
+        "[package]".bright_blue(),
+    );
+
+    let deprecate_usage = format!(
+        "{} deprecate {} {}",
+        "volt".bright_green().bold(),
+        "<package>[@range]".bright_blue(),
+        "<message>".bright_blue(),
+    );
+
+    let dist_tag_usage = format!(
+        "{} dist-tag {} {} {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+        "[package]".bright_blue(),
+        "[tag]".bright_blue(),
+    );
+
+    let why_usage = format!(
+        "{} why {}",
+        "volt".bright_green().bold(),
+        "<package>".bright_blue(),
+    );
+
+    let query_usage = format!(
+        "{} query {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let policy_usage = format!(
+        "{} policy {}",
+        "volt".bright_green().bold(),
+        "<command>".bright_blue(),
+    );
+
+    let check_usage = format!(
+        "{} check {}",
+        "volt".bright_green().bold(),
+        "[flags]".bright_blue(),
+    );
+
+    let run_usage = format!(
+        "{} run {} {}",
+        "volt".bright_green().bold(),
+        "<script>".bright_blue(),
+        "[-- <args>...]".bright_blue(),
+    );
+
+    let mut app = clap::App::new("volt")
         .author("XtremeDevX <xtremedevx@gmail.com>")
         .about("Manage your NPM packages")
         .override_help(volt_help.as_str())
+        .arg(
+            Arg::new("version")
+                .short('V')
+                .long("version")
+                .about("Print the version, with --verbose also the experiments that are on."),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .multiple_occurrences(true)
+                .about("Output verbose messages on internal operations, -vv for every detail."),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .about("Only print errors and warnings."),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .global(true)
+                .about("Print the results as a JSON document instead of text."),
+        )
         .subcommand(
             clap::App::new("add")
                 .about("Add a package to the dependencies for your project.")
                 .override_usage(add_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to install from instead of the configured one."),
+                )
                 .arg(
                     Arg::new("package-names")
                         .about("Packages to add to the dependencies for your project.")
                         .multiple_values(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::new("auto-install-peers")
+                        .short('P')
+                        .long("auto-install-peers")
+                        .about("Install missing peer dependencies."),
+                )
+                .arg(
+                    Arg::new("global")
+                        .short('g')
+                        .long("global")
+                        .about("Install globally and link its executables."),
+                )
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .takes_value(true)
+                        .about("Only install versions published before the given date."),
+                )
+                .arg(
+                    Arg::new("file-integrity")
+                        .long("file-integrity")
+                        .about("Record a hash of every installed file in the lockfile."),
+                )
+                .arg(
+                    Arg::new("prefer-dedupe")
+                        .long("prefer-dedupe")
+                        .about("Reuse locked versions that satisfy the requested range."),
+                )
+                .arg(
+                    Arg::new("save-workspace-protocol")
+                        .long("save-workspace-protocol")
+                        .takes_value(true)
+                        .possible_values(&["true", "false"])
+                        .about("Save workspace packages as `workspace:` ranges."),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .about("Install packages that introduce install scripts without asking."),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .about("Don't run the install scripts of dependencies or of the project."),
+                )
+                .arg(
+                    Arg::new("engine-strict")
+                        .long("engine-strict")
+                        .about("Fail when node doesn't satisfy the `engines` of a package."),
+                )
+                .arg(
+                    Arg::new("force-overwrite")
+                        .long("force-overwrite")
+                        .about("Replace files in node_modules that volt didn't create."),
+                )
+                .arg(
+                    Arg::new("low-memory")
+                        .long("low-memory")
+                        .about("Use less memory at the cost of speed, for small containers."),
+                )
+                .arg(
+                    Arg::new("no-progress")
+                        .long("no-progress")
+                        .about("Log every package on a line instead of drawing progress bars."),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<usize>())
+                        .about("Downloads and extractions run at once."),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .about("Resolve from the metadata cache and install from the store only."),
+                )
+                .arg(
+                    Arg::new("prefer-offline")
+                        .long("prefer-offline")
+                        .conflicts_with("offline")
+                        .about("Only ask the registry for packages missing from the cache."),
+                )
+                .arg(
+                    Arg::new("track")
+                        .long("track")
+                        .takes_value(true)
+                        .about("Record why the packages were added, e.g. a ticket url."),
+                )
+                .arg(
+                    Arg::new("filter")
+                        .short('F')
+                        .long("filter")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .about("Add to the workspace packages matching the filter."),
                 ),
         )
         .subcommand(
@@ -104,17 +639,743 @@ Commands:
                 .override_usage(init_usage.as_str())
                 .arg(Arg::new("yes").short('y').about("Use default options")),
         )
+        .subcommand(
+            clap::App::new("audit")
+                .about("Audit the packages in the lockfile.")
+                .override_usage(audit_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["signatures"])
+                        .about("`signatures` verifies registry signatures instead of reporting advisories."),
+                )
+                .arg(
+                    Arg::new("level")
+                        .long("level")
+                        .takes_value(true)
+                        .possible_values(&["info", "low", "moderate", "high", "critical"])
+                        .about("Least severe advisories that fail the audit, overrides volt.toml."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("bug-report")
+                .about("Bundle version, config, logs and lockfile into an archive for an issue.")
+                .override_usage(bug_report_usage.as_str())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .about("Write the archive to this path."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("cache")
+                .about("Manage the package archives committed in .volt/cache.")
+                .override_usage(cache_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["vendor", "unvendor"])
+                        .required(true)
+                        .about("`vendor` fills .volt/cache from volt.lock, `unvendor` removes it."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("config")
+                .about("Read and change settings in the volt.toml of the project.")
+                .override_usage(config_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["get", "set"])
+                        .required(true)
+                        .about("`get` prints a setting, `set` changes it."),
+                )
+                .arg(
+                    Arg::new("key")
+                        .required(true)
+                        .about("Dotted key in volt.toml, e.g. `experiments.isolated-linker`."),
+                )
+                .arg(Arg::new("value").about("Value `set` writes.")),
+        )
+        .subcommand(
+            clap::App::new("clean")
+                .about("Remove node_modules, the install state and optionally build output.")
+                .override_usage(clean_usage.as_str())
+                .arg(
+                    Arg::new("build")
+                        .short('b')
+                        .long("build")
+                        .about("Also remove the directories listed under `clean` in volt.toml."),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .about("List what would be removed without removing anything."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("env-diff")
+                .about("Compare two environment snapshots of `volt run --env-snapshot`.")
+                .override_usage(env_diff_usage.as_str())
+                .arg(
+                    Arg::new("before")
+                        .required(true)
+                        .about("Snapshot to compare against."),
+                )
+                .arg(
+                    Arg::new("after")
+                        .required(true)
+                        .about("Snapshot to compare."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("export-lockfile")
+                .about("Export the lockfile in the format of another package manager.")
+                .override_usage(export_lockfile_usage.as_str())
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["npm"])
+                        .default_value("npm")
+                        .about("Format to export, `npm` writes a package-lock.json."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .about("Path to write, defaults to package-lock.json."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("sbom")
+                .about("Generate a software bill of materials of the lockfile.")
+                .override_usage(sbom_usage.as_str())
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["cyclonedx", "spdx"])
+                        .default_value("cyclonedx")
+                        .about("Format to write, CycloneDX 1.4 or SPDX 2.3 JSON."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .about("Path to write, the bill of materials is printed without one."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("graph")
+                .about("Export the dependency graph of the lockfile.")
+                .override_usage(graph_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["export"])
+                        .about("`export` prints the graph or writes it to a database."),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "json"])
+                        .default_value("dot")
+                        .about("Graphviz DOT, or JSON with the dependencies of every version."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .about("Path to write the graph to, it's printed without one."),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<usize>())
+                        .about("Leave out versions more than this many dependencies deep."),
+                )
+                .arg(
+                    Arg::new("collapse-dev")
+                        .long("collapse-dev")
+                        .about("Show dev dependencies without their own dependencies."),
+                )
+                .arg(
+                    Arg::new("sqlite")
+                        .long("sqlite")
+                        .takes_value(true)
+                        .about("Path of the SQLite database to write instead."),
+                )
+                .arg(
+                    Arg::new("no-advisories")
+                        .long("no-advisories")
+                        .about("Don't ask the registry for security advisories."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("info")
+                .about("Show a package and whether it fits the current project.")
+                .override_usage(info_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to fetch the package from instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .about("Package to show, optionally with a version range."),
+                )
+                .arg(
+                    Arg::new("field")
+                        .about("Field to print like `dist.tarball`, or `engines` or `peer` to check."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("ci")
+                .about("Remove node_modules and install exactly what volt.lock describes.")
+                .override_usage(ci_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to download from instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .about("Accept all prompts while installing dependencies."),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .about("Don't run the install scripts of dependencies or of the project."),
+                )
+                .arg(
+                    Arg::new("engine-strict")
+                        .long("engine-strict")
+                        .about("Fail when node doesn't satisfy the `engines` of a package."),
+                )
+                .arg(
+                    Arg::new("force-overwrite")
+                        .long("force-overwrite")
+                        .about("Replace files in node_modules that volt didn't create."),
+                )
+                .arg(
+                    Arg::new("low-memory")
+                        .long("low-memory")
+                        .about("Use less memory at the cost of speed, for small containers."),
+                )
+                .arg(
+                    Arg::new("no-progress")
+                        .long("no-progress")
+                        .about("Log every package on a line instead of drawing progress bars."),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<usize>())
+                        .about("Downloads and extractions run at once."),
+                )
+                .arg(
+                    Arg::new("offline").long("offline").about(
+                        "Install from the store only, failing for packages that aren't in it.",
+                    ),
+                ),
+        )
+        .subcommand(
+            clap::App::new("install")
+                .about("Install the dependencies of the project or workspace.")
+                .override_usage(install_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to install from instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .takes_value(true)
+                        .about("Only install versions published before the given date."),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .about("Accept all prompts while installing dependencies."),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .about("Don't run the install scripts of dependencies or of the project."),
+                )
+                .arg(
+                    Arg::new("engine-strict")
+                        .long("engine-strict")
+                        .about("Fail when node doesn't satisfy the `engines` of a package."),
+                )
+                .arg(Arg::new("frozen-lockfile").long("frozen-lockfile").about(
+                    "Fail instead of updating volt.lock when it doesn't match package.json.",
+                ))
+                .arg(
+                    Arg::new("force-overwrite")
+                        .long("force-overwrite")
+                        .about("Replace files in node_modules that volt didn't create."),
+                )
+                .arg(
+                    Arg::new("low-memory")
+                        .long("low-memory")
+                        .about("Use less memory at the cost of speed, for small containers."),
+                )
+                .arg(
+                    Arg::new("no-progress")
+                        .long("no-progress")
+                        .about("Log every package on a line instead of drawing progress bars."),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<usize>())
+                        .about("Downloads and extractions run at once."),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .about("Resolve from the metadata cache and install from the store only."),
+                )
+                .arg(
+                    Arg::new("prefer-offline")
+                        .long("prefer-offline")
+                        .conflicts_with("offline")
+                        .about("Only ask the registry for packages missing from the cache."),
+                )
+                .arg(
+                    Arg::new("filter")
+                        .short('F')
+                        .long("filter")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .about("Only install the workspace packages matching the filter."),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .about("Keep running and install again when declared dependencies change."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("licenses")
+                .about("Summarize the licenses of the installed packages.")
+                .override_usage(licenses_usage.as_str())
+                .arg(
+                    Arg::new("deny")
+                        .long("deny")
+                        .takes_value(true)
+                        .about("Comma-separated licenses that fail the command."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("lint-scripts")
+                .about("Flag dangerous patterns in lifecycle scripts.")
+                .override_usage(lint_scripts_usage.as_str())
+                .arg(
+                    Arg::new("min-severity")
+                        .long("min-severity")
+                        .takes_value(true)
+                        .possible_values(&["low", "medium", "high", "critical"])
+                        .about("Only report findings at least this severe."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("list")
+                .visible_alias("ls")
+                .about("List the direct dependencies and why they were added.")
+                .override_usage(list_usage.as_str())
+                .arg(
+                    Arg::new("interactive")
+                        .short('i')
+                        .long("interactive")
+                        .about("Explore the whole dependency tree in the terminal."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("node")
+                .about("Install node versions and pin the one scripts run with.")
+                .override_usage(node_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["install", "use", "list"])
+                        .required(true)
+                        .about("`install` or `use` a version, or `list` them."),
+                )
+                .arg(
+                    Arg::new("version")
+                        .about("Version like `18`, `18.12.0`, `lts` or `latest`."),
+                )
+                .arg(
+                    Arg::new("remote")
+                        .long("remote")
+                        .about("With `list`, list the newest release of every major version."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("owner")
+                .about("List, add or remove the owners of a package on the registry.")
+                .override_usage(owner_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry of the package instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["add", "rm", "ls"])
+                        .required(true)
+                        .about("`add` or `rm` an owner, or `ls` them."),
+                )
+                .arg(
+                    Arg::new("arguments")
+                        .multiple_values(true)
+                        .about("User to add or remove, then the package, the current one by default."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("deprecate")
+                .about("Deprecate versions of a package on the registry.")
+                .override_usage(deprecate_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry of the package instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .about("Package with the range or dist-tag of the versions, all without one."),
+                )
+                .arg(
+                    Arg::new("message")
+                        .required(true)
+                        .about("Deprecation message, an empty one takes the deprecation back."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("dist-tag")
+                .about("List, add or remove the dist-tags of a package on the registry.")
+                .override_usage(dist_tag_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry of the package instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["add", "rm", "ls"])
+                        .required(true)
+                        .about("`add` or `rm` a tag, or `ls` them."),
+                )
+                .arg(
+                    Arg::new("arguments")
+                        .multiple_values(true)
+                        .about("The package, `<package>@<version>` for `add`, then the tag."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("why")
+                .about("Show which dependencies pull a package in.")
+                .override_usage(why_usage.as_str())
+                .arg(
+                    Arg::new("package")
+                        .about("Package to explain.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            clap::App::new("query")
+                .about("Answer json queries about package.json on stdin, for editor plugins.")
+                .override_usage(query_usage.as_str()),
+        )
+        .subcommand(
+            clap::App::new("vendor")
+                .about("Copy a dependency into vendor/ and depend on the copy.")
+                .override_usage(vendor_usage.as_str())
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .about("Package to vendor, optionally with a version."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("verify")
+                .about("Verify every installed package against the lockfile, file by file.")
+                .override_usage(verify_usage.as_str()),
+        )
+        .subcommand(
+            clap::App::new("check")
+                .about("Check that node_modules matches the lockfile.")
+                .override_usage(check_usage.as_str())
+                .arg(
+                    Arg::new("deep")
+                        .short('d')
+                        .long("deep")
+                        .about("Also verify every file against the hashes in the lockfile."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("completions")
+                .about("Print a script completing volt's commands and flags in a shell.")
+                .override_usage(completions_usage.as_str())
+                .arg(
+                    Arg::new("shell")
+                        .possible_values(crate::commands::completions::SHELLS)
+                        .required(true)
+                        .about("Shell to complete in."),
+                ),
+        )
         .subcommand(
             clap::App::new("compress")
                 .about("Interactively create and edit your package.json file.")
                 .override_usage(compress_usage.as_str()),
+        )
+        .subcommand(
+            clap::App::new("pack")
+                .about("Pack the current package into a tarball.")
+                .override_usage(pack_usage.as_str())
+                .arg(
+                    Arg::new("notice")
+                        .short('n')
+                        .long("notice")
+                        .about("Add a NOTICE with the licenses of bundled dependencies."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("ping")
+                .about("Check the registries and mirrors can be reached and who their tokens belong to.")
+                .override_usage(ping_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .short('r')
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Only check this registry."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("policy")
+                .about("Check the dependencies against the rules under `[policy]` in volt.toml.")
+                .override_usage(policy_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["check"])
+                        .required(true)
+                        .about("`check` fails when a dependency breaks a rule."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("publish")
+                .about("Publish the current package to the registry.")
+                .override_usage(publish_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to publish to instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .takes_value(true)
+                        .about("Publish under the given dist-tag."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("run")
+                .about("Run a script defined in package.json.")
+                .override_usage(run_usage.as_str())
+                .arg(Arg::new("script").about("Script to run, lists the scripts when omitted."))
+                .arg(
+                    Arg::new("script-args")
+                        .about("Arguments passed on to the script.")
+                        .multiple_values(true)
+                        .last(true),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .about("Show what would run without running anything."),
+                )
+                .arg(
+                    Arg::new("env-snapshot")
+                        .long("env-snapshot")
+                        .takes_value(true)
+                        .requires("script")
+                        .conflicts_with_all(&["recursive", "filter"])
+                        .about("Record the environment the script runs with in a file."),
+                )
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .about("Run in every workspace package, dependencies first."),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<usize>())
+                        .about("Number of packages to run at once with `--recursive`."),
+                )
+                .arg(
+                    Arg::new("filter")
+                        .short('F')
+                        .long("filter")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .about("Run in the workspace packages matching the filter."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("serve-registry")
+                .about("Serve a caching mirror of the npm registry.")
+                .override_usage(serve_registry_usage.as_str())
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .takes_value(true)
+                        .about("Address to listen on, defaults to 127.0.0.1."),
+                )
+                .arg(
+                    Arg::new("port")
+                        .short('p')
+                        .long("port")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<u16>())
+                        .about("Port to listen on, defaults to 4873."),
+                )
+                .arg(
+                    Arg::new("max-age")
+                        .long("max-age")
+                        .takes_value(true)
+                        .validator(|value| value.parse::<u64>())
+                        .about("Seconds a cached document is served without asking the registry."),
+                )
+                .arg(
+                    Arg::new("offline")
+                        .long("offline")
+                        .about("Only serve what is already cached."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("sign")
+                .about("Sign volt.lock and the install summary, or check their signatures.")
+                .override_usage(sign_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["keygen", "verify"])
+                        .about("`keygen` creates a key pair, `verify` checks the signatures."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .about("Where `keygen` writes the secret key."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("store")
+                .about("Manage the package store shared by every project.")
+                .override_usage(store_usage.as_str())
+                .arg(
+                    Arg::new("command")
+                        .possible_values(&["prune"])
+                        .required(true)
+                        .about("`prune` removes shared virtual store entries no project uses."),
+                ),
+        )
+        .subcommand(
+            clap::App::new("unpublish")
+                .about("Remove a package or a single version of it from the registry.")
+                .override_usage(unpublish_usage.as_str())
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .takes_value(true)
+                        .about("Registry to unpublish from instead of the configured one."),
+                )
+                .arg(
+                    Arg::new("package")
+                        .about("Package (and optionally the version) to unpublish.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .about("Confirm that you really want to unpublish."),
+                ),
         );
 
-    let matches = app.get_matches();
+    let matches = app.clone().get_matches();
+
+    if matches.is_present("version") {
+        print_version(matches.is_present("verbose"));
+        return Ok(());
+    }
+
+    // the scripts are generated from the arguments above, which only exist here
+    if let Some(("completions", args)) = matches.subcommand() {
+        return Completions::generate(&mut app, args.value_of("shell").unwrap_or_default());
+    }
+
+    // stdout of `volt query` is read by editor plugins, one json response per line
+    let machine_readable = matches.subcommand_name() == Some("query");
+
+    let command = matches.subcommand_name().unwrap_or_default().to_string();
+
+    if occurrences(&matches, "json") > 0 && !machine_readable && !command.is_empty() {
+        output::enable();
+    }
+
+    logging::init(
+        occurrences(&matches, "quiet") > 0,
+        occurrences(&matches, "verbose"),
+    );
 
-    map_subcommand(matches).await?;
+    let result = map_subcommand(matches).await;
 
-    println!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+    if output::is_json() {
+        output::emit(&command, start.elapsed(), result.as_ref().err());
+        timings::report(&command, start.elapsed());
+
+        return result;
+    }
+
+    result?;
+
+    // `volt` on its own only prints suggestions
+    if !machine_readable && !command.is_empty() {
+        timings::report(&command, start.elapsed());
+
+        println!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+    }
 
     Ok(())
 }